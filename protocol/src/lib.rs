@@ -0,0 +1,157 @@
+//! Wire format shared by the sync server and its clients
+//!
+//! Everything sent over `/api/*` and `/ws` is defined here, so the server and the `sp`
+//! sync client can't drift apart.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Op {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub db_id: Option<i64>,
+    pub id: String,
+    pub op_type: String,
+    pub payload: String,
+    pub timestamp: String,
+    /// Id of the device that made the op
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushOpsRequest {
+    pub workspace_id: String,
+    pub ops: Vec<Op>,
+    /// Human-readable name of the device the ops' `client_id` stands for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushOpsResponse {
+    /// Ops newly stored by this request
+    pub accepted: usize,
+    /// Outcome of each op, in request order
+    pub results: Vec<OpResult>,
+}
+
+/// What happened to one pushed op. The op `id` is its idempotency key: pushing it again
+/// reports `Duplicate` instead of storing it twice.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpResult {
+    pub id: String,
+    pub status: OpStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpStatus {
+    Accepted,
+    Duplicate,
+    Rejected,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetOpsQuery {
+    pub after: Option<i64>,
+    /// Most ops to return; all of them when absent
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub workspace_id: String,
+    pub data: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_op_id: Option<String>,
+    pub updated_at: String,
+    /// Server id of the `last_op_id` op, filled in when the server sends a snapshot: the
+    /// ops after it are the ones the snapshot doesn't cover. Absent when compaction has
+    /// dropped that op, as every op left is then newer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WsMessage {
+    pub msg_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workspace_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ops: Option<Vec<Op>>,
+    /// Per-op outcome, sent back to the pushing client in an `ack`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub results: Option<Vec<OpResult>>,
+    /// Id a client gives itself when subscribing, e.g. its device id
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// Human-readable name for `client_id`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_name: Option<String>,
+    /// The client that joined or left, in `join`/`leave` events
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client: Option<PresenceClient>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A WebSocket connection subscribed to a workspace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PresenceClient {
+    /// Assigned by the server, unique per connection
+    pub connection_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// When it subscribed, RFC 3339
+    pub since: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    /// `op` or `snapshot`
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub op_id: Option<String>,
+    /// Matching excerpt with the terms in `[brackets]`
+    pub snippet: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportResponse {
+    /// Ops newly stored by the import
+    pub accepted: usize,
+    /// Outcome of each imported op, in archive order
+    pub results: Vec<OpResult>,
+    /// Whether the archive's snapshot was restored; an existing snapshot is kept
+    pub snapshot_restored: bool,
+}
+
+/// Exchanges a one-time login code (`sp-server tokens code`) for a token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginRequest {
+    pub code: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginResponse {
+    pub token: String,
+    /// Name the token was created under
+    pub name: String,
+}
+
+/// Who the request's bearer token belongs to (`/api/whoami`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WhoAmIResponse {
+    /// Name of the token
+    pub name: String,
+}
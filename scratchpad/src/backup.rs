@@ -0,0 +1,500 @@
+//! Workspace backups for `sp backup` / `sp restore`
+//!
+//! A backup is a gzipped tarball of the workspace with paths relative to its root.
+//! `.gitignore` files and `.spignore` files (same syntax, in the workspace or a session) are
+//! respected, so build output in worktrees and other bulky files can be left out.
+//!
+//! With a `[backup]` config section, a scheduled backup is taken when one is due: sp
+//! spawns `sp backup --scheduled` in the background on start, which writes into the
+//! backup directory and prunes old archives. Scheduled archives are named
+//! `<prefix>-<timestamp>.tar.gz`, with a prefix per workspace, so the newest file tells
+//! when the workspace was last backed up.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::SystemTime;
+
+use anyhow::{Context as _, Result, anyhow, bail};
+use chrono::{DateTime, Duration, Local, NaiveDateTime, TimeZone};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+
+use crate::models::{BackupConfig, Context};
+use crate::names::slugify;
+use crate::spignore::IGNORE_FILE;
+
+/// What to do when a restored session (or other top-level entry) already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Conflict {
+    /// Keep the existing entry and skip the archived one
+    Skip,
+    /// Replace the existing entry
+    Overwrite,
+    /// Restore under a new name (`<name>-restored`)
+    Rename,
+}
+
+#[derive(Debug, Default)]
+pub struct RestoreSummary {
+    pub restored: Vec<String>,
+    /// Restored under a new name: (archived name, new name)
+    pub renamed: Vec<(String, String)>,
+    pub skipped: Vec<String>,
+}
+
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+/// A scheduled backup running for longer than this is assumed to have died
+const STALE_LOCK: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Default archive name: `scratchpad-backup-<timestamp>.tar.gz`
+pub fn default_file_name() -> String {
+    format!(
+        "scratchpad-backup-{}.tar.gz",
+        Local::now().format(TIMESTAMP_FORMAT)
+    )
+}
+
+/// Parse a backup interval: "hourly", "daily", "weekly", "<N>h" or "<N>d"
+pub fn parse_interval(input: &str) -> Result<Duration> {
+    let input = input.trim().to_lowercase();
+    let parse_n = |n: &str| {
+        n.parse::<i64>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| anyhow!("Invalid backup interval: {input}"))
+    };
+    match input.as_str() {
+        "hourly" => Ok(Duration::hours(1)),
+        "daily" => Ok(Duration::days(1)),
+        "weekly" => Ok(Duration::weeks(1)),
+        _ => {
+            if let Some(n) = input.strip_suffix('h') {
+                Ok(Duration::hours(parse_n(n)?))
+            } else if let Some(n) = input.strip_suffix('d') {
+                Ok(Duration::days(parse_n(n)?))
+            } else {
+                Err(anyhow!("Invalid backup interval: {input}"))
+            }
+        }
+    }
+}
+
+pub fn backup_dir(config: &BackupConfig) -> PathBuf {
+    match &config.path {
+        Some(path) => PathBuf::from(path),
+        None => directories::ProjectDirs::from("", "", "scratchpad")
+            .map(|d| d.data_dir().join("backups"))
+            .unwrap_or_else(|| PathBuf::from("~/.local/share/scratchpad/backups")),
+    }
+}
+
+/// Archive name prefix for a workspace's scheduled backups
+pub fn schedule_prefix(context: &Context) -> String {
+    match context {
+        Context::User => "scratchpad".to_string(),
+        Context::Project(_) => {
+            let name = slugify(&context.display_name()).unwrap_or_else(|| "project".into());
+            format!("scratchpad-{name}")
+        }
+    }
+}
+
+/// Scheduled archives for `prefix` in `dir` with their timestamps, newest first
+pub fn scheduled_backups(dir: &Path, prefix: &str) -> Vec<(PathBuf, DateTime<Local>)> {
+    let mut found: Vec<_> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            let stamp = name
+                .strip_prefix(prefix)?
+                .strip_prefix('-')?
+                .strip_suffix(".tar.gz")?;
+            let time = NaiveDateTime::parse_from_str(stamp, TIMESTAMP_FORMAT).ok()?;
+            let time = Local.from_local_datetime(&time).earliest()?;
+            Some((e.path(), time))
+        })
+        .collect();
+    found.sort_by_key(|(_, time)| std::cmp::Reverse(*time));
+    found
+}
+
+/// Whether a scheduled backup is due for the workspace
+pub fn is_due(config: &BackupConfig, context: &Context, now: DateTime<Local>) -> bool {
+    let Ok(interval) = parse_interval(&config.interval) else {
+        return false;
+    };
+    let dir = backup_dir(config);
+    if lock_held(&lock_path(&dir, &schedule_prefix(context))) {
+        return false;
+    }
+    match scheduled_backups(&dir, &schedule_prefix(context)).first() {
+        Some((_, last)) => now - *last >= interval,
+        None => true,
+    }
+}
+
+/// Start `sp backup --scheduled` in the background if a backup is due. Never fails the
+/// calling command.
+pub fn spawn_if_due(config: Option<&BackupConfig>, context: &Context) {
+    let Some(config) = config else {
+        return;
+    };
+    if !is_due(config, context, Local::now()) {
+        return;
+    }
+    let Ok(exe) = std::env::current_exe() else {
+        return;
+    };
+    let context_flag = match context {
+        Context::User => "--user",
+        Context::Project(_) => "--project",
+    };
+    let _ = Command::new(exe)
+        .args([context_flag, "backup", "--scheduled"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+}
+
+/// Take a scheduled backup and prune old ones, keeping `config.keep`.
+/// Returns None when another scheduled backup is already running.
+pub fn run_scheduled(
+    config: &BackupConfig,
+    context: &Context,
+    workspace: &Path,
+) -> Result<Option<PathBuf>> {
+    let dir = backup_dir(config);
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let prefix = schedule_prefix(context);
+    let lock = lock_path(&dir, &prefix);
+    if lock_held(&lock) {
+        return Ok(None);
+    }
+    let _ = fs::remove_file(&lock);
+    if File::create_new(&lock).is_err() {
+        return Ok(None);
+    }
+
+    let output = dir.join(format!(
+        "{prefix}-{}.tar.gz",
+        Local::now().format(TIMESTAMP_FORMAT)
+    ));
+    let result = create_backup(workspace, &output).map(|_| {
+        for (old, _) in scheduled_backups(&dir, &prefix)
+            .iter()
+            .skip(config.keep.max(1))
+        {
+            let _ = fs::remove_file(old);
+        }
+    });
+    let _ = fs::remove_file(&lock);
+    result.map(|()| Some(output))
+}
+
+fn lock_path(dir: &Path, prefix: &str) -> PathBuf {
+    dir.join(format!(".{prefix}.lock"))
+}
+
+fn lock_held(lock: &Path) -> bool {
+    fs::metadata(lock)
+        .and_then(|m| m.modified())
+        .is_ok_and(|t| {
+            SystemTime::now()
+                .duration_since(t)
+                .is_ok_and(|age| age < STALE_LOCK)
+        })
+}
+
+/// Archive `workspace` into `output`. Returns the number of files written.
+pub fn create_backup(workspace: &Path, output: &Path) -> Result<usize> {
+    let file =
+        File::create(output).with_context(|| format!("Failed to create {}", output.display()))?;
+    let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    let output = output
+        .canonicalize()
+        .unwrap_or_else(|_| output.to_path_buf());
+
+    let walker = ignore::WalkBuilder::new(workspace)
+        .hidden(false)
+        .parents(false)
+        .require_git(false)
+        .add_custom_ignore_filename(IGNORE_FILE)
+        .build();
+    let mut count = 0;
+    for entry in walker {
+        let entry = entry.context("Failed to walk workspace")?;
+        let path = entry.path();
+        if !entry.file_type().is_some_and(|t| t.is_file()) || path == output {
+            continue;
+        }
+        let relative = path.strip_prefix(workspace).unwrap_or(path);
+        archive
+            .append_path_with_name(path, relative)
+            .with_context(|| format!("Failed to add {}", path.display()))?;
+        count += 1;
+    }
+    archive
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .context("Failed to finish archive")?;
+    Ok(count)
+}
+
+/// Extract a backup into `workspace`, resolving clashes per top-level entry
+pub fn restore_backup(
+    archive_path: &Path,
+    workspace: &Path,
+    on_conflict: Conflict,
+) -> Result<RestoreSummary> {
+    let open = || -> Result<tar::Archive<GzDecoder<File>>> {
+        let file = File::open(archive_path)
+            .with_context(|| format!("Failed to open {}", archive_path.display()))?;
+        Ok(tar::Archive::new(GzDecoder::new(file)))
+    };
+
+    // First pass: check every entry, then decide where each top-level entry goes.
+    // Backups hold only files and directories; a link could point extraction outside
+    // the workspace, so an archive with one is refused before anything is written.
+    let mut names = BTreeSet::new();
+    for entry in open()?.entries().context("Failed to read archive")? {
+        let entry = entry.context("Failed to read archive entry")?;
+        let kind = entry.header().entry_type();
+        if !(kind.is_file() || kind.is_dir()) {
+            bail!(
+                "Unsupported entry in archive: {} ({kind:?})",
+                entry.path()?.display()
+            );
+        }
+        if let Some((top, _)) = split_top(&entry.path()?)? {
+            names.insert(top);
+        }
+    }
+    let mut summary = RestoreSummary::default();
+    let mut targets = HashMap::new();
+    for name in names {
+        let existing = workspace.join(&name);
+        if !existing.exists() {
+            summary.restored.push(name.clone());
+            targets.insert(name.clone(), name);
+            continue;
+        }
+        match on_conflict {
+            Conflict::Skip => summary.skipped.push(name),
+            Conflict::Overwrite => {
+                if existing.is_dir() {
+                    fs::remove_dir_all(&existing)
+                } else {
+                    fs::remove_file(&existing)
+                }
+                .with_context(|| format!("Failed to replace {}", existing.display()))?;
+                summary.restored.push(name.clone());
+                targets.insert(name.clone(), name);
+            }
+            Conflict::Rename => {
+                let new_name = free_name(workspace, &name);
+                summary.renamed.push((name.clone(), new_name.clone()));
+                targets.insert(name, new_name);
+            }
+        }
+    }
+
+    // Second pass: extract
+    for entry in open()?.entries().context("Failed to read archive")? {
+        let mut entry = entry.context("Failed to read archive entry")?;
+        let path = entry.path()?.into_owned();
+        let Some((top, rest)) = split_top(&path)? else {
+            continue;
+        };
+        let Some(target) = targets.get(&top) else {
+            continue;
+        };
+        let mut dest = workspace.join(target);
+        if !rest.as_os_str().is_empty() {
+            dest.push(rest);
+        }
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        entry
+            .unpack(&dest)
+            .with_context(|| format!("Failed to extract {}", path.display()))?;
+    }
+    Ok(summary)
+}
+
+/// Split an archive path into its top-level name and the rest, rejecting paths that
+/// could escape the workspace
+fn split_top(path: &Path) -> Result<Option<(String, PathBuf)>> {
+    let mut components = path.components().filter(|c| *c != Component::CurDir);
+    let Some(Component::Normal(top)) = components.next() else {
+        return Ok(None);
+    };
+    let rest: PathBuf = components
+        .map(|c| match c {
+            Component::Normal(part) => Ok(part),
+            _ => Err(anyhow::anyhow!(
+                "Unsafe path in archive: {}",
+                path.display()
+            )),
+        })
+        .collect::<Result<_>>()?;
+    Ok(Some((top.to_string_lossy().to_string(), rest)))
+}
+
+/// `<name>-restored`, or `<name>-restored-N` if that exists too
+fn free_name(workspace: &Path, name: &str) -> String {
+    let base = format!("{name}-restored");
+    let mut candidate = base.clone();
+    let mut i = 2;
+    while workspace.join(&candidate).exists() {
+        candidate = format!("{base}-{i}");
+        i += 1;
+    }
+    candidate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_intervals() {
+        assert_eq!(parse_interval("daily").unwrap(), Duration::days(1));
+        assert_eq!(parse_interval("12h").unwrap(), Duration::hours(12));
+        assert_eq!(parse_interval("3d").unwrap(), Duration::days(3));
+        assert!(parse_interval("0d").is_err());
+        assert!(parse_interval("often").is_err());
+    }
+
+    #[test]
+    fn scheduled_backups_rotate() {
+        let dir = tempfile::tempdir().unwrap();
+        let ws = dir.path().join("ws");
+        fs::create_dir_all(ws.join("alpha")).unwrap();
+        fs::write(ws.join("alpha/notes.md"), "alpha").unwrap();
+        let backups = dir.path().join("backups");
+        fs::create_dir_all(&backups).unwrap();
+        for stamp in ["20240101-000000", "20240102-000000", "20240103-000000"] {
+            fs::write(backups.join(format!("scratchpad-{stamp}.tar.gz")), "").unwrap();
+        }
+        // Another workspace's archives are left alone
+        fs::write(backups.join("scratchpad-proj-20240101-000000.tar.gz"), "").unwrap();
+
+        let config = BackupConfig {
+            interval: "daily".into(),
+            keep: 2,
+            path: Some(backups.to_string_lossy().to_string()),
+        };
+        assert!(is_due(&config, &Context::User, Local::now()));
+        let output = run_scheduled(&config, &Context::User, &ws)
+            .unwrap()
+            .unwrap();
+
+        let kept = scheduled_backups(&backups, "scratchpad");
+        assert_eq!(kept.len(), 2);
+        assert_eq!(kept[0].0, output);
+        assert!(
+            backups
+                .join("scratchpad-proj-20240101-000000.tar.gz")
+                .exists()
+        );
+        assert!(!is_due(&config, &Context::User, Local::now()));
+    }
+
+    #[test]
+    fn round_trips_with_ignores_and_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let ws = dir.path().join("ws");
+        fs::create_dir_all(ws.join("alpha/target")).unwrap();
+        fs::write(ws.join("alpha/notes.md"), "alpha").unwrap();
+        fs::write(ws.join("alpha/.session.toml"), "").unwrap();
+        fs::write(ws.join("alpha/target/big.bin"), "x").unwrap();
+        fs::write(ws.join("alpha/debug.log"), "x").unwrap();
+        fs::write(ws.join("alpha/.gitignore"), "target/\n").unwrap();
+        fs::write(ws.join(IGNORE_FILE), "*.log\n").unwrap();
+
+        let archive = dir.path().join("backup.tar.gz");
+        assert_eq!(create_backup(&ws, &archive).unwrap(), 4);
+
+        // Fresh workspace: everything is restored
+        let fresh = dir.path().join("fresh");
+        fs::create_dir_all(&fresh).unwrap();
+        let summary = restore_backup(&archive, &fresh, Conflict::Skip).unwrap();
+        assert_eq!(summary.restored, vec![".spignore", "alpha"]);
+        assert_eq!(
+            fs::read_to_string(fresh.join("alpha/notes.md")).unwrap(),
+            "alpha"
+        );
+        assert!(fresh.join("alpha/.session.toml").exists());
+        assert!(!fresh.join("alpha/target").exists());
+        assert!(!fresh.join("alpha/debug.log").exists());
+
+        fs::write(ws.join("alpha/notes.md"), "changed").unwrap();
+        let summary = restore_backup(&archive, &ws, Conflict::Skip).unwrap();
+        assert_eq!(summary.skipped.len(), 2);
+        assert_eq!(
+            fs::read_to_string(ws.join("alpha/notes.md")).unwrap(),
+            "changed"
+        );
+
+        let summary = restore_backup(&archive, &ws, Conflict::Rename).unwrap();
+        assert!(
+            summary
+                .renamed
+                .contains(&("alpha".into(), "alpha-restored".into()))
+        );
+        assert_eq!(
+            fs::read_to_string(ws.join("alpha-restored/notes.md")).unwrap(),
+            "alpha"
+        );
+
+        restore_backup(&archive, &ws, Conflict::Overwrite).unwrap();
+        assert_eq!(
+            fs::read_to_string(ws.join("alpha/notes.md")).unwrap(),
+            "alpha"
+        );
+        assert!(!ws.join("alpha/target").exists());
+    }
+
+    #[test]
+    fn refuses_archives_with_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = dir.path().join("outside");
+        fs::create_dir_all(&outside).unwrap();
+        let ws = dir.path().join("ws");
+        fs::create_dir_all(&ws).unwrap();
+
+        for kind in [tar::EntryType::Symlink, tar::EntryType::Link] {
+            // `sess/x` links outside, then `sess/x/authorized_keys` writes through it
+            let archive = dir.path().join("evil.tar.gz");
+            let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+                File::create(&archive).unwrap(),
+                flate2::Compression::default(),
+            ));
+            let mut link = tar::Header::new_gnu();
+            link.set_entry_type(kind);
+            link.set_size(0);
+            builder.append_link(&mut link, "sess/x", &outside).unwrap();
+            let mut file = tar::Header::new_gnu();
+            file.set_size(3);
+            file.set_mode(0o644);
+            builder
+                .append_data(&mut file, "sess/x/authorized_keys", &b"key"[..])
+                .unwrap();
+            builder.into_inner().unwrap().finish().unwrap();
+
+            let error = restore_backup(&archive, &ws, Conflict::Overwrite).unwrap_err();
+            assert!(error.to_string().contains("sess/x"), "{error}");
+            assert!(!outside.join("authorized_keys").exists());
+            assert!(!ws.join("sess").exists());
+        }
+    }
+}
@@ -0,0 +1,237 @@
+//! Pattern-based renames and retagging across sessions (`sp bulk`)
+//!
+//! Sessions are matched by slug with a glob where `*` matches any run of characters and
+//! `?` a single one. In a rename target, each wildcard is filled with what the matching
+//! wildcard captured, in order: `tmp-*` → `experiment-*` turns `tmp-cache` into
+//! `experiment-cache`.
+
+use std::collections::HashSet;
+
+use anyhow::{Context as _, Result, bail};
+use regex::Regex;
+
+use crate::names::slugify;
+use crate::naming;
+use crate::storage::Storage;
+use crate::tags;
+
+/// A slug glob compiled to an anchored regex with one capture group per wildcard
+pub struct Pattern {
+    regex: Regex,
+    wildcards: usize,
+}
+
+impl Pattern {
+    pub fn new(glob: &str) -> Result<Self> {
+        let mut regex = String::from("^");
+        let mut wildcards = 0;
+        for c in glob.chars() {
+            match c {
+                '*' => {
+                    regex.push_str("(.*)");
+                    wildcards += 1;
+                }
+                '?' => {
+                    regex.push_str("(.)");
+                    wildcards += 1;
+                }
+                c => regex.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        regex.push('$');
+        let regex = Regex::new(&regex).with_context(|| format!("Invalid pattern: {glob}"))?;
+        Ok(Self { regex, wildcards })
+    }
+
+    pub fn matches(&self, slug: &str) -> bool {
+        self.regex.is_match(slug)
+    }
+
+    /// `target` with its wildcards replaced by what this pattern captured in `slug`
+    fn substitute(&self, slug: &str, target: &str) -> Option<String> {
+        let captures = self.regex.captures(slug)?;
+        let mut groups = captures.iter().skip(1).flatten();
+        let mut out = String::new();
+        for c in target.chars() {
+            match c {
+                '*' | '?' => out.push_str(groups.next()?.as_str()),
+                c => out.push(c),
+            }
+        }
+        Some(out)
+    }
+}
+
+pub struct Rename {
+    pub from: String,
+    pub to: String,
+}
+
+/// Renames for every session matching `pattern`. Fails if a new name is invalid or
+/// collides with another session.
+pub fn plan_renames(storage: &Storage, pattern: &str, replace: &str) -> Result<Vec<Rename>> {
+    let pattern = Pattern::new(pattern)?;
+    let target_wildcards = replace.chars().filter(|c| matches!(c, '*' | '?')).count();
+    if target_wildcards > pattern.wildcards {
+        bail!("--replace has more wildcards than --match");
+    }
+
+    let existing: HashSet<String> = storage.existing_slugs()?.into_iter().collect();
+    let mut targets = HashSet::new();
+    let mut renames = Vec::new();
+    let mut slugs: Vec<&String> = existing.iter().filter(|s| pattern.matches(s)).collect();
+    slugs.sort();
+    for slug in slugs {
+        let Some(target) = pattern.substitute(slug, replace) else {
+            continue;
+        };
+        if target == *slug {
+            continue;
+        }
+        if slugify(&target).as_deref() != Some(target.as_str()) {
+            bail!("Invalid session name '{target}' (renaming '{slug}')");
+        }
+        naming::check(storage.config(), &target)?;
+        if existing.contains(&target) || !targets.insert(target.clone()) {
+            bail!("Renaming '{slug}' to '{target}' would collide with another session");
+        }
+        renames.push(Rename {
+            from: slug.clone(),
+            to: target,
+        });
+    }
+    Ok(renames)
+}
+
+pub fn apply_renames(storage: &Storage, renames: &[Rename]) -> Result<()> {
+    for rename in renames {
+        storage.rename_session(&rename.from, &rename.to)?;
+    }
+    Ok(())
+}
+
+pub struct Retag {
+    pub slug: String,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+/// Tag changes for every session matching `pattern`; sessions left unchanged are skipped
+pub fn plan_tags(
+    storage: &Storage,
+    pattern: &str,
+    add: &[String],
+    remove: &[String],
+) -> Result<Vec<Retag>> {
+    let pattern = Pattern::new(pattern)?;
+    let removed: Vec<String> = remove.iter().filter_map(|t| tags::normalize(t)).collect();
+    let mut retags = Vec::new();
+    let mut slugs = storage.existing_slugs()?;
+    slugs.sort();
+    for slug in slugs.into_iter().filter(|s| pattern.matches(s)) {
+        let before = storage.load_meta(&slug)?.tags;
+        let mut after = before.clone();
+        tags::add(&mut after, add);
+        after.retain(|t| !removed.contains(t));
+        if after != before {
+            retags.push(Retag {
+                slug,
+                before,
+                after,
+            });
+        }
+    }
+    Ok(retags)
+}
+
+pub fn apply_tags(storage: &Storage, retags: &[Retag]) -> Result<()> {
+    for retag in retags {
+        let mut meta = storage.load_meta(&retag.slug)?;
+        meta.tags = retag.after.clone();
+        storage.save_meta(&retag.slug, &meta)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Config, Context, Session};
+
+    fn storage_with(slugs: &[&str]) -> (tempfile::TempDir, Storage) {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            workspace_path: dir.path().to_string_lossy().to_string(),
+            ..Config::default()
+        };
+        let storage = Storage::new(config, Context::User);
+        for slug in slugs {
+            storage.create_session(&Session::new(*slug), None).unwrap();
+        }
+        (dir, storage)
+    }
+
+    #[test]
+    fn matches_globs_and_substitutes_captures() {
+        let pattern = Pattern::new("tmp-*-v?").unwrap();
+        assert!(pattern.matches("tmp-cache-v2"));
+        assert!(!pattern.matches("tmp-cache-v10"));
+        assert!(!pattern.matches("old-tmp-cache-v2"));
+        assert_eq!(
+            pattern.substitute("tmp-cache-v2", "exp-*-*").as_deref(),
+            Some("exp-cache-2")
+        );
+        assert!(Pattern::new("a.b").unwrap().matches("a.b"));
+        assert!(!Pattern::new("a.b").unwrap().matches("axb"));
+    }
+
+    #[test]
+    fn renames_matching_sessions() {
+        let (_dir, storage) = storage_with(&["tmp-cache", "tmp-queue", "keep", "experiment-x"]);
+        let renames = plan_renames(&storage, "tmp-*", "experiment-*").unwrap();
+        let pairs: Vec<_> = renames.iter().map(|r| (&*r.from, &*r.to)).collect();
+        assert_eq!(
+            pairs,
+            [
+                ("tmp-cache", "experiment-cache"),
+                ("tmp-queue", "experiment-queue")
+            ]
+        );
+        apply_renames(&storage, &renames).unwrap();
+        assert!(storage.session_dir("experiment-queue").is_dir());
+
+        assert!(plan_renames(&storage, "experiment-*", "x").is_err());
+        assert!(plan_renames(&storage, "keep", "Not Valid").is_err());
+        assert!(plan_renames(&storage, "k*", "*-*").is_err());
+
+        let mut config = storage.config().clone();
+        config.naming = Some(crate::models::NamingConfig {
+            pattern: Some("^exp-".to_string()),
+            ..Default::default()
+        });
+        let storage = Storage::new(config, Context::User);
+        assert!(plan_renames(&storage, "keep", "kept").is_err());
+        assert!(plan_renames(&storage, "keep", "exp-kept").is_ok());
+    }
+
+    #[test]
+    fn retags_matching_sessions() {
+        let (_dir, storage) = storage_with(&["auth-login", "oauth-flow", "cache"]);
+        let mut meta = storage.load_meta("oauth-flow").unwrap();
+        meta.tags = vec!["security".to_string(), "wip".to_string()];
+        storage.save_meta("oauth-flow", &meta).unwrap();
+
+        let retags = plan_tags(
+            &storage,
+            "*auth*",
+            &["Security".to_string()],
+            &["wip".to_string()],
+        )
+        .unwrap();
+        assert_eq!(retags.len(), 2);
+        apply_tags(&storage, &retags).unwrap();
+        assert_eq!(storage.load_meta("auth-login").unwrap().tags, ["security"]);
+        assert_eq!(storage.load_meta("oauth-flow").unwrap().tags, ["security"]);
+        assert!(storage.load_meta("cache").unwrap().tags.is_empty());
+    }
+}
@@ -0,0 +1,172 @@
+//! Session deadlines and iCalendar export
+//!
+//! Due dates live in session metadata. `sp export --ics` turns each one into an all-day
+//! VEVENT with a morning reminder, so deadlines show up in any calendar app.
+
+use anyhow::{Result, bail};
+use chrono::{Duration, NaiveDate, Utc};
+
+use crate::storage::Storage;
+
+/// Parse a due date: `YYYY-MM-DD`, `today`, `tomorrow`, or `+N` (days from today)
+pub fn parse_due(input: &str, today: NaiveDate) -> Result<NaiveDate> {
+    let input = input.trim();
+    match input {
+        "today" => return Ok(today),
+        "tomorrow" => return Ok(today + Duration::days(1)),
+        _ => {}
+    }
+    if let Some(days) = input.strip_prefix('+') {
+        let days = days.strip_suffix('d').unwrap_or(days);
+        if let Ok(days) = days.parse::<i64>() {
+            return Ok(today + Duration::days(days));
+        }
+    }
+    match NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        Ok(date) => Ok(date),
+        Err(_) => bail!("Invalid date '{input}' (use YYYY-MM-DD, today, tomorrow or +N)"),
+    }
+}
+
+/// Parse a span of time: `<N>m`, `<N>h`, `<N>d` or `<N>w`, with N > 0
+pub fn parse_span(input: &str) -> Option<Duration> {
+    let input = input.trim().to_lowercase();
+    let unit = input.chars().last()?;
+    let n: i64 = input[..input.len() - unit.len_utf8()]
+        .parse()
+        .ok()
+        .filter(|n| *n > 0)?;
+    match unit {
+        'm' => Some(Duration::minutes(n)),
+        'h' => Some(Duration::hours(n)),
+        'd' => Some(Duration::days(n)),
+        'w' => Some(Duration::weeks(n)),
+        _ => None,
+    }
+}
+
+/// Build a calendar with one all-day event per session that has a due date
+pub fn export_ics(storage: &Storage) -> Result<String> {
+    let mut out = String::new();
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, "PRODID:-//scratchpad//sp//EN");
+    push_line(&mut out, "CALSCALE:GREGORIAN");
+
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ");
+    for session in storage.list_sessions()? {
+        let Some(due) = storage.load_meta(&session.slug)?.due else {
+            continue;
+        };
+        let dir = storage.session_dir(&session.slug);
+        push_line(&mut out, "BEGIN:VEVENT");
+        push_line(&mut out, &format!("UID:{}@scratchpad", session.slug));
+        push_line(&mut out, &format!("DTSTAMP:{stamp}"));
+        push_line(
+            &mut out,
+            &format!("DTSTART;VALUE=DATE:{}", due.format("%Y%m%d")),
+        );
+        push_line(
+            &mut out,
+            &format!(
+                "DTEND;VALUE=DATE:{}",
+                (due + Duration::days(1)).format("%Y%m%d")
+            ),
+        );
+        push_line(
+            &mut out,
+            &format!("SUMMARY:{}", escape_text(&session.display_title())),
+        );
+        push_line(
+            &mut out,
+            &format!("DESCRIPTION:{}", escape_text(&dir.to_string_lossy())),
+        );
+        push_line(&mut out, "BEGIN:VALARM");
+        push_line(&mut out, "ACTION:DISPLAY");
+        push_line(
+            &mut out,
+            &format!("DESCRIPTION:{}", escape_text(&session.display_title())),
+        );
+        // All-day events start at midnight; remind at 09:00 on the due day
+        push_line(&mut out, "TRIGGER:PT9H");
+        push_line(&mut out, "END:VALARM");
+        push_line(&mut out, "END:VEVENT");
+    }
+
+    push_line(&mut out, "END:VCALENDAR");
+    Ok(out)
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Append a content line, folded at 75 octets and terminated with CRLF (RFC 5545 §3.1)
+fn push_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Config, Context, Session};
+
+    #[test]
+    fn parse_due_accepts_dates_and_offsets() {
+        let today = NaiveDate::from_ymd_opt(2025, 1, 30).unwrap();
+        let feb = |d| NaiveDate::from_ymd_opt(2025, 2, d).unwrap();
+        assert_eq!(parse_due("2025-02-03", today).unwrap(), feb(3));
+        assert_eq!(
+            parse_due("tomorrow", today).unwrap(),
+            today.succ_opt().unwrap()
+        );
+        assert_eq!(parse_due("+3", today).unwrap(), feb(2));
+        assert_eq!(parse_due("+3d", today).unwrap(), feb(2));
+        assert!(parse_due("next week", today).is_err());
+    }
+
+    #[test]
+    fn exports_only_sessions_with_due_dates() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            workspace_path: dir.path().to_string_lossy().to_string(),
+            ..Config::default()
+        };
+        let storage = Storage::new(config, Context::User);
+        storage
+            .create_session(&Session::new("release"), None)
+            .unwrap();
+        storage.create_session(&Session::new("idle"), None).unwrap();
+        let mut meta = storage.load_meta("release").unwrap();
+        meta.due = NaiveDate::from_ymd_opt(2025, 3, 14);
+        storage.save_meta("release", &meta).unwrap();
+
+        let ics = export_ics(&storage).unwrap();
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.contains("UID:release@scratchpad\r\n"));
+        assert!(ics.contains("DTSTART;VALUE=DATE:20250314\r\n"));
+        assert!(ics.contains("DTEND;VALUE=DATE:20250315\r\n"));
+        assert!(!ics.contains("idle"));
+    }
+
+    #[test]
+    fn long_lines_are_folded() {
+        let mut out = String::new();
+        push_line(&mut out, &"x".repeat(100));
+        let lines: Vec<_> = out.split("\r\n").collect();
+        assert_eq!(lines[0].len(), 75);
+        assert_eq!(lines[1], format!(" {}", "x".repeat(25)));
+    }
+}
@@ -0,0 +1,114 @@
+//! Live capture of a stream into a session file
+//!
+//! Lines are read on a background thread and written out in batches, at least every
+//! `flush_interval`, so a long-running producer shows up in the session while it runs.
+
+use std::io::{BufRead, Write};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context as _, Result};
+use chrono::Local;
+
+pub struct CaptureOptions {
+    /// Prefix each line with the local time it was received
+    pub timestamps: bool,
+    pub flush_interval: Duration,
+    /// Echo input to stdout so the capture can sit in the middle of a pipe
+    pub tee: bool,
+}
+
+/// Copy `input` to `out` until EOF, returning the number of lines captured
+pub fn capture<R, W>(input: R, out: &mut W, opts: &CaptureOptions) -> Result<usize>
+where
+    R: BufRead + Send + 'static,
+    W: Write,
+{
+    let (tx, rx) = mpsc::channel::<String>();
+    let reader = thread::spawn(move || -> std::io::Result<()> {
+        let mut input = input;
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            if input.read_until(b'\n', &mut buf)? == 0 {
+                return Ok(());
+            }
+            let line = String::from_utf8_lossy(&buf).into_owned();
+            if tx.send(line).is_err() {
+                return Ok(());
+            }
+        }
+    });
+
+    let mut pending = String::new();
+    let mut lines = 0;
+    let mut last_flush = Instant::now();
+    loop {
+        let wait = opts.flush_interval.saturating_sub(last_flush.elapsed());
+        let finished = match rx.recv_timeout(wait) {
+            Ok(line) => {
+                if opts.tee {
+                    print!("{line}");
+                }
+                if opts.timestamps {
+                    pending.push_str(&format!("[{}] ", Local::now().format("%H:%M:%S")));
+                }
+                pending.push_str(&line);
+                if !line.ends_with('\n') {
+                    pending.push('\n');
+                }
+                lines += 1;
+                false
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => true,
+        };
+
+        if finished || last_flush.elapsed() >= opts.flush_interval {
+            if !pending.is_empty() {
+                out.write_all(pending.as_bytes())
+                    .context("Failed to write capture")?;
+                out.flush()?;
+                pending.clear();
+            }
+            if opts.tee {
+                std::io::stdout().flush()?;
+            }
+            last_flush = Instant::now();
+        }
+        if finished {
+            break;
+        }
+    }
+
+    reader
+        .join()
+        .map_err(|_| anyhow::anyhow!("Capture reader panicked"))?
+        .context("Failed to read input")?;
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn captures_all_lines_with_timestamps() {
+        let input = Cursor::new(b"building\ndone".to_vec());
+        let mut out = Vec::new();
+        let opts = CaptureOptions {
+            timestamps: true,
+            flush_interval: Duration::from_millis(10),
+            tee: false,
+        };
+        assert_eq!(capture(input, &mut out, &opts).unwrap(), 2);
+
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with('[') && lines[0].ends_with("] building"));
+        assert!(lines[1].ends_with("] done"));
+    }
+}
@@ -1,6 +1,10 @@
-use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 
-use crate::models::Agent;
+use clap::{Parser, Subcommand, ValueEnum};
+
+use crate::backup::Conflict;
+use crate::models::{Agent, Relation, Status};
+use crate::sync::throttle::parse_rate;
 
 #[derive(Parser)]
 #[command(name = "sp")]
@@ -11,9 +15,14 @@ pub struct Cli {
     #[arg(short = 'u', long)]
     pub user: bool,
 
-    /// Force project context (.scratchpad/)
-    #[arg(short = 'p', long)]
-    pub project: bool,
+    /// Force project context (.scratchpad/): the nearest one, or with `--project=<dir>`
+    /// the one in that directory when several are nested (a monorepo's and a package's)
+    #[arg(short = 'p', long, value_name = "DIR", require_equals = true)]
+    pub project: Option<Option<PathBuf>>,
+
+    /// Disable everything that changes the workspace
+    #[arg(long)]
+    pub read_only: bool,
 
     #[command(subcommand)]
     pub command: Option<Command>,
@@ -26,6 +35,16 @@ pub enum Command {
     New {
         /// Session name (slug). If not provided, one will be generated.
         name: Option<String>,
+        /// Store the session's files encrypted with the key from `[encryption]`
+        #[arg(long)]
+        encrypted: bool,
+        /// Create the session for an issue (JIRA-123, gh#456): named after it, with its
+        /// title and description as the starting notes
+        #[arg(long, value_name = "ISSUE")]
+        issue: Option<String>,
+        /// Skip the `[naming]` rules
+        #[arg(long)]
+        no_verify: bool,
     },
 
     /// Create a quick session with initial note
@@ -38,8 +57,26 @@ pub enum Command {
     /// Open a session in TUI
     #[command(alias = "o")]
     Open {
-        /// Session name (can be prefix)
+        /// Session name (can be prefix), optionally followed by a file in it
+        /// to preview (`quantum-reactor/plan.md`)
         name: Option<String>,
+
+        /// Open the session for the current git branch, creating it if needed
+        /// (requires `branch_sessions` in the config)
+        #[arg(long, conflicts_with = "name")]
+        branch: bool,
+    },
+
+    /// Open an `sp://` link in the TUI, or register `sp` as the system handler for them
+    Url {
+        /// Link to open: sp://<session>[/<file>], optionally with ?project=<dir> or
+        /// ?workspace=<name> when the session isn't in the user workspace
+        #[arg(required_unless_present = "register")]
+        url: Option<String>,
+        /// Register `sp url` as the handler for sp:// links (desktop entry on Linux,
+        /// registry on Windows)
+        #[arg(long, conflicts_with = "url")]
+        register: bool,
     },
 
     /// Run an agent in the session context
@@ -62,11 +99,32 @@ pub enum Command {
     Edit {
         /// Session name (can be prefix)
         name: Option<String>,
+
+        /// Edit the workspace dashboard (`_index.md`) instead of a session
+        #[arg(long, conflicts_with = "name")]
+        index: bool,
+
+        /// Open the project the session is about alongside the notes (its newest
+        /// worktree, else the project's repository), split where the editor can
+        #[arg(long, conflicts_with = "index")]
+        project: bool,
     },
 
     /// List all sessions
     #[command(alias = "ls")]
-    List,
+    List {
+        /// Print sessions as JSON, including metadata such as links
+        #[arg(long)]
+        json: bool,
+
+        /// Only sessions with this status
+        #[arg(long)]
+        status: Option<Status>,
+
+        /// Only sessions with due reminders, showing the reminders
+        #[arg(long)]
+        due: bool,
+    },
 
     /// Initialize a project-local scratchpad
     Init {
@@ -85,6 +143,23 @@ pub enum Command {
         current: Option<String>,
         /// New session name
         new_name: String,
+        /// Skip the `[naming]` rules
+        #[arg(long)]
+        no_verify: bool,
+    },
+
+    /// Record how two sessions relate, e.g. a forked experiment and its origin
+    Link {
+        /// Session name (or prefix)
+        name: String,
+        /// The other session (or prefix)
+        other: String,
+        /// What OTHER is to NAME
+        #[arg(long, value_enum, default_value_t = Relation::Related)]
+        rel: Relation,
+        /// Remove the link between the two sessions instead
+        #[arg(long)]
+        remove: bool,
     },
 
     /// Print session directory path
@@ -107,6 +182,10 @@ pub enum Command {
         /// Output flat list (no tree chars, for piping)
         #[arg(long)]
         flat: bool,
+        /// Print every file and directory, however deep, as JSON with its size, mtime and
+        /// whether it's the entry point
+        #[arg(long, conflicts_with = "flat")]
+        json: bool,
     },
 
     /// Read session entry point or a specific file
@@ -116,6 +195,68 @@ pub enum Command {
         name: Option<String>,
         /// Specific file to read (relative to session dir)
         file: Option<String>,
+        /// Render markdown for the terminal, with glow if installed
+        #[arg(long)]
+        render: bool,
+    },
+
+    /// Search file contents across sessions
+    #[command(alias = "search")]
+    Grep {
+        /// Text to search for (case-insensitive), or a description with --semantic
+        query: String,
+        /// Limit the search to one session (can be prefix)
+        #[arg(short, long)]
+        session: Option<String>,
+        /// Stop after this many matches (sessions with --semantic) [default: 200, 10]
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+        /// Rank sessions by meaning using the embeddings index (`sp index build`)
+        #[arg(long)]
+        semantic: bool,
+    },
+
+    /// Manage the embeddings index used by `sp search --semantic`
+    Index {
+        #[command(subcommand)]
+        action: IndexAction,
+    },
+
+    /// Regex find-and-replace across a session's files (originals are backed up)
+    Replace {
+        /// Session name (can be prefix)
+        #[arg(conflicts_with = "all_sessions")]
+        name: Option<String>,
+        /// Regular expression to search for, matched line by line
+        #[arg(long)]
+        find: String,
+        /// Replacement text; `$1` / `${name}` refer to capture groups
+        #[arg(long)]
+        replace: String,
+        /// Replace in every session of the workspace
+        #[arg(long)]
+        all_sessions: bool,
+        /// Apply without confirmation
+        #[arg(long)]
+        yes: bool,
+    },
+
+    /// Rename or retag every session whose name matches a glob, after showing the changes
+    Bulk {
+        #[command(subcommand)]
+        action: BulkAction,
+    },
+
+    /// List open tasks (`- [ ]`) and TODO: markers across sessions
+    Todos {
+        /// Limit to one session (can be prefix)
+        name: Option<String>,
+    },
+
+    /// Append the clipboard to a session (or a new quick session)
+    Clip {
+        /// Session name (can be prefix). If omitted, a quick session is created.
+        name: Option<String>,
     },
 
     /// Write stdin to session entry point or a specific file
@@ -126,6 +267,23 @@ pub enum Command {
         file: Option<String>,
     },
 
+    /// Stream stdin into a session file as it arrives
+    Capture {
+        /// Session name (can be prefix)
+        name: String,
+        /// File to append to (relative to session dir, default: capture.log)
+        file: Option<String>,
+        /// Don't prefix lines with the time they were received
+        #[arg(long)]
+        no_timestamps: bool,
+        /// Also echo input to stdout
+        #[arg(long)]
+        tee: bool,
+        /// Seconds between flushes to disk (encrypted sessions are written once input ends)
+        #[arg(long, default_value_t = 2)]
+        interval: u64,
+    },
+
     /// Delete a session
     #[command(alias = "rm")]
     Delete {
@@ -136,9 +294,91 @@ pub enum Command {
         yes: bool,
     },
 
+    /// Walk through stale sessions, keeping, archiving, deleting or snoozing each
+    Review {
+        /// Sessions untouched for more than this many days are stale
+        #[arg(long, default_value_t = 30)]
+        days: u32,
+        /// Days a snoozed session stays out of review
+        #[arg(long, default_value_t = 14)]
+        snooze: u32,
+    },
+
+    /// Show, add or remove a session's tags, or let the agent pick them with --auto
+    Tag {
+        /// Session name (can be prefix)
+        #[arg(required_unless_present = "all")]
+        name: Option<String>,
+        /// Tags to add (or remove with --remove)
+        tags: Vec<String>,
+        /// Remove the given tags instead of adding them
+        #[arg(long, requires = "tags")]
+        remove: bool,
+        /// Ask the default agent for tags from `tag_vocabulary`
+        #[arg(long, conflicts_with_all = ["tags", "remove"])]
+        auto: bool,
+        /// Auto-tag every session
+        #[arg(long, requires = "auto", conflicts_with = "name")]
+        all: bool,
+        /// Apply suggestions without asking
+        #[arg(short, long, requires = "auto")]
+        yes: bool,
+    },
+
+    /// Save an agent conversation transcript as transcript.md in its session
+    Ingest {
+        /// Agent whose transcripts to read
+        source: IngestSource,
+        /// Session name (can be prefix). Default: $SP_SESSION, or the session the
+        /// conversation was started in
+        name: Option<String>,
+        /// Conversation id (default: the latest conversation started in the session)
+        #[arg(long)]
+        session_id: Option<String>,
+    },
+
+    /// Have the default agent summarize a session into SUMMARY.md
+    Summarize {
+        /// Session name (can be prefix)
+        name: Option<String>,
+        /// Also send this file (relative to the session; repeatable)
+        #[arg(long = "file", value_name = "PATH")]
+        files: Vec<String>,
+        /// Only store the one-line description in the metadata, without writing SUMMARY.md
+        #[arg(long)]
+        meta_only: bool,
+    },
+
+    /// Summarize recent activity as a markdown report
+    Digest {
+        /// Start of the window: 12h, 3d, 1w or a YYYY-MM-DD date
+        #[arg(long, default_value = "1w")]
+        since: String,
+        /// Have the default agent rewrite the report as a summary
+        #[arg(long)]
+        llm: bool,
+    },
+
+    /// Find sessions with identical or near-identical notes and merge or trash them
+    Dedupe {
+        /// Minimum share of common lines (0.0-1.0) for notes to count as duplicates
+        #[arg(long, default_value_t = 0.9)]
+        threshold: f64,
+    },
+
     /// Show active context and workspace path
     Context,
 
+    /// List sessions whose names break the `[naming]` rules
+    Validate,
+
+    /// Check the workspace for problems, such as files other users can read
+    Doctor {
+        /// Remove group and other access from workspace and config files
+        #[arg(long)]
+        fix_perms: bool,
+    },
+
     /// Manage configuration
     #[command(alias = "cfg")]
     Config {
@@ -146,6 +386,150 @@ pub enum Command {
         action: ConfigAction,
     },
 
+    /// Create a git worktree of the current repo inside a session
+    #[command(alias = "wt")]
+    Worktree {
+        /// Session name (can be prefix)
+        name: String,
+        /// Branch to check out (created if missing, default: sp/<session>)
+        branch: Option<String>,
+    },
+
+    /// Draft a pull request description from a session's notes and changes
+    Pr {
+        /// Session name (can be prefix)
+        name: Option<String>,
+        /// Branch to compare worktrees against (default: origin/HEAD, main or master)
+        #[arg(long)]
+        base: Option<String>,
+        /// Open the pull request with `gh pr create` instead of printing the draft
+        #[arg(long)]
+        create: bool,
+    },
+
+    /// Set, show or clear a session's due date
+    Due {
+        /// Session name (can be prefix)
+        name: String,
+        /// YYYY-MM-DD, today, tomorrow or +N days. Omit to show the current due date.
+        date: Option<String>,
+        /// Remove the due date
+        #[arg(long, conflicts_with = "date")]
+        clear: bool,
+    },
+
+    /// Set a follow-up reminder, list a session's reminders, or snooze/complete due ones
+    Remind {
+        /// Session name (can be prefix)
+        name: String,
+        /// When and what, e.g. `in 2d "check agent results"` or `tomorrow "review PR"`.
+        /// Omit to list the session's reminders.
+        #[arg(num_args = 0.., conflicts_with_all = ["snooze", "done"])]
+        reminder: Vec<String>,
+        /// Push due reminders back, e.g. 4h or 1d
+        #[arg(long, value_name = "SPAN", conflicts_with = "done")]
+        snooze: Option<String>,
+        /// Complete (remove) due reminders
+        #[arg(long)]
+        done: bool,
+    },
+
+    /// Export sessions to another tool
+    #[command(group(clap::ArgGroup::new("target").required(true)))]
+    Export {
+        /// Obsidian/Logseq vault directory
+        #[arg(long, group = "target")]
+        vault: Option<PathBuf>,
+        /// Print due dates as an iCalendar (.ics) feed on stdout
+        #[arg(long, group = "target")]
+        ics: bool,
+        /// Folder inside the vault that holds the sessions
+        #[arg(long, default_value = "scratchpad")]
+        folder: String,
+        /// Also write tags into the frontmatter, for tools that round-trip it
+        #[arg(long, requires = "vault")]
+        frontmatter: bool,
+    },
+
+    /// Import sessions from another tool
+    #[command(group(clap::ArgGroup::new("source").required(true)))]
+    Import {
+        /// Obsidian/Logseq vault directory
+        #[arg(long, group = "source")]
+        vault: Option<PathBuf>,
+        /// Folder inside the vault that holds the sessions
+        #[arg(long, default_value = "scratchpad")]
+        folder: String,
+        /// Take created/updated times and tags from each entry point's frontmatter
+        #[arg(long)]
+        frontmatter: bool,
+    },
+
+    /// Archive the whole workspace (respecting .gitignore and .spignore)
+    #[command(args_conflicts_with_subcommands = true)]
+    Backup {
+        #[command(subcommand)]
+        action: Option<BackupAction>,
+        /// Archive file or directory to write into (default: ./scratchpad-backup-<time>.tar.gz)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Internal: take a scheduled backup into the configured directory
+        #[arg(long, hide = true, conflicts_with = "output")]
+        scheduled: bool,
+    },
+
+    /// Save a compressed point-in-time copy of a session, or list, diff and restore them
+    #[command(args_conflicts_with_subcommands = true)]
+    Snapshot {
+        #[command(subcommand)]
+        action: Option<SnapshotAction>,
+        /// Session name (can be prefix)
+        name: Option<String>,
+        /// Label to find the snapshot by later (e.g. "before-refactor")
+        label: Option<String>,
+    },
+
+    /// Restore sessions from a `sp backup` archive
+    Restore {
+        /// Archive created by `sp backup`
+        archive: PathBuf,
+        /// What to do with sessions that already exist
+        #[arg(long, value_enum, default_value_t = Conflict::Skip)]
+        on_conflict: Conflict,
+    },
+
+    /// Serve the workspace to editor integrations
+    #[command(group(clap::ArgGroup::new("transport").required(true)))]
+    Serve {
+        /// Speak JSON-RPC 2.0 over stdin/stdout (one message per line)
+        #[arg(long, group = "transport")]
+        stdio: bool,
+        /// Serve a local REST API on this address (e.g. 127.0.0.1:7777)
+        #[arg(long, group = "transport", value_name = "ADDR")]
+        http: Option<String>,
+        /// Allow creating and writing sessions over HTTP
+        #[arg(long, requires = "http")]
+        write: bool,
+    },
+
+    /// Show local usage counters: sessions created and agent runs per week, top commands
+    Usage {
+        /// Weeks to chart
+        #[arg(long, default_value_t = 12)]
+        weeks: usize,
+    },
+
+    /// Replace this binary with the latest release (or a given one), after checking
+    /// its checksum
+    SelfUpdate {
+        /// Release tag to install instead of the latest (e.g. v0.2.0)
+        #[arg(long)]
+        version: Option<String>,
+        /// Only report whether a newer release is available
+        #[arg(long)]
+        check: bool,
+    },
+
     /// Internal: hook handler for agent integrations
     #[command(hide = true)]
     Hook {
@@ -153,8 +537,216 @@ pub enum Command {
         name: String,
     },
 
-    /// Sync sessions with server (not yet implemented)
-    Sync,
+    /// Pull remote changes from the sync server, then push local ones
+    Sync {
+        #[command(subcommand)]
+        action: Option<SyncAction>,
+        /// Cap transfer speed each way, in bytes per second: 500k, 2M, ...
+        #[arg(long, value_name = "RATE", value_parser = parse_rate)]
+        limit_rate: Option<u64>,
+        /// Create the key for `[server] encrypt = true` (sync.key in the config directory)
+        /// instead of syncing
+        #[arg(long)]
+        new_key: bool,
+        /// Sync directly with another machine over SSH instead of the server: `host` for
+        /// its user context, `host:path` for the project at that path
+        #[arg(long, value_name = "HOST[:PATH]", conflicts_with = "new_key")]
+        peer: Option<String>,
+        /// Sync through a shared folder (Dropbox, Syncthing, ...) instead of the server
+        #[arg(long, value_name = "DIR", conflicts_with_all = ["peer", "new_key"])]
+        folder: Option<PathBuf>,
+        /// Keep running, syncing whenever files change here or on the server
+        #[arg(long, conflicts_with_all = ["peer", "folder", "new_key"])]
+        watch: bool,
+        /// On the first sync with the server, start from its snapshot of the workspace
+        /// instead of replaying every change
+        #[arg(long, conflicts_with_all = ["peer", "folder", "new_key", "watch"])]
+        init: bool,
+        /// After syncing, store a snapshot of the workspace on the server for `--init`
+        #[arg(long, conflicts_with_all = ["peer", "folder", "new_key", "watch"])]
+        snapshot: bool,
+        /// Answer a peer's sync on stdin/stdout (run by `--peer` over SSH)
+        #[arg(long, hide = true, conflicts_with_all = ["peer", "folder", "new_key", "watch"])]
+        serve: bool,
+    },
+}
+
+impl Command {
+    /// Whether the command changes the workspace (refused in read-only mode)
+    pub fn is_mutating(&self) -> bool {
+        match self {
+            Command::Due { date, clear, .. } => date.is_some() || *clear,
+            Command::Remind {
+                reminder,
+                snooze,
+                done,
+                ..
+            } => !reminder.is_empty() || snooze.is_some() || *done,
+            Command::Serve { write, .. } => *write,
+            Command::Doctor { fix_perms } => *fix_perms,
+            Command::Snapshot { action, .. } => !matches!(
+                action,
+                Some(SnapshotAction::List { .. } | SnapshotAction::Diff { .. })
+            ),
+            Command::New { .. }
+            | Command::Quick { .. }
+            | Command::Run { .. }
+            | Command::Edit { .. }
+            | Command::Rename { .. }
+            | Command::Link { .. }
+            | Command::Replace { .. }
+            | Command::Clip { .. }
+            | Command::Write { .. }
+            | Command::Capture { .. }
+            | Command::Delete { .. }
+            | Command::Review { .. }
+            | Command::Dedupe { .. }
+            | Command::Summarize { .. }
+            | Command::Ingest { .. }
+            | Command::Worktree { .. }
+            | Command::Import { .. }
+            | Command::Restore { .. }
+            | Command::Init { .. }
+            | Command::Sync { action: None, .. } => true,
+            Command::Sync {
+                action: Some(SyncAction::Login { .. } | SyncAction::Status),
+                ..
+            } => false,
+            Command::Tag { tags, auto, .. } => *auto || !tags.is_empty(),
+            Command::Index { action } => matches!(action, IndexAction::Build { .. }),
+            Command::Bulk { action } => !action.dry_run(),
+            Command::Open { .. }
+            | Command::Url { .. }
+            | Command::View { .. }
+            | Command::List { .. }
+            | Command::Path { .. }
+            | Command::Folder { .. }
+            | Command::Files { .. }
+            | Command::Read { .. }
+            | Command::Grep { .. }
+            | Command::Todos { .. }
+            | Command::Context
+            | Command::Validate
+            | Command::Config { .. }
+            | Command::Export { .. }
+            | Command::Pr { .. }
+            | Command::Digest { .. }
+            | Command::Backup { .. }
+            | Command::Hook { .. }
+            | Command::Usage { .. }
+            | Command::SelfUpdate { .. } => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum IngestSource {
+    /// Claude Code (~/.claude/projects)
+    Claude,
+}
+
+#[derive(Subcommand)]
+pub enum IndexAction {
+    /// Embed new and changed notes (encrypted sessions are skipped)
+    Build {
+        /// Re-embed everything instead of reusing unchanged files
+        #[arg(long)]
+        rebuild: bool,
+    },
+    /// Show what the index holds
+    Status,
+}
+
+#[derive(Subcommand)]
+pub enum BulkAction {
+    /// Rename matching sessions, e.g. --match 'tmp-*' --replace 'experiment-*'
+    Rename {
+        /// Session name glob (`*` and `?` wildcards)
+        #[arg(long = "match", value_name = "GLOB")]
+        pattern: String,
+        /// New name; each wildcard takes what the matching one in --match captured
+        #[arg(long)]
+        replace: String,
+        /// Only show what would change
+        #[arg(long)]
+        dry_run: bool,
+        /// Apply without confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Add or remove tags on matching sessions, e.g. --match '*auth*' --add security
+    Tag {
+        /// Session name glob (`*` and `?` wildcards)
+        #[arg(long = "match", value_name = "GLOB")]
+        pattern: String,
+        /// Tags to add
+        #[arg(long, required_unless_present = "remove")]
+        add: Vec<String>,
+        /// Tags to remove
+        #[arg(long)]
+        remove: Vec<String>,
+        /// Only show what would change
+        #[arg(long)]
+        dry_run: bool,
+        /// Apply without confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+impl BulkAction {
+    fn dry_run(&self) -> bool {
+        match self {
+            BulkAction::Rename { dry_run, .. } | BulkAction::Tag { dry_run, .. } => *dry_run,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum BackupAction {
+    /// Show the scheduled backup settings and when the workspace was last backed up
+    Status,
+}
+
+#[derive(Subcommand)]
+pub enum SyncAction {
+    /// Log in to a sync server with a one-time code from `sp-server tokens code`, saving
+    /// the token to [server] in the config file
+    Login {
+        /// Server URL (default: [server] url, else prompts)
+        #[arg(long)]
+        url: Option<String>,
+        /// One-time login code (prompts if not given)
+        #[arg(long)]
+        code: Option<String>,
+    },
+    /// Show unsynced sessions, unresolved conflicts and captures staged because of them
+    Status,
+}
+
+#[derive(Subcommand)]
+pub enum SnapshotAction {
+    /// List a session's snapshots, newest first
+    List {
+        /// Session name (can be prefix)
+        name: String,
+    },
+    /// Show what changed since a snapshot (or between two snapshots)
+    Diff {
+        /// Session name (can be prefix)
+        name: String,
+        /// Snapshot name, label, or prefix (e.g. the date)
+        snapshot: String,
+        /// Compare against this snapshot instead of the current files
+        other: Option<String>,
+    },
+    /// Replace the session's files with a snapshot (the current state is snapshotted first)
+    Restore {
+        /// Session name (can be prefix)
+        name: String,
+        /// Snapshot name, label, or prefix (e.g. the date)
+        snapshot: String,
+    },
 }
 
 #[derive(Subcommand)]
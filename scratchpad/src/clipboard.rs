@@ -0,0 +1,46 @@
+//! System clipboard access via the platform's paste utility
+
+use std::process::Command;
+
+use anyhow::{Context, Result, anyhow};
+
+/// Paste commands to try, in order, for the current platform
+fn paste_commands() -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(target_os = "macos") {
+        vec![("pbpaste", &[])]
+    } else if cfg!(target_os = "windows") {
+        vec![("powershell", &["-NoProfile", "-Command", "Get-Clipboard"])]
+    } else {
+        let mut commands: Vec<(&str, &[&str])> = Vec::new();
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            commands.push(("wl-paste", &["--no-newline"]));
+        }
+        commands.push(("xclip", &["-selection", "clipboard", "-o"]));
+        commands.push(("xsel", &["--clipboard", "--output"]));
+        commands
+    }
+}
+
+/// Read the clipboard as text
+pub fn read_clipboard() -> Result<String> {
+    let Some((program, args)) = paste_commands()
+        .into_iter()
+        .find(|(program, _)| which::which(program).is_ok())
+    else {
+        return Err(anyhow!(
+            "No clipboard tool found (install wl-clipboard, xclip or xsel)"
+        ));
+    };
+
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to run {program}"))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{program} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -60,10 +60,97 @@ config_version = {CURRENT_CONFIG_VERSION}
 # Name generation strategy: "auto", "claude", "codex", or "static"
 # name_generator = "auto"
 
+# Browse without changing anything, e.g. a teammate's synced workspace or a backup
+# read_only = false
+
+# Create session directories and files as 0700/0600 instead of following the umask,
+# since notes often hold tokens (fix older files with `sp doctor --fix-perms`)
+# private_files = false
+
+# In a project, keep one session per git branch (`branch-<name>`): `sp open --branch`
+# opens it, creating it on first use, and the TUI marks it in the list
+# branch_sessions = false
+
+# Group the TUI's session list under headers: "date" (Today, Yesterday, This week, Older)
+# or "tag" (first tag). `F` cycles through them, Enter or `f` folds a group
+# group_by = "none"
+
+# Row layout for `sp list` and the TUI's session list. Fields: slug, title, tags, size,
+# status, updated, created; `:25` pads or cuts to 25 columns, `:relative` gives "2h ago"
+# list_format = "{{slug:25}} {{tags}} {{updated:relative}}"
+
+# Count commands, sessions created and agent runs in usage.json next to this file, for
+# `sp usage`. Nothing is sent anywhere
+# usage_stats = true
+
+# Tags `sp tag --auto` lets the agent pick from
+# tag_vocabulary = ["bug", "feature", "research", "infra", "perf"]
+
+# Other workspaces to switch to with `W` in the TUI, by name
+# [workspaces]
+# work = "~/work/scratchpad"
+
 # Sync server (optional)
 # [server]
 # url = "http://localhost:3000"
 # token = "your-token"
+# workspace = "user"   # server workspace; defaults to "user" or "project-<repo>"
+# encrypt = true       # end-to-end encryption; create the key with `sp sync --new-key`
+
+# Which contexts `sp sync` may sync (optional), as globs over "user" and
+# "project:<repo path>". An empty include means every context; exclude wins.
+# Without a [server], `folder` syncs through a directory shared by Dropbox, Syncthing, ...
+# [sync]
+# include = ["user"]
+# exclude = ["project:~/clients/*"]
+# folder = "~/Dropbox/scratchpad-sync"
+# device_name = "laptop"   # what the server calls this machine; defaults to the hostname
+# backend = "git"         # or "server", "folder"; git syncs through `remote`
+# remote = "git@github.com:me/scratchpad-sync.git"
+# ignore = ["*.log", "target/"]   # workspace paths never synced
+# max_file_size = "5M"            # larger files stay on this machine
+
+# Scheduled backups (optional), taken in the background when sp starts and one is due
+# [backup]
+# interval = "daily"   # hourly, daily, weekly, or e.g. "12h" / "3d"
+# keep = 7
+# path = "/path/to/backups"
+
+# Key for encrypted sessions (`sp new --encrypted`): an age identity file or a passphrase
+# [encryption]
+# identity = "/path/to/key.txt"   # created with `age-keygen -o key.txt`
+# passphrase = "..."
+
+# Issue fetching for `sp new --issue` (optional). GitHub issues (gh#456) use the `gh` CLI;
+# other keys run `command` with {{id}} replaced, which prints the title then the description
+# [issues]
+# command = "/path/to/fetch-issue {{id}}"
+# token = "..."   # passed as SP_ISSUE_TOKEN (and GH_TOKEN for gh)
+
+# Pull request description for `sp pr` (optional): a markdown file using {{title}},
+# {{session}}, {{notes}} and {{changes}} (worktree commits and diffstats, saved patches)
+# [pr]
+# template = "/path/to/pr-template.md"
+
+# Session naming rules (optional), checked wherever sessions are created or renamed
+# (`sp new` and `sp rename` skip them with --no-verify); `sp validate` lists existing
+# sessions that break them
+# [naming]
+# pattern = "^[a-z]+-[0-9]+-"   # regex over the slug, e.g. a ticket id prefix
+# min_length = 5
+# max_length = 40
+
+# Semantic search (`sp index build`, then `sp search --semantic "..."`). Uses a local
+# Ollama server by default, or a command that reads text on stdin and prints a JSON array
+# [embeddings]
+# model = "nomic-embed-text"
+# url = "http://localhost:11434"
+# command = "/path/to/embed"
+
+# Webhook notifications (optional): session.created, session.deleted, agent.finished
+# [notifications]
+# url = "https://hooks.slack.com/services/..."
+# events = ["agent.finished"]
 "#
     )
 }
@@ -92,6 +179,32 @@ fn save_config_atomic(path: &PathBuf, content: &str) -> Result<()> {
     Ok(())
 }
 
+/// Point `[server]` at `url` with `token`, keeping the rest of the config file (and its
+/// comments) as it is. The file is created if missing and always saved as 0600.
+pub fn save_server_login(url: &str, token: &str) -> Result<PathBuf> {
+    let path = config_path();
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => config_template(),
+        Err(e) => return Err(e).context("Failed to read config file"),
+    };
+    let content = with_server_login(&content, url, token)?;
+    save_config_atomic(&path, &content)?;
+    Ok(path)
+}
+
+fn with_server_login(content: &str, url: &str, token: &str) -> Result<String> {
+    let mut doc: toml_edit::DocumentMut = content.parse().context("Failed to parse config file")?;
+    let server = doc
+        .entry("server")
+        .or_insert_with(toml_edit::table)
+        .as_table_mut()
+        .context("[server] in the config file isn't a table")?;
+    server["url"] = toml_edit::value(url);
+    server["token"] = toml_edit::value(token);
+    Ok(doc.to_string())
+}
+
 pub fn handle_config(action: ConfigAction, config: &Config) -> Result<()> {
     match action {
         ConfigAction::Init { force } => {
@@ -164,6 +277,23 @@ mod tests {
         assert_eq!(config.config_version, CURRENT_CONFIG_VERSION);
     }
 
+    #[test]
+    fn login_keeps_the_rest_of_the_config() {
+        let content = "# My notes\nworkspace_path = \"/tmp/notes\"\n\n[server]\nencrypt = true\n";
+        let content = with_server_login(content, "https://sync.example", "abc").unwrap();
+        assert!(content.starts_with("# My notes\n"));
+        let config: Config = toml::from_str(&content).unwrap();
+        let server = config.server.unwrap();
+        assert_eq!(server.url, "https://sync.example");
+        assert_eq!(server.token.as_deref(), Some("abc"));
+        assert!(server.encrypt);
+        assert_eq!(config.workspace_path, "/tmp/notes");
+
+        let fresh = with_server_login(&config_template(), "http://localhost:3000", "t").unwrap();
+        let config: Config = toml::from_str(&fresh).unwrap();
+        assert_eq!(config.server.unwrap().token.as_deref(), Some("t"));
+    }
+
     #[test]
     #[cfg(unix)]
     fn atomic_save_sets_permissions() {
@@ -0,0 +1,292 @@
+//! Encrypted sessions: files stored as age-encrypted `<name>.age`
+//!
+//! The key comes from the `[encryption]` config section, either an age identity file or
+//! a passphrase. Previews decrypt in memory. Editing decrypts into a private temp
+//! directory and the plaintext is overwritten and removed once the editor exits.
+
+use std::fs;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use age::secrecy::SecretString;
+use anyhow::{Context as _, Result, anyhow};
+
+use crate::models::Config;
+
+/// Extension of encrypted files
+pub const EXTENSION: &str = "age";
+
+pub enum Cipher {
+    Identity(age::x25519::Identity),
+    Passphrase(SecretString),
+}
+
+impl Cipher {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let encryption = config.encryption.as_ref();
+        if let Some(path) = encryption.and_then(|e| e.identity.as_deref()) {
+            return Ok(Cipher::Identity(read_identity(Path::new(path))?));
+        }
+        if let Some(passphrase) = encryption.and_then(|e| e.passphrase.clone()) {
+            return Ok(Cipher::Passphrase(SecretString::from(passphrase)));
+        }
+        Err(anyhow!(
+            "Encryption is not configured: set [encryption] identity (an age key file) \
+             or passphrase in {}",
+            crate::config::config_path().display()
+        ))
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let result = match self {
+            Cipher::Identity(identity) => age::encrypt(&identity.to_public(), plaintext),
+            Cipher::Passphrase(passphrase) => {
+                age::encrypt(&age::scrypt::Recipient::new(passphrase.clone()), plaintext)
+            }
+        };
+        result.map_err(|e| anyhow!("Encryption failed: {e}"))
+    }
+
+    pub fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let result = match self {
+            Cipher::Identity(identity) => age::decrypt(identity, ciphertext),
+            Cipher::Passphrase(passphrase) => {
+                age::decrypt(&age::scrypt::Identity::new(passphrase.clone()), ciphertext)
+            }
+        };
+        result.map_err(|e| anyhow!("Decryption failed: {e}"))
+    }
+
+    pub fn decrypt_file(&self, path: &Path) -> Result<Vec<u8>> {
+        let ciphertext =
+            fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        self.decrypt(&ciphertext)
+            .with_context(|| format!("Failed to decrypt {}", path.display()))
+    }
+
+    pub fn encrypt_to_file(&self, path: &Path, plaintext: &[u8]) -> Result<()> {
+        fs::write(path, self.encrypt(plaintext)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// The `AGE-SECRET-KEY-...` line of an age identity file
+pub fn read_identity(path: &Path) -> Result<age::x25519::Identity> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read identity file {}", path.display()))?;
+    let key = content
+        .lines()
+        .map(str::trim)
+        .find(|l| l.starts_with("AGE-SECRET-KEY-"))
+        .ok_or_else(|| anyhow!("No AGE-SECRET-KEY in {}", path.display()))?;
+    age::x25519::Identity::from_str(key)
+        .map_err(|e| anyhow!("Invalid identity in {}: {e}", path.display()))
+}
+
+pub fn is_encrypted(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == EXTENSION)
+}
+
+/// `notes.md` -> `notes.md.age`
+pub fn encrypted_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{EXTENSION}"));
+    PathBuf::from(name)
+}
+
+/// Encrypt every plain file under `dir` in place (hidden files such as metadata stay
+/// readable). Returns the number of files encrypted.
+pub fn encrypt_dir(cipher: &Cipher, dir: &Path) -> Result<usize> {
+    let mut count = 0;
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        let hidden = path
+            .file_name()
+            .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+        if hidden || is_encrypted(&path) {
+            continue;
+        }
+        if path.is_dir() {
+            count += encrypt_dir(cipher, &path)?;
+            continue;
+        }
+        let plaintext = fs::read(&path)?;
+        cipher.encrypt_to_file(&encrypted_path(&path), &plaintext)?;
+        shred(&path)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Whether any file under `dir` is a `.age` file
+pub fn contains_encrypted(dir: &Path) -> bool {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .any(|entry| {
+            let path = entry.path();
+            is_encrypted(&path) || (path.is_dir() && contains_encrypted(&path))
+        })
+}
+
+/// Decrypt every `.age` file under `dir` in place, the reverse of `encrypt_dir`. Returns
+/// the number of files decrypted.
+pub fn decrypt_dir(cipher: &Cipher, dir: &Path) -> Result<usize> {
+    let mut count = 0;
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            count += decrypt_dir(cipher, &path)?;
+        } else if is_encrypted(&path) {
+            let plaintext = cipher.decrypt_file(&path)?;
+            let plain = path.with_extension("");
+            fs::write(&plain, plaintext)
+                .with_context(|| format!("Failed to write {}", plain.display()))?;
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Decrypt `path` into a private temp file, run `open` on it, then re-encrypt any changes
+/// and shred the plaintext
+pub fn edit_encrypted(
+    cipher: &Cipher,
+    path: &Path,
+    open: impl FnOnce(&Path) -> Result<()>,
+) -> Result<()> {
+    let plaintext = cipher.decrypt_file(path)?;
+    let dir = private_temp_dir()?;
+    // Keep the inner name (notes.md) so editors pick the right syntax
+    let name = path
+        .file_stem()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("file"));
+    let temp = dir.join(name);
+
+    let result = write_private(&temp, &plaintext)
+        .and_then(|()| open(&temp))
+        .and_then(|()| {
+            let edited = fs::read(&temp).context("Failed to read edited file")?;
+            if edited != plaintext {
+                cipher.encrypt_to_file(path, &edited)?;
+            }
+            Ok(())
+        });
+    // Editors leave backups and swap files next to it, holding the plaintext too
+    let cleanup = shred_dir(&dir).and_then(|()| {
+        fs::remove_dir_all(&dir).with_context(|| format!("Failed to remove {}", dir.display()))
+    });
+    result.and(cleanup)
+}
+
+fn private_temp_dir() -> Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!(
+        "sp-{}-{}",
+        std::process::id(),
+        rand::random::<u32>()
+    ));
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder
+        .create(&dir)
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+    Ok(dir)
+}
+
+pub fn write_private(path: &Path, content: &[u8]) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    file.write_all(content)?;
+    Ok(())
+}
+
+/// Overwrite a file with zeros before removing it. Missing files are fine (an editor may
+/// have replaced the file by renaming).
+pub fn shred(path: &Path) -> Result<()> {
+    let Ok(meta) = fs::metadata(path) else {
+        return Ok(());
+    };
+    if let Ok(mut file) = fs::OpenOptions::new().write(true).open(path) {
+        let _ = file
+            .write_all(&vec![0; meta.len() as usize])
+            .and_then(|()| file.sync_all());
+    }
+    fs::remove_file(path).with_context(|| format!("Failed to remove {}", path.display()))
+}
+
+/// `shred` every file under `dir`, without following symlinks. Goes on past failures,
+/// returning the first.
+fn shred_dir(dir: &Path) -> Result<()> {
+    let entries = fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+    let mut result = Ok(());
+    for entry in entries {
+        let shredded = entry.map_err(anyhow::Error::from).and_then(|entry| {
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                shred_dir(&path)
+            } else {
+                shred(&path)
+            }
+        });
+        result = result.and(shredded);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher() -> Cipher {
+        Cipher::Identity(age::x25519::Identity::generate())
+    }
+
+    #[test]
+    fn encrypts_directory_and_edits_through_temp_file() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("notes.md"), "token=abc").unwrap();
+        fs::write(dir.path().join("sub/data.txt"), "x").unwrap();
+        fs::write(dir.path().join(".session.toml"), "encrypted = true").unwrap();
+
+        let cipher = cipher();
+        assert_eq!(encrypt_dir(&cipher, dir.path()).unwrap(), 2);
+        let notes = dir.path().join("notes.md.age");
+        assert!(!dir.path().join("notes.md").exists());
+        assert!(dir.path().join("sub/data.txt.age").exists());
+        assert!(dir.path().join(".session.toml").exists());
+        assert!(!fs::read(&notes).unwrap().windows(3).any(|w| w == b"abc"));
+        assert_eq!(cipher.decrypt_file(&notes).unwrap(), b"token=abc");
+
+        let mut temp_path = PathBuf::new();
+        edit_encrypted(&cipher, &notes, |temp| {
+            assert_eq!(temp.file_name().unwrap(), "notes.md");
+            temp_path = temp.to_path_buf();
+            fs::write(temp, "token=xyz")?;
+            // What vim and emacs leave behind
+            fs::write(temp.with_file_name(".notes.md.swp"), "token=xyz")?;
+            fs::write(temp.with_file_name("notes.md~"), "token=abc")?;
+            Ok(())
+        })
+        .unwrap();
+        assert!(!temp_path.parent().unwrap().exists());
+        assert_eq!(cipher.decrypt_file(&notes).unwrap(), b"token=xyz");
+    }
+}
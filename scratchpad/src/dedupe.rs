@@ -0,0 +1,217 @@
+//! Duplicate session detection for `sp dedupe`
+//!
+//! Entry points are compared after normalizing whitespace: equal checksums mean identical
+//! notes, and a high share of common lines means near-identical ones (an agent re-ran and
+//! produced a slightly different copy).
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::hash::{Hash, Hasher};
+
+use anyhow::{Context as _, Result};
+
+use crate::models::Session;
+use crate::storage::{META_FILE, Storage};
+
+/// Sessions whose notes are (nearly) the same, oldest first
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    pub sessions: Vec<Session>,
+    /// All entry points have the same checksum
+    pub identical: bool,
+    /// Lowest similarity among the pairs that linked the group (0.0–1.0)
+    pub similarity: f64,
+}
+
+struct Fingerprint {
+    session: Session,
+    checksum: u64,
+    lines: HashSet<String>,
+}
+
+/// Group sessions whose entry points are at least `threshold` similar. Sessions with
+/// empty notes are ignored.
+pub fn find_duplicates(storage: &Storage, threshold: f64) -> Result<Vec<DuplicateGroup>> {
+    let mut prints: Vec<Fingerprint> = storage
+        .list_sessions()?
+        .into_iter()
+        .filter_map(|session| {
+            let content = storage.read_notes(&session.slug).ok()?;
+            let lines = normalized_lines(&content);
+            if lines.is_empty() {
+                return None;
+            }
+            let mut hasher = DefaultHasher::new();
+            lines.hash(&mut hasher);
+            Some(Fingerprint {
+                session,
+                checksum: hasher.finish(),
+                lines: lines.into_iter().collect(),
+            })
+        })
+        .collect();
+    prints.sort_by_key(|p| p.session.created_at);
+
+    // Union-find over similar pairs
+    let mut parent: Vec<usize> = (0..prints.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    let mut min_similarity = vec![1.0_f64; prints.len()];
+    for i in 0..prints.len() {
+        for j in i + 1..prints.len() {
+            let sim = if prints[i].checksum == prints[j].checksum {
+                1.0
+            } else {
+                similarity(&prints[i].lines, &prints[j].lines)
+            };
+            if sim >= threshold {
+                let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                let merged = min_similarity[a].min(min_similarity[b]).min(sim);
+                parent[b] = a;
+                min_similarity[a] = merged;
+            }
+        }
+    }
+
+    let roots: BTreeSet<usize> = (0..prints.len()).map(|i| root(&mut parent, i)).collect();
+    let mut groups = Vec::new();
+    for r in roots {
+        let members: Vec<usize> = (0..prints.len())
+            .filter(|&i| root(&mut parent, i) == r)
+            .collect();
+        if members.len() < 2 {
+            continue;
+        }
+        let checksum = prints[members[0]].checksum;
+        groups.push(DuplicateGroup {
+            identical: members.iter().all(|&i| prints[i].checksum == checksum),
+            similarity: min_similarity[r],
+            sessions: members.iter().map(|&i| prints[i].session.clone()).collect(),
+        });
+    }
+    Ok(groups)
+}
+
+/// Non-empty lines with surrounding whitespace removed
+fn normalized_lines(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Jaccard similarity of two line sets
+fn similarity(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let common = a.intersection(b).count();
+    let total = a.len() + b.len() - common;
+    if total == 0 {
+        1.0
+    } else {
+        common as f64 / total as f64
+    }
+}
+
+/// Move the contents of `duplicate` into `keeper` and delete `duplicate`.
+/// Files identical to the keeper's are dropped; other clashes go to `keeper/<duplicate>/`.
+pub fn merge_into(storage: &Storage, keeper: &str, duplicate: &str) -> Result<()> {
+    let src_dir = storage.session_dir(duplicate);
+    let dest_dir = storage.session_dir(keeper);
+    let entries =
+        fs::read_dir(&src_dir).with_context(|| format!("Failed to read {}", src_dir.display()))?;
+    for entry in entries.filter_map(|e| e.ok()) {
+        let name = entry.file_name();
+        if name == META_FILE {
+            continue;
+        }
+        let src = entry.path();
+        let dest = dest_dir.join(&name);
+        if !dest.exists() {
+            fs::rename(&src, &dest).with_context(|| format!("Failed to move {}", src.display()))?;
+            continue;
+        }
+        let same = src.is_file() && dest.is_file() && fs::read(&src).ok() == fs::read(&dest).ok();
+        if !same {
+            let clash_dir = dest_dir.join(duplicate);
+            fs::create_dir_all(&clash_dir).context("Failed to create merge directory")?;
+            fs::rename(&src, clash_dir.join(&name))
+                .with_context(|| format!("Failed to move {}", src.display()))?;
+        }
+    }
+    storage.delete_session(duplicate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Config, Context};
+
+    fn test_storage(dir: &std::path::Path) -> Storage {
+        let config = Config {
+            workspace_path: dir.to_string_lossy().to_string(),
+            ..Config::default()
+        };
+        Storage::new(config, Context::User)
+    }
+
+    #[test]
+    fn groups_identical_and_similar_notes() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = test_storage(dir.path());
+        let plan = "# Plan\n- one\n- two\n- three\n- four\n";
+        for (slug, note) in [
+            ("a", plan.to_string()),
+            ("b", format!("  {}\n\n", plan.replace('\n', "  \n"))),
+            ("c", format!("{plan}- five\n")),
+            ("d", "something else entirely\n".to_string()),
+            ("e", String::new()),
+        ] {
+            storage
+                .create_session(&Session::new(slug), Some(&note))
+                .unwrap();
+        }
+
+        let exact = find_duplicates(&storage, 1.0).unwrap();
+        assert_eq!(exact.len(), 1);
+        assert!(exact[0].identical);
+        let mut slugs: Vec<_> = exact[0].sessions.iter().map(|s| s.slug.as_str()).collect();
+        slugs.sort();
+        assert_eq!(slugs, vec!["a", "b"]);
+
+        let near = find_duplicates(&storage, 0.8).unwrap();
+        assert_eq!(near.len(), 1);
+        assert_eq!(near[0].sessions.len(), 3);
+        assert!(!near[0].identical);
+        assert!((near[0].similarity - 5.0 / 6.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn merge_moves_new_files_and_keeps_clashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = test_storage(dir.path());
+        storage
+            .create_session(&Session::new("keep"), Some("same"))
+            .unwrap();
+        storage
+            .create_session(&Session::new("dup"), Some("same"))
+            .unwrap();
+        fs::write(storage.session_dir("keep").join("out.txt"), "old").unwrap();
+        fs::write(storage.session_dir("dup").join("out.txt"), "new").unwrap();
+        fs::write(storage.session_dir("dup").join("extra.md"), "x").unwrap();
+
+        merge_into(&storage, "keep", "dup").unwrap();
+        let keep = storage.session_dir("keep");
+        assert!(!storage.session_dir("dup").exists());
+        assert!(keep.join("extra.md").exists());
+        assert!(!keep.join("dup/notes.md").exists());
+        assert_eq!(fs::read_to_string(keep.join("dup/out.txt")).unwrap(), "new");
+        assert_eq!(fs::read_to_string(keep.join("out.txt")).unwrap(), "old");
+    }
+}
@@ -0,0 +1,190 @@
+//! Activity digests for standups and weekly reviews (`sp digest`)
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+
+use crate::calendar;
+use crate::llm;
+use crate::models::{Agent, Session};
+use crate::storage::{Storage, first_heading};
+use crate::todos::{self, TodoItem};
+
+/// Longest note excerpt shown per session
+const EXCERPT_CHARS: usize = 120;
+
+/// Start of the digest window: `<N>h`, `<N>d`, `<N>w`, or a `YYYY-MM-DD` date
+pub fn parse_since(input: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    let input = input.trim().to_lowercase();
+    if let Ok(date) = NaiveDate::parse_from_str(&input, "%Y-%m-%d") {
+        let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+        return Local
+            .from_local_datetime(&midnight)
+            .earliest()
+            .map(|t| t.with_timezone(&Utc))
+            .ok_or_else(|| anyhow!("Invalid date: {input}"));
+    }
+    let span = calendar::parse_span(&input)
+        .ok_or_else(|| anyhow!("Invalid --since: {input} (use e.g. 12h, 3d, 1w or 2024-05-01)"))?;
+    Ok(now - span)
+}
+
+struct Entry {
+    session: Session,
+    title: Option<String>,
+    excerpt: Option<String>,
+    runs: usize,
+}
+
+/// Markdown report of the sessions created or updated since `since`
+pub fn build(storage: &Storage, since: DateTime<Utc>, now: DateTime<Utc>) -> Result<String> {
+    let mut created = Vec::new();
+    let mut updated = Vec::new();
+    let mut done: Vec<TodoItem> = Vec::new();
+
+    let mut sessions = storage.list_sessions()?;
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.updated_at));
+    for session in sessions {
+        if session.updated_at < since {
+            continue;
+        }
+        let notes = storage.read_notes(&session.slug).unwrap_or_default();
+        let runs = storage
+            .load_meta(&session.slug)
+            .map(|meta| meta.runs.iter().filter(|r| r.started_at >= since).count())
+            .unwrap_or(0);
+        done.extend(todos::session_done(
+            &storage.session_dir(&session.slug),
+            &session.slug,
+        ));
+        let entry = Entry {
+            title: first_heading(&notes),
+            excerpt: excerpt(&notes),
+            runs,
+            session,
+        };
+        if entry.session.created_at >= since {
+            created.push(entry);
+        } else {
+            updated.push(entry);
+        }
+    }
+
+    let mut out = format!(
+        "# Digest: {} – {}\n",
+        since.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+        now.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+    );
+    if created.is_empty() && updated.is_empty() {
+        out.push_str("\nNo activity.\n");
+        return Ok(out);
+    }
+    push_section(&mut out, "Created", &created);
+    push_section(&mut out, "Updated", &updated);
+    if !done.is_empty() {
+        out.push_str(&format!("\n## Completed TODOs ({})\n\n", done.len()));
+        for item in &done {
+            out.push_str(&format!("- [x] {} ({})\n", item.text, item.slug));
+        }
+    }
+    Ok(out)
+}
+
+fn push_section(out: &mut String, heading: &str, entries: &[Entry]) {
+    if entries.is_empty() {
+        return;
+    }
+    out.push_str(&format!("\n## {heading} ({})\n\n", entries.len()));
+    for entry in entries {
+        out.push_str(&format!("- **{}**", entry.session.slug));
+        if let Some(title) = &entry.title {
+            out.push_str(&format!(": {title}"));
+        }
+        match entry.runs {
+            0 => {}
+            1 => out.push_str(" (1 agent run)"),
+            n => out.push_str(&format!(" ({n} agent runs)")),
+        }
+        out.push('\n');
+        if let Some(excerpt) = &entry.excerpt {
+            out.push_str(&format!("  > {excerpt}\n"));
+        }
+    }
+}
+
+/// First line of prose in the notes, skipping headings, tasks and code blocks
+fn excerpt(notes: &str) -> Option<String> {
+    let mut in_code_block = false;
+    let line = notes.lines().map(str::trim).find(|line| {
+        if line.starts_with("```") {
+            in_code_block = !in_code_block;
+            return false;
+        }
+        !in_code_block
+            && !line.is_empty()
+            && !line.starts_with('#')
+            && !line.starts_with("- [")
+            && !line.starts_with("* [")
+    })?;
+    let mut excerpt: String = line.chars().take(EXCERPT_CHARS).collect();
+    if line.chars().count() > EXCERPT_CHARS {
+        excerpt.push('…');
+    }
+    Some(excerpt)
+}
+
+/// Have the agent rewrite the report into a short standup-style summary
+pub fn refine(report: &str, agent: Agent) -> Result<String> {
+    let prompt = format!(
+        "Rewrite this activity digest of my scratchpad sessions as a concise markdown \
+         summary for a standup or weekly review: group related work, highlight progress and \
+         completed tasks, and keep session names. Output only the summary.\n\n{report}"
+    );
+    Ok(llm::ask(agent, &prompt)? + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Config, Context};
+    use chrono::Duration;
+
+    #[test]
+    fn parses_since() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 10, 12, 0, 0).unwrap();
+        assert_eq!(parse_since("1w", now).unwrap(), now - Duration::weeks(1));
+        assert_eq!(parse_since("3d", now).unwrap(), now - Duration::days(3));
+        assert_eq!(parse_since("12H", now).unwrap(), now - Duration::hours(12));
+        assert!(parse_since("2024-05-01", now).unwrap() < now - Duration::days(8));
+        assert!(parse_since("0d", now).is_err());
+        assert!(parse_since("week", now).is_err());
+        assert!(parse_since("", now).is_err());
+        assert!(parse_since("3é", now).is_err());
+    }
+
+    #[test]
+    fn reports_recent_sessions_and_completed_tasks() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            workspace_path: dir.path().to_string_lossy().to_string(),
+            ..Config::default()
+        };
+        let storage = Storage::new(config, Context::User);
+        storage
+            .create_session(
+                &Session::new("cache-bug"),
+                Some("# Cache bug\n\nStale entries after deploy.\n- [x] reproduce\n- [ ] fix\n"),
+            )
+            .unwrap();
+
+        let now = Utc::now();
+        let report = build(&storage, now - Duration::days(1), now).unwrap();
+        assert!(report.contains("## Created (1)"));
+        assert!(report.contains("- **cache-bug**: Cache bug\n  > Stale entries after deploy.\n"));
+        assert!(report.contains("## Completed TODOs (1)\n\n- [x] reproduce (cache-bug)\n"));
+        assert!(!report.contains("## Updated"));
+
+        let later = now + Duration::days(2);
+        let report = build(&storage, later - Duration::days(1), later).unwrap();
+        assert!(report.ends_with("No activity.\n"));
+    }
+}
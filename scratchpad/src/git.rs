@@ -0,0 +1,137 @@
+//! Thin wrappers around the `git` CLI
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context as _, Result, anyhow};
+
+/// Run `git` in `dir` and return trimmed stdout, failing with git's stderr on error
+pub fn run(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("git {}: {}", args.join(" "), stderr.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Top-level directory of the repository containing `dir`
+pub fn repo_root(dir: &Path) -> Option<PathBuf> {
+    run(dir, &["rev-parse", "--show-toplevel"])
+        .ok()
+        .map(PathBuf::from)
+}
+
+/// Name of the checked-out branch, or None when HEAD is detached
+pub fn current_branch(dir: &Path) -> Option<String> {
+    run(dir, &["symbolic-ref", "--quiet", "--short", "HEAD"]).ok()
+}
+
+/// Branch that pull requests target: the remote's HEAD (e.g. `origin/main`), else a local
+/// `main` or `master`
+pub fn default_branch(dir: &Path) -> String {
+    if let Ok(remote_head) = run(dir, &["rev-parse", "--abbrev-ref", "origin/HEAD"]) {
+        return remote_head;
+    }
+    if branch_exists(dir, "main") || !branch_exists(dir, "master") {
+        "main".to_string()
+    } else {
+        "master".to_string()
+    }
+}
+
+pub fn branch_exists(repo: &Path, branch: &str) -> bool {
+    run(
+        repo,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("refs/heads/{branch}"),
+        ],
+    )
+    .is_ok()
+}
+
+/// Branch and working tree summary of a repository
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepoStatus {
+    /// Branch name, or `HEAD` when detached
+    pub branch: String,
+    /// Modified, staged and untracked paths
+    pub dirty: usize,
+    pub ahead: usize,
+    pub behind: usize,
+}
+
+pub fn status(dir: &Path) -> Option<RepoStatus> {
+    run(dir, &["status", "--porcelain=v1", "--branch"])
+        .ok()
+        .map(|out| parse_status(&out))
+}
+
+/// Parse `git status --porcelain=v1 --branch` output
+fn parse_status(output: &str) -> RepoStatus {
+    let mut status = RepoStatus {
+        branch: "HEAD".to_string(),
+        dirty: 0,
+        ahead: 0,
+        behind: 0,
+    };
+    for line in output.lines() {
+        let Some(header) = line.strip_prefix("## ") else {
+            if !line.is_empty() {
+                status.dirty += 1;
+            }
+            continue;
+        };
+        // "main...origin/main [ahead 1, behind 2]" | "No commits yet on main" | "HEAD (no branch)"
+        let (names, tracking) = match header.split_once(" [") {
+            Some((names, rest)) => (names, rest.trim_end_matches(']')),
+            None => (header, ""),
+        };
+        let local = names.split("...").next().unwrap_or(names);
+        status.branch = local
+            .strip_prefix("No commits yet on ")
+            .or_else(|| local.strip_prefix("Initial commit on "))
+            .unwrap_or(local)
+            .split(' ')
+            .next()
+            .unwrap_or("HEAD")
+            .to_string();
+        for part in tracking.split(", ") {
+            if let Some(n) = part.strip_prefix("ahead ") {
+                status.ahead = n.parse().unwrap_or(0);
+            } else if let Some(n) = part.strip_prefix("behind ") {
+                status.behind = n.parse().unwrap_or(0);
+            }
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_branch_tracking_and_dirty_count() {
+        let out = "## main...origin/main [ahead 2, behind 1]\n M src/lib.rs\n?? notes.md\n";
+        assert_eq!(
+            parse_status(out),
+            RepoStatus {
+                branch: "main".to_string(),
+                dirty: 2,
+                ahead: 2,
+                behind: 1,
+            }
+        );
+        assert_eq!(parse_status("## No commits yet on dev").branch, "dev");
+        assert_eq!(parse_status("## HEAD (no branch)").branch, "HEAD");
+    }
+}
@@ -0,0 +1,288 @@
+//! Local REST API over the workspace
+//!
+//! A thin HTTP front end for the same methods as the JSON-RPC mode:
+//!
+//! - `GET  /sessions`                      list sessions
+//! - `GET  /sessions/{slug}[?file=path]`   read the entry point or a file
+//! - `GET  /search?q=...[&session=..][&limit=N]`
+//! - `POST /sessions` (JSON `{name?, note?}`)                 create, requires `--write`
+//! - `PUT  /sessions/{slug}[?file=path]` (JSON `{content}`)  write, requires `--write`
+//!
+//! Only `Host: localhost` or `127.0.0.1` is served, so a web page can't reach the API
+//! by rebinding its own domain to this machine. Writes must be sent as
+//! `application/json`, which a cross-origin form can't do, and `PUT` names its session
+//! exactly rather than by prefix.
+
+use anyhow::{Result, anyhow};
+use serde_json::{Map, Value, json};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::models::Config;
+use crate::rpc::{self, RpcError};
+use crate::storage::Storage;
+
+/// Hosts requests may be addressed to
+const ALLOWED_HOSTS: [&str; 3] = ["localhost", "127.0.0.1", "[::1]"];
+
+/// Writes sent as anything but JSON
+const UNSUPPORTED_MEDIA_TYPE: i64 = -32004;
+
+pub fn serve_http(storage: &Storage, config: &Config, addr: &str, writable: bool) -> Result<()> {
+    let server = Server::http(addr).map_err(|e| anyhow!("Failed to bind {addr}: {e}"))?;
+    let mode = if writable { "read-write" } else { "read-only" };
+    eprintln!("Serving {mode} API on http://{addr}");
+    serve(&server, storage, config, writable);
+    Ok(())
+}
+
+fn serve(server: &Server, storage: &Storage, config: &Config, writable: bool) {
+    for mut request in server.incoming_requests() {
+        let (status, body) = match handle(storage, config, &mut request, writable) {
+            Ok(result) => (200, result),
+            Err(e) => (
+                status_for(&e),
+                json!({ "error": { "code": e.code, "message": e.message } }),
+            ),
+        };
+        let header = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+            .expect("static header is valid");
+        let response = Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(header);
+        let _ = request.respond(response);
+    }
+}
+
+fn handle(
+    storage: &Storage,
+    config: &Config,
+    request: &mut Request,
+    writable: bool,
+) -> Result<Value, RpcError> {
+    if !allowed_host(header(request, "Host")) {
+        return Err(RpcError::new(rpc::FORBIDDEN, "Host not allowed"));
+    }
+    let (path, query) = match request.url().split_once('?') {
+        Some((path, query)) => (path.to_string(), query.to_string()),
+        None => (request.url().to_string(), String::new()),
+    };
+    let mut params: Map<String, Value> = form_urlencoded::parse(query.as_bytes())
+        .map(|(k, v)| (k.into_owned(), Value::String(v.into_owned())))
+        .collect();
+    if let Some(limit) = params.get("limit").and_then(Value::as_str) {
+        let limit: u64 = limit
+            .parse()
+            .map_err(|_| RpcError::new(rpc::INVALID_PARAMS, "limit must be a number"))?;
+        params.insert("limit".into(), json!(limit));
+    }
+
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let method = request.method().clone();
+    let (rpc_method, needs_write) = match (&method, segments.as_slice()) {
+        (Method::Get, ["sessions"]) => ("list", false),
+        (Method::Get, ["sessions", slug]) => {
+            params.insert("session".into(), json!(decode(slug)));
+            ("read", false)
+        }
+        (Method::Get, ["search"]) => {
+            if let Some(q) = params.remove("q") {
+                params.insert("query".into(), q);
+            }
+            ("search", false)
+        }
+        (Method::Post, ["sessions"]) => {
+            if let Value::Object(body) = read_json(request)? {
+                params.extend(body);
+            }
+            ("create", true)
+        }
+        (Method::Put, ["sessions", slug]) => {
+            if let Value::Object(body) = read_json(request)? {
+                params.extend(body);
+            }
+            let slug = decode(slug);
+            // The session is named exactly: a prefix could overwrite another one
+            if !storage.existing_slugs()?.contains(&slug) {
+                return Err(RpcError::new(
+                    rpc::SESSION_NOT_FOUND,
+                    format!("Session not found: {slug}"),
+                ));
+            }
+            params.insert("session".into(), json!(slug));
+            ("write", true)
+        }
+        _ => {
+            return Err(RpcError::new(
+                rpc::METHOD_NOT_FOUND,
+                format!("No route for {method} {path}"),
+            ));
+        }
+    };
+    if needs_write && !writable {
+        return Err(RpcError::new(
+            rpc::FORBIDDEN,
+            "Server is read-only (start with --write)",
+        ));
+    }
+
+    rpc::dispatch(storage, config, rpc_method, &Value::Object(params))
+}
+
+fn status_for(error: &RpcError) -> u16 {
+    match error.code {
+        rpc::METHOD_NOT_FOUND | rpc::SESSION_NOT_FOUND => 404,
+        rpc::INVALID_PARAMS => 400,
+        rpc::FORBIDDEN => 403,
+        UNSUPPORTED_MEDIA_TYPE => 415,
+        _ => 500,
+    }
+}
+
+fn header<'a>(request: &'a Request, name: &'static str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.as_str())
+}
+
+/// Whether `host` (with or without a port) is this machine's loopback name
+fn allowed_host(host: Option<&str>) -> bool {
+    let Some(host) = host.map(str::trim) else {
+        return false;
+    };
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if !name.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => host,
+    };
+    ALLOWED_HOSTS
+        .iter()
+        .any(|allowed| name.eq_ignore_ascii_case(allowed))
+}
+
+/// Decode a percent-encoded path segment (a literal `+` stays a `+`)
+fn decode(segment: &str) -> String {
+    form_urlencoded::parse(format!("v={}", segment.replace('+', "%2B")).as_bytes())
+        .next()
+        .map(|(_, v)| v.into_owned())
+        .unwrap_or_default()
+}
+
+fn read_body(request: &mut Request) -> Result<String, RpcError> {
+    let mut body = String::new();
+    request
+        .as_reader()
+        .read_to_string(&mut body)
+        .map_err(|e| RpcError::new(rpc::INVALID_PARAMS, format!("Invalid body: {e}")))?;
+    Ok(body)
+}
+
+fn read_json(request: &mut Request) -> Result<Value, RpcError> {
+    let json = header(request, "Content-Type")
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"));
+    if !json {
+        return Err(RpcError::new(
+            UNSUPPORTED_MEDIA_TYPE,
+            "Send writes as Content-Type: application/json",
+        ));
+    }
+    let body = read_body(request)?;
+    if body.trim().is_empty() {
+        return Ok(Value::Null);
+    }
+    serde_json::from_str(&body)
+        .map_err(|e| RpcError::new(rpc::INVALID_PARAMS, format!("Invalid JSON: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Context, Session};
+
+    /// Serve a workspace in `dir` on a free port, returning its address
+    fn start(dir: &std::path::Path) -> String {
+        let config = Config {
+            workspace_path: dir.to_string_lossy().to_string(),
+            name_generator: "static".to_string(),
+            ..Config::default()
+        };
+        let storage = Storage::new(config.clone(), Context::User);
+        for slug in ["my-plan", "my"] {
+            storage.create_session(&Session::new(slug), None).unwrap();
+        }
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let addr = server.server_addr().to_ip().unwrap().to_string();
+        std::thread::spawn(move || serve(&server, &storage, &config, true));
+        addr
+    }
+
+    /// Status and body of a response, error statuses included
+    fn send(request: ureq::Request, body: Option<&str>) -> (u16, Value) {
+        let result = match body {
+            Some(body) => request.send_string(body),
+            None => request.call(),
+        };
+        let response = match result {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(e) => panic!("{e}"),
+        };
+        (response.status(), response.into_json().unwrap())
+    }
+
+    #[test]
+    fn only_serves_loopback_hosts() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = start(dir.path());
+        let url = format!("http://{addr}/sessions");
+
+        let (status, sessions) = send(ureq::get(&url), None);
+        assert_eq!(status, 200);
+        assert_eq!(sessions.as_array().unwrap().len(), 2);
+        let (status, _) = send(ureq::get(&url).set("Host", "localhost:7777"), None);
+        assert_eq!(status, 200);
+
+        let (status, body) = send(ureq::get(&url).set("Host", "evil.example:7777"), None);
+        assert_eq!(status, 403);
+        assert_eq!(body["error"]["code"], rpc::FORBIDDEN);
+        let (status, _) = send(ureq::get(&url).set("Host", "localhost.evil.example"), None);
+        assert_eq!(status, 403);
+    }
+
+    #[test]
+    fn writes_need_json_and_an_exact_slug() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = start(dir.path());
+        let url = |slug: &str| format!("http://{addr}/sessions/{slug}");
+
+        // A form post, as a cross-origin page could send, is refused
+        let (status, _) = send(
+            ureq::put(&url("my-plan")).set("Content-Type", "text/plain"),
+            Some(r##"{"content":"# Hi"}"##),
+        );
+        assert_eq!(status, 415);
+        let (status, _) = send(
+            ureq::post(&format!("http://{addr}/sessions"))
+                .set("Content-Type", "application/x-www-form-urlencoded"),
+            Some("name=x"),
+        );
+        assert_eq!(status, 415);
+
+        let (status, body) = send(
+            ureq::put(&url("my-plan")).set("Content-Type", "application/json; charset=utf-8"),
+            Some(r##"{"content":"# Hi"}"##),
+        );
+        assert_eq!(status, 200, "{body}");
+        let (_, read) = send(ureq::get(&url("my-plan")), None);
+        assert_eq!(read["content"], "# Hi");
+
+        // "my-p" would prefix-match my-plan; writes must name the session
+        let (status, _) = send(
+            ureq::put(&url("my-p")).set("Content-Type", "application/json"),
+            Some(r#"{"content":"gone"}"#),
+        );
+        assert_eq!(status, 404);
+        let (_, read) = send(ureq::get(&url("my-plan")), None);
+        assert_eq!(read["content"], "# Hi");
+    }
+}
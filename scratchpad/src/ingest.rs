@@ -0,0 +1,239 @@
+//! Agent transcript ingestion (`sp ingest claude`)
+//!
+//! Claude Code keeps one JSONL file per conversation under
+//! `~/.claude/projects/<cwd with every non-alphanumeric character as '-'>/<id>.jsonl`.
+//! The transcript of an agent started in a session is rendered as markdown, keeping the
+//! user and assistant text and a one-line note per tool call.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, bail};
+use serde_json::Value;
+
+pub const TRANSCRIPT_FILE: &str = "transcript.md";
+
+/// Longest tool input shown in a tool-call line
+const TOOL_INPUT_CHARS: usize = 100;
+
+/// `$CLAUDE_CONFIG_DIR/projects`, or `~/.claude/projects`
+pub fn claude_projects_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("CLAUDE_CONFIG_DIR") {
+        return Some(PathBuf::from(dir).join("projects"));
+    }
+    directories::BaseDirs::new().map(|d| d.home_dir().join(".claude").join("projects"))
+}
+
+/// Claude Code's directory name for a working directory
+fn project_dir_name(cwd: &Path) -> String {
+    cwd.to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+/// The transcript `session_id`, wherever it was started
+pub fn find_by_id(projects: &Path, session_id: &str) -> Option<PathBuf> {
+    let file_name = format!("{session_id}.jsonl");
+    fs::read_dir(projects)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path().join(&file_name))
+        .find(|path| path.is_file())
+}
+
+/// Most recent transcript of an agent started in `session_dir` or below it (e.g. in a
+/// worktree)
+pub fn find_latest(projects: &Path, session_dir: &Path) -> Option<PathBuf> {
+    let name = project_dir_name(session_dir);
+    let nested = format!("{name}-");
+    fs::read_dir(projects)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            let dir = e.file_name().to_string_lossy().to_string();
+            dir == name || dir.starts_with(&nested)
+        })
+        .filter_map(|e| fs::read_dir(e.path()).ok())
+        .flatten()
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "jsonl"))
+        .filter_map(|p| Some((fs::metadata(&p).ok()?.modified().ok()?, p)))
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+}
+
+/// Working directory the conversation was started in
+pub fn transcript_cwd(content: &str) -> Option<PathBuf> {
+    content
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .find_map(|entry| entry.get("cwd")?.as_str().map(PathBuf::from))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Speaker {
+    User,
+    Assistant,
+}
+
+/// Markdown rendering of a Claude Code JSONL transcript
+pub fn render(content: &str, source: &Path) -> Result<String> {
+    // A conversation still in progress may end in a partially written line
+    let entries: Vec<Value> = content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    if entries.is_empty() {
+        bail!("{} is not a Claude Code transcript", source.display());
+    }
+
+    let id = source
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let started = entries
+        .iter()
+        .find_map(|e| e.get("timestamp")?.as_str())
+        .unwrap_or("unknown time");
+    let mut out = format!("# Claude Code transcript\n\nConversation `{id}`, started {started}\n");
+
+    let mut last: Option<Speaker> = None;
+    for entry in &entries {
+        if entry.get("isSidechain").and_then(Value::as_bool) == Some(true)
+            || entry.get("isMeta").and_then(Value::as_bool) == Some(true)
+        {
+            continue;
+        }
+        let speaker = match entry.get("type").and_then(Value::as_str) {
+            Some("user") => Speaker::User,
+            Some("assistant") => Speaker::Assistant,
+            _ => continue,
+        };
+        let blocks = message_blocks(entry.pointer("/message/content"));
+        if blocks.is_empty() {
+            continue;
+        }
+        if last != Some(speaker) {
+            out.push_str(match speaker {
+                Speaker::User => "\n## User\n",
+                Speaker::Assistant => "\n## Assistant\n",
+            });
+            last = Some(speaker);
+        }
+        for block in blocks {
+            out.push('\n');
+            out.push_str(&block);
+            out.push('\n');
+        }
+    }
+    Ok(out)
+}
+
+/// Text and tool-call lines of a message; tool results, thinking and harness-injected
+/// text are dropped
+fn message_blocks(content: Option<&Value>) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let push_text = |blocks: &mut Vec<String>, text: &str| {
+        let text = text.trim();
+        let injected = ["<command-", "<local-command-", "<system-reminder>"];
+        if !text.is_empty() && !injected.iter().any(|tag| text.starts_with(tag)) {
+            blocks.push(text.to_string());
+        }
+    };
+    match content {
+        Some(Value::String(text)) => push_text(&mut blocks, text),
+        Some(Value::Array(items)) => {
+            for item in items {
+                match item.get("type").and_then(Value::as_str) {
+                    Some("text") => {
+                        if let Some(text) = item.get("text").and_then(Value::as_str) {
+                            push_text(&mut blocks, text);
+                        }
+                    }
+                    Some("tool_use") => blocks.push(tool_line(item)),
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+    blocks
+}
+
+/// `> Tool: Bash `cargo test``, showing the most telling input field
+fn tool_line(item: &Value) -> String {
+    let name = item.get("name").and_then(Value::as_str).unwrap_or("tool");
+    let input = item.get("input");
+    let detail = [
+        "file_path",
+        "command",
+        "pattern",
+        "url",
+        "path",
+        "description",
+    ]
+    .iter()
+    .find_map(|key| input?.get(key)?.as_str());
+    match detail {
+        Some(detail) => {
+            let detail = detail.lines().next().unwrap_or_default();
+            let mut short: String = detail.chars().take(TOOL_INPUT_CHARS).collect();
+            if detail.chars().count() > TOOL_INPUT_CHARS {
+                short.push('…');
+            }
+            format!("> Tool: {name} `{short}`")
+        }
+        None => format!("> Tool: {name}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_project_dirs_like_claude_code() {
+        assert_eq!(
+            project_dir_name(Path::new("/home/me/scratchpad/quantum.reactor")),
+            "-home-me-scratchpad-quantum-reactor"
+        );
+    }
+
+    #[test]
+    fn renders_conversation_and_finds_latest() {
+        let transcript = [
+            r#"{"type":"queue-operation","operation":"enqueue"}"#,
+            r#"{"type":"user","cwd":"/ws/exp","timestamp":"2024-05-01T10:00:00Z","message":{"role":"user","content":"Why is the queue slow?"}}"#,
+            r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"thinking","thinking":"hmm"},{"type":"text","text":"Let me look."},{"type":"tool_use","name":"Bash","input":{"command":"cargo bench\nmore"}}]}}"#,
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","content":"ok"}]}}"#,
+            r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"Lock contention."}]}}"#,
+            r#"{"type":"user","isMeta":true,"message":{"role":"user","content":"<command-name>/clear</command-name>"}}"#,
+        ]
+        .join("\n");
+
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join(project_dir_name(Path::new("/ws/exp")));
+        fs::create_dir_all(&project).unwrap();
+        let path = project.join("abc-123.jsonl");
+        fs::write(&path, &transcript).unwrap();
+        fs::create_dir_all(dir.path().join("-ws-experiment")).unwrap();
+
+        assert_eq!(
+            find_latest(dir.path(), Path::new("/ws/exp")),
+            Some(path.clone())
+        );
+        assert_eq!(find_by_id(dir.path(), "abc-123"), Some(path.clone()));
+        assert_eq!(find_by_id(dir.path(), "nope"), None);
+        assert_eq!(transcript_cwd(&transcript), Some(PathBuf::from("/ws/exp")));
+
+        assert_eq!(
+            render(&transcript, &path).unwrap(),
+            "# Claude Code transcript\n\n\
+             Conversation `abc-123`, started 2024-05-01T10:00:00Z\n\
+             \n## User\n\nWhy is the queue slow?\n\
+             \n## Assistant\n\nLet me look.\n\n> Tool: Bash `cargo bench`\n\nLock contention.\n"
+        );
+    }
+}
@@ -0,0 +1,91 @@
+//! Creating a project-local scratchpad (`sp init`, and the TUI's workspace switcher)
+
+use std::fs;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+
+/// The project-local workspace directory
+const PROJECT_DIR: &str = ".scratchpad";
+const IGNORE_ENTRY: &str = ".scratchpad/";
+
+/// Where `.scratchpad/` is kept out of git
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ignore {
+    /// `.gitignore`, visible to collaborators
+    Gitignore,
+    /// `.git/info/exclude`, local only
+    Exclude,
+}
+
+/// Create `<root>/.scratchpad/` and ignore it. Returns the workspace directory and what
+/// was done, one line each.
+pub fn init(root: &Path, ignore: Ignore) -> Result<(PathBuf, Vec<String>)> {
+    let mut done = Vec::new();
+    let dir = root.join(PROJECT_DIR);
+    if dir.exists() {
+        done.push(".scratchpad/ already exists".to_string());
+    } else {
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        done.push("Created .scratchpad/".to_string());
+    }
+
+    let (path, label) = match ignore {
+        Ignore::Gitignore => (root.join(".gitignore"), ".gitignore"),
+        Ignore::Exclude => (root.join(".git/info/exclude"), ".git/info/exclude"),
+    };
+    if ignore == Ignore::Exclude && !path.parent().is_some_and(Path::exists) {
+        done.push("Warning: .git/info/ not found, skipping ignore".to_string());
+        return Ok((dir, done));
+    }
+    let existing = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    if existing.lines().any(|l| l.trim() == IGNORE_ENTRY) {
+        done.push(format!(".scratchpad/ already in {label}"));
+    } else {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        // Add newline if file doesn't end with one
+        if !existing.is_empty() && !existing.ends_with('\n') {
+            writeln!(file)?;
+        }
+        writeln!(file, "{IGNORE_ENTRY}")?;
+        done.push(format!("Added .scratchpad/ to {label}"));
+    }
+    Ok((dir, done))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignores_the_scratchpad_once() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join(".git/info")).unwrap();
+        fs::write(dir.path().join(".git/info/exclude"), "*.log").unwrap();
+
+        let (workspace, done) = init(dir.path(), Ignore::Exclude).unwrap();
+        assert!(workspace.is_dir());
+        assert_eq!(done[1], "Added .scratchpad/ to .git/info/exclude");
+        let (_, done) = init(dir.path(), Ignore::Exclude).unwrap();
+        assert_eq!(
+            done,
+            [
+                ".scratchpad/ already exists",
+                ".scratchpad/ already in .git/info/exclude"
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join(".git/info/exclude")).unwrap(),
+            "*.log\n.scratchpad/\n"
+        );
+    }
+}
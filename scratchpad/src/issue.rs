@@ -0,0 +1,194 @@
+//! Issue-tracker references for `sp new --issue`
+//!
+//! `gh#456` (or `#456`) is a GitHub issue in the current repository and is fetched with the
+//! `gh` CLI. Any other key such as `JIRA-123` needs `[issues] command`, which is run with
+//! `{id}` replaced by the key and must print the title on its first line and the
+//! description after it.
+
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context as _, Result, anyhow, bail};
+use serde::Deserialize;
+
+use crate::models::IssuesConfig;
+use crate::names::slugify;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IssueRef {
+    GitHub(u64),
+    /// Tracker key such as `JIRA-123`
+    Key(String),
+}
+
+impl IssueRef {
+    pub fn parse(input: &str) -> Result<Self> {
+        let input = input.trim();
+        if let Some(number) = input
+            .strip_prefix("gh#")
+            .or_else(|| input.strip_prefix('#'))
+        {
+            return number
+                .parse()
+                .map(IssueRef::GitHub)
+                .map_err(|_| anyhow!("Invalid GitHub issue number: {input}"));
+        }
+        if slugify(input).is_none() || input.chars().any(char::is_whitespace) {
+            bail!("Invalid issue reference: {input:?} (expected e.g. JIRA-123 or gh#456)");
+        }
+        Ok(IssueRef::Key(input.to_string()))
+    }
+
+    /// Session slug named after the issue: `gh-456`, `jira-123`
+    pub fn slug(&self) -> String {
+        match self {
+            IssueRef::GitHub(number) => format!("gh-{number}"),
+            IssueRef::Key(key) => slugify(key).unwrap_or_default(),
+        }
+    }
+
+    fn id(&self) -> String {
+        match self {
+            IssueRef::GitHub(number) => number.to_string(),
+            IssueRef::Key(key) => key.clone(),
+        }
+    }
+}
+
+impl fmt::Display for IssueRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IssueRef::GitHub(number) => write!(f, "gh#{number}"),
+            IssueRef::Key(key) => f.write_str(key),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Issue {
+    pub title: String,
+    #[serde(default)]
+    pub body: String,
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+/// Fetch an issue's title and description. Returns None when nothing is configured to
+/// fetch it with (a tracker key without `[issues] command`).
+pub fn fetch(issue: &IssueRef, config: Option<&IssuesConfig>, cwd: &Path) -> Result<Option<Issue>> {
+    let command = config.and_then(|c| c.command.as_deref());
+    let token = config.and_then(|c| c.token.as_deref());
+    match (issue, command) {
+        (_, Some(command)) => run_command(command, issue, token, cwd).map(Some),
+        (IssueRef::GitHub(number), None) => fetch_github(*number, token, cwd).map(Some),
+        (IssueRef::Key(_), None) => Ok(None),
+    }
+}
+
+fn fetch_github(number: u64, token: Option<&str>, cwd: &Path) -> Result<Issue> {
+    let mut cmd = Command::new("gh");
+    cmd.args([
+        "issue",
+        "view",
+        &number.to_string(),
+        "--json",
+        "title,body,url",
+    ])
+    .current_dir(cwd);
+    if let Some(token) = token {
+        cmd.env("GH_TOKEN", token);
+    }
+    let stdout = output(cmd, "gh")?;
+    serde_json::from_str(&stdout).context("Failed to parse `gh issue view` output")
+}
+
+/// Run the configured command; the token, if any, is passed as `SP_ISSUE_TOKEN`
+fn run_command(command: &str, issue: &IssueRef, token: Option<&str>, cwd: &Path) -> Result<Issue> {
+    let id = issue.id();
+    let mut parts = command
+        .split_whitespace()
+        .map(|part| part.replace("{id}", &id));
+    let program = parts.next().context("`[issues] command` is empty")?;
+    let mut cmd = Command::new(&program);
+    cmd.args(parts).current_dir(cwd);
+    if let Some(token) = token {
+        cmd.env("SP_ISSUE_TOKEN", token);
+    }
+    let stdout = output(cmd, &program)?;
+    let (title, body) = stdout.split_once('\n').unwrap_or((&stdout, ""));
+    if title.trim().is_empty() {
+        bail!("{program} printed no issue title for {issue}");
+    }
+    Ok(Issue {
+        title: title.trim().to_string(),
+        body: body.trim().to_string(),
+        url: None,
+    })
+}
+
+fn output(mut cmd: Command, program: &str) -> Result<String> {
+    let output = cmd
+        .output()
+        .with_context(|| format!("Failed to run {program}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("{program} failed: {}", stderr.trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Starting notes for a session created from an issue
+pub fn brief(issue_ref: &IssueRef, issue: Option<&Issue>) -> String {
+    let Some(issue) = issue else {
+        return format!("# {issue_ref}\n");
+    };
+    let mut notes = format!("# {}: {}\n\n", issue_ref, issue.title);
+    if let Some(url) = &issue.url {
+        notes.push_str(&format!("{url}\n\n"));
+    }
+    if !issue.body.is_empty() {
+        notes.push_str(issue.body.trim_end());
+        notes.push('\n');
+    }
+    notes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_references() {
+        assert_eq!(IssueRef::parse("gh#456").unwrap(), IssueRef::GitHub(456));
+        assert_eq!(IssueRef::parse("#7").unwrap(), IssueRef::GitHub(7));
+        assert!(IssueRef::parse("gh#abc").is_err());
+        assert!(IssueRef::parse("not an issue").is_err());
+
+        let jira = IssueRef::parse("JIRA-123").unwrap();
+        assert_eq!(jira, IssueRef::Key("JIRA-123".to_string()));
+        assert_eq!(jira.slug(), "jira-123");
+        assert_eq!(jira.to_string(), "JIRA-123");
+        assert_eq!(IssueRef::GitHub(456).slug(), "gh-456");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn fetches_with_configured_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = IssuesConfig {
+            command: Some("echo {id}".to_string()),
+            token: None,
+        };
+        let issue_ref = IssueRef::parse("JIRA-9").unwrap();
+        let issue = fetch(&issue_ref, Some(&config), dir.path())
+            .unwrap()
+            .unwrap();
+        assert_eq!(issue.title, "JIRA-9");
+        assert_eq!(brief(&issue_ref, Some(&issue)), "# JIRA-9: JIRA-9\n\n");
+
+        // Tracker keys are not fetched without a command
+        assert_eq!(fetch(&issue_ref, None, dir.path()).unwrap(), None);
+        assert_eq!(brief(&issue_ref, None), "# JIRA-9\n");
+    }
+}
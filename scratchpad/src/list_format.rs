@@ -0,0 +1,255 @@
+//! `list_format`: the row layout of `sp list` and the TUI's session list
+//!
+//! A template like `"{slug:25} {tags} {updated:relative}"`: each `{field}` is replaced
+//! by the session's value, and the rest is kept as written (`{{` and `}}` for braces).
+//! Fields are `slug`, `title`, `tags`, `size`, `status`, `updated` and `created`. After
+//! a colon, a number pads or cuts the value to that many columns, and `relative` shows
+//! a date as "2h ago"; both may be given, as in `{updated:relative:8}`.
+
+use anyhow::{Result, anyhow, bail};
+use chrono::{DateTime, Utc};
+
+use crate::models::{Session, Status};
+use crate::remind;
+use crate::storage::format_size;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Slug,
+    Title,
+    Tags,
+    Size,
+    Status,
+    Updated,
+    Created,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "slug" => Self::Slug,
+            "title" => Self::Title,
+            "tags" => Self::Tags,
+            "size" => Self::Size,
+            "status" => Self::Status,
+            "updated" => Self::Updated,
+            "created" => Self::Created,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    Text(String),
+    Field {
+        field: Field,
+        width: Option<usize>,
+        relative: bool,
+    },
+}
+
+/// A parsed `list_format` template
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ListFormat {
+    template: String,
+    pieces: Vec<Piece>,
+}
+
+/// What a row shows of one session
+pub struct Row<'a> {
+    pub session: &'a Session,
+    pub title: Option<&'a str>,
+    pub tags: &'a [String],
+    /// None while it's still being measured
+    pub size: Option<u64>,
+    pub status: Status,
+}
+
+impl ListFormat {
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut spec = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => spec.push(c),
+                            None => bail!("Unclosed '{{{spec}' in list_format"),
+                        }
+                    }
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(parse_field(&spec)?);
+                }
+                '}' => bail!("Unmatched '}}' in list_format (write '}}}}' for a brace)"),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+        Ok(Self {
+            template: template.to_string(),
+            pieces,
+        })
+    }
+
+    /// Whether the template shows `field`, e.g. to skip measuring sizes it doesn't show
+    pub fn uses(&self, field: Field) -> bool {
+        self.pieces
+            .iter()
+            .any(|piece| matches!(piece, Piece::Field { field: f, .. } if *f == field))
+    }
+
+    /// The row as text, each part with the field it shows (None for literal text)
+    pub fn segments(&self, row: &Row, now: DateTime<Utc>) -> Vec<(Option<Field>, String)> {
+        self.pieces
+            .iter()
+            .map(|piece| match piece {
+                Piece::Text(text) => (None, text.clone()),
+                Piece::Field {
+                    field,
+                    width,
+                    relative,
+                } => {
+                    let value = value(row, *field, *relative, now);
+                    (Some(*field), fit(&value, *width))
+                }
+            })
+            .collect()
+    }
+
+    pub fn render(&self, row: &Row, now: DateTime<Utc>) -> String {
+        self.segments(row, now)
+            .into_iter()
+            .map(|(_, text)| text)
+            .collect()
+    }
+}
+
+impl TryFrom<String> for ListFormat {
+    type Error = anyhow::Error;
+
+    fn try_from(template: String) -> Result<Self> {
+        Self::parse(&template)
+    }
+}
+
+impl From<ListFormat> for String {
+    fn from(format: ListFormat) -> Self {
+        format.template
+    }
+}
+
+fn parse_field(spec: &str) -> Result<Piece> {
+    let mut parts = spec.split(':');
+    let name = parts.next().unwrap_or_default().trim();
+    let field = Field::parse(name).ok_or_else(|| {
+        anyhow!(
+            "Unknown list_format field '{{{name}}}' \
+             (use slug, title, tags, size, status, updated or created)"
+        )
+    })?;
+    let (mut width, mut relative) = (None, false);
+    for option in parts.map(str::trim) {
+        if option == "relative" && matches!(field, Field::Updated | Field::Created) {
+            relative = true;
+        } else if let Ok(n) = option.parse() {
+            width = Some(n);
+        } else {
+            bail!("Invalid list_format option '{option}' in '{{{spec}}}'");
+        }
+    }
+    Ok(Piece::Field {
+        field,
+        width,
+        relative,
+    })
+}
+
+fn value(row: &Row, field: Field, relative: bool, now: DateTime<Utc>) -> String {
+    let date = |at: DateTime<Utc>| {
+        if relative {
+            remind::relative(at, now)
+        } else {
+            at.format("%Y-%m-%d %H:%M").to_string()
+        }
+    };
+    match field {
+        Field::Slug => row.session.slug.clone(),
+        Field::Title => row.title.unwrap_or_default().to_string(),
+        Field::Tags => row
+            .tags
+            .iter()
+            .map(|tag| format!("#{tag}"))
+            .collect::<Vec<_>>()
+            .join(" "),
+        Field::Size => row.size.map(format_size).unwrap_or_else(|| "…".to_string()),
+        Field::Status => row.status.to_string(),
+        Field::Updated => date(row.session.updated_at),
+        Field::Created => date(row.session.created_at),
+    }
+}
+
+/// Pad `value` to `width` columns, or cut it to fit with an ellipsis
+fn fit(value: &str, width: Option<usize>) -> String {
+    let Some(width) = width else {
+        return value.to_string();
+    };
+    let len = value.chars().count();
+    if len <= width {
+        format!("{value:<width$}")
+    } else {
+        let mut out: String = value.chars().take(width.saturating_sub(1)).collect();
+        out.push('…');
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn renders_fields_with_widths_and_relative_dates() {
+        let now = Utc::now();
+        let mut session = Session::new("quantum-reactor-redesign");
+        session.updated_at = now - Duration::hours(2);
+        let tags = ["bug".to_string(), "perf".to_string()];
+        let row = Row {
+            session: &session,
+            title: Some("Reactor"),
+            tags: &tags,
+            size: Some(2048),
+            status: Status::Active,
+        };
+
+        let format = ListFormat::parse("{slug:10} {tags} {updated:relative}").unwrap();
+        assert_eq!(format.render(&row, now), "quantum-r… #bug #perf 2h ago");
+        let format = ListFormat::parse("{{{status:8}}} {title} {size}").unwrap();
+        assert_eq!(format.render(&row, now), "{active  } Reactor 2.0K");
+        assert!(format.uses(Field::Size));
+        assert!(!format.uses(Field::Slug));
+
+        assert!(ListFormat::parse("{slug} {owner}").is_err());
+        assert!(ListFormat::parse("{slug:relative}").is_err());
+        assert!(ListFormat::parse("{slug}}").is_err());
+        assert!(ListFormat::parse("{slug").is_err());
+    }
+}
@@ -0,0 +1,30 @@
+//! One-shot prompts to the configured agent CLI (`claude --print`, `codex --quiet`)
+
+use std::process::{Command, Stdio};
+
+use anyhow::{Result, anyhow, bail};
+
+use crate::models::Agent;
+
+/// Run `prompt` through `agent` non-interactively and return its trimmed answer
+pub fn ask(agent: Agent, prompt: &str) -> Result<String> {
+    let args: &[&str] = match agent {
+        Agent::Claude => &["--print", "-p"],
+        Agent::Codex => &["--quiet", "-p"],
+    };
+    let output = Command::new(agent.command())
+        .args(args)
+        .arg(prompt)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| anyhow!("Failed to run {agent}: {e}"))?;
+    if !output.status.success() {
+        bail!("{agent} exited with {}", output.status);
+    }
+    let answer = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if answer.is_empty() {
+        bail!("{agent} returned an empty answer");
+    }
+    Ok(answer)
+}
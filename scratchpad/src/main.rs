@@ -1,27 +1,70 @@
+mod backup;
+mod bulk;
+mod calendar;
+mod capture;
 mod cli;
+mod clipboard;
 mod config;
+mod crypto;
+mod dedupe;
+mod digest;
+mod git;
 mod hook;
+mod http;
+mod ingest;
+mod init;
+mod issue;
+mod list_format;
+mod llm;
 mod markdown;
+mod marks;
 mod models;
 mod names;
+mod naming;
+mod notify;
 mod open;
+mod perms;
+mod pr;
+mod remind;
+mod replace;
+mod review;
+mod rpc;
+mod scheme;
+mod search;
+mod semantic;
+mod snapshot;
+mod spignore;
 mod storage;
+mod summary;
+mod sync;
+mod tags;
+mod templates;
+mod todos;
 mod tui;
+mod update;
+mod usage;
+mod vault;
+mod viewed;
 
 use std::fs;
 use std::io::{self, IsTerminal, Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process;
 
 use anyhow::{Context as _, Result};
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
 
-use cli::{Cli, Command};
+use cli::{
+    BackupAction, BulkAction, Cli, Command, ConfigAction, IndexAction, SnapshotAction, SyncAction,
+};
 use config::load_config;
-use models::{Context, Session};
+use list_format::Field;
+use models::{Config, Context, Relation, Session, SyncBackend, SyncConfig};
 use names::{generate_session_name, slugify, slugify_or_generate};
-use open::{open_folder, open_path_blocking, open_with_editor};
-use storage::{Storage, available_contexts, build_file_tree, detect_context};
+use open::{open_folder, open_path_blocking, open_with_editor, open_with_editor_and_project};
+use storage::{
+    Storage, TitleCache, available_contexts, build_file_tree, detect_context, dir_size, project_at,
+};
 
 fn pick_session_fzf(storage: &Storage) -> Result<Session> {
     let sessions = storage.list_sessions()?;
@@ -87,60 +130,532 @@ fn resolve_session(storage: &Storage, name: Option<String>) -> Result<Session> {
     }
 }
 
+/// A file of the session, which must exist
+fn resolve_session_file(storage: &Storage, slug: &str, file: &str) -> Result<PathBuf> {
+    let path = storage.session_file(slug, file)?;
+    if !path.is_file() {
+        eprintln!("File not found in {slug}: {file}");
+        process::exit(1);
+    }
+    Ok(path)
+}
+
+/// Open an `sp://` link in the TUI, or with no link register `sp` as their handler
+fn handle_url(mut config: Config, url: Option<String>) -> Result<()> {
+    let Some(url) = url else {
+        let exe = std::env::current_exe().context("Failed to locate the sp binary")?;
+        for line in scheme::register(&exe)? {
+            println!("{line}");
+        }
+        return Ok(());
+    };
+    let link = scheme::parse(&url)?;
+    let context = match &link.project {
+        Some(project) => match detect_context(project, &config) {
+            Context::User => {
+                eprintln!("No .scratchpad/ found in {}", project.display());
+                process::exit(1);
+            }
+            context => context,
+        },
+        None => Context::User,
+    };
+    if let Some(name) = &link.workspace {
+        let Some(path) = config.workspaces.get(name) else {
+            eprintln!("No workspace named {name} under [workspaces]");
+            process::exit(1);
+        };
+        config.workspace_path = sync::expand_home(path);
+    }
+    let storage = Storage::new(config.clone(), context.clone());
+    let session = resolve_session(&storage, Some(link.session))?;
+    let file = link
+        .file
+        .map(|file| resolve_session_file(&storage, &session.slug, &file))
+        .transpose()?;
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let contexts = available_contexts(link.project.as_deref().unwrap_or(&cwd), &config);
+    tui::run(
+        config,
+        context,
+        contexts,
+        Some(&session.slug),
+        file.as_deref(),
+    )
+}
+
+/// Slug of the current branch's session, created on first use
+fn open_branch_session(storage: &Storage, config: &Config, context: &Context) -> Result<String> {
+    if !config.branch_sessions {
+        eprintln!("Branch sessions are off. Set `branch_sessions = true` in the config.");
+        process::exit(1);
+    }
+    if matches!(context, Context::User) {
+        eprintln!("Branch sessions need a project (run `sp init` in a git repository).");
+        process::exit(1);
+    }
+    let Some(slug) = storage.branch_session() else {
+        eprintln!("Not on a git branch");
+        process::exit(1);
+    };
+    if config.read_only && !storage.session_dir(&slug).is_dir() {
+        eprintln!("Session not found: {slug} (read-only mode, not creating it)");
+        process::exit(1);
+    }
+    if storage.ensure_session(&slug)? {
+        send_notification(
+            config,
+            context,
+            notify::Event::SessionCreated { slug: &slug },
+        );
+        eprintln!("Created session: {slug}");
+    }
+    Ok(slug)
+}
+
+/// Open a pull request from the session's worktree (or the current directory) with `gh`
+fn create_pull_request(
+    storage: &Storage,
+    session: &Session,
+    draft: &pr::PrDraft,
+    base: Option<&str>,
+    cwd: &Path,
+) -> Result<()> {
+    let meta = storage.load_meta(&session.slug)?;
+    let dir = meta
+        .worktrees
+        .first()
+        .map(|wt| storage.session_dir(&session.slug).join(&wt.path))
+        .filter(|dir| dir.is_dir())
+        .unwrap_or_else(|| cwd.to_path_buf());
+
+    let mut cmd = process::Command::new("gh");
+    cmd.args(["pr", "create", "--title", &draft.title, "--body-file", "-"])
+        .current_dir(&dir)
+        .stdin(process::Stdio::piped());
+    if let Some(base) = base {
+        // gh wants the branch name on the remote, not `origin/main`
+        cmd.args(["--base", base.strip_prefix("origin/").unwrap_or(base)]);
+    }
+    let mut child = cmd
+        .spawn()
+        .context("Failed to run gh (is the GitHub CLI installed?)")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(draft.body.as_bytes())?;
+    }
+    if !child.wait()?.success() {
+        eprintln!("gh pr create failed");
+        process::exit(1);
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_tag(
+    storage: &Storage,
+    config: &Config,
+    name: Option<String>,
+    new_tags: Vec<String>,
+    remove: bool,
+    auto: bool,
+    all: bool,
+    yes: bool,
+) -> Result<()> {
+    if !auto {
+        let session = resolve_session(storage, name)?;
+        let mut meta = storage.load_meta(&session.slug)?;
+        if remove {
+            let removed: Vec<_> = new_tags.iter().filter_map(|t| tags::normalize(t)).collect();
+            meta.tags.retain(|t| !removed.contains(t));
+            storage.save_meta(&session.slug, &meta)?;
+        } else if tags::add(&mut meta.tags, &new_tags) {
+            storage.save_meta(&session.slug, &meta)?;
+        }
+        if meta.tags.is_empty() {
+            println!("No tags: {}", session.slug);
+        } else {
+            println!("{}: {}", session.slug, meta.tags.join(", "));
+        }
+        return Ok(());
+    }
+
+    let sessions = if all {
+        storage.list_sessions()?
+    } else {
+        vec![resolve_session(storage, name)?]
+    };
+    for session in sessions {
+        let suggested = match tags::suggest(
+            storage,
+            &session.slug,
+            &config.tag_vocabulary,
+            config.default_agent,
+        ) {
+            Ok(suggested) => suggested,
+            Err(e) if all => {
+                eprintln!("{}: {e:#}", session.slug);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let mut meta = storage.load_meta(&session.slug)?;
+        let mut updated = meta.tags.clone();
+        if suggested.is_empty() || !tags::add(&mut updated, &suggested) {
+            println!("{}: no new tags", session.slug);
+            continue;
+        }
+        let question = format!("{}: tag with {}?", session.slug, suggested.join(", "));
+        if yes || confirm(&question)? {
+            meta.tags = updated;
+            storage.save_meta(&session.slug, &meta)?;
+            println!("{}: {}", session.slug, meta.tags.join(", "));
+        }
+    }
+    Ok(())
+}
+
+fn handle_ingest_claude(
+    storage: &Storage,
+    name: Option<String>,
+    session_id: Option<String>,
+) -> Result<()> {
+    let Some(projects) = ingest::claude_projects_dir().filter(|dir| dir.is_dir()) else {
+        eprintln!("No Claude Code transcripts found (~/.claude/projects does not exist).");
+        process::exit(1);
+    };
+    let name = name.or_else(|| std::env::var("SP_SESSION").ok());
+
+    let (session, transcript) = match (name, session_id) {
+        (name, Some(id)) => {
+            let Some(transcript) = ingest::find_by_id(&projects, &id) else {
+                eprintln!("Conversation not found: {id}");
+                process::exit(1);
+            };
+            let session = match name {
+                Some(name) => resolve_session(storage, Some(name))?,
+                None => {
+                    // The session the conversation was started in (or below)
+                    let content = fs::read_to_string(&transcript)?;
+                    let cwd = ingest::transcript_cwd(&content);
+                    let found = storage.list_sessions()?.into_iter().find(|s| {
+                        cwd.as_ref()
+                            .is_some_and(|cwd| cwd.starts_with(storage.session_dir(&s.slug)))
+                    });
+                    match found {
+                        Some(session) => session,
+                        None => {
+                            eprintln!(
+                                "Conversation {id} was not started in a session here; name the session to store it in."
+                            );
+                            process::exit(1);
+                        }
+                    }
+                }
+            };
+            (session, transcript)
+        }
+        (name, None) => {
+            let session = resolve_session(storage, name)?;
+            let dir = storage.session_dir(&session.slug);
+            let Some(transcript) = ingest::find_latest(&projects, &dir) else {
+                eprintln!(
+                    "No Claude Code conversation was started in {}",
+                    dir.display()
+                );
+                process::exit(1);
+            };
+            (session, transcript)
+        }
+    };
+
+    let content = fs::read_to_string(&transcript)
+        .with_context(|| format!("Failed to read {}", transcript.display()))?;
+    let markdown = ingest::render(&content, &transcript)?;
+    storage.write_session_file(&session.slug, ingest::TRANSCRIPT_FILE, &markdown)?;
+    println!(
+        "Saved {} to {}/{}",
+        transcript.file_stem().unwrap_or_default().to_string_lossy(),
+        session.slug,
+        ingest::TRANSCRIPT_FILE
+    );
+    Ok(())
+}
+
+/// Ask a yes/no question on stderr; anything but `y` is no
+fn confirm(question: &str) -> Result<bool> {
+    eprint!("{question} [y/N]: ");
+    io::stderr().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim().eq_ignore_ascii_case("y"))
+}
+
+fn handle_bulk(storage: &Storage, action: BulkAction) -> Result<()> {
+    let (red, green, reset) = if io::stdout().is_terminal() {
+        ("\x1b[31m", "\x1b[32m", "\x1b[0m")
+    } else {
+        ("", "", "")
+    };
+    match action {
+        BulkAction::Rename {
+            pattern,
+            replace,
+            dry_run,
+            yes,
+        } => {
+            let renames = bulk::plan_renames(storage, &pattern, &replace)?;
+            if renames.is_empty() {
+                println!("No sessions to rename.");
+                return Ok(());
+            }
+            for rename in &renames {
+                println!("{red}- {}{reset}", rename.from);
+                println!("{green}+ {}{reset}", rename.to);
+            }
+            let question = format!("Rename {} sessions?", renames.len());
+            if dry_run || !(yes || confirm(&question)?) {
+                return Ok(());
+            }
+            bulk::apply_renames(storage, &renames)?;
+            eprintln!("Renamed {} sessions", renames.len());
+        }
+        BulkAction::Tag {
+            pattern,
+            add,
+            remove,
+            dry_run,
+            yes,
+        } => {
+            let retags = bulk::plan_tags(storage, &pattern, &add, &remove)?;
+            if retags.is_empty() {
+                println!("No sessions to retag.");
+                return Ok(());
+            }
+            let show = |tags: &[String]| {
+                if tags.is_empty() {
+                    "(none)".to_string()
+                } else {
+                    tags.join(", ")
+                }
+            };
+            for retag in &retags {
+                println!("{}", retag.slug);
+                println!("  {red}- {}{reset}", show(&retag.before));
+                println!("  {green}+ {}{reset}", show(&retag.after));
+            }
+            let question = format!("Retag {} sessions?", retags.len());
+            if dry_run || !(yes || confirm(&question)?) {
+                return Ok(());
+            }
+            bulk::apply_tags(storage, &retags)?;
+            eprintln!("Retagged {} sessions", retags.len());
+        }
+    }
+    Ok(())
+}
+
+/// Cut `text` to `max` characters, marking the cut with `…`
+fn truncate_chars(text: &str, max: usize) -> String {
+    let mut out: String = text.chars().take(max).collect();
+    if text.chars().count() > max {
+        out.push('…');
+    }
+    out
+}
+
+/// Fire a webhook for a session event, warning (not failing) if delivery fails
+fn send_notification(config: &Config, context: &Context, event: notify::Event) {
+    if let Err(e) = notify::send(config, context, &event) {
+        eprintln!("Warning: {e:#}");
+    }
+}
+
 fn main() -> Result<()> {
-    let cli = Cli::parse();
-    let config = load_config()?;
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    // Cheap commands skip config loading and workspace setup entirely. Hooks run on
+    // every agent tool call, so they must stay fast.
+    let command = match cli.command {
+        Some(Command::Hook { name }) => return hook::handle(&name),
+        Some(Command::SelfUpdate { version, check }) => return update::run(version, check),
+        Some(Command::Init { gitignore, exclude }) => return handle_init(gitignore, exclude),
+        Some(Command::Config {
+            action: ConfigAction::Path,
+        }) => {
+            print!("{}", config::config_path().display());
+            return Ok(());
+        }
+        other => other,
+    };
+
+    let mut config = load_config()?;
+    usage::record_command(&config, matches.subcommand_name().unwrap_or("tui"));
+    if let Some(Command::Usage { weeks }) = command {
+        return usage::show(weeks);
+    }
+    if let Some(Command::Config { action }) = command {
+        return config::handle_config(action, &config);
+    }
+    if let Some(Command::Sync {
+        action: Some(SyncAction::Login { url, code }),
+        ..
+    }) = command
+    {
+        return handle_sync_login(&config, url, code);
+    }
+    config.read_only |= cli.read_only;
+    if config.private_files {
+        perms::restrict_umask();
+    }
+    if config.read_only && command.as_ref().is_some_and(Command::is_mutating) {
+        eprintln!("Read-only mode: this command would change the workspace.");
+        process::exit(1);
+    }
+
+    if let Some(Command::Url { url, .. }) = command {
+        return handle_url(config, url);
+    }
 
     // Determine context based on flags or auto-detection
     let cwd = std::env::current_dir().unwrap_or_default();
     let context = if cli.user {
         Context::User
-    } else if cli.project {
+    } else if let Some(dir) = &cli.project {
         // Find or error if no project context
         let contexts = available_contexts(&cwd, &config);
-        contexts
-            .into_iter()
-            .find(|c| matches!(c, Context::Project(_)))
-            .unwrap_or_else(|| {
-                eprintln!("No .scratchpad/ found in current directory or parents.");
-                eprintln!("Run 'sp init' to create one.");
-                process::exit(1);
-            })
+        let found = match dir {
+            Some(dir) => project_at(&contexts, &cwd.join(dir)),
+            None => contexts
+                .iter()
+                .find(|c| matches!(c, Context::Project(_)))
+                .cloned(),
+        };
+        found.unwrap_or_else(|| {
+            let roots: Vec<&Path> = contexts
+                .iter()
+                .filter_map(|c| match c {
+                    Context::Project(pad) => pad.parent(),
+                    Context::User => None,
+                })
+                .collect();
+            match dir {
+                Some(dir) if !roots.is_empty() => {
+                    eprintln!("{} isn't one of the projects here:", dir.display());
+                    for root in roots {
+                        eprintln!("  {}", root.display());
+                    }
+                }
+                _ => {
+                    eprintln!("No .scratchpad/ found in current directory or parents.");
+                    eprintln!("Run 'sp init' to create one.");
+                }
+            }
+            process::exit(1);
+        })
     } else {
         detect_context(&cwd, &config)
     };
 
     let storage = Storage::new(config.clone(), context.clone());
-    storage.ensure_workspace()?;
+    if !config.read_only {
+        storage.ensure_workspace()?;
+    }
+
+    if !matches!(command, Some(Command::Backup { .. })) {
+        backup::spawn_if_due(config.backup.as_ref(), &context);
+    }
 
-    match cli.command {
+    match command {
         None => {
             let contexts = available_contexts(&cwd, &config);
-            tui::run(config, context, contexts, None)?;
+            tui::run(config, context, contexts, None, None)?;
         }
-        Some(Command::New { name }) => {
+        Some(Command::New {
+            name,
+            encrypted,
+            issue,
+            no_verify,
+        }) => {
+            // Fail before creating anything when no key is configured
+            if encrypted {
+                storage.cipher()?;
+            }
+            let issue = issue.as_deref().map(issue::IssueRef::parse).transpose()?;
             let existing = storage.existing_slugs()?;
-            let slug = match name {
-                Some(n) => slugify_or_generate(&n, &existing, &config),
-                None => generate_session_name(&existing, &config),
+            let slug = match (name, &issue) {
+                (Some(n), _) => slugify_or_generate(&n, &existing, &config),
+                (None, Some(issue)) => issue.slug(),
+                (None, None) => generate_session_name(&existing, &config),
+            };
+            if !no_verify && let Err(e) = naming::check(&config, &slug) {
+                anyhow::bail!("{e:#} (use --no-verify to skip)");
+            }
+            let brief = match &issue {
+                Some(issue_ref) => {
+                    let fetched = issue::fetch(issue_ref, config.issues.as_ref(), &cwd)
+                        .unwrap_or_else(|e| {
+                            eprintln!("Warning: could not fetch {issue_ref}: {e:#}");
+                            None
+                        });
+                    Some(issue::brief(issue_ref, fetched.as_ref()))
+                }
+                None => None,
             };
             let session = Session::new(&slug);
-            storage.create_session(&session, None)?;
+            storage.create_session(&session, brief.as_deref())?;
+            if let Some(issue_ref) = &issue {
+                let mut meta = storage.load_meta(&slug)?;
+                meta.issue = Some(issue_ref.to_string());
+                storage.save_meta(&slug, &meta)?;
+            }
+            if encrypted {
+                storage.encrypt_session(&slug)?;
+            }
+            send_notification(
+                &config,
+                &context,
+                notify::Event::SessionCreated { slug: &slug },
+            );
             println!("Created session: {slug}");
             println!("  {}", storage.session_dir(&slug).display());
         }
         Some(Command::Quick { text }) => {
             let existing = storage.existing_slugs()?;
             let slug = generate_session_name(&existing, &config);
+            naming::check(&config, &slug)?;
             let session = Session::new(&slug);
             storage.create_session(&session, Some(&text))?;
+            send_notification(
+                &config,
+                &context,
+                notify::Event::SessionCreated { slug: &slug },
+            );
             println!("Created quick session: {slug}");
             println!("  {}", storage.session_dir(&slug).display());
         }
-        Some(Command::Open { name }) => {
+        Some(Command::Open { branch: true, .. }) => {
+            let slug = open_branch_session(&storage, &config, &context)?;
+            let contexts = available_contexts(&cwd, &config);
+            tui::run(config, context, contexts, Some(&slug), None)?;
+        }
+        Some(Command::Open { name, .. }) => {
+            let (name, file) = match name.as_deref().and_then(|n| n.split_once('/')) {
+                Some((session, file)) => (Some(session.to_string()), Some(file.to_string())),
+                None => (name, None),
+            };
             let session = resolve_session(&storage, name)?;
+            let file = file
+                .map(|file| resolve_session_file(&storage, &session.slug, &file))
+                .transpose()?;
             let contexts = available_contexts(&cwd, &config);
-            tui::run(config, context, contexts, Some(&session.slug))?;
+            tui::run(
+                config,
+                context,
+                contexts,
+                Some(&session.slug),
+                file.as_deref(),
+            )?;
         }
         Some(Command::Run { name, agent }) => {
             let session = resolve_session(&storage, name)?;
@@ -152,12 +667,33 @@ fn main() -> Result<()> {
             };
             println!("Running {agent} in session: {}", session.display_title());
 
+            let started_at = chrono::Utc::now();
+            let started = std::time::Instant::now();
             let status = process::Command::new(agent.command())
                 .current_dir(&session_dir)
                 .env("SP_SESSION", &session.slug)
                 .env("SP_CONTEXT", context_label)
                 .env("SP_WORKSPACE", storage.workspace_path())
                 .status()?;
+            let run = models::RunRecord {
+                agent,
+                started_at,
+                duration_secs: started.elapsed().as_secs(),
+                exit_code: status.code(),
+            };
+            if let Err(e) = storage.record_run(&session.slug, run) {
+                eprintln!("Warning: failed to record run: {e:#}");
+            }
+            send_notification(
+                &config,
+                &context,
+                notify::Event::AgentFinished {
+                    slug: &session.slug,
+                    agent,
+                    exit_code: status.code(),
+                    duration: started.elapsed(),
+                },
+            );
 
             if !status.success() {
                 process::exit(status.code().unwrap_or(1));
@@ -167,27 +703,72 @@ fn main() -> Result<()> {
             let session = resolve_session(&storage, name)?;
             let session_dir = storage.session_dir(&session.slug);
             if let Some(entry_point) = storage.find_entry_point(&session.slug) {
-                open_path_blocking(&entry_point, config.viewer.as_deref())?;
+                if crypto::is_encrypted(&entry_point) {
+                    crypto::edit_encrypted(&storage.cipher()?, &entry_point, |plain| {
+                        open_path_blocking(plain, config.viewer.as_deref())
+                    })?;
+                } else {
+                    open_path_blocking(&entry_point, config.viewer.as_deref())?;
+                }
             } else {
                 open_folder(&session_dir)?;
             }
         }
-        Some(Command::Edit { name }) => {
+        Some(Command::Edit { index: true, .. }) => {
+            open_with_editor(&storage.ensure_dashboard()?, config.editor.as_deref())?;
+        }
+        Some(Command::Edit { name, project, .. }) => {
             let session = resolve_session(&storage, name)?;
             let session_dir = storage.session_dir(&session.slug);
+            let root = project.then(|| storage.project_root(&session.slug));
+            if root.as_ref().is_some_and(Option::is_none) {
+                eprintln!(
+                    "Session '{}' has no project: it has no worktree and isn't in a project \
+                     scratchpad",
+                    session.slug
+                );
+                process::exit(1);
+            }
+            let root = root.flatten();
+            let edit = |path: &Path| match &root {
+                Some(root) => open_with_editor_and_project(path, root, config.editor.as_deref()),
+                None => open_with_editor(path, config.editor.as_deref()),
+            };
             if let Some(entry_point) = storage.find_entry_point(&session.slug) {
-                open_with_editor(&entry_point, config.editor.as_deref())?;
+                if crypto::is_encrypted(&entry_point) {
+                    crypto::edit_encrypted(&storage.cipher()?, &entry_point, edit)?;
+                } else {
+                    edit(&entry_point)?;
+                }
+            } else if storage.load_meta(&session.slug)?.encrypted {
+                storage.write_notes(&session.slug, "")?;
+                let notes_path = crypto::encrypted_path(&session_dir.join("notes.md"));
+                crypto::edit_encrypted(&storage.cipher()?, &notes_path, edit)?;
             } else {
                 let notes_path = session_dir.join("notes.md");
                 if !notes_path.exists() {
                     fs::write(&notes_path, "")?;
                 }
-                open_with_editor(&notes_path, config.editor.as_deref())?;
+                edit(&notes_path)?;
             }
         }
-        Some(Command::List) => {
-            let sessions = storage.list_sessions()?;
-            if sessions.is_empty() {
+        Some(Command::List { json, status, due }) => {
+            let now = chrono::Utc::now();
+            let mut sessions = storage.list_sessions()?;
+            if status.is_some() || due {
+                sessions.retain(|s| {
+                    let meta = storage.load_meta(&s.slug).unwrap_or_default();
+                    status.is_none_or(|status| meta.status == status)
+                        && (!due || meta.reminders.iter().any(|r| r.is_due(now)))
+                });
+            }
+            if json {
+                let values: Vec<_> = sessions
+                    .iter()
+                    .map(|s| rpc::session_json(&storage, s))
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&values)?);
+            } else if sessions.is_empty() {
                 eprintln!("No sessions found.");
             } else if io::stdout().is_terminal() {
                 let context_label = match &context {
@@ -195,19 +776,50 @@ fn main() -> Result<()> {
                     Context::Project(_) => format!("Project: {}", context.display_name()),
                 };
                 println!("[{context_label}]");
-                println!("{:<25}  UPDATED", "NAME");
-                println!("{}", "-".repeat(50));
+                if config.list_format.is_none() {
+                    println!("{:<25}  UPDATED", "NAME");
+                    println!("{}", "-".repeat(50));
+                }
+                let mut titles = TitleCache::default();
                 for session in sessions {
-                    let name = if session.slug.len() > 25 {
-                        format!("{}...", &session.slug[..22])
+                    let meta = storage.load_meta(&session.slug).unwrap_or_default();
+                    let reminder = meta.reminders.iter().find(|r| r.is_due(now));
+                    let summary = match (reminder, meta.summary) {
+                        (Some(reminder), _) => format!(
+                            "  ⏰ {} ({})",
+                            truncate_chars(&reminder.message, 60),
+                            remind::relative(reminder.at, now)
+                        ),
+                        (None, Some(summary)) => format!("  {}", truncate_chars(&summary, 60)),
+                        (None, None) => String::new(),
+                    };
+                    let Some(format) = &config.list_format else {
+                        let name = if session.slug.len() > 25 {
+                            format!("{}...", &session.slug[..22])
+                        } else {
+                            session.slug.clone()
+                        };
+                        println!(
+                            "{:<25}  {}{summary}",
+                            name,
+                            session.updated_at.format("%Y-%m-%d %H:%M")
+                        );
+                        continue;
+                    };
+                    let dir = storage.session_dir(&session.slug);
+                    let title = if format.uses(Field::Title) {
+                        titles.title_for(&dir)
                     } else {
-                        session.slug.clone()
+                        None
                     };
-                    println!(
-                        "{:<25}  {}",
-                        name,
-                        session.updated_at.format("%Y-%m-%d %H:%M")
-                    );
+                    let row = list_format::Row {
+                        session: &session,
+                        title: title.as_deref(),
+                        tags: &meta.tags,
+                        size: format.uses(Field::Size).then(|| dir_size(&dir)),
+                        status: meta.status,
+                    };
+                    println!("{}{summary}", format.render(&row, now));
                 }
             } else {
                 for session in sessions {
@@ -215,10 +827,11 @@ fn main() -> Result<()> {
                 }
             }
         }
-        Some(Command::Init { gitignore, exclude }) => {
-            handle_init(gitignore, exclude)?;
-        }
-        Some(Command::Rename { current, new_name }) => {
+        Some(Command::Rename {
+            current,
+            new_name,
+            no_verify,
+        }) => {
             let session = resolve_session(&storage, current)?;
             let new_slug = match slugify(&new_name) {
                 Some(s) => s,
@@ -227,9 +840,36 @@ fn main() -> Result<()> {
                     process::exit(1);
                 }
             };
+            if !no_verify && let Err(e) = naming::check(&config, &new_slug) {
+                anyhow::bail!("{e:#} (use --no-verify to skip)");
+            }
             storage.rename_session(&session.slug, &new_slug)?;
             println!("Renamed '{}' to '{new_slug}'", session.slug);
         }
+        Some(Command::Link {
+            name,
+            other,
+            rel,
+            remove,
+        }) => {
+            let session = resolve_session(&storage, Some(name))?;
+            let other = resolve_session(&storage, Some(other))?;
+            if remove {
+                if !storage.unlink_sessions(&session.slug, &other.slug)? {
+                    eprintln!("{} and {} are not linked.", session.slug, other.slug);
+                    process::exit(1);
+                }
+                println!("Unlinked {} and {}", session.slug, other.slug);
+            } else {
+                storage.link_sessions(&session.slug, &other.slug, rel)?;
+                let (a, b) = (&session.slug, &other.slug);
+                match rel {
+                    Relation::Parent => println!("Linked: {b} is the parent of {a}"),
+                    Relation::Child => println!("Linked: {b} is a child of {a}"),
+                    Relation::Related => println!("Linked: {a} and {b} are related"),
+                }
+            }
+        }
         Some(Command::Path { name }) => {
             let session = resolve_session(&storage, name)?;
             print!("{}", storage.session_dir(&session.slug).display());
@@ -239,41 +879,267 @@ fn main() -> Result<()> {
             let session_dir = storage.session_dir(&session.slug);
             open_folder(&session_dir)?;
         }
-        Some(Command::Files { name, flat }) => {
+        Some(Command::Files { name, flat, json }) => {
             let session = resolve_session(&storage, name)?;
             let session_dir = storage.session_dir(&session.slug);
             let entry_point = storage.find_entry_point(&session.slug);
-            let tree = build_file_tree(&session_dir, entry_point.as_deref(), 3);
+            let depth = if json { usize::MAX } else { 3 };
+            let tree = build_file_tree(&session_dir, entry_point.as_deref(), depth);
 
-            if flat || !io::stdout().is_terminal() {
+            if json {
+                let values: Vec<_> = tree
+                    .iter()
+                    .map(|entry| file_entry_json(&session_dir, entry))
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&values)?);
+            } else if flat || !io::stdout().is_terminal() {
                 print_file_tree_flat(&tree);
             } else {
                 println!("{}/", session.slug);
                 print_file_tree_ansi(&tree);
             }
         }
-        Some(Command::Read { name, file }) => {
+        Some(Command::Read { name, file, render }) => {
             let session = resolve_session(&storage, name)?;
             let content = match file {
-                Some(f) => {
-                    let path = storage.session_dir(&session.slug).join(&f);
-                    fs::read_to_string(&path).with_context(|| format!("Failed to read {f}"))?
-                }
+                Some(f) => storage
+                    .read_session_file(&session.slug, &f)
+                    .with_context(|| format!("Failed to read {f}"))?,
                 None => storage.read_notes(&session.slug)?,
             };
-            print!("{content}");
+            if render {
+                let styled = io::stdout().is_terminal();
+                let width = crossterm::terminal::size().map_or(80, |(width, _)| width);
+                print!("{}", markdown::render_ansi(&content, width, styled));
+            } else {
+                print!("{content}");
+            }
+        }
+        Some(Command::Grep {
+            query,
+            session,
+            limit,
+            semantic: true,
+        }) => {
+            let Some(index) = semantic::load(&storage)? else {
+                eprintln!("No embeddings index yet. Run `sp index build` first.");
+                process::exit(1);
+            };
+            let embedder = semantic::Embedder::from_config(config.embeddings.as_ref());
+            let slug = match session {
+                Some(name) => Some(resolve_session(&storage, Some(name))?.slug),
+                None => None,
+            };
+            let mut hits = semantic::search(&index, &embedder, &query, usize::MAX)?;
+            hits.retain(|hit| slug.as_ref().is_none_or(|s| *s == hit.slug));
+            hits.truncate(limit.unwrap_or(10));
+            if hits.is_empty() {
+                process::exit(1);
+            }
+            for hit in hits {
+                println!(
+                    "{:.3}  {}/{}  {}",
+                    hit.score,
+                    hit.slug,
+                    hit.path.display(),
+                    hit.preview
+                );
+            }
+        }
+        Some(Command::Grep {
+            query,
+            session,
+            limit,
+            ..
+        }) => {
+            let opts = search::SearchOptions {
+                max_results: limit.unwrap_or(200),
+                ..search::SearchOptions::default()
+            };
+            let results = match session {
+                Some(name) => {
+                    let session = resolve_session(&storage, Some(name))?;
+                    let dir = storage.session_dir(&session.slug);
+                    search::search_session(&dir, &session.slug, &query, &opts)
+                }
+                None => search::search_workspace(&storage.workspace_path(), &query, &opts),
+            };
+            if results.is_empty() {
+                process::exit(1);
+            }
+            for m in results {
+                println!("{}/{}:{}:{}", m.slug, m.path.display(), m.line, m.text);
+            }
+        }
+        Some(Command::Index { action }) => {
+            let embedder = semantic::Embedder::from_config(config.embeddings.as_ref());
+            match action {
+                IndexAction::Build { rebuild } => {
+                    let stats = semantic::build(&storage, &embedder, rebuild)?;
+                    println!(
+                        "Indexed {} files: {} chunks embedded, {} unchanged",
+                        stats.files, stats.embedded, stats.reused
+                    );
+                }
+                IndexAction::Status => match semantic::load(&storage)? {
+                    Some(index) => {
+                        println!("Backend: {}", index.backend());
+                        println!("Sessions: {}", index.sessions());
+                        println!("Chunks: {}", index.len());
+                        println!("Path: {}", semantic::index_path(&storage).display());
+                    }
+                    None => println!("No embeddings index. Run `sp index build`."),
+                },
+            }
+        }
+        Some(Command::Replace {
+            name,
+            find,
+            replace: replacement,
+            all_sessions,
+            yes,
+        }) => {
+            let re = regex::Regex::new(&find).context("Invalid --find pattern")?;
+            // Encrypted files would have to be decrypted to search, so they're left alone
+            let edits = if all_sessions {
+                let mut edits =
+                    replace::preview_workspace(&storage.workspace_path(), &re, &replacement);
+                edits.retain(|edit| storage.load_meta(&edit.slug).is_ok_and(|m| !m.encrypted));
+                edits
+            } else {
+                let session = resolve_session(&storage, name)?;
+                if storage.load_meta(&session.slug)?.encrypted {
+                    anyhow::bail!("'{}' is encrypted; sp replace can't edit it", session.slug);
+                }
+                let dir = storage.session_dir(&session.slug);
+                replace::preview_session(&dir, &session.slug, &re, &replacement)
+            };
+            if edits.is_empty() {
+                println!("No matches.");
+                return Ok(());
+            }
+
+            let (red, green, reset) = if io::stdout().is_terminal() {
+                ("\x1b[31m", "\x1b[32m", "\x1b[0m")
+            } else {
+                ("", "", "")
+            };
+            let mut count = 0;
+            for edit in &edits {
+                println!("{}/{}", edit.slug, edit.path.display());
+                for line in &edit.lines {
+                    println!("  {:>4} {red}- {}{reset}", line.line, line.before);
+                    println!("       {green}+ {}{reset}", line.after);
+                }
+                count += edit.lines.len();
+            }
+            if !yes {
+                eprint!("Replace {count} lines in {} files? [y/N]: ", edits.len());
+                io::stderr().flush()?;
+                let mut input = String::new();
+                io::stdin().read_line(&mut input)?;
+                if input.trim().to_lowercase() != "y" {
+                    process::exit(0);
+                }
+            }
+            let backup = replace::apply(&storage.workspace_path(), &edits, &re, &replacement)?;
+            eprintln!("Replaced {count} lines in {} files", edits.len());
+            eprintln!("Backup: {}", backup.display());
+        }
+        Some(Command::Bulk { action }) => handle_bulk(&storage, action)?,
+        Some(Command::Todos { name }) => {
+            let items = match name {
+                Some(name) => {
+                    let session = resolve_session(&storage, Some(name))?;
+                    let dir = storage.session_dir(&session.slug);
+                    todos::session_todos(&dir, &session.slug)
+                }
+                None => todos::workspace_todos(&storage.workspace_path()),
+            };
+            if items.is_empty() {
+                println!("No open items.");
+            }
+            let mut current = None;
+            for item in &items {
+                if current != Some(&item.slug) {
+                    if current.is_some() {
+                        println!();
+                    }
+                    println!("{}", item.slug);
+                    current = Some(&item.slug);
+                }
+                println!("  {}:{}  {}", item.path.display(), item.line, item.text);
+            }
+        }
+        Some(Command::Clip { name }) => {
+            let text = clipboard::read_clipboard()?;
+            if text.trim().is_empty() {
+                eprintln!("Clipboard is empty.");
+                process::exit(1);
+            }
+            match name {
+                Some(name) => {
+                    let session = resolve_session(&storage, Some(name))?;
+                    match sync::staging::append_notes(&storage, &config, &session.slug, &text)? {
+                        Some((pending, staged)) => {
+                            eprintln!("{}", sync::staging::notice(&session.slug, pending, &staged))
+                        }
+                        None => println!("Appended clipboard to: {}", session.slug),
+                    }
+                }
+                None => {
+                    let existing = storage.existing_slugs()?;
+                    let slug = generate_session_name(&existing, &config);
+                    naming::check(&config, &slug)?;
+                    storage.create_session(&Session::new(&slug), Some(&text))?;
+                    send_notification(
+                        &config,
+                        &context,
+                        notify::Event::SessionCreated { slug: &slug },
+                    );
+                    println!("Created quick session: {slug}");
+                    println!("  {}", storage.session_dir(&slug).display());
+                }
+            }
         }
         Some(Command::Write { name, file }) => {
             let session = resolve_session(&storage, Some(name))?;
             let mut content = String::new();
             io::stdin().read_to_string(&mut content)?;
-            match file {
-                Some(f) => {
-                    let path = storage.session_dir(&session.slug).join(&f);
-                    fs::write(&path, &content).with_context(|| format!("Failed to write {f}"))?;
-                }
-                None => storage.write_notes(&session.slug, &content)?,
+            if let Some((pending, staged)) =
+                sync::staging::write(&storage, &config, &session.slug, file.as_deref(), &content)?
+            {
+                eprintln!("{}", sync::staging::notice(&session.slug, pending, &staged));
+            }
+        }
+        Some(Command::Capture {
+            name,
+            file,
+            no_timestamps,
+            tee,
+            interval,
+        }) => {
+            let session = resolve_session(&storage, Some(name))?;
+            let file = file.unwrap_or_else(|| "capture.log".to_string());
+            let pending = sync::staging::check(&storage, &config, &session.slug);
+            let target = match pending {
+                Some(_) => sync::staging::reserve(&storage, &session.slug, &file)?,
+                None => file.clone(),
+            };
+            let opts = capture::CaptureOptions {
+                timestamps: !no_timestamps,
+                flush_interval: std::time::Duration::from_secs(interval.max(1)),
+                tee,
             };
+            let (path, lines) =
+                storage.append_session_file(&session.slug, &target, |mut out| {
+                    capture::capture(io::BufReader::new(io::stdin()), &mut out, &opts)
+                })?;
+            eprintln!("Captured {lines} lines to {}", path.display());
+            if let Some(pending) = pending {
+                let staged = sync::staging::record(&storage, &session.slug, &file, &target)?;
+                eprintln!("{}", sync::staging::notice(&session.slug, pending, &staged));
+            }
         }
         Some(Command::Delete { name, yes }) => {
             let session = resolve_session(&storage, Some(name))?;
@@ -287,8 +1153,21 @@ fn main() -> Result<()> {
                 }
             }
             storage.delete_session(&session.slug)?;
+            send_notification(
+                &config,
+                &context,
+                notify::Event::SessionDeleted {
+                    slug: &session.slug,
+                },
+            );
             eprintln!("Deleted: {}", session.slug);
         }
+        Some(Command::Review { days, snooze }) => {
+            handle_review(&storage, &config, &context, days, snooze)?;
+        }
+        Some(Command::Dedupe { threshold }) => {
+            handle_dedupe(&storage, &config, &context, threshold)?;
+        }
         Some(Command::Context) => match &context {
             Context::User => {
                 println!("user\t{}", storage.workspace_path().display());
@@ -297,102 +1176,899 @@ fn main() -> Result<()> {
                 println!("project\t{}", storage.workspace_path().display());
             }
         },
-        Some(Command::Config { action }) => {
-            config::handle_config(action, &config)?;
+        Some(Command::Validate) => handle_validate(&storage, &config)?,
+        Some(Command::Doctor { fix_perms }) => handle_doctor(&storage, &config, fix_perms)?,
+        Some(Command::Worktree { name, branch }) => {
+            let session = resolve_session(&storage, Some(name))?;
+            let Some(repo) = git::repo_root(&cwd) else {
+                eprintln!("Not inside a git repository.");
+                process::exit(1);
+            };
+            let branch = branch.unwrap_or_else(|| format!("sp/{}", session.slug));
+            let dir_name = slugify(&branch).unwrap_or_else(|| "worktree".to_string());
+            let wt_path = storage.session_dir(&session.slug).join(&dir_name);
+            if wt_path.exists() {
+                eprintln!("{} already exists", wt_path.display());
+                process::exit(1);
+            }
+
+            let wt_str = wt_path.to_string_lossy();
+            if git::branch_exists(&repo, &branch) {
+                git::run(&repo, &["worktree", "add", &wt_str, &branch])?;
+            } else {
+                git::run(&repo, &["worktree", "add", "-b", &branch, &wt_str])?;
+            }
+
+            let mut meta = storage.load_meta(&session.slug)?;
+            meta.worktrees.push(models::WorktreeMeta {
+                repo,
+                path: dir_name.into(),
+                branch: branch.clone(),
+            });
+            storage.save_meta(&session.slug, &meta)?;
+            println!("Created worktree on '{branch}'");
+            println!("  {}", wt_path.display());
         }
-        Some(Command::Hook { name }) => {
-            hook::handle(&name)?;
+        Some(Command::Tag {
+            name,
+            tags,
+            remove,
+            auto,
+            all,
+            yes,
+        }) => handle_tag(&storage, &config, name, tags, remove, auto, all, yes)?,
+        Some(Command::Ingest {
+            source: cli::IngestSource::Claude,
+            name,
+            session_id,
+        }) => handle_ingest_claude(&storage, name, session_id)?,
+        Some(Command::Summarize {
+            name,
+            files,
+            meta_only,
+        }) => {
+            let session = resolve_session(&storage, name)?;
+            eprintln!(
+                "Summarizing {} with {}...",
+                session.slug, config.default_agent
+            );
+            let text = summary::generate(&storage, &session.slug, &files, config.default_agent)?;
+            let mut meta = storage.load_meta(&session.slug)?;
+            // The metadata file is never encrypted, so keep encrypted summaries out of it
+            if meta.encrypted {
+                if meta_only {
+                    eprintln!("Encrypted sessions keep their summary in SUMMARY.md only.");
+                    process::exit(1);
+                }
+            } else {
+                meta.summary = summary::short(&text);
+                storage.save_meta(&session.slug, &meta)?;
+            }
+            if !meta_only {
+                storage.write_session_file(&session.slug, summary::SUMMARY_FILE, &(text + "\n"))?;
+                println!("Wrote {}", summary::SUMMARY_FILE);
+            }
+            if let Some(short) = &meta.summary {
+                println!("{short}");
+            }
+        }
+        Some(Command::Digest { since, llm }) => {
+            let now = chrono::Utc::now();
+            let since = digest::parse_since(&since, now)?;
+            let report = digest::build(&storage, since, now)?;
+            if llm {
+                match digest::refine(&report, config.default_agent) {
+                    Ok(summary) => print!("{summary}"),
+                    Err(e) => {
+                        eprintln!("Warning: {e:#}; showing the plain digest");
+                        print!("{report}");
+                    }
+                }
+            } else {
+                print!("{report}");
+            }
         }
-        Some(Command::Sync) => {
-            println!("Sync not yet implemented.");
-            println!("Configure server in {}", config::config_path().display());
+        Some(Command::Pr { name, base, create }) => {
+            let session = resolve_session(&storage, name)?;
+            let template_path = config.pr.as_ref().and_then(|pr| pr.template.as_deref());
+            let template = pr::load_template(template_path)?;
+            let draft = pr::draft(&storage, &session, &template, base.as_deref())?;
+            if create {
+                create_pull_request(&storage, &session, &draft, base.as_deref(), &cwd)?;
+            } else {
+                println!("# {}\n", draft.title);
+                print!("{}", draft.body);
+            }
+        }
+        Some(Command::Due { name, date, clear }) => {
+            let session = resolve_session(&storage, Some(name))?;
+            let mut meta = storage.load_meta(&session.slug)?;
+            if clear {
+                meta.due = None;
+                storage.save_meta(&session.slug, &meta)?;
+                println!("Cleared due date: {}", session.slug);
+            } else if let Some(date) = date {
+                let due = calendar::parse_due(&date, chrono::Local::now().date_naive())?;
+                meta.due = Some(due);
+                storage.save_meta(&session.slug, &meta)?;
+                println!("{} due {due}", session.slug);
+            } else if let Some(due) = meta.due {
+                println!("{due}");
+            } else {
+                println!("No due date: {}", session.slug);
+            }
+        }
+        Some(Command::Remind {
+            name,
+            reminder,
+            snooze,
+            done,
+        }) => {
+            let session = resolve_session(&storage, Some(name))?;
+            let mut meta = storage.load_meta(&session.slug)?;
+            let now = chrono::Utc::now();
+            if !reminder.is_empty() {
+                let reminder = remind::parse(&reminder, now)?;
+                println!(
+                    "Reminder set for {} ({}): {}",
+                    session.slug,
+                    reminder
+                        .at
+                        .with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d %H:%M"),
+                    reminder.message
+                );
+                remind::add(&mut meta, reminder);
+                storage.save_meta(&session.slug, &meta)?;
+            } else if let Some(span) = snooze {
+                let Some(span) = calendar::parse_span(&span) else {
+                    eprintln!("Invalid --snooze: {span} (use e.g. 4h, 1d or 1w)");
+                    process::exit(1);
+                };
+                let count = remind::snooze(&mut meta, span, now);
+                storage.save_meta(&session.slug, &meta)?;
+                println!("Snoozed {count} reminder(s): {}", session.slug);
+            } else if done {
+                let count = remind::complete(&mut meta, now);
+                storage.save_meta(&session.slug, &meta)?;
+                println!("Completed {count} reminder(s): {}", session.slug);
+            } else if meta.reminders.is_empty() {
+                println!("No reminders: {}", session.slug);
+            } else {
+                for reminder in &meta.reminders {
+                    println!(
+                        "{}  {}{}",
+                        reminder
+                            .at
+                            .with_timezone(&chrono::Local)
+                            .format("%Y-%m-%d %H:%M"),
+                        reminder.message,
+                        if reminder.is_due(now) { "  (due)" } else { "" }
+                    );
+                }
+            }
+        }
+        Some(Command::Export {
+            vault,
+            ics,
+            folder,
+            frontmatter,
+        }) => {
+            if ics {
+                print!("{}", calendar::export_ics(&storage)?);
+            } else if let Some(vault) = vault {
+                let summary = vault::export_vault(&storage, &vault, &folder, frontmatter)?;
+                println!(
+                    "Exported {} sessions to {}",
+                    summary.exported,
+                    vault.join(&folder).display()
+                );
+            }
+        }
+        Some(Command::Import {
+            vault,
+            folder,
+            frontmatter,
+        }) => {
+            if let Some(vault) = vault {
+                let summary = vault::import_vault(&storage, &vault, &folder, frontmatter)?;
+                for slug in &summary.imported {
+                    println!("Imported: {slug}");
+                }
+                for slug in &summary.skipped {
+                    eprintln!("Skipped (already exists or invalid name): {slug}");
+                }
+            }
+        }
+        Some(Command::Backup {
+            action: Some(BackupAction::Status),
+            ..
+        }) => handle_backup_status(&config, &context),
+        Some(Command::Backup {
+            scheduled: true, ..
+        }) => {
+            let Some(backup_config) = &config.backup else {
+                eprintln!("No [backup] section in {}", config::config_path().display());
+                process::exit(1);
+            };
+            if let Some(path) =
+                backup::run_scheduled(backup_config, &context, &storage.workspace_path())?
+            {
+                println!("Backed up to {}", path.display());
+            }
+        }
+        Some(Command::Backup { output, .. }) => {
+            let output = match output {
+                Some(path) if path.is_dir() => path.join(backup::default_file_name()),
+                Some(path) => path,
+                None => backup::default_file_name().into(),
+            };
+            let count = backup::create_backup(&storage.workspace_path(), &output)?;
+            println!("Backed up {count} files to {}", output.display());
+        }
+        Some(Command::Snapshot {
+            action: Some(action),
+            ..
+        }) => handle_snapshot_action(&storage, action)?,
+        Some(Command::Snapshot { name, label, .. }) => {
+            let session = resolve_session(&storage, name)?;
+            let snap = snapshot::create(&storage.session_dir(&session.slug), label.as_deref())?;
+            println!(
+                "Saved snapshot {} ({})",
+                snap.name,
+                storage::format_size(snap.size)
+            );
+        }
+        Some(Command::Restore {
+            archive,
+            on_conflict,
+        }) => {
+            let summary = backup::restore_backup(&archive, &storage.workspace_path(), on_conflict)?;
+            for name in &summary.restored {
+                println!("Restored: {name}");
+            }
+            for (name, new_name) in &summary.renamed {
+                println!("Restored: {name} as {new_name}");
+            }
+            for name in &summary.skipped {
+                eprintln!("Skipped (already exists): {name}");
+            }
+        }
+        Some(Command::Serve { stdio, http, write }) => {
+            if stdio {
+                rpc::serve_stdio(&storage, &config)?;
+            } else if let Some(addr) = http {
+                http::serve_http(&storage, &config, &addr, write)?;
+            }
+        }
+        Some(
+            Command::Init { .. }
+            | Command::Config { .. }
+            | Command::Hook { .. }
+            | Command::SelfUpdate { .. }
+            | Command::Usage { .. }
+            | Command::Url { .. }
+            | Command::Sync {
+                action: Some(SyncAction::Login { .. }),
+                ..
+            },
+        ) => {
+            unreachable!("handled before workspace setup")
+        }
+        Some(Command::Sync {
+            action: Some(SyncAction::Status),
+            ..
+        }) => {
+            let status = sync::status::check(&storage, &config)?;
+            let workspace = storage.workspace_path();
+            print!(
+                "{}",
+                sync_status_report(
+                    status.as_ref(),
+                    &sync::conflicts::list(&workspace),
+                    &sync::staging::list(&workspace),
+                )
+            );
+        }
+        Some(Command::Sync {
+            action: None,
+            limit_rate,
+            new_key,
+            peer,
+            folder,
+            watch,
+            init,
+            snapshot,
+            serve,
+        }) => {
+            if serve {
+                if !sync::allowed(config.sync.as_ref(), &context)? {
+                    anyhow::bail!(
+                        "{} is excluded from sync on this machine",
+                        sync::context_label(&context)
+                    );
+                }
+                let device = sync::device::Device::load(config.sync.as_ref())?;
+                sync::peer::serve(&storage, &device)?;
+            } else {
+                handle_sync(
+                    &storage,
+                    &config,
+                    limit_rate,
+                    new_key,
+                    peer.as_deref(),
+                    folder.as_deref(),
+                    watch,
+                    init,
+                    snapshot,
+                )?;
+            }
         }
     }
 
     Ok(())
 }
 
-fn handle_init(gitignore: bool, exclude: bool) -> Result<()> {
-    // 1. Create .scratchpad/ directory
-    let scratchpad_dir = Path::new(".scratchpad");
-    if scratchpad_dir.exists() {
-        println!(".scratchpad/ already exists");
-    } else {
-        fs::create_dir_all(scratchpad_dir)?;
-        println!("Created .scratchpad/");
+fn handle_review(
+    storage: &Storage,
+    config: &Config,
+    context: &Context,
+    days: u32,
+    snooze_days: u32,
+) -> Result<()> {
+    let now = chrono::Utc::now();
+    let today = chrono::Local::now().date_naive();
+    let stale = review::stale_sessions(storage, days, now, today)?;
+    if stale.is_empty() {
+        println!("No sessions untouched for more than {days} days.");
+        return Ok(());
     }
 
-    // 2. Determine ignore method
-    let use_gitignore = if gitignore {
-        true
-    } else if exclude {
-        false
-    } else {
-        // Interactive prompt
-        println!("\nWhere should .scratchpad/ be ignored?");
-        println!("  1) .gitignore (visible to collaborators)");
-        println!("  2) .git/info/exclude (local only)");
-        print!("\nChoice [1/2]: ");
-        io::stdout().flush()?;
+    let total = stale.len();
+    let (mut reviewed, mut archived, mut deleted, mut snoozed) = (0, 0, 0, 0);
+    for (i, session) in stale.iter().enumerate() {
+        let idle = (now - session.updated_at).num_days();
+        let title = storage
+            .find_entry_point(&session.slug)
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|content| storage::first_heading(&content));
+        println!();
+        println!("[{}/{total}] {}  ({idle} days idle)", i + 1, session.slug);
+        if let Some(title) = title {
+            println!("  {title}");
+        }
+        println!("  {}", storage.session_dir(&session.slug).display());
 
+        let choice = loop {
+            eprint!("[k]eep, [a]rchive, [d]elete, [s]nooze {snooze_days}d, [q]uit: ");
+            io::stderr().flush()?;
+            let mut input = String::new();
+            if io::stdin().read_line(&mut input)? == 0 {
+                break "q".to_string();
+            }
+            let input = input.trim().to_lowercase();
+            if matches!(input.as_str(), "" | "k" | "a" | "d" | "s" | "q") {
+                break input;
+            }
+        };
+        match choice.as_str() {
+            "a" => {
+                let dest = storage.archive_session(&session.slug)?;
+                println!("Archived to {}", dest.display());
+                archived += 1;
+            }
+            "d" => {
+                storage.delete_session(&session.slug)?;
+                send_notification(
+                    config,
+                    context,
+                    notify::Event::SessionDeleted {
+                        slug: &session.slug,
+                    },
+                );
+                println!("Deleted: {}", session.slug);
+                deleted += 1;
+            }
+            "s" => {
+                let until = today + chrono::Duration::days(snooze_days.into());
+                review::snooze(storage, &session.slug, until)?;
+                println!("Snoozed until {until}");
+                snoozed += 1;
+            }
+            "q" => break,
+            _ => {}
+        }
+        reviewed += 1;
+    }
+    println!();
+    println!(
+        "Reviewed {reviewed} of {total}: {archived} archived, {deleted} deleted, {snoozed} snoozed"
+    );
+    Ok(())
+}
+
+fn handle_dedupe(
+    storage: &Storage,
+    config: &Config,
+    context: &Context,
+    threshold: f64,
+) -> Result<()> {
+    let groups = dedupe::find_duplicates(storage, threshold.clamp(0.0, 1.0))?;
+    if groups.is_empty() {
+        println!("No duplicate sessions found.");
+        return Ok(());
+    }
+
+    for (i, group) in groups.iter().enumerate() {
+        let kind = if group.identical {
+            "identical".to_string()
+        } else {
+            format!("{:.0}% similar", group.similarity * 100.0)
+        };
+        println!();
+        println!("[{}/{}] {kind}", i + 1, groups.len());
+        for session in &group.sessions {
+            println!(
+                "  {}  (updated {})",
+                session.slug,
+                session.updated_at.format("%Y-%m-%d %H:%M")
+            );
+        }
+        let (keeper, duplicates) = group
+            .sessions
+            .split_first()
+            .expect("groups have 2+ sessions");
+
+        let choice = loop {
+            eprint!(
+                "[m]erge into {}, [t]rash the others, [s]kip, [q]uit: ",
+                keeper.slug
+            );
+            io::stderr().flush()?;
+            let mut input = String::new();
+            if io::stdin().read_line(&mut input)? == 0 {
+                break "q".to_string();
+            }
+            let input = input.trim().to_lowercase();
+            if matches!(input.as_str(), "" | "m" | "t" | "s" | "q") {
+                break input;
+            }
+        };
+        match choice.as_str() {
+            "m" => {
+                for dup in duplicates {
+                    dedupe::merge_into(storage, &keeper.slug, &dup.slug)?;
+                    send_notification(
+                        config,
+                        context,
+                        notify::Event::SessionDeleted { slug: &dup.slug },
+                    );
+                    println!("Merged {} into {}", dup.slug, keeper.slug);
+                }
+            }
+            "t" => {
+                for dup in duplicates {
+                    let dest = storage.archive_session(&dup.slug)?;
+                    println!("Trashed {} (moved to {})", dup.slug, dest.display());
+                }
+            }
+            "q" => break,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_sync(
+    storage: &Storage,
+    config: &Config,
+    limit_rate: Option<u64>,
+    new_key: bool,
+    peer: Option<&str>,
+    folder: Option<&Path>,
+    watch: bool,
+    init: bool,
+    snapshot: bool,
+) -> Result<()> {
+    if new_key {
+        let path = sync::seal::key_path();
+        sync::seal::Sealer::create(&path)?;
+        println!("Created sync key {}", path.display());
+        println!(
+            "Copy it to the same place on every machine syncing with it, and set \
+             encrypt = true in [server]. Without it, synced notes can't be read."
+        );
+        return Ok(());
+    }
+    if !sync::allowed(config.sync.as_ref(), storage.context())? {
+        eprintln!(
+            "Sync is off for {} ([sync] in {})",
+            sync::context_label(storage.context()),
+            config::config_path().display()
+        );
+        process::exit(1);
+    }
+    let encrypt = config.server.as_ref().is_some_and(|s| s.encrypt);
+    let device = sync::device::Device::load(config.sync.as_ref())?;
+    if let Some(peer) = peer {
+        print_sync_report(&sync::peer::run(storage, peer, &device)?, encrypt);
+        return Ok(());
+    }
+    let backend = config.sync.as_ref().and_then(|s| s.backend);
+    if folder.is_none() && backend == Some(SyncBackend::Git) {
+        let Some(remote) = config.sync.as_ref().and_then(SyncConfig::git_remote) else {
+            eprintln!(
+                "backend = \"git\" needs the repository to sync through, as remote = \"...\" \
+                 in [sync] ({})",
+                config::config_path().display()
+            );
+            process::exit(1);
+        };
+        if watch || init || snapshot {
+            eprintln!("--watch, --init and --snapshot need a [server], not the git backend");
+            process::exit(1);
+        }
+        print_sync_report(&sync::git::run(storage, remote, &device)?, false);
+        return Ok(());
+    }
+    let shared = config
+        .sync
+        .as_ref()
+        .and_then(|s| s.folder.as_deref())
+        .filter(|_| backend != Some(SyncBackend::Server));
+    let server = config
+        .server
+        .as_ref()
+        .filter(|_| backend != Some(SyncBackend::Folder));
+    let server = match (folder, server, shared) {
+        (Some(folder), _, _) => {
+            print_sync_report(&sync::folder::run(storage, folder, &device)?, false);
+            return Ok(());
+        }
+        (None, Some(server), _) => server,
+        (None, None, Some(shared)) => {
+            if watch {
+                eprintln!("--watch needs a [server]; it can't watch a shared folder");
+                process::exit(1);
+            }
+            if init || snapshot {
+                eprintln!(
+                    "Snapshots are kept by a sync server; --init and --snapshot need a [server]"
+                );
+                process::exit(1);
+            }
+            let folder = PathBuf::from(sync::expand_home(shared));
+            print_sync_report(&sync::folder::run(storage, &folder, &device)?, false);
+            return Ok(());
+        }
+        (None, None, None) => {
+            eprintln!(
+                "No sync server configured. Add a [server] section to {}, sync through a \
+                 shared folder with --folder, or with another machine using --peer",
+                config::config_path().display()
+            );
+            process::exit(1);
+        }
+    };
+    if watch {
+        println!(
+            "Syncing {} as it changes (Ctrl-C to stop)",
+            storage.workspace_path().display()
+        );
+        return sync::watch::run(storage, server, &device, limit_rate, |report| {
+            let quiet = report.pulled == 0
+                && report.pushed == 0
+                && report.conflicts.is_empty()
+                && report.merged.is_empty()
+                && report.rejected.is_empty()
+                && report.incomplete.is_empty()
+                && report.unreadable == 0;
+            if !quiet {
+                print!("{}  ", chrono::Local::now().format("%H:%M:%S"));
+                print_sync_report(report, encrypt);
+            }
+        });
+    }
+    if init {
+        match sync::snapshot::init(storage, server, &device, limit_rate)? {
+            Some(restored) => {
+                let taken_at = chrono::DateTime::parse_from_rfc3339(&restored.taken_at)
+                    .map(|t| {
+                        t.with_timezone(&chrono::Local)
+                            .format("%Y-%m-%d %H:%M")
+                            .to_string()
+                    })
+                    .unwrap_or(restored.taken_at);
+                print!("From the server's snapshot of {taken_at}: ");
+                print_sync_report(&restored.report, encrypt);
+            }
+            None => println!("The server has no snapshot of this workspace; syncing every change"),
+        }
+    }
+    let report = sync::run(storage, server, &device, limit_rate)?;
+    print_sync_report(&report, encrypt);
+    if snapshot {
+        let files = sync::snapshot::store(storage, server, &device, limit_rate, &report)?;
+        println!("Stored a snapshot of {files} files on the server");
+    }
+    Ok(())
+}
+
+fn handle_sync_login(config: &Config, url: Option<String>, code: Option<String>) -> Result<()> {
+    let prompt = |label: &str| -> Result<String> {
+        print!("{label}: ");
+        io::stdout().flush()?;
         let mut input = String::new();
         io::stdin().read_line(&mut input)?;
-        input.trim() == "1"
+        Ok(input.trim().to_string())
+    };
+    let url = match url.or_else(|| config.server.as_ref().map(|s| s.url.clone())) {
+        Some(url) => url,
+        None => prompt("Sync server URL")?,
+    };
+    let code = match code {
+        Some(code) => code,
+        None => prompt("Login code (from `sp-server tokens code <name>`)")?,
     };
+    if url.is_empty() || code.is_empty() {
+        eprintln!("A server URL and a login code are needed to log in.");
+        process::exit(1);
+    }
 
-    // 3. Write ignore entry
-    let entry = ".scratchpad/";
-    if use_gitignore {
-        let gitignore_path = Path::new(".gitignore");
-        let existing = if gitignore_path.exists() {
-            fs::read_to_string(gitignore_path)?
-        } else {
-            String::new()
-        };
+    let login = sync::login(&url, &code)?;
+    let path = config::save_server_login(&url, &login.token)?;
+    let server = config::load_config()?
+        .server
+        .context("[server] missing after saving the login")?;
+    let name = sync::whoami(&server).context("The server doesn't accept the new token")?;
+    println!(
+        "Logged in to {url} as '{name}'; token saved to {}",
+        path.display()
+    );
+    Ok(())
+}
 
-        if existing.lines().any(|l| l.trim() == entry) {
-            println!(".scratchpad/ already in .gitignore");
-        } else {
-            let mut file = fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(gitignore_path)?;
-            // Add newline if file doesn't end with one
-            if !existing.is_empty() && !existing.ends_with('\n') {
-                writeln!(file)?;
+/// `sp sync status`: what a sync would move, and what's waiting on the user
+fn sync_status_report(
+    status: Option<&sync::status::Status>,
+    conflicts: &[sync::conflicts::Conflict],
+    staged: &[sync::staging::Staged],
+) -> String {
+    let mut out = String::new();
+    let list = |sessions: &std::collections::BTreeSet<String>| {
+        sessions.iter().cloned().collect::<Vec<_>>().join(", ")
+    };
+    match status {
+        None => out.push_str("Sync isn't set up\n"),
+        Some(status) if status.is_synced() => out.push_str("Up to date\n"),
+        Some(status) => {
+            if !status.unpushed.is_empty() {
+                out.push_str(&format!("Unpushed: {}\n", list(&status.unpushed)));
+            }
+            if !status.unapplied.is_empty() {
+                out.push_str(&format!("Unapplied: {}\n", list(&status.unapplied)));
+            }
+            if let Some(error) = &status.remote_error {
+                out.push_str(&format!("Remote not checked: {error}\n"));
             }
-            writeln!(file, "{entry}")?;
-            println!("Added .scratchpad/ to .gitignore");
         }
-    } else {
-        let exclude_path = Path::new(".git/info/exclude");
-        if let Some(parent) = exclude_path.parent() {
-            if parent.exists() {
-                let existing = if exclude_path.exists() {
-                    fs::read_to_string(exclude_path)?
-                } else {
-                    String::new()
-                };
+    }
+    for conflict in conflicts {
+        out.push_str(&format!(
+            "Conflict: {} (the other side is in {})\n",
+            conflict.path, conflict.saved
+        ));
+    }
+    for capture in staged {
+        out.push_str(&format!(
+            "Staged: {} for {}; merge it in once the session is in sync, then delete it\n",
+            capture.staged, capture.path
+        ));
+    }
+    out
+}
 
-                if existing.lines().any(|l| l.trim() == entry) {
-                    println!(".scratchpad/ already in .git/info/exclude");
-                } else {
-                    let mut file = fs::OpenOptions::new()
-                        .create(true)
-                        .append(true)
-                        .open(exclude_path)?;
-                    if !existing.is_empty() && !existing.ends_with('\n') {
-                        writeln!(file)?;
-                    }
-                    writeln!(file, "{entry}")?;
-                    println!("Added .scratchpad/ to .git/info/exclude");
-                }
+fn print_sync_report(report: &sync::Report, encrypt: bool) {
+    println!("Pulled {} changes, pushed {}", report.pulled, report.pushed);
+    for path in &report.merged {
+        println!("Merged: {path} changed on both sides; the changes were combined");
+    }
+    for path in &report.conflicts {
+        println!(
+            "Conflict: {path} changed on both sides; kept yours, theirs (or a marked-up \
+             merge) saved alongside"
+        );
+    }
+    for (path, reason) in &report.rejected {
+        println!("Rejected: {path} ({reason})");
+    }
+    for path in &report.incomplete {
+        println!("Not written: {path} (some of its content never arrived)");
+    }
+    if report.unreadable > 0 {
+        println!(
+            "Skipped {} encrypted changes: {}",
+            report.unreadable,
+            if encrypt {
+                "they were sealed with a different sync key"
             } else {
-                println!("Warning: .git/info/ not found, skipping ignore");
+                "set encrypt = true in [server] and copy sync.key from another machine"
+            }
+        );
+    }
+    if !report.too_large.is_empty() {
+        println!(
+            "Skipped {} files over [sync] max_file_size: {}",
+            report.too_large.len(),
+            report.too_large.join(", ")
+        );
+    }
+}
+
+fn handle_backup_status(config: &Config, context: &Context) {
+    let Some(backup_config) = &config.backup else {
+        println!("Scheduled backups are off.");
+        println!(
+            "Add a [backup] section to {}",
+            config::config_path().display()
+        );
+        return;
+    };
+    let dir = backup::backup_dir(backup_config);
+    println!("Interval:  {}", backup_config.interval);
+    println!("Keep:      {}", backup_config.keep);
+    println!("Directory: {}", dir.display());
+    if let Err(e) = backup::parse_interval(&backup_config.interval) {
+        eprintln!("Warning: {e}");
+    }
+
+    let backups = backup::scheduled_backups(&dir, &backup::schedule_prefix(context));
+    match backups.first() {
+        Some((path, time)) => {
+            println!(
+                "Last:      {} ({})",
+                time.format("%Y-%m-%d %H:%M"),
+                path.display()
+            );
+            println!("Stored:    {}", backups.len());
+        }
+        None => println!("Last:      never"),
+    }
+}
+
+fn handle_snapshot_action(storage: &Storage, action: SnapshotAction) -> Result<()> {
+    match action {
+        SnapshotAction::List { name } => {
+            let session = resolve_session(storage, Some(name))?;
+            let snapshots = snapshot::list(&storage.session_dir(&session.slug));
+            if snapshots.is_empty() {
+                eprintln!("No snapshots of {}.", session.slug);
+            }
+            for snap in snapshots {
+                println!(
+                    "{}  {:<24}  {}",
+                    snap.created.format("%Y-%m-%d %H:%M:%S"),
+                    snap.label.as_deref().unwrap_or("-"),
+                    storage::format_size(snap.size)
+                );
             }
         }
+        SnapshotAction::Diff {
+            name,
+            snapshot: old,
+            other,
+        } => {
+            let session = resolve_session(storage, Some(name))?;
+            let dir = storage.session_dir(&session.slug);
+            let old = snapshot::find(&dir, &old)?;
+            let new = other.map(|o| snapshot::find(&dir, &o)).transpose()?;
+            if !snapshot::diff(&dir, &old, new.as_ref())? {
+                eprintln!("No changes.");
+            }
+        }
+        SnapshotAction::Restore {
+            name,
+            snapshot: query,
+        } => {
+            let session = resolve_session(storage, Some(name))?;
+            let dir = storage.session_dir(&session.slug);
+            let target = snapshot::find(&dir, &query)?;
+            let safety = snapshot::create(&dir, Some("pre-restore"))?;
+            let count = snapshot::restore(&dir, &target)?;
+            println!("Restored {count} files from {}", target.name);
+            println!("Previous state saved as snapshot {}", safety.name);
+        }
+    }
+    Ok(())
+}
+
+fn handle_validate(storage: &Storage, config: &Config) -> Result<()> {
+    let Some(rules) = naming::Rules::from_config(config)? else {
+        println!("No naming rules configured (add [naming] to the config)");
+        return Ok(());
+    };
+    let slugs = storage.existing_slugs()?;
+    let broken = naming::audit(&rules, &slugs);
+    if broken.is_empty() {
+        println!("All {} session names follow the naming rules", slugs.len());
+        return Ok(());
+    }
+    for (slug, problems) in &broken {
+        println!("{slug}: {}", problems.join(", "));
+    }
+    eprintln!(
+        "{} of {} sessions break the naming rules (fix with `sp rename`)",
+        broken.len(),
+        slugs.len()
+    );
+    process::exit(1);
+}
+
+fn handle_doctor(storage: &Storage, config: &Config, fix_perms: bool) -> Result<()> {
+    let workspace = storage.workspace_path();
+    println!("Workspace:     {}", workspace.display());
+    println!(
+        "Private files: {}",
+        if config.private_files { "on" } else { "off" }
+    );
+
+    let mut exposed = perms::find_exposed(&workspace);
+    let config_file = config::config_path();
+    if config_file.exists() {
+        exposed.extend(perms::find_exposed(&config_file));
+    }
+    if exposed.is_empty() {
+        println!("Permissions:   ok");
+        return Ok(());
+    }
+
+    println!(
+        "Permissions:   {} entries accessible by other users",
+        exposed.len()
+    );
+    const SHOWN: usize = 10;
+    for entry in exposed.iter().take(SHOWN) {
+        println!("  {:o}  {}", entry.mode, entry.path.display());
+    }
+    if exposed.len() > SHOWN {
+        println!("  ... and {} more", exposed.len() - SHOWN);
+    }
+
+    if fix_perms {
+        perms::fix(&exposed)?;
+        println!("Restricted {} entries to owner-only access.", exposed.len());
+    } else {
+        println!("Run `sp doctor --fix-perms` to restrict them.");
     }
+    Ok(())
+}
+
+fn handle_init(gitignore: bool, exclude: bool) -> Result<()> {
+    let ignore = if gitignore {
+        init::Ignore::Gitignore
+    } else if exclude {
+        init::Ignore::Exclude
+    } else {
+        // Interactive prompt
+        println!("Where should .scratchpad/ be ignored?");
+        println!("  1) .gitignore (visible to collaborators)");
+        println!("  2) .git/info/exclude (local only)");
+        print!("\nChoice [1/2]: ");
+        io::stdout().flush()?;
 
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if input.trim() == "1" {
+            init::Ignore::Gitignore
+        } else {
+            init::Ignore::Exclude
+        }
+    };
+    let (_, done) = init::init(Path::new("."), ignore)?;
+    for line in done {
+        println!("{line}");
+    }
     Ok(())
 }
 
@@ -443,6 +2119,21 @@ fn print_file_tree_ansi(tree: &[models::FileTreeEntry]) {
     }
 }
 
+/// A file tree entry for `sp files --json`; directories have no size
+fn file_entry_json(session_dir: &Path, entry: &models::FileTreeEntry) -> serde_json::Value {
+    let metadata = fs::metadata(&entry.path).ok();
+    let path = entry.path.strip_prefix(session_dir).unwrap_or(&entry.path);
+    serde_json::json!({
+        "path": path.to_string_lossy().replace('\\', "/"),
+        "size": metadata.as_ref().filter(|m| m.is_file()).map(|m| m.len()),
+        "mtime": metadata
+            .and_then(|m| m.modified().ok())
+            .map(|mtime| chrono::DateTime::<chrono::Utc>::from(mtime).to_rfc3339()),
+        "is_dir": entry.is_dir,
+        "is_entry_point": entry.is_entry_point,
+    })
+}
+
 fn print_file_tree_flat(tree: &[models::FileTreeEntry]) {
     for entry in tree {
         if entry.is_dir {
@@ -471,3 +2162,44 @@ fn print_flat_path(tree: &[models::FileTreeEntry], target: &models::FileTreeEntr
     path_parts.reverse();
     println!("{}", path_parts.join("/"));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_entries_serialize_relative_paths_and_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("notes.md"), "# Hi").unwrap();
+        fs::write(dir.path().join("sub/plan.md"), "plan").unwrap();
+        let entry_point = dir.path().join("notes.md");
+
+        let tree = build_file_tree(dir.path(), Some(&entry_point), usize::MAX);
+        let values: Vec<_> = tree
+            .iter()
+            .map(|entry| file_entry_json(dir.path(), entry))
+            .collect();
+        assert_eq!(values.len(), 3);
+
+        let notes = &values[0];
+        assert_eq!(notes["path"], "notes.md");
+        assert_eq!(notes["size"], 4);
+        assert_eq!(notes["is_dir"], false);
+        assert_eq!(notes["is_entry_point"], true);
+        let mtime = notes["mtime"].as_str().unwrap();
+        assert!(
+            chrono::DateTime::parse_from_rfc3339(mtime).is_ok(),
+            "{mtime}"
+        );
+
+        let sub = &values[1];
+        assert_eq!(sub["path"], "sub");
+        assert!(sub["size"].is_null());
+        assert_eq!(sub["is_dir"], true);
+        assert_eq!(sub["is_entry_point"], false);
+
+        assert_eq!(values[2]["path"], "sub/plan.md");
+        assert_eq!(values[2]["size"], 4);
+    }
+}
@@ -25,10 +25,30 @@ pub fn render_markdown(content: &str, width: u16) -> Result<Text<'static>> {
     }
 }
 
+/// Markdown as ANSI-styled text for a terminal `width` columns wide, from glow when it's
+/// installed, else the basic renderer. Unstyled (`styled` false) for output that isn't
+/// a terminal.
+pub fn render_ansi(content: &str, width: u16, styled: bool) -> String {
+    let style = if styled { "auto" } else { "notty" };
+    match glow(content, width, style) {
+        Ok(output) => String::from_utf8_lossy(&output).into_owned(),
+        Err(_) => text_to_ansi(&render_basic(content), styled),
+    }
+}
+
 fn render_with_glow(content: &str, width: u16) -> Result<Text<'static>> {
+    let text = glow(content, width, "auto")?
+        .into_text()
+        .context("Failed to parse ANSI output from glow")?;
+
+    Ok(convert_text(text))
+}
+
+/// Raw output of glow rendering `content` in `style`
+fn glow(content: &str, width: u16, style: &str) -> Result<Vec<u8>> {
     let width = width.max(20);
     let mut child = Command::new("glow")
-        .args(["-s", "auto", "-w", &width.to_string(), "-n"])
+        .args(["-s", style, "-w", &width.to_string(), "-n"])
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
@@ -51,13 +71,60 @@ fn render_with_glow(content: &str, width: u16) -> Result<Text<'static>> {
         let msg = if msg.is_empty() { "glow failed" } else { msg };
         return Err(anyhow!("{msg}"));
     }
+    Ok(output.stdout)
+}
 
-    let text = output
-        .stdout
-        .into_text()
-        .context("Failed to parse ANSI output from glow")?;
+/// Rendered text as lines with SGR escapes for its colors and modifiers
+fn text_to_ansi(text: &Text, styled: bool) -> String {
+    let mut out = String::new();
+    for line in &text.lines {
+        for span in &line.spans {
+            let style = line.style.patch(span.style);
+            let mut codes: Vec<String> = style.fg.and_then(sgr_color).into_iter().collect();
+            for (modifier, code) in [
+                (Modifier::BOLD, "1"),
+                (Modifier::DIM, "2"),
+                (Modifier::ITALIC, "3"),
+                (Modifier::UNDERLINED, "4"),
+            ] {
+                if style.add_modifier.contains(modifier) {
+                    codes.push(code.to_string());
+                }
+            }
+            if styled && !codes.is_empty() {
+                out.push_str(&format!("\x1b[{}m{}\x1b[0m", codes.join(";"), span.content));
+            } else {
+                out.push_str(&span.content);
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
 
-    Ok(convert_text(text))
+fn sgr_color(color: Color) -> Option<String> {
+    let code = match color {
+        Color::Reset => return None,
+        Color::Black => 30,
+        Color::Red => 31,
+        Color::Green => 32,
+        Color::Yellow => 33,
+        Color::Blue => 34,
+        Color::Magenta => 35,
+        Color::Cyan => 36,
+        Color::Gray => 37,
+        Color::DarkGray => 90,
+        Color::LightRed => 91,
+        Color::LightGreen => 92,
+        Color::LightYellow => 93,
+        Color::LightBlue => 94,
+        Color::LightMagenta => 95,
+        Color::LightCyan => 96,
+        Color::White => 97,
+        Color::Indexed(i) => return Some(format!("38;5;{i}")),
+        Color::Rgb(r, g, b) => return Some(format!("38;2;{r};{g};{b}")),
+    };
+    Some(code.to_string())
 }
 
 /// Basic markdown renderer for when glow is not available
@@ -289,3 +356,42 @@ fn convert_alignment(alignment: core_layout::Alignment) -> Alignment {
         core_layout::Alignment::Right => Alignment::Right,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic_rendering_becomes_sgr_escapes() {
+        let content = "# Title\nSome **bold** and `code`";
+        let text = render_basic(content);
+        let ansi = text_to_ansi(&text, true);
+        assert_eq!(
+            ansi,
+            "\x1b[36;1mTitle\x1b[0m\n\
+             Some \x1b[1mbold\x1b[0m and \x1b[32mcode\x1b[0m\n"
+        );
+        // Every style is closed, so nothing leaks into what the terminal prints next
+        assert!(ansi.trim_end().ends_with("\x1b[0m"));
+        assert_eq!(ansi.matches("\x1b[0m").count(), 3);
+
+        assert_eq!(text_to_ansi(&text, false), "Title\nSome bold and code\n");
+
+        // Without glow, `render_ansi` falls back to exactly this
+        if glow(content, 80, "auto").is_err() {
+            assert_eq!(render_ansi(content, 80, true), ansi);
+        }
+    }
+
+    #[test]
+    fn colors_map_to_sgr_codes() {
+        assert_eq!(sgr_color(Color::Reset), None);
+        assert_eq!(sgr_color(Color::Red).as_deref(), Some("31"));
+        assert_eq!(sgr_color(Color::DarkGray).as_deref(), Some("90"));
+        assert_eq!(sgr_color(Color::Indexed(208)).as_deref(), Some("38;5;208"));
+        assert_eq!(
+            sgr_color(Color::Rgb(1, 2, 3)).as_deref(),
+            Some("38;2;1;2;3")
+        );
+    }
+}
@@ -0,0 +1,40 @@
+//! Per-workspace session marks for the TUI list (`m a` to set, `' a` to jump)
+//!
+//! Stored in `<workspace>/.marks.toml`, mark letter to session slug, which stays on this
+//! machine like `.viewed.toml`. A mark whose session was renamed or deleted is reported
+//! when jumped to, and replaced by setting it again.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+
+const MARKS_FILE: &str = ".marks.toml";
+
+pub struct Marks {
+    path: PathBuf,
+    marks: BTreeMap<String, String>,
+}
+
+impl Marks {
+    pub fn load(workspace: &Path) -> Self {
+        let path = workspace.join(MARKS_FILE);
+        let marks = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| toml::from_str(&content).ok())
+            .unwrap_or_default();
+        Self { path, marks }
+    }
+
+    pub fn get(&self, mark: char) -> Option<&str> {
+        self.marks.get(&mark.to_string()).map(String::as_str)
+    }
+
+    pub fn set(&mut self, mark: char, slug: &str) -> Result<()> {
+        self.marks.insert(mark.to_string(), slug.to_string());
+        let content = toml::to_string(&self.marks).context("Failed to serialize marks")?;
+        fs::write(&self.path, content)
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
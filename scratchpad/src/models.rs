@@ -1,8 +1,11 @@
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::list_format::ListFormat;
+
 /// A session is identified by its slug (folder name).
 /// Timestamps are derived from filesystem metadata.
 #[derive(Debug, Clone)]
@@ -10,7 +13,7 @@ use serde::{Deserialize, Serialize};
 pub struct Session {
     /// Folder name, e.g., "quantum-reactor"
     pub slug: String,
-    /// From filesystem creation time (or mtime as fallback)
+    /// From `created` in the metadata, else filesystem creation time (or mtime as fallback)
     pub created_at: DateTime<Utc>,
     /// From filesystem mtime
     pub updated_at: DateTime<Utc>,
@@ -52,6 +55,14 @@ pub enum Context {
 }
 
 impl Context {
+    /// Short machine-readable label: "user" or "project"
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Context::User => "user",
+            Context::Project(_) => "project",
+        }
+    }
+
     pub fn display_name(&self) -> String {
         match self {
             Context::User => "User".to_string(),
@@ -72,6 +83,18 @@ pub enum Agent {
     Codex,
 }
 
+/// Headers the TUI groups the session list under
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    #[default]
+    None,
+    /// Today, Yesterday, This week and Older, by last update
+    Date,
+    /// Each session's first tag
+    Tag,
+}
+
 impl Agent {
     pub fn command(&self) -> &'static str {
         match self {
@@ -102,10 +125,178 @@ impl std::str::FromStr for Agent {
     }
 }
 
+/// Optional per-session metadata, stored as `.session.toml` inside the session directory.
+/// Sessions without the file simply have default (empty) metadata.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionMeta {
+    /// Deadline set with `sp due`, exported by `sp export --ics`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<NaiveDate>,
+    /// Follow-ups set with `sp remind`, removed once completed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reminders: Vec<Reminder>,
+    /// Skipped by `sp review` until this date
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snoozed_until: Option<NaiveDate>,
+    /// Files are stored age-encrypted (`sp new --encrypted`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub encrypted: bool,
+    /// Issue the session was created for (`sp new --issue`), e.g. `gh#456` or `JIRA-123`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issue: Option<String>,
+    /// Workflow column on the TUI board, filtered with `sp list --status`
+    #[serde(default, skip_serializing_if = "Status::is_inbox")]
+    pub status: Status,
+    /// Labels set with `sp tag`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// One-line description from `sp summarize` (full text in `SUMMARY.md`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    /// Relationships to other sessions recorded with `sp link`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<SessionLink>,
+    /// Git worktrees created inside the session with `sp worktree`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub worktrees: Vec<WorktreeMeta>,
+    /// Agent runs, oldest first (capped, see `Storage::record_run`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub runs: Vec<RunRecord>,
+    /// Creation time carried over by `sp import --frontmatter`, since the filesystem's
+    /// can't be set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<DateTime<Utc>>,
+}
+
+/// Where a session is in its workflow
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// Not started (sessions without a status)
+    #[default]
+    Inbox,
+    Active,
+    Blocked,
+    Done,
+}
+
+impl Status {
+    /// Board columns, left to right
+    pub const ALL: [Status; 4] = [Status::Inbox, Status::Active, Status::Blocked, Status::Done];
+
+    pub fn is_inbox(&self) -> bool {
+        *self == Status::Inbox
+    }
+
+    pub fn index(self) -> usize {
+        Self::ALL.iter().position(|s| *s == self).unwrap_or(0)
+    }
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Status::Inbox => write!(f, "inbox"),
+            Status::Active => write!(f, "active"),
+            Status::Blocked => write!(f, "blocked"),
+            Status::Done => write!(f, "done"),
+        }
+    }
+}
+
+impl std::str::FromStr for Status {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "inbox" => Ok(Status::Inbox),
+            "active" => Ok(Status::Active),
+            "blocked" => Ok(Status::Blocked),
+            "done" => Ok(Status::Done),
+            _ => Err(format!(
+                "Unknown status: {s} (inbox, active, blocked, done)"
+            )),
+        }
+    }
+}
+
+/// What another session is to this one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Relation {
+    /// The session this one was forked from
+    Parent,
+    /// A session forked from this one
+    Child,
+    Related,
+}
+
+impl Relation {
+    /// The relation recorded on the other session
+    pub fn inverse(self) -> Self {
+        match self {
+            Relation::Parent => Relation::Child,
+            Relation::Child => Relation::Parent,
+            Relation::Related => Relation::Related,
+        }
+    }
+}
+
+impl std::fmt::Display for Relation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Relation::Parent => write!(f, "parent"),
+            Relation::Child => write!(f, "child"),
+            Relation::Related => write!(f, "related"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionLink {
+    pub rel: Relation,
+    /// Slug of the other session
+    pub session: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorktreeMeta {
+    /// Repository the worktree belongs to
+    pub repo: PathBuf,
+    /// Worktree directory, relative to the session directory
+    pub path: PathBuf,
+    pub branch: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reminder {
+    pub at: DateTime<Utc>,
+    pub message: String,
+}
+
+impl Reminder {
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.at <= now
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub agent: Agent,
+    pub started_at: DateTime<Utc>,
+    pub duration_secs: u64,
+    /// None when the agent was killed by a signal
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+}
+
 /// A single entry in a file tree (pre-order traversal, flat list)
 #[derive(Debug, Clone)]
 pub struct FileTreeEntry {
     pub name: String,
+    pub path: PathBuf,
     pub is_dir: bool,
     pub depth: usize,
     pub is_last: bool,
@@ -117,6 +308,146 @@ pub struct FileTreeEntry {
 pub struct ServerConfig {
     pub url: String,
     pub token: Option<String>,
+    /// Server workspace to sync with; defaults to `user`, or `project-<repo>` in a project
+    #[serde(default)]
+    pub workspace: Option<String>,
+    /// Encrypt synced ops with the key in `sync.key`, so the server sees only ciphertext
+    #[serde(default)]
+    pub encrypt: bool,
+}
+
+/// Which contexts `sp sync` may sync
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncConfig {
+    /// Contexts to sync, as globs over `user` and `project:<repo path>`; empty means all
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Contexts never synced, even when included
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Shared folder (Dropbox, Syncthing, ...) to sync through when there's no `[server]`
+    #[serde(default)]
+    pub folder: Option<String>,
+    /// Name this machine's changes are attributed to (default: the hostname)
+    #[serde(default)]
+    pub device_name: Option<String>,
+    /// What `sp sync` syncs through; by default the `[server]`, else `folder`
+    #[serde(default)]
+    pub backend: Option<SyncBackend>,
+    /// Git repository to sync through with `backend = "git"`
+    #[serde(default)]
+    pub remote: Option<String>,
+    /// Gitignore-style globs over workspace paths that stay on this machine
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    /// Files larger than this (e.g. "5M") aren't synced
+    #[serde(default)]
+    pub max_file_size: Option<String>,
+}
+
+impl SyncConfig {
+    /// The git remote to sync through, when that's the backend
+    pub fn git_remote(&self) -> Option<&str> {
+        (self.backend == Some(SyncBackend::Git))
+            .then_some(self.remote.as_deref())
+            .flatten()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncBackend {
+    Server,
+    /// A directory shared by Dropbox, Syncthing, ...
+    Folder,
+    /// A git repository: `sp sync` commits ops to a clone and pushes them
+    Git,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationsConfig {
+    /// Webhook URL receiving a JSON POST per event
+    pub url: String,
+    /// Events to send (e.g. "agent.finished"); empty means all
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupConfig {
+    /// How often to back up: "hourly", "daily", "weekly", or "<N>h" / "<N>d"
+    #[serde(default = "default_backup_interval")]
+    pub interval: String,
+    /// Scheduled backups to keep per workspace
+    #[serde(default = "default_backup_keep")]
+    pub keep: usize,
+    /// Directory for scheduled backups (absolute path, default: the scratchpad data directory)
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EncryptionConfig {
+    /// age identity file (an `AGE-SECRET-KEY-...` line, as written by `age-keygen`)
+    #[serde(default)]
+    pub identity: Option<String>,
+    /// Passphrase used when no identity is set
+    #[serde(default)]
+    pub passphrase: Option<String>,
+}
+
+/// How `sp new --issue` fetches an issue's title and description
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IssuesConfig {
+    /// Command run with `{id}` replaced by the issue key; prints the title, then the body
+    #[serde(default)]
+    pub command: Option<String>,
+    /// Passed to the command as `SP_ISSUE_TOKEN` (and to `gh` as `GH_TOKEN`)
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+/// Embedding backend for `sp index build` / `sp search --semantic`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmbeddingsConfig {
+    /// Command reading text on stdin and printing its vector; overrides the Ollama backend
+    #[serde(default)]
+    pub command: Option<String>,
+    /// Ollama-compatible server (default http://localhost:11434)
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Embedding model served by it (default nomic-embed-text)
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+/// Pull request drafts (`sp pr`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PrConfig {
+    /// Markdown file with `{title}`, `{session}`, `{notes}` and `{changes}` placeholders
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+/// Session naming rules, checked when sessions are created or renamed and audited by
+/// `sp validate`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NamingConfig {
+    /// Regex every slug must match, e.g. `^[a-z]+-[0-9]+-` for a ticket id prefix
+    #[serde(default)]
+    pub pattern: Option<String>,
+    #[serde(default)]
+    pub min_length: Option<usize>,
+    #[serde(default)]
+    pub max_length: Option<usize>,
+}
+
+fn default_backup_interval() -> String {
+    "daily".to_string()
+}
+
+fn default_backup_keep() -> usize {
+    7
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -146,6 +477,70 @@ pub struct Config {
     /// Optional sync server configuration
     #[serde(default)]
     pub server: Option<ServerConfig>,
+
+    /// Context include/exclude rules for sync
+    #[serde(default)]
+    pub sync: Option<SyncConfig>,
+
+    /// Optional webhook notifications on session events
+    #[serde(default)]
+    pub notifications: Option<NotificationsConfig>,
+
+    /// Optional scheduled backups, taken in the background when due
+    #[serde(default)]
+    pub backup: Option<BackupConfig>,
+
+    /// Key for encrypted sessions
+    #[serde(default)]
+    pub encryption: Option<EncryptionConfig>,
+
+    /// Issue fetching for `sp new --issue`
+    #[serde(default)]
+    pub issues: Option<IssuesConfig>,
+
+    /// Pull request description template for `sp pr`
+    #[serde(default)]
+    pub pr: Option<PrConfig>,
+
+    /// Embedding backend for semantic search
+    #[serde(default)]
+    pub embeddings: Option<EmbeddingsConfig>,
+
+    /// Rules session names must follow
+    #[serde(default)]
+    pub naming: Option<NamingConfig>,
+
+    /// Tags `sp tag --auto` may choose from
+    #[serde(default)]
+    pub tag_vocabulary: Vec<String>,
+
+    /// Disable every action that changes the workspace (also `sp --read-only`)
+    #[serde(default)]
+    pub read_only: bool,
+
+    /// Create session directories and files readable only by you (0700/0600)
+    #[serde(default)]
+    pub private_files: bool,
+
+    /// In Project context, map the checked-out git branch to a `branch-<name>` session
+    #[serde(default)]
+    pub branch_sessions: bool,
+
+    /// Group the TUI's session list by date or tag (`F` cycles through them)
+    #[serde(default)]
+    pub group_by: GroupBy,
+
+    /// Row layout for `sp list` and the TUI's session list, e.g. "{slug:25} {updated:relative}"
+    #[serde(default)]
+    pub list_format: Option<ListFormat>,
+
+    /// Other user workspaces by name, to switch to in the TUI (`W`)
+    #[serde(default)]
+    pub workspaces: BTreeMap<String, String>,
+
+    /// Count commands, sessions created and agent runs locally for `sp usage`
+    #[serde(default = "default_usage_stats")]
+    pub usage_stats: bool,
 }
 
 pub fn default_workspace_path() -> String {
@@ -156,6 +551,10 @@ fn default_name_generator() -> String {
     "auto".to_string()
 }
 
+fn default_usage_stats() -> bool {
+    true
+}
+
 fn dirs_home() -> std::path::PathBuf {
     directories::BaseDirs::new()
         .map(|d| d.home_dir().to_path_buf())
@@ -172,6 +571,22 @@ impl Default for Config {
             viewer: None,
             name_generator: default_name_generator(),
             server: None,
+            sync: None,
+            notifications: None,
+            backup: None,
+            encryption: None,
+            issues: None,
+            pr: None,
+            embeddings: None,
+            naming: None,
+            tag_vocabulary: Vec::new(),
+            read_only: false,
+            private_files: false,
+            branch_sessions: false,
+            group_by: GroupBy::None,
+            list_format: None,
+            workspaces: BTreeMap::new(),
+            usage_stats: default_usage_stats(),
         }
     }
 }
@@ -0,0 +1,129 @@
+//! Session naming rules from `[naming]`: a regex and length bounds slugs must meet
+//!
+//! Every way of naming a session (`sp new`, `sp rename`, `sp bulk rename`, `sp quick`,
+//! `sp clip`, the TUI and RPC `create`) refuses names that break them; `sp new` and
+//! `sp rename` can skip that with `--no-verify`. `sp validate` lists the existing sessions
+//! that do.
+
+use anyhow::{Context as _, Result, bail};
+use regex::Regex;
+
+use crate::models::{Config, NamingConfig};
+
+pub struct Rules {
+    pattern: Option<Regex>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+}
+
+impl Rules {
+    /// The configured rules; None when there are none
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        config.naming.as_ref().map(Self::new).transpose()
+    }
+
+    fn new(naming: &NamingConfig) -> Result<Self> {
+        let pattern = naming
+            .pattern
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .context("Invalid [naming] pattern")?;
+        Ok(Self {
+            pattern,
+            min_length: naming.min_length,
+            max_length: naming.max_length,
+        })
+    }
+
+    /// How `slug` breaks the rules, empty when it follows them
+    pub fn problems(&self, slug: &str) -> Vec<String> {
+        let mut problems = Vec::new();
+        let len = slug.chars().count();
+        if let Some(min) = self.min_length
+            && len < min
+        {
+            problems.push(format!("shorter than {min} characters"));
+        }
+        if let Some(max) = self.max_length
+            && len > max
+        {
+            problems.push(format!("longer than {max} characters"));
+        }
+        if let Some(pattern) = &self.pattern
+            && !pattern.is_match(slug)
+        {
+            problems.push(format!("doesn't match {}", pattern.as_str()));
+        }
+        problems
+    }
+}
+
+/// Refuse a new session name that breaks the rules
+pub fn check(config: &Config, slug: &str) -> Result<()> {
+    let Some(rules) = Rules::from_config(config)? else {
+        return Ok(());
+    };
+    let problems = rules.problems(slug);
+    if !problems.is_empty() {
+        bail!(
+            "Session name '{slug}' breaks the naming rules: {}",
+            problems.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Sessions among `slugs` that break the rules, with how
+pub fn audit<'a>(rules: &Rules, slugs: &'a [String]) -> Vec<(&'a str, Vec<String>)> {
+    slugs
+        .iter()
+        .map(|slug| (slug.as_str(), rules.problems(slug)))
+        .filter(|(_, problems)| !problems.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(pattern: &str, min: usize, max: usize) -> Rules {
+        Rules::new(&NamingConfig {
+            pattern: Some(pattern.to_string()),
+            min_length: Some(min),
+            max_length: Some(max),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn reports_each_broken_rule() {
+        let rules = rules("^[a-z]+-[0-9]+-", 8, 20);
+        assert!(rules.problems("proj-12-login").is_empty());
+        assert_eq!(
+            rules.problems("quantum-reactor-with-a-long-name"),
+            vec![
+                "longer than 20 characters".to_string(),
+                "doesn't match ^[a-z]+-[0-9]+-".to_string(),
+            ]
+        );
+        assert_eq!(
+            rules.problems("p-1-x"),
+            vec!["shorter than 8 characters".to_string()]
+        );
+
+        let slugs = vec!["proj-12-login".to_string(), "scratch".to_string()];
+        let broken = audit(&rules, &slugs);
+        assert_eq!(broken.len(), 1);
+        assert_eq!(broken[0].0, "scratch");
+    }
+
+    #[test]
+    fn rejects_an_invalid_pattern() {
+        let naming = NamingConfig {
+            pattern: Some("(".to_string()),
+            ..Default::default()
+        };
+        assert!(Rules::new(&naming).is_err());
+    }
+}
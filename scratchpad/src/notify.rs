@@ -0,0 +1,156 @@
+//! Webhook notifications for session events
+//!
+//! Configured via `[notifications] url = "..."`. Each event is POSTed as JSON with both a
+//! `text` (Slack) and `content` (Discord) summary, plus structured fields for other consumers.
+
+use std::time::Duration;
+
+use anyhow::{Context as _, Result};
+use serde_json::{Value, json};
+
+use crate::models::{Agent, Config, Context};
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub enum Event<'a> {
+    SessionCreated {
+        slug: &'a str,
+    },
+    SessionDeleted {
+        slug: &'a str,
+    },
+    AgentFinished {
+        slug: &'a str,
+        agent: Agent,
+        exit_code: Option<i32>,
+        duration: Duration,
+    },
+}
+
+impl Event<'_> {
+    /// Event name, also used for filtering via `notifications.events`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::SessionCreated { .. } => "session.created",
+            Event::SessionDeleted { .. } => "session.deleted",
+            Event::AgentFinished { .. } => "agent.finished",
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        match self {
+            Event::SessionCreated { slug } => format!("Session created: {slug}"),
+            Event::SessionDeleted { slug } => format!("Session deleted: {slug}"),
+            Event::AgentFinished {
+                slug,
+                agent,
+                exit_code,
+                duration,
+            } => {
+                let status = match exit_code {
+                    Some(0) => "finished".to_string(),
+                    Some(code) => format!("exited with {code}"),
+                    None => "was interrupted".to_string(),
+                };
+                format!(
+                    "{agent} {status} in session {slug} after {}",
+                    format_duration(*duration)
+                )
+            }
+        }
+    }
+
+    fn payload(&self, context: &Context) -> Value {
+        let mut payload = json!({
+            "event": self.name(),
+            "text": self.summary(),
+            "content": self.summary(),
+            "context": context.kind(),
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+        });
+        match self {
+            Event::SessionCreated { slug } | Event::SessionDeleted { slug } => {
+                payload["session"] = json!(slug);
+            }
+            Event::AgentFinished {
+                slug,
+                agent,
+                exit_code,
+                duration,
+            } => {
+                payload["session"] = json!(slug);
+                payload["agent"] = json!(agent.command());
+                payload["exit_code"] = json!(exit_code);
+                payload["duration_secs"] = json!(duration.as_secs());
+            }
+        }
+        payload
+    }
+}
+
+/// Count the event in the local usage stats and send it to the configured webhook. The
+/// webhook is skipped when notifications are not configured or the event is filtered out.
+pub fn send(config: &Config, context: &Context, event: &Event) -> Result<()> {
+    crate::usage::record_event(config, event);
+    let Some(notifications) = &config.notifications else {
+        return Ok(());
+    };
+    if !notifications.events.is_empty() && !notifications.events.iter().any(|e| e == event.name()) {
+        return Ok(());
+    }
+
+    ureq::post(&notifications.url)
+        .timeout(WEBHOOK_TIMEOUT)
+        .send_json(event.payload(context))
+        .with_context(|| format!("Webhook {} failed", event.name()))?;
+    Ok(())
+}
+
+/// Compact duration, e.g. `42s`, `2m05s`, `1h10m`
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    if secs < 60 {
+        format!("{secs}s")
+    } else if secs < 3600 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{:02}m", secs / 3600, (secs % 3600) / 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn agent_payload_includes_summary_and_fields() {
+        let event = Event::AgentFinished {
+            slug: "quantum-reactor",
+            agent: Agent::Claude,
+            exit_code: Some(0),
+            duration: Duration::from_secs(125),
+        };
+        let payload = event.payload(&Context::User);
+        assert_eq!(payload["event"], "agent.finished");
+        assert_eq!(payload["session"], "quantum-reactor");
+        assert_eq!(payload["duration_secs"], 125);
+        assert_eq!(
+            payload["text"],
+            "claude finished in session quantum-reactor after 2m05s"
+        );
+    }
+
+    #[test]
+    fn unconfigured_or_filtered_events_are_skipped() {
+        let mut config = Config::default();
+        let event = Event::SessionCreated { slug: "a" };
+        assert!(send(&config, &Context::User, &event).is_ok());
+
+        config.notifications = Some(crate::models::NotificationsConfig {
+            // Unroutable: would fail if the filter let the event through
+            url: "http://127.0.0.1:9/hook".to_string(),
+            events: vec!["agent.finished".to_string()],
+        });
+        assert!(send(&config, &Context::User, &event).is_ok());
+    }
+}
@@ -1,3 +1,4 @@
+use std::ffi::OsString;
 use std::path::Path;
 use std::process::Command;
 
@@ -54,18 +55,85 @@ pub fn open_path_nonblocking(path: &Path, viewer: Option<&str>) -> Result<()> {
     Ok(())
 }
 
-/// Open a file with the specified editor (blocking, waits for editor to close)
-pub fn open_with_editor(path: &Path, editor: Option<&str>) -> Result<()> {
-    let editor = editor
+/// The editor to use: `editor` from the config, else `$EDITOR`, `$VISUAL` or vi
+fn resolve_editor(editor: Option<&str>) -> String {
+    editor
         .map(String::from)
         .or_else(|| std::env::var("EDITOR").ok())
         .or_else(|| std::env::var("VISUAL").ok())
-        .unwrap_or_else(|| "vi".to_string());
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+/// `/usr/bin/nvim` → `nvim`
+fn program_name(program: &str) -> String {
+    Path::new(program)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Editor arguments that open `path`, positioned at `line` when given.
+/// Most terminal editors take `+N`; GUI editors and helix take `path:N`.
+fn editor_path_args(program: &str, path: &Path, line: Option<usize>) -> Vec<OsString> {
+    let Some(line) = line else {
+        return vec![path.into()];
+    };
+    let name = program_name(program);
+    let with_line = || {
+        let mut arg = path.as_os_str().to_owned();
+        arg.push(format!(":{line}"));
+        arg
+    };
+    match name.as_str() {
+        "code" | "code-insiders" | "codium" | "cursor" => vec!["-g".into(), with_line()],
+        "subl" | "zed" | "hx" | "helix" => vec![with_line()],
+        _ => vec![format!("+{line}").into(), path.into()],
+    }
+}
+
+/// Open a file with the specified editor (blocking, waits for editor to close)
+pub fn open_with_editor(path: &Path, editor: Option<&str>) -> Result<()> {
+    open_with_editor_at(path, None, editor)
+}
+
+/// Editor arguments that open the project directory `root` next to `path`: split side
+/// by side in vim, neovim and helix, in one window in GUI editors
+fn editor_project_args(program: &str, root: &Path, path: &Path) -> Vec<OsString> {
+    let both = [root.into(), path.into()];
+    let split = match program_name(program).as_str() {
+        "vim" | "nvim" | "vi" | "gvim" | "mvim" => Some("-O"),
+        "hx" | "helix" => Some("--vsplit"),
+        _ => None,
+    };
+    split.map(OsString::from).into_iter().chain(both).collect()
+}
+
+/// Open a file at a 1-based line with the specified editor (blocking)
+pub fn open_with_editor_at(path: &Path, line: Option<usize>, editor: Option<&str>) -> Result<()> {
+    let editor = resolve_editor(editor);
 
     let (program, args) = split_command(&editor);
     let status = Command::new(program)
         .args(args)
-        .arg(path)
+        .args(editor_path_args(program, path, line))
+        .status()
+        .with_context(|| format!("Failed to open {} with {editor}", path.display()))?;
+
+    if !status.success() {
+        return Err(anyhow!("Editor exited with status: {status}"));
+    }
+    Ok(())
+}
+
+/// Open a file together with the project directory `root` it describes, from that
+/// directory (blocking)
+pub fn open_with_editor_and_project(path: &Path, root: &Path, editor: Option<&str>) -> Result<()> {
+    let editor = resolve_editor(editor);
+    let (program, args) = split_command(&editor);
+    let status = Command::new(program)
+        .args(args)
+        .args(editor_project_args(program, root, path))
+        .current_dir(root)
         .status()
         .with_context(|| format!("Failed to open {} with {editor}", path.display()))?;
 
@@ -78,11 +146,7 @@ pub fn open_with_editor(path: &Path, editor: Option<&str>) -> Result<()> {
 /// Open a file with the specified editor (non-blocking)
 #[allow(dead_code)]
 pub fn open_with_editor_nonblocking(path: &Path, editor: Option<&str>) -> Result<()> {
-    let editor = editor
-        .map(String::from)
-        .or_else(|| std::env::var("EDITOR").ok())
-        .or_else(|| std::env::var("VISUAL").ok())
-        .unwrap_or_else(|| "vi".to_string());
+    let editor = resolve_editor(editor);
 
     let (program, args) = split_command(&editor);
     Command::new(program)
@@ -150,6 +214,37 @@ mod tests {
         assert_eq!(args, vec!["--paging=always", "--style=numbers"]);
     }
 
+    #[test]
+    fn test_editor_path_args_with_line() {
+        let path = Path::new("/s/notes.md");
+        assert_eq!(
+            editor_path_args("nvim", path, Some(3)),
+            vec!["+3", "/s/notes.md"]
+        );
+        assert_eq!(
+            editor_path_args("/usr/bin/code", path, Some(3)),
+            vec!["-g", "/s/notes.md:3"]
+        );
+        assert_eq!(editor_path_args("hx", path, None), vec!["/s/notes.md"]);
+    }
+
+    #[test]
+    fn test_editor_project_args() {
+        let (root, path) = (Path::new("/repo"), Path::new("/s/notes.md"));
+        assert_eq!(
+            editor_project_args("code", root, path),
+            vec!["/repo", "/s/notes.md"]
+        );
+        assert_eq!(
+            editor_project_args("/usr/bin/nvim", root, path),
+            vec!["-O", "/repo", "/s/notes.md"]
+        );
+        assert_eq!(
+            editor_project_args("hx", root, path),
+            vec!["--vsplit", "/repo", "/s/notes.md"]
+        );
+    }
+
     #[test]
     fn test_split_command_extra_whitespace() {
         let (program, args) = split_command("code   --wait   --new-window");
@@ -0,0 +1,123 @@
+//! Private permissions for workspace files
+//!
+//! With `private_files` enabled, sp sets its umask to 077 so every directory and file it
+//! creates (and those written by the agents and editors it starts) is 0700/0600.
+//! `sp doctor --fix-perms` strips group and other access from files created before.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+
+/// Make files created from now on by this process and its children owner-only
+pub fn restrict_umask() {
+    #[cfg(unix)]
+    // SAFETY: umask only swaps the process file mode mask and cannot fail
+    unsafe {
+        libc::umask(0o077);
+    }
+}
+
+/// A file or directory that other users can access
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exposed {
+    pub path: PathBuf,
+    pub mode: u32,
+}
+
+/// Entries at or below `root` with any group or other permission bits. Symlinks are not
+/// followed.
+pub fn find_exposed(root: &Path) -> Vec<Exposed> {
+    let mut found = Vec::new();
+    collect(root, &mut found);
+    found
+}
+
+fn collect(path: &Path, found: &mut Vec<Exposed>) {
+    let Ok(meta) = path.symlink_metadata() else {
+        return;
+    };
+    if meta.file_type().is_symlink() {
+        return;
+    }
+    let mode = mode(&meta);
+    if mode & 0o077 != 0 {
+        found.push(Exposed {
+            path: path.to_path_buf(),
+            mode,
+        });
+    }
+    if meta.is_dir() {
+        let Ok(entries) = fs::read_dir(path) else {
+            return;
+        };
+        let mut children: Vec<PathBuf> = entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+        children.sort();
+        for child in children {
+            collect(&child, found);
+        }
+    }
+}
+
+/// Remove group and other access, keeping the owner's bits (so 0755 -> 0700, 0644 -> 0600)
+pub fn fix(exposed: &[Exposed]) -> Result<()> {
+    for entry in exposed {
+        set_mode(&entry.path, entry.mode & 0o700)
+            .with_context(|| format!("Failed to change permissions of {}", entry.path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn mode(meta: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o777
+}
+
+#[cfg(not(unix))]
+fn mode(_meta: &fs::Metadata) -> u32 {
+    0
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn finds_and_fixes_exposed_entries() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("ws");
+        fs::create_dir_all(root.join("s1")).unwrap();
+        fs::write(root.join("s1/notes.md"), "token").unwrap();
+        fs::write(root.join("s1/run.sh"), "").unwrap();
+        set_mode(&root, 0o700).unwrap();
+        set_mode(&root.join("s1"), 0o755).unwrap();
+        set_mode(&root.join("s1/notes.md"), 0o644).unwrap();
+        set_mode(&root.join("s1/run.sh"), 0o700).unwrap();
+
+        let exposed = find_exposed(&root);
+        let paths: Vec<_> = exposed.iter().map(|e| e.path.clone()).collect();
+        assert_eq!(paths, vec![root.join("s1"), root.join("s1/notes.md")]);
+
+        fix(&exposed).unwrap();
+        assert!(find_exposed(&root).is_empty());
+        let mode = |p: &Path| fs::metadata(p).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&root.join("s1")), 0o700);
+        assert_eq!(mode(&root.join("s1/notes.md")), 0o600);
+        assert_eq!(mode(&root.join("s1/run.sh")), 0o700);
+    }
+}
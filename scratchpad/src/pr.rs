@@ -0,0 +1,179 @@
+//! Pull request descriptions assembled from a session (`sp pr`)
+//!
+//! The description is a template with `{title}`, `{session}`, `{notes}` and `{changes}`
+//! placeholders. `{changes}` lists, for each worktree created with `sp worktree`, the
+//! commits and diffstat against the base branch, followed by any `.diff`/`.patch` files
+//! saved in the session.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+
+use crate::git;
+use crate::models::{Session, SessionMeta};
+use crate::storage::{Storage, first_heading};
+
+pub const DEFAULT_TEMPLATE: &str = "## Summary
+
+{notes}
+
+## Changes
+
+{changes}
+";
+
+pub struct PrDraft {
+    pub title: String,
+    pub body: String,
+}
+
+/// Build the draft for `session`. `base` is the branch worktrees are compared against.
+pub fn draft(
+    storage: &Storage,
+    session: &Session,
+    template: &str,
+    base: Option<&str>,
+) -> Result<PrDraft> {
+    let notes = storage.read_notes(&session.slug).unwrap_or_default();
+    let title = first_heading(&notes).unwrap_or_else(|| session.display_title());
+    let meta = storage.load_meta(&session.slug)?;
+    let changes = changes(&storage.session_dir(&session.slug), &meta, base);
+
+    let body = template
+        .replace("{title}", &title)
+        .replace("{session}", &session.slug)
+        .replace("{notes}", strip_title(&notes).trim())
+        .replace("{changes}", changes.trim());
+    Ok(PrDraft { title, body })
+}
+
+/// Notes without a leading `# Title` line, which becomes the PR title instead
+fn strip_title(notes: &str) -> &str {
+    let trimmed = notes.trim_start();
+    match trimmed.split_once('\n') {
+        Some((first, rest)) if first.starts_with("# ") => rest,
+        None if trimmed.starts_with("# ") => "",
+        _ => notes,
+    }
+}
+
+fn changes(session_dir: &Path, meta: &SessionMeta, base: Option<&str>) -> String {
+    let mut out = String::new();
+    for worktree in &meta.worktrees {
+        let dir = session_dir.join(&worktree.path);
+        if !dir.is_dir() {
+            continue;
+        }
+        let base = base
+            .map(str::to_string)
+            .unwrap_or_else(|| git::default_branch(&dir));
+        out.push_str(&format!("### `{}` (vs `{base}`)\n\n", worktree.branch));
+        let log = git::run(
+            &dir,
+            &[
+                "log",
+                "--oneline",
+                "--no-decorate",
+                &format!("{base}..HEAD"),
+            ],
+        );
+        let stat = git::run(&dir, &["diff", "--stat", &format!("{base}...HEAD")]);
+        match (log, stat) {
+            (Ok(log), Ok(stat)) if !log.is_empty() || !stat.is_empty() => {
+                for line in log.lines() {
+                    out.push_str(&format!("- {line}\n"));
+                }
+                if !stat.is_empty() {
+                    out.push_str(&format!("\n```\n{stat}\n```\n"));
+                }
+            }
+            (Ok(_), Ok(_)) => out.push_str("No commits yet.\n"),
+            (Err(e), _) | (_, Err(e)) => out.push_str(&format!("Could not compare: {e}\n")),
+        }
+        out.push('\n');
+    }
+
+    for patch in patch_files(session_dir) {
+        let Ok(content) = fs::read_to_string(&patch) else {
+            continue;
+        };
+        let name = patch.strip_prefix(session_dir).unwrap_or(&patch);
+        out.push_str(&format!(
+            "### {}\n\n```diff\n{}\n```\n\n",
+            name.display(),
+            content.trim_end()
+        ));
+    }
+
+    if out.is_empty() {
+        out.push_str("No worktrees or patches in this session.");
+    }
+    out
+}
+
+/// `.diff` and `.patch` files at the top of the session, sorted by name
+fn patch_files(session_dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(session_dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<_> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.is_file()
+                && p.extension()
+                    .is_some_and(|ext| ext == "diff" || ext == "patch")
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Template from the `[pr] template` file, or the built-in one
+pub fn load_template(path: Option<&str>) -> Result<String> {
+    match path {
+        Some(path) => {
+            fs::read_to_string(path).with_context(|| format!("Failed to read PR template {path}"))
+        }
+        None => Ok(DEFAULT_TEMPLATE.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Config, Context};
+
+    #[test]
+    fn drafts_from_notes_and_patches() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            workspace_path: dir.path().to_string_lossy().to_string(),
+            ..Config::default()
+        };
+        let storage = Storage::new(config, Context::User);
+        let session = Session::new("retry-fix");
+        storage
+            .create_session(&session, Some("# Retry on 503\n\nBackoff was missing.\n"))
+            .unwrap();
+        fs::write(
+            storage.session_dir("retry-fix").join("fix.patch"),
+            "+retry()\n",
+        )
+        .unwrap();
+
+        let pr = draft(
+            &storage,
+            &session,
+            "{title}|{session}|{notes}|{changes}",
+            None,
+        )
+        .unwrap();
+        assert_eq!(pr.title, "Retry on 503");
+        assert_eq!(
+            pr.body,
+            "Retry on 503|retry-fix|Backoff was missing.|### fix.patch\n\n```diff\n+retry()\n```"
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! Session follow-up reminders (`sp remind`)
+//!
+//! Reminders live in session metadata. Once due they are listed by `sp list --due` and at
+//! the top of the TUI until they are snoozed or completed.
+
+use anyhow::{Result, anyhow, bail};
+use chrono::{DateTime, Duration, Local, NaiveTime, TimeZone, Utc};
+
+use crate::calendar;
+use crate::models::{Reminder, SessionMeta};
+
+/// Reminders set for a day (rather than after a span) go off at this local time
+const DAY_REMINDER_HOUR: u32 = 9;
+
+/// Default snooze, used by the TUI
+pub const SNOOZE: Duration = Duration::days(1);
+
+/// `[in] <when> <message...>`, e.g. `in 2d check agent results` or `tomorrow review PR`
+pub fn parse(args: &[String], now: DateTime<Utc>) -> Result<Reminder> {
+    let args = match args.first() {
+        Some(first) if first == "in" => &args[1..],
+        _ => args,
+    };
+    let Some((when, message)) = args.split_first() else {
+        bail!("Missing reminder time (e.g. `in 2d`, `tomorrow` or 2024-05-01)");
+    };
+    let message = message.join(" ").trim().to_string();
+    if message.is_empty() {
+        bail!("Missing reminder message");
+    }
+    Ok(Reminder {
+        at: parse_when(when, now)?,
+        message,
+    })
+}
+
+/// A span from now (`30m`, `4h`, `2d`, `1w`) or a day (`tomorrow`, `+3`, `2024-05-01`)
+pub fn parse_when(input: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    if let Some(span) = calendar::parse_span(input) {
+        return Ok(now + span);
+    }
+    let today = now.with_timezone(&Local).date_naive();
+    let day = calendar::parse_due(input, today).map_err(|_| {
+        anyhow!("Invalid reminder time '{input}' (use e.g. 2d, 4h, tomorrow or YYYY-MM-DD)")
+    })?;
+    let time = NaiveTime::from_hms_opt(DAY_REMINDER_HOUR, 0, 0).unwrap_or_default();
+    Local
+        .from_local_datetime(&day.and_time(time))
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+        .ok_or_else(|| anyhow!("Invalid reminder time '{input}'"))
+}
+
+/// Add a reminder, keeping them ordered by time
+pub fn add(meta: &mut SessionMeta, reminder: Reminder) {
+    meta.reminders.push(reminder);
+    meta.reminders.sort_by_key(|r| r.at);
+}
+
+/// Push the due reminders back by `span` from now. Returns how many were snoozed.
+pub fn snooze(meta: &mut SessionMeta, span: Duration, now: DateTime<Utc>) -> usize {
+    let mut count = 0;
+    for reminder in meta.reminders.iter_mut().filter(|r| r.is_due(now)) {
+        reminder.at = now + span;
+        count += 1;
+    }
+    meta.reminders.sort_by_key(|r| r.at);
+    count
+}
+
+/// Remove the due reminders. Returns how many were completed.
+pub fn complete(meta: &mut SessionMeta, now: DateTime<Utc>) -> usize {
+    let before = meta.reminders.len();
+    meta.reminders.retain(|r| !r.is_due(now));
+    before - meta.reminders.len()
+}
+
+/// `2h ago`, `in 3d`, for showing how far a reminder is from now
+pub fn relative(at: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let delta = at - now;
+    let span = delta.abs();
+    let amount = if span.num_days() > 0 {
+        format!("{}d", span.num_days())
+    } else if span.num_hours() > 0 {
+        format!("{}h", span.num_hours())
+    } else {
+        format!("{}m", span.num_minutes())
+    };
+    if delta <= Duration::zero() {
+        format!("{amount} ago")
+    } else {
+        format!("in {amount}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split(' ').map(str::to_string).collect()
+    }
+
+    #[test]
+    fn parses_reminders() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 10, 12, 0, 0).unwrap();
+        let reminder = parse(&args("in 2d check agent results"), now).unwrap();
+        assert_eq!(reminder.at, now + Duration::days(2));
+        assert_eq!(reminder.message, "check agent results");
+        assert_eq!(
+            parse(&args("30m rerun"), now).unwrap().at,
+            now + Duration::minutes(30)
+        );
+
+        let dated = parse(&args("2024-05-20 ship it"), now).unwrap();
+        assert_eq!(
+            dated.at.with_timezone(&Local).date_naive(),
+            chrono::NaiveDate::from_ymd_opt(2024, 5, 20).unwrap()
+        );
+
+        assert!(parse(&args("in 2d"), now).is_err());
+        assert!(parse(&args("someday review"), now).is_err());
+        assert!(parse(&[], now).is_err());
+    }
+
+    #[test]
+    fn snoozes_and_completes_only_due_reminders() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 10, 12, 0, 0).unwrap();
+        let mut meta = SessionMeta::default();
+        add(&mut meta, parse(&args("in 1w later"), now).unwrap());
+        add(&mut meta, parse(&args("in 1h soon"), now).unwrap());
+        assert_eq!(meta.reminders[0].message, "soon");
+
+        let later = now + Duration::hours(2);
+        assert_eq!(snooze(&mut meta, SNOOZE, later), 1);
+        assert_eq!(meta.reminders[0].at, later + SNOOZE);
+        assert_eq!(complete(&mut meta, later), 0);
+
+        let much_later = now + Duration::weeks(2);
+        assert_eq!(complete(&mut meta, much_later), 2);
+        assert!(meta.reminders.is_empty());
+
+        assert_eq!(relative(now - Duration::hours(3), now), "3h ago");
+        assert_eq!(relative(now + Duration::days(2), now), "in 2d");
+    }
+}
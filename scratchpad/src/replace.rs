@@ -0,0 +1,162 @@
+//! Regex find-and-replace across session files for `sp replace`
+//!
+//! Replacement is line by line, so the preview shows exactly what will be written.
+//! Originals are copied to `<workspace>/.backups/replace-<timestamp>/` before writing.
+//! Encrypted files are never edited, as their plaintext would end up in the backups.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+use chrono::Local;
+use regex::Regex;
+
+use crate::crypto;
+use crate::search::{self, SearchMatch, SearchOptions};
+
+/// Hidden workspace directory holding backups of replaced files
+pub const BACKUPS_DIR: &str = ".backups";
+
+/// Upper bound on matched lines, to keep a runaway pattern from producing a huge preview
+const MAX_MATCHES: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineEdit {
+    /// 1-based line number
+    pub line: usize,
+    pub before: String,
+    pub after: String,
+}
+
+/// Planned changes to one file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEdit {
+    pub slug: String,
+    /// Path relative to the session directory
+    pub path: PathBuf,
+    pub lines: Vec<LineEdit>,
+}
+
+/// Planned edits in one session
+pub fn preview_session(dir: &Path, slug: &str, re: &Regex, replacement: &str) -> Vec<FileEdit> {
+    let matches = search::scan_session(dir, slug, &options(), &|line| re.is_match(line));
+    group(matches, re, replacement)
+}
+
+/// Planned edits across every session in the workspace
+pub fn preview_workspace(workspace: &Path, re: &Regex, replacement: &str) -> Vec<FileEdit> {
+    let matches = search::scan_workspace(workspace, &options(), &|line| re.is_match(line));
+    group(matches, re, replacement)
+}
+
+fn options() -> SearchOptions {
+    SearchOptions {
+        max_results: MAX_MATCHES,
+        ..SearchOptions::default()
+    }
+}
+
+/// Group matched lines (sorted by session, path, line) into per-file edits
+fn group(matches: Vec<SearchMatch>, re: &Regex, replacement: &str) -> Vec<FileEdit> {
+    let mut edits: Vec<FileEdit> = Vec::new();
+    for m in matches {
+        let after = re.replace_all(&m.text, replacement).into_owned();
+        if after == m.text {
+            continue;
+        }
+        let edit = LineEdit {
+            line: m.line,
+            before: m.text,
+            after,
+        };
+        match edits.last_mut() {
+            Some(last) if last.slug == m.slug && last.path == m.path => last.lines.push(edit),
+            _ => edits.push(FileEdit {
+                slug: m.slug,
+                path: m.path,
+                lines: vec![edit],
+            }),
+        }
+    }
+    edits
+}
+
+/// Back up and rewrite every file in `edits`. Returns the backup directory.
+pub fn apply(
+    workspace: &Path,
+    edits: &[FileEdit],
+    re: &Regex,
+    replacement: &str,
+) -> Result<PathBuf> {
+    let backup_root = workspace
+        .join(BACKUPS_DIR)
+        .join(format!("replace-{}", Local::now().format("%Y%m%d-%H%M%S")));
+    for edit in edits {
+        let path = workspace.join(&edit.slug).join(&edit.path);
+        if crypto::is_encrypted(&path) {
+            anyhow::bail!("{} is encrypted; sp replace can't edit it", path.display());
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+
+        let backup = backup_root.join(&edit.slug).join(&edit.path);
+        if let Some(parent) = backup.parent() {
+            fs::create_dir_all(parent).context("Failed to create backup directory")?;
+        }
+        fs::write(&backup, &content)
+            .with_context(|| format!("Failed to back up {}", path.display()))?;
+
+        let replaced: String = content
+            .split_inclusive('\n')
+            .map(|line| {
+                let (text, ending) = split_line_ending(line);
+                format!("{}{ending}", re.replace_all(text, replacement))
+            })
+            .collect();
+        fs::write(&path, replaced)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(backup_root)
+}
+
+/// Split a line into its text and trailing `\n` / `\r\n`
+fn split_line_ending(line: &str) -> (&str, &str) {
+    let text = line.trim_end_matches(['\n', '\r']);
+    (text, &line[text.len()..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn previews_then_applies_with_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let session = dir.path().join("alpha");
+        fs::create_dir_all(session.join("src")).unwrap();
+        fs::write(session.join("notes.md"), "use old_name\r\nkeep\n").unwrap();
+        fs::write(session.join("src/lib.rs"), "fn old_name() {}\nold_name();").unwrap();
+
+        let re = Regex::new(r"old_(\w+)").unwrap();
+        let edits = preview_session(&session, "alpha", &re, "new_$1");
+        assert_eq!(edits.len(), 2);
+        assert_eq!(edits[0].path, PathBuf::from("notes.md"));
+        assert_eq!(edits[0].lines[0].after, "use new_name");
+        assert_eq!(edits[1].lines.len(), 2);
+
+        let backup = apply(dir.path(), &edits, &re, "new_$1").unwrap();
+        assert_eq!(
+            fs::read_to_string(session.join("notes.md")).unwrap(),
+            "use new_name\r\nkeep\n"
+        );
+        assert_eq!(
+            fs::read_to_string(session.join("src/lib.rs")).unwrap(),
+            "fn new_name() {}\nnew_name();"
+        );
+        assert_eq!(
+            fs::read_to_string(backup.join("alpha/src/lib.rs")).unwrap(),
+            "fn old_name() {}\nold_name();"
+        );
+        assert!(preview_workspace(dir.path(), &re, "new_$1").is_empty());
+    }
+}
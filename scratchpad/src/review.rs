@@ -0,0 +1,71 @@
+//! Stale session review for `sp review`
+//!
+//! A session is stale when it hasn't been touched for a number of days and isn't snoozed.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+use crate::models::Session;
+use crate::storage::Storage;
+
+/// Sessions untouched for more than `days` days, oldest first. Sessions snoozed past
+/// `today` are left out.
+pub fn stale_sessions(
+    storage: &Storage,
+    days: u32,
+    now: DateTime<Utc>,
+    today: NaiveDate,
+) -> Result<Vec<Session>> {
+    let cutoff = now - Duration::days(days.into());
+    let mut stale: Vec<Session> = storage
+        .list_sessions()?
+        .into_iter()
+        .filter(|s| s.updated_at < cutoff)
+        .filter(|s| {
+            let meta = storage.load_meta(&s.slug).unwrap_or_default();
+            meta.snoozed_until.is_none_or(|until| until <= today)
+        })
+        .collect();
+    stale.sort_by_key(|s| s.updated_at);
+    Ok(stale)
+}
+
+/// Hide a session from review until `until`
+pub fn snooze(storage: &Storage, slug: &str, until: NaiveDate) -> Result<()> {
+    let mut meta = storage.load_meta(slug)?;
+    meta.snoozed_until = Some(until);
+    storage.save_meta(slug, &meta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Config, Context};
+
+    #[test]
+    fn skips_fresh_and_snoozed_sessions() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            workspace_path: dir.path().to_string_lossy().to_string(),
+            ..Config::default()
+        };
+        let storage = Storage::new(config, Context::User);
+        for slug in ["a", "b"] {
+            storage.create_session(&Session::new(slug), None).unwrap();
+        }
+
+        // Pretend a month has passed
+        let now = Utc::now() + Duration::days(30);
+        let today = now.date_naive();
+        assert!(stale_sessions(&storage, 60, now, today).unwrap().is_empty());
+        assert_eq!(stale_sessions(&storage, 7, now, today).unwrap().len(), 2);
+
+        snooze(&storage, "a", today + Duration::days(1)).unwrap();
+        let stale = stale_sessions(&storage, 7, now, today).unwrap();
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].slug, "b");
+
+        let later = today + Duration::days(1);
+        assert_eq!(stale_sessions(&storage, 7, now, later).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,275 @@
+//! JSON-RPC 2.0 over stdio for editor integrations
+//!
+//! One request per line on stdin, one response per line on stdout. Methods:
+//! `list`, `read`, `write`, `create`, `search`. A `write` to a session that isn't in sync
+//! is staged (see `sync::staging`), and the result says where.
+
+use std::io::{self, BufRead, Write};
+
+use anyhow::{Context as _, Result};
+use serde_json::{Value, json};
+
+use crate::models::{Config, Session};
+use crate::names::{generate_session_name, slugify_or_generate};
+use crate::naming;
+use crate::search::{self, SearchOptions};
+use crate::storage::Storage;
+use crate::sync::staging;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+pub const SESSION_NOT_FOUND: i64 = -32001;
+/// Writes against a read-only server or workspace
+pub const FORBIDDEN: i64 = -32003;
+
+/// Errors returned to the client with a JSON-RPC error code
+#[derive(Debug)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<anyhow::Error> for RpcError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::new(SERVER_ERROR, format!("{e:#}"))
+    }
+}
+
+pub fn serve_stdio(storage: &Storage, config: &Config) -> Result<()> {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout().lock();
+
+    for line in stdin.lock().lines() {
+        let line = line.context("Failed to read stdin")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = handle_line(storage, config, &line);
+        // Notifications (requests without an id) get no reply
+        if response.get("id").is_some_and(Value::is_null) && response.get("error").is_none() {
+            continue;
+        }
+        writeln!(stdout, "{response}")?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+fn handle_line(storage: &Storage, config: &Config, line: &str) -> Value {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(v) => v,
+        Err(e) => return error_response(Value::Null, RpcError::new(PARSE_ERROR, e.to_string())),
+    };
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let Some(method) = request.get("method").and_then(Value::as_str) else {
+        return error_response(id, RpcError::new(INVALID_REQUEST, "Missing method"));
+    };
+    let params = request.get("params").cloned().unwrap_or(Value::Null);
+
+    match dispatch(storage, config, method, &params) {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => error_response(id, e),
+    }
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": error.code, "message": error.message },
+    })
+}
+
+/// Execute a single method. Shared by the stdio and HTTP front ends.
+pub fn dispatch(
+    storage: &Storage,
+    config: &Config,
+    method: &str,
+    params: &Value,
+) -> Result<Value, RpcError> {
+    if config.read_only && matches!(method, "write" | "create") {
+        return Err(RpcError::new(FORBIDDEN, "Workspace is read-only"));
+    }
+    match method {
+        "list" => {
+            let sessions = storage.list_sessions()?;
+            Ok(Value::Array(
+                sessions.iter().map(|s| session_json(storage, s)).collect(),
+            ))
+        }
+        "read" => {
+            let session = find_session(storage, params)?;
+            let content = match optional_str(params, "file") {
+                Some(file) => storage
+                    .read_session_file(&session.slug, file)
+                    .with_context(|| format!("Failed to read {file}"))?,
+                None => storage.read_notes(&session.slug)?,
+            };
+            Ok(json!({ "session": session.slug, "content": content }))
+        }
+        "write" => {
+            let session = find_session(storage, params)?;
+            let content = required_str(params, "content")?;
+            let file = optional_str(params, "file");
+            match staging::write(storage, config, &session.slug, file, content)? {
+                Some((_, staged)) => {
+                    Ok(json!({ "session": session.slug, "staged": staged.staged }))
+                }
+                None => Ok(json!({ "session": session.slug })),
+            }
+        }
+        "create" => {
+            let existing = storage.existing_slugs()?;
+            let slug = match optional_str(params, "name") {
+                Some(name) => slugify_or_generate(name, &existing, config),
+                None => generate_session_name(&existing, config),
+            };
+            naming::check(config, &slug)
+                .map_err(|e| RpcError::new(INVALID_PARAMS, format!("{e:#}")))?;
+            let session = Session::new(&slug);
+            storage.create_session(&session, optional_str(params, "note"))?;
+            Ok(session_json(storage, &session))
+        }
+        "search" => {
+            let query = required_str(params, "query")?;
+            let opts = SearchOptions {
+                max_results: params
+                    .get("limit")
+                    .and_then(Value::as_u64)
+                    .map(|n| n as usize)
+                    .unwrap_or(SearchOptions::default().max_results),
+                ..SearchOptions::default()
+            };
+            let results = match optional_str(params, "session") {
+                Some(_) => {
+                    let session = find_session(storage, params)?;
+                    let dir = storage.session_dir(&session.slug);
+                    search::search_session(&dir, &session.slug, query, &opts)
+                }
+                None => search::search_workspace(&storage.workspace_path(), query, &opts),
+            };
+            Ok(Value::Array(
+                results
+                    .into_iter()
+                    .map(|m| {
+                        json!({
+                            "session": m.slug,
+                            "path": m.path,
+                            "line": m.line,
+                            "text": m.text,
+                        })
+                    })
+                    .collect(),
+            ))
+        }
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Unknown method: {method}"),
+        )),
+    }
+}
+
+pub fn session_json(storage: &Storage, session: &Session) -> Value {
+    let meta = storage.load_meta(&session.slug).unwrap_or_default();
+    json!({
+        "slug": session.slug,
+        "title": session.display_title(),
+        "created_at": session.created_at.to_rfc3339(),
+        "updated_at": session.updated_at.to_rfc3339(),
+        "path": storage.session_dir(&session.slug),
+        "links": meta.links,
+        "issue": meta.issue,
+        "summary": meta.summary,
+        "tags": meta.tags,
+        "status": meta.status,
+        "reminders": meta.reminders,
+    })
+}
+
+fn find_session(storage: &Storage, params: &Value) -> Result<Session, RpcError> {
+    let name = required_str(params, "session")?;
+    storage
+        .find_session_by_name(name)?
+        .ok_or_else(|| RpcError::new(SESSION_NOT_FOUND, format!("Session not found: {name}")))
+}
+
+fn required_str<'a>(params: &'a Value, key: &str) -> Result<&'a str, RpcError> {
+    optional_str(params, key)
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("Missing string param: {key}")))
+}
+
+fn optional_str<'a>(params: &'a Value, key: &str) -> Option<&'a str> {
+    params.get(key).and_then(Value::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Context;
+
+    fn test_storage(dir: &std::path::Path) -> (Storage, Config) {
+        let config = Config {
+            workspace_path: dir.to_string_lossy().to_string(),
+            name_generator: "static".to_string(),
+            ..Config::default()
+        };
+        (Storage::new(config.clone(), Context::User), config)
+    }
+
+    #[test]
+    fn create_write_read_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let (storage, config) = test_storage(dir.path());
+
+        let line = r#"{"jsonrpc":"2.0","id":1,"method":"create","params":{"name":"My Plan"}}"#;
+        let created = handle_line(&storage, &config, line);
+        assert_eq!(created["result"]["slug"], "my-plan");
+
+        let line = r##"{"jsonrpc":"2.0","id":2,"method":"write","params":{"session":"my","content":"# Hi"}}"##;
+        assert!(handle_line(&storage, &config, line).get("error").is_none());
+
+        let line = r#"{"jsonrpc":"2.0","id":3,"method":"read","params":{"session":"my-plan"}}"#;
+        let read = handle_line(&storage, &config, line);
+        assert_eq!(read["id"], 3);
+        assert_eq!(read["result"]["content"], "# Hi");
+    }
+
+    #[test]
+    fn reports_protocol_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let (storage, config) = test_storage(dir.path());
+
+        let parse = handle_line(&storage, &config, "{not json");
+        assert_eq!(parse["error"]["code"], PARSE_ERROR);
+
+        let unknown = handle_line(&storage, &config, r#"{"id":1,"method":"nope"}"#);
+        assert_eq!(unknown["error"]["code"], METHOD_NOT_FOUND);
+
+        let missing = handle_line(&storage, &config, r#"{"id":2,"method":"read","params":{}}"#);
+        assert_eq!(missing["error"]["code"], INVALID_PARAMS);
+
+        let config = Config {
+            naming: Some(crate::models::NamingConfig {
+                min_length: Some(10),
+                ..Default::default()
+            }),
+            ..config
+        };
+        let line = r#"{"id":3,"method":"create","params":{"name":"short"}}"#;
+        let create = handle_line(&storage, &config, line);
+        assert_eq!(create["error"]["code"], INVALID_PARAMS);
+        assert!(!dir.path().join("short").exists());
+    }
+}
@@ -0,0 +1,176 @@
+//! `sp://` links (`sp url`): `sp://<session>[/<file>]`, opening the user workspace
+//! unless `?project=<dir>` or `?workspace=<name>` says otherwise, plus registering
+//! `sp url` as the system handler for them
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context as _, Result, bail};
+
+const SCHEME: &str = "sp://";
+
+/// Where a link points
+#[derive(Debug, PartialEq)]
+pub struct Link {
+    /// Session name (can be prefix)
+    pub session: String,
+    /// File to preview, relative to the session directory
+    pub file: Option<String>,
+    /// Project whose `.scratchpad/` holds the session
+    pub project: Option<PathBuf>,
+    /// Named workspace from `[workspaces]` holding the session
+    pub workspace: Option<String>,
+}
+
+pub fn parse(url: &str) -> Result<Link> {
+    let rest = url
+        .get(..SCHEME.len())
+        .filter(|scheme| scheme.eq_ignore_ascii_case(SCHEME))
+        .map(|_| &url[SCHEME.len()..])
+        .with_context(|| format!("Not an sp:// link: {url}"))?;
+    let rest = rest.split_once('#').map_or(rest, |(before, _)| before);
+    let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let (session, file) = match path.split_once('/') {
+        Some((session, file)) => (session, Some(file.trim_end_matches('/'))),
+        None => (path, None),
+    };
+    let session = decode(session);
+    if session.is_empty() {
+        bail!("No session in link: {url}");
+    }
+    let mut link = Link {
+        session,
+        file: file.filter(|f| !f.is_empty()).map(decode),
+        project: None,
+        workspace: None,
+    };
+    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+        match &*key {
+            "project" => link.project = Some(PathBuf::from(value.into_owned())),
+            "workspace" => link.workspace = Some(value.into_owned()),
+            _ => {}
+        }
+    }
+    if link.project.is_some() && link.workspace.is_some() {
+        bail!("A link can name a project or a workspace, not both: {url}");
+    }
+    Ok(link)
+}
+
+/// Decode a percent-encoded path segment (a literal `+` stays a `+`)
+fn decode(segment: &str) -> String {
+    form_urlencoded::parse(format!("v={}", segment.replace('+', "%2B")).as_bytes())
+        .next()
+        .map(|(_, v)| v.into_owned())
+        .unwrap_or_default()
+}
+
+/// Make `exe url <link>` the handler for `sp://` links. Returns what was done, one
+/// line each.
+pub fn register(exe: &Path) -> Result<Vec<String>> {
+    if cfg!(target_os = "windows") {
+        register_windows(exe)
+    } else if cfg!(target_os = "macos") {
+        bail!(
+            "macOS only takes URL handlers from app bundles; wrap `{} url` in one (e.g. with \
+             Automator or Platypus) that declares the `sp` scheme",
+            exe.display()
+        )
+    } else {
+        register_xdg(exe)
+    }
+}
+
+/// Name of the desktop entry that handles `sp://` on freedesktop systems
+const DESKTOP_FILE: &str = "sp-url.desktop";
+
+fn register_xdg(exe: &Path) -> Result<Vec<String>> {
+    let dir = directories::BaseDirs::new()
+        .context("Could not determine the data directory")?
+        .data_dir()
+        .join("applications");
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = dir.join(DESKTOP_FILE);
+    // Terminal=true: the TUI needs one, and browsers don't launch handlers in one
+    let entry = format!(
+        "[Desktop Entry]\nType=Application\nName=Scratchpad\nExec=\"{}\" url %u\n\
+         Terminal=true\nNoDisplay=true\nMimeType=x-scheme-handler/sp;\n",
+        exe.display()
+    );
+    fs::write(&path, entry).with_context(|| format!("Failed to write {}", path.display()))?;
+    let mut done = vec![format!("Wrote {}", path.display())];
+    let status = Command::new("xdg-mime")
+        .args(["default", DESKTOP_FILE, "x-scheme-handler/sp"])
+        .status();
+    match status {
+        Ok(status) if status.success() => {
+            done.push("Set it as the default for x-scheme-handler/sp".to_string())
+        }
+        _ => done.push(format!(
+            "Could not run xdg-mime; set {DESKTOP_FILE} as the handler for \
+             x-scheme-handler/sp yourself"
+        )),
+    }
+    Ok(done)
+}
+
+fn register_windows(exe: &Path) -> Result<Vec<String>> {
+    let key = r"HKCU\Software\Classes\sp";
+    let command = format!("\"{}\" url \"%1\"", exe.display());
+    let entries: [(String, Option<&str>, &str); 3] = [
+        (key.to_string(), None, "URL:Scratchpad"),
+        (key.to_string(), Some("URL Protocol"), ""),
+        (format!(r"{key}\shell\open\command"), None, &command),
+    ];
+    for (path, name, data) in &entries {
+        let mut cmd = Command::new("reg");
+        cmd.args(["add", path]);
+        match name {
+            Some(name) => cmd.args(["/v", name]),
+            None => cmd.arg("/ve"),
+        };
+        let status = cmd
+            .args(["/d", data, "/f"])
+            .status()
+            .context("Failed to run reg")?;
+        if !status.success() {
+            bail!("reg add {path} failed");
+        }
+    }
+    Ok(vec![format!("Registered {key}")])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_session_and_file() {
+        let link = parse("sp://quantum-reactor/notes/plan%20v2.md").unwrap();
+        assert_eq!(link.session, "quantum-reactor");
+        assert_eq!(link.file.as_deref(), Some("notes/plan v2.md"));
+        assert_eq!(link.project, None);
+
+        let link = parse("SP://quantum-reactor/").unwrap();
+        assert_eq!(link.session, "quantum-reactor");
+        assert_eq!(link.file, None);
+    }
+
+    #[test]
+    fn parses_where_the_session_lives() {
+        let link = parse("sp://alpha?project=%2Fsrc%2Fapp#top").unwrap();
+        assert_eq!(link.project, Some(PathBuf::from("/src/app")));
+        let link = parse("sp://alpha/a.md?workspace=work").unwrap();
+        assert_eq!(link.workspace.as_deref(), Some("work"));
+        assert_eq!(link.file.as_deref(), Some("a.md"));
+        assert!(parse("sp://alpha?project=/src&workspace=work").is_err());
+    }
+
+    #[test]
+    fn rejects_other_links() {
+        assert!(parse("https://example.com/alpha").is_err());
+        assert!(parse("sp://").is_err());
+        assert!(parse("sp:///plan.md").is_err());
+    }
+}
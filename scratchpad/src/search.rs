@@ -0,0 +1,321 @@
+//! Full-text search over session files
+//!
+//! The scanner itself is generic over a line predicate, so other line-oriented views
+//! (e.g. TODO aggregation) share the same file walking and limits.
+//!
+//! Files are scanned on a small pool of worker threads. Large files and binaries are
+//! skipped, and all workers stop as soon as the match limit is reached.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+
+use crate::spignore::IgnoreRules;
+
+/// Files larger than this are not searched (agent logs, datasets, build output)
+pub const MAX_FILE_SIZE: u64 = 2 * 1024 * 1024;
+
+/// Bytes inspected for NUL characters to decide whether a file is binary
+const BINARY_SNIFF_LEN: usize = 8 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    pub max_results: usize,
+    pub max_file_size: u64,
+    /// Only search files with these extensions (all files when empty)
+    pub extensions: Vec<&'static str>,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            max_results: 200,
+            max_file_size: MAX_FILE_SIZE,
+            extensions: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchMatch {
+    /// Session slug the file belongs to
+    pub slug: String,
+    /// Path relative to the session directory
+    pub path: PathBuf,
+    /// 1-based line number
+    pub line: usize,
+    pub text: String,
+}
+
+/// Search every session in the workspace for `query` (case-insensitive substring).
+/// Results are sorted by session, path, and line.
+pub fn search_workspace(workspace: &Path, query: &str, opts: &SearchOptions) -> Vec<SearchMatch> {
+    let query = query.to_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    scan_workspace(workspace, opts, &|line| {
+        line.to_lowercase().contains(&query)
+    })
+}
+
+/// Collect every line accepted by `matcher` across all sessions in the workspace
+pub fn scan_workspace(
+    workspace: &Path,
+    opts: &SearchOptions,
+    matcher: &Matcher<'_>,
+) -> Vec<SearchMatch> {
+    let mut files = Vec::new();
+    if let Ok(entries) = fs::read_dir(workspace) {
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+            if path.is_dir() && !name.starts_with('.') {
+                let rules = IgnoreRules::for_session(&path);
+                collect_files(&path, &path, &name, &rules, &mut files);
+            }
+        }
+    }
+    search_files(files, opts, matcher)
+}
+
+/// Search a single session directory for `query`
+pub fn search_session(
+    session_dir: &Path,
+    slug: &str,
+    query: &str,
+    opts: &SearchOptions,
+) -> Vec<SearchMatch> {
+    let query = query.to_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    scan_session(session_dir, slug, opts, &|line| {
+        line.to_lowercase().contains(&query)
+    })
+}
+
+/// Collect every line accepted by `matcher` in a single session directory
+pub fn scan_session(
+    session_dir: &Path,
+    slug: &str,
+    opts: &SearchOptions,
+    matcher: &Matcher<'_>,
+) -> Vec<SearchMatch> {
+    let mut files = Vec::new();
+    let rules = IgnoreRules::for_session(session_dir);
+    collect_files(session_dir, session_dir, slug, &rules, &mut files);
+    search_files(files, opts, matcher)
+}
+
+/// Every file in a session that search would look at (hidden and `.spignore`d paths
+/// excluded), unsorted
+pub fn session_files(session_dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let rules = IgnoreRules::for_session(session_dir);
+    collect_files(session_dir, session_dir, "", &rules, &mut files);
+    files.into_iter().map(|job| job.path).collect()
+}
+
+/// Line predicate shared by the scanning workers
+pub type Matcher<'a> = dyn Fn(&str) -> bool + Sync + 'a;
+
+struct FileJob {
+    slug: String,
+    root: PathBuf,
+    path: PathBuf,
+}
+
+fn collect_files(
+    root: &Path,
+    dir: &Path,
+    slug: &str,
+    rules: &IgnoreRules,
+    files: &mut Vec<FileJob>,
+) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        let is_dir = path.is_dir();
+        if rules.is_ignored(&path, is_dir) {
+            continue;
+        }
+        if is_dir {
+            collect_files(root, &path, slug, rules, files);
+        } else {
+            files.push(FileJob {
+                slug: slug.to_string(),
+                root: root.to_path_buf(),
+                path,
+            });
+        }
+    }
+}
+
+fn search_files(
+    files: Vec<FileJob>,
+    opts: &SearchOptions,
+    matcher: &Matcher<'_>,
+) -> Vec<SearchMatch> {
+    let files: Vec<FileJob> = files
+        .into_iter()
+        .filter(|job| {
+            opts.extensions.is_empty()
+                || job
+                    .path
+                    .extension()
+                    .is_some_and(|ext| opts.extensions.iter().any(|e| ext == *e))
+        })
+        .collect();
+    if files.is_empty() || opts.max_results == 0 {
+        return Vec::new();
+    }
+
+    let next = AtomicUsize::new(0);
+    let found = AtomicUsize::new(0);
+    let done = AtomicBool::new(false);
+    let results = Mutex::new(Vec::new());
+
+    let workers = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+        .min(files.len());
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                while !done.load(Ordering::Relaxed) {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(job) = files.get(i) else {
+                        break;
+                    };
+                    let matches = search_file(job, opts, matcher);
+                    if matches.is_empty() {
+                        continue;
+                    }
+                    let total = found.fetch_add(matches.len(), Ordering::Relaxed) + matches.len();
+                    if total >= opts.max_results {
+                        done.store(true, Ordering::Relaxed);
+                    }
+                    results.lock().unwrap().extend(matches);
+                }
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by(|a, b| (&a.slug, &a.path, a.line).cmp(&(&b.slug, &b.path, b.line)));
+    results.truncate(opts.max_results);
+    results
+}
+
+fn search_file(job: &FileJob, opts: &SearchOptions, matcher: &Matcher<'_>) -> Vec<SearchMatch> {
+    let too_big = fs::metadata(&job.path)
+        .map(|m| m.len() > opts.max_file_size)
+        .unwrap_or(true);
+    if too_big {
+        return Vec::new();
+    }
+
+    let Ok(bytes) = fs::read(&job.path) else {
+        return Vec::new();
+    };
+    if is_binary(&bytes) {
+        return Vec::new();
+    }
+
+    let relative = job
+        .path
+        .strip_prefix(&job.root)
+        .unwrap_or(&job.path)
+        .to_path_buf();
+
+    String::from_utf8_lossy(&bytes)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| matcher(line))
+        .take(opts.max_results)
+        .map(|(i, line)| SearchMatch {
+            slug: job.slug.clone(),
+            path: relative.clone(),
+            line: i + 1,
+            text: line.to_string(),
+        })
+        .collect()
+}
+
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(BINARY_SNIFF_LEN)].contains(&0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_matches_and_skips_binary_and_large_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let session = dir.path().join("alpha");
+        fs::create_dir_all(session.join("sub")).unwrap();
+        fs::write(session.join("notes.md"), "first\nFix the Race\nlast\n").unwrap();
+        fs::write(session.join("sub/log.txt"), "race again\n").unwrap();
+        fs::write(session.join("blob.bin"), b"race\0\0\0").unwrap();
+        fs::write(session.join("big.log"), "race\n".repeat(100)).unwrap();
+        fs::create_dir_all(dir.path().join(".sync")).unwrap();
+        fs::write(dir.path().join(".sync/journal"), "race\n").unwrap();
+
+        let opts = SearchOptions {
+            max_results: 10,
+            max_file_size: 100,
+            ..SearchOptions::default()
+        };
+        let results = search_workspace(dir.path(), "race", &opts);
+        let found: Vec<_> = results
+            .iter()
+            .map(|m| (m.path.to_string_lossy().to_string(), m.line))
+            .collect();
+        assert_eq!(
+            found,
+            vec![("notes.md".to_string(), 2), ("sub/log.txt".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn skips_spignored_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let session = dir.path().join("alpha");
+        fs::create_dir_all(session.join("node_modules/pkg")).unwrap();
+        fs::write(session.join("notes.md"), "race\n").unwrap();
+        fs::write(session.join("node_modules/pkg/index.js"), "race\n").unwrap();
+        fs::write(session.join("data.csv"), "race\n").unwrap();
+        fs::write(dir.path().join(".spignore"), "node_modules/\n").unwrap();
+        fs::write(session.join(".spignore"), "*.csv\n").unwrap();
+
+        let results = search_session(&session, "alpha", "race", &SearchOptions::default());
+        let paths: Vec<_> = results.iter().map(|m| m.path.clone()).collect();
+        assert_eq!(paths, vec![PathBuf::from("notes.md")]);
+    }
+
+    #[test]
+    fn stops_at_match_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let session = dir.path().join("alpha");
+        fs::create_dir_all(&session).unwrap();
+        for i in 0..20 {
+            fs::write(session.join(format!("{i}.md")), "hit\nhit\n").unwrap();
+        }
+
+        let opts = SearchOptions {
+            max_results: 5,
+            ..SearchOptions::default()
+        };
+        assert_eq!(search_workspace(dir.path(), "HIT", &opts).len(), 5);
+    }
+}
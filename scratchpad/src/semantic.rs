@@ -0,0 +1,407 @@
+//! Opt-in semantic search over session notes (`sp index build`, `sp search --semantic`)
+//!
+//! Notes are split into paragraph-sized chunks and embedded either by `[embeddings]
+//! command` (chunk text on stdin, a JSON array or whitespace-separated floats on stdout)
+//! or by a local Ollama-compatible server. Vectors are kept in a hidden file in the
+//! workspace and only recomputed for files that changed since the last build.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Write as _;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use anyhow::{Context as _, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::models::EmbeddingsConfig;
+use crate::search;
+use crate::storage::Storage;
+
+pub const INDEX_FILE: &str = ".embeddings.json";
+
+const NOTE_EXTENSIONS: &[&str] = &["md", "markdown", "txt"];
+
+/// Target chunk length in characters; paragraphs are packed up to this size
+const CHUNK_CHARS: usize = 1500;
+
+const PREVIEW_CHARS: usize = 80;
+
+const DEFAULT_OLLAMA_URL: &str = "http://localhost:11434";
+const DEFAULT_OLLAMA_MODEL: &str = "nomic-embed-text";
+const OLLAMA_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub enum Embedder {
+    Command(String),
+    Ollama { url: String, model: String },
+}
+
+impl Embedder {
+    pub fn from_config(config: Option<&EmbeddingsConfig>) -> Self {
+        let config = config.cloned().unwrap_or_default();
+        match config.command {
+            Some(command) => Embedder::Command(command),
+            None => Embedder::Ollama {
+                url: config.url.unwrap_or_else(|| DEFAULT_OLLAMA_URL.to_string()),
+                model: config
+                    .model
+                    .unwrap_or_else(|| DEFAULT_OLLAMA_MODEL.to_string()),
+            },
+        }
+    }
+
+    /// Identifies the vector space; an index built by another backend is rebuilt
+    fn id(&self) -> String {
+        match self {
+            Embedder::Command(command) => format!("command:{command}"),
+            Embedder::Ollama { model, .. } => format!("ollama:{model}"),
+        }
+    }
+
+    pub fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        match self {
+            Embedder::Command(command) => embed_with_command(command, text),
+            Embedder::Ollama { url, model } => embed_with_ollama(url, model, text),
+        }
+    }
+}
+
+fn embed_with_command(command: &str, text: &str) -> Result<Vec<f32>> {
+    let mut parts = command.split_whitespace();
+    let program = parts.next().context("`[embeddings] command` is empty")?;
+    let mut child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .with_context(|| format!("Failed to run {program}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!("{program} exited with {}", output.status);
+    }
+    parse_vector(&String::from_utf8_lossy(&output.stdout))
+        .with_context(|| format!("{program} did not print an embedding"))
+}
+
+fn embed_with_ollama(url: &str, model: &str, text: &str) -> Result<Vec<f32>> {
+    #[derive(Deserialize)]
+    struct Response {
+        embedding: Vec<f32>,
+    }
+    let endpoint = format!("{}/api/embeddings", url.trim_end_matches('/'));
+    let response: Response = ureq::post(&endpoint)
+        .timeout(OLLAMA_TIMEOUT)
+        .send_json(serde_json::json!({ "model": model, "prompt": text }))
+        .with_context(|| format!("Embedding request to {endpoint} failed"))?
+        .into_json()
+        .context("Invalid embedding response")?;
+    Ok(response.embedding)
+}
+
+/// A JSON array of numbers, or whitespace-separated numbers
+fn parse_vector(output: &str) -> Result<Vec<f32>> {
+    let output = output.trim();
+    let vector: Vec<f32> = if output.starts_with('[') {
+        serde_json::from_str(output)?
+    } else {
+        output
+            .split_whitespace()
+            .map(|n| n.parse().map_err(|_| anyhow!("Not a number: {n}")))
+            .collect::<Result<_>>()?
+    };
+    if vector.is_empty() {
+        bail!("Empty embedding");
+    }
+    Ok(vector)
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Index {
+    backend: String,
+    chunks: Vec<Chunk>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Chunk {
+    slug: String,
+    /// Path relative to the session directory
+    path: PathBuf,
+    /// File mtime (seconds) when the chunk was embedded
+    modified: u64,
+    preview: String,
+    vector: Vec<f32>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct BuildStats {
+    pub embedded: usize,
+    pub reused: usize,
+    pub files: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hit {
+    pub slug: String,
+    pub path: PathBuf,
+    pub score: f32,
+    pub preview: String,
+}
+
+pub fn index_path(storage: &Storage) -> PathBuf {
+    storage.workspace_path().join(INDEX_FILE)
+}
+
+pub fn load(storage: &Storage) -> Result<Option<Index>> {
+    let path = index_path(storage);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(&path).context("Failed to read the embeddings index")?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .context("Embeddings index is corrupt; run `sp index build --rebuild`")
+}
+
+/// Embed every note in the workspace, reusing vectors of unchanged files unless `rebuild`.
+/// Encrypted sessions are skipped so their plaintext never reaches the embedder.
+pub fn build(storage: &Storage, embedder: &Embedder, rebuild: bool) -> Result<BuildStats> {
+    let previous = match load(storage)? {
+        Some(index) if !rebuild && index.backend == embedder.id() => index,
+        _ => Index::default(),
+    };
+    let mut cached: HashMap<(String, PathBuf), Vec<Chunk>> = HashMap::new();
+    for chunk in previous.chunks {
+        cached
+            .entry((chunk.slug.clone(), chunk.path.clone()))
+            .or_default()
+            .push(chunk);
+    }
+
+    let mut index = Index {
+        backend: embedder.id(),
+        chunks: Vec::new(),
+    };
+    let mut stats = BuildStats::default();
+    for session in storage.list_sessions()? {
+        if storage
+            .load_meta(&session.slug)
+            .is_ok_and(|meta| meta.encrypted)
+        {
+            continue;
+        }
+        let dir = storage.session_dir(&session.slug);
+        for path in search::session_files(&dir) {
+            if !path
+                .extension()
+                .is_some_and(|ext| NOTE_EXTENSIONS.iter().any(|e| ext == *e))
+            {
+                continue;
+            }
+            let Ok(meta) = fs::metadata(&path) else {
+                continue;
+            };
+            if meta.len() > search::MAX_FILE_SIZE {
+                continue;
+            }
+            let modified = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_secs());
+            let relative = path.strip_prefix(&dir).unwrap_or(&path).to_path_buf();
+            stats.files += 1;
+
+            let key = (session.slug.clone(), relative.clone());
+            if let Some(chunks) = cached.remove(&key)
+                && chunks.iter().all(|c| c.modified == modified)
+            {
+                stats.reused += chunks.len();
+                index.chunks.extend(chunks);
+                continue;
+            }
+            let Ok(content) = fs::read_to_string(&path) else {
+                continue;
+            };
+            for text in chunk_text(&content) {
+                let vector = embedder.embed(&text).with_context(|| {
+                    format!("Failed to embed {}/{}", session.slug, relative.display())
+                })?;
+                index.chunks.push(Chunk {
+                    slug: session.slug.clone(),
+                    path: relative.clone(),
+                    modified,
+                    preview: preview(&text),
+                    vector,
+                });
+                stats.embedded += 1;
+            }
+        }
+    }
+
+    let json = serde_json::to_string(&index)?;
+    fs::write(index_path(storage), json).context("Failed to write the embeddings index")?;
+    Ok(stats)
+}
+
+/// Sessions ranked by their best-matching chunk, most similar first
+pub fn search(index: &Index, embedder: &Embedder, query: &str, limit: usize) -> Result<Vec<Hit>> {
+    if index.backend != embedder.id() {
+        bail!("The index was built with another embedder; run `sp index build --rebuild`");
+    }
+    let query = embedder.embed(query)?;
+    let mut best: HashMap<&str, Hit> = HashMap::new();
+    for chunk in &index.chunks {
+        let score = cosine(&query, &chunk.vector);
+        if best
+            .get(chunk.slug.as_str())
+            .is_some_and(|hit| hit.score >= score)
+        {
+            continue;
+        }
+        best.insert(
+            &chunk.slug,
+            Hit {
+                slug: chunk.slug.clone(),
+                path: chunk.path.clone(),
+                score,
+                preview: chunk.preview.clone(),
+            },
+        );
+    }
+    let mut hits: Vec<Hit> = best.into_values().collect();
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits.truncate(limit);
+    Ok(hits)
+}
+
+impl Index {
+    pub fn sessions(&self) -> usize {
+        self.chunks
+            .iter()
+            .map(|c| &c.slug)
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn backend(&self) -> &str {
+        &self.backend
+    }
+}
+
+/// Pack paragraphs into chunks of about `CHUNK_CHARS`; longer paragraphs are split
+fn chunk_text(content: &str) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for paragraph in content
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+    {
+        if !current.is_empty() && current.len() + paragraph.len() > CHUNK_CHARS {
+            chunks.push(std::mem::take(&mut current));
+        }
+        let chars: Vec<char> = paragraph.chars().collect();
+        for piece in chars.chunks(CHUNK_CHARS) {
+            if !current.is_empty() {
+                current.push_str("\n\n");
+            }
+            current.extend(piece);
+            if current.len() >= CHUNK_CHARS {
+                chunks.push(std::mem::take(&mut current));
+            }
+        }
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+fn preview(text: &str) -> String {
+    let line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut preview: String = line.chars().take(PREVIEW_CHARS).collect();
+    if line.chars().count() > PREVIEW_CHARS {
+        preview.push('…');
+    }
+    preview
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let denom = norm(a) * norm(b);
+    if denom == 0.0 { 0.0 } else { dot / denom }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Config, Context, Session};
+
+    #[test]
+    fn parses_vectors_and_chunks_text() {
+        assert_eq!(parse_vector("[0.5, -1]\n").unwrap(), vec![0.5, -1.0]);
+        assert_eq!(parse_vector("1 2\n3").unwrap(), vec![1.0, 2.0, 3.0]);
+        assert!(parse_vector("").is_err());
+        assert!(parse_vector("error: no model").is_err());
+
+        assert_eq!(chunk_text("a\n\nb\n\n\n"), vec!["a\n\nb".to_string()]);
+        let long = "x".repeat(CHUNK_CHARS * 2 + 10);
+        assert_eq!(chunk_text(&long).len(), 3);
+        assert!((cosine(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert_eq!(cosine(&[1.0, 0.0], &[0.0, 1.0]), 0.0);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn builds_incrementally_and_ranks_sessions() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            workspace_path: dir.path().to_string_lossy().to_string(),
+            ..Config::default()
+        };
+        let storage = Storage::new(config, Context::User);
+        storage
+            .create_session(&Session::new("queue"), Some("race in the worker queue"))
+            .unwrap();
+        storage
+            .create_session(&Session::new("css"), Some("button colors"))
+            .unwrap();
+
+        // Toy embedder: [mentions "queue", mentions "button", 1]
+        let script = dir.path().join("embed.sh");
+        fs::write(
+            &script,
+            "#!/bin/sh\nt=$(cat)\nq=0; b=0\ncase \"$t\" in *queue*) q=1;; esac\ncase \"$t\" in *button*) b=1;; esac\necho \"[$q, $b, 1]\"\n",
+        )
+        .unwrap();
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        let embedder = Embedder::Command(script.to_string_lossy().to_string());
+
+        let stats = build(&storage, &embedder, false).unwrap();
+        assert_eq!((stats.embedded, stats.reused, stats.files), (2, 0, 2));
+        let stats = build(&storage, &embedder, false).unwrap();
+        assert_eq!((stats.embedded, stats.reused), (0, 2));
+
+        let index = load(&storage).unwrap().unwrap();
+        assert_eq!(index.sessions(), 2);
+        let hits = search(&index, &embedder, "that queue fix", 10).unwrap();
+        assert_eq!(hits[0].slug, "queue");
+        assert_eq!(hits[0].preview, "race in the worker queue");
+        assert!(hits[0].score > hits[1].score);
+        assert_eq!(search(&index, &embedder, "q", 1).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,429 @@
+//! Named point-in-time copies of a session for `sp snapshot`
+//!
+//! Snapshots are gzipped tarballs in the session's hidden `.snapshots/` directory, named
+//! `<timestamp>[-<label>].tar.gz`. They cover the files a backup would (respecting
+//! `.gitignore` and `.spignore`) minus session metadata and `.git`, so restoring leaves
+//! ignored files such as `node_modules/`, run history and repositories alone.
+//!
+//! Large files are kept out of the tarballs: they are stored once per content hash in
+//! `.snapshots/objects/` and listed in a manifest inside the tarball, so snapshotting a
+//! session with big unchanged artifacts again costs no extra space. Objects are written
+//! with a reflink where the filesystem supports it, restored the same way (never
+//! hardlinked, since an in-place edit would change the snapshot too), and hardlinked into
+//! the throwaway copies `sp snapshot diff` compares.
+
+use std::fs::{self, File};
+use std::io::{self, Read as _};
+use std::path::{Component, Path, PathBuf};
+use std::process;
+
+use anyhow::{Context as _, Result};
+use chrono::{Local, NaiveDateTime};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use sha2::{Digest, Sha256};
+
+use crate::names::slugify;
+use crate::spignore::IgnoreRules;
+use crate::storage::META_FILE;
+
+/// Hidden session directory holding snapshots
+pub const SNAPSHOTS_DIR: &str = ".snapshots";
+
+/// Content-addressed store for large files, shared by a session's snapshots
+const OBJECTS_DIR: &str = "objects";
+
+/// Tarball entry listing `<hash>\t<path>` for files kept in the object store
+const MANIFEST: &str = ".sp-objects";
+
+/// Files at least this large go to the object store instead of the tarball
+const OBJECT_MIN_SIZE: u64 = 1024 * 1024;
+
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+const EXTENSION: &str = ".tar.gz";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// File name without the extension: `<timestamp>[-<label>]`
+    pub name: String,
+    pub label: Option<String>,
+    pub created: NaiveDateTime,
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// Snapshot the session's files. Returns the new snapshot.
+pub fn create(session_dir: &Path, label: Option<&str>) -> Result<Snapshot> {
+    let label = match label {
+        Some(l) => Some(slugify(l).with_context(|| format!("Invalid snapshot label: '{l}'"))?),
+        None => None,
+    };
+    let mut name = Local::now().format(TIMESTAMP_FORMAT).to_string();
+    if let Some(label) = &label {
+        name = format!("{name}-{label}");
+    }
+    let dir = session_dir.join(SNAPSHOTS_DIR);
+    fs::create_dir_all(&dir).context("Failed to create snapshot directory")?;
+    let path = dir.join(format!("{name}{EXTENSION}"));
+    if path.exists() {
+        anyhow::bail!("Snapshot {name} already exists");
+    }
+
+    let file =
+        File::create(&path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    let mut manifest = String::new();
+    for relative in session_files(session_dir)? {
+        let source = session_dir.join(&relative);
+        let size = fs::metadata(&source).map(|m| m.len()).unwrap_or(0);
+        if size >= OBJECT_MIN_SIZE && relative.to_str().is_some() {
+            let hash = store_object(&dir.join(OBJECTS_DIR), &source)?;
+            manifest.push_str(&format!("{hash}\t{}\n", relative.display()));
+            continue;
+        }
+        archive
+            .append_path_with_name(&source, &relative)
+            .with_context(|| format!("Failed to add {}", relative.display()))?;
+    }
+    if !manifest.is_empty() {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(manifest.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        archive
+            .append_data(&mut header, MANIFEST, manifest.as_bytes())
+            .context("Failed to add object manifest")?;
+    }
+    archive
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .context("Failed to finish snapshot")?;
+
+    parse(&path).context("Failed to read new snapshot")
+}
+
+/// A session's snapshots, newest first
+pub fn list(session_dir: &Path) -> Vec<Snapshot> {
+    let Ok(entries) = fs::read_dir(session_dir.join(SNAPSHOTS_DIR)) else {
+        return Vec::new();
+    };
+    let mut snapshots: Vec<Snapshot> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| parse(&e.path()))
+        .collect();
+    snapshots.sort_by(|a, b| b.created.cmp(&a.created).then(b.name.cmp(&a.name)));
+    snapshots
+}
+
+fn parse(path: &Path) -> Option<Snapshot> {
+    let name = path.file_name()?.to_str()?.strip_suffix(EXTENSION)?;
+    let stamp = name.get(..15)?;
+    let created = NaiveDateTime::parse_from_str(stamp, TIMESTAMP_FORMAT).ok()?;
+    let label = name
+        .get(15..)
+        .and_then(|rest| rest.strip_prefix('-'))
+        .map(str::to_string);
+    Some(Snapshot {
+        name: name.to_string(),
+        label,
+        created,
+        path: path.to_path_buf(),
+        size: fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+    })
+}
+
+/// Find a snapshot by full name or label, or a prefix of either (e.g. the date). Label
+/// matches pick the newest snapshot with that label.
+pub fn find(session_dir: &Path, query: &str) -> Result<Snapshot> {
+    let snapshots = list(session_dir);
+    if let Some(s) = snapshots
+        .iter()
+        .find(|s| s.name == query || s.label.as_deref() == Some(query))
+    {
+        return Ok(s.clone());
+    }
+    let matches: Vec<&Snapshot> = snapshots
+        .iter()
+        .filter(|s| {
+            s.name.starts_with(query) || s.label.as_ref().is_some_and(|l| l.starts_with(query))
+        })
+        .collect();
+    match matches.as_slice() {
+        [one] => Ok((*one).clone()),
+        [] => anyhow::bail!("No snapshot matching '{query}'"),
+        many => anyhow::bail!(
+            "'{query}' matches several snapshots: {}",
+            many.iter()
+                .map(|s| s.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// Replace the session's snapshotted files with the snapshot's. Files a snapshot leaves
+/// out are kept. Returns the number of files restored.
+pub fn restore(session_dir: &Path, snapshot: &Snapshot) -> Result<usize> {
+    for relative in session_files(session_dir)? {
+        let path = session_dir.join(&relative);
+        fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
+        // Drop directories the removal emptied
+        for dir in relative.ancestors().skip(1) {
+            if dir.as_os_str().is_empty() || fs::remove_dir(session_dir.join(dir)).is_err() {
+                break;
+            }
+        }
+    }
+    extract(snapshot, session_dir, false)
+}
+
+/// Unpack a snapshot into `dest`. Stored objects are hardlinked with `link_objects`
+/// (only for copies nobody edits), otherwise reflinked or copied. Returns the number of
+/// files.
+pub fn extract(snapshot: &Snapshot, dest: &Path, link_objects: bool) -> Result<usize> {
+    let file = File::open(&snapshot.path)
+        .with_context(|| format!("Failed to open {}", snapshot.path.display()))?;
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+    let mut count = 0;
+    let mut manifest = String::new();
+    for entry in archive.entries().context("Failed to read snapshot")? {
+        let mut entry = entry.context("Failed to read snapshot")?;
+        if entry.path().is_ok_and(|p| p == Path::new(MANIFEST)) {
+            entry
+                .read_to_string(&mut manifest)
+                .context("Failed to read object manifest")?;
+            continue;
+        }
+        // unpack_in refuses paths that would escape `dest`
+        if entry
+            .unpack_in(dest)
+            .context("Failed to extract snapshot")?
+            && entry.header().entry_type().is_file()
+        {
+            count += 1;
+        }
+    }
+
+    let objects = snapshot
+        .path
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join(OBJECTS_DIR);
+    for line in manifest.lines() {
+        let Some((hash, relative)) = line.split_once('\t') else {
+            continue;
+        };
+        let relative = Path::new(relative);
+        let safe = relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+        if !safe || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            anyhow::bail!("Invalid object entry in snapshot: {line}");
+        }
+        let target = dest.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let object = objects.join(hash);
+        let placed = if link_objects {
+            fs::hard_link(&object, &target).or_else(|_| clone_file(&object, &target))
+        } else {
+            clone_file(&object, &target)
+        };
+        placed.with_context(|| format!("Failed to restore {}", relative.display()))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Add a file to the object store unless its content is already there. Returns its hash.
+fn store_object(objects: &Path, source: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    let mut file =
+        File::open(source).with_context(|| format!("Failed to read {}", source.display()))?;
+    io::copy(&mut file, &mut hasher)
+        .with_context(|| format!("Failed to read {}", source.display()))?;
+    let hash: String = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+
+    let object = objects.join(&hash);
+    if !object.exists() {
+        fs::create_dir_all(objects).context("Failed to create object store")?;
+        // Write under a temporary name so an interrupted copy never looks complete
+        let partial = objects.join(format!("{hash}.partial"));
+        clone_file(source, &partial)
+            .and_then(|()| fs::rename(&partial, &object))
+            .with_context(|| format!("Failed to store {}", source.display()))?;
+    }
+    Ok(hash)
+}
+
+/// Copy a file, sharing its blocks (reflink) when the filesystem supports it
+fn clone_file(source: &Path, dest: &Path) -> io::Result<()> {
+    if dest.exists() {
+        fs::remove_file(dest)?;
+    }
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::AsRawFd;
+        let src = File::open(source)?;
+        let dst = File::create(dest)?;
+        // SAFETY: both descriptors are open for the duration of the call
+        if unsafe { libc::ioctl(dst.as_raw_fd(), libc::FICLONE, src.as_raw_fd()) } == 0 {
+            return Ok(());
+        }
+    }
+    // std::fs::copy already clones on macOS (clonefile) where possible
+    fs::copy(source, dest).map(|_| ())
+}
+
+/// Files a snapshot of the session contains, relative to the session directory
+fn session_files(session_dir: &Path) -> Result<Vec<PathBuf>> {
+    let rules = IgnoreRules::for_session(session_dir);
+    let skipped = [session_dir.join(SNAPSHOTS_DIR), session_dir.join(META_FILE)];
+    let walker = ignore::WalkBuilder::new(session_dir)
+        .hidden(false)
+        .parents(false)
+        .require_git(false)
+        .filter_entry(move |entry| {
+            let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+            !skipped.iter().any(|p| p == entry.path())
+                && entry.file_name() != ".git"
+                && !rules.is_ignored(entry.path(), is_dir)
+        })
+        .build();
+    let mut files = Vec::new();
+    for entry in walker {
+        let entry = entry.context("Failed to walk session")?;
+        if entry.file_type().is_some_and(|t| t.is_file()) {
+            let relative = entry
+                .path()
+                .strip_prefix(session_dir)
+                .unwrap_or(entry.path());
+            files.push(relative.to_path_buf());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Run `diff -ruN` between a snapshot and the session's current files (or a second
+/// snapshot). Returns whether there were differences.
+pub fn diff(session_dir: &Path, old: &Snapshot, new: Option<&Snapshot>) -> Result<bool> {
+    let temp = tempdir()?;
+    let result = (|| {
+        fs::create_dir_all(temp.join(&old.name))?;
+        extract(old, &temp.join(&old.name), true)?;
+        let new_name = match new {
+            Some(snapshot) => {
+                fs::create_dir_all(temp.join(&snapshot.name))?;
+                extract(snapshot, &temp.join(&snapshot.name), true)?;
+                snapshot.name.clone()
+            }
+            None => {
+                let current = temp.join("current");
+                fs::create_dir_all(&current)?;
+                for relative in session_files(session_dir)? {
+                    let dest = current.join(&relative);
+                    if let Some(parent) = dest.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::copy(session_dir.join(&relative), &dest)?;
+                }
+                "current".to_string()
+            }
+        };
+        let status = process::Command::new("diff")
+            .args(["-ruN", &old.name, &new_name])
+            .current_dir(&temp)
+            .status()
+            .context("Failed to run diff")?;
+        match status.code() {
+            Some(0) => Ok(false),
+            Some(1) => Ok(true),
+            _ => anyhow::bail!("diff failed"),
+        }
+    })();
+    let _ = fs::remove_dir_all(&temp);
+    result
+}
+
+fn tempdir() -> Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!(
+        "sp-snapshot-{}-{}",
+        std::process::id(),
+        rand::random::<u32>()
+    ));
+    fs::create_dir_all(&dir).context("Failed to create temporary directory")?;
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_restore_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let session = dir.path().join("alpha");
+        fs::create_dir_all(session.join("src")).unwrap();
+        fs::create_dir_all(session.join("node_modules")).unwrap();
+        fs::write(session.join("notes.md"), "v1").unwrap();
+        fs::write(session.join("src/lib.rs"), "fn a() {}").unwrap();
+        fs::write(session.join("node_modules/dep.js"), "dep").unwrap();
+        fs::write(session.join(".spignore"), "node_modules/\n").unwrap();
+        fs::write(session.join(META_FILE), "due = 2026-01-01\n").unwrap();
+
+        let snap = create(&session, Some("Before Refactor")).unwrap();
+        assert_eq!(snap.label.as_deref(), Some("before-refactor"));
+        assert_eq!(find(&session, "before-refactor").unwrap(), snap);
+        assert_eq!(find(&session, &snap.name[..8]).unwrap(), snap);
+        assert_eq!(find(&session, "before").unwrap(), snap);
+        assert!(find(&session, "nope").is_err());
+
+        // The agent rewrites everything
+        fs::write(session.join("notes.md"), "v2").unwrap();
+        fs::remove_dir_all(session.join("src")).unwrap();
+        fs::create_dir_all(session.join("gen")).unwrap();
+        fs::write(session.join("gen/out.rs"), "generated").unwrap();
+
+        assert_eq!(restore(&session, &snap).unwrap(), 3);
+        assert_eq!(fs::read_to_string(session.join("notes.md")).unwrap(), "v1");
+        assert!(session.join("src/lib.rs").exists());
+        assert!(!session.join("gen").exists());
+        assert!(session.join("node_modules/dep.js").exists());
+        assert!(session.join(META_FILE).exists());
+        assert_eq!(list(&session), vec![snap]);
+    }
+
+    #[test]
+    fn large_files_are_stored_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let session = dir.path().join("alpha");
+        fs::create_dir_all(session.join("data")).unwrap();
+        let big = vec![7u8; OBJECT_MIN_SIZE as usize];
+        fs::write(session.join("data/model.bin"), &big).unwrap();
+        fs::write(session.join("notes.md"), "v1").unwrap();
+
+        let first = create(&session, Some("one")).unwrap();
+        let second = create(&session, Some("two")).unwrap();
+        assert!(first.size < 10_000 && second.size < 10_000);
+        let objects: Vec<_> = fs::read_dir(session.join(SNAPSHOTS_DIR).join(OBJECTS_DIR))
+            .unwrap()
+            .collect();
+        assert_eq!(objects.len(), 1);
+
+        fs::write(session.join("data/model.bin"), b"changed").unwrap();
+        assert_eq!(restore(&session, &first).unwrap(), 2);
+        assert_eq!(fs::read(session.join("data/model.bin")).unwrap(), big);
+
+        // Restored files are copies: editing one leaves the snapshot intact
+        fs::write(session.join("data/model.bin"), b"edited").unwrap();
+        restore(&session, &second).unwrap();
+        assert_eq!(fs::read(session.join("data/model.bin")).unwrap(), big);
+    }
+}
@@ -0,0 +1,77 @@
+//! `.spignore` files: gitignore-style globs for paths that file trees, search, export and
+//! backups leave out (e.g. `node_modules/`, `*.parquet`)
+//!
+//! A workspace-level `.spignore` applies to every session; a session's own `.spignore`
+//! is read after it, so it can add patterns or re-include paths with `!`.
+
+use std::path::Path;
+
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+pub const IGNORE_FILE: &str = ".spignore";
+
+#[derive(Default)]
+pub struct IgnoreRules {
+    /// Workspace rules first, then the session's
+    matchers: Vec<Gitignore>,
+}
+
+impl IgnoreRules {
+    /// Rules for a session directory: its workspace's `.spignore`, then its own
+    pub fn for_session(session_dir: &Path) -> Self {
+        let mut matchers = Vec::new();
+        let dirs = session_dir.parent().into_iter().chain([session_dir]);
+        for dir in dirs {
+            let file = dir.join(IGNORE_FILE);
+            if !file.is_file() {
+                continue;
+            }
+            let mut builder = GitignoreBuilder::new(dir);
+            // Invalid lines are skipped; the valid ones still apply
+            let _ = builder.add(&file);
+            if let Ok(matcher) = builder.build() {
+                matchers.push(matcher);
+            }
+        }
+        Self { matchers }
+    }
+
+    /// Whether `path` (inside the session) is excluded. The last matching rule wins.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut ignored = false;
+        for matcher in &self.matchers {
+            match matcher.matched(path, is_dir) {
+                Match::Ignore(_) => ignored = true,
+                Match::Whitelist(_) => ignored = false,
+                Match::None => {}
+            }
+        }
+        ignored
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn combines_workspace_and_session_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let session = dir.path().join("exp");
+        fs::create_dir_all(&session).unwrap();
+        fs::write(dir.path().join(IGNORE_FILE), "node_modules/\n*.parquet\n").unwrap();
+        fs::write(session.join(IGNORE_FILE), "!keep.parquet\n/out\n").unwrap();
+
+        let rules = IgnoreRules::for_session(&session);
+        assert!(rules.is_ignored(&session.join("node_modules"), true));
+        assert!(rules.is_ignored(&session.join("web/node_modules"), true));
+        assert!(!rules.is_ignored(&session.join("node_modules"), false));
+        assert!(rules.is_ignored(&session.join("data/big.parquet"), false));
+        assert!(!rules.is_ignored(&session.join("keep.parquet"), false));
+        assert!(rules.is_ignored(&session.join("out"), true));
+        assert!(!rules.is_ignored(&session.join("src/out"), true));
+        assert!(!rules.is_ignored(&session.join("notes.md"), false));
+    }
+}
@@ -1,10 +1,36 @@
+use std::collections::HashMap;
 use std::fs;
+use std::io::{Read as _, Write};
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 use anyhow::{Context as _, Result};
 use chrono::{TimeZone, Utc};
 
-use crate::models::{Config, Context, FileTreeEntry, Session};
+use crate::crypto::{self, Cipher};
+use crate::git;
+use crate::models::{
+    Config, Context, FileTreeEntry, Relation, RunRecord, Session, SessionLink, SessionMeta,
+};
+use crate::names::slugify;
+use crate::spignore::IgnoreRules;
+use crate::sync::journal::{self, Event};
+
+/// Metadata file inside a session directory (hidden, so it never shows in file trees)
+pub const META_FILE: &str = ".session.toml";
+
+/// Hidden workspace directory holding archived sessions (skipped like any hidden entry)
+pub const ARCHIVE_DIR: &str = ".archive";
+
+/// Workspace dashboard note (`sp edit --index`), shown when the TUI opens without a session
+pub const DASHBOARD_FILE: &str = "_index.md";
+
+/// Starting content for a new dashboard note
+const DASHBOARD_TEMPLATE: &str =
+    "# Dashboard\n\n## Pinned\n\n- \n\n## Conventions\n\n- \n\n## Priorities\n\n- [ ] \n";
+
+/// Agent runs kept in a session's metadata
+const MAX_RUN_HISTORY: usize = 50;
 
 pub struct Storage {
     config: Config,
@@ -28,15 +54,70 @@ impl Storage {
         &self.context
     }
 
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
     pub fn switch_context(&mut self, context: Context) {
         self.context = context;
     }
 
+    /// The workspace dashboard note, which may not exist yet
+    pub fn dashboard_path(&self) -> PathBuf {
+        self.workspace_path().join(DASHBOARD_FILE)
+    }
+
+    /// The dashboard note, created from a starter template if missing
+    pub fn ensure_dashboard(&self) -> Result<PathBuf> {
+        let path = self.dashboard_path();
+        if !path.exists() {
+            fs::create_dir_all(self.workspace_path())?;
+            fs::write(&path, DASHBOARD_TEMPLATE)
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+        }
+        Ok(path)
+    }
+
     /// Get the directory for a session by slug
     pub fn session_dir(&self, slug: &str) -> PathBuf {
         self.workspace_path().join(slug)
     }
 
+    /// Resolve a path relative to a session directory, rejecting paths that escape it
+    pub fn session_file(&self, slug: &str, relative: &str) -> Result<PathBuf> {
+        let rel = Path::new(relative);
+        let escapes = rel.components().any(|c| {
+            !matches!(
+                c,
+                std::path::Component::Normal(_) | std::path::Component::CurDir
+            )
+        });
+        if relative.is_empty() || escapes {
+            anyhow::bail!("Invalid file path: {relative}");
+        }
+        Ok(self.session_dir(slug).join(rel))
+    }
+
+    /// Note a change for the next `sp sync`; only kept while a sync server is configured
+    fn journal(&self, event: Event) {
+        if self.config.server.is_some() {
+            // Best effort: without the entry, sync just re-reads more of the workspace
+            let _ = journal::record(&self.workspace_path(), event);
+        }
+    }
+
+    fn journal_write(&self, slug: &str, path: &Path) {
+        let file = path
+            .strip_prefix(self.session_dir(slug))
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string();
+        self.journal(Event::Write {
+            session: slug.to_string(),
+            file,
+        });
+    }
+
     pub fn ensure_workspace(&self) -> Result<()> {
         fs::create_dir_all(self.workspace_path())
             .context("Failed to create workspace directory")?;
@@ -61,6 +142,9 @@ impl Storage {
         fs::write(session_dir.join("notes.md"), notes_content)
             .context("Failed to create notes.md")?;
 
+        self.journal(Event::Create {
+            session: session.slug.clone(),
+        });
         Ok(())
     }
 
@@ -73,100 +157,338 @@ impl Storage {
         let mut sessions = Vec::new();
         for entry in fs::read_dir(&workspace).context("Failed to read workspace directory")? {
             let entry = entry?;
-            let path = entry.path();
-
-            // Only include directories (not files like config)
-            if !path.is_dir() {
-                continue;
-            }
-
-            // Skip hidden directories
-            if let Some(name) = path.file_name() {
-                if name.to_string_lossy().starts_with('.') {
-                    continue;
-                }
-            }
-
-            let slug = path
-                .file_name()
-                .map(|n| n.to_string_lossy().to_string())
-                .unwrap_or_default();
-
-            if slug.is_empty() {
-                continue;
+            if let Some(session) = session_from_path(&entry.path()) {
+                sessions.push(session);
             }
-
-            // Get timestamps from filesystem metadata
-            let metadata = fs::metadata(&path).ok();
-            let (created_at, updated_at) = if let Some(meta) = metadata {
-                let mtime = meta
-                    .modified()
-                    .ok()
-                    .and_then(|t| {
-                        t.duration_since(std::time::UNIX_EPOCH)
-                            .ok()
-                            .map(|d| Utc.timestamp_opt(d.as_secs() as i64, 0).unwrap())
-                    })
-                    .unwrap_or_else(Utc::now);
-
-                // Try to get creation time, fall back to mtime
-                let ctime = meta
-                    .created()
-                    .ok()
-                    .and_then(|t| {
-                        t.duration_since(std::time::UNIX_EPOCH)
-                            .ok()
-                            .map(|d| Utc.timestamp_opt(d.as_secs() as i64, 0).unwrap())
-                    })
-                    .unwrap_or(mtime);
-
-                (ctime, mtime)
-            } else {
-                let now = Utc::now();
-                (now, now)
-            };
-
-            sessions.push(Session {
-                slug,
-                created_at,
-                updated_at,
-            });
         }
 
         // Sort by updated_at descending (most recent first)
-        sessions.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.updated_at));
         Ok(sessions)
     }
 
+    /// Load a single session by exact slug, re-reading its filesystem metadata
+    pub fn load_session(&self, slug: &str) -> Option<Session> {
+        session_from_path(&self.session_dir(slug))
+    }
+
     /// Find the entry point file for a session (main.md, notes.md, readme.md, or first .md)
     pub fn find_entry_point(&self, slug: &str) -> Option<PathBuf> {
         let session_dir = self.session_dir(slug);
         find_entry_point_in_dir(&session_dir)
     }
 
+    /// The code a session's notes are about: the newest worktree made in it with
+    /// `sp worktree`, else the repository of the project scratchpad
+    pub fn project_root(&self, slug: &str) -> Option<PathBuf> {
+        let meta = self.load_meta(slug).unwrap_or_default();
+        let worktree = meta
+            .worktrees
+            .last()
+            .map(|wt| self.session_dir(slug).join(&wt.path))
+            .filter(|path| path.is_dir());
+        worktree.or_else(|| match &self.context {
+            Context::Project(path) => path.parent().map(Path::to_path_buf),
+            Context::User => None,
+        })
+    }
+
     /// Read the entry point file content
     pub fn read_notes(&self, slug: &str) -> Result<String> {
         if let Some(entry_point) = self.find_entry_point(slug) {
-            fs::read_to_string(&entry_point)
-                .with_context(|| format!("Failed to read {}", entry_point.display()))
+            self.read_text(&entry_point)
         } else {
             Ok(String::new())
         }
     }
 
     pub fn write_notes(&self, slug: &str, content: &str) -> Result<()> {
-        let notes_path = self.session_dir(slug).join("notes.md");
-        fs::write(&notes_path, content).context("Failed to write notes.md")
+        let notes_path = self.new_file_path(slug, "notes.md")?;
+        self.write_text(&notes_path, content)?;
+        self.journal_write(slug, &notes_path);
+        Ok(())
+    }
+
+    /// Append text to the entry point (notes.md if there is none), separated by a blank line
+    pub fn append_notes(&self, slug: &str, text: &str) -> Result<()> {
+        let path = match self.find_entry_point(slug) {
+            Some(path) => path,
+            None => self.new_file_path(slug, "notes.md")?,
+        };
+        let existing = if path.exists() {
+            self.read_text(&path)?
+        } else {
+            String::new()
+        };
+
+        let mut content = existing.trim_end().to_string();
+        if !content.is_empty() {
+            content.push_str("\n\n");
+        }
+        content.push_str(text.trim_end());
+        content.push('\n');
+        self.write_text(&path, &content)?;
+        self.journal_write(slug, &path);
+        Ok(())
+    }
+
+    /// Read a session file given by its plain relative path, decrypting `<file>.age` in
+    /// encrypted sessions
+    pub fn read_session_file(&self, slug: &str, relative: &str) -> Result<String> {
+        let path = self.session_file(slug, relative)?;
+        let encrypted = crypto::encrypted_path(&path);
+        if !path.exists() && encrypted.exists() {
+            return self.read_text(&encrypted);
+        }
+        self.read_text(&path)
+    }
+
+    /// Write a session file given by its plain relative path (encrypted in encrypted sessions)
+    pub fn write_session_file(&self, slug: &str, relative: &str, content: &str) -> Result<()> {
+        let path = self.new_file_path(slug, relative)?;
+        self.write_text(&path, content)?;
+        self.journal_write(slug, &path);
+        Ok(())
+    }
+
+    /// Append what `write` writes to a session file given by its plain relative path.
+    /// Plain files are appended to as it goes; in encrypted sessions the output is held
+    /// until `write` returns, then the file is re-encrypted with it added. Returns where
+    /// the file is stored.
+    pub fn append_session_file<T>(
+        &self,
+        slug: &str,
+        relative: &str,
+        write: impl FnOnce(&mut dyn Write) -> Result<T>,
+    ) -> Result<(PathBuf, T)> {
+        let path = self.new_file_path(slug, relative)?;
+        let result = if crypto::is_encrypted(&path) {
+            let mut added = Vec::new();
+            let result = write(&mut added);
+            if !added.is_empty() {
+                let mut content = if path.exists() {
+                    self.read_text(&path)?
+                } else {
+                    String::new()
+                };
+                content.push_str(&String::from_utf8_lossy(&added));
+                self.write_text(&path, &content)?;
+            }
+            result
+        } else {
+            let mut file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .with_context(|| format!("Failed to open {relative}"))?;
+            write(&mut file)
+        };
+        self.journal_write(slug, &path);
+        Ok((path, result?))
+    }
+
+    /// Where a file with this plain relative path is stored: `<file>.age` in encrypted
+    /// sessions
+    pub fn new_file_path(&self, slug: &str, relative: &str) -> Result<PathBuf> {
+        let path = self.session_file(slug, relative)?;
+        if self.load_meta(slug)?.encrypted {
+            Ok(crypto::encrypted_path(&path))
+        } else {
+            Ok(path)
+        }
+    }
+
+    /// Read a text file, decrypting it if it is a `.age` file
+    pub fn read_text(&self, path: &Path) -> Result<String> {
+        if crypto::is_encrypted(path) {
+            let plaintext = self.cipher()?.decrypt_file(path)?;
+            return String::from_utf8(plaintext)
+                .with_context(|| format!("{} is not valid UTF-8", path.display()));
+        }
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+    }
+
+    /// Write a text file, encrypting it if it is a `.age` file
+    pub fn write_text(&self, path: &Path, content: &str) -> Result<()> {
+        if crypto::is_encrypted(path) {
+            return self.cipher()?.encrypt_to_file(path, content.as_bytes());
+        }
+        fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn cipher(&self) -> Result<Cipher> {
+        Cipher::from_config(&self.config)
+    }
+
+    /// Encrypt every file of a session and mark it encrypted. Returns the number of files.
+    pub fn encrypt_session(&self, slug: &str) -> Result<usize> {
+        let count = crypto::encrypt_dir(&self.cipher()?, &self.session_dir(slug))?;
+        let mut meta = self.load_meta(slug)?;
+        meta.encrypted = true;
+        self.save_meta(slug, &meta)?;
+        Ok(count)
+    }
+
+    /// Load a session's metadata, or defaults if it has none
+    pub fn load_meta(&self, slug: &str) -> Result<SessionMeta> {
+        let path = self.session_dir(slug).join(META_FILE);
+        if !path.exists() {
+            return Ok(SessionMeta::default());
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn save_meta(&self, slug: &str, meta: &SessionMeta) -> Result<()> {
+        let path = self.session_dir(slug).join(META_FILE);
+        let content = toml::to_string_pretty(meta).context("Failed to serialize metadata")?;
+        fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+        self.journal_write(slug, &path);
+        Ok(())
+    }
+
+    /// Copy or move a file or directory into the top level of another session, encrypting
+    /// or decrypting it to match the destination. Refuses to overwrite an existing entry.
+    /// Returns the new path.
+    pub fn transfer_file(&self, src: &Path, dest_slug: &str, move_file: bool) -> Result<PathBuf> {
+        let name = src
+            .file_name()
+            .with_context(|| format!("Invalid path: {}", src.display()))?;
+        let encrypt = self.load_meta(dest_slug)?.encrypted;
+        let mut dest = self.session_dir(dest_slug).join(name);
+        if src.is_file() {
+            if encrypt {
+                dest = crypto::encrypted_path(&dest);
+            } else if crypto::is_encrypted(src) {
+                dest = dest.with_extension("");
+            }
+        }
+        if dest.exists() {
+            let name = dest.file_name().unwrap_or(name).to_string_lossy();
+            anyhow::bail!("{name} already exists in {dest_slug}");
+        }
+
+        if src.is_dir() {
+            if move_file {
+                fs::rename(src, &dest)
+                    .with_context(|| format!("Failed to move {}", src.display()))?;
+            } else {
+                copy_dir_recursive(src, &dest)?;
+            }
+            if encrypt {
+                crypto::encrypt_dir(&self.cipher()?, &dest)?;
+            } else if crypto::contains_encrypted(&dest) {
+                crypto::decrypt_dir(&self.cipher()?, &dest)?;
+            }
+        } else if encrypt != crypto::is_encrypted(src) {
+            let cipher = self.cipher()?;
+            if encrypt {
+                let plaintext =
+                    fs::read(src).with_context(|| format!("Failed to read {}", src.display()))?;
+                cipher.encrypt_to_file(&dest, &plaintext)?;
+            } else {
+                let plaintext = cipher.decrypt_file(src)?;
+                fs::write(&dest, plaintext)
+                    .with_context(|| format!("Failed to write {}", dest.display()))?;
+            }
+            if move_file {
+                fs::remove_file(src)
+                    .with_context(|| format!("Failed to remove {}", src.display()))?;
+            }
+        } else if move_file {
+            fs::rename(src, &dest).with_context(|| format!("Failed to move {}", src.display()))?;
+        } else {
+            fs::copy(src, &dest).with_context(|| format!("Failed to copy {}", src.display()))?;
+        }
+        self.journal_write(dest_slug, &dest);
+        Ok(dest)
+    }
+
+    /// Append an agent run to the session's history, keeping the most recent `MAX_RUN_HISTORY`
+    pub fn record_run(&self, slug: &str, run: RunRecord) -> Result<()> {
+        let mut meta = self.load_meta(slug)?;
+        meta.runs.push(run);
+        let excess = meta.runs.len().saturating_sub(MAX_RUN_HISTORY);
+        meta.runs.drain(..excess);
+        self.save_meta(slug, &meta)
+    }
+
+    /// Record that `other` is `rel` to `slug` (and the inverse on `other`), replacing any
+    /// earlier link between the two
+    pub fn link_sessions(&self, slug: &str, other: &str, rel: Relation) -> Result<()> {
+        if slug == other {
+            anyhow::bail!("A session cannot be linked to itself");
+        }
+        for (from, to, rel) in [(slug, other, rel), (other, slug, rel.inverse())] {
+            let mut meta = self.load_meta(from)?;
+            meta.links.retain(|l| l.session != to);
+            meta.links.push(SessionLink {
+                rel,
+                session: to.to_string(),
+            });
+            self.save_meta(from, &meta)?;
+        }
+        Ok(())
+    }
+
+    /// Remove the link between two sessions. Returns false if there was none.
+    pub fn unlink_sessions(&self, slug: &str, other: &str) -> Result<bool> {
+        let mut found = false;
+        for (from, to) in [(slug, other), (other, slug)] {
+            let mut meta = self.load_meta(from)?;
+            let before = meta.links.len();
+            meta.links.retain(|l| l.session != to);
+            if meta.links.len() != before {
+                found = true;
+                self.save_meta(from, &meta)?;
+            }
+        }
+        Ok(found)
     }
 
     pub fn delete_session(&self, slug: &str) -> Result<()> {
         let session_dir = self.session_dir(slug);
         if session_dir.exists() {
+            // Unregister worktrees so the repository doesn't keep dangling entries
+            for wt in self.load_meta(slug).unwrap_or_default().worktrees {
+                let wt_path = session_dir.join(&wt.path);
+                let _ = git::run(
+                    &wt.repo,
+                    &["worktree", "remove", "--force", &wt_path.to_string_lossy()],
+                );
+            }
             fs::remove_dir_all(&session_dir).context("Failed to delete session directory")?;
+            self.journal(Event::Delete {
+                session: slug.to_string(),
+            });
         }
         Ok(())
     }
 
+    /// Move a session into the workspace's archive directory. Returns the new location.
+    pub fn archive_session(&self, slug: &str) -> Result<PathBuf> {
+        let archive = self.workspace_path().join(ARCHIVE_DIR);
+        fs::create_dir_all(&archive).context("Failed to create archive directory")?;
+        let dest = archive.join(slug);
+        if dest.exists() {
+            anyhow::bail!("'{slug}' is already in the archive");
+        }
+        let worktrees = self.load_meta(slug).unwrap_or_default().worktrees;
+        fs::rename(self.session_dir(slug), &dest).context("Failed to archive session")?;
+        self.journal(Event::Delete {
+            session: slug.to_string(),
+        });
+        // Point the repositories at the worktrees' new location
+        for wt in worktrees {
+            let _ = git::run(
+                &wt.repo,
+                &["worktree", "repair", &dest.join(&wt.path).to_string_lossy()],
+            );
+        }
+        Ok(dest)
+    }
+
     /// Find a session by exact name or prefix match
     pub fn find_session_by_name(&self, name: &str) -> Result<Option<Session>> {
         let sessions = self.list_sessions()?;
@@ -201,7 +523,37 @@ impl Storage {
             anyhow::bail!("Session '{new_slug}' already exists");
         }
 
+        let worktrees = self.load_meta(old_slug).unwrap_or_default().worktrees;
         fs::rename(&old_dir, &new_dir).context("Failed to rename session directory")?;
+        self.journal(Event::Rename {
+            from: old_slug.to_string(),
+            to: new_slug.to_string(),
+        });
+        // Point the repositories at the worktrees' new location
+        for wt in worktrees {
+            let _ = git::run(
+                &wt.repo,
+                &[
+                    "worktree",
+                    "repair",
+                    &new_dir.join(&wt.path).to_string_lossy(),
+                ],
+            );
+        }
+
+        // Keep links from other sessions pointing at the new name
+        for link in self.load_meta(new_slug).unwrap_or_default().links {
+            let Ok(mut meta) = self.load_meta(&link.session) else {
+                continue;
+            };
+            if !self.session_dir(&link.session).exists() {
+                continue;
+            }
+            for l in meta.links.iter_mut().filter(|l| l.session == old_slug) {
+                l.session = new_slug.to_string();
+            }
+            self.save_meta(&link.session, &meta)?;
+        }
         Ok(())
     }
 
@@ -209,16 +561,102 @@ impl Storage {
     pub fn existing_slugs(&self) -> Result<Vec<String>> {
         Ok(self.list_sessions()?.into_iter().map(|s| s.slug).collect())
     }
+
+    /// Slug of the session for the project's checked-out branch. None unless
+    /// `branch_sessions` is enabled, the context is a project, and HEAD is on a branch.
+    pub fn branch_session(&self) -> Option<String> {
+        if !self.config.branch_sessions {
+            return None;
+        }
+        let Context::Project(pad) = &self.context else {
+            return None;
+        };
+        git::current_branch(pad.parent()?).and_then(|branch| branch_session_slug(&branch))
+    }
+
+    /// Create the session for `slug` unless it exists. Returns whether it was created.
+    pub fn ensure_session(&self, slug: &str) -> Result<bool> {
+        if self.session_dir(slug).is_dir() {
+            return Ok(false);
+        }
+        self.create_session(&Session::new(slug), None)?;
+        Ok(true)
+    }
+}
+
+/// Session slug for a git branch: `feature/login-form` -> `branch-feature-login-form`
+pub fn branch_session_slug(branch: &str) -> Option<String> {
+    slugify(branch).map(|slug| format!("branch-{slug}"))
+}
+
+/// Build a session from a workspace entry. Returns None for files and hidden directories.
+fn session_from_path(path: &Path) -> Option<Session> {
+    // Only include directories (not files like config)
+    if !path.is_dir() {
+        return None;
+    }
+
+    let slug = path.file_name()?.to_string_lossy().to_string();
+
+    // Skip hidden directories
+    if slug.is_empty() || slug.starts_with('.') {
+        return None;
+    }
+
+    // Get timestamps from filesystem metadata
+    let (created_at, updated_at) = if let Ok(meta) = fs::metadata(path) {
+        let mtime = meta
+            .modified()
+            .ok()
+            .and_then(|t| {
+                t.duration_since(std::time::UNIX_EPOCH)
+                    .ok()
+                    .map(|d| Utc.timestamp_opt(d.as_secs() as i64, 0).unwrap())
+            })
+            .unwrap_or_else(Utc::now);
+
+        // Try to get creation time, fall back to mtime
+        let ctime = meta
+            .created()
+            .ok()
+            .and_then(|t| {
+                t.duration_since(std::time::UNIX_EPOCH)
+                    .ok()
+                    .map(|d| Utc.timestamp_opt(d.as_secs() as i64, 0).unwrap())
+            })
+            .unwrap_or(mtime);
+
+        (ctime, mtime)
+    } else {
+        let now = Utc::now();
+        (now, now)
+    };
+
+    let created_at = fs::read_to_string(path.join(META_FILE))
+        .ok()
+        .and_then(|content| toml::from_str::<SessionMeta>(&content).ok())
+        .and_then(|meta| meta.created)
+        .unwrap_or(created_at);
+
+    Some(Session {
+        slug,
+        created_at,
+        updated_at,
+    })
 }
 
 /// Find the entry point markdown file in a directory
 pub fn find_entry_point_in_dir(dir: &Path) -> Option<PathBuf> {
-    // Priority order per spec
+    // Priority order per spec; encrypted sessions store `<name>.age`
     for name in ["main.md", "notes.md", "readme.md", "README.md"] {
         let path = dir.join(name);
         if path.exists() {
             return Some(path);
         }
+        let encrypted = crypto::encrypted_path(&path);
+        if encrypted.exists() {
+            return Some(encrypted);
+        }
     }
 
     // Fallback: first .md file alphabetically
@@ -237,11 +675,168 @@ pub fn find_entry_point_in_dir(dir: &Path) -> Option<PathBuf> {
     md_files.first().cloned()
 }
 
-/// List all files in a session directory
+/// Read at most `limit` bytes of a text file for previewing.
+/// Returns the content and whether the file was cut short. A truncated read ends at the
+/// last complete line so multi-byte characters and markdown blocks are not split mid-line.
+pub fn read_file_head(path: &Path, limit: usize) -> std::io::Result<(String, bool)> {
+    let file = fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut buf = Vec::with_capacity(len.min(limit as u64) as usize);
+    file.take(limit as u64).read_to_end(&mut buf)?;
+
+    let truncated = len > buf.len() as u64;
+    if truncated && let Some(last_newline) = buf.iter().rposition(|&b| b == b'\n') {
+        buf.truncate(last_newline + 1);
+    }
+    Ok((String::from_utf8_lossy(&buf).into_owned(), truncated))
+}
+
+/// Bytes of the entry point scanned when looking for a title heading
+const TITLE_SCAN_LIMIT: usize = 8 * 1024;
+
+/// Extract the text of the first markdown heading (`# Title`, `## Title`, ...)
+pub fn first_heading(content: &str) -> Option<String> {
+    headings(content).next().map(|heading| heading.text)
+}
+
+/// A markdown heading of a note
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heading {
+    /// 0-based line it's on
+    pub line: usize,
+    pub level: usize,
+    pub text: String,
+}
+
+/// Each markdown heading, outside code blocks
+pub fn headings(content: &str) -> impl Iterator<Item = Heading> + '_ {
+    let mut in_code_block = false;
+    content.lines().enumerate().filter_map(move |(i, line)| {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            in_code_block = !in_code_block;
+            return None;
+        }
+        if in_code_block || !trimmed.starts_with('#') {
+            return None;
+        }
+        let text = trimmed.trim_start_matches('#');
+        (text.starts_with(' ') && !text.trim().is_empty()).then(|| Heading {
+            line: i,
+            level: trimmed.len() - text.len(),
+            text: text.trim().to_string(),
+        })
+    })
+}
+
+/// Derived session titles (first heading of the entry point), cached by entry point mtime
+/// so list refreshes only re-read notes that actually changed.
+#[derive(Default)]
+pub struct TitleCache {
+    entries: HashMap<PathBuf, (SystemTime, Option<String>)>,
+}
+
+impl TitleCache {
+    pub fn title_for(&mut self, session_dir: &Path) -> Option<String> {
+        let entry_point = find_entry_point_in_dir(session_dir)?;
+        let mtime = fs::metadata(&entry_point).and_then(|m| m.modified()).ok()?;
+
+        if let Some((cached_mtime, title)) = self.entries.get(&entry_point)
+            && *cached_mtime == mtime
+        {
+            return title.clone();
+        }
+
+        // Titles of encrypted sessions would need the key on every refresh
+        let title = if crypto::is_encrypted(&entry_point) {
+            None
+        } else {
+            read_file_head(&entry_point, TITLE_SCAN_LIMIT)
+                .ok()
+                .and_then(|(content, _)| first_heading(&content))
+        };
+        self.entries.insert(entry_point, (mtime, title.clone()));
+        title
+    }
+}
+
+/// Human-readable byte count, e.g. `512B`, `3.4K`, `12M`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["K", "M", "G", "T"];
+    if bytes < 1024 {
+        return format!("{bytes}B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if value < 10.0 {
+        format!("{value:.1}{}", UNITS[unit])
+    } else {
+        format!("{value:.0}{}", UNITS[unit])
+    }
+}
+
+/// Total size in bytes of all files below `dir` (symlinks are not followed)
+pub fn dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let meta = e.path().symlink_metadata().ok()?;
+            Some(if meta.is_dir() {
+                dir_size(&e.path())
+            } else {
+                meta.len()
+            })
+        })
+        .sum()
+}
+
+/// Recursively copy the visible (non-dot) contents of `src` into `dst`, creating it
+pub fn copy_dir_recursive(src: &Path, dst: &Path) -> Result<()> {
+    copy_dir_filtered(src, dst, &IgnoreRules::default())
+}
+
+/// Copy a directory, leaving out hidden entries and paths matched by `rules`
+pub fn copy_dir_filtered(src: &Path, dst: &Path, rules: &IgnoreRules) -> Result<()> {
+    fs::create_dir_all(dst).with_context(|| format!("Failed to create {}", dst.display()))?;
+    for entry in fs::read_dir(src).with_context(|| format!("Failed to read {}", src.display()))? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let from = entry.path();
+        let to = dst.join(entry.file_name());
+        let is_dir = from.is_dir();
+        if rules.is_ignored(&from, is_dir) {
+            continue;
+        }
+        if is_dir {
+            copy_dir_filtered(&from, &to, rules)?;
+        } else {
+            fs::copy(&from, &to).with_context(|| format!("Failed to copy {}", from.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// List all files in a session directory, except those matched by `.spignore`
 pub fn list_session_files(dir: &Path) -> Vec<PathBuf> {
+    let rules = IgnoreRules::for_session(dir);
     fs::read_dir(dir)
         .ok()
-        .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path()).collect())
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| !rules.is_ignored(p, p.is_dir()))
+                .collect()
+        })
         .unwrap_or_default()
 }
 
@@ -252,13 +847,15 @@ pub fn build_file_tree(
     max_depth: usize,
 ) -> Vec<FileTreeEntry> {
     let mut entries = Vec::new();
-    build_file_tree_recursive(dir, entry_point, 0, max_depth, &[], &mut entries);
+    let rules = IgnoreRules::for_session(dir);
+    build_file_tree_recursive(dir, entry_point, &rules, 0, max_depth, &[], &mut entries);
     entries
 }
 
 fn build_file_tree_recursive(
     dir: &Path,
     entry_point: Option<&Path>,
+    rules: &IgnoreRules,
     depth: usize,
     max_depth: usize,
     ancestor_is_last: &[bool],
@@ -273,6 +870,8 @@ fn build_file_tree_recursive(
         Err(_) => return,
     };
 
+    // Symlinks count as files and aren't descended into: a link to a parent would loop,
+    // and one elsewhere would list files outside the session
     let mut children: Vec<_> = read_dir
         .filter_map(|e| e.ok())
         .filter(|e| {
@@ -281,22 +880,22 @@ fn build_file_tree_recursive(
                 .map(|n| !n.starts_with('.'))
                 .unwrap_or(false)
         })
+        .map(|e| {
+            let is_dir = e.file_type().is_ok_and(|t| t.is_dir());
+            (e, is_dir)
+        })
+        .filter(|(e, is_dir)| !rules.is_ignored(&e.path(), *is_dir))
         .collect();
 
-    children.sort_by(|a, b| {
-        let a_is_dir = a.path().is_dir();
-        let b_is_dir = b.path().is_dir();
-        match (a_is_dir, b_is_dir) {
-            (false, true) => std::cmp::Ordering::Less,
-            (true, false) => std::cmp::Ordering::Greater,
-            _ => a.file_name().cmp(&b.file_name()),
-        }
+    children.sort_by(|(a, a_is_dir), (b, b_is_dir)| match (a_is_dir, b_is_dir) {
+        (false, true) => std::cmp::Ordering::Less,
+        (true, false) => std::cmp::Ordering::Greater,
+        _ => a.file_name().cmp(&b.file_name()),
     });
 
     let total = children.len();
-    for (i, child) in children.into_iter().enumerate() {
+    for (i, (child, is_dir)) in children.into_iter().enumerate() {
         let path = child.path();
-        let is_dir = path.is_dir();
         let is_last = i == total - 1;
         let name = if is_dir {
             format!("{}/", child.file_name().to_string_lossy())
@@ -308,6 +907,7 @@ fn build_file_tree_recursive(
 
         entries.push(FileTreeEntry {
             name,
+            path: path.clone(),
             is_dir,
             depth,
             is_last,
@@ -321,6 +921,7 @@ fn build_file_tree_recursive(
             build_file_tree_recursive(
                 &path,
                 entry_point,
+                rules,
                 depth + 1,
                 max_depth,
                 &next_ancestors,
@@ -342,7 +943,9 @@ pub fn detect_context(cwd: &Path, _config: &Config) -> Context {
     Context::User
 }
 
-/// Get all available contexts from cwd
+/// Get all available contexts from cwd: the user context, then every project whose
+/// `.scratchpad/` is in cwd or a parent, nearest first (a package's before its
+/// monorepo's)
 pub fn available_contexts(cwd: &Path, _config: &Config) -> Vec<Context> {
     let mut contexts = vec![Context::User];
 
@@ -350,9 +953,561 @@ pub fn available_contexts(cwd: &Path, _config: &Config) -> Vec<Context> {
         let project_pad = ancestor.join(".scratchpad");
         if project_pad.is_dir() {
             contexts.push(Context::Project(project_pad));
-            break;
         }
     }
 
     contexts
 }
+
+/// The project in `contexts` whose scratchpad belongs to `dir`
+pub fn project_at(contexts: &[Context], dir: &Path) -> Option<Context> {
+    let dir = fs::canonicalize(dir).ok()?;
+    contexts
+        .iter()
+        .find(|context| match context {
+            Context::Project(pad) => pad
+                .parent()
+                .and_then(|root| fs::canonicalize(root).ok())
+                .is_some_and(|root| root == dir),
+            Context::User => false,
+        })
+        .cloned()
+}
+
+/// How the TUI names a context: `user`, or `project: ` and its directory. Projects are
+/// named by their path from the outermost one's parent, so nested projects read
+/// `mono`, `mono/packages/api`.
+pub fn context_label(context: &Context, contexts: &[Context]) -> String {
+    let Context::Project(pad) = context else {
+        return "user".to_string();
+    };
+    let outermost = contexts.iter().rev().find_map(|c| match c {
+        Context::Project(pad) => pad.parent().and_then(Path::parent),
+        Context::User => None,
+    });
+    let name = pad
+        .parent()
+        .zip(outermost)
+        .and_then(|(root, base)| root.strip_prefix(base).ok())
+        .filter(|path| !path.as_os_str().is_empty())
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|| context.display_name());
+    format!("project: {name}")
+}
+
+/// A workspace the TUI can switch to (`W`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkspaceChoice {
+    pub name: String,
+    pub context: Context,
+    /// `workspace_path` to use while it's open; only named workspaces change it
+    pub workspace_path: String,
+}
+
+/// The available contexts, then the `[workspaces]` configured by name
+pub fn workspace_choices(config: &Config, contexts: &[Context]) -> Vec<WorkspaceChoice> {
+    let mut choices: Vec<WorkspaceChoice> = contexts
+        .iter()
+        .map(|context| WorkspaceChoice {
+            name: context_label(context, contexts),
+            context: context.clone(),
+            workspace_path: config.workspace_path.clone(),
+        })
+        .collect();
+    choices.extend(
+        config
+            .workspaces
+            .iter()
+            .map(|(name, path)| WorkspaceChoice {
+                name: name.clone(),
+                context: Context::User,
+                workspace_path: crate::sync::expand_home(path),
+            }),
+    );
+    choices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_storage(dir: &Path) -> Storage {
+        let config = Config {
+            workspace_path: dir.to_string_lossy().to_string(),
+            ..Config::default()
+        };
+        Storage::new(config, Context::User)
+    }
+
+    #[test]
+    fn archive_hides_session_from_listing() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = test_storage(dir.path());
+        storage
+            .create_session(&Session::new("old"), Some("x"))
+            .unwrap();
+
+        let dest = storage.archive_session("old").unwrap();
+        assert_eq!(dest, dir.path().join(ARCHIVE_DIR).join("old"));
+        assert!(dest.join("notes.md").exists());
+        assert!(storage.list_sessions().unwrap().is_empty());
+
+        storage.create_session(&Session::new("old"), None).unwrap();
+        assert!(storage.archive_session("old").is_err());
+    }
+
+    #[test]
+    fn rename_repairs_worktrees() {
+        let (dir, repo) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let storage = test_storage(dir.path());
+        storage.create_session(&Session::new("old"), None).unwrap();
+        git::run(repo.path(), &["init", "--quiet"]).unwrap();
+        git::run(
+            repo.path(),
+            &[
+                "-c",
+                "user.name=t",
+                "-c",
+                "user.email=t@t",
+                "commit",
+                "--quiet",
+                "--allow-empty",
+                "-m",
+                "init",
+            ],
+        )
+        .unwrap();
+        let wt = storage.session_dir("old").join("wt");
+        git::run(
+            repo.path(),
+            &[
+                "worktree",
+                "add",
+                "--quiet",
+                "-b",
+                "sp/old",
+                &wt.to_string_lossy(),
+            ],
+        )
+        .unwrap();
+        let mut meta = storage.load_meta("old").unwrap();
+        meta.worktrees.push(crate::models::WorktreeMeta {
+            repo: repo.path().to_path_buf(),
+            path: PathBuf::from("wt"),
+            branch: "sp/old".to_string(),
+        });
+        storage.save_meta("old", &meta).unwrap();
+
+        storage.rename_session("old", "new").unwrap();
+        let moved = storage
+            .session_dir("new")
+            .join("wt")
+            .canonicalize()
+            .unwrap();
+        let list = git::run(repo.path(), &["worktree", "list", "--porcelain"]).unwrap();
+        assert!(
+            list.contains(&format!("worktree {}", moved.display())),
+            "{list}"
+        );
+        assert!(!list.contains("prunable"), "{list}");
+        assert_eq!(
+            git::run(&moved, &["branch", "--show-current"]).unwrap(),
+            "sp/old"
+        );
+    }
+
+    #[test]
+    fn transfer_file_copies_moves_and_refuses_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = test_storage(dir.path());
+        storage.create_session(&Session::new("from"), None).unwrap();
+        storage.create_session(&Session::new("to"), None).unwrap();
+        let src = storage.session_dir("from").join("plan.txt");
+        fs::write(&src, "x").unwrap();
+
+        let copied = storage.transfer_file(&src, "to", false).unwrap();
+        assert!(src.exists() && copied.exists());
+        assert!(storage.transfer_file(&src, "to", true).is_err());
+
+        fs::remove_file(&copied).unwrap();
+        storage.transfer_file(&src, "to", true).unwrap();
+        assert!(!src.exists());
+        assert!(storage.session_dir("to").join("plan.txt").exists());
+    }
+
+    #[test]
+    fn transfer_file_encrypts_and_decrypts_to_match_the_destination() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = encrypted_storage(dir.path());
+        for slug in ["plain", "secret"] {
+            storage.create_session(&Session::new(slug), None).unwrap();
+        }
+        storage.encrypt_session("secret").unwrap();
+        let plain = storage.session_dir("plain");
+        let secret = storage.session_dir("secret");
+        fs::write(plain.join("plan.txt"), "x").unwrap();
+        fs::create_dir(plain.join("refs")).unwrap();
+        fs::write(plain.join("refs/link.txt"), "y").unwrap();
+
+        let moved = storage
+            .transfer_file(&plain.join("plan.txt"), "secret", true)
+            .unwrap();
+        assert_eq!(moved, secret.join("plan.txt.age"));
+        assert!(!secret.join("plan.txt").exists());
+        assert_eq!(
+            storage.read_session_file("secret", "plan.txt").unwrap(),
+            "x"
+        );
+        storage
+            .transfer_file(&plain.join("refs"), "secret", false)
+            .unwrap();
+        assert!(secret.join("refs/link.txt.age").exists());
+        assert!(!secret.join("refs/link.txt").exists());
+
+        let back = storage.transfer_file(&moved, "plain", true).unwrap();
+        assert_eq!(back, plain.join("plan.txt"));
+        assert_eq!(fs::read_to_string(&back).unwrap(), "x");
+        fs::remove_dir_all(plain.join("refs")).unwrap();
+        storage
+            .transfer_file(&secret.join("refs"), "plain", true)
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(plain.join("refs/link.txt")).unwrap(),
+            "y"
+        );
+    }
+
+    #[test]
+    fn record_run_caps_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = test_storage(dir.path());
+        storage.create_session(&Session::new("runs"), None).unwrap();
+        for i in 0..MAX_RUN_HISTORY + 3 {
+            let run = RunRecord {
+                agent: crate::models::Agent::Claude,
+                started_at: Utc::now(),
+                duration_secs: i as u64,
+                exit_code: Some(0),
+            };
+            storage.record_run("runs", run).unwrap();
+        }
+        let runs = storage.load_meta("runs").unwrap().runs;
+        assert_eq!(runs.len(), MAX_RUN_HISTORY);
+        assert_eq!(runs[0].duration_secs, 3);
+    }
+
+    #[test]
+    fn append_notes_separates_with_blank_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = test_storage(dir.path());
+        storage.create_session(&Session::new("clip"), None).unwrap();
+        fs::remove_file(storage.session_dir("clip").join("notes.md")).unwrap();
+
+        storage.append_notes("clip", "first\n\n").unwrap();
+        storage.append_notes("clip", "second").unwrap();
+        assert_eq!(storage.read_notes("clip").unwrap(), "first\n\nsecond\n");
+    }
+
+    #[test]
+    fn load_session_skips_hidden_and_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = test_storage(dir.path());
+        storage
+            .create_session(&Session::new("alpha"), None)
+            .unwrap();
+        fs::create_dir_all(dir.path().join(".hidden")).unwrap();
+
+        assert_eq!(storage.load_session("alpha").unwrap().slug, "alpha");
+        assert!(storage.load_session(".hidden").is_none());
+        assert!(storage.load_session("missing").is_none());
+        assert_eq!(storage.list_sessions().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn meta_defaults_when_missing_and_roundtrips() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = test_storage(dir.path());
+        storage
+            .create_session(&Session::new("alpha"), None)
+            .unwrap();
+        assert!(storage.load_meta("alpha").unwrap().worktrees.is_empty());
+
+        let mut meta = SessionMeta::default();
+        meta.worktrees.push(crate::models::WorktreeMeta {
+            repo: PathBuf::from("/repo"),
+            path: PathBuf::from("worktree"),
+            branch: "sp/alpha".to_string(),
+        });
+        storage.save_meta("alpha", &meta).unwrap();
+        let loaded = storage.load_meta("alpha").unwrap();
+        assert_eq!(loaded.worktrees[0].branch, "sp/alpha");
+        // Metadata is hidden from the file tree
+        assert_eq!(
+            build_file_tree(&storage.session_dir("alpha"), None, 3).len(),
+            1
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_tree_does_not_follow_symlinks() {
+        let (dir, outside) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let storage = test_storage(dir.path());
+        storage
+            .create_session(&Session::new("alpha"), None)
+            .unwrap();
+        let session = storage.session_dir("alpha");
+        fs::create_dir(session.join("sub")).unwrap();
+        fs::write(outside.path().join("secret.txt"), "x").unwrap();
+        std::os::unix::fs::symlink(&session, session.join("sub/loop")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), session.join("out")).unwrap();
+
+        let tree = build_file_tree(&session, None, usize::MAX);
+        let names: Vec<_> = tree.iter().map(|e| (e.name.as_str(), e.is_dir)).collect();
+        assert_eq!(
+            names,
+            [
+                ("notes.md", false),
+                ("out", false),
+                ("sub/", true),
+                ("loop", false)
+            ]
+        );
+    }
+
+    #[test]
+    fn session_file_rejects_escaping_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = test_storage(dir.path());
+        assert!(storage.session_file("a", "notes.md").is_ok());
+        assert!(storage.session_file("a", "sub/plan.md").is_ok());
+        assert!(storage.session_file("a", "../b/notes.md").is_err());
+        assert!(storage.session_file("a", "/etc/passwd").is_err());
+        assert!(storage.session_file("a", "").is_err());
+    }
+
+    #[test]
+    fn first_heading_skips_code_blocks_and_tags() {
+        assert_eq!(
+            first_heading("intro\n```\n# not a title\n```\n## Real Title\n"),
+            Some("Real Title".to_string())
+        );
+        assert_eq!(first_heading("#tag only\nplain text"), None);
+        assert_eq!(first_heading(""), None);
+    }
+
+    #[test]
+    fn title_cache_reuses_until_mtime_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let notes = dir.path().join("notes.md");
+        fs::write(&notes, "# First\n").unwrap();
+
+        let mut cache = TitleCache::default();
+        assert_eq!(cache.title_for(dir.path()), Some("First".to_string()));
+
+        // Same mtime: the cached title is returned even though content changed
+        let mtime = fs::metadata(&notes).unwrap().modified().unwrap();
+        fs::write(&notes, "# Second\n").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&notes)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+        assert_eq!(cache.title_for(dir.path()), Some("First".to_string()));
+
+        let later = mtime + std::time::Duration::from_secs(5);
+        fs::File::options()
+            .write(true)
+            .open(&notes)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!(cache.title_for(dir.path()), Some("Second".to_string()));
+    }
+
+    #[test]
+    fn dir_size_sums_nested_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        fs::write(dir.path().join("one.md"), "12345").unwrap();
+        fs::write(dir.path().join("a/b/two.txt"), "123").unwrap();
+        assert_eq!(dir_size(dir.path()), 8);
+        assert_eq!(dir_size(&dir.path().join("missing")), 0);
+    }
+
+    #[test]
+    fn read_file_head_truncates_at_line_boundary() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("big.md");
+        fs::write(&path, "line one\nline two\nline three\n").unwrap();
+
+        let (head, truncated) = read_file_head(&path, 14).unwrap();
+        assert!(truncated);
+        assert_eq!(head, "line one\n");
+
+        let (full, truncated) = read_file_head(&path, 1024).unwrap();
+        assert!(!truncated);
+        assert_eq!(full, "line one\nline two\nline three\n");
+    }
+
+    /// Storage for `<dir>/ws` with a fresh age key
+    fn encrypted_storage(dir: &Path) -> Storage {
+        use age::secrecy::ExposeSecret;
+
+        let key = dir.join("key.txt");
+        let identity = age::x25519::Identity::generate();
+        fs::write(
+            &key,
+            format!("# test key\n{}\n", identity.to_string().expose_secret()),
+        )
+        .unwrap();
+        let config = Config {
+            workspace_path: dir.join("ws").to_string_lossy().to_string(),
+            encryption: Some(crate::models::EncryptionConfig {
+                identity: Some(key.to_string_lossy().to_string()),
+                passphrase: None,
+            }),
+            ..Config::default()
+        };
+        Storage::new(config, Context::User)
+    }
+
+    #[test]
+    fn encrypted_session_round_trips_notes_and_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = encrypted_storage(dir.path());
+        storage
+            .create_session(&Session::new("secret"), Some("# Keys\n"))
+            .unwrap();
+        assert_eq!(storage.encrypt_session("secret").unwrap(), 1);
+
+        let session_dir = storage.session_dir("secret");
+        assert!(!session_dir.join("notes.md").exists());
+        assert_eq!(
+            storage.find_entry_point("secret"),
+            Some(session_dir.join("notes.md.age"))
+        );
+        assert_eq!(storage.read_notes("secret").unwrap(), "# Keys\n");
+        storage.append_notes("secret", "rotate").unwrap();
+        assert_eq!(storage.read_notes("secret").unwrap(), "# Keys\n\nrotate\n");
+
+        storage
+            .write_session_file("secret", "env.txt", "TOKEN=1")
+            .unwrap();
+        assert!(session_dir.join("env.txt.age").exists());
+        assert_eq!(
+            storage.read_session_file("secret", "env.txt").unwrap(),
+            "TOKEN=1"
+        );
+        assert_eq!(TitleCache::default().title_for(&session_dir), None);
+
+        for line in ["one\n", "two\n"] {
+            let (path, ()) = storage
+                .append_session_file("secret", "capture.log", |out| {
+                    Ok(out.write_all(line.as_bytes())?)
+                })
+                .unwrap();
+            assert_eq!(path, session_dir.join("capture.log.age"));
+        }
+        assert!(!session_dir.join("capture.log").exists());
+        assert_eq!(
+            storage.read_session_file("secret", "capture.log").unwrap(),
+            "one\ntwo\n"
+        );
+    }
+
+    #[test]
+    fn links_are_recorded_on_both_sides_and_follow_renames() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = test_storage(dir.path());
+        for slug in ["origin", "fork", "other"] {
+            storage.create_session(&Session::new(slug), None).unwrap();
+        }
+        storage
+            .link_sessions("fork", "origin", Relation::Parent)
+            .unwrap();
+        storage
+            .link_sessions("fork", "other", Relation::Related)
+            .unwrap();
+        assert!(
+            storage
+                .link_sessions("fork", "fork", Relation::Related)
+                .is_err()
+        );
+
+        let origin = storage.load_meta("origin").unwrap();
+        assert_eq!(
+            origin.links,
+            vec![SessionLink {
+                rel: Relation::Child,
+                session: "fork".to_string()
+            }]
+        );
+
+        storage.rename_session("fork", "fork-2").unwrap();
+        assert_eq!(
+            storage.load_meta("origin").unwrap().links[0].session,
+            "fork-2"
+        );
+        assert_eq!(
+            storage.load_meta("other").unwrap().links[0].session,
+            "fork-2"
+        );
+
+        assert!(storage.unlink_sessions("origin", "fork-2").unwrap());
+        assert!(storage.load_meta("origin").unwrap().links.is_empty());
+        assert_eq!(storage.load_meta("fork-2").unwrap().links.len(), 1);
+        assert!(!storage.unlink_sessions("origin", "fork-2").unwrap());
+    }
+
+    #[test]
+    fn branch_sessions_are_named_after_the_branch() {
+        assert_eq!(
+            branch_session_slug("feature/Login_form").as_deref(),
+            Some("branch-feature-login-form")
+        );
+        assert_eq!(branch_session_slug("main").as_deref(), Some("branch-main"));
+        assert_eq!(branch_session_slug("///"), None);
+
+        // Off unless enabled, and only in a project
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(test_storage(dir.path()).branch_session(), None);
+    }
+
+    #[test]
+    fn nested_projects_are_all_available_nearest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let mono = dir.path().join("mono");
+        let api = mono.join("packages/api");
+        fs::create_dir_all(mono.join(".scratchpad")).unwrap();
+        fs::create_dir_all(api.join(".scratchpad")).unwrap();
+        fs::create_dir_all(api.join("src")).unwrap();
+
+        let contexts = available_contexts(&api.join("src"), &Config::default());
+        let projects: Vec<&Context> = contexts
+            .iter()
+            .filter(|c| matches!(c, Context::Project(pad) if pad.starts_with(dir.path())))
+            .collect();
+        assert_eq!(
+            projects,
+            vec![
+                &Context::Project(api.join(".scratchpad")),
+                &Context::Project(mono.join(".scratchpad")),
+            ]
+        );
+        assert_eq!(contexts[0], Context::User);
+
+        assert_eq!(
+            project_at(&contexts, &api.join("src/../../..")),
+            Some(Context::Project(mono.join(".scratchpad")))
+        );
+        assert_eq!(project_at(&contexts, &api.join("src")), None);
+
+        assert_eq!(
+            context_label(projects[0], &contexts),
+            "project: mono/packages/api"
+        );
+        assert_eq!(context_label(projects[1], &contexts), "project: mono");
+    }
+}
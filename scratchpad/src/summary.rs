@@ -0,0 +1,93 @@
+//! Agent-written session summaries (`sp summarize`)
+//!
+//! The full summary goes to `SUMMARY.md`; its first paragraph is kept in the session
+//! metadata so `sp list` and the TUI can show it without reading files.
+
+use anyhow::{Context as _, Result, bail};
+
+use crate::llm;
+use crate::models::Agent;
+use crate::storage::Storage;
+
+pub const SUMMARY_FILE: &str = "SUMMARY.md";
+
+/// Per-file cap on what is sent to the agent, in characters
+const MAX_FILE_CHARS: usize = 50_000;
+
+/// Ask `agent` to summarize the session's entry point plus `extra` files (relative paths)
+pub fn generate(storage: &Storage, slug: &str, extra: &[String], agent: Agent) -> Result<String> {
+    let session_dir = storage.session_dir(slug);
+    let entry = storage
+        .find_entry_point(slug)
+        .filter(|path| !path.ends_with(SUMMARY_FILE))
+        .context("Session has no notes to summarize")?;
+    let entry_name = entry
+        .strip_prefix(&session_dir)
+        .unwrap_or(&entry)
+        .to_string_lossy()
+        .trim_end_matches(".age")
+        .to_string();
+
+    let mut files = vec![(entry_name, storage.read_notes(slug)?)];
+    for relative in extra {
+        let content = storage.read_session_file(slug, relative)?;
+        files.push((relative.clone(), content));
+    }
+    if files.iter().all(|(_, content)| content.trim().is_empty()) {
+        bail!("Session notes are empty");
+    }
+    llm::ask(agent, &build_prompt(slug, &files))
+}
+
+fn build_prompt(slug: &str, files: &[(String, String)]) -> String {
+    let mut prompt = format!(
+        "Summarize the scratchpad session \"{slug}\" from the files below. Start with one \
+         sentence describing what the session is about, then a blank line, then short \
+         markdown bullets covering the goal, findings, decisions and open questions. \
+         Output only the summary.\n"
+    );
+    for (name, content) in files {
+        let truncated: String = content.chars().take(MAX_FILE_CHARS).collect();
+        prompt.push_str(&format!("\n--- {name} ---\n{truncated}\n"));
+    }
+    prompt
+}
+
+/// The summary's first paragraph (headings skipped) joined onto one line, for listings
+pub fn short(summary: &str) -> Option<String> {
+    summary.split("\n\n").find_map(|paragraph| {
+        let text = paragraph
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect::<Vec<_>>()
+            .join(" ");
+        (!text.is_empty()).then_some(text)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_summary_is_the_first_paragraph() {
+        assert_eq!(
+            short("# Summary\nRetries for the\nupload queue.\n\n- Goal: fewer 503s").as_deref(),
+            Some("Retries for the upload queue.")
+        );
+        assert_eq!(short("  \n"), None);
+    }
+
+    #[test]
+    fn prompt_includes_every_file() {
+        let files = vec![
+            ("notes.md".to_string(), "queue race".to_string()),
+            ("log.txt".to_string(), "panic at 12:00".to_string()),
+        ];
+        let prompt = build_prompt("queue-fix", &files);
+        assert!(prompt.contains("\"queue-fix\""));
+        assert!(prompt.contains("--- notes.md ---\nqueue race\n"));
+        assert!(prompt.contains("--- log.txt ---\npanic at 12:00\n"));
+    }
+}
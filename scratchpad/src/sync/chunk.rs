@@ -0,0 +1,148 @@
+//! Content-defined chunking of large synced files
+//!
+//! Chunk boundaries come from a rolling gear hash over the content rather than fixed
+//! offsets, so an edit only changes the chunks around it: re-syncing a large file after a
+//! one-line change sends a chunk or two instead of the whole file. Chunks are cached in
+//! `chunks/<sha256>` in the remote's state directory once the remote has them, which is
+//! how both sides know which chunks they can skip sending and rebuild files from.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+
+use super::hash;
+
+/// Files at least this large are synced as chunks
+pub const CHUNKED_FILE_SIZE: usize = 256 * 1024;
+
+const MIN_CHUNK: usize = 16 * 1024;
+const MAX_CHUNK: usize = 128 * 1024;
+/// A boundary falls where the top 15 bits of the hash are zero, ~32 KiB apart on average
+const BOUNDARY_MASK: u64 = 0x7fff << 49;
+
+const CHUNKS_DIR: &str = "chunks";
+
+/// Pseudo-random value per byte, fixed so every client cuts the same boundaries
+const GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    // splitmix64
+    let mut table = [0; 256];
+    let mut state: u64 = 0x5eed_5c4a_7c4d_0001;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// Split `data` at content-defined boundaries
+pub fn split(data: &[u8]) -> Vec<&[u8]> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut rolling: u64 = 0;
+    for (i, byte) in data.iter().enumerate() {
+        rolling = (rolling << 1).wrapping_add(GEAR[*byte as usize]);
+        let len = i + 1 - start;
+        if (len >= MIN_CHUNK && rolling & BOUNDARY_MASK == 0) || len >= MAX_CHUNK {
+            chunks.push(&data[start..=i]);
+            start = i + 1;
+            rolling = 0;
+        }
+    }
+    if start < data.len() {
+        chunks.push(&data[start..]);
+    }
+    chunks
+}
+
+/// Chunks known to be on a remote, by hash
+pub struct ChunkStore {
+    dir: PathBuf,
+}
+
+impl ChunkStore {
+    /// The cache in a remote's state directory
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.join(CHUNKS_DIR),
+        }
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.dir.join(hash).is_file()
+    }
+
+    pub fn put(&self, hash: &str, data: &[u8]) -> Result<()> {
+        if self.contains(hash) {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir).context("Failed to create chunk cache")?;
+        // Written under a temporary name so an interrupted write never looks complete
+        let partial = self.dir.join(format!("{hash}.partial"));
+        fs::write(&partial, data)
+            .and_then(|()| fs::rename(&partial, self.dir.join(hash)))
+            .with_context(|| format!("Failed to cache chunk {hash}"))
+    }
+
+    /// The file made of `hashes`, or None if a chunk is missing or corrupt
+    pub fn assemble(&self, hashes: &[String]) -> Option<Vec<u8>> {
+        let mut data = Vec::new();
+        for expected in hashes {
+            let chunk = fs::read(self.dir.join(expected)).ok()?;
+            if hash(&chunk) != *expected {
+                return None;
+            }
+            data.extend_from_slice(&chunk);
+        }
+        Some(data)
+    }
+
+    /// Drop cached chunks not in `keep`
+    pub fn retain(&self, keep: &HashSet<&str>) {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !keep.contains(name.as_str()) {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(lines: usize) -> String {
+        (0..lines)
+            .map(|i| format!("line {i}: the quick brown fox jumps over the lazy dog\n"))
+            .collect()
+    }
+
+    #[test]
+    fn an_edit_only_changes_nearby_chunks() {
+        let original = text(20_000);
+        let chunks = split(original.as_bytes());
+        assert!(chunks.len() > 10);
+        assert!(chunks.iter().all(|c| c.len() <= MAX_CHUNK));
+        assert_eq!(chunks.concat(), original.as_bytes());
+
+        let edited = original.replacen("line 10000:", "line 10000 (edited):", 1);
+        let before: HashSet<String> = chunks.iter().map(|c| hash(c)).collect();
+        let changed = split(edited.as_bytes())
+            .iter()
+            .filter(|c| !before.contains(&hash(c)))
+            .count();
+        assert!(changed <= 2, "{changed} chunks changed");
+    }
+}
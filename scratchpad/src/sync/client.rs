@@ -0,0 +1,194 @@
+//! HTTP client for the sync server's op log
+
+use std::io::Read;
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use scratchpad_protocol::{
+    LoginRequest, LoginResponse, Op, PushOpsRequest, PushOpsResponse, Snapshot, WhoAmIResponse,
+};
+
+use super::Remote;
+use super::device::Device;
+use super::throttle::Throttle;
+use crate::models::ServerConfig;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct Client {
+    url: String,
+    token: Option<String>,
+    workspace_id: String,
+    /// Sent with pushes so the server can name the device they come from
+    device_name: String,
+    /// Bytes per second each way, from `--limit-rate`
+    rate: Option<u64>,
+}
+
+impl Client {
+    pub fn new(
+        server: &ServerConfig,
+        workspace_id: &str,
+        device: &Device,
+        rate: Option<u64>,
+    ) -> Self {
+        Self {
+            url: server.url.trim_end_matches('/').to_string(),
+            token: server.token.clone(),
+            workspace_id: workspace_id.to_string(),
+            device_name: device.name.clone(),
+            rate,
+        }
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        let request =
+            ureq::request(method, &format!("{}{path}", self.url)).timeout(REQUEST_TIMEOUT);
+        match &self.token {
+            Some(token) => request.set("Authorization", &format!("Bearer {token}")),
+            None => request,
+        }
+    }
+
+    /// The workspace's snapshot, if it has one
+    pub fn snapshot(&self) -> Result<Option<Snapshot>> {
+        let response = self
+            .request("GET", &format!("/api/snapshot/{}", self.workspace_id))
+            .call();
+        match response {
+            Ok(response) => Ok(Some(serde_json::from_reader(
+                self.throttle(response.into_reader()),
+            )?)),
+            Err(ureq::Error::Status(404, _)) => Ok(None),
+            Err(e) => Err(error(e)),
+        }
+    }
+
+    /// Replace the workspace's snapshot
+    pub fn save_snapshot(&self, snapshot: &Snapshot) -> Result<()> {
+        let body = serde_json::to_vec(snapshot)?;
+        self.request("POST", &format!("/api/snapshot/{}", self.workspace_id))
+            .set("Content-Type", "application/json")
+            .set("Content-Length", &body.len().to_string())
+            .send(self.throttle(body.as_slice()))
+            .map_err(error)?;
+        Ok(())
+    }
+
+    fn throttle<'a>(&self, reader: impl Read + Send + 'a) -> Box<dyn Read + Send + 'a> {
+        match self.rate {
+            Some(rate) => Box::new(Throttle::new(reader, rate)),
+            None => Box::new(reader),
+        }
+    }
+}
+
+fn error(error: ureq::Error) -> anyhow::Error {
+    match error {
+        ureq::Error::Status(401, response) => {
+            let body = response.into_string().unwrap_or_default();
+            anyhow!(
+                "Sync server returned 401: {} (run `sp sync login`, or set [server] token)",
+                body.trim()
+            )
+        }
+        ureq::Error::Status(code, response) => {
+            let body = response.into_string().unwrap_or_default();
+            anyhow!("Sync server returned {code}: {}", body.trim())
+        }
+        ureq::Error::Transport(e) => anyhow!("Failed to reach sync server: {e}"),
+    }
+}
+
+/// Name of the token `server` is configured with, as the server knows it
+pub fn whoami(server: &ServerConfig) -> Result<String> {
+    let url = format!("{}/api/whoami", server.url.trim_end_matches('/'));
+    let mut request = ureq::get(&url).timeout(REQUEST_TIMEOUT);
+    if let Some(token) = &server.token {
+        request = request.set("Authorization", &format!("Bearer {token}"));
+    }
+    let response: WhoAmIResponse = request.call().map_err(error)?.into_json()?;
+    Ok(response.name)
+}
+
+/// Exchange a one-time login code for a token from the server at `url`
+pub fn login(url: &str, code: &str) -> Result<LoginResponse> {
+    let url = format!("{}/api/login", url.trim_end_matches('/'));
+    let response = ureq::post(&url)
+        .timeout(REQUEST_TIMEOUT)
+        .send_json(LoginRequest {
+            code: code.trim().to_string(),
+        });
+    match response {
+        Ok(response) => Ok(response.into_json()?),
+        Err(ureq::Error::Status(401, _)) => Err(anyhow!(
+            "The server didn't accept the code: it's wrong, used or expired"
+        )),
+        Err(e) => Err(error(e)),
+    }
+}
+
+impl Remote for Client {
+    fn name(&self) -> String {
+        format!("{}#{}", self.url, self.workspace_id)
+    }
+
+    fn pull(&mut self, after: Option<i64>, limit: usize) -> Result<Vec<Op>> {
+        let mut request = self
+            .request("GET", &format!("/api/ops/{}", self.workspace_id))
+            .query("limit", &limit.to_string());
+        if let Some(after) = after {
+            request = request.query("after", &after.to_string());
+        }
+        let response = request.call().map_err(error)?;
+        Ok(serde_json::from_reader(
+            self.throttle(response.into_reader()),
+        )?)
+    }
+
+    fn put_blob(&mut self, hash: &str, data: &[u8]) -> Result<bool> {
+        let response = self
+            .request("PUT", &format!("/api/blobs/{}/{hash}", self.workspace_id))
+            .set("Content-Type", "application/octet-stream")
+            .set("Content-Length", &data.len().to_string())
+            .send(self.throttle(data));
+        match response {
+            Ok(_) => Ok(true),
+            // Servers from before the blob API take blobs as ops
+            Err(ureq::Error::Status(404 | 405, _)) => Ok(false),
+            Err(e) => Err(error(e)),
+        }
+    }
+
+    fn get_blob(&mut self, hash: &str) -> Result<Option<Vec<u8>>> {
+        let response = self
+            .request("GET", &format!("/api/blobs/{}/{hash}", self.workspace_id))
+            .call();
+        match response {
+            Ok(response) => {
+                let mut data = Vec::new();
+                self.throttle(response.into_reader())
+                    .read_to_end(&mut data)?;
+                Ok(Some(data))
+            }
+            Err(ureq::Error::Status(404 | 405, _)) => Ok(None),
+            Err(e) => Err(error(e)),
+        }
+    }
+
+    fn push(&mut self, ops: Vec<Op>) -> Result<PushOpsResponse> {
+        let body = PushOpsRequest {
+            workspace_id: self.workspace_id.clone(),
+            ops,
+            client_name: Some(self.device_name.clone()),
+        };
+        let body = serde_json::to_vec(&body)?;
+        let response = self
+            .request("POST", "/api/ops")
+            .set("Content-Type", "application/json")
+            .set("Content-Length", &body.len().to_string())
+            .send(self.throttle(body.as_slice()))
+            .map_err(error)?;
+        Ok(response.into_json()?)
+    }
+}
@@ -0,0 +1,182 @@
+//! Conflicts a sync left behind, until they're resolved (the TUI's resolution view)
+//!
+//! A conflict keeps the local file and saves the other side next to it: the remote
+//! version as `<name>.remote.<ext>`, or for markdown a merge with conflict markers as
+//! `<name>.conflict.md` (see `merge.rs`). Each is recorded in `.sync/conflicts.json`
+//! under the file's sync path. Resolving writes the chosen version over the file and
+//! deletes the saved one; a record whose saved file is already gone was dealt with by
+//! hand and is dropped.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use anyhow::{Context as _, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use super::{SYNC_DIR, merge};
+
+const CONFLICTS_FILE: &str = "conflicts.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Conflict {
+    /// Sync path of the file, kept as it was here
+    pub path: String,
+    /// Sync path the other side was saved as
+    pub saved: String,
+    /// `saved` is a merge with conflict markers rather than the remote version
+    #[serde(default)]
+    pub merged: bool,
+}
+
+impl Conflict {
+    pub fn session(&self) -> &str {
+        self.path.split('/').next().unwrap_or(&self.path)
+    }
+
+    /// The file's name within its session
+    pub fn name(&self) -> &str {
+        self.path
+            .split_once('/')
+            .map_or(&self.path, |(_, name)| name)
+    }
+}
+
+/// The versions to choose from
+pub struct Sides {
+    pub local: String,
+    /// For a merge, the merge with every conflict settled the remote way
+    pub remote: String,
+    /// With conflict markers, unless they've been edited out
+    pub merged: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resolution {
+    Local,
+    Remote,
+    Merged,
+}
+
+/// Note a conflict, replacing any earlier one for the same file
+pub fn record(workspace: &Path, conflict: Conflict) -> Result<()> {
+    let mut conflicts = load(workspace)?;
+    conflicts.retain(|c| c.path != conflict.path);
+    conflicts.push(conflict);
+    save(workspace, &conflicts)
+}
+
+/// Conflicts not resolved yet
+pub fn list(workspace: &Path) -> Vec<Conflict> {
+    load(workspace)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|c| workspace.join(&c.saved).is_file())
+        .collect()
+}
+
+pub fn sides(workspace: &Path, conflict: &Conflict) -> Result<Sides> {
+    let read = |path: &str| match fs::read_to_string(workspace.join(path)) {
+        Ok(content) => Ok(content),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {path}")),
+    };
+    let local = read(&conflict.path)?;
+    let saved = read(&conflict.saved)?;
+    Ok(if conflict.merged {
+        Sides {
+            local,
+            remote: merge::take_remote(&saved),
+            merged: Some(saved),
+        }
+    } else {
+        Sides {
+            local,
+            remote: saved,
+            merged: None,
+        }
+    })
+}
+
+/// Keep the chosen version and forget the conflict. A merge is only taken once its
+/// conflict markers have been edited out.
+pub fn resolve(workspace: &Path, conflict: &Conflict, resolution: Resolution) -> Result<()> {
+    let sides = sides(workspace, conflict)?;
+    let content = match resolution {
+        Resolution::Local => None,
+        Resolution::Remote => Some(sides.remote),
+        Resolution::Merged => match sides.merged {
+            Some(merged) if merge::has_markers(&merged) => {
+                bail!("{} still has conflict markers", conflict.saved)
+            }
+            Some(merged) => Some(merged),
+            None => bail!("No merge to take for {}", conflict.path),
+        },
+    };
+    if let Some(content) = content {
+        let file = workspace.join(&conflict.path);
+        fs::write(&file, content).with_context(|| format!("Failed to write {}", file.display()))?;
+    }
+    let saved = workspace.join(&conflict.saved);
+    match fs::remove_file(&saved) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            return Err(e).with_context(|| format!("Failed to delete {}", saved.display()));
+        }
+        _ => {}
+    }
+    let mut conflicts = load(workspace)?;
+    conflicts.retain(|c| c.path != conflict.path);
+    save(workspace, &conflicts)
+}
+
+fn load(workspace: &Path) -> Result<Vec<Conflict>> {
+    let path = workspace.join(SYNC_DIR).join(CONFLICTS_FILE);
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content)
+            .with_context(|| format!("Invalid conflict list {}", path.display())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+fn save(workspace: &Path, conflicts: &[Conflict]) -> Result<()> {
+    let dir = workspace.join(SYNC_DIR);
+    fs::create_dir_all(&dir).context("Failed to create sync directory")?;
+    fs::write(dir.join(CONFLICTS_FILE), serde_json::to_string(conflicts)?)
+        .context("Failed to save the conflict list")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolving_keeps_the_chosen_side() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path();
+        fs::create_dir_all(workspace.join("plans")).unwrap();
+        fs::write(workspace.join("plans/notes.md"), "a\nmine\n").unwrap();
+        fs::write(
+            workspace.join("plans/notes.conflict.md"),
+            "a\n<<<<<<< local\nmine\n=======\ntheirs\n>>>>>>> remote\n",
+        )
+        .unwrap();
+        let conflict = Conflict {
+            path: "plans/notes.md".to_string(),
+            saved: "plans/notes.conflict.md".to_string(),
+            merged: true,
+        };
+        record(workspace, conflict.clone()).unwrap();
+        assert_eq!(list(workspace).len(), 1);
+        assert_eq!(sides(workspace, &conflict).unwrap().remote, "a\ntheirs\n");
+
+        assert!(resolve(workspace, &conflict, Resolution::Merged).is_err());
+        resolve(workspace, &conflict, Resolution::Remote).unwrap();
+        assert_eq!(
+            fs::read_to_string(workspace.join("plans/notes.md")).unwrap(),
+            "a\ntheirs\n"
+        );
+        assert!(!workspace.join("plans/notes.conflict.md").exists());
+        assert!(list(workspace).is_empty());
+    }
+}
@@ -0,0 +1,71 @@
+//! This machine's identity among those syncing
+//!
+//! The id is a UUID generated on first use and kept in `device-id` next to the config
+//! file, so it survives reinstalls and is the same for every workspace. Every op this
+//! machine pushes carries it as `client_id`, which lets the server attribute changes and
+//! lets `--watch` tell its own pushes apart when the server announces them. The name is
+//! only for people: `[sync] device_name`, or the hostname.
+
+use std::fs;
+use std::io;
+
+use anyhow::{Context as _, Result};
+
+use crate::models::SyncConfig;
+
+const DEVICE_FILE: &str = "device-id";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Device {
+    pub id: String,
+    pub name: String,
+}
+
+impl Device {
+    pub fn load(rules: Option<&SyncConfig>) -> Result<Self> {
+        let name = rules
+            .and_then(|r| r.device_name.clone())
+            .filter(|name| !name.trim().is_empty())
+            .or_else(hostname)
+            .unwrap_or_else(|| "unknown".to_string());
+        Ok(Self { id: id()?, name })
+    }
+}
+
+/// This machine's device id, created on first use. Ids from before they were UUIDs are
+/// kept as they are.
+pub fn id() -> Result<String> {
+    let path = crate::config::config_path().with_file_name(DEVICE_FILE);
+    match fs::read_to_string(&path) {
+        Ok(id) if !id.trim().is_empty() => return Ok(id.trim().to_string()),
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+    let id = uuid::Uuid::new_v4().to_string();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context("Failed to create config directory")?;
+    }
+    fs::write(&path, format!("{id}\n"))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(id)
+}
+
+#[cfg(unix)]
+fn hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer outlives the call, which writes at most its length
+    if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    let name = String::from_utf8_lossy(&buf[..len]).trim().to_string();
+    (!name.is_empty()).then_some(name)
+}
+
+#[cfg(not(unix))]
+fn hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME")
+        .ok()
+        .filter(|name| !name.is_empty())
+}
@@ -0,0 +1,89 @@
+//! Files kept off the sync by `[sync] ignore` and `max_file_size`
+//!
+//! `ignore` holds gitignore-style globs over workspace paths (`*.log`, `target/`,
+//! `plans/data/`), applied on top of each session's `.spignore`: matching files are
+//! never scanned, and removing a synced file by ignoring it doesn't delete it elsewhere.
+//! Files over `max_file_size` (e.g. `"5M"`) are still seen but left out when ops are
+//! built, and listed in the report.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context as _, Result, anyhow};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+use super::throttle::parse_rate;
+use crate::models::SyncConfig;
+
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    ignore: Option<Arc<Gitignore>>,
+    max_size: Option<u64>,
+}
+
+impl Filter {
+    /// The filter `rules` set for `workspace`
+    pub fn new(workspace: &Path, rules: Option<&SyncConfig>) -> Result<Self> {
+        let Some(rules) = rules else {
+            return Ok(Self::default());
+        };
+        let ignore = if rules.ignore.is_empty() {
+            None
+        } else {
+            let mut builder = GitignoreBuilder::new(workspace);
+            for pattern in &rules.ignore {
+                builder
+                    .add_line(None, pattern)
+                    .with_context(|| format!("Invalid [sync] ignore pattern '{pattern}'"))?;
+            }
+            Some(Arc::new(builder.build()?))
+        };
+        let max_size = rules
+            .max_file_size
+            .as_deref()
+            .map(parse_rate)
+            .transpose()
+            .map_err(|e| anyhow!("Invalid [sync] max_file_size: {e}"))?;
+        Ok(Self { ignore, max_size })
+    }
+
+    /// Whether `path`, in the workspace or relative to it, is ignored
+    pub fn ignores(&self, path: &Path, is_dir: bool) -> bool {
+        self.ignore
+            .as_ref()
+            .is_some_and(|ignore| ignore.matched_path_or_any_parents(path, is_dir).is_ignore())
+    }
+
+    /// Whether a file of `size` bytes is too large to sync
+    pub fn too_large(&self, size: u64) -> bool {
+        self.max_size.is_some_and(|max| size > max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignores_patterns_and_large_files() {
+        let workspace = Path::new("/work/scratchpad");
+        let rules = SyncConfig {
+            ignore: vec!["*.log".to_string(), "target/".to_string()],
+            max_file_size: Some("1k".to_string()),
+            ..SyncConfig::default()
+        };
+        let filter = Filter::new(workspace, Some(&rules)).unwrap();
+        assert!(filter.ignores(&workspace.join("plans/build.log"), false));
+        assert!(filter.ignores(Path::new("plans/target"), true));
+        assert!(filter.ignores(Path::new("plans/target/debug/app"), false));
+        assert!(!filter.ignores(Path::new("plans/notes.md"), false));
+        assert!(filter.too_large(1025));
+        assert!(!filter.too_large(1024));
+
+        let rules = SyncConfig {
+            max_file_size: Some("huge".to_string()),
+            ..SyncConfig::default()
+        };
+        assert!(Filter::new(workspace, Some(&rules)).is_err());
+    }
+}
@@ -0,0 +1,278 @@
+//! Sync through a shared folder (Dropbox, Syncthing, iCloud Drive, ...)
+//!
+//! Each device appends the ops it pushes to its own `<folder>/<workspace>/<device>.jsonl`
+//! and never touches anyone else's file, so the file syncing service has nothing to
+//! merge: no locks, and no conflicted copies. Reading merges every device's file. The
+//! order ops were first seen in is kept in a local index (`index.json` in the remote's
+//! state directory), which gives each op the stable position sync uses as its cursor.
+//! Edits made on two devices before either saw the other's conflict the way they do
+//! through the server: each side keeps its own and saves the other alongside.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+use scratchpad_protocol::{Op, OpResult, OpStatus, PushOpsResponse};
+
+use super::device::Device;
+use super::{Remote, Report, SYNC_DIR, default_workspace_id, filter, sync_with};
+use crate::names::slugify;
+use crate::storage::Storage;
+
+const LOG_EXTENSION: &str = "jsonl";
+const INDEX_FILE: &str = "index.json";
+/// Where the state of syncing through each folder lives, by folder
+const FOLDERS_DIR: &str = "folders";
+
+/// Sync the workspace through `folder`
+pub fn run(storage: &Storage, folder: &Path, device: &Device) -> Result<Report> {
+    storage.ensure_workspace()?;
+    let workspace = storage.workspace_path();
+    let dir = state_dir(&workspace, folder);
+    let workspace_id = default_workspace_id(storage.context());
+    let mut log = FolderLog::open(folder, &workspace_id, &device.id, &dir);
+    sync_with(
+        &workspace,
+        &dir,
+        &mut log,
+        &device.id,
+        None,
+        &filter(storage)?,
+    )
+}
+
+/// Where the state of syncing `workspace` through `folder` lives
+pub fn state_dir(workspace: &Path, folder: &Path) -> PathBuf {
+    workspace
+        .join(SYNC_DIR)
+        .join(FOLDERS_DIR)
+        .join(slugify(&folder.to_string_lossy()).unwrap_or_else(|| "folder".to_string()))
+}
+
+pub struct FolderLog {
+    /// `<folder>/<workspace>`
+    dir: PathBuf,
+    device: String,
+    index_path: PathBuf,
+    /// (device, line) of every op seen, in the order first seen
+    index: Vec<(String, usize)>,
+    /// Each device's ops, as of the last read
+    logs: BTreeMap<String, Vec<Op>>,
+    ids: HashSet<String>,
+    read: bool,
+}
+
+impl FolderLog {
+    /// The log for `workspace_id` in `folder`, written to as `device`, indexed in
+    /// `state_dir`
+    pub fn open(folder: &Path, workspace_id: &str, device: &str, state_dir: &Path) -> Self {
+        Self {
+            dir: folder.join(workspace_id),
+            device: device.to_string(),
+            index_path: state_dir.join(INDEX_FILE),
+            index: Vec::new(),
+            logs: BTreeMap::new(),
+            ids: HashSet::new(),
+            read: false,
+        }
+    }
+
+    /// Read every device's file and index ops not seen before
+    fn read(&mut self) -> Result<()> {
+        self.index = match fs::read_to_string(&self.index_path) {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("Invalid index {}", self.index_path.display()))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e).context("Failed to read folder index"),
+        };
+
+        self.logs.clear();
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries.filter_map(|e| e.ok()).collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", self.dir.display()));
+            }
+        };
+        for entry in entries {
+            let path = entry.path();
+            if path.extension().is_none_or(|e| e != LOG_EXTENSION) {
+                continue;
+            }
+            let Some(device) = path.file_stem().map(|s| s.to_string_lossy().to_string()) else {
+                continue;
+            };
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            // A line still being synced in is incomplete; it's picked up next time
+            let ops: Vec<Op> = content
+                .lines()
+                .map_while(|line| serde_json::from_str(line).ok())
+                .collect();
+            self.logs.insert(device, ops);
+        }
+        self.ids = self
+            .logs
+            .values()
+            .flatten()
+            .map(|op| op.id.clone())
+            .collect();
+
+        let seen: HashSet<(String, usize)> = self.index.iter().cloned().collect();
+        let new: Vec<(String, usize)> = self
+            .logs
+            .iter()
+            .flat_map(|(device, ops)| (0..ops.len()).map(|line| (device.clone(), line)))
+            .filter(|key| !seen.contains(key))
+            .collect();
+        if !new.is_empty() {
+            self.index.extend(new);
+            if let Some(dir) = self.index_path.parent() {
+                fs::create_dir_all(dir).context("Failed to create sync directory")?;
+            }
+            fs::write(&self.index_path, serde_json::to_string(&self.index)?)
+                .context("Failed to write folder index")?;
+        }
+        self.read = true;
+        Ok(())
+    }
+
+    fn op_at(&self, device: &str, line: usize) -> Option<&Op> {
+        self.logs.get(device)?.get(line)
+    }
+}
+
+impl Remote for FolderLog {
+    fn name(&self) -> String {
+        format!("folder:{}", self.dir.display())
+    }
+
+    fn pull(&mut self, after: Option<i64>, limit: usize) -> Result<Vec<Op>> {
+        if !self.read {
+            self.read()?;
+        }
+        let start = after.map_or(0, |id| id.max(0) as usize);
+        Ok(self
+            .index
+            .iter()
+            .enumerate()
+            .skip(start)
+            .take(limit)
+            .map(|(i, (device, line))| {
+                // An op whose file went missing is an empty slot that apply skips
+                let op = self.op_at(device, *line).cloned().unwrap_or(Op {
+                    db_id: None,
+                    id: String::new(),
+                    op_type: String::new(),
+                    payload: String::new(),
+                    timestamp: String::new(),
+                    client_id: None,
+                });
+                Op {
+                    db_id: Some(i as i64 + 1),
+                    ..op
+                }
+            })
+            .collect())
+    }
+
+    fn push(&mut self, ops: Vec<Op>) -> Result<PushOpsResponse> {
+        if !self.read {
+            self.read()?;
+        }
+        let mut lines = String::new();
+        let mut results = Vec::new();
+        for op in ops {
+            let status = if self.ids.contains(&op.id) {
+                OpStatus::Duplicate
+            } else {
+                OpStatus::Accepted
+            };
+            results.push(OpResult {
+                id: op.id.clone(),
+                status,
+                reason: None,
+            });
+            if status == OpStatus::Accepted {
+                let op = Op { db_id: None, ..op };
+                lines.push_str(&serde_json::to_string(&op)?);
+                lines.push('\n');
+                self.ids.insert(op.id);
+            }
+        }
+
+        if !lines.is_empty() {
+            fs::create_dir_all(&self.dir)
+                .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+            let path = self.dir.join(format!("{}.{LOG_EXTENSION}", self.device));
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .and_then(|mut file| file.write_all(lines.as_bytes()))
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        let accepted = results
+            .iter()
+            .filter(|r| r.status == OpStatus::Accepted)
+            .count();
+        Ok(PushOpsResponse { accepted, results })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::Filter;
+
+    #[test]
+    fn devices_sync_through_their_own_files() {
+        let (a, b, shared) = (
+            tempfile::tempdir().unwrap(),
+            tempfile::tempdir().unwrap(),
+            tempfile::tempdir().unwrap(),
+        );
+        let sync = |workspace: &Path, device: &str| {
+            let dir = workspace.join(SYNC_DIR);
+            let mut log = FolderLog::open(shared.path(), "user", device, &dir);
+            sync_with(workspace, &dir, &mut log, device, None, &Filter::default()).unwrap()
+        };
+        fs::create_dir_all(a.path().join("plans")).unwrap();
+        fs::create_dir_all(b.path().join("ideas")).unwrap();
+        fs::write(a.path().join("plans/notes.md"), "from a").unwrap();
+        fs::write(b.path().join("ideas/notes.md"), "from b").unwrap();
+        assert_eq!(sync(a.path(), "a").pushed, 1);
+        let report = sync(b.path(), "b");
+        assert_eq!((report.pulled, report.pushed), (1, 1));
+        assert_eq!(sync(a.path(), "a").pulled, 1);
+
+        for workspace in [a.path(), b.path()] {
+            assert_eq!(
+                fs::read_to_string(workspace.join("plans/notes.md")).unwrap(),
+                "from a"
+            );
+            assert_eq!(
+                fs::read_to_string(workspace.join("ideas/notes.md")).unwrap(),
+                "from b"
+            );
+        }
+        let mut files: Vec<String> = fs::read_dir(shared.path().join("user"))
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["a.jsonl", "b.jsonl"]);
+        let ops = fs::read_to_string(shared.path().join("user/a.jsonl")).unwrap();
+        assert!(ops.lines().all(|line| {
+            serde_json::from_str::<Op>(line)
+                .unwrap()
+                .client_id
+                .as_deref()
+                == Some("a")
+        }));
+        let report = sync(a.path(), "a");
+        assert_eq!((report.pulled, report.pushed), (0, 0));
+    }
+}
@@ -0,0 +1,210 @@
+//! Sync through a git remote, for those who'd rather not run `sp-server`
+//!
+//! With `[sync] backend = "git"`, `sp sync` keeps a clone of `[sync] remote` in the
+//! remote's state directory (`.sync/git/<remote>/repo`) and syncs through it the way it
+//! syncs through a shared folder: each device appends its ops to its own
+//! `<workspace>/<device>.jsonl` (see `folder.rs`). A sync fetches and merges the remote
+//! branch, syncs with the clone, then commits what it pushed and pushes that. As no two
+//! devices write the same file, the merge is a fast-forward, or a merge commit when this
+//! device has commits the remote hasn't seen, and never conflicts. A push rejected because
+//! another device pushed in between is retried after merging again; commits a failed push
+//! leaves behind go out with the next sync.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+
+use super::device::Device;
+use super::folder::FolderLog;
+use super::{Filter, Report, SYNC_DIR, default_workspace_id, filter, sync_with};
+use crate::git;
+use crate::names::slugify;
+use crate::storage::Storage;
+
+/// Where the state of syncing through each git remote lives, by remote
+const GIT_DIR: &str = "git";
+/// The clone, inside a remote's state directory
+const CLONE_DIR: &str = "repo";
+/// Pushes tried before giving up on a remote other devices keep pushing to
+const PUSH_ATTEMPTS: usize = 3;
+
+/// Sync the workspace through the git repository at `remote`
+pub fn run(storage: &Storage, remote: &str, device: &Device) -> Result<Report> {
+    storage.ensure_workspace()?;
+    let workspace = storage.workspace_path();
+    let dir = state_dir(&workspace, remote);
+    sync_through(
+        &workspace,
+        &dir,
+        remote,
+        &default_workspace_id(storage.context()),
+        device,
+        &filter(storage)?,
+    )
+}
+
+/// Where the state of syncing `workspace` through `remote` lives
+pub fn state_dir(workspace: &Path, remote: &str) -> PathBuf {
+    workspace
+        .join(SYNC_DIR)
+        .join(GIT_DIR)
+        .join(slugify(remote).unwrap_or_else(|| "remote".to_string()))
+}
+
+/// The op log in the clone, as of the last fetch
+pub fn log(dir: &Path, workspace_id: &str, device: &str) -> FolderLog {
+    FolderLog::open(&dir.join(CLONE_DIR), workspace_id, device, dir)
+}
+
+fn sync_through(
+    workspace: &Path,
+    dir: &Path,
+    remote: &str,
+    workspace_id: &str,
+    device: &Device,
+    filter: &Filter,
+) -> Result<Report> {
+    let repo = dir.join(CLONE_DIR);
+    let branch = checkout(&repo, remote)?;
+    merge(&repo, &branch, device)?;
+    let report = sync_with(
+        workspace,
+        dir,
+        &mut log(dir, workspace_id, &device.id),
+        &device.id,
+        None,
+        filter,
+    )?;
+    publish(&repo, &branch, device)?;
+    Ok(report)
+}
+
+/// Clone `remote` on first use. Returns the branch synced through.
+fn checkout(repo: &Path, remote: &str) -> Result<String> {
+    if !repo.join(".git").exists() {
+        let parent = repo.parent().context("Invalid sync directory")?;
+        fs::create_dir_all(parent).context("Failed to create sync directory")?;
+        git::run(parent, &["clone", "--quiet", remote, CLONE_DIR])
+            .with_context(|| format!("Failed to clone {remote}"))?;
+    }
+    git::current_branch(repo).context("The sync clone's HEAD is detached")
+}
+
+/// Fetch, and merge what other devices pushed
+fn merge(repo: &Path, branch: &str, device: &Device) -> Result<()> {
+    git::run(repo, &["fetch", "--quiet", "origin"])?;
+    let upstream = format!("origin/{branch}");
+    if !has_commit(repo, &upstream) {
+        // Nothing pushed yet
+        return Ok(());
+    }
+    if let Err(e) = commit_as(repo, device, &["merge", "--quiet", "--no-edit", &upstream]) {
+        let _ = git::run(repo, &["merge", "--abort"]);
+        return Err(e.context(format!("Failed to merge {upstream}")));
+    }
+    Ok(())
+}
+
+/// Commit this device's new ops and push every commit the remote hasn't got
+fn publish(repo: &Path, branch: &str, device: &Device) -> Result<()> {
+    if !git::run(repo, &["status", "--porcelain"])?.is_empty() {
+        git::run(repo, &["add", "--all"])?;
+        let message = format!("Sync from {}", device.name);
+        commit_as(repo, device, &["commit", "--quiet", "-m", &message])?;
+    }
+    let upstream = format!("origin/{branch}");
+    let refspec = format!("HEAD:{branch}");
+    for attempt in 1..=PUSH_ATTEMPTS {
+        let ahead = !has_commit(repo, &upstream)
+            || git::run(repo, &["rev-list", "--count", &format!("{upstream}..HEAD")])? != "0";
+        if !has_commit(repo, "HEAD") || !ahead {
+            return Ok(());
+        }
+        match git::run(repo, &["push", "--quiet", "origin", &refspec]) {
+            Ok(_) => return Ok(()),
+            Err(e) if attempt == PUSH_ATTEMPTS => {
+                return Err(e.context("Other devices keep pushing; sync again"));
+            }
+            // Rejected as another device pushed since the fetch
+            Err(_) => merge(repo, branch, device)?,
+        }
+    }
+    Ok(())
+}
+
+fn has_commit(repo: &Path, rev: &str) -> bool {
+    git::run(repo, &["rev-parse", "--verify", "--quiet", rev]).is_ok()
+}
+
+/// Run a git command that commits, as this device, so it works where git has no user
+fn commit_as(repo: &Path, device: &Device, args: &[&str]) -> Result<String> {
+    let name = format!("user.name={}", device.name);
+    let email = format!("user.email={}@scratchpad", device.id);
+    let mut full = vec!["-c", &name, "-c", &email];
+    full.extend_from_slice(args);
+    git::run(repo, &full)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn devices_sync_through_a_git_remote() {
+        let (a, b, remote) = (
+            tempfile::tempdir().unwrap(),
+            tempfile::tempdir().unwrap(),
+            tempfile::tempdir().unwrap(),
+        );
+        git::run(remote.path(), &["init", "--quiet", "--bare"]).unwrap();
+        let url = remote.path().to_string_lossy().to_string();
+        let device = |id: &str| Device {
+            id: id.to_string(),
+            name: id.to_string(),
+        };
+        let sync = |workspace: &Path, id: &str| {
+            let dir = state_dir(workspace, &url);
+            sync_through(
+                workspace,
+                &dir,
+                &url,
+                "user",
+                &device(id),
+                &Filter::default(),
+            )
+            .unwrap()
+        };
+        fs::create_dir_all(a.path().join("plans")).unwrap();
+        fs::create_dir_all(b.path().join("ideas")).unwrap();
+        fs::write(a.path().join("plans/notes.md"), "from a").unwrap();
+        fs::write(b.path().join("ideas/notes.md"), "from b").unwrap();
+        assert_eq!(sync(a.path(), "a").pushed, 1);
+        let report = sync(b.path(), "b");
+        assert_eq!((report.pulled, report.pushed), (1, 1));
+
+        // b's next push is rejected, as a pushed after b fetched, and goes out merged
+        fs::write(a.path().join("plans/notes.md"), "from a, again").unwrap();
+        fs::write(b.path().join("ideas/notes.md"), "from b, again").unwrap();
+        let dir = state_dir(b.path(), &url);
+        let mut log_b = log(&dir, "user", "b");
+        sync_with(b.path(), &dir, &mut log_b, "b", None, &Filter::default()).unwrap();
+        assert_eq!(sync(a.path(), "a").pulled, 1);
+        let branch = git::current_branch(&dir.join(CLONE_DIR)).unwrap();
+        publish(&dir.join(CLONE_DIR), &branch, &device("b")).unwrap();
+
+        let report = sync(a.path(), "a");
+        assert_eq!((report.pulled, report.pushed), (1, 0));
+        assert_eq!(sync(b.path(), "b").pulled, 1);
+        for workspace in [a.path(), b.path()] {
+            assert_eq!(
+                fs::read_to_string(workspace.join("plans/notes.md")).unwrap(),
+                "from a, again"
+            );
+            assert_eq!(
+                fs::read_to_string(workspace.join("ideas/notes.md")).unwrap(),
+                "from b, again"
+            );
+        }
+    }
+}
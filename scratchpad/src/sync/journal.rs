@@ -0,0 +1,153 @@
+//! Offline op journal: `.sync/journal.jsonl`
+//!
+//! While a sync server is configured, `Storage` appends a line here for every session it
+//! creates, renames, deletes or writes to, whether or not the server is reachable. The
+//! next `sp sync` replays the journal: renames become `session.rename` ops instead of a
+//! delete and re-upload of every file, and only the sessions it names are re-read in
+//! full. Replayed entries are dropped once the sync succeeds.
+
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::SYNC_DIR;
+
+const JOURNAL_FILE: &str = "journal.jsonl";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Create { session: String },
+    Rename { from: String, to: String },
+    Delete { session: String },
+    Write { session: String, file: String },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    at: DateTime<Utc>,
+    #[serde(flatten)]
+    event: Event,
+}
+
+fn path(workspace: &Path) -> PathBuf {
+    workspace.join(SYNC_DIR).join(JOURNAL_FILE)
+}
+
+pub fn record(workspace: &Path, event: Event) -> Result<()> {
+    let path = path(workspace);
+    fs::create_dir_all(workspace.join(SYNC_DIR)).context("Failed to create sync directory")?;
+    let entry = Entry {
+        at: Utc::now(),
+        event,
+    };
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(&entry)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Journal entries waiting to be synced
+#[derive(Debug, Default)]
+pub struct Replay {
+    pub events: Vec<Event>,
+    /// Bytes read, so entries appended during the sync survive `consume`
+    len: usize,
+}
+
+impl Replay {
+    /// Sessions whose files may have changed
+    pub fn touched(&self) -> HashSet<&str> {
+        self.events
+            .iter()
+            .flat_map(|event| match event {
+                Event::Create { session }
+                | Event::Delete { session }
+                | Event::Write { session, .. } => vec![session.as_str()],
+                Event::Rename { from, to } => vec![from.as_str(), to.as_str()],
+            })
+            .collect()
+    }
+}
+
+pub fn read(workspace: &Path) -> Result<Replay> {
+    let path = path(workspace);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Replay::default()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    // Only complete lines: a write may be in progress
+    let len = content.rfind('\n').map_or(0, |i| i + 1);
+    let events = content[..len]
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Entry>(line).ok())
+        .map(|entry| entry.event)
+        .collect();
+    Ok(Replay { events, len })
+}
+
+/// Drop the entries `replay` covered
+pub fn consume(workspace: &Path, replay: &Replay) -> Result<()> {
+    if replay.len == 0 {
+        return Ok(());
+    }
+    let path = path(workspace);
+    let content = fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let rest = content.get(replay.len..).unwrap_or_default();
+    fs::write(&path, rest).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replays_and_consumes_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path();
+        record(
+            workspace,
+            Event::Create {
+                session: "a".to_string(),
+            },
+        )
+        .unwrap();
+        record(
+            workspace,
+            Event::Rename {
+                from: "a".to_string(),
+                to: "b".to_string(),
+            },
+        )
+        .unwrap();
+
+        let replay = read(workspace).unwrap();
+        assert_eq!(replay.events.len(), 2);
+        assert_eq!(replay.touched(), HashSet::from(["a", "b"]));
+
+        // Appended while syncing: kept for the next sync
+        record(
+            workspace,
+            Event::Delete {
+                session: "b".to_string(),
+            },
+        )
+        .unwrap();
+        consume(workspace, &replay).unwrap();
+        assert_eq!(
+            read(workspace).unwrap().events,
+            [Event::Delete {
+                session: "b".to_string()
+            }]
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! An op log kept in a JSON-lines file, for syncing without a server
+//!
+//! It stores ops the way the server does: op ids are idempotency keys, and an op's log id
+//! is its line number, so cursors work the same against either.
+
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+use scratchpad_protocol::{Op, OpResult, OpStatus, PushOpsResponse};
+
+use super::Remote;
+
+pub struct OpLog {
+    path: PathBuf,
+    ops: Vec<Op>,
+    ids: HashSet<String>,
+}
+
+impl OpLog {
+    pub fn open(path: &Path) -> Result<Self> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        // A line cut short by an interrupted write is dropped with everything after it
+        let ops: Vec<Op> = content
+            .lines()
+            .map_while(|line| serde_json::from_str(line).ok())
+            .collect();
+        let ids = ops.iter().map(|op| op.id.clone()).collect();
+        Ok(Self {
+            path: path.to_path_buf(),
+            ops,
+            ids,
+        })
+    }
+}
+
+impl Remote for OpLog {
+    fn name(&self) -> String {
+        format!("log:{}", self.path.display())
+    }
+
+    fn pull(&mut self, after: Option<i64>, limit: usize) -> Result<Vec<Op>> {
+        let start = after.map_or(0, |id| id.max(0) as usize);
+        Ok(self
+            .ops
+            .iter()
+            .enumerate()
+            .skip(start)
+            .take(limit)
+            .map(|(i, op)| Op {
+                db_id: Some(i as i64 + 1),
+                ..op.clone()
+            })
+            .collect())
+    }
+
+    fn push(&mut self, ops: Vec<Op>) -> Result<PushOpsResponse> {
+        let mut lines = String::new();
+        let mut results = Vec::new();
+        for op in ops {
+            let (status, reason) = if op.id.is_empty() || op.op_type.is_empty() {
+                (
+                    OpStatus::Rejected,
+                    Some("missing id or op_type".to_string()),
+                )
+            } else if self.ids.contains(&op.id) {
+                (OpStatus::Duplicate, None)
+            } else {
+                (OpStatus::Accepted, None)
+            };
+            results.push(OpResult {
+                id: op.id.clone(),
+                status,
+                reason,
+            });
+            if status == OpStatus::Accepted {
+                let op = Op { db_id: None, ..op };
+                lines.push_str(&serde_json::to_string(&op)?);
+                lines.push('\n');
+                self.ids.insert(op.id.clone());
+                self.ops.push(op);
+            }
+        }
+
+        if !lines.is_empty() {
+            if let Some(dir) = self.path.parent() {
+                fs::create_dir_all(dir)
+                    .with_context(|| format!("Failed to create {}", dir.display()))?;
+            }
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .and_then(|mut file| file.write_all(lines.as_bytes()))
+                .with_context(|| format!("Failed to write {}", self.path.display()))?;
+        }
+        let accepted = results
+            .iter()
+            .filter(|r| r.status == OpStatus::Accepted)
+            .count();
+        Ok(PushOpsResponse { accepted, results })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn op(id: &str) -> Op {
+        Op {
+            db_id: None,
+            id: id.to_string(),
+            op_type: "file.put".to_string(),
+            payload: "{}".to_string(),
+            timestamp: "2026-01-01T00:00:00Z".to_string(),
+            client_id: None,
+        }
+    }
+
+    #[test]
+    fn stores_each_op_once_across_reopens() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ops.jsonl");
+        let mut log = OpLog::open(&path).unwrap();
+        let response = log.push(vec![op("a"), op("b"), op("")]).unwrap();
+        assert_eq!(response.accepted, 2);
+        assert_eq!(response.results[2].status, OpStatus::Rejected);
+
+        let mut log = OpLog::open(&path).unwrap();
+        let response = log.push(vec![op("b"), op("c")]).unwrap();
+        let statuses: Vec<OpStatus> = response.results.iter().map(|r| r.status).collect();
+        assert_eq!(statuses, [OpStatus::Duplicate, OpStatus::Accepted]);
+
+        let after_a = log.pull(Some(1), 10).unwrap();
+        let ids: Vec<(Option<i64>, &str)> = after_a
+            .iter()
+            .map(|op| (op.db_id, op.id.as_str()))
+            .collect();
+        assert_eq!(ids, [(Some(2), "b"), (Some(3), "c")]);
+    }
+}
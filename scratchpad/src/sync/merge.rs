@@ -0,0 +1,251 @@
+//! Three-way merge of markdown files edited on two machines
+//!
+//! When a pulled file changed here too, its content as of the last sync (kept in the
+//! remote's state directory, see `Bases`) is the base of a line-based merge: hunks only
+//! one side touched are taken from that side. Hunks both sides changed differently
+//! conflict, and the whole merge is then written next to the file as
+//! `<name>.conflict.md`, with the usual `<<<<<<<` / `=======` / `>>>>>>>` markers, while
+//! the local file stays as it was.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+
+const BASES_DIR: &str = "bases";
+const LOCAL_MARKER: &str = "<<<<<<< local";
+const SEPARATOR: &str = "=======";
+const REMOTE_MARKER: &str = ">>>>>>> remote";
+/// Past this many line pairs to compare, don't try: the files are treated as conflicting
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// Whether a sync path is merged rather than replaced on conflict
+pub fn mergeable(path: &str) -> bool {
+    path.ends_with(".md")
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Merge {
+    Clean(String),
+    /// Both sides, with markers around the hunks that conflict
+    Conflict(String),
+}
+
+/// Merge the edits `ours` and `theirs` made to `base`
+pub fn merge(base: &str, ours: &str, theirs: &str) -> Merge {
+    let base: Vec<&str> = base.split_inclusive('\n').collect();
+    let ours: Vec<&str> = ours.split_inclusive('\n').collect();
+    let theirs: Vec<&str> = theirs.split_inclusive('\n').collect();
+    let (Some(in_ours), Some(in_theirs)) = (matches(&base, &ours), matches(&base, &theirs)) else {
+        return Merge::Conflict(conflict(&ours, &theirs));
+    };
+
+    let mut out = String::new();
+    let mut conflicted = false;
+    let (mut i, mut j, mut k) = (0, 0, 0);
+    loop {
+        // Lines unchanged on both sides
+        while i < base.len() && in_ours[i] == Some(j) && in_theirs[i] == Some(k) {
+            out.push_str(base[i]);
+            (i, j, k) = (i + 1, j + 1, k + 1);
+        }
+        // Up to the next base line both sides kept
+        let next = (i..base.len()).find(|&b| in_ours[b].is_some() && in_theirs[b].is_some());
+        let (b, jo, kt) = match next {
+            Some(b) => (b, in_ours[b].unwrap_or(j), in_theirs[b].unwrap_or(k)),
+            None => (base.len(), ours.len(), theirs.len()),
+        };
+        let (old, mine, other) = (&base[i..b], &ours[j..jo], &theirs[k..kt]);
+        if mine == old || mine == other {
+            other.iter().for_each(|line| out.push_str(line));
+        } else if other == old {
+            mine.iter().for_each(|line| out.push_str(line));
+        } else {
+            out.push_str(&conflict(mine, other));
+            conflicted = true;
+        }
+        if next.is_none() {
+            break;
+        }
+        (i, j, k) = (b, jo, kt);
+    }
+
+    if conflicted {
+        Merge::Conflict(out)
+    } else {
+        Merge::Clean(out)
+    }
+}
+
+fn conflict(ours: &[&str], theirs: &[&str]) -> String {
+    let side = |lines: &[&str]| {
+        let mut text = lines.concat();
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text
+    };
+    format!(
+        "{LOCAL_MARKER}\n{}{SEPARATOR}\n{}{REMOTE_MARKER}\n",
+        side(ours),
+        side(theirs)
+    )
+}
+
+/// Whether a merge still has conflict markers in it
+pub fn has_markers(text: &str) -> bool {
+    text.lines()
+        .any(|line| line == LOCAL_MARKER || line == REMOTE_MARKER)
+}
+
+/// A merge with every conflict settled in favor of the remote side
+pub fn take_remote(marked: &str) -> String {
+    let mut out = String::new();
+    // Inside a conflict: Some(true) on the remote side, Some(false) on the local one
+    let mut remote_side = None;
+    for line in marked.split_inclusive('\n') {
+        match (line.trim_end_matches('\n'), remote_side) {
+            (LOCAL_MARKER, None) => remote_side = Some(false),
+            (SEPARATOR, Some(false)) => remote_side = Some(true),
+            (REMOTE_MARKER, Some(true)) => remote_side = None,
+            (_, Some(false)) => {}
+            _ => out.push_str(line),
+        }
+    }
+    out
+}
+
+/// For each line of `a`, the line of `b` it's kept as (a longest common subsequence),
+/// or None when the files are too large to compare
+fn matches(a: &[&str], b: &[&str]) -> Option<Vec<Option<usize>>> {
+    let mut result = vec![None; a.len()];
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    for (i, slot) in result.iter_mut().enumerate().take(prefix) {
+        *slot = Some(i);
+    }
+    for s in 0..suffix {
+        result[a.len() - 1 - s] = Some(b.len() - 1 - s);
+    }
+
+    let (a_mid, b_mid) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    let (n, m) = (a_mid.len(), b_mid.len());
+    if n == 0 || m == 0 {
+        return Some(result);
+    }
+    if n * m > MAX_DIFF_CELLS {
+        return None;
+    }
+    // lengths[i][j]: LCS length of a_mid[i..] and b_mid[j..]
+    let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+    let at = |i: usize, j: usize| i * (m + 1) + j;
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[at(i, j)] = if a_mid[i] == b_mid[j] {
+                lengths[at(i + 1, j + 1)] + 1
+            } else {
+                lengths[at(i + 1, j)].max(lengths[at(i, j + 1)])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if a_mid[i] == b_mid[j] {
+            result[prefix + i] = Some(prefix + j);
+            (i, j) = (i + 1, j + 1);
+        } else if lengths[at(i + 1, j)] >= lengths[at(i, j + 1)] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    Some(result)
+}
+
+/// `notes.md` → `notes.conflict.md`
+pub fn conflict_path(file: &Path) -> PathBuf {
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    file.with_file_name(format!("{stem}.conflict.md"))
+}
+
+/// Content of mergeable files as of the last sync, by hash
+pub struct Bases {
+    dir: PathBuf,
+}
+
+impl Bases {
+    /// The bases in a remote's state directory
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.join(BASES_DIR),
+        }
+    }
+
+    pub fn get(&self, hash: &str) -> Option<String> {
+        fs::read_to_string(self.dir.join(hash)).ok()
+    }
+
+    pub fn put(&self, hash: &str, content: &str) -> Result<()> {
+        let path = self.dir.join(hash);
+        if path.is_file() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir).context("Failed to create merge base cache")?;
+        let partial = self.dir.join(format!("{hash}.partial"));
+        fs::write(&partial, content)
+            .and_then(|()| fs::rename(&partial, &path))
+            .with_context(|| format!("Failed to save merge base {hash}"))
+    }
+
+    /// Drop bases not in `keep`
+    pub fn retain(&self, keep: &HashSet<&str>) {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !keep.contains(name.as_str()) {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_edits_to_different_lines() {
+        let base = "# Plan\n\nfirst\nsecond\nthird\n";
+        let ours = "# Plan\n\nfirst, edited here\nsecond\nthird\n";
+        let theirs = "# Plan\n\nfirst\nsecond\nthird\nfourth, added there\n";
+        assert_eq!(
+            merge(base, ours, theirs),
+            Merge::Clean(
+                "# Plan\n\nfirst, edited here\nsecond\nthird\nfourth, added there\n".into()
+            )
+        );
+        assert_eq!(merge(base, ours, ours), Merge::Clean(ours.into()));
+    }
+
+    #[test]
+    fn marks_lines_edited_on_both_sides() {
+        let merged = merge("a\nb\nc", "a\nB here\nc", "a\nB there\nc");
+        assert_eq!(
+            merged,
+            Merge::Conflict("a\n<<<<<<< local\nB here\n=======\nB there\n>>>>>>> remote\nc".into())
+        );
+        let Merge::Conflict(marked) = merged else {
+            unreachable!()
+        };
+        assert!(has_markers(&marked));
+        assert_eq!(take_remote(&marked), "a\nB there\nc");
+    }
+}
@@ -0,0 +1,1653 @@
+//! Session sync with `sp-server` (`sp sync`)
+//!
+//! Session files are synced as `file.put` / `file.delete` ops addressed by their
+//! workspace-relative path (`<slug>/<file>`), plus the dashboard note. Hidden files stay
+//! on this machine, except a session's `.session.toml` and `.spignore`; `.gitignore` and
+//! `.spignore` rules apply as they do for snapshots, and `[sync] ignore` and
+//! `max_file_size` keep more files local (see `filter.rs`).
+//!
+//! `.sync/state.json` remembers the content hash of every file as of the last sync and
+//! the server id of the last op applied. That hash is the base of a three-way comparison:
+//! a remote change is applied when the local file still matches the base. When both sides
+//! changed, markdown files are merged line by line against their last synced content
+//! (see `merge.rs`); otherwise, or when the merge conflicts, the local version is kept
+//! (and pushed) with the remote one written next to it as `<name>.remote.<ext>`, or the
+//! conflicting merge as `<name>.conflict.md`.
+//!
+//! Files of `CHUNKED_FILE_SIZE` or more are sent as content-defined chunks (`chunk.put`
+//! ops) plus a `file.put` listing them, so a small edit to a large file syncs only the
+//! chunks it touched (see `chunk.rs`).
+//!
+//! Files that aren't UTF-8 text — screenshots, PDFs, binaries — go up whole as a blob,
+//! plus a `file.put` referencing it by hash and size. The server stores blobs apart from
+//! the op log (`/api/blobs`); other remotes, and encrypted syncs, get a `blob.put` op
+//! carrying the bytes. A blob is sent once per remote whatever the number of files
+//! holding it, and the receiving side writes the file once the blob it references has
+//! arrived and matches its hash.
+//!
+//! Transfers survive dropped connections: pulls come in pages with the cursor saved after
+//! each, and pushes in batches with the state saved after each, while chunks already
+//! received or acknowledged stay in the chunk cache, as do blobs. A retried sync only moves what is
+//! left. `--limit-rate` caps the speed of both directions (see `throttle.rs`).
+//!
+//! Besides the server, a peer machine can be the remote (`sp sync --peer`, see `peer.rs`),
+//! as can a shared folder (`folder.rs`) or a git repository (`git.rs`).
+//! Each remote has its own state directory — `.sync/` itself for the server — holding
+//! the state file and chunk cache for it.
+//!
+//! With `[server] encrypt = true`, ops are sealed just before they're pushed and opened
+//! as they're pulled (see `seal.rs`); everything in between works on plain payloads.
+//!
+//! Session renames recorded in the offline journal (see `journal.rs`) are pushed as
+//! `session.rename` ops, moving the directory on other machines instead of deleting and
+//! re-uploading it. Files are only re-read when their session is in the journal or their
+//! size or modification time changed since the last sync.
+//!
+//! A new machine can start from a snapshot of the workspace stored on the server instead
+//! of replaying every op (see `snapshot.rs`).
+
+mod chunk;
+mod client;
+pub mod conflicts;
+pub mod device;
+mod filter;
+pub mod folder;
+pub mod git;
+pub mod journal;
+mod log;
+mod merge;
+pub mod peer;
+pub mod seal;
+pub mod snapshot;
+pub mod staging;
+pub mod status;
+pub mod throttle;
+pub mod watch;
+
+use chunk::{CHUNKED_FILE_SIZE, ChunkStore};
+use client::Client;
+pub use client::{login, whoami};
+use conflicts::Conflict;
+use device::Device;
+pub use filter::Filter;
+use journal::{Event, Replay};
+use merge::{Bases, Merge};
+use seal::Sealer;
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::hash::{DefaultHasher, Hash as _, Hasher as _};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use anyhow::{Context as _, Result};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use scratchpad_protocol::{Op, OpStatus, PushOpsResponse};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::bulk::Pattern;
+use crate::models::{Context, ServerConfig, SyncConfig};
+use crate::names::slugify;
+use crate::spignore::{IGNORE_FILE, IgnoreRules};
+use crate::storage::{DASHBOARD_FILE, META_FILE, Storage};
+
+/// Hidden workspace directory for sync bookkeeping
+pub const SYNC_DIR: &str = ".sync";
+const STATE_FILE: &str = "state.json";
+
+pub const PUT: &str = "file.put";
+pub const DELETE: &str = "file.delete";
+pub const CHUNK: &str = "chunk.put";
+pub const BLOB: &str = "blob.put";
+pub const RENAME: &str = "session.rename";
+
+/// Most ops pulled per request; the cursor is saved after each page, so an interrupted
+/// pull resumes where it stopped
+const PULL_PAGE: usize = 100;
+
+/// Most ops, and about the most payload bytes, sent per push request
+const PUSH_BATCH: usize = 200;
+const PUSH_BATCH_BYTES: usize = 1024 * 1024;
+
+/// Payload of `file.put` (with content, chunks or a blob) and `file.delete` ops
+#[derive(Debug, Serialize, Deserialize)]
+struct FileChange {
+    /// Workspace-relative, `/`-separated
+    path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
+    /// Hashes of the chunks making up a large file, in order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    chunks: Option<Vec<String>>,
+    /// The blob holding a file that isn't text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    blob: Option<BlobRef>,
+}
+
+impl FileChange {
+    fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            content: None,
+            chunks: None,
+            blob: None,
+        }
+    }
+}
+
+/// A blob as a `file.put` references it
+#[derive(Debug, Serialize, Deserialize)]
+struct BlobRef {
+    /// SHA-256 of the blob, which is also the file's content hash
+    hash: String,
+    size: u64,
+}
+
+/// Payload of `chunk.put` and `blob.put` ops
+#[derive(Debug, Serialize, Deserialize)]
+struct ChunkData {
+    hash: String,
+    /// Base64
+    data: String,
+}
+
+/// Payload of `session.rename` ops
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionRename {
+    from: String,
+    to: String,
+}
+
+/// Size and modification time of a synced file, to skip re-reading unchanged ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct FileStat {
+    size: u64,
+    modified_ns: u128,
+}
+
+impl FileStat {
+    fn of(file: &Path) -> Option<Self> {
+        let metadata = fs::metadata(file).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            size: metadata.len(),
+            modified_ns: modified.as_nanos(),
+        })
+    }
+}
+
+/// What the last sync left behind
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SyncState {
+    /// Server and workspace this state is for; syncing elsewhere starts over
+    #[serde(default)]
+    remote: String,
+    /// Server id of the last op applied
+    #[serde(default)]
+    cursor: Option<i64>,
+    /// Op id of that op, which a snapshot taken from this state covers the log up to
+    #[serde(default)]
+    last_op: Option<String>,
+    /// Content hash of each synced file as of the last sync
+    #[serde(default)]
+    files: BTreeMap<String, String>,
+    /// Chunks of each file synced as chunks, as of the last sync
+    #[serde(default)]
+    chunked: BTreeMap<String, Vec<String>>,
+    /// Stat of each synced file when its hash was last taken
+    #[serde(default)]
+    stats: BTreeMap<String, FileStat>,
+}
+
+impl SyncState {
+    fn path(dir: &Path) -> PathBuf {
+        dir.join(STATE_FILE)
+    }
+
+    /// State saved in `dir` for `remote`, or a fresh one
+    pub fn load(dir: &Path, remote: &str) -> Result<Self> {
+        let path = Self::path(dir);
+        let state: Self = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("Invalid sync state {}", path.display()))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", path.display()));
+            }
+        };
+        if state.remote == remote {
+            Ok(state)
+        } else {
+            Ok(Self {
+                remote: remote.to_string(),
+                ..Self::default()
+            })
+        }
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let path = Self::path(dir);
+        fs::create_dir_all(dir).context("Failed to create sync directory")?;
+        fs::write(&path, serde_json::to_string_pretty(self)? + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Move what's known about session `from` to `to`
+    fn rename_session(&mut self, from: &str, to: &str) {
+        fn rename<V>(map: &mut BTreeMap<String, V>, from: &str, to: &str) {
+            let moved: Vec<String> = map
+                .keys()
+                .filter(|path| path.strip_prefix(from).is_some_and(|p| p.starts_with('/')))
+                .cloned()
+                .collect();
+            for path in moved {
+                if let Some(value) = map.remove(&path) {
+                    map.insert(format!("{to}{}", &path[from.len()..]), value);
+                }
+            }
+        }
+        rename(&mut self.files, from, to);
+        rename(&mut self.chunked, from, to);
+        rename(&mut self.stats, from, to);
+    }
+
+    fn has_session(&self, session: &str) -> bool {
+        self.files.keys().any(|path| {
+            path.strip_prefix(session)
+                .is_some_and(|p| p.starts_with('/'))
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Remote changes written to the workspace
+    pub pulled: usize,
+    /// Local changes newly stored on the server
+    pub pushed: usize,
+    /// Files changed on both sides that couldn't be merged; the remote version (or the
+    /// merge, with conflict markers) was saved next to them
+    pub conflicts: Vec<String>,
+    /// Files changed on both sides whose changes were merged
+    pub merged: Vec<String>,
+    /// Files left out for being over `[sync] max_file_size`
+    pub too_large: Vec<String>,
+    /// Files the server refused, with its reason
+    pub rejected: Vec<(String, String)>,
+    /// Remote files not written because some of their chunks, or their blob, never
+    /// arrived
+    pub incomplete: Vec<String>,
+    /// Remote changes encrypted with a key this machine doesn't have
+    pub unreadable: usize,
+}
+
+/// Server workspace a context syncs to: `server.workspace` if set, else the default
+pub fn workspace_id(server: &ServerConfig, context: &Context) -> String {
+    match &server.workspace {
+        Some(id) => id.clone(),
+        None => default_workspace_id(context),
+    }
+}
+
+/// `user`, or `project-<repo directory>`
+pub fn default_workspace_id(context: &Context) -> String {
+    match context {
+        Context::User => "user".to_string(),
+        Context::Project(path) => {
+            let repo = path
+                .parent()
+                .and_then(|p| p.file_name())
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            format!("project-{}", slugify(&repo).unwrap_or_default())
+        }
+    }
+}
+
+/// `~/notes` → `/home/me/notes`
+pub fn expand_home(path: &str) -> String {
+    let home = directories::BaseDirs::new().map(|d| d.home_dir().to_string_lossy().to_string());
+    match (path.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{home}{rest}")
+        }
+        _ => path.to_string(),
+    }
+}
+
+/// How `[sync]` rules name a context: `user` or `project:<repo path>`
+pub fn context_label(context: &Context) -> String {
+    match context {
+        Context::User => "user".to_string(),
+        Context::Project(path) => {
+            let repo = path.parent().unwrap_or(path);
+            format!("project:{}", repo.display())
+        }
+    }
+}
+
+/// Whether the `[sync]` rules let `context` be synced
+pub fn allowed(rules: Option<&SyncConfig>, context: &Context) -> Result<bool> {
+    let Some(rules) = rules else {
+        return Ok(true);
+    };
+    let label = context_label(context);
+    let matches = |patterns: &[String]| -> Result<bool> {
+        for pattern in patterns {
+            let pattern = match pattern.strip_prefix("project:") {
+                Some(path) => format!("project:{}", expand_home(path)),
+                None => pattern.clone(),
+            };
+            if Pattern::new(&pattern)?.matches(&label) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    };
+    Ok((rules.include.is_empty() || matches(&rules.include)?) && !matches(&rules.exclude)?)
+}
+
+/// An op log to sync with: the sync server, or a peer
+pub trait Remote {
+    /// Identifies the log; sync state saved for another one is discarded
+    fn name(&self) -> String;
+
+    /// Up to `limit` ops after the one with log id `after` (from the first when None),
+    /// oldest first
+    fn pull(&mut self, after: Option<i64>, limit: usize) -> Result<Vec<Op>>;
+
+    fn push(&mut self, ops: Vec<Op>) -> Result<PushOpsResponse>;
+
+    /// Store a blob apart from the op log, when the remote can. Returns false when it
+    /// can't, and the blob goes in a `blob.put` op instead.
+    fn put_blob(&mut self, _hash: &str, _data: &[u8]) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// A blob stored apart from the op log, if the remote has it
+    fn get_blob(&mut self, _hash: &str) -> Result<Option<Vec<u8>>> {
+        Ok(None)
+    }
+}
+
+/// Pull changes from the sync server into the workspace, then push local ones, at most
+/// `rate` bytes per second each way if set
+pub fn run(
+    storage: &Storage,
+    server: &ServerConfig,
+    device: &Device,
+    rate: Option<u64>,
+) -> Result<Report> {
+    storage.ensure_workspace()?;
+    let workspace = storage.workspace_path();
+    let mut client = Client::new(
+        server,
+        &workspace_id(server, storage.context()),
+        device,
+        rate,
+    );
+    sync_with(
+        &workspace,
+        &workspace.join(SYNC_DIR),
+        &mut client,
+        &device.id,
+        sealer(server)?.as_ref(),
+        &filter(storage)?,
+    )
+}
+
+/// What `[sync]` keeps on this machine
+pub fn filter(storage: &Storage) -> Result<Filter> {
+    Filter::new(&storage.workspace_path(), storage.config().sync.as_ref())
+}
+
+/// The sealer for `[server] encrypt = true`
+fn sealer(server: &ServerConfig) -> Result<Option<Sealer>> {
+    if server.encrypt {
+        Ok(Some(Sealer::load(&seal::key_path())?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Sync `workspace` with `remote`, keeping what's known about it in `dir`. Ops pushed
+/// are stamped with the id of `device`; files `filter` leaves out aren't pushed.
+pub fn sync_with(
+    workspace: &Path,
+    dir: &Path,
+    remote: &mut dyn Remote,
+    device: &str,
+    sealer: Option<&Sealer>,
+    filter: &Filter,
+) -> Result<Report> {
+    let store = ChunkStore::new(dir);
+    let bases = Bases::new(dir);
+    let mut state = SyncState::load(dir, &remote.name())?;
+    let mut report = Report::default();
+    let replay = journal::read(workspace)?;
+
+    pull_into(workspace, dir, remote, sealer, &mut state, &mut report)?;
+
+    let mut pending = local_changes(workspace, &store, &mut state, &replay, filter, &mut report)?;
+    // Blob contents would reach the remote in the clear, so encrypted syncs keep them in
+    // sealed ops
+    if sealer.is_none() {
+        let mut kept = Vec::with_capacity(pending.len());
+        for p in pending {
+            match &p {
+                Pending::Chunk { op, hash, data } if op.op_type == BLOB => {
+                    if remote.put_blob(hash, data)? {
+                        state.record(&store, &bases, &p)?;
+                    } else {
+                        kept.push(p);
+                    }
+                }
+                _ => kept.push(p),
+            }
+        }
+        pending = kept;
+    }
+    for batch in batches(&pending) {
+        let ops = batch
+            .iter()
+            .map(|p| p.outgoing(device, sealer))
+            .collect::<Result<_>>()?;
+        let response = remote.push(ops)?;
+        for (pending, result) in batch.iter().zip(response.results) {
+            match result.status {
+                OpStatus::Accepted | OpStatus::Duplicate => {
+                    if result.status == OpStatus::Accepted && pending.is_file() {
+                        report.pushed += 1;
+                    }
+                    state.record(&store, &bases, pending)?;
+                }
+                OpStatus::Rejected => report
+                    .rejected
+                    .push((pending.label(), result.reason.unwrap_or_default())),
+            }
+        }
+        state.save(dir)?;
+    }
+    journal::consume(workspace, &replay)?;
+
+    // A blob's hash is its file's, so synced files keep theirs cached
+    let referenced: HashSet<&str> = state
+        .chunked
+        .values()
+        .flatten()
+        .chain(state.files.values())
+        .map(String::as_str)
+        .collect();
+    store.retain(&referenced);
+    let merge_bases: HashSet<&str> = state
+        .files
+        .iter()
+        .filter(|(path, _)| merge::mergeable(path))
+        .map(|(_, hash)| hash.as_str())
+        .collect();
+    bases.retain(&merge_bases);
+    Ok(report)
+}
+
+/// Pull changes from the sync server into the workspace, leaving local ones for the
+/// next sync (the TUI's live updates)
+pub fn pull(storage: &Storage, server: &ServerConfig, device: &Device) -> Result<Report> {
+    let workspace = storage.workspace_path();
+    let dir = workspace.join(SYNC_DIR);
+    let mut client = Client::new(
+        server,
+        &workspace_id(server, storage.context()),
+        device,
+        None,
+    );
+    let mut state = SyncState::load(&dir, &client.name())?;
+    let mut report = Report::default();
+    pull_into(
+        &workspace,
+        &dir,
+        &mut client,
+        sealer(server)?.as_ref(),
+        &mut state,
+        &mut report,
+    )?;
+    Ok(report)
+}
+
+/// Apply the remote's ops past the cursor, a page at a time, saving the state after each
+fn pull_into(
+    workspace: &Path,
+    dir: &Path,
+    remote: &mut dyn Remote,
+    sealer: Option<&Sealer>,
+    state: &mut SyncState,
+    report: &mut Report,
+) -> Result<()> {
+    let store = ChunkStore::new(dir);
+    let bases = Bases::new(dir);
+    loop {
+        let cursor = state.cursor;
+        let mut ops = remote.pull(cursor, PULL_PAGE)?;
+        for op in &mut ops {
+            open(op, sealer, report);
+        }
+        fetch_blobs(remote, &store, &ops)?;
+        apply(workspace, &store, &bases, state, &ops, report)?;
+        state.save(dir)?;
+        if ops.len() < PULL_PAGE || state.cursor == cursor {
+            return Ok(());
+        }
+    }
+}
+
+/// Fetch the blobs `ops` reference that aren't cached or carried by a `blob.put` among
+/// them, from remotes that store blobs apart from the op log
+fn fetch_blobs(remote: &mut dyn Remote, store: &ChunkStore, ops: &[Op]) -> Result<()> {
+    let carried: HashSet<&str> = ops
+        .iter()
+        .filter(|op| op.op_type == BLOB)
+        .filter_map(|op| op.id.strip_prefix("blob-"))
+        .collect();
+    for op in ops.iter().filter(|op| op.op_type == PUT) {
+        let Ok(FileChange {
+            blob: Some(blob), ..
+        }) = serde_json::from_str(&op.payload)
+        else {
+            continue;
+        };
+        if store.contains(&blob.hash) || carried.contains(blob.hash.as_str()) {
+            continue;
+        }
+        // One that doesn't match its hash is left out, and its file listed as incomplete
+        if let Some(data) = remote.get_blob(&blob.hash)?
+            && hash(&data) == blob.hash
+        {
+            store.put(&blob.hash, &data)?;
+        }
+    }
+    Ok(())
+}
+
+/// A local change waiting to be pushed
+enum Pending {
+    /// A file written (with the hash of its content, and its chunks if chunked) or deleted
+    File {
+        op: Op,
+        path: String,
+        hash: Option<String>,
+        chunks: Option<Vec<String>>,
+        stat: Option<FileStat>,
+    },
+    /// A chunk of a large file, or the blob of a binary one, cached once the server
+    /// has it
+    Chunk { op: Op, hash: String, data: Vec<u8> },
+    /// A session renamed; the state already follows it
+    Rename { op: Op, from: String, to: String },
+}
+
+impl Pending {
+    fn op(&self) -> &Op {
+        match self {
+            Pending::File { op, .. } | Pending::Chunk { op, .. } | Pending::Rename { op, .. } => op,
+        }
+    }
+
+    /// The op as sent: from `device`, and sealed when encrypting, with chunk ids that
+    /// don't reveal the chunk's content hash
+    fn outgoing(&self, device: &str, sealer: Option<&Sealer>) -> Result<Op> {
+        let op = Op {
+            client_id: Some(device.to_string()),
+            ..self.op().clone()
+        };
+        let Some(sealer) = sealer else {
+            return Ok(op);
+        };
+        let id = match self {
+            Pending::Chunk { op, hash, .. } => {
+                let (prefix, _) = op.id.split_once('-').unwrap_or(("chunk", ""));
+                format!("{prefix}-{}", sealer.chunk_id(hash))
+            }
+            _ => op.id.clone(),
+        };
+        Ok(Op {
+            id,
+            payload: sealer.seal(&op.payload)?,
+            ..op
+        })
+    }
+
+    fn chunk(hash: &str, data: &[u8]) -> Result<Self> {
+        let payload = ChunkData {
+            hash: hash.to_string(),
+            data: BASE64.encode(data),
+        };
+        Ok(Pending::Chunk {
+            // Chunk ids are content-derived, so the server stores each once
+            op: new_op(CHUNK, format!("chunk-{hash}"), &payload)?,
+            hash: hash.to_string(),
+            data: data.to_vec(),
+        })
+    }
+
+    fn blob(hash: &str, data: &[u8]) -> Result<Self> {
+        let payload = ChunkData {
+            hash: hash.to_string(),
+            data: BASE64.encode(data),
+        };
+        Ok(Pending::Chunk {
+            op: new_op(BLOB, format!("blob-{hash}"), &payload)?,
+            hash: hash.to_string(),
+            data: data.to_vec(),
+        })
+    }
+
+    /// Whether it counts as a change pushed
+    fn is_file(&self) -> bool {
+        !matches!(self, Pending::Chunk { .. })
+    }
+
+    fn label(&self) -> String {
+        match self {
+            Pending::File { path, .. } => path.clone(),
+            Pending::Chunk { op, hash, .. } if op.op_type == BLOB => format!("blob {hash}"),
+            Pending::Chunk { hash, .. } => format!("chunk {hash}"),
+            Pending::Rename { from, to, .. } => format!("{from} → {to}"),
+        }
+    }
+}
+
+impl SyncState {
+    /// Note a change the server now has
+    fn record(&mut self, store: &ChunkStore, bases: &Bases, pending: &Pending) -> Result<()> {
+        match pending {
+            Pending::File {
+                op,
+                path,
+                hash,
+                chunks,
+                stat,
+            } => {
+                if let Some(hash) = hash
+                    && merge::mergeable(path)
+                    && let Ok(FileChange {
+                        content: Some(content),
+                        ..
+                    }) = serde_json::from_str(&op.payload)
+                {
+                    bases.put(hash, &content)?;
+                }
+                match hash {
+                    Some(hash) => self.files.insert(path.clone(), hash.clone()),
+                    None => self.files.remove(path),
+                };
+                match chunks {
+                    Some(chunks) => self.chunked.insert(path.clone(), chunks.clone()),
+                    None => self.chunked.remove(path),
+                };
+                match stat {
+                    Some(stat) => self.stats.insert(path.clone(), *stat),
+                    None => self.stats.remove(path),
+                };
+            }
+            Pending::Chunk { hash, data, .. } => store.put(hash, data)?,
+            Pending::Rename { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+/// Decrypt a pulled op in place. Plain ops pass through; sealed ones this machine can't
+/// open are counted and left sealed, which `apply` skips.
+fn open(op: &mut Op, sealer: Option<&Sealer>, report: &mut Report) {
+    if !seal::is_sealed(&op.payload) {
+        return;
+    }
+    match sealer.and_then(|s| s.open(&op.payload)) {
+        Some(payload) => op.payload = payload,
+        None => report.unreadable += 1,
+    }
+}
+
+/// Push requests of at most `PUSH_BATCH` ops and about `PUSH_BATCH_BYTES` of payload
+fn batches(pending: &[Pending]) -> Vec<&[Pending]> {
+    let mut batches = Vec::new();
+    let (mut start, mut bytes) = (0, 0);
+    for (i, p) in pending.iter().enumerate() {
+        let size = p.op().payload.len();
+        if i > start && (i - start == PUSH_BATCH || bytes + size > PUSH_BATCH_BYTES) {
+            batches.push(&pending[start..i]);
+            (start, bytes) = (i, 0);
+        }
+        bytes += size;
+    }
+    if start < pending.len() {
+        batches.push(&pending[start..]);
+    }
+    batches
+}
+
+fn new_op(op_type: &str, id: String, payload: &impl Serialize) -> Result<Op> {
+    Ok(Op {
+        db_id: None,
+        id,
+        op_type: op_type.to_string(),
+        payload: serde_json::to_string(payload)?,
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        client_id: None,
+    })
+}
+
+fn random_id() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+/// Ops for the journal's session renames, then for every synced file that changed
+/// since the last sync. Large files are split into chunks and binary ones sent as blobs,
+/// sending only the chunks and blobs the server doesn't have yet.
+fn local_changes(
+    workspace: &Path,
+    store: &ChunkStore,
+    state: &mut SyncState,
+    replay: &Replay,
+    filter: &Filter,
+    report: &mut Report,
+) -> Result<Vec<Pending>> {
+    let mut pending = Vec::new();
+    for event in &replay.events {
+        if let Event::Rename { from, to } = event
+            && state.has_session(from)
+            && !state.has_session(to)
+        {
+            let rename = SessionRename {
+                from: from.clone(),
+                to: to.clone(),
+            };
+            pending.push(Pending::Rename {
+                op: new_op(RENAME, random_id(), &rename)?,
+                from: from.clone(),
+                to: to.clone(),
+            });
+            state.rename_session(from, to);
+        }
+    }
+
+    let files = scan(workspace, filter)?;
+    let touched = replay.touched();
+    let mut queued_chunks = HashSet::new();
+    for (path, file) in &files {
+        let stat = FileStat::of(file);
+        let session = path.split('/').next().unwrap_or_default();
+        if !touched.contains(session)
+            && stat.is_some()
+            && state.stats.get(path) == stat.as_ref()
+            && state.files.contains_key(path)
+        {
+            continue;
+        }
+        if stat
+            .as_ref()
+            .is_some_and(|stat| filter.too_large(stat.size))
+        {
+            report.too_large.push(path.clone());
+            continue;
+        }
+        let bytes = fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+        let hash = hash(&bytes);
+        if state.files.get(path) == Some(&hash) {
+            if let Some(stat) = stat {
+                state.stats.insert(path.clone(), stat);
+            }
+            continue;
+        }
+
+        let (change, chunks) = if std::str::from_utf8(&bytes).is_err() {
+            if !store.contains(&hash) && queued_chunks.insert(hash.clone()) {
+                pending.push(Pending::blob(&hash, &bytes)?);
+            }
+            let change = FileChange {
+                blob: Some(BlobRef {
+                    hash: hash.clone(),
+                    size: bytes.len() as u64,
+                }),
+                ..FileChange::new(path)
+            };
+            (change, None)
+        } else if bytes.len() >= CHUNKED_FILE_SIZE {
+            let mut hashes = Vec::new();
+            for data in chunk::split(&bytes) {
+                let chunk_hash = self::hash(data);
+                if !store.contains(&chunk_hash) && queued_chunks.insert(chunk_hash.clone()) {
+                    pending.push(Pending::chunk(&chunk_hash, data)?);
+                }
+                hashes.push(chunk_hash);
+            }
+            let change = FileChange {
+                chunks: Some(hashes.clone()),
+                ..FileChange::new(path)
+            };
+            (change, Some(hashes))
+        } else {
+            let change = FileChange {
+                content: Some(String::from_utf8(bytes).unwrap_or_default()),
+                ..FileChange::new(path)
+            };
+            (change, None)
+        };
+        pending.push(Pending::File {
+            op: new_op(PUT, random_id(), &change)?,
+            path: path.clone(),
+            hash: Some(hash),
+            chunks,
+            stat,
+        });
+    }
+    let removed = state
+        .files
+        .keys()
+        .filter(|p| !files.contains_key(*p) && !filter.ignores(Path::new(p), false));
+    for path in removed {
+        pending.push(Pending::File {
+            op: new_op(DELETE, random_id(), &FileChange::new(path))?,
+            path: path.clone(),
+            hash: None,
+            chunks: None,
+            stat: None,
+        });
+    }
+    Ok(pending)
+}
+
+/// Apply ops pulled from the server, advancing the cursor past them
+fn apply(
+    workspace: &Path,
+    store: &ChunkStore,
+    bases: &Bases,
+    state: &mut SyncState,
+    ops: &[Op],
+    report: &mut Report,
+) -> Result<()> {
+    for op in ops {
+        if let Some(id) = op.db_id
+            && state.cursor.is_none_or(|cursor| id > cursor)
+        {
+            state.cursor = Some(id);
+            state.last_op = Some(op.id.clone());
+        }
+        // Ops this version doesn't understand, or for paths it never writes, are skipped
+        if op.op_type == CHUNK || op.op_type == BLOB {
+            if let Ok(chunk) = serde_json::from_str::<ChunkData>(&op.payload)
+                && let Ok(data) = BASE64.decode(&chunk.data)
+                && hash(&data) == chunk.hash
+            {
+                store.put(&chunk.hash, &data)?;
+            }
+            continue;
+        }
+        if op.op_type == RENAME {
+            if let Ok(rename) = serde_json::from_str::<SessionRename>(&op.payload) {
+                apply_rename(workspace, state, &rename, report)?;
+            }
+            continue;
+        }
+        let Ok(change) = serde_json::from_str::<FileChange>(&op.payload) else {
+            continue;
+        };
+        let Some(file) = local_path(workspace, &change.path) else {
+            continue;
+        };
+        let local = match fs::read(&file) {
+            Ok(bytes) => Some(hash(&bytes)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", file.display())),
+        };
+        let base = state.files.get(&change.path).cloned();
+
+        match op.op_type.as_str() {
+            PUT => {
+                let assembled = match (change.content, &change.chunks, &change.blob) {
+                    (Some(content), _, _) => Some(content.into_bytes()),
+                    (None, Some(chunks), _) => store.assemble(chunks),
+                    (None, None, Some(blob)) => store.assemble(std::slice::from_ref(&blob.hash)),
+                    (None, None, None) => continue,
+                };
+                let Some(content) = assembled else {
+                    report.incomplete.push(change.path);
+                    continue;
+                };
+                let remote = hash(&content);
+                if base.as_ref() == Some(&remote) || local.as_ref() == Some(&remote) {
+                    // Already here, e.g. our own push coming back
+                } else if local.is_none() || local == base {
+                    write_file(&file, &content)?;
+                    if let Some(stat) = FileStat::of(&file) {
+                        state.stats.insert(change.path.clone(), stat);
+                    }
+                    report.pulled += 1;
+                } else {
+                    // Both sides changed: merge, or keep ours, which then gets pushed
+                    // over theirs
+                    let saved =
+                        match three_way(&file, &change.path, base.as_deref(), bases, &content) {
+                            Some(Merge::Clean(merged)) => {
+                                write_file(&file, merged.as_bytes())?;
+                                report.merged.push(change.path.clone());
+                                None
+                            }
+                            Some(Merge::Conflict(marked)) => {
+                                let saved = merge::conflict_path(&file);
+                                write_file(&saved, marked.as_bytes())?;
+                                Some((saved, true))
+                            }
+                            None => {
+                                let saved = conflict_path(&file);
+                                write_file(&saved, &content)?;
+                                Some((saved, false))
+                            }
+                        };
+                    if let Some((saved, merged)) = saved {
+                        let saved = saved.strip_prefix(workspace).unwrap_or(&saved);
+                        conflicts::record(
+                            workspace,
+                            Conflict {
+                                path: change.path.clone(),
+                                saved: saved.to_string_lossy().replace('\\', "/"),
+                                merged,
+                            },
+                        )?;
+                        report.conflicts.push(change.path.clone());
+                    }
+                    // The base no longer matches the local file: make sure it's re-read
+                    state.stats.remove(&change.path);
+                }
+                if merge::mergeable(&change.path)
+                    && let Ok(text) = std::str::from_utf8(&content)
+                {
+                    bases.put(&remote, text)?;
+                }
+                match change.chunks {
+                    Some(chunks) => state.chunked.insert(change.path.clone(), chunks),
+                    None => state.chunked.remove(&change.path),
+                };
+                state.files.insert(change.path, remote);
+            }
+            DELETE => {
+                if base.is_some() && local.is_some() && local == base {
+                    fs::remove_file(&file)
+                        .with_context(|| format!("Failed to delete {}", file.display()))?;
+                    prune_empty_dirs(workspace, &file);
+                    report.pulled += 1;
+                }
+                // A file edited here since survives, and is pushed back as new
+                state.files.remove(&change.path);
+                state.chunked.remove(&change.path);
+                state.stats.remove(&change.path);
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Merge a remote edit of `file` with the local one, if it's mergeable and its base is
+/// still known
+fn three_way(
+    file: &Path,
+    path: &str,
+    base: Option<&str>,
+    bases: &Bases,
+    remote: &[u8],
+) -> Option<Merge> {
+    if !merge::mergeable(path) {
+        return None;
+    }
+    let base = bases.get(base?)?;
+    let local = fs::read_to_string(file).ok()?;
+    Some(merge::merge(
+        &base,
+        &local,
+        std::str::from_utf8(remote).ok()?,
+    ))
+}
+
+/// Follow a session renamed elsewhere. If the new name is taken here, nothing moves and
+/// the session's files come back under the new name on their own.
+fn apply_rename(
+    workspace: &Path,
+    state: &mut SyncState,
+    rename: &SessionRename,
+    report: &mut Report,
+) -> Result<()> {
+    let (Some(from), Some(to)) = (
+        session_path(workspace, &rename.from),
+        session_path(workspace, &rename.to),
+    ) else {
+        return Ok(());
+    };
+    if to.exists() || state.has_session(&rename.to) {
+        if from.is_dir() {
+            report
+                .conflicts
+                .push(format!("{} → {}", rename.from, rename.to));
+        }
+        return Ok(());
+    }
+    if from.is_dir() {
+        fs::rename(&from, &to).with_context(|| format!("Failed to rename {}", from.display()))?;
+        report.pulled += 1;
+    }
+    state.rename_session(&rename.from, &rename.to);
+    Ok(())
+}
+
+/// Every synced file in the workspace, by its sync path
+fn scan(workspace: &Path, filter: &Filter) -> Result<BTreeMap<String, PathBuf>> {
+    let mut files = BTreeMap::new();
+    let dashboard = workspace.join(DASHBOARD_FILE);
+    if dashboard.is_file() {
+        files.insert(DASHBOARD_FILE.to_string(), dashboard);
+    }
+    for entry in fs::read_dir(workspace).context("Failed to read workspace directory")? {
+        let dir = entry?.path();
+        if !dir.is_dir()
+            || dir
+                .file_name()
+                .is_some_and(|n| is_hidden(&n.to_string_lossy()))
+        {
+            continue;
+        }
+        if filter.ignores(&dir, true) {
+            continue;
+        }
+        let rules = IgnoreRules::for_session(&dir);
+        let filter = filter.clone();
+        let walker = ignore::WalkBuilder::new(&dir)
+            .hidden(false)
+            .parents(false)
+            .require_git(false)
+            .filter_entry(move |entry| {
+                let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+                entry.depth() == 0
+                    || (is_synced_name(&entry.file_name().to_string_lossy(), is_dir)
+                        && !rules.is_ignored(entry.path(), is_dir)
+                        && !filter.ignores(entry.path(), is_dir))
+            })
+            .build();
+        for entry in walker {
+            let entry = entry.context("Failed to walk session")?;
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            let relative = entry.path().strip_prefix(workspace).unwrap_or(entry.path());
+            let parts: Vec<String> = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect();
+            let path = parts.join("/");
+            if local_path(workspace, &path).is_some() {
+                files.insert(path, entry.path().to_path_buf());
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Changes whenever a synced file is added, removed or modified. Only stats files, so
+/// it's cheap enough to poll.
+pub fn fingerprint(workspace: &Path, filter: &Filter) -> Result<u64> {
+    let mut hasher = DefaultHasher::new();
+    for (path, file) in scan(workspace, filter)? {
+        path.hash(&mut hasher);
+        FileStat::of(&file).hash(&mut hasher);
+    }
+    Ok(hasher.finish())
+}
+
+fn is_hidden(name: &str) -> bool {
+    name.starts_with('.')
+}
+
+/// Whether an entry inside a session is synced: visible ones, plus the session's
+/// metadata and ignore rules
+fn is_synced_name(name: &str, is_dir: bool) -> bool {
+    !is_hidden(name) || (!is_dir && (name == META_FILE || name == IGNORE_FILE))
+}
+
+/// Where a sync path lives in the workspace; None for paths sync never writes
+fn local_path(workspace: &Path, path: &str) -> Option<PathBuf> {
+    let parts: Vec<&str> = path.split('/').collect();
+    let normal = |part: &&str| {
+        let mut components = Path::new(part).components();
+        matches!(components.next(), Some(Component::Normal(_))) && components.next().is_none()
+    };
+    if !parts.iter().all(normal) {
+        return None;
+    }
+    let valid = match parts.as_slice() {
+        [file] => *file == DASHBOARD_FILE,
+        [session, rest @ ..] => {
+            let last = rest.len() - 1;
+            !is_hidden(session)
+                && rest
+                    .iter()
+                    .enumerate()
+                    .all(|(i, part)| is_synced_name(part, i != last))
+        }
+        [] => false,
+    };
+    valid.then(|| workspace.join(path))
+}
+
+/// Where a session named in a sync op lives; None for names sync never writes
+fn session_path(workspace: &Path, session: &str) -> Option<PathBuf> {
+    if session.contains('/') || session == DASHBOARD_FILE {
+        return None;
+    }
+    local_path(workspace, &format!("{session}/x")).map(|_| workspace.join(session))
+}
+
+/// `notes.md` → `notes.remote.md`
+fn conflict_path(file: &Path) -> PathBuf {
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match file.extension() {
+        Some(ext) => format!("{stem}.remote.{}", ext.to_string_lossy()),
+        None => format!("{stem}.remote"),
+    };
+    file.with_file_name(name)
+}
+
+fn write_file(file: &Path, content: &[u8]) -> Result<()> {
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(file, content).with_context(|| format!("Failed to write {}", file.display()))
+}
+
+/// Remove the directories a deletion left empty, up to the workspace
+fn prune_empty_dirs(workspace: &Path, file: &Path) {
+    let mut dir = file.parent();
+    while let Some(d) = dir {
+        if d == workspace || fs::remove_dir(d).is_err() {
+            break;
+        }
+        dir = d.parent();
+    }
+}
+
+fn hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn remote_op(db_id: i64, op_type: &str, path: &str, content: Option<&str>) -> Op {
+        let change = FileChange {
+            content: content.map(str::to_string),
+            ..FileChange::new(path)
+        };
+        Op {
+            db_id: Some(db_id),
+            ..new_op(op_type, random_id(), &change).unwrap()
+        }
+    }
+
+    /// Push every local change, as if the server accepted them all
+    fn push(workspace: &Path, state: &mut SyncState) -> Vec<Pending> {
+        let store = ChunkStore::new(&workspace.join(SYNC_DIR));
+        let bases = Bases::new(&workspace.join(SYNC_DIR));
+        let mut report = Report::default();
+        let replay = journal::read(workspace).unwrap();
+        let filter = Filter::default();
+        let pending =
+            local_changes(workspace, &store, state, &replay, &filter, &mut report).unwrap();
+        for p in &pending {
+            state.record(&store, &bases, p).unwrap();
+        }
+        pending
+    }
+
+    fn push_all(workspace: &Path, state: &mut SyncState) -> Vec<String> {
+        push(workspace, state)
+            .iter()
+            .map(|p| format!("{} {}", p.op().op_type, p.label()))
+            .collect()
+    }
+
+    fn apply_all(workspace: &Path, state: &mut SyncState, ops: &[Op]) -> Report {
+        let mut report = Report::default();
+        apply(
+            workspace,
+            &ChunkStore::new(&workspace.join(SYNC_DIR)),
+            &Bases::new(&workspace.join(SYNC_DIR)),
+            state,
+            ops,
+            &mut report,
+        )
+        .unwrap();
+        report
+    }
+
+    #[test]
+    fn sync_rules_select_contexts() {
+        let rules = |include: &[&str], exclude: &[&str]| SyncConfig {
+            include: include.iter().map(|s| s.to_string()).collect(),
+            exclude: exclude.iter().map(|s| s.to_string()).collect(),
+            ..SyncConfig::default()
+        };
+        let client = Context::Project(PathBuf::from("/work/clients/acme/.scratchpad"));
+        let own = Context::Project(PathBuf::from("/work/tools/sp/.scratchpad"));
+        let allowed =
+            |rules: &SyncConfig, context: &Context| allowed(Some(rules), context).unwrap();
+
+        assert!(allowed(&rules(&[], &[]), &client));
+        assert!(allowed(&rules(&["user"], &[]), &Context::User));
+        assert!(!allowed(&rules(&["user"], &[]), &own));
+        let no_clients = rules(&[], &["project:/work/clients/*"]);
+        assert!(!allowed(&no_clients, &client));
+        assert!(allowed(&no_clients, &own));
+        assert!(allowed(&no_clients, &Context::User));
+        assert!(!allowed(
+            &rules(&["project:*"], &["project:*/acme"]),
+            &client
+        ));
+    }
+
+    #[test]
+    fn pushes_changed_files_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path();
+        let session = workspace.join("quantum-reactor");
+        fs::create_dir_all(session.join(".snapshots")).unwrap();
+        fs::create_dir_all(workspace.join(SYNC_DIR)).unwrap();
+        fs::write(session.join("notes.md"), "# Notes\n").unwrap();
+        fs::write(session.join(META_FILE), "tags = []\n").unwrap();
+        fs::write(session.join(".snapshots/a.tar.gz"), "x").unwrap();
+        fs::write(session.join("plot.png"), [0xff, 0xfe, 0x00]).unwrap();
+        fs::write(workspace.join(SYNC_DIR).join("journal"), "x").unwrap();
+
+        let mut state = SyncState::default();
+        let mut report = Report::default();
+        let store = ChunkStore::new(&workspace.join(SYNC_DIR));
+        local_changes(
+            workspace,
+            &store,
+            &mut state,
+            &Replay::default(),
+            &Filter::default(),
+            &mut report,
+        )
+        .unwrap();
+        let blob = format!("blob.put blob {}", hash(&[0xff, 0xfe, 0x00]));
+        assert_eq!(
+            push_all(workspace, &mut state),
+            [
+                "file.put quantum-reactor/.session.toml",
+                "file.put quantum-reactor/notes.md",
+                &blob,
+                "file.put quantum-reactor/plot.png"
+            ]
+        );
+        assert!(push_all(workspace, &mut state).is_empty());
+
+        fs::remove_file(session.join("notes.md")).unwrap();
+        assert_eq!(
+            push_all(workspace, &mut state),
+            ["file.delete quantum-reactor/notes.md"]
+        );
+    }
+
+    #[test]
+    fn keeps_ignored_and_large_files_local() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path();
+        let session = workspace.join("quantum-reactor");
+        fs::create_dir_all(session.join("target")).unwrap();
+        fs::write(session.join("notes.md"), "# Notes\n").unwrap();
+        fs::write(session.join("build.log"), "ok\n").unwrap();
+        let mut state = SyncState::default();
+        assert_eq!(push_all(workspace, &mut state).len(), 2);
+
+        let rules = SyncConfig {
+            ignore: vec!["*.log".to_string(), "target/".to_string()],
+            max_file_size: Some("1k".to_string()),
+            ..SyncConfig::default()
+        };
+        let filter = Filter::new(workspace, Some(&rules)).unwrap();
+        fs::remove_file(session.join("build.log")).unwrap();
+        fs::write(session.join("target/out.txt"), "built").unwrap();
+        fs::write(session.join("trace.txt"), "x".repeat(2048)).unwrap();
+        let mut report = Report::default();
+        let store = ChunkStore::new(&workspace.join(SYNC_DIR));
+        let pending = local_changes(
+            workspace,
+            &store,
+            &mut state,
+            &Replay::default(),
+            &filter,
+            &mut report,
+        )
+        .unwrap();
+        // Ignoring a synced file doesn't delete it elsewhere
+        assert!(pending.is_empty());
+        assert_eq!(report.too_large, ["quantum-reactor/trace.txt"]);
+    }
+
+    #[test]
+    fn applies_remote_changes_against_the_last_synced_base() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path();
+        let session = workspace.join("quantum-reactor");
+        fs::create_dir_all(&session).unwrap();
+        fs::write(session.join("notes.md"), "base").unwrap();
+        fs::write(session.join("plan.txt"), "base").unwrap();
+        let mut state = SyncState::default();
+        push_all(workspace, &mut state);
+        fs::write(session.join("plan.txt"), "local edit").unwrap();
+
+        let ops = [
+            remote_op(1, PUT, "quantum-reactor/notes.md", Some("remote")),
+            remote_op(2, PUT, "quantum-reactor/plan.txt", Some("remote plan")),
+            remote_op(3, PUT, "new-session/notes.md", Some("hi")),
+            remote_op(4, PUT, "../escape.md", Some("x")),
+            remote_op(5, PUT, ".sync/state.json", Some("x")),
+            remote_op(6, PUT, "quantum-reactor/.snapshots/x", Some("x")),
+        ];
+        let report = apply_all(workspace, &mut state, &ops);
+        assert_eq!(state.cursor, Some(6));
+        assert_eq!(report.pulled, 2);
+        assert_eq!(report.conflicts, ["quantum-reactor/plan.txt"]);
+        assert_eq!(
+            fs::read_to_string(session.join("notes.md")).unwrap(),
+            "remote"
+        );
+        assert_eq!(
+            fs::read_to_string(session.join("plan.txt")).unwrap(),
+            "local edit"
+        );
+        assert_eq!(
+            fs::read_to_string(session.join("plan.remote.txt")).unwrap(),
+            "remote plan"
+        );
+        assert!(workspace.join("new-session/notes.md").is_file());
+        assert!(!dir.path().parent().unwrap().join("escape.md").exists());
+        assert!(!session.join(".snapshots").exists());
+
+        // The local edit wins and is pushed; the remote copy is a new file
+        assert_eq!(
+            push_all(workspace, &mut state),
+            [
+                "file.put quantum-reactor/plan.remote.txt",
+                "file.put quantum-reactor/plan.txt"
+            ]
+        );
+
+        let ops = [remote_op(7, DELETE, "new-session/notes.md", None)];
+        apply_all(workspace, &mut state, &ops);
+        assert!(!workspace.join("new-session").exists());
+    }
+
+    #[test]
+    fn large_files_sync_only_changed_chunks() {
+        let (a, b) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let (mut state_a, mut state_b) = (SyncState::default(), SyncState::default());
+        let log: String = (0..20_000)
+            .map(|i| format!("step {i}: loss 0.{:04}\n", i * 7919 % 10_000))
+            .collect();
+        fs::create_dir_all(a.path().join("training")).unwrap();
+        fs::write(a.path().join("training/run.log"), &log).unwrap();
+
+        let first = push(a.path(), &mut state_a);
+        let chunks = first.iter().filter(|p| !p.is_file()).count();
+        assert!(chunks > 1);
+        let ops: Vec<Op> = first.iter().map(|p| p.op().clone()).collect();
+        apply_all(b.path(), &mut state_b, &ops);
+        assert_eq!(
+            fs::read_to_string(b.path().join("training/run.log")).unwrap(),
+            log
+        );
+
+        let edited = log.replacen("step 10000:", "step 10000 (resumed):", 1);
+        fs::write(a.path().join("training/run.log"), &edited).unwrap();
+        let second = push(a.path(), &mut state_a);
+        let resent = second.iter().filter(|p| !p.is_file()).count();
+        assert!(
+            (1..=2).contains(&resent),
+            "{resent} of {chunks} chunks resent"
+        );
+
+        let ops: Vec<Op> = second.iter().map(|p| p.op().clone()).collect();
+        let report = apply_all(b.path(), &mut state_b, &ops);
+        assert_eq!(report.pulled, 1);
+        assert_eq!(
+            fs::read_to_string(b.path().join("training/run.log")).unwrap(),
+            edited
+        );
+    }
+
+    #[test]
+    fn journaled_renames_move_the_session_elsewhere() {
+        let (a, b) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let (mut state_a, mut state_b) = (SyncState::default(), SyncState::default());
+        fs::create_dir_all(a.path().join("draft")).unwrap();
+        fs::write(a.path().join("draft/notes.md"), "# Draft\n").unwrap();
+        let ops: Vec<Op> = push(a.path(), &mut state_a)
+            .iter()
+            .map(|p| p.op().clone())
+            .collect();
+        apply_all(b.path(), &mut state_b, &ops);
+
+        fs::rename(a.path().join("draft"), a.path().join("final")).unwrap();
+        let rename = Event::Rename {
+            from: "draft".to_string(),
+            to: "final".to_string(),
+        };
+        journal::record(a.path(), rename).unwrap();
+        let pending = push(a.path(), &mut state_a);
+        let labels: Vec<String> = pending.iter().map(Pending::label).collect();
+        assert_eq!(labels, ["draft → final"]);
+
+        let ops: Vec<Op> = pending.iter().map(|p| p.op().clone()).collect();
+        let report = apply_all(b.path(), &mut state_b, &ops);
+        assert_eq!(report.pulled, 1);
+        assert!(!b.path().join("draft").exists());
+        assert_eq!(
+            fs::read_to_string(b.path().join("final/notes.md")).unwrap(),
+            "# Draft\n"
+        );
+        assert!(push_all(b.path(), &mut state_b).is_empty());
+    }
+
+    #[test]
+    fn workspaces_sync_through_a_shared_log() {
+        let (a, b, shared) = (
+            tempfile::tempdir().unwrap(),
+            tempfile::tempdir().unwrap(),
+            tempfile::tempdir().unwrap(),
+        );
+        let sync = |workspace: &Path| {
+            let mut log = log::OpLog::open(&shared.path().join("ops.jsonl")).unwrap();
+            sync_with(
+                workspace,
+                &workspace.join(SYNC_DIR),
+                &mut log,
+                "test",
+                None,
+                &Filter::default(),
+            )
+            .unwrap()
+        };
+        fs::create_dir_all(a.path().join("plans")).unwrap();
+        fs::write(a.path().join("plans/notes.md"), "v1").unwrap();
+        assert_eq!(sync(a.path()).pushed, 1);
+        assert_eq!(sync(b.path()).pulled, 1);
+
+        fs::write(b.path().join("plans/notes.md"), "v2").unwrap();
+        assert_eq!(sync(b.path()).pushed, 1);
+        let report = sync(a.path());
+        assert_eq!((report.pulled, report.pushed), (1, 0));
+        assert_eq!(
+            fs::read_to_string(a.path().join("plans/notes.md")).unwrap(),
+            "v2"
+        );
+    }
+
+    #[test]
+    fn binary_files_sync_as_blobs() {
+        let (a, b, shared) = (
+            tempfile::tempdir().unwrap(),
+            tempfile::tempdir().unwrap(),
+            tempfile::tempdir().unwrap(),
+        );
+        let log_path = shared.path().join("ops.jsonl");
+        let sync = |workspace: &Path| {
+            let mut log = log::OpLog::open(&log_path).unwrap();
+            sync_with(
+                workspace,
+                &workspace.join(SYNC_DIR),
+                &mut log,
+                "test",
+                None,
+                &Filter::default(),
+            )
+            .unwrap()
+        };
+        let png: Vec<u8> = (0..=255).cycle().take(4096).collect();
+        fs::create_dir_all(a.path().join("plans")).unwrap();
+        fs::write(a.path().join("plans/plot.png"), &png).unwrap();
+        fs::write(a.path().join("plans/copy.png"), &png).unwrap();
+        assert_eq!(sync(a.path()).pushed, 2);
+        let name = log::OpLog::open(&log_path).unwrap().name();
+        let ops = log::OpLog::open(&log_path).unwrap().pull(None, 10).unwrap();
+        // The two files share one blob
+        assert_eq!(ops.iter().filter(|op| op.op_type == BLOB).count(), 1);
+
+        assert_eq!(sync(b.path()).pulled, 2);
+        for file in ["plans/plot.png", "plans/copy.png"] {
+            assert_eq!(fs::read(b.path().join(file)).unwrap(), png);
+        }
+
+        // A file referencing a blob that never arrived isn't written
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = SyncState::load(&dir.path().join(SYNC_DIR), &name).unwrap();
+        let files: Vec<Op> = ops.into_iter().filter(|op| op.op_type == PUT).collect();
+        let report = apply_all(dir.path(), &mut state, &files);
+        assert_eq!(report.incomplete.len(), 2);
+        assert!(!dir.path().join("plans/plot.png").exists());
+    }
+
+    /// An op log that keeps blobs apart from it, like the server
+    struct BlobRemote {
+        log: log::OpLog,
+        blobs: HashMap<String, Vec<u8>>,
+    }
+
+    impl Remote for BlobRemote {
+        fn name(&self) -> String {
+            self.log.name()
+        }
+
+        fn pull(&mut self, after: Option<i64>, limit: usize) -> Result<Vec<Op>> {
+            self.log.pull(after, limit)
+        }
+
+        fn push(&mut self, ops: Vec<Op>) -> Result<PushOpsResponse> {
+            self.log.push(ops)
+        }
+
+        fn put_blob(&mut self, hash: &str, data: &[u8]) -> Result<bool> {
+            self.blobs.insert(hash.to_string(), data.to_vec());
+            Ok(true)
+        }
+
+        fn get_blob(&mut self, hash: &str) -> Result<Option<Vec<u8>>> {
+            Ok(self.blobs.get(hash).cloned())
+        }
+    }
+
+    #[test]
+    fn blobs_go_apart_from_ops_when_the_remote_stores_them() {
+        let (a, b, shared) = (
+            tempfile::tempdir().unwrap(),
+            tempfile::tempdir().unwrap(),
+            tempfile::tempdir().unwrap(),
+        );
+        let mut remote = BlobRemote {
+            log: log::OpLog::open(&shared.path().join("ops.jsonl")).unwrap(),
+            blobs: HashMap::new(),
+        };
+        let mut sync = |workspace: &Path| {
+            sync_with(
+                workspace,
+                &workspace.join(SYNC_DIR),
+                &mut remote,
+                "test",
+                None,
+                &Filter::default(),
+            )
+            .unwrap()
+        };
+        let png: Vec<u8> = (0..=255).cycle().take(4096).collect();
+        fs::create_dir_all(a.path().join("plans")).unwrap();
+        fs::write(a.path().join("plans/plot.png"), &png).unwrap();
+        assert_eq!(sync(a.path()).pushed, 1);
+        assert_eq!(sync(b.path()).pulled, 1);
+        assert_eq!(fs::read(b.path().join("plans/plot.png")).unwrap(), png);
+
+        assert_eq!(remote.blobs.len(), 1);
+        let ops = remote.log.pull(None, 10).unwrap();
+        assert!(ops.iter().all(|op| op.op_type != BLOB));
+    }
+
+    #[test]
+    fn notes_edited_on_both_sides_are_merged() {
+        let (a, b, shared) = (
+            tempfile::tempdir().unwrap(),
+            tempfile::tempdir().unwrap(),
+            tempfile::tempdir().unwrap(),
+        );
+        let sync = |workspace: &Path| {
+            let mut log = log::OpLog::open(&shared.path().join("ops.jsonl")).unwrap();
+            sync_with(
+                workspace,
+                &workspace.join(SYNC_DIR),
+                &mut log,
+                "test",
+                None,
+                &Filter::default(),
+            )
+            .unwrap()
+        };
+        let notes = |workspace: &Path| workspace.join("plans/notes.md");
+        fs::create_dir_all(a.path().join("plans")).unwrap();
+        fs::write(notes(a.path()), "# Plans\n\none\ntwo\n").unwrap();
+        sync(a.path());
+        sync(b.path());
+
+        fs::write(notes(a.path()), "# Plans\n\none, from a\ntwo\n").unwrap();
+        fs::write(notes(b.path()), "# Plans\n\none\ntwo\nthree, from b\n").unwrap();
+        sync(a.path());
+        let report = sync(b.path());
+        assert_eq!(report.merged, ["plans/notes.md"]);
+        assert_eq!(sync(a.path()).pulled, 1);
+        let merged = "# Plans\n\none, from a\ntwo\nthree, from b\n";
+        assert_eq!(fs::read_to_string(notes(a.path())).unwrap(), merged);
+        assert_eq!(fs::read_to_string(notes(b.path())).unwrap(), merged);
+
+        fs::write(notes(a.path()), "# Plans\n\nONE\ntwo\nthree, from b\n").unwrap();
+        fs::write(notes(b.path()), "# Plans\n\nuno\ntwo\nthree, from b\n").unwrap();
+        sync(a.path());
+        let report = sync(b.path());
+        assert_eq!(report.conflicts, ["plans/notes.md"]);
+        let marked = fs::read_to_string(b.path().join("plans/notes.conflict.md")).unwrap();
+        assert!(marked.contains("<<<<<<< local\nuno\n=======\nONE\n>>>>>>> remote\n"));
+        assert!(fs::read_to_string(notes(b.path())).unwrap().contains("uno"));
+    }
+}
@@ -0,0 +1,184 @@
+//! Peer-to-peer sync over SSH (`sp sync --peer`)
+//!
+//! `sp sync --peer host[:path]` runs `ssh host sp sync --serve` and exchanges ops with it
+//! over the connection's stdin/stdout, one JSON request and response per line. Without a
+//! path the peer serves its user context; with one, the project at that path.
+//!
+//! The serving side keeps an op log of its own (`.sync/served/ops.jsonl`, see `log.rs`)
+//! and syncs its workspace against that log before answering and again once the
+//! connection closes, so peers carry the same ops the server would, and a machine that
+//! peers with two others passes changes between them.
+
+use std::io::{self, BufRead as _, BufReader, Write as _};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use anyhow::{Context as _, Result, anyhow};
+use scratchpad_protocol::{Op, PushOpsResponse};
+use serde::{Deserialize, Serialize};
+
+use super::device::Device;
+use super::log::OpLog;
+use super::{Remote, Report, SYNC_DIR, filter, sync_with};
+use crate::names::slugify;
+use crate::storage::Storage;
+
+/// Where a served workspace keeps its op log and the state of its own sync with it
+const SERVED_DIR: &str = "served";
+const LOG_FILE: &str = "ops.jsonl";
+/// Where the state of syncing with each peer lives, by peer
+const PEERS_DIR: &str = "peers";
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Request {
+    Pull { after: Option<i64>, limit: usize },
+    Push { ops: Vec<Op> },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Response {
+    Ops(Vec<Op>),
+    Pushed(PushOpsResponse),
+    Error(String),
+}
+
+/// Sync the workspace with the peer `spec` (`host` or `host:path`)
+pub fn run(storage: &Storage, spec: &str, device: &Device) -> Result<Report> {
+    storage.ensure_workspace()?;
+    let workspace = storage.workspace_path();
+    let dir = workspace
+        .join(SYNC_DIR)
+        .join(PEERS_DIR)
+        .join(slugify(spec).unwrap_or_else(|| "peer".to_string()));
+    let filter = filter(storage)?;
+    let mut peer = Peer::connect(spec)?;
+    let report = sync_with(&workspace, &dir, &mut peer, &device.id, None, &filter)?;
+    peer.finish()?;
+    Ok(report)
+}
+
+/// Serve the workspace to a peer on stdin/stdout (`sp sync --serve`)
+pub fn serve(storage: &Storage, device: &Device) -> Result<()> {
+    storage.ensure_workspace()?;
+    let workspace = storage.workspace_path();
+    let dir = workspace.join(SYNC_DIR).join(SERVED_DIR);
+    let filter = filter(storage)?;
+    let mut log = OpLog::open(&dir.join(LOG_FILE))?;
+    sync_with(&workspace, &dir, &mut log, &device.id, None, &filter)?;
+
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let response = match serde_json::from_str(&line?) {
+            Ok(Request::Pull { after, limit }) => log.pull(after, limit).map(Response::Ops),
+            Ok(Request::Push { ops }) => log.push(ops).map(Response::Pushed),
+            Err(e) => Err(anyhow!("Invalid request: {e}")),
+        }
+        .unwrap_or_else(|e| Response::Error(format!("{e:#}")));
+        writeln!(stdout, "{}", serde_json::to_string(&response)?)?;
+        stdout.flush()?;
+    }
+
+    // Apply what the peer pushed
+    sync_with(&workspace, &dir, &mut log, &device.id, None, &filter)?;
+    Ok(())
+}
+
+struct Peer {
+    spec: String,
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Peer {
+    fn connect(spec: &str) -> Result<Self> {
+        let (host, path) = match spec.split_once(':') {
+            Some((host, path)) => (host, Some(path)),
+            None => (spec, None),
+        };
+        let remote_command = match path {
+            Some(path) => format!("cd {} && sp sync --serve", shell_quote(path)),
+            None => "sp --user sync --serve".to_string(),
+        };
+        // stderr stays attached so ssh can prompt and report errors
+        let mut child = Command::new("ssh")
+            .arg(host)
+            .arg(remote_command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .context("Failed to run ssh")?;
+        let stdin = child.stdin.take();
+        let stdout = BufReader::new(child.stdout.take().context("No stdout from ssh")?);
+        Ok(Self {
+            spec: spec.to_string(),
+            child,
+            stdin,
+            stdout,
+        })
+    }
+
+    fn call(&mut self, request: &Request) -> Result<Response> {
+        let stdin = self.stdin.as_mut().context("Peer connection closed")?;
+        writeln!(stdin, "{}", serde_json::to_string(request)?)
+            .and_then(|()| stdin.flush())
+            .map_err(|e| anyhow!("Lost connection to {}: {e}", self.spec))?;
+        let mut line = String::new();
+        if self.stdout.read_line(&mut line)? == 0 {
+            return Err(anyhow!(
+                "{} closed the connection (is sp installed there?)",
+                self.spec
+            ));
+        }
+        match serde_json::from_str(&line)? {
+            Response::Error(e) => Err(anyhow!("{}: {e}", self.spec)),
+            response => Ok(response),
+        }
+    }
+
+    /// Close the connection and wait for the peer to apply what it received
+    fn finish(mut self) -> Result<()> {
+        drop(self.stdin.take());
+        let status = self.child.wait().context("Failed to wait for ssh")?;
+        if !status.success() {
+            return Err(anyhow!(
+                "{} failed to apply the changes ({status})",
+                self.spec
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Remote for Peer {
+    fn name(&self) -> String {
+        format!("peer:{}", self.spec)
+    }
+
+    fn pull(&mut self, after: Option<i64>, limit: usize) -> Result<Vec<Op>> {
+        match self.call(&Request::Pull { after, limit })? {
+            Response::Ops(ops) => Ok(ops),
+            _ => Err(anyhow!("Unexpected reply from {}", self.spec)),
+        }
+    }
+
+    fn push(&mut self, ops: Vec<Op>) -> Result<PushOpsResponse> {
+        match self.call(&Request::Push { ops })? {
+            Response::Pushed(response) => Ok(response),
+            _ => Err(anyhow!("Unexpected reply from {}", self.spec)),
+        }
+    }
+}
+
+impl Drop for Peer {
+    fn drop(&mut self) {
+        drop(self.stdin.take());
+        let _ = self.child.wait();
+    }
+}
+
+/// `it's` → `'it'\''s'`
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
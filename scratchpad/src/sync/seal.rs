@@ -0,0 +1,129 @@
+//! End-to-end encryption of synced ops (`[server] encrypt = true`)
+//!
+//! Payloads are age-encrypted to a key kept next to the config file (`sync.key`), so the
+//! server stores and indexes only ciphertext. Every machine syncing the workspace needs
+//! the same key: one creates it with `sp sync --new-key`, the others get a copy. Chunk op
+//! ids are keyed hashes rather than plain content hashes, so the server can't test
+//! whether a workspace holds a known file either. Op types, ids and timestamps stay
+//! readable; the server needs them to store and order ops.
+
+use std::path::{Path, PathBuf};
+
+use age::secrecy::ExposeSecret as _;
+use age::x25519::{Identity, Recipient};
+use anyhow::{Result, anyhow};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::{Deserialize, Serialize};
+
+use super::hash;
+use crate::crypto::{read_identity, write_private};
+
+const KEY_FILE: &str = "sync.key";
+
+/// Where the sync key lives: next to the config file
+pub fn key_path() -> PathBuf {
+    crate::config::config_path().with_file_name(KEY_FILE)
+}
+
+/// Payload of an encrypted op
+#[derive(Serialize, Deserialize)]
+struct Sealed {
+    /// Base64 of the age ciphertext of the plain payload
+    sealed: String,
+}
+
+pub struct Sealer {
+    identity: Identity,
+    recipient: Recipient,
+}
+
+impl Sealer {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Err(anyhow!(
+                "No sync key at {}: copy it from a machine that already syncs this \
+                 workspace, or create one with `sp sync --new-key`",
+                path.display()
+            ));
+        }
+        Ok(Self::new(read_identity(path)?))
+    }
+
+    /// Generate a key at `path`, refusing to replace one
+    pub fn create(path: &Path) -> Result<Self> {
+        if path.exists() {
+            return Err(anyhow!("{} already exists", path.display()));
+        }
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let identity = Identity::generate();
+        let content = format!(
+            "# scratchpad sync key: copy to every machine syncing with it\n\
+             # public key: {}\n{}\n",
+            identity.to_public(),
+            identity.to_string().expose_secret()
+        );
+        write_private(path, content.as_bytes())?;
+        Ok(Self::new(identity))
+    }
+
+    fn new(identity: Identity) -> Self {
+        let recipient = identity.to_public();
+        Self {
+            identity,
+            recipient,
+        }
+    }
+
+    pub fn seal(&self, payload: &str) -> Result<String> {
+        let ciphertext = age::encrypt(&self.recipient, payload.as_bytes())
+            .map_err(|e| anyhow!("Encryption failed: {e}"))?;
+        Ok(serde_json::to_string(&Sealed {
+            sealed: BASE64.encode(ciphertext),
+        })?)
+    }
+
+    /// The plain payload of a sealed one; None if it isn't sealed for this key
+    pub fn open(&self, payload: &str) -> Option<String> {
+        let sealed: Sealed = serde_json::from_str(payload).ok()?;
+        let ciphertext = BASE64.decode(sealed.sealed).ok()?;
+        let plaintext = age::decrypt(&self.identity, &ciphertext).ok()?;
+        String::from_utf8(plaintext).ok()
+    }
+
+    /// Stand-in for a chunk's content hash that only holders of the key can compute
+    pub fn chunk_id(&self, chunk_hash: &str) -> String {
+        let key = self.identity.to_string();
+        hash(format!("{}:{chunk_hash}", key.expose_secret()).as_bytes())
+    }
+}
+
+/// Whether a payload is encrypted, for any key
+pub fn is_sealed(payload: &str) -> bool {
+    serde_json::from_str::<Sealed>(payload).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seals_for_the_same_key_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let sealer = Sealer::create(&dir.path().join(KEY_FILE)).unwrap();
+        assert!(Sealer::create(&dir.path().join(KEY_FILE)).is_err());
+        let copy = Sealer::load(&dir.path().join(KEY_FILE)).unwrap();
+
+        let payload = r#"{"path":"plans/notes.md","content":"secret"}"#;
+        let sealed = sealer.seal(payload).unwrap();
+        assert!(is_sealed(&sealed) && !sealed.contains("secret"));
+        assert_eq!(copy.open(&sealed).as_deref(), Some(payload));
+        assert_eq!(sealer.chunk_id("abc"), copy.chunk_id("abc"));
+
+        let other = Sealer::create(&dir.path().join("other.key")).unwrap();
+        assert_eq!(other.open(&sealed), None);
+        assert_ne!(other.chunk_id("abc"), sealer.chunk_id("abc"));
+    }
+}
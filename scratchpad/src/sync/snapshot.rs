@@ -0,0 +1,261 @@
+//! Workspace snapshots on the sync server, so a new machine needn't replay every op
+//!
+//! `sp sync --snapshot` stores, after syncing, the workspace as this machine last synced
+//! it: a `file.put` op for each synced file, plus the `chunk.put` ops of large ones and
+//! the `blob.put` ops of binary ones, sealed like any push when encrypting. Its
+//! `last_op_id` is the last op pulled, so the snapshot stands for the log up to there
+//! (and `sp-server compact` may drop those ops). `sp sync --init` applies the ops to a
+//! workspace that never synced with the server, then pulls from the op after
+//! `last_op_id`. A file changed here since it was synced stops the snapshot, as the
+//! version the log has is gone; ops pushed after the last pull are in it as well, and
+//! change nothing when pulled again.
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context as _, Result, bail};
+use scratchpad_protocol::{Op, Snapshot};
+
+use super::chunk::{self, ChunkStore};
+use super::client::Client;
+use super::device::Device;
+use super::merge::Bases;
+use super::seal::Sealer;
+use super::{
+    BlobRef, FileChange, PUT, Pending, Remote as _, Report, SYNC_DIR, SyncState, apply, hash,
+    local_path, new_op, open, random_id, sealer, workspace_id,
+};
+use crate::models::ServerConfig;
+use crate::storage::Storage;
+
+/// A snapshot `init` started from
+pub struct Restored {
+    /// When the snapshot was stored, RFC 3339
+    pub taken_at: String,
+    pub report: Report,
+}
+
+/// Before the first sync with the server, write the workspace as the server's snapshot
+/// has it. None when there's no snapshot, and the sync starts from the first op.
+pub fn init(
+    storage: &Storage,
+    server: &ServerConfig,
+    device: &Device,
+    rate: Option<u64>,
+) -> Result<Option<Restored>> {
+    storage.ensure_workspace()?;
+    let workspace = storage.workspace_path();
+    let dir = workspace.join(SYNC_DIR);
+    let client = Client::new(
+        server,
+        &workspace_id(server, storage.context()),
+        device,
+        rate,
+    );
+    let state = SyncState::load(&dir, &client.name())?;
+    if state.cursor.is_some() || !state.files.is_empty() {
+        bail!("This workspace has synced with the server before; --init is for the first sync");
+    }
+    let Some(snapshot) = client.snapshot()? else {
+        return Ok(None);
+    };
+    let report = restore(&workspace, &dir, state, &snapshot, sealer(server)?.as_ref())?;
+    Ok(Some(Restored {
+        taken_at: snapshot.updated_at,
+        report,
+    }))
+}
+
+/// Replace the server's snapshot with the workspace as of the sync that returned
+/// `report`. Returns how many files it holds.
+pub fn store(
+    storage: &Storage,
+    server: &ServerConfig,
+    device: &Device,
+    rate: Option<u64>,
+    report: &Report,
+) -> Result<usize> {
+    if report.unreadable > 0 || !report.incomplete.is_empty() {
+        bail!(
+            "Not storing a snapshot: some changes from the server couldn't be applied here, \
+             so it would leave them out"
+        );
+    }
+    let workspace = storage.workspace_path();
+    let workspace_id = workspace_id(server, storage.context());
+    let client = Client::new(server, &workspace_id, device, rate);
+    let state = SyncState::load(&workspace.join(SYNC_DIR), &client.name())?;
+    let (ops, files) = capture(&workspace, &state, &device.id, sealer(server)?.as_ref())?;
+    client.save_snapshot(&Snapshot {
+        workspace_id,
+        data: serde_json::to_string(&ops)?,
+        last_op_id: state.last_op.clone(),
+        updated_at: chrono::Utc::now().to_rfc3339(),
+        cursor: None,
+    })?;
+    Ok(files)
+}
+
+/// Ops recreating every file as it was last synced, and how many files that is
+fn capture(
+    workspace: &Path,
+    state: &SyncState,
+    device: &str,
+    sealer: Option<&Sealer>,
+) -> Result<(Vec<Op>, usize)> {
+    let mut pending = Vec::new();
+    let mut chunks = HashSet::new();
+    for (path, synced) in &state.files {
+        let Some(file) = local_path(workspace, path) else {
+            continue;
+        };
+        let bytes = fs::read(&file).ok().filter(|bytes| hash(bytes) == *synced);
+        let Some(bytes) = bytes else {
+            bail!("{path} changed since it was synced; sync again before storing a snapshot");
+        };
+        let change = if std::str::from_utf8(&bytes).is_err() {
+            if chunks.insert(synced.clone()) {
+                pending.push(Pending::blob(synced, &bytes)?);
+            }
+            FileChange {
+                blob: Some(BlobRef {
+                    hash: synced.clone(),
+                    size: bytes.len() as u64,
+                }),
+                ..FileChange::new(path)
+            }
+        } else if state.chunked.contains_key(path) {
+            let mut hashes = Vec::new();
+            for data in chunk::split(&bytes) {
+                let chunk_hash = hash(data);
+                if chunks.insert(chunk_hash.clone()) {
+                    pending.push(Pending::chunk(&chunk_hash, data)?);
+                }
+                hashes.push(chunk_hash);
+            }
+            FileChange {
+                chunks: Some(hashes),
+                ..FileChange::new(path)
+            }
+        } else {
+            FileChange {
+                content: Some(String::from_utf8(bytes).unwrap_or_default()),
+                ..FileChange::new(path)
+            }
+        };
+        pending.push(Pending::File {
+            op: new_op(PUT, random_id(), &change)?,
+            path: path.clone(),
+            hash: None,
+            chunks: None,
+            stat: None,
+        });
+    }
+    let files = pending.iter().filter(|p| p.is_file()).count();
+    let ops = pending
+        .iter()
+        .map(|p| p.outgoing(device, sealer))
+        .collect::<Result<_>>()?;
+    Ok((ops, files))
+}
+
+/// Apply a snapshot's ops as if pulled, then continue from where it ends
+fn restore(
+    workspace: &Path,
+    dir: &Path,
+    mut state: SyncState,
+    snapshot: &Snapshot,
+    sealer: Option<&Sealer>,
+) -> Result<Report> {
+    let mut ops: Vec<Op> =
+        serde_json::from_str(&snapshot.data).context("Invalid snapshot on the server")?;
+    let mut report = Report::default();
+    for op in &mut ops {
+        // Not from the log: only the snapshot's own cursor counts
+        op.db_id = None;
+        open(op, sealer, &mut report);
+    }
+    let store = ChunkStore::new(dir);
+    apply(
+        workspace,
+        &store,
+        &Bases::new(dir),
+        &mut state,
+        &ops,
+        &mut report,
+    )?;
+    if snapshot.cursor.is_some() {
+        state.cursor = snapshot.cursor;
+        state.last_op = snapshot.last_op_id.clone();
+    }
+    state.save(dir)?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::{Filter, Remote, log::OpLog, sync_with};
+
+    #[test]
+    fn a_new_workspace_starts_from_the_snapshot() {
+        let (a, b, c, shared) = (
+            tempfile::tempdir().unwrap(),
+            tempfile::tempdir().unwrap(),
+            tempfile::tempdir().unwrap(),
+            tempfile::tempdir().unwrap(),
+        );
+        let log_path = shared.path().join("ops.jsonl");
+        let sync = |workspace: &Path| {
+            let mut log = OpLog::open(&log_path).unwrap();
+            sync_with(
+                workspace,
+                &workspace.join(SYNC_DIR),
+                &mut log,
+                "test",
+                None,
+                &Filter::default(),
+            )
+            .unwrap()
+        };
+        fs::create_dir_all(a.path().join("plans")).unwrap();
+        fs::write(a.path().join("plans/notes.md"), "v1").unwrap();
+        fs::write(a.path().join("plans/todo.md"), "- [ ] ship").unwrap();
+        sync(a.path());
+        fs::write(a.path().join("plans/notes.md"), "v2").unwrap();
+        sync(a.path());
+        assert_eq!(sync(b.path()).pulled, 3);
+
+        fs::write(b.path().join("plans/todo.md"), "- [x] ship").unwrap();
+        let name = OpLog::open(&log_path).unwrap().name();
+        let state = || SyncState::load(&b.path().join(SYNC_DIR), &name).unwrap();
+        assert!(capture(b.path(), &state(), "test", None).is_err());
+        assert_eq!(sync(b.path()).pushed, 1);
+        let state = state();
+        let (ops, files) = capture(b.path(), &state, "test", None).unwrap();
+        assert_eq!(files, 2);
+        let snapshot = Snapshot {
+            workspace_id: "user".to_string(),
+            data: serde_json::to_string(&ops).unwrap(),
+            last_op_id: state.last_op.clone(),
+            updated_at: String::new(),
+            cursor: state.cursor,
+        };
+
+        let dir = c.path().join(SYNC_DIR);
+        let fresh = SyncState::load(&dir, &name).unwrap();
+        let report = restore(c.path(), &dir, fresh, &snapshot, None).unwrap();
+        assert_eq!(report.pulled, 2);
+        assert_eq!(
+            fs::read_to_string(c.path().join("plans/notes.md")).unwrap(),
+            "v2"
+        );
+        // Only b's push comes after the snapshot, which already has it
+        let report = sync(c.path());
+        assert_eq!((report.pulled, report.pushed), (0, 0));
+        assert_eq!(
+            fs::read_to_string(c.path().join("plans/todo.md")).unwrap(),
+            "- [x] ship"
+        );
+    }
+}
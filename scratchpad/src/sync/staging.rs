@@ -0,0 +1,316 @@
+//! Captures set aside while their session is out of step with its remote
+//!
+//! Writing into a session with an unresolved sync conflict, or with changes from another
+//! machine that haven't been applied here yet, would bury one side once the two meet. So
+//! while that's the case, `sp write`, `sp capture`, `sp clip`, the RPC/HTTP `write`
+//! method and TUI edits put what they'd write next to the file as
+//! `<name>.staged-<time>.<ext>` instead, recorded in `.sync/staged.json` under the file's
+//! sync path. Being a plain session file it syncs like any other. `sp sync status` lists
+//! captures until their staged file is deleted, once merged in by hand.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use anyhow::{Context as _, Result};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use super::status::{self, Status};
+use super::{SYNC_DIR, conflicts};
+use crate::crypto;
+use crate::models::Config;
+use crate::storage::Storage;
+
+const STAGED_FILE: &str = "staged.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Staged {
+    /// Sync path of the file the capture was meant for
+    pub path: String,
+    /// Sync path the capture was written to
+    pub staged: String,
+}
+
+/// Why a session's files shouldn't be written to directly
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pending {
+    Conflict,
+    /// The remote has changes to it not applied here yet
+    Remote,
+}
+
+impl Pending {
+    pub fn describe(self) -> &'static str {
+        match self {
+            Pending::Conflict => "has an unresolved sync conflict",
+            Pending::Remote => "has changes from another machine not synced here yet",
+        }
+    }
+}
+
+/// What a write to `slug` would stage for, or None when it can go through
+pub fn pending(workspace: &Path, slug: &str, status: Option<&Status>) -> Option<Pending> {
+    if conflicts::list(workspace)
+        .iter()
+        .any(|c| c.session() == slug)
+    {
+        Some(Pending::Conflict)
+    } else if status.is_some_and(|s| s.unapplied.contains(slug)) {
+        Some(Pending::Remote)
+    } else {
+        None
+    }
+}
+
+/// `pending`, asking the remote when there's no conflict. A remote that can't be reached
+/// doesn't hold up the write.
+pub fn check(storage: &Storage, config: &Config, slug: &str) -> Option<Pending> {
+    let workspace = storage.workspace_path();
+    pending(&workspace, slug, None).or_else(|| {
+        let status = status::check(storage, config).ok().flatten();
+        pending(&workspace, slug, status.as_ref())
+    })
+}
+
+/// Write `content` to `file` in `slug` (its notes when None), or stage it while the
+/// session is pending
+pub fn write(
+    storage: &Storage,
+    config: &Config,
+    slug: &str,
+    file: Option<&str>,
+    content: &str,
+) -> Result<Option<(Pending, Staged)>> {
+    if let Some(pending) = check(storage, config, slug) {
+        let staged = stage(storage, slug, file.unwrap_or("notes.md"), content)?;
+        return Ok(Some((pending, staged)));
+    }
+    match file {
+        Some(file) => storage
+            .write_session_file(slug, file, content)
+            .with_context(|| format!("Failed to write {file}"))?,
+        None => storage.write_notes(slug, content)?,
+    }
+    Ok(None)
+}
+
+/// Append `text` to the notes of `slug`, or stage it while the session is pending
+pub fn append_notes(
+    storage: &Storage,
+    config: &Config,
+    slug: &str,
+    text: &str,
+) -> Result<Option<(Pending, Staged)>> {
+    if let Some(pending) = check(storage, config, slug) {
+        let staged = stage(storage, slug, &notes_name(storage, slug), text)?;
+        return Ok(Some((pending, staged)));
+    }
+    storage.append_notes(slug, text)?;
+    Ok(None)
+}
+
+/// Write `content` as a staged capture for `file` in `slug`
+pub fn stage(storage: &Storage, slug: &str, file: &str, content: &str) -> Result<Staged> {
+    let relative = reserve(storage, slug, file)?;
+    storage
+        .write_session_file(slug, &relative, content)
+        .with_context(|| format!("Failed to stage {relative}"))?;
+    record(storage, slug, file, &relative)
+}
+
+/// A free name to stage a capture for `file` in `slug` under
+pub fn reserve(storage: &Storage, slug: &str, file: &str) -> Result<String> {
+    let stamp = Local::now().format("%Y%m%d-%H%M%S").to_string();
+    let mut relative = staged_name(file, &stamp);
+    for n in 2.. {
+        if !storage.new_file_path(slug, &relative)?.exists() {
+            break;
+        }
+        relative = staged_name(file, &format!("{stamp}-{n}"));
+    }
+    Ok(relative)
+}
+
+/// Note that `relative` in `slug` holds a capture meant for `file`
+pub fn record(storage: &Storage, slug: &str, file: &str, relative: &str) -> Result<Staged> {
+    let workspace = storage.workspace_path();
+    let sync_path = |path: &Path| {
+        path.strip_prefix(&workspace)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/")
+    };
+    let staged = Staged {
+        path: sync_path(&storage.session_file(slug, file)?),
+        staged: sync_path(&storage.new_file_path(slug, relative)?),
+    };
+    let mut all = load(&workspace)?;
+    all.push(staged.clone());
+    save(&workspace, &all)?;
+    Ok(staged)
+}
+
+/// What to tell the user about a staged write
+pub fn notice(slug: &str, pending: Pending, staged: &Staged) -> String {
+    format!(
+        "'{slug}' {}; staged the capture as {} (see `sp sync status`)",
+        pending.describe(),
+        staged.staged
+    )
+}
+
+/// Staged captures not merged in yet
+pub fn list(workspace: &Path) -> Vec<Staged> {
+    load(workspace)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|s| workspace.join(&s.staged).is_file())
+        .collect()
+}
+
+/// Plain name of the file notes are appended to
+fn notes_name(storage: &Storage, slug: &str) -> String {
+    storage
+        .find_entry_point(slug)
+        .and_then(|path| {
+            let plain = if crypto::is_encrypted(&path) {
+                path.with_extension("")
+            } else {
+                path
+            };
+            plain
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "notes.md".to_string())
+}
+
+/// `notes.md` → `notes.staged-<stamp>.md`
+fn staged_name(file: &str, stamp: &str) -> String {
+    let path = Path::new(file);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}.staged-{stamp}.{}", ext.to_string_lossy()),
+        None => format!("{stem}.staged-{stamp}"),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+fn load(workspace: &Path) -> Result<Vec<Staged>> {
+    let path = workspace.join(SYNC_DIR).join(STAGED_FILE);
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content)
+            .with_context(|| format!("Invalid staged capture list {}", path.display())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+fn save(workspace: &Path, staged: &[Staged]) -> Result<()> {
+    let dir = workspace.join(SYNC_DIR);
+    fs::create_dir_all(&dir).context("Failed to create sync directory")?;
+    // Drop the ones already dealt with
+    let staged: Vec<_> = staged
+        .iter()
+        .filter(|s| workspace.join(&s.staged).is_file())
+        .collect();
+    fs::write(dir.join(STAGED_FILE), serde_json::to_string(&staged)?)
+        .context("Failed to save the staged capture list")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Context, Session};
+
+    fn storage(workspace: &Path) -> (Storage, Config) {
+        let config = Config {
+            workspace_path: workspace.to_string_lossy().to_string(),
+            ..Config::default()
+        };
+        (Storage::new(config.clone(), Context::User), config)
+    }
+
+    #[test]
+    fn writes_to_conflicted_sessions_are_staged_and_listed() {
+        let dir = tempfile::tempdir().unwrap();
+        let (storage, config) = storage(dir.path());
+        for slug in ["plans", "ideas"] {
+            storage
+                .create_session(&Session::new(slug), Some("mine"))
+                .unwrap();
+        }
+        fs::write(dir.path().join("plans/notes.remote.md"), "theirs").unwrap();
+        conflicts::record(
+            dir.path(),
+            conflicts::Conflict {
+                path: "plans/notes.md".to_string(),
+                saved: "plans/notes.remote.md".to_string(),
+                merged: false,
+            },
+        )
+        .unwrap();
+
+        // Sessions without a conflict are written as usual
+        let staged = write(&storage, &config, "ideas", None, "new idea").unwrap();
+        assert_eq!(staged, None);
+        assert_eq!(storage.read_notes("ideas").unwrap(), "new idea");
+
+        let (pending, staged) = write(&storage, &config, "plans", None, "captured")
+            .unwrap()
+            .unwrap();
+        assert_eq!(pending, Pending::Conflict);
+        assert_eq!(staged.path, "plans/notes.md");
+        assert!(
+            staged.staged.starts_with("plans/notes.staged-"),
+            "{staged:?}"
+        );
+        assert!(staged.staged.ends_with(".md"), "{staged:?}");
+        assert_eq!(storage.read_notes("plans").unwrap(), "mine");
+        assert_eq!(
+            fs::read_to_string(dir.path().join(&staged.staged)).unwrap(),
+            "captured"
+        );
+        let (_, appended) = append_notes(&storage, &config, "plans", "clipped")
+            .unwrap()
+            .unwrap();
+        assert_ne!(appended.staged, staged.staged);
+        assert_eq!(appended.path, "plans/notes.md");
+        assert_eq!(list(dir.path()), vec![staged.clone(), appended.clone()]);
+
+        // Merged in and deleted, a capture is no longer listed
+        fs::remove_file(dir.path().join(&staged.staged)).unwrap();
+        assert_eq!(list(dir.path()), vec![appended]);
+    }
+
+    #[test]
+    fn sessions_with_unapplied_remote_changes_are_pending() {
+        let dir = tempfile::tempdir().unwrap();
+        let status = Status {
+            unapplied: ["plans".to_string()].into(),
+            unpushed: ["ideas".to_string()].into(),
+            ..Status::default()
+        };
+        assert_eq!(
+            pending(dir.path(), "plans", Some(&status)),
+            Some(Pending::Remote)
+        );
+        // Local changes not pushed yet are just written over
+        assert_eq!(pending(dir.path(), "ideas", Some(&status)), None);
+        assert_eq!(pending(dir.path(), "plans", None), None);
+    }
+
+    #[test]
+    fn staged_names_keep_the_extension_and_folder() {
+        assert_eq!(
+            staged_name("notes.md", "20260101-120000"),
+            "notes.staged-20260101-120000.md"
+        );
+        assert_eq!(
+            staged_name("logs/capture.log", "1"),
+            "logs/capture.staged-1.log"
+        );
+        assert_eq!(staged_name("TODO", "1"), "TODO.staged-1");
+    }
+}
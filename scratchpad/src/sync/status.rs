@@ -0,0 +1,271 @@
+//! Where the workspace stands with its remote, without syncing (the TUI's sync markers)
+//!
+//! Local changes are found the way a sync finds them, against the state the last sync
+//! left. Remote ones are the ops after the cursor that applying would change something
+//! with, so this machine's own pushes coming back don't count.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+
+use super::device::Device;
+use super::folder::FolderLog;
+use super::{
+    DELETE, FileChange, FileStat, Filter, PULL_PAGE, PUT, RENAME, Remote, Report, SYNC_DIR,
+    SessionRename, SyncState, hash, open, scan, seal,
+};
+use crate::models::{Config, SyncBackend, SyncConfig};
+use crate::storage::Storage;
+
+/// Pages of remote ops looked at; further behind than that, what was seen is reported
+const MAX_PAGES: usize = 10;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Status {
+    /// Sessions with local changes not pushed yet
+    pub unpushed: BTreeSet<String>,
+    /// Sessions with remote changes not applied here yet
+    pub unapplied: BTreeSet<String>,
+    /// Why the remote couldn't be checked; `unapplied` is then empty
+    pub remote_error: Option<String>,
+}
+
+impl Status {
+    pub fn is_synced(&self) -> bool {
+        self.unpushed.is_empty() && self.unapplied.is_empty() && self.remote_error.is_none()
+    }
+}
+
+/// Status against the configured server, or `[sync] folder` without one, or the git
+/// remote with `backend = "git"`; None when none is set
+pub fn check(storage: &Storage, config: &Config) -> Result<Option<Status>> {
+    let workspace = storage.workspace_path();
+    if !workspace.exists() {
+        return Ok(None);
+    }
+    let context = storage.context();
+    let rules = config.sync.as_ref();
+    let backend = rules.and_then(|s| s.backend);
+    let filter = Filter::new(&workspace, rules)?;
+    if let Some(remote) = rules.and_then(SyncConfig::git_remote) {
+        // As of the last fetch, which only a sync does
+        let dir = super::git::state_dir(&workspace, remote);
+        let mut log = super::git::log(
+            &dir,
+            &super::default_workspace_id(context),
+            &super::device::id()?,
+        );
+        return check_with(&workspace, &dir, &mut log, None, &filter).map(Some);
+    }
+    let server = config
+        .server
+        .as_ref()
+        .filter(|_| backend != Some(SyncBackend::Folder));
+    if let Some(server) = server {
+        let device = Device::load(config.sync.as_ref())?;
+        let mut client = super::client::Client::new(
+            server,
+            &super::workspace_id(server, context),
+            &device,
+            None,
+        );
+        // Without the key, sealed ops can't be told apart and count as changes
+        let sealer = if server.encrypt {
+            seal::Sealer::load(&seal::key_path()).ok()
+        } else {
+            None
+        };
+        return check_with(
+            &workspace,
+            &workspace.join(SYNC_DIR),
+            &mut client,
+            sealer.as_ref(),
+            &filter,
+        )
+        .map(Some);
+    }
+    let folder = rules
+        .and_then(|s| s.folder.as_deref())
+        .filter(|_| backend != Some(SyncBackend::Server));
+    let Some(folder) = folder else {
+        return Ok(None);
+    };
+    let folder = Path::new(&super::expand_home(folder)).to_path_buf();
+    let dir = super::folder::state_dir(&workspace, &folder);
+    let mut log = FolderLog::open(
+        &folder,
+        &super::default_workspace_id(context),
+        &super::device::id()?,
+        &dir,
+    );
+    check_with(&workspace, &dir, &mut log, None, &filter).map(Some)
+}
+
+fn check_with(
+    workspace: &Path,
+    dir: &Path,
+    remote: &mut dyn Remote,
+    sealer: Option<&seal::Sealer>,
+    filter: &Filter,
+) -> Result<Status> {
+    let state = SyncState::load(dir, &remote.name())?;
+    let mut status = Status {
+        unpushed: unpushed(workspace, &state, filter)?,
+        ..Status::default()
+    };
+    match unapplied(remote, &state, sealer) {
+        Ok(sessions) => status.unapplied = sessions,
+        Err(e) => status.remote_error = Some(format!("{e:#}")),
+    }
+    Ok(status)
+}
+
+/// Sessions with files that changed or disappeared since the last sync
+fn unpushed(workspace: &Path, state: &SyncState, filter: &Filter) -> Result<BTreeSet<String>> {
+    let files = scan(workspace, filter)?;
+    let mut sessions = BTreeSet::new();
+    for (path, file) in &files {
+        let stat = FileStat::of(file);
+        if stat
+            .as_ref()
+            .is_some_and(|stat| filter.too_large(stat.size))
+        {
+            continue;
+        }
+        let Some(synced) = state.files.get(path) else {
+            sessions.insert(session_of(path));
+            continue;
+        };
+        if stat.is_some() && state.stats.get(path) == stat.as_ref() {
+            continue;
+        }
+        if fs::read(file).is_ok_and(|bytes| hash(&bytes) != *synced) {
+            sessions.insert(session_of(path));
+        }
+    }
+    let removed = state
+        .files
+        .keys()
+        .filter(|p| !files.contains_key(*p) && !filter.ignores(Path::new(p), false));
+    for path in removed {
+        sessions.insert(session_of(path));
+    }
+    Ok(sessions)
+}
+
+/// Sessions that ops after the cursor would change
+fn unapplied(
+    remote: &mut dyn Remote,
+    state: &SyncState,
+    sealer: Option<&seal::Sealer>,
+) -> Result<BTreeSet<String>> {
+    let (mut files, mut chunked) = (state.files.clone(), state.chunked.clone());
+    let mut sessions = BTreeSet::new();
+    let mut cursor = state.cursor;
+    for _ in 0..MAX_PAGES {
+        let ops = remote.pull(cursor, PULL_PAGE)?;
+        for mut op in ops.iter().cloned() {
+            open(&mut op, sealer, &mut Report::default());
+            match op.op_type.as_str() {
+                RENAME => {
+                    if let Ok(rename) = serde_json::from_str::<SessionRename>(&op.payload)
+                        && files.keys().any(|p| session_of(p) == rename.from)
+                    {
+                        sessions.insert(rename.from);
+                    }
+                }
+                PUT | DELETE => {
+                    let Ok(change) = serde_json::from_str::<FileChange>(&op.payload) else {
+                        continue;
+                    };
+                    let content = change.content.as_deref().map(|c| hash(c.as_bytes()));
+                    let changed = match (op.op_type.as_str(), &change.chunks) {
+                        (DELETE, _) => files.remove(&change.path).is_some(),
+                        (_, Some(chunks)) => {
+                            chunked.insert(change.path.clone(), chunks.clone())
+                                != Some(chunks.clone())
+                        }
+                        _ => content.is_some() && files.get(&change.path) != content.as_ref(),
+                    };
+                    if let Some(content) = content {
+                        files.insert(change.path.clone(), content);
+                    }
+                    if changed {
+                        sessions.insert(session_of(&change.path));
+                    }
+                }
+                _ => {}
+            }
+        }
+        let last = ops.iter().filter_map(|op| op.db_id).max();
+        if ops.len() < PULL_PAGE || last.is_none_or(|id| Some(id) <= cursor) {
+            break;
+        }
+        cursor = last;
+    }
+    Ok(sessions)
+}
+
+/// `plans/notes.md` → `plans`; top-level files (the dashboard) stand for themselves
+fn session_of(path: &str) -> String {
+    path.split('/').next().unwrap_or(path).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::{log::OpLog, sync_with};
+
+    #[test]
+    fn reports_what_each_side_has_not_synced() {
+        let (a, b, shared) = (
+            tempfile::tempdir().unwrap(),
+            tempfile::tempdir().unwrap(),
+            tempfile::tempdir().unwrap(),
+        );
+        let log = || OpLog::open(&shared.path().join("ops.jsonl")).unwrap();
+        let sync = |workspace: &Path| {
+            sync_with(
+                workspace,
+                &workspace.join(SYNC_DIR),
+                &mut log(),
+                "test",
+                None,
+                &Filter::default(),
+            )
+            .unwrap()
+        };
+        let status = |workspace: &Path| {
+            check_with(
+                workspace,
+                &workspace.join(SYNC_DIR),
+                &mut log(),
+                None,
+                &Filter::default(),
+            )
+            .unwrap()
+        };
+        fs::create_dir_all(a.path().join("plans")).unwrap();
+        fs::create_dir_all(a.path().join("ideas")).unwrap();
+        fs::write(a.path().join("plans/notes.md"), "v1").unwrap();
+        fs::write(a.path().join("ideas/notes.md"), "v1").unwrap();
+        assert_eq!(
+            status(a.path()).unpushed,
+            BTreeSet::from(["ideas".to_string(), "plans".to_string()])
+        );
+        sync(a.path());
+        assert!(status(a.path()).is_synced());
+        assert_eq!(status(b.path()).unapplied.len(), 2);
+        sync(b.path());
+
+        fs::write(a.path().join("plans/notes.md"), "v2").unwrap();
+        let here = status(a.path());
+        assert_eq!(here.unpushed, BTreeSet::from(["plans".to_string()]));
+        sync(a.path());
+        let there = status(b.path());
+        assert!(there.unpushed.is_empty());
+        assert_eq!(there.unapplied, BTreeSet::from(["plans".to_string()]));
+    }
+}
@@ -0,0 +1,91 @@
+//! Transfer rate cap for `sp sync --limit-rate`
+
+use std::io::{self, Read};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Wraps a reader so it yields at most `rate` bytes per second on average
+pub struct Throttle<R> {
+    inner: R,
+    rate: u64,
+    start: Instant,
+    bytes: u64,
+}
+
+impl<R: Read> Throttle<R> {
+    pub fn new(inner: R, rate: u64) -> Self {
+        Self {
+            inner,
+            rate: rate.max(1),
+            start: Instant::now(),
+            bytes: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for Throttle<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Reads of a tenth of a second's worth keep the rate steady rather than bursty
+        let len = buf.len().min((self.rate / 10).max(1) as usize);
+        let n = self.inner.read(&mut buf[..len])?;
+        self.bytes += n as u64;
+        let due = Duration::from_secs_f64(self.bytes as f64 / self.rate as f64);
+        if let Some(wait) = due.checked_sub(self.start.elapsed()) {
+            thread::sleep(wait);
+        }
+        Ok(n)
+    }
+}
+
+/// Bytes per second from `500k`, `2M`, `1G` (binary units) or a plain byte count
+pub fn parse_rate(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let (number, unit) = match value.char_indices().find(|(_, c)| c.is_ascii_alphabetic()) {
+        Some((i, _)) => value.split_at(i),
+        None => (value, ""),
+    };
+    let number: f64 = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("'{value}' is not a rate like 500k or 2M"))?;
+    let multiplier: u64 = match unit.to_ascii_lowercase().trim_end_matches('b') {
+        "" => 1,
+        "k" => 1024,
+        "m" => 1024 * 1024,
+        "g" => 1024 * 1024 * 1024,
+        _ => return Err(format!("unknown unit '{unit}' (use k, M or G)")),
+    };
+    let rate = (number * multiplier as f64) as u64;
+    if rate == 0 {
+        return Err("rate must be above zero".to_string());
+    }
+    Ok(rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rates() {
+        assert_eq!(parse_rate("2048"), Ok(2048));
+        assert_eq!(parse_rate("500k"), Ok(500 * 1024));
+        assert_eq!(parse_rate("1.5M"), Ok(3 * 512 * 1024));
+        assert_eq!(parse_rate("1GB"), Ok(1024 * 1024 * 1024));
+        assert!(parse_rate("0").is_err());
+        assert!(parse_rate("fast").is_err());
+        assert!(parse_rate("10x").is_err());
+    }
+
+    #[test]
+    fn caps_the_read_rate() {
+        let data = vec![0u8; 4000];
+        let start = Instant::now();
+        let mut out = Vec::new();
+        Throttle::new(data.as_slice(), 20_000)
+            .read_to_end(&mut out)
+            .unwrap();
+        assert_eq!(out.len(), 4000);
+        assert!(start.elapsed() >= Duration::from_millis(190));
+    }
+}
@@ -0,0 +1,207 @@
+//! Continuous sync with the server (`sp sync --watch`)
+//!
+//! Local changes are noticed by polling the workspace every `POLL_INTERVAL`, which only
+//! looks at file sizes and modification times. Remote ones are announced on the server's
+//! WebSocket, subscribed to for the workspace from a background thread. While the
+//! WebSocket is down, the server is polled every `FALLBACK_INTERVAL` instead and the
+//! thread keeps reconnecting. Announcements of ops this device pushed itself are
+//! ignored: there's nothing to pull.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SendError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use scratchpad_protocol::WsMessage;
+use tungstenite::Message;
+use tungstenite::client::IntoClientRequest as _;
+use tungstenite::http::header::AUTHORIZATION;
+
+use super::device::Device;
+use super::{Report, fingerprint, workspace_id};
+use crate::models::ServerConfig;
+use crate::storage::Storage;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const FALLBACK_INTERVAL: Duration = Duration::from_secs(60);
+/// Wait after a failed sync or a dropped WebSocket before trying again
+const RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// Sync now and whenever either side changes, until interrupted. `on_sync` gets the
+/// report of every sync; failures are printed and retried.
+pub fn run(
+    storage: &Storage,
+    server: &ServerConfig,
+    device: &Device,
+    rate: Option<u64>,
+    mut on_sync: impl FnMut(&Report),
+) -> Result<()> {
+    storage.ensure_workspace()?;
+    let workspace = storage.workspace_path();
+    let filter = super::filter(storage)?;
+    let (rx, live) = announcements(server, &workspace_id(server, storage.context()), device);
+
+    let mut synced: Option<u64> = None;
+    let mut last_sync: Option<Instant> = None;
+    let mut retry_at: Option<Instant> = None;
+    loop {
+        let announced = match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(()) => {
+                while rx.try_recv().is_ok() {}
+                true
+            }
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => false,
+        };
+        if retry_at.is_some_and(|at| Instant::now() < at) {
+            continue;
+        }
+        let changed = synced != Some(fingerprint(&workspace, &filter)?);
+        let due = !live.load(Ordering::Relaxed)
+            && last_sync.is_none_or(|at| at.elapsed() >= FALLBACK_INTERVAL);
+        if !(announced || changed || due || retry_at.is_some()) {
+            continue;
+        }
+
+        last_sync = Some(Instant::now());
+        match super::run(storage, server, device, rate) {
+            Ok(report) => {
+                retry_at = None;
+                synced = Some(fingerprint(&workspace, &filter)?);
+                on_sync(&report);
+            }
+            Err(e) => {
+                eprintln!("Sync failed, retrying in {}s: {e:#}", RETRY_DELAY.as_secs());
+                retry_at = Some(Instant::now() + RETRY_DELAY);
+            }
+        }
+    }
+}
+
+/// Subscribe to `workspace_id` from a background thread. The receiver gets a message
+/// whenever another device's ops are announced, and on each (re)connect to catch up on
+/// what was missed; the flag tells whether the WebSocket is up. The thread ends once the
+/// receiver is dropped and it has something to send.
+pub fn announcements(
+    server: &ServerConfig,
+    workspace_id: &str,
+    device: &Device,
+) -> (Receiver<()>, Arc<AtomicBool>) {
+    let (tx, rx) = mpsc::channel();
+    let live = Arc::new(AtomicBool::new(false));
+    let url = websocket_url(&server.url);
+    let token = server.token.clone();
+    let workspace_id = workspace_id.to_string();
+    let device = device.clone();
+    let connected = live.clone();
+    thread::spawn(move || {
+        listen(
+            &url,
+            token.as_deref(),
+            &workspace_id,
+            &device,
+            &tx,
+            &connected,
+        );
+    });
+    (rx, live)
+}
+
+/// Whether every op announced came from `device`
+fn is_echo(message: &WsMessage, device: &Device) -> bool {
+    message.ops.as_ref().is_some_and(|ops| {
+        !ops.is_empty()
+            && ops
+                .iter()
+                .all(|op| op.client_id.as_deref() == Some(device.id.as_str()))
+    })
+}
+
+/// `http://host/` → `ws://host/ws`
+fn websocket_url(url: &str) -> String {
+    let url = url.trim_end_matches('/');
+    let url = match url.split_once("://") {
+        Some(("https", rest)) => format!("wss://{rest}"),
+        Some((_, rest)) => format!("ws://{rest}"),
+        None => format!("ws://{url}"),
+    };
+    format!("{url}/ws")
+}
+
+/// Keep a subscription to the workspace open, sending on `tx` whenever new ops are
+/// announced (and on each connect, to catch up on what was missed), until nobody listens
+fn listen(
+    url: &str,
+    token: Option<&str>,
+    workspace_id: &str,
+    device: &Device,
+    tx: &Sender<()>,
+    live: &AtomicBool,
+) {
+    loop {
+        let result = subscribe(url, token, workspace_id, device, tx, live);
+        live.store(false, Ordering::Relaxed);
+        if result.is_err_and(|e| e.is::<SendError<()>>()) {
+            return;
+        }
+        thread::sleep(RETRY_DELAY);
+    }
+}
+
+fn subscribe(
+    url: &str,
+    token: Option<&str>,
+    workspace_id: &str,
+    device: &Device,
+    tx: &Sender<()>,
+    live: &AtomicBool,
+) -> Result<()> {
+    let mut request = url.into_client_request()?;
+    if let Some(token) = token {
+        request
+            .headers_mut()
+            .insert(AUTHORIZATION, format!("Bearer {token}").parse()?);
+    }
+    let (mut socket, _) = tungstenite::connect(request)?;
+    let subscribe = WsMessage {
+        msg_type: "subscribe".to_string(),
+        workspace_id: Some(workspace_id.to_string()),
+        client_id: Some(device.id.clone()),
+        client_name: Some(device.name.clone()),
+        ..Default::default()
+    };
+    socket.send(Message::text(serde_json::to_string(&subscribe)?))?;
+    live.store(true, Ordering::Relaxed);
+    tx.send(())?;
+
+    loop {
+        match socket.read()? {
+            Message::Text(text)
+                if serde_json::from_str::<WsMessage>(&text)
+                    .is_ok_and(|m| m.msg_type == "op" && !is_echo(&m, device)) =>
+            {
+                tx.send(())?;
+            }
+            Message::Close(_) => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn websocket_url_follows_the_server_url() {
+        assert_eq!(
+            websocket_url("http://localhost:3000"),
+            "ws://localhost:3000/ws"
+        );
+        assert_eq!(
+            websocket_url("https://sp.example.com/"),
+            "wss://sp.example.com/ws"
+        );
+    }
+}
@@ -0,0 +1,103 @@
+//! Session tags (`sp tag`), optionally proposed by the agent from `tag_vocabulary`
+
+use anyhow::{Result, bail};
+
+use crate::llm;
+use crate::models::Agent;
+use crate::storage::Storage;
+
+/// Per-session cap on the notes sent for auto-tagging, in characters
+const MAX_NOTES_CHARS: usize = 20_000;
+
+/// Lowercase, with spaces turned into dashes; None for blank input
+pub fn normalize(tag: &str) -> Option<String> {
+    let tag = tag
+        .trim()
+        .trim_start_matches('#')
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase();
+    (!tag.is_empty()).then_some(tag)
+}
+
+/// Add `new` to `tags`, keeping them sorted and unique. Returns whether anything changed.
+pub fn add(tags: &mut Vec<String>, new: &[String]) -> bool {
+    let before = tags.len();
+    tags.extend(new.iter().filter_map(|t| normalize(t)));
+    tags.sort();
+    tags.dedup();
+    tags.len() != before
+}
+
+/// Ask `agent` which vocabulary tags fit the session's notes
+pub fn suggest(
+    storage: &Storage,
+    slug: &str,
+    vocabulary: &[String],
+    agent: Agent,
+) -> Result<Vec<String>> {
+    if vocabulary.is_empty() {
+        bail!("Set `tag_vocabulary` in the config to use --auto");
+    }
+    let notes = storage.read_notes(slug)?;
+    if notes.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let notes: String = notes.chars().take(MAX_NOTES_CHARS).collect();
+    let prompt = format!(
+        "Pick the tags that describe this scratchpad session, using only tags from this \
+         list: {}. Output only the chosen tags, comma-separated, or nothing if none fit.\n\n\
+         --- {slug} ---\n{notes}",
+        vocabulary.join(", ")
+    );
+    Ok(parse_suggestions(&llm::ask(agent, &prompt)?, vocabulary))
+}
+
+/// Tags from the agent's answer that are in the vocabulary, in vocabulary order
+fn parse_suggestions(answer: &str, vocabulary: &[String]) -> Vec<String> {
+    // Tolerate list bullets and code formatting around each tag
+    let decoration = |c: char| c.is_whitespace() || "`-*".contains(c);
+    let proposed: Vec<String> = answer
+        .split([',', '\n'])
+        .filter_map(|t| normalize(t.trim_matches(decoration)))
+        .collect();
+    vocabulary
+        .iter()
+        .filter_map(|t| normalize(t))
+        .filter(|t| proposed.contains(t))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_and_adds_tags() {
+        assert_eq!(normalize(" #Bug Fix ").as_deref(), Some("bug-fix"));
+        assert_eq!(normalize("  "), None);
+
+        let mut tags = vec!["infra".to_string()];
+        assert!(add(&mut tags, &["Bug".to_string(), "infra".to_string()]));
+        assert_eq!(tags, vec!["bug", "infra"]);
+        assert!(!add(&mut tags, &["BUG".to_string()]));
+    }
+
+    #[test]
+    fn keeps_only_vocabulary_suggestions() {
+        let vocabulary: Vec<String> = ["bug", "perf", "research", "infra"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            parse_suggestions("Perf, bug, security\n", &vocabulary),
+            vec!["bug", "perf"]
+        );
+        assert_eq!(
+            parse_suggestions("- research\n- `infra`", &vocabulary),
+            vec!["research", "infra"]
+        );
+        assert!(parse_suggestions("", &vocabulary).is_empty());
+    }
+}
@@ -0,0 +1,147 @@
+//! Session templates: markdown files whose `{{name}}` placeholders are filled in when a
+//! session is created from them
+//!
+//! Templates live in the workspace's `.templates/` directory and in `templates/` next to
+//! the config file. A workspace template shadows a global one with the same name.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+
+/// Workspace directory holding templates (hidden, so it is never listed as a session)
+pub const TEMPLATES_DIR: &str = ".templates";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    /// File name without the `.md` extension
+    pub name: String,
+    pub path: PathBuf,
+}
+
+pub fn global_dir() -> PathBuf {
+    config::config_path().with_file_name("templates")
+}
+
+/// Templates available in a workspace, sorted by name
+pub fn list_templates(workspace: &Path) -> Vec<Template> {
+    list_in(&[workspace.join(TEMPLATES_DIR), global_dir()])
+}
+
+/// Collect `*.md` files from `dirs`; earlier directories win on name clashes
+fn list_in(dirs: &[PathBuf]) -> Vec<Template> {
+    let mut found = BTreeMap::new();
+    for dir in dirs {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            let is_md = path
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("md"));
+            if !is_md || !path.is_file() {
+                continue;
+            }
+            if let Some(stem) = path.file_stem() {
+                found
+                    .entry(stem.to_string_lossy().to_string())
+                    .or_insert(path);
+            }
+        }
+    }
+    found
+        .into_iter()
+        .map(|(name, path)| Template { name, path })
+        .collect()
+}
+
+/// Placeholder names in order of first appearance
+pub fn variables(content: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut rest = content;
+    while let Some((_, end, name)) = next_placeholder(rest) {
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+        rest = &rest[end..];
+    }
+    names
+}
+
+/// Substitute placeholders with `values`. Placeholders without a value are left as is.
+pub fn render(content: &str, values: &[(String, String)]) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some((start, end, name)) = next_placeholder(rest) {
+        out.push_str(&rest[..start]);
+        match values.iter().find(|(n, _)| n == name) {
+            Some((_, value)) => out.push_str(value),
+            None => out.push_str(&rest[start..end]),
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Byte range and trimmed name of the next `{{ name }}` in `s`. Braces around anything
+/// other than a simple name (letters, digits, `_`, `-`) are not placeholders.
+fn next_placeholder(s: &str) -> Option<(usize, usize, &str)> {
+    let mut offset = 0;
+    while let Some(open) = s[offset..].find("{{") {
+        let start = offset + open;
+        let close = s[start + 2..].find("}}")?;
+        let end = start + 2 + close + 2;
+        let name = s[start + 2..end - 2].trim();
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+        if valid {
+            return Some((start, end, name));
+        }
+        offset = start + 2;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_and_renders_variables() {
+        let content =
+            "# {{ticket}}: {{ goal }}\n\nFix {{ticket}}. Keep {{ not a var }} and {{}}.\n";
+        assert_eq!(variables(content), vec!["ticket", "goal"]);
+
+        let values = vec![
+            ("ticket".to_string(), "ABC-1".to_string()),
+            ("goal".to_string(), "faster builds".to_string()),
+        ];
+        assert_eq!(
+            render(content, &values),
+            "# ABC-1: faster builds\n\nFix ABC-1. Keep {{ not a var }} and {{}}.\n"
+        );
+        assert_eq!(render("{{missing}}", &values), "{{missing}}");
+    }
+
+    #[test]
+    fn workspace_templates_shadow_global_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let local = dir.path().join("local");
+        let global = dir.path().join("global");
+        fs::create_dir_all(&local).unwrap();
+        fs::create_dir_all(&global).unwrap();
+        fs::write(local.join("bug.md"), "local").unwrap();
+        fs::write(global.join("bug.md"), "global").unwrap();
+        fs::write(global.join("brief.md"), "").unwrap();
+        fs::write(global.join("notes.txt"), "").unwrap();
+
+        let templates = list_in(&[local.clone(), global]);
+        let names: Vec<_> = templates.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["brief", "bug"]);
+        assert_eq!(templates[1].path, local.join("bug.md"));
+    }
+}
@@ -0,0 +1,130 @@
+//! Open action items across sessions: unchecked `- [ ]` tasks and `TODO:` markers.
+//! Checked `- [x]` tasks are collected separately for `sp digest`.
+//!
+//! Only notes (markdown and text files) are scanned, so TODOs in code that agents check out
+//! into a session (e.g. worktrees) don't drown out the actual notes.
+
+use std::path::{Path, PathBuf};
+
+use crate::search::{self, SearchMatch, SearchOptions};
+
+const NOTE_EXTENSIONS: &[&str] = &["md", "markdown", "txt"];
+
+/// Upper bound on collected items, to keep huge workspaces responsive
+const MAX_TODOS: usize = 2000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TodoItem {
+    pub slug: String,
+    /// Path relative to the session directory
+    pub path: PathBuf,
+    /// 1-based line number
+    pub line: usize,
+    /// Item text without the checkbox or marker
+    pub text: String,
+}
+
+/// Scan every session in the workspace, sorted by session, file and line
+pub fn workspace_todos(workspace: &Path) -> Vec<TodoItem> {
+    collect(
+        search::scan_workspace(workspace, &options(), &|line| todo_text(line).is_some()),
+        todo_text,
+    )
+}
+
+/// Scan a single session
+pub fn session_todos(session_dir: &Path, slug: &str) -> Vec<TodoItem> {
+    collect(
+        search::scan_session(session_dir, slug, &options(), &|line| {
+            todo_text(line).is_some()
+        }),
+        todo_text,
+    )
+}
+
+/// Checked-off `- [x]` tasks in a single session
+pub fn session_done(session_dir: &Path, slug: &str) -> Vec<TodoItem> {
+    collect(
+        search::scan_session(session_dir, slug, &options(), &|line| {
+            done_text(line).is_some()
+        }),
+        done_text,
+    )
+}
+
+fn options() -> SearchOptions {
+    SearchOptions {
+        max_results: MAX_TODOS,
+        extensions: NOTE_EXTENSIONS.to_vec(),
+        ..SearchOptions::default()
+    }
+}
+
+fn collect(matches: Vec<SearchMatch>, item_text: fn(&str) -> Option<&str>) -> Vec<TodoItem> {
+    matches
+        .into_iter()
+        .filter_map(|m| {
+            let text = item_text(&m.text)?.to_string();
+            Some(TodoItem {
+                slug: m.slug,
+                path: m.path,
+                line: m.line,
+                text,
+            })
+        })
+        .collect()
+}
+
+/// The action text if `line` is an unchecked task or carries a `TODO:` marker
+fn todo_text(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    for bullet in ["- [ ]", "* [ ]", "+ [ ]"] {
+        if let Some(rest) = trimmed.strip_prefix(bullet) {
+            return Some(rest.trim());
+        }
+    }
+    line.find("TODO:").map(|i| line[i + "TODO:".len()..].trim())
+}
+
+/// The task text if `line` is a checked task
+fn done_text(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    let rest = trimmed
+        .strip_prefix(['-', '*', '+'])?
+        .strip_prefix(" [")?
+        .strip_prefix(['x', 'X'])?
+        .strip_prefix(']')?;
+    Some(rest.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn recognizes_tasks_and_markers() {
+        assert_eq!(todo_text("  - [ ] write tests"), Some("write tests"));
+        assert_eq!(todo_text("* [ ] ship"), Some("ship"));
+        assert_eq!(todo_text("- [x] done"), None);
+        assert_eq!(todo_text("// TODO: handle errors"), Some("handle errors"));
+        assert_eq!(todo_text("todo later"), None);
+        assert_eq!(done_text("- [x] done"), Some("done"));
+        assert_eq!(done_text("  * [X] shipped"), Some("shipped"));
+        assert_eq!(done_text("- [ ] open"), None);
+    }
+
+    #[test]
+    fn scans_notes_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let session = dir.path().join("alpha");
+        fs::create_dir_all(&session).unwrap();
+        fs::write(session.join("notes.md"), "# Plan\n- [ ] one\n- [x] two\n").unwrap();
+        fs::write(session.join("main.rs"), "// TODO: not a note\n").unwrap();
+
+        let todos = workspace_todos(dir.path());
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].text, "one");
+        assert_eq!(todos[0].line, 2);
+    }
+}
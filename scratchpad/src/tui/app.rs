@@ -1,17 +1,72 @@
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use std::{
     collections::hash_map::DefaultHasher,
     hash::{Hash, Hasher},
 };
 
 use anyhow::Result;
+use chrono::{DateTime, Datelike as _, Local, NaiveDate, Utc};
 use crossterm::event::{KeyCode, KeyEvent};
 use ratatui::text::{Line, Text};
 
+use super::live::LiveWorker;
+use super::queue::RunQueue;
+use super::sizes::SizeWorker;
+use super::sync_status::SyncWorker;
+use super::ui::ListRowCache;
+use crate::calendar;
+use crate::config;
+use crate::crypto;
+use crate::git::{self, RepoStatus};
+use crate::init;
 use crate::markdown;
-use crate::models::{Agent, Config, Context, FileTreeEntry, Session};
+use crate::marks::Marks;
+use crate::models::{
+    Agent, Config, Context, FileTreeEntry, GroupBy, Reminder, RunRecord, Session, SessionMeta,
+    Status,
+};
 use crate::names::{generate_session_name, slugify_or_generate};
-use crate::storage::{Storage, build_file_tree, list_session_files};
+use crate::naming;
+use crate::notify;
+use crate::remind;
+use crate::storage::{
+    Storage, TitleCache, WorkspaceChoice, build_file_tree, headings, list_session_files,
+    read_file_head, workspace_choices,
+};
+use crate::sync::{
+    self,
+    conflicts::{Conflict, Resolution, Sides},
+};
+use crate::templates::{self, Template};
+use crate::todos::{self, TodoItem};
+use crate::viewed::ViewedState;
+
+/// Bytes of the entry point loaded into the preview at a time
+const PREVIEW_CHUNK: usize = 256 * 1024;
+
+/// How long typing must pause before the search filter is re-applied
+const SEARCH_DEBOUNCE: Duration = Duration::from_millis(120);
+
+/// How often the sync status is re-checked while the TUI is open
+const SYNC_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often every session's size is recomputed: edits below its top level don't change
+/// the session's mtime, which is all that's checked in between
+const SIZE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often the event loop looks for remote changes pulled in the background
+const LIVE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Columns h/l move the unwrapped notes by
+const HSCROLL_STEP: u16 = 8;
+
+/// How often the event loop checks on queued agent runs while any is pending
+const QUEUE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long a lone `g` waits for a second one before switching context
+const KEY_TIMEOUT: Duration = Duration::from_millis(500);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Mode {
@@ -19,6 +74,29 @@ pub enum Mode {
     Search,
     NewSession,
     QuickSession,
+    EditMeta(MetaField),
+    /// Selecting a range of note lines to split into a new session
+    SelectLines,
+    PickSession,
+    PickTemplate,
+    /// Choosing a linked session to jump to
+    PickLink,
+    /// Choosing a heading of the notes to scroll to
+    Outline,
+    /// Agent runs queued in the background
+    Queue,
+    /// Prompting for the next variable of `App::template_fill`
+    TemplateVar,
+    Todos,
+    Timeline,
+    /// Sessions in columns by status
+    Board,
+    /// Choosing a version of a file with a sync conflict
+    Resolve,
+    /// Choosing a workspace to switch to
+    PickWorkspace,
+    /// Choosing how to ignore a new project scratchpad
+    InitProject,
     Help,
 }
 
@@ -28,12 +106,149 @@ pub enum Focus {
     Detail,
 }
 
+/// Tabs of the detail pane
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetailTab {
+    Notes,
+    Files,
+    Runs,
+    Meta,
+}
+
+impl DetailTab {
+    pub const ALL: [DetailTab; 4] = [
+        DetailTab::Notes,
+        DetailTab::Files,
+        DetailTab::Runs,
+        DetailTab::Meta,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            DetailTab::Notes => "Notes",
+            DetailTab::Files => "Files",
+            DetailTab::Runs => "Runs",
+            DetailTab::Meta => "Meta",
+        }
+    }
+
+    pub fn index(self) -> usize {
+        Self::ALL.iter().position(|&t| t == self).unwrap_or(0)
+    }
+}
+
+/// Editable fields of the Meta tab
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetaField {
+    Due,
+    Status,
+}
+
+impl MetaField {
+    pub const ALL: [MetaField; 2] = [MetaField::Due, MetaField::Status];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MetaField::Status => "Status",
+            MetaField::Due => "Due",
+        }
+    }
+
+    /// Current value as shown in (and pre-filled into) the editor
+    pub fn value(self, meta: &SessionMeta) -> String {
+        match self {
+            MetaField::Status => meta.status.to_string(),
+            MetaField::Due => meta.due.map(|d| d.to_string()).unwrap_or_default(),
+        }
+    }
+
+    /// Parse an edited value into `meta`. An empty value clears the field.
+    fn apply(self, meta: &mut SessionMeta, input: &str) -> Result<()> {
+        match self {
+            MetaField::Status => {
+                meta.status = if input.trim().is_empty() {
+                    Status::Inbox
+                } else {
+                    input.parse().map_err(anyhow::Error::msg)?
+                };
+            }
+            MetaField::Due => {
+                meta.due = if input.trim().is_empty() {
+                    None
+                } else {
+                    Some(calendar::parse_due(input, Local::now().date_naive())?)
+                };
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A template being instantiated: variable values are collected one by one, then the
+/// session name is asked for
+pub struct TemplateFill {
+    pub name: String,
+    content: String,
+    pub variables: Vec<String>,
+    values: Vec<(String, String)>,
+}
+
+impl TemplateFill {
+    /// Variable being prompted for, None once all have values
+    pub fn current(&self) -> Option<&str> {
+        self.variables.get(self.values.len()).map(String::as_str)
+    }
+
+    fn render(&self) -> String {
+        templates::render(&self.content, &self.values)
+    }
+}
+
+/// Keys typed ahead of a list motion
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Prefix {
+    /// Digits of a count (`5j`), and the tab and scroll to go back to when `1`-`4`
+    /// switched tabs before turning out to be a count
+    Count(usize, Option<(DetailTab, u16)>),
+    /// The first `g` of `gg`, typed at
+    G(Instant),
+    /// `m`, then the mark to set
+    Mark,
+    /// `'`, then the mark to jump to
+    JumpMark,
+}
+
+/// A heading of the notes, for the outline popup
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlineEntry {
+    pub level: usize,
+    pub text: String,
+    /// Row of the rendered preview it's on
+    pub row: u16,
+}
+
+/// A row of the session list
+#[derive(Debug, Clone, PartialEq)]
+pub enum ListRow {
+    /// Header of a group, with how many sessions it has and whether they're hidden
+    Group {
+        label: String,
+        count: usize,
+        collapsed: bool,
+    },
+    /// Index into `App::sessions`
+    Session(usize),
+}
+
 pub enum Action {
     Continue,
     Quit,
     RunAgent(String, Agent), // slug, agent
     ViewExternal(PathBuf),
-    EditExternal(PathBuf),
+    /// Open in $EDITOR, optionally at a 1-based line
+    EditExternal(PathBuf, Option<usize>),
+    /// Open notes in $EDITOR next to the project directory they're about
+    EditWithProject(PathBuf, PathBuf),
     OpenFolder(PathBuf),
 }
 
@@ -43,15 +258,68 @@ pub struct App {
     pub context: Context,
     pub available_contexts: Vec<Context>,
     pub sessions: Vec<Session>,
+    /// First heading of each session's entry point, keyed by slug
+    pub titles: HashMap<String, String>,
+    title_cache: TitleCache,
+    /// Due dates from session metadata, keyed by slug
+    pub due_dates: HashMap<String, NaiveDate>,
+    /// Tags of each tagged session, for grouping by tag and `list_format`
+    pub tags: HashMap<String, Vec<String>>,
+    /// Session directory sizes with the session mtime they were computed for
+    sizes: HashMap<String, (DateTime<Utc>, u64)>,
+    size_worker: SizeWorker,
+    /// When every session's size was last requested
+    sizes_checked_at: Option<Instant>,
+    /// Order the list by directory size instead of last update
+    pub sort_by_size: bool,
+    /// Unsynced sessions on either side; None when sync isn't set up or not checked yet
+    pub sync_status: Option<sync::status::Status>,
+    sync_worker: SyncWorker,
+    sync_checked_at: Option<Instant>,
+    /// Pulls remote changes as the server announces them; None when not syncing
+    live: Option<LiveWorker>,
+    /// Sync conflicts not resolved yet, across the workspace
+    pub conflicts: Vec<Conflict>,
+    /// Conflict open in the resolution view, with its versions
+    pub resolving: Option<(Conflict, Sides)>,
+    pub resolve_scroll: u16,
+    /// Selected row of `rows`
     pub selected_index: usize,
+    /// The session list as shown: `filtered_sessions`, under group headers when grouped
+    pub rows: Vec<ListRow>,
+    pub group_by: GroupBy,
+    /// Groups whose sessions are hidden, by label
+    collapsed_groups: HashSet<String>,
+    /// First visible row of the session list
+    pub list_offset: usize,
+    pub list_rows: ListRowCache,
     pub mode: Mode,
     pub focus: Focus,
     pub input: String,
     pub search_query: String,
     pub filtered_sessions: Vec<usize>,
+    /// Query that produced `filtered_sessions`; None when the list must be rebuilt from scratch
+    applied_query: Option<String>,
+    /// Query active before entering search mode, restored on Esc
+    search_before_edit: String,
+    /// Set while typing in search mode; the filter runs once the debounce elapses
+    search_pending_since: Option<Instant>,
     pub notes_content: String,
+    /// The detail panel shows the workspace dashboard (`_index.md`) instead of a session
+    pub dashboard: bool,
+    /// True when only the first `preview_limit` bytes of the entry point are loaded
+    pub notes_truncated: bool,
+    preview_limit: usize,
     pub notes_scroll: u16,
+    /// Off shows wide code blocks and tables unwrapped, scrolled sideways with h/l
+    pub notes_wrap: bool,
+    pub notes_hscroll: u16,
     pub error_message: Option<String>,
+    /// Shown in the status bar until the next key, e.g. a queued run finishing
+    pub notice: Option<String>,
+    /// Agent runs started with `a`
+    pub queue: RunQueue,
+    pub queue_cursor: usize,
     pub show_preview: bool,
     pub rendered_notes: Option<Text<'static>>,
     rendered_notes_hash: u64,
@@ -59,6 +327,59 @@ pub struct App {
     /// Files in the session directory (for when no .md entry point)
     pub session_files: Vec<PathBuf>,
     pub file_tree: Vec<FileTreeEntry>,
+    /// A file other than the entry point shown in the Notes tab (`sp open <session>/<file>`)
+    pub previewed_file: Option<PathBuf>,
+    pub detail_tab: DetailTab,
+    /// Selected row in the Files tab
+    pub file_cursor: usize,
+    /// Metadata of the selected session (Runs and Meta tabs)
+    pub meta: SessionMeta,
+    /// Selected field in the Meta tab
+    pub meta_cursor: usize,
+    /// File being copied or moved to another session: (path, is_move)
+    pub transfer: Option<(PathBuf, bool)>,
+    /// Selected row in the destination picker
+    pub picker_cursor: usize,
+    /// Line selection in the Notes tab: fixed end and moving end (0-based raw lines)
+    pub line_anchor: usize,
+    pub line_cursor: usize,
+    /// Templates offered by the template picker
+    pub templates: Vec<Template>,
+    pub template_cursor: usize,
+    pub template_fill: Option<TemplateFill>,
+    /// Selected row in the linked-session picker
+    pub link_cursor: usize,
+    /// Headings of the notes while the outline popup is open
+    pub outline: Vec<OutlineEntry>,
+    pub outline_cursor: usize,
+    /// Git state of the project repository (Project context only)
+    pub repo_status: Option<RepoStatus>,
+    /// Session for the checked-out branch (`branch_sessions`, Project context only)
+    pub branch_session: Option<String>,
+    /// Last-viewed times for unread badges
+    pub viewed: ViewedState,
+    marks: Marks,
+    prefix: Option<Prefix>,
+    /// Open action items across the workspace (TODO view)
+    pub todos: Vec<TodoItem>,
+    pub todo_cursor: usize,
+    /// Day selected in the timeline view (local time)
+    pub timeline_day: NaiveDate,
+    /// Selected session among those updated on `timeline_day`
+    pub timeline_cursor: usize,
+    /// Status of every session, for the board
+    pub statuses: HashMap<String, Status>,
+    /// Pending reminders by session, due ones are shown above the list
+    pub reminders: HashMap<String, Vec<Reminder>>,
+    /// Selected column (index into `Status::ALL`) and row of the board
+    pub board_column: usize,
+    pub board_cursor: usize,
+    /// Workspaces offered by the switcher; the first is the user workspace
+    pub workspace_choices: Vec<WorkspaceChoice>,
+    /// Selected row of the switcher, which ends with the `init_root` offer if there is one
+    pub workspace_cursor: usize,
+    /// Repository the TUI was opened in, when it has no project scratchpad yet
+    pub init_root: Option<PathBuf>,
 }
 
 impl App {
@@ -68,225 +389,1180 @@ impl App {
         context: Context,
         available_contexts: Vec<Context>,
     ) -> Self {
+        let viewed = ViewedState::load(&storage.workspace_path());
+        let marks = Marks::load(&storage.workspace_path());
+        let workspace_choices = workspace_choices(&config, &available_contexts);
+        let group_by = config.group_by;
+        let live = LiveWorker::spawn(&config, &context);
+        let init_root = if available_contexts
+            .iter()
+            .any(|c| matches!(c, Context::Project(_)))
+        {
+            None
+        } else {
+            std::env::current_dir()
+                .ok()
+                .and_then(|cwd| git::repo_root(&cwd))
+        };
         Self {
             storage,
             config,
             context,
             available_contexts,
             sessions: Vec::new(),
+            titles: HashMap::new(),
+            title_cache: TitleCache::default(),
+            due_dates: HashMap::new(),
+            tags: HashMap::new(),
+            sizes: HashMap::new(),
+            size_worker: SizeWorker::spawn(),
+            sizes_checked_at: None,
+            sort_by_size: false,
+            sync_status: None,
+            sync_worker: SyncWorker::spawn(),
+            live,
+            sync_checked_at: None,
+            conflicts: Vec::new(),
+            resolving: None,
+            resolve_scroll: 0,
             selected_index: 0,
+            rows: Vec::new(),
+            group_by,
+            collapsed_groups: HashSet::new(),
+            list_offset: 0,
+            list_rows: ListRowCache::default(),
             mode: Mode::Normal,
             focus: Focus::List,
             input: String::new(),
             search_query: String::new(),
             filtered_sessions: Vec::new(),
+            applied_query: None,
+            search_before_edit: String::new(),
+            search_pending_since: None,
             notes_content: String::new(),
+            dashboard: false,
+            notes_truncated: false,
+            preview_limit: PREVIEW_CHUNK,
             notes_scroll: 0,
+            notes_wrap: true,
+            notes_hscroll: 0,
             error_message: None,
+            notice: None,
+            queue: RunQueue::spawn(),
+            queue_cursor: 0,
             show_preview: true,
             rendered_notes: None,
             rendered_notes_hash: 0,
             rendered_notes_width: 0,
             session_files: Vec::new(),
             file_tree: Vec::new(),
+            previewed_file: None,
+            detail_tab: DetailTab::Notes,
+            file_cursor: 0,
+            meta: SessionMeta::default(),
+            meta_cursor: 0,
+            transfer: None,
+            picker_cursor: 0,
+            line_anchor: 0,
+            line_cursor: 0,
+            templates: Vec::new(),
+            template_cursor: 0,
+            template_fill: None,
+            link_cursor: 0,
+            outline: Vec::new(),
+            outline_cursor: 0,
+            repo_status: None,
+            branch_session: None,
+            viewed,
+            marks,
+            prefix: None,
+            todos: Vec::new(),
+            todo_cursor: 0,
+            timeline_day: Local::now().date_naive(),
+            timeline_cursor: 0,
+            statuses: HashMap::new(),
+            reminders: HashMap::new(),
+            board_column: 0,
+            board_cursor: 0,
+            workspace_choices,
+            workspace_cursor: 0,
+            init_root,
         }
     }
 
     pub fn refresh_sessions(&mut self) -> Result<()> {
         self.sessions = self.storage.list_sessions()?;
+        self.update_repo_status();
+        self.list_rows.clear();
+        self.titles.clear();
+        self.due_dates.clear();
+        self.tags.clear();
+        self.statuses.clear();
+        self.reminders.clear();
+        for i in 0..self.sessions.len() {
+            let slug = self.sessions[i].slug.clone();
+            self.update_title(&slug);
+            self.update_cached_meta(&slug);
+        }
+        self.request_sizes();
+        self.request_sync_status();
+        self.conflicts = sync::conflicts::list(&self.storage.workspace_path());
+        self.applied_query = None;
         self.apply_filter();
         self.load_selected_notes();
         Ok(())
     }
 
-    fn apply_filter(&mut self) {
-        if self.search_query.is_empty() {
-            self.filtered_sessions = (0..self.sessions.len()).collect();
-        } else {
-            let query = self.search_query.to_lowercase();
-            self.filtered_sessions = self
-                .sessions
-                .iter()
-                .enumerate()
-                .filter(|(_, s)| {
-                    s.slug.to_lowercase().contains(&query)
-                        || s.display_title().to_lowercase().contains(&query)
-                })
-                .map(|(i, _)| i)
-                .collect();
+    /// Re-read a single session after an external process touched it, keeping it selected.
+    /// Falls back to a full refresh if the session disappeared.
+    pub fn refresh_session(&mut self, slug: &str) -> Result<()> {
+        let Some(updated) = self.storage.load_session(slug) else {
+            return self.refresh_sessions();
+        };
+
+        match self.sessions.iter().position(|s| s.slug == slug) {
+            Some(i) => self.sessions[i] = updated,
+            None => self.sessions.push(updated),
         }
+        self.sessions
+            .sort_by_key(|s| std::cmp::Reverse(s.updated_at));
+        self.update_title(slug);
+        self.update_cached_meta(slug);
+        self.update_repo_status();
+        self.request_sizes();
 
-        if self.selected_index >= self.filtered_sessions.len() {
-            self.selected_index = self.filtered_sessions.len().saturating_sub(1);
+        self.applied_query = None;
+        self.apply_filter();
+        if let Some(i) = self.row_of(slug) {
+            self.selected_index = i;
         }
+        self.load_selected_notes();
+        Ok(())
     }
 
-    pub fn selected_session(&self) -> Option<&Session> {
-        self.filtered_sessions
-            .get(self.selected_index)
-            .and_then(|&i| self.sessions.get(i))
+    fn update_title(&mut self, slug: &str) {
+        let dir = self.storage.session_dir(slug);
+        match self.title_cache.title_for(&dir) {
+            Some(title) => self.titles.insert(slug.to_string(), title),
+            None => self.titles.remove(slug),
+        };
     }
 
-    fn load_selected_notes(&mut self) {
-        self.session_files.clear();
-        self.file_tree.clear();
+    /// Agents usually touch the project repo, so this is re-read on every refresh
+    fn update_repo_status(&mut self) {
+        self.repo_status = match &self.context {
+            Context::Project(path) => path.parent().and_then(git::status),
+            Context::User => None,
+        };
+        self.branch_session = self.storage.branch_session();
+    }
 
-        if let Some(session) = self.selected_session() {
-            let slug = session.slug.clone();
-            let session_dir = self.storage.session_dir(&slug);
-            let entry_point = self.storage.find_entry_point(&slug);
+    /// Cache the due date, status and reminders shown outside the Meta tab
+    fn update_cached_meta(&mut self, slug: &str) {
+        let meta = self.storage.load_meta(slug).unwrap_or_default();
+        match meta.due {
+            Some(due) => self.due_dates.insert(slug.to_string(), due),
+            None => self.due_dates.remove(slug),
+        };
+        if meta.tags.is_empty() {
+            self.tags.remove(slug);
+        } else {
+            self.tags.insert(slug.to_string(), meta.tags);
+        }
+        self.statuses.insert(slug.to_string(), meta.status);
+        if meta.reminders.is_empty() {
+            self.reminders.remove(slug);
+        } else {
+            self.reminders.insert(slug.to_string(), meta.reminders);
+        }
+    }
 
-            self.file_tree = build_file_tree(&session_dir, entry_point.as_deref(), 3);
+    /// Due reminders across sessions, oldest first
+    pub fn due_reminders(&self, now: DateTime<Utc>) -> Vec<(&str, &Reminder)> {
+        let mut due: Vec<(&str, &Reminder)> = self
+            .reminders
+            .iter()
+            .flat_map(|(slug, reminders)| reminders.iter().map(move |r| (slug.as_str(), r)))
+            .filter(|(_, r)| r.is_due(now))
+            .collect();
+        due.sort_by_key(|(_, r)| r.at);
+        due
+    }
 
-            if let Some(ref ep) = entry_point {
-                match std::fs::read_to_string(ep) {
-                    Ok(content) => self.notes_content = content,
-                    Err(_) => self.notes_content = String::new(),
-                }
+    /// Snooze (or complete) the selected session's due reminders
+    fn update_reminders(&mut self, complete: bool) {
+        let Some(slug) = self.selected_session().map(|s| s.slug.clone()) else {
+            return;
+        };
+        let now = Utc::now();
+        let result = self.storage.load_meta(&slug).and_then(|mut meta| {
+            let count = if complete {
+                remind::complete(&mut meta, now)
             } else {
-                self.notes_content = String::new();
-                self.session_files = list_session_files(&session_dir);
-                self.session_files.sort();
+                remind::snooze(&mut meta, remind::SNOOZE, now)
+            };
+            if count > 0 {
+                self.storage.save_meta(&slug, &meta)?;
             }
-        } else {
-            self.notes_content = String::new();
+            Ok(meta)
+        });
+        match result {
+            Ok(meta) => {
+                self.meta.reminders = meta.reminders;
+                self.update_cached_meta(&slug);
+            }
+            Err(e) => self.set_error(format!("{e:#}")),
         }
-        self.notes_scroll = 0;
-        self.invalidate_rendered_notes();
     }
 
-    pub fn select_session_by_name(&mut self, name: &str) {
-        let name_lower = name.to_lowercase();
-        for (i, idx) in self.filtered_sessions.iter().enumerate() {
-            if let Some(session) = self.sessions.get(*idx) {
-                if session.slug.to_lowercase() == name_lower
-                    || session.slug.to_lowercase().starts_with(&name_lower)
-                {
-                    self.selected_index = i;
-                    self.load_selected_notes();
-                    return;
-                }
+    /// Queue size computation for sessions whose mtime changed since their size was cached,
+    /// or for all of them every `SIZE_CHECK_INTERVAL`
+    fn request_sizes(&mut self) {
+        let expired = self
+            .sizes_checked_at
+            .is_none_or(|at| at.elapsed() >= SIZE_CHECK_INTERVAL);
+        if expired {
+            self.sizes_checked_at = Some(Instant::now());
+        }
+        for session in &self.sessions {
+            let fresh = !expired
+                && self
+                    .sizes
+                    .get(&session.slug)
+                    .is_some_and(|(mtime, _)| *mtime == session.updated_at);
+            if !fresh {
+                self.size_worker.request(
+                    session.slug.clone(),
+                    self.storage.session_dir(&session.slug),
+                );
             }
         }
     }
 
-    pub fn set_error(&mut self, msg: String) {
-        self.error_message = Some(msg);
+    /// Queue a sync status check, unless one is running or sync isn't set up
+    fn request_sync_status(&mut self) {
+        let configured = self.config.server.is_some()
+            || self
+                .config
+                .sync
+                .as_ref()
+                .is_some_and(|s| s.folder.is_some() || s.git_remote().is_some());
+        if configured && !self.sync_worker.is_busy() {
+            self.sync_worker
+                .request(self.config.clone(), self.context.clone());
+            self.sync_checked_at = Some(Instant::now());
+        }
     }
 
-    pub fn ensure_rendered_notes(&mut self, width: u16) {
-        // If we have session files instead of notes content, skip rendering
-        if !self.session_files.is_empty() {
-            self.rendered_notes = None;
+    fn receive_sync_status(&mut self) {
+        if let Some((workspace, status)) = self.sync_worker.drain()
+            && workspace == self.storage.workspace_path()
+        {
+            self.sync_status = status;
+            // A sync may have run meanwhile
+            self.conflicts = sync::conflicts::list(&self.storage.workspace_path());
+        }
+    }
+
+    /// Unresolved conflicts in the selected session
+    pub fn session_conflicts(&self) -> Vec<&Conflict> {
+        match self.selected_session() {
+            Some(session) if !self.dashboard => self
+                .conflicts
+                .iter()
+                .filter(|c| c.session() == session.slug)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Open the resolution view on the selected session's first conflict, or leave it
+    /// when there are none left
+    fn open_next_conflict(&mut self) {
+        self.resolve_scroll = 0;
+        let Some(conflict) = self.session_conflicts().first().map(|c| (*c).clone()) else {
+            self.resolving = None;
+            self.mode = Mode::Normal;
             return;
+        };
+        match sync::conflicts::sides(&self.storage.workspace_path(), &conflict) {
+            Ok(sides) => {
+                self.resolving = Some((conflict, sides));
+                self.mode = Mode::Resolve;
+            }
+            Err(e) => {
+                self.resolving = None;
+                self.mode = Mode::Normal;
+                self.set_error(format!("{e:#}"));
+            }
         }
+    }
 
-        if self.notes_content.is_empty() {
-            self.rendered_notes = Some(Text::from(Line::from("")));
-            self.rendered_notes_hash = 0;
-            self.rendered_notes_width = width;
+    fn resolve_conflict(&mut self, resolution: Resolution) {
+        let Some((conflict, _)) = &self.resolving else {
+            return;
+        };
+        let workspace = self.storage.workspace_path();
+        if let Err(e) = sync::conflicts::resolve(&workspace, conflict, resolution) {
+            self.set_error(format!("{e:#}"));
             return;
         }
+        let _ = self.refresh_sessions();
+        self.load_selected_notes();
+        self.open_next_conflict();
+    }
 
-        let width = width.max(20);
-        let hash = calculate_hash(&self.notes_content);
-        if self.rendered_notes.is_some()
-            && self.rendered_notes_hash == hash
-            && self.rendered_notes_width == width
+    /// Cached size of a session directory; None until the background worker reports it
+    pub fn session_size(&self, slug: &str) -> Option<u64> {
+        self.sizes.get(slug).map(|(_, size)| *size)
+    }
+
+    fn receive_sizes(&mut self) {
+        let results = self.size_worker.drain();
+        if results.is_empty() {
+            return;
+        }
+        for (slug, size) in results {
+            if let Some(session) = self.sessions.iter().find(|s| s.slug == slug) {
+                self.sizes.insert(slug, (session.updated_at, size));
+            }
+        }
+        if self.sort_by_size {
+            self.applied_query = None;
+            self.apply_filter();
+        }
+    }
+
+    /// Filter sessions by `search_query`. When the query extends the previously applied one,
+    /// only the current matches are re-checked. The selected session stays selected if it
+    /// still matches.
+    fn apply_filter(&mut self) {
+        let selected = self.rows.get(self.selected_index).cloned();
+        let selected_slug = self.selected_session().map(|s| s.slug.clone());
+        let query = self.search_query.to_lowercase();
+
+        if query.is_empty() {
+            self.filtered_sessions = (0..self.sessions.len()).collect();
+        } else {
+            let candidates: Vec<usize> = match &self.applied_query {
+                Some(prev) if query.starts_with(prev.as_str()) => {
+                    std::mem::take(&mut self.filtered_sessions)
+                }
+                _ => (0..self.sessions.len()).collect(),
+            };
+            self.filtered_sessions = candidates
+                .into_iter()
+                .filter(|&i| {
+                    let s = &self.sessions[i];
+                    s.slug.to_lowercase().contains(&query)
+                        || s.display_title().to_lowercase().contains(&query)
+                        || self
+                            .titles
+                            .get(&s.slug)
+                            .is_some_and(|t| t.to_lowercase().contains(&query))
+                })
+                .collect();
+        }
+        self.applied_query = Some(query);
+
+        if self.sort_by_size {
+            let sizes = &self.sizes;
+            let sessions = &self.sessions;
+            self.filtered_sessions.sort_by_key(|&i| {
+                std::cmp::Reverse(sizes.get(&sessions[i].slug).map(|(_, size)| *size))
+            });
+        }
+
+        self.build_rows();
+        if let Some(slug) = selected_slug
+            && let Some(i) = self.row_of(&slug)
+        {
+            self.selected_index = i;
+        } else if let Some(ListRow::Group { label, .. }) = selected
+            && let Some(i) = self.group_row(&label)
         {
+            self.selected_index = i;
+        }
+
+        if self.selected_index >= self.rows.len() {
+            self.selected_index = self.rows.len().saturating_sub(1);
+        }
+    }
+
+    /// Lay out `filtered_sessions` as rows: as they are, or under a header per group with
+    /// the groups in order and each keeping the sessions' order
+    fn build_rows(&mut self) {
+        if self.group_by == GroupBy::None {
+            self.rows = self
+                .filtered_sessions
+                .iter()
+                .map(|&i| ListRow::Session(i))
+                .collect();
             return;
         }
+        let today = Local::now().date_naive();
+        let mut groups: Vec<((u8, String), Vec<usize>)> = Vec::new();
+        for &i in &self.filtered_sessions {
+            let key = self.group_of(&self.sessions[i], today);
+            match groups.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, members)) => members.push(i),
+                None => groups.push((key, vec![i])),
+            }
+        }
+        groups.sort_by(|(a, _), (b, _)| a.cmp(b));
+        self.rows.clear();
+        for ((_, label), members) in groups {
+            let collapsed = self.collapsed_groups.contains(&label);
+            self.rows.push(ListRow::Group {
+                label,
+                count: members.len(),
+                collapsed,
+            });
+            if !collapsed {
+                self.rows.extend(members.into_iter().map(ListRow::Session));
+            }
+        }
+    }
 
-        match markdown::render_markdown(&self.notes_content, width) {
-            Ok(text) => {
-                self.rendered_notes = Some(text);
+    /// Sort key and label of the group a session goes under
+    fn group_of(&self, session: &Session, today: NaiveDate) -> (u8, String) {
+        match self.group_by {
+            GroupBy::Date => {
+                let day = session.updated_at.with_timezone(&Local).date_naive();
+                let week_start =
+                    today - chrono::Days::new(u64::from(today.weekday().num_days_from_monday()));
+                let (order, label) = if day >= today {
+                    (0, "Today")
+                } else if day == today.pred_opt().unwrap_or(today) {
+                    (1, "Yesterday")
+                } else if day >= week_start {
+                    (2, "This week")
+                } else {
+                    (3, "Older")
+                };
+                (order, label.to_string())
             }
-            Err(e) => {
-                self.rendered_notes = Some(Text::from(Line::from(format!("glow error: {e}"))));
+            GroupBy::Tag => match self.tags.get(&session.slug).and_then(|tags| tags.first()) {
+                Some(tag) => (0, tag.clone()),
+                None => (1, "Untagged".to_string()),
+            },
+            GroupBy::None => (0, String::new()),
+        }
+    }
+
+    /// Row showing the session `slug`
+    fn row_of(&self, slug: &str) -> Option<usize> {
+        self.rows.iter().position(|row| {
+            matches!(row, ListRow::Session(i) if self.sessions.get(*i).is_some_and(|s| s.slug == slug))
+        })
+    }
+
+    fn group_row(&self, name: &str) -> Option<usize> {
+        self.rows
+            .iter()
+            .position(|row| matches!(row, ListRow::Group { label, .. } if label == name))
+    }
+
+    /// Fold or unfold the selected group, or the selected session's, leaving its header
+    /// selected
+    fn toggle_group(&mut self) {
+        let label = match self.rows.get(self.selected_index) {
+            Some(ListRow::Group { label, .. }) => label.clone(),
+            Some(&ListRow::Session(i)) if self.group_by != GroupBy::None => {
+                self.group_of(&self.sessions[i], Local::now().date_naive())
+                    .1
             }
+            _ => return,
+        };
+        if !self.collapsed_groups.remove(&label) {
+            self.collapsed_groups.insert(label.clone());
         }
+        self.build_rows();
+        self.selected_index = self.group_row(&label).unwrap_or(0);
+        self.load_selected_notes();
+    }
 
-        self.rendered_notes_hash = hash;
-        self.rendered_notes_width = width;
+    /// Switch to the next way of grouping the list: none, date, tag
+    fn cycle_grouping(&mut self) {
+        self.group_by = match self.group_by {
+            GroupBy::None => GroupBy::Date,
+            GroupBy::Date => GroupBy::Tag,
+            GroupBy::Tag => GroupBy::None,
+        };
+        self.collapsed_groups.clear();
+        self.applied_query = None;
+        self.apply_filter();
+        self.load_selected_notes();
     }
 
-    fn invalidate_rendered_notes(&mut self) {
-        self.rendered_notes = None;
-        self.rendered_notes_hash = 0;
-        self.rendered_notes_width = 0;
+    /// Reload after remote changes were pulled, keeping the selection and, if it's the
+    /// same session, where the preview was scrolled to
+    fn receive_live_changes(&mut self) {
+        if !self.live.as_ref().is_some_and(LiveWorker::drain) {
+            return;
+        }
+        let selected = self.selected_session().map(|s| s.slug.clone());
+        let scroll = self.notes_scroll;
+        if let Err(e) = self.refresh_sessions() {
+            self.set_error(format!("Failed to load sessions: {e}"));
+            return;
+        }
+        if selected.is_some() && self.selected_session().map(|s| s.slug.clone()) == selected {
+            self.notes_scroll = scroll;
+        }
     }
 
-    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
-        self.error_message = None;
+    /// Apply the search query typed so far, reloading the preview only if the selection moved.
+    fn apply_live_search(&mut self) {
+        self.search_pending_since = None;
+        if self.search_query == self.input {
+            return;
+        }
+        let before = self.selected_session().map(|s| s.slug.clone());
+        self.search_query = self.input.clone();
+        self.apply_filter();
+        if self.selected_session().map(|s| s.slug.clone()) != before {
+            self.load_selected_notes();
+        }
+    }
 
-        match self.mode {
-            Mode::Normal => self.handle_normal_key(key),
-            Mode::Search => self.handle_search_key(key),
-            Mode::NewSession => self.handle_new_session_key(key),
-            Mode::QuickSession => self.handle_quick_session_key(key),
-            Mode::Help => self.handle_help_key(key),
+    /// How long the event loop may block before `tick` has work to do
+    pub fn poll_timeout(&self) -> Option<Duration> {
+        let search = self
+            .search_pending_since
+            .map(|since| SEARCH_DEBOUNCE.saturating_sub(since.elapsed()));
+        let sizes = (self.size_worker.is_busy() || self.sync_worker.is_busy())
+            .then_some(Duration::from_millis(100));
+        let live = self.live.as_ref().map(|_| LIVE_POLL_INTERVAL);
+        let queue = self.queue.is_active().then_some(QUEUE_POLL_INTERVAL);
+        let lone_g = match self.prefix {
+            Some(Prefix::G(at)) => Some(KEY_TIMEOUT.saturating_sub(at.elapsed())),
+            _ => None,
+        };
+        let sync_check = self
+            .sync_checked_at
+            .filter(|_| !self.sync_worker.is_busy())
+            .map(|at| SYNC_CHECK_INTERVAL.saturating_sub(at.elapsed()));
+        search
+            .into_iter()
+            .chain(sizes)
+            .chain(sync_check)
+            .chain(live)
+            .chain(queue)
+            .chain(lone_g)
+            .min()
+    }
+
+    /// Record and announce the queued agent runs that ended
+    fn receive_finished_runs(&mut self) {
+        for run in self.queue.drain() {
+            let exit_code = match run.exit_code {
+                Ok(exit_code) => exit_code,
+                Err(e) => {
+                    self.set_error(e);
+                    continue;
+                }
+            };
+            let record = RunRecord {
+                agent: run.agent,
+                started_at: run.started_at,
+                duration_secs: run.duration.as_secs(),
+                exit_code,
+            };
+            if let Err(e) = self.storage.record_run(&run.slug, record) {
+                self.set_error(format!("Failed to record run: {e}"));
+            }
+            let event = notify::Event::AgentFinished {
+                slug: &run.slug,
+                agent: run.agent,
+                exit_code,
+                duration: run.duration,
+            };
+            self.notice = Some(event.summary());
+            self.notify(event);
+            // Keep the selection where it was
+            let selected = self.selected_session().map(|s| s.slug.clone());
+            if let Err(e) = self.refresh_session(&run.slug) {
+                self.set_error(format!("{e:#}"));
+            }
+            if let Some(i) = selected.and_then(|slug| self.row_of(&slug)) {
+                self.selected_index = i;
+                self.load_selected_notes();
+            }
         }
     }
 
-    fn handle_normal_key(&mut self, key: KeyEvent) -> Action {
+    /// The selected session's entry point, creating `notes.md` if it has none
+    fn notes_to_edit(&self, slug: &str) -> PathBuf {
+        if let Some(file) = &self.previewed_file {
+            return file.clone();
+        }
+        if let Some(entry_point) = self.storage.find_entry_point(slug) {
+            return entry_point;
+        }
+        let mut notes_path = self.storage.session_dir(slug).join("notes.md");
+        if self.meta.encrypted {
+            notes_path = crypto::encrypted_path(&notes_path);
+        }
+        if !notes_path.exists() {
+            let _ = self.storage.write_text(&notes_path, "");
+        }
+        notes_path
+    }
+
+    /// The file to edit in place of `path`: a staged copy of it while its session isn't in
+    /// sync (see `sync::staging`), going by the last sync status
+    pub fn stage_edit(&mut self, path: &Path) -> Result<PathBuf> {
+        let workspace = self.storage.workspace_path();
+        let Ok(relative) = path.strip_prefix(&workspace) else {
+            return Ok(path.to_path_buf());
+        };
+        let mut components = relative.components();
+        let (Some(slug), file) = (components.next(), components.as_path()) else {
+            return Ok(path.to_path_buf());
+        };
+        let slug = slug.as_os_str().to_string_lossy().into_owned();
+        if file.as_os_str().is_empty() || !self.storage.session_dir(&slug).is_dir() {
+            return Ok(path.to_path_buf());
+        }
+        let Some(pending) = sync::staging::pending(&workspace, &slug, self.sync_status.as_ref())
+        else {
+            return Ok(path.to_path_buf());
+        };
+
+        let file = if crypto::is_encrypted(file) {
+            file.with_extension("")
+        } else {
+            file.to_path_buf()
+        };
+        let content = if path.exists() {
+            self.storage.read_text(path)?
+        } else {
+            String::new()
+        };
+        let staged = sync::staging::stage(&self.storage, &slug, &file.to_string_lossy(), &content)?;
+        self.notice = Some(sync::staging::notice(&slug, pending, &staged));
+        Ok(workspace.join(&staged.staged))
+    }
+
+    /// Queue the default agent to run in the selected session
+    fn queue_run(&mut self) {
+        let Some(session) = self.selected_session() else {
+            return;
+        };
+        let slug = session.slug.clone();
+        let agent = self.config.default_agent;
+        let ahead = self.queue.jobs.iter().filter(|j| !j.is_finished()).count();
+        self.queue
+            .push(slug.clone(), agent, self.storage.session_dir(&slug));
+        self.notice = Some(match ahead {
+            0 => format!("Running {agent} in {slug} in the background (A: queue)"),
+            n => format!("Queued {agent} for {slug}, after {n} more (A: queue)"),
+        });
+    }
+
+    fn handle_queue_key(&mut self, key: KeyEvent) -> Action {
+        let last = self.queue.jobs.len().saturating_sub(1);
         match key.code {
-            KeyCode::Char('q') => Action::Quit,
-            KeyCode::Char('?') => {
-                self.mode = Mode::Help;
-                Action::Continue
-            }
-            KeyCode::Char('/') => {
-                self.mode = Mode::Search;
-                self.input.clear();
-                Action::Continue
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('A') => {
+                self.mode = Mode::Normal;
             }
-            KeyCode::Char('n') => {
-                self.mode = Mode::NewSession;
-                self.input.clear();
-                Action::Continue
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.queue_cursor = self.queue_cursor.saturating_sub(1);
             }
-            KeyCode::Char('Q') => {
-                self.mode = Mode::QuickSession;
-                self.input.clear();
-                Action::Continue
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.queue_cursor = (self.queue_cursor + 1).min(last);
             }
-            KeyCode::Char('p') => {
+            KeyCode::Char('x') => self.queue.cancel(self.queue_cursor),
+            KeyCode::Char('c') => self.queue.clear_finished(),
+            KeyCode::Enter => {
+                if let Some(job) = self.queue.jobs.get(self.queue_cursor) {
+                    let slug = job.slug.clone();
+                    if self.jump_to_session(&slug) {
+                        self.mode = Mode::Normal;
+                    }
+                }
+            }
+            _ => {}
+        }
+        self.queue_cursor = self
+            .queue_cursor
+            .min(self.queue.jobs.len().saturating_sub(1));
+        Action::Continue
+    }
+
+    /// Run time-based work (debounced search, background sizes and sync status). Called by
+    /// the event loop after each poll.
+    pub fn tick(&mut self) {
+        self.receive_sizes();
+        self.receive_sync_status();
+        self.receive_live_changes();
+        self.receive_finished_runs();
+        if self
+            .sync_checked_at
+            .is_some_and(|at| at.elapsed() >= SYNC_CHECK_INTERVAL)
+        {
+            self.request_sync_status();
+        }
+        if !self.size_worker.is_busy()
+            && self
+                .sizes_checked_at
+                .is_some_and(|at| at.elapsed() >= SIZE_CHECK_INTERVAL)
+        {
+            self.request_sizes();
+        }
+        if let Some(since) = self.search_pending_since
+            && since.elapsed() >= SEARCH_DEBOUNCE
+        {
+            self.apply_live_search();
+        }
+        if let Some(Prefix::G(at)) = self.prefix
+            && at.elapsed() >= KEY_TIMEOUT
+        {
+            self.prefix = None;
+            self.switch_context();
+        }
+    }
+
+    /// The session on the selected row; None on a group header
+    pub fn selected_session(&self) -> Option<&Session> {
+        match self.rows.get(self.selected_index) {
+            Some(&ListRow::Session(i)) => self.sessions.get(i),
+            _ => None,
+        }
+    }
+
+    fn load_selected_notes(&mut self) {
+        self.dashboard = false;
+        self.session_files.clear();
+        self.file_tree.clear();
+        self.file_cursor = 0;
+        self.previewed_file = None;
+        self.meta = SessionMeta::default();
+
+        if let Some(session) = self.selected_session() {
+            let slug = session.slug.clone();
+            let unread = self.viewed.is_unread(session);
+            let session_dir = self.storage.session_dir(&slug);
+            let entry_point = self.storage.find_entry_point(&slug);
+            self.meta = self.storage.load_meta(&slug).unwrap_or_default();
+            if unread
+                && !self.config.read_only
+                && let Err(e) = self.viewed.mark_viewed(&slug)
+            {
+                self.set_error(format!("{e:#}"));
+            }
+
+            self.file_tree = build_file_tree(&session_dir, entry_point.as_deref(), 3);
+
+            self.preview_limit = PREVIEW_CHUNK;
+            if let Some(ref ep) = entry_point {
+                self.read_preview(ep);
+            } else {
+                self.notes_content = String::new();
+                self.notes_truncated = false;
+                self.session_files = list_session_files(&session_dir);
+                self.session_files.sort();
+            }
+        } else if !self.show_dashboard() {
+            self.notes_content = String::new();
+            self.notes_truncated = false;
+        }
+        self.notes_scroll = 0;
+        self.notes_hscroll = 0;
+        self.invalidate_rendered_notes();
+    }
+
+    /// Show the workspace dashboard in the detail panel until another session is
+    /// selected. Returns false when the workspace has none.
+    pub fn show_dashboard(&mut self) -> bool {
+        let Ok(content) = std::fs::read_to_string(self.storage.dashboard_path()) else {
+            return false;
+        };
+        self.session_files.clear();
+        self.file_tree.clear();
+        self.file_cursor = 0;
+        self.previewed_file = None;
+        self.meta = SessionMeta::default();
+        self.notes_content = content;
+        self.notes_truncated = false;
+        self.notes_scroll = 0;
+        self.notes_hscroll = 0;
+        self.detail_tab = DetailTab::Notes;
+        self.dashboard = true;
+        self.invalidate_rendered_notes();
+        true
+    }
+
+    fn read_preview(&mut self, entry_point: &std::path::Path) {
+        if crypto::is_encrypted(entry_point) {
+            // Decrypted in memory only; encrypted notes are not chunked
+            self.notes_truncated = false;
+            self.notes_content = match self.storage.read_text(entry_point) {
+                Ok(content) => content,
+                Err(e) => format!("Encrypted session: {e:#}"),
+            };
+            return;
+        }
+        (self.notes_content, self.notes_truncated) =
+            read_file_head(entry_point, self.preview_limit).unwrap_or_default();
+    }
+
+    /// Load the next chunk of a truncated entry point, keeping the scroll position
+    fn load_more_notes(&mut self) {
+        if !self.notes_truncated {
+            return;
+        }
+        let Some(slug) = self.selected_session().map(|s| s.slug.clone()) else {
+            return;
+        };
+        let file = self
+            .previewed_file
+            .clone()
+            .or_else(|| self.storage.find_entry_point(&slug));
+        if let Some(ep) = file {
+            self.preview_limit = self.preview_limit.saturating_add(PREVIEW_CHUNK);
+            self.read_preview(&ep);
+            self.invalidate_rendered_notes();
+        }
+    }
+
+    pub fn select_session_by_name(&mut self, name: &str) {
+        let name_lower = name.to_lowercase();
+        for (i, row) in self.rows.iter().enumerate() {
+            if let ListRow::Session(idx) = row
+                && let Some(session) = self.sessions.get(*idx)
+                && (session.slug.to_lowercase() == name_lower
+                    || session.slug.to_lowercase().starts_with(&name_lower))
+            {
+                self.selected_index = i;
+                self.load_selected_notes();
+                return;
+            }
+        }
+    }
+
+    /// Show a file of the selected session in the Notes tab instead of its entry point,
+    /// with the Files tab's cursor on it
+    pub fn preview_file(&mut self, path: &Path) {
+        if self.selected_session().is_none() {
+            return;
+        }
+        if let Some(i) = self.file_tree.iter().position(|e| e.path == path) {
+            self.file_cursor = i;
+        }
+        self.previewed_file = Some(path.to_path_buf());
+        self.preview_limit = PREVIEW_CHUNK;
+        self.read_preview(path);
+        self.notes_scroll = 0;
+        self.notes_hscroll = 0;
+        self.detail_tab = DetailTab::Notes;
+        self.focus = Focus::Detail;
+        self.invalidate_rendered_notes();
+    }
+
+    pub fn set_error(&mut self, msg: String) {
+        self.error_message = Some(msg);
+    }
+
+    /// Fire a webhook for a session event, surfacing delivery failures as an error popup
+    pub fn notify(&mut self, event: notify::Event) {
+        if let Err(e) = notify::send(&self.config, &self.context, &event) {
+            self.set_error(format!("{e:#}"));
+        }
+    }
+
+    pub fn ensure_rendered_notes(&mut self, width: u16) {
+        // If we have session files instead of notes content, skip rendering
+        if !self.session_files.is_empty() {
+            self.rendered_notes = None;
+            return;
+        }
+
+        if self.notes_content.is_empty() {
+            self.rendered_notes = Some(Text::from(Line::from("")));
+            self.rendered_notes_hash = 0;
+            self.rendered_notes_width = width;
+            return;
+        }
+
+        let width = width.max(20);
+        let hash = calculate_hash(&self.notes_content);
+        if self.rendered_notes.is_some()
+            && self.rendered_notes_hash == hash
+            && self.rendered_notes_width == width
+        {
+            return;
+        }
+
+        match markdown::render_markdown(&self.notes_content, width) {
+            Ok(text) => {
+                self.rendered_notes = Some(text);
+            }
+            Err(e) => {
+                self.rendered_notes = Some(Text::from(Line::from(format!("glow error: {e}"))));
+            }
+        }
+
+        self.rendered_notes_hash = hash;
+        self.rendered_notes_width = width;
+    }
+
+    fn invalidate_rendered_notes(&mut self) {
+        self.rendered_notes = None;
+        self.rendered_notes_hash = 0;
+        self.rendered_notes_width = 0;
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
+        self.error_message = None;
+        self.notice = None;
+
+        match self.mode {
+            Mode::Normal => self.handle_normal_key(key),
+            Mode::Search => self.handle_search_key(key),
+            Mode::NewSession => self.handle_new_session_key(key),
+            Mode::QuickSession => self.handle_quick_session_key(key),
+            Mode::EditMeta(field) => self.handle_edit_meta_key(field, key),
+            Mode::SelectLines => self.handle_select_lines_key(key),
+            Mode::PickSession => self.handle_pick_session_key(key),
+            Mode::PickTemplate => self.handle_pick_template_key(key),
+            Mode::PickLink => self.handle_pick_link_key(key),
+            Mode::Outline => self.handle_outline_key(key),
+            Mode::Queue => self.handle_queue_key(key),
+            Mode::TemplateVar => self.handle_template_var_key(key),
+            Mode::Todos => self.handle_todos_key(key),
+            Mode::Timeline => self.handle_timeline_key(key),
+            Mode::Board => self.handle_board_key(key),
+            Mode::Resolve => self.handle_resolve_key(key),
+            Mode::PickWorkspace => self.handle_pick_workspace_key(key),
+            Mode::InitProject => self.handle_init_project_key(key),
+            Mode::Help => self.handle_help_key(key),
+        }
+    }
+
+    /// Keys that change the workspace, refused in read-only mode
+    fn is_mutating_key(&self, key: KeyEvent) -> bool {
+        let detail = self.focus == Focus::Detail;
+        match key.code {
+            KeyCode::Char('n' | 'Q' | 't' | 'r' | 'a' | 'e' | 'E' | 'M' | 'z' | 'D' | 'X') => true,
+            KeyCode::Char('V') => detail && self.detail_tab == DetailTab::Notes,
+            KeyCode::Char('y' | 'x') => detail && self.detail_tab == DetailTab::Files,
+            KeyCode::Enter => detail && self.detail_tab == DetailTab::Meta,
+            _ => false,
+        }
+    }
+
+    fn handle_normal_key(&mut self, key: KeyEvent) -> Action {
+        if self.config.read_only && self.is_mutating_key(key) {
+            self.set_error("Read-only mode".to_string());
+            return Action::Continue;
+        }
+        if let Some(action) = self.handle_prefix(key) {
+            return action;
+        }
+        match key.code {
+            KeyCode::Char('q') => Action::Quit,
+            KeyCode::Char('?') => {
+                self.mode = Mode::Help;
+                Action::Continue
+            }
+            KeyCode::Char('/') => {
+                self.mode = Mode::Search;
+                self.search_before_edit = self.search_query.clone();
+                self.input = self.search_query.clone();
+                Action::Continue
+            }
+            KeyCode::Char('n') => {
+                self.mode = Mode::NewSession;
+                self.input.clear();
+                Action::Continue
+            }
+            KeyCode::Char('t') => {
+                self.templates = templates::list_templates(&self.storage.workspace_path());
+                if self.templates.is_empty() {
+                    self.set_error(format!(
+                        "No templates in {} or {}",
+                        templates::TEMPLATES_DIR,
+                        templates::global_dir().display()
+                    ));
+                } else {
+                    self.template_cursor = 0;
+                    self.mode = Mode::PickTemplate;
+                }
+                Action::Continue
+            }
+            KeyCode::Char('Q') => {
+                self.mode = Mode::QuickSession;
+                self.input.clear();
+                Action::Continue
+            }
+            KeyCode::Char('p') => {
                 self.show_preview = !self.show_preview;
                 Action::Continue
             }
-            // 'g' - toggle context
-            KeyCode::Char('g') => {
-                if self.available_contexts.len() > 1 {
-                    let current_idx = self
-                        .available_contexts
+            KeyCode::Char('+') => {
+                self.load_more_notes();
+                Action::Continue
+            }
+            KeyCode::Char('O') => {
+                self.open_outline();
+                Action::Continue
+            }
+            KeyCode::Char('L') => {
+                if self.meta.links.is_empty() {
+                    self.set_error("No linked sessions. Add one with `sp link`.".to_string());
+                } else {
+                    self.link_cursor = 0;
+                    self.mode = Mode::PickLink;
+                }
+                Action::Continue
+            }
+            KeyCode::Char('S') => {
+                self.sort_by_size = !self.sort_by_size;
+                // Re-filtering from scratch restores recency order when toggled off
+                self.applied_query = None;
+                self.apply_filter();
+                self.load_selected_notes();
+                Action::Continue
+            }
+            KeyCode::Char('T') => {
+                self.refresh_todos();
+                self.todo_cursor = 0;
+                self.mode = Mode::Todos;
+                Action::Continue
+            }
+            KeyCode::Char('C') => {
+                self.timeline_day = self
+                    .selected_session()
+                    .map(|s| local_day(s.updated_at))
+                    .unwrap_or_else(|| Local::now().date_naive());
+                self.timeline_cursor = 0;
+                self.mode = Mode::Timeline;
+                Action::Continue
+            }
+            KeyCode::Char('B') => {
+                let status = self.meta.status;
+                self.board_column = status.index();
+                self.board_cursor = self.selected_session().map_or(0, |selected| {
+                    self.board_sessions(status)
                         .iter()
-                        .position(|c| c == &self.context)
-                        .unwrap_or(0);
-                    let next_idx = (current_idx + 1) % self.available_contexts.len();
-                    self.context = self.available_contexts[next_idx].clone();
-                    self.storage.switch_context(self.context.clone());
-                    let _ = self.refresh_sessions();
+                        .position(|s| s.slug == selected.slug)
+                        .unwrap_or(0)
+                });
+                self.mode = Mode::Board;
+                Action::Continue
+            }
+            KeyCode::Char('X') => {
+                if self.session_conflicts().is_empty() {
+                    self.set_error("No sync conflicts in this session".to_string());
+                } else {
+                    self.open_next_conflict();
+                }
+                Action::Continue
+            }
+            KeyCode::Char(c @ ('z' | 'D')) => {
+                self.update_reminders(c == 'D');
+                Action::Continue
+            }
+            KeyCode::Char('M') => {
+                match self.viewed.mark_all_viewed() {
+                    Ok(()) => self.list_rows.clear(),
+                    Err(e) => self.set_error(format!("{e:#}")),
+                }
+                Action::Continue
+            }
+            KeyCode::Char('R') => {
+                if let Err(e) = self.refresh_sessions() {
+                    self.set_error(format!("Failed to refresh: {e}"));
+                }
+                Action::Continue
+            }
+            // 'W' - switch to another workspace
+            KeyCode::Char('W') => {
+                if self.workspace_choices.len() > 1 || self.init_root.is_some() {
+                    self.workspace_cursor = self.current_workspace().unwrap_or(0);
+                    self.mode = Mode::PickWorkspace;
+                } else {
+                    self.set_error(format!(
+                        "No other workspaces; name some under [workspaces] in {}",
+                        config::config_path().display()
+                    ));
                 }
                 Action::Continue
             }
             // 'e' - edit with editor
+            KeyCode::Char('e')
+                if self.focus == Focus::Detail && self.detail_tab == DetailTab::Files =>
+            {
+                match self.file_tree.get(self.file_cursor) {
+                    Some(entry) if !entry.is_dir => Action::EditExternal(entry.path.clone(), None),
+                    _ => Action::Continue,
+                }
+            }
+            // 'V' - select note lines to split into a new quick session
+            KeyCode::Char('V')
+                if self.focus == Focus::Detail
+                    && self.detail_tab == DetailTab::Notes
+                    && !self.notes_content.is_empty() =>
+            {
+                let last = self.notes_content.lines().count().saturating_sub(1);
+                self.line_anchor = (self.notes_scroll as usize).min(last);
+                self.line_cursor = self.line_anchor;
+                self.mode = Mode::SelectLines;
+                Action::Continue
+            }
+            // 'y' / 'x' - copy / move the selected file to another session
+            KeyCode::Char(c @ ('y' | 'x'))
+                if self.focus == Focus::Detail && self.detail_tab == DetailTab::Files =>
+            {
+                if let Some(entry) = self.file_tree.get(self.file_cursor) {
+                    self.transfer = Some((entry.path.clone(), c == 'x'));
+                    self.picker_cursor = 0;
+                    self.input.clear();
+                    self.mode = Mode::PickSession;
+                }
+                Action::Continue
+            }
+            KeyCode::Char('e') if self.dashboard => match self.storage.ensure_dashboard() {
+                Ok(path) => Action::EditExternal(path, None),
+                Err(e) => {
+                    self.set_error(format!("{e:#}"));
+                    Action::Continue
+                }
+            },
+            KeyCode::Char('H') => {
+                if !self.show_dashboard() {
+                    self.set_error(
+                        "No dashboard yet, create one with `sp edit --index`".to_string(),
+                    );
+                }
+                Action::Continue
+            }
             KeyCode::Char('e') => {
                 if let Some(session) = self.selected_session() {
                     let slug = session.slug.clone();
-                    if let Some(entry_point) = self.storage.find_entry_point(&slug) {
-                        Action::EditExternal(entry_point)
-                    } else {
-                        // Create notes.md if no entry point
-                        let notes_path = self.storage.session_dir(&slug).join("notes.md");
-                        if !notes_path.exists() {
-                            let _ = std::fs::write(&notes_path, "");
-                        }
-                        Action::EditExternal(notes_path)
-                    }
+                    Action::EditExternal(self.notes_to_edit(&slug), None)
                 } else {
                     Action::Continue
                 }
             }
+            KeyCode::Char('E') => {
+                let Some(slug) = self.selected_session().map(|s| s.slug.clone()) else {
+                    return Action::Continue;
+                };
+                match self.storage.project_root(&slug) {
+                    Some(root) => Action::EditWithProject(self.notes_to_edit(&slug), root),
+                    None => {
+                        self.set_error(
+                            "No project for this session: it has no worktree and isn't in a \
+                             project scratchpad"
+                                .to_string(),
+                        );
+                        Action::Continue
+                    }
+                }
+            }
             // 'v' - view with viewer
             KeyCode::Char('v') => {
                 if let Some(session) = self.selected_session() {
@@ -320,6 +1596,48 @@ impl App {
                     Action::Continue
                 }
             }
+            KeyCode::Char('a') => {
+                self.queue_run();
+                Action::Continue
+            }
+            KeyCode::Char('A') => {
+                self.queue_cursor = 0;
+                self.mode = Mode::Queue;
+                Action::Continue
+            }
+            KeyCode::Char('w') => {
+                self.notes_wrap = !self.notes_wrap;
+                self.notes_hscroll = 0;
+                Action::Continue
+            }
+            KeyCode::Char('h') if self.scrolls_sideways() => {
+                self.notes_hscroll = self.notes_hscroll.saturating_sub(HSCROLL_STEP);
+                Action::Continue
+            }
+            KeyCode::Char('l') if self.scrolls_sideways() => {
+                self.notes_hscroll = self.notes_hscroll.saturating_add(HSCROLL_STEP);
+                Action::Continue
+            }
+            KeyCode::Char('h') => {
+                let i = self.detail_tab.index();
+                let len = DetailTab::ALL.len();
+                self.set_detail_tab(DetailTab::ALL[(i + len - 1) % len]);
+                Action::Continue
+            }
+            KeyCode::Char('l') => {
+                let i = self.detail_tab.index();
+                self.set_detail_tab(DetailTab::ALL[(i + 1) % DetailTab::ALL.len()]);
+                Action::Continue
+            }
+            KeyCode::Enter if self.focus == Focus::Detail => self.activate_detail_item(),
+            KeyCode::Up | KeyCode::Char('k') if self.focus == Focus::Detail => {
+                self.move_detail_cursor(-1);
+                Action::Continue
+            }
+            KeyCode::Down | KeyCode::Char('j') if self.focus == Focus::Detail => {
+                self.move_detail_cursor(1);
+                Action::Continue
+            }
             KeyCode::Up | KeyCode::Char('k') => {
                 if self.selected_index > 0 {
                     self.selected_index -= 1;
@@ -327,8 +1645,25 @@ impl App {
                 }
                 Action::Continue
             }
+            KeyCode::Enter
+                if matches!(
+                    self.rows.get(self.selected_index),
+                    Some(ListRow::Group { .. })
+                ) =>
+            {
+                self.toggle_group();
+                Action::Continue
+            }
+            KeyCode::Char('f') => {
+                self.toggle_group();
+                Action::Continue
+            }
+            KeyCode::Char('F') => {
+                self.cycle_grouping();
+                Action::Continue
+            }
             KeyCode::Down | KeyCode::Char('j') => {
-                if self.selected_index < self.filtered_sessions.len().saturating_sub(1) {
+                if self.selected_index < self.rows.len().saturating_sub(1) {
                     self.selected_index += 1;
                     self.load_selected_notes();
                 }
@@ -364,19 +1699,21 @@ impl App {
     fn handle_search_key(&mut self, key: KeyEvent) -> Action {
         match key.code {
             KeyCode::Enter => {
-                self.search_query = self.input.clone();
-                self.apply_filter();
-                self.load_selected_notes();
+                self.apply_live_search();
                 self.mode = Mode::Normal;
             }
             KeyCode::Esc => {
+                self.input = std::mem::take(&mut self.search_before_edit);
+                self.apply_live_search();
                 self.mode = Mode::Normal;
             }
             KeyCode::Backspace => {
                 self.input.pop();
+                self.search_pending_since = Some(Instant::now());
             }
             KeyCode::Char(c) => {
                 self.input.push(c);
+                self.search_pending_since = Some(Instant::now());
             }
             _ => {}
         }
@@ -393,15 +1730,20 @@ impl App {
                     slugify_or_generate(&self.input, &existing, &self.config)
                 };
 
+                let note = self.template_fill.take().map(|t| t.render());
                 let session = Session::new(&slug);
-                if let Err(e) = self.storage.create_session(&session, None) {
+                if let Err(e) = naming::check(&self.config, &slug) {
+                    self.set_error(format!("{e:#}"));
+                } else if let Err(e) = self.storage.create_session(&session, note.as_deref()) {
                     self.set_error(format!("Failed to create session: {e}"));
                 } else {
                     let _ = self.refresh_sessions();
+                    self.notify(notify::Event::SessionCreated { slug: &slug });
                 }
                 self.mode = Mode::Normal;
             }
             KeyCode::Esc => {
+                self.template_fill = None;
                 self.mode = Mode::Normal;
             }
             KeyCode::Backspace => {
@@ -415,31 +1757,851 @@ impl App {
         Action::Continue
     }
 
-    fn handle_quick_session_key(&mut self, key: KeyEvent) -> Action {
+    fn handle_pick_template_key(&mut self, key: KeyEvent) -> Action {
         match key.code {
             KeyCode::Enter => {
-                if !self.input.is_empty() {
-                    // Generate a random name for quick session
-                    let existing = self.storage.existing_slugs().unwrap_or_default();
-                    let slug = generate_session_name(&existing, &self.config);
-
-                    let session = Session::new(&slug);
-                    if let Err(e) = self.storage.create_session(&session, Some(&self.input)) {
-                        self.set_error(format!("Failed to create session: {e}"));
-                    } else {
-                        let _ = self.refresh_sessions();
+                if let Some(template) = self.templates.get(self.template_cursor) {
+                    match std::fs::read_to_string(&template.path) {
+                        Ok(content) => {
+                            self.template_fill = Some(TemplateFill {
+                                name: template.name.clone(),
+                                variables: templates::variables(&content),
+                                content,
+                                values: Vec::new(),
+                            });
+                            self.input.clear();
+                            self.advance_template_fill();
+                        }
+                        Err(e) => {
+                            self.set_error(format!("Failed to read template: {e}"));
+                            self.mode = Mode::Normal;
+                        }
                     }
                 }
-                self.mode = Mode::Normal;
             }
-            KeyCode::Esc => {
+            KeyCode::Esc | KeyCode::Char('q') => {
                 self.mode = Mode::Normal;
             }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.template_cursor = self.template_cursor.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.template_cursor =
+                    (self.template_cursor + 1).min(self.templates.len().saturating_sub(1));
+            }
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    fn handle_pick_workspace_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Enter => {
+                self.mode = Mode::Normal;
+                match self.workspace_choices.get(self.workspace_cursor).cloned() {
+                    Some(choice) => {
+                        if self.current_workspace() != Some(self.workspace_cursor) {
+                            self.switch_workspace(choice);
+                        }
+                    }
+                    None if self.config.read_only => {
+                        self.set_error("Read-only mode: can't create a scratchpad".to_string());
+                    }
+                    None => self.mode = Mode::InitProject,
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('q') => self.mode = Mode::Normal,
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.workspace_cursor = self.workspace_cursor.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                let rows = self.workspace_choices.len() + usize::from(self.init_root.is_some());
+                self.workspace_cursor = (self.workspace_cursor + 1).min(rows.saturating_sub(1));
+            }
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    fn handle_init_project_key(&mut self, key: KeyEvent) -> Action {
+        let ignore = match key.code {
+            KeyCode::Char('1') => init::Ignore::Gitignore,
+            KeyCode::Char('2') => init::Ignore::Exclude,
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.mode = Mode::Normal;
+                return Action::Continue;
+            }
+            _ => return Action::Continue,
+        };
+        self.mode = Mode::Normal;
+        if let Some(root) = self.init_root.clone() {
+            self.init_project(&root, ignore);
+        }
+        Action::Continue
+    }
+
+    /// Create the repository's project scratchpad and switch to it
+    fn init_project(&mut self, root: &Path, ignore: init::Ignore) {
+        let (dir, done) = match init::init(root, ignore) {
+            Ok(result) => result,
+            Err(e) => {
+                self.set_error(format!("Failed to create the scratchpad: {e:#}"));
+                return;
+            }
+        };
+        let context = Context::Project(dir);
+        let user_path = self.workspace_choices.first().map_or_else(
+            || self.config.workspace_path.clone(),
+            |c| c.workspace_path.clone(),
+        );
+        let choice = WorkspaceChoice {
+            name: format!("project: {}", context.display_name()),
+            context: context.clone(),
+            workspace_path: user_path,
+        };
+        self.workspace_choices
+            .insert(self.available_contexts.len(), choice.clone());
+        self.available_contexts.push(context);
+        self.init_root = None;
+        self.switch_workspace(choice);
+        if let Some(warning) = done.into_iter().find(|line| line.starts_with("Warning")) {
+            self.set_error(warning);
+        }
+    }
+
+    /// Index of the open workspace among `workspace_choices`
+    pub fn current_workspace(&self) -> Option<usize> {
+        self.workspace_choices.iter().position(|choice| {
+            choice.context == self.context && choice.workspace_path == self.config.workspace_path
+        })
+    }
+
+    /// Name of the open `[workspaces]` entry, if one is open
+    pub fn named_workspace(&self) -> Option<&str> {
+        let i = self.current_workspace()?;
+        (i >= self.available_contexts.len()).then(|| self.workspace_choices[i].name.as_str())
+    }
+
+    /// Open another workspace in place, keeping the search filter
+    fn switch_workspace(&mut self, choice: WorkspaceChoice) {
+        self.config.workspace_path = choice.workspace_path;
+        self.context = choice.context;
+        self.storage = Storage::new(self.config.clone(), self.context.clone());
+        self.selected_index = 0;
+        self.list_offset = 0;
+        self.reload_workspace();
+    }
+
+    /// Drop what was cached about the previous workspace and load the current one
+    fn reload_workspace(&mut self) {
+        self.viewed = ViewedState::load(&self.storage.workspace_path());
+        self.marks = Marks::load(&self.storage.workspace_path());
+        self.sizes.clear();
+        self.sizes_checked_at = None;
+        self.sync_status = None;
+        self.sync_checked_at = None;
+        self.live = LiveWorker::spawn(&self.config, &self.context);
+        if let Err(e) = self.refresh_sessions() {
+            self.set_error(format!("Failed to load sessions: {e}"));
+        }
+    }
+
+    fn handle_pick_link_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Enter => {
+                if let Some(link) = self.meta.links.get(self.link_cursor).cloned() {
+                    self.mode = Mode::Normal;
+                    if !self.jump_to_session(&link.session) {
+                        self.set_error(format!("Session not found: {}", link.session));
+                    }
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.link_cursor = self.link_cursor.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.link_cursor =
+                    (self.link_cursor + 1).min(self.meta.links.len().saturating_sub(1));
+            }
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    /// List the headings of the notes, starting at the one the preview is scrolled to
+    fn open_outline(&mut self) {
+        let rendered: Vec<String> = self
+            .rendered_notes
+            .iter()
+            .flat_map(|text| &text.lines)
+            .map(|line| line.spans.iter().map(|s| s.content.as_ref()).collect())
+            .collect();
+        // Headings are looked for in the rendered preview in order, so one repeated is
+        // found where it is; the source line stands in for one the renderer changed
+        let mut from = 0;
+        let mut outline = Vec::new();
+        for heading in headings(&self.notes_content) {
+            let row = match rendered[from..]
+                .iter()
+                .position(|l| l.contains(&heading.text))
+            {
+                Some(i) => {
+                    from += i + 1;
+                    from - 1
+                }
+                None => heading.line,
+            };
+            outline.push(OutlineEntry {
+                level: heading.level,
+                text: heading.text,
+                row: row.min(u16::MAX as usize) as u16,
+            });
+        }
+        if outline.is_empty() || !self.session_files.is_empty() {
+            self.set_error("No headings in the notes".to_string());
+            return;
+        }
+        self.outline_cursor = outline
+            .iter()
+            .rposition(|entry| entry.row <= self.notes_scroll)
+            .unwrap_or(0);
+        self.outline = outline;
+        self.mode = Mode::Outline;
+    }
+
+    fn handle_outline_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Enter => {
+                if let Some(entry) = self.outline.get(self.outline_cursor) {
+                    let row = entry.row;
+                    self.set_detail_tab(DetailTab::Notes);
+                    self.notes_scroll = row;
+                }
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.outline_cursor = self.outline_cursor.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.outline_cursor =
+                    (self.outline_cursor + 1).min(self.outline.len().saturating_sub(1));
+            }
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    /// Select a session by exact slug, clearing the search filter if it hides it.
+    /// Returns false if there is no such session.
+    fn jump_to_session(&mut self, slug: &str) -> bool {
+        let filtered = |app: &Self| {
+            app.filtered_sessions
+                .iter()
+                .any(|&i| app.sessions.get(i).is_some_and(|s| s.slug == slug))
+        };
+        if !filtered(self) && !self.search_query.is_empty() {
+            self.search_query.clear();
+            self.apply_filter();
+        }
+        // Unfold its group if it's folded
+        if self.row_of(slug).is_none()
+            && let Some(session) = self.sessions.iter().find(|s| s.slug == slug)
+        {
+            let (_, label) = self.group_of(session, Local::now().date_naive());
+            self.collapsed_groups.remove(&label);
+            self.build_rows();
+        }
+        match self.row_of(slug) {
+            Some(i) => {
+                self.selected_index = i;
+                self.load_selected_notes();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Prompt for the next unset template variable, or for the session name once all are set
+    fn advance_template_fill(&mut self) {
+        self.mode = match self.template_fill.as_ref().and_then(TemplateFill::current) {
+            Some(_) => Mode::TemplateVar,
+            None => Mode::NewSession,
+        };
+    }
+
+    fn handle_template_var_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Enter => {
+                if let Some(fill) = self.template_fill.as_mut()
+                    && let Some(name) = fill.current().map(str::to_string)
+                {
+                    fill.values.push((name, std::mem::take(&mut self.input)));
+                }
+                self.advance_template_fill();
+            }
+            KeyCode::Esc => {
+                self.template_fill = None;
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+            }
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    fn handle_quick_session_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Enter => {
+                if !self.input.is_empty() {
+                    let note = self.input.clone();
+                    self.create_quick_session(&note);
+                }
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Esc => {
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+            }
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    /// Create a session with a generated name and `note` as its notes
+    fn create_quick_session(&mut self, note: &str) {
+        let existing = self.storage.existing_slugs().unwrap_or_default();
+        let slug = generate_session_name(&existing, &self.config);
+
+        let session = Session::new(&slug);
+        if let Err(e) = naming::check(&self.config, &slug) {
+            self.set_error(format!("{e:#}"));
+        } else if let Err(e) = self.storage.create_session(&session, Some(note)) {
+            self.set_error(format!("Failed to create session: {e}"));
+        } else {
+            let _ = self.refresh_sessions();
+            self.notify(notify::Event::SessionCreated { slug: &slug });
+        }
+    }
+
+    /// Selected note lines as an inclusive, ordered range
+    pub fn selected_lines(&self) -> (usize, usize) {
+        (
+            self.line_anchor.min(self.line_cursor),
+            self.line_anchor.max(self.line_cursor),
+        )
+    }
+
+    fn handle_select_lines_key(&mut self, key: KeyEvent) -> Action {
+        let last = self.notes_content.lines().count().saturating_sub(1);
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.line_cursor = self.line_cursor.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.line_cursor = (self.line_cursor + 1).min(last);
+            }
+            KeyCode::Enter => {
+                let (start, end) = self.selected_lines();
+                let text: Vec<&str> = self
+                    .notes_content
+                    .lines()
+                    .skip(start)
+                    .take(end - start + 1)
+                    .collect();
+                let text = text.join("\n");
+                if !text.trim().is_empty() {
+                    self.create_quick_session(&text);
+                }
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Esc | KeyCode::Char('V') => {
+                self.mode = Mode::Normal;
+            }
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    /// Counts, `gg`/`G` and marks, which take more than one key. None when `key` is
+    /// left to the normal bindings: it isn't one of them, or it ends a count or a lone
+    /// `g` without being a motion.
+    fn handle_prefix(&mut self, key: KeyEvent) -> Option<Action> {
+        match (self.prefix.take(), key.code) {
+            (Some(Prefix::Mark), KeyCode::Char(c)) if c.is_ascii_alphabetic() => {
+                self.set_mark(c);
+            }
+            (Some(Prefix::JumpMark), KeyCode::Char(c)) if c.is_ascii_alphabetic() => {
+                self.jump_to_mark(c);
+            }
+            // Anything else cancels
+            (Some(Prefix::Mark | Prefix::JumpMark), _) => {}
+            (Some(Prefix::G(_)), KeyCode::Char('g')) => self.select_row(0),
+            (Some(Prefix::G(_)), _) => {
+                self.switch_context();
+                if self.mode == Mode::PickWorkspace {
+                    return Some(self.handle_pick_workspace_key(key));
+                }
+                return self.handle_prefix(key);
+            }
+            (Some(Prefix::Count(count, undo)), KeyCode::Char(c @ '0'..='9')) => {
+                let count = count
+                    .saturating_mul(10)
+                    .saturating_add(c as usize - '0' as usize);
+                self.prefix = Some(Prefix::Count(count.min(100_000), undo));
+            }
+            (
+                Some(Prefix::Count(count, undo)),
+                code @ (KeyCode::Char('j' | 'k' | 'G') | KeyCode::Down | KeyCode::Up),
+            ) => {
+                if let Some((tab, scroll)) = undo {
+                    self.detail_tab = tab;
+                    self.notes_scroll = scroll;
+                }
+                let delta = count as isize;
+                match code {
+                    KeyCode::Char('G') => self.select_row(count.saturating_sub(1)),
+                    _ if self.focus == Focus::Detail => {
+                        let down = matches!(code, KeyCode::Char('j') | KeyCode::Down);
+                        self.move_detail_cursor(if down { delta } else { -delta });
+                    }
+                    KeyCode::Char('j') | KeyCode::Down => {
+                        self.select_row(self.selected_index.saturating_add(count));
+                    }
+                    _ => self.select_row(self.selected_index.saturating_sub(count)),
+                }
+            }
+            (Some(Prefix::Count(..)), _) => return self.handle_prefix(key),
+            (None, KeyCode::Char(c @ '1'..='9')) => {
+                // 1-4 switch tabs straight away, which a motion after them undoes
+                let undo = ('1'..='4').contains(&c).then(|| {
+                    let before = (self.detail_tab, self.notes_scroll);
+                    self.set_detail_tab(DetailTab::ALL[c as usize - '1' as usize]);
+                    before
+                });
+                self.prefix = Some(Prefix::Count(c as usize - '0' as usize, undo));
+            }
+            (None, KeyCode::Char('g')) => self.prefix = Some(Prefix::G(Instant::now())),
+            (None, KeyCode::Char('G')) => self.select_row(self.rows.len().saturating_sub(1)),
+            (None, KeyCode::Char('m')) => self.prefix = Some(Prefix::Mark),
+            (None, KeyCode::Char('\'')) => self.prefix = Some(Prefix::JumpMark),
+            _ => return None,
+        }
+        Some(Action::Continue)
+    }
+
+    /// Select the list row `index`, or the last one past the end
+    fn select_row(&mut self, index: usize) {
+        let index = index.min(self.rows.len().saturating_sub(1));
+        if index != self.selected_index {
+            self.selected_index = index;
+            self.load_selected_notes();
+        }
+    }
+
+    fn set_mark(&mut self, mark: char) {
+        let Some(slug) = self.selected_session().map(|s| s.slug.clone()) else {
+            self.set_error("Select a session to mark".to_string());
+            return;
+        };
+        if self.config.read_only {
+            self.set_error("Read-only mode".to_string());
+        } else if let Err(e) = self.marks.set(mark, &slug) {
+            self.set_error(format!("{e:#}"));
+        }
+    }
+
+    fn jump_to_mark(&mut self, mark: char) {
+        match self.marks.get(mark).map(str::to_string) {
+            None => self.set_error(format!("Mark '{mark}' isn't set")),
+            Some(slug) if !self.jump_to_session(&slug) => {
+                self.set_error(format!("Mark '{mark}': session not found: {slug}"));
+            }
+            Some(_) => {}
+        }
+    }
+
+    /// Cycle to the next available context (`g`)
+    /// Toggle between the user context and the project; with several projects (a
+    /// monorepo's and its packages'), pick one in the workspace switcher instead
+    fn switch_context(&mut self) {
+        if self.available_contexts.len() > 2 {
+            self.workspace_cursor = self.current_workspace().unwrap_or(0);
+            self.mode = Mode::PickWorkspace;
+        } else if self.available_contexts.len() > 1 {
+            let current_idx = self
+                .available_contexts
+                .iter()
+                .position(|c| c == &self.context)
+                .unwrap_or(0);
+            let next_idx = (current_idx + 1) % self.available_contexts.len();
+            self.context = self.available_contexts[next_idx].clone();
+            self.storage.switch_context(self.context.clone());
+            self.reload_workspace();
+        }
+    }
+
+    /// h/l scroll the unwrapped notes instead of switching tabs
+    fn scrolls_sideways(&self) -> bool {
+        !self.notes_wrap && self.focus == Focus::Detail && self.detail_tab == DetailTab::Notes
+    }
+
+    fn set_detail_tab(&mut self, tab: DetailTab) {
+        self.detail_tab = tab;
+        self.notes_scroll = 0;
+        self.notes_hscroll = 0;
+    }
+
+    /// j/k inside the detail pane: scroll text tabs, move the cursor in list tabs
+    fn move_detail_cursor(&mut self, delta: isize) {
+        let step = |cursor: usize, len: usize| {
+            cursor
+                .saturating_add_signed(delta)
+                .min(len.saturating_sub(1))
+        };
+        match self.detail_tab {
+            DetailTab::Notes | DetailTab::Runs => {
+                self.notes_scroll = self.notes_scroll.saturating_add_signed(delta as i16);
+            }
+            DetailTab::Files => {
+                self.file_cursor = step(self.file_cursor, self.file_tree.len());
+            }
+            DetailTab::Meta => {
+                self.meta_cursor = step(self.meta_cursor, MetaField::ALL.len());
+            }
+        }
+    }
+
+    /// Enter inside the detail pane: open the selected file or edit the selected field
+    fn activate_detail_item(&mut self) -> Action {
+        match self.detail_tab {
+            DetailTab::Files => match self.file_tree.get(self.file_cursor) {
+                Some(entry) if entry.is_dir => Action::OpenFolder(entry.path.clone()),
+                Some(entry) => Action::ViewExternal(entry.path.clone()),
+                None => Action::Continue,
+            },
+            DetailTab::Meta => {
+                if self.selected_session().is_some() {
+                    let field = MetaField::ALL[self.meta_cursor];
+                    self.input = field.value(&self.meta);
+                    self.mode = Mode::EditMeta(field);
+                }
+                Action::Continue
+            }
+            DetailTab::Notes | DetailTab::Runs => Action::Continue,
+        }
+    }
+
+    fn handle_edit_meta_key(&mut self, field: MetaField, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Enter => {
+                if let Some(slug) = self.selected_session().map(|s| s.slug.clone()) {
+                    let mut meta = self.meta.clone();
+                    let result = field
+                        .apply(&mut meta, &self.input)
+                        .and_then(|()| self.storage.save_meta(&slug, &meta));
+                    match result {
+                        Ok(()) => {
+                            self.meta = meta;
+                            self.update_cached_meta(&slug);
+                        }
+                        Err(e) => self.set_error(format!("{e:#}")),
+                    }
+                }
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Esc => {
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+            }
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    /// Destination sessions for a file transfer: all but the selected one, filtered by `input`
+    pub fn picker_candidates(&self) -> Vec<&Session> {
+        let current = self.selected_session().map(|s| s.slug.as_str());
+        let query = self.input.to_lowercase();
+        self.sessions
+            .iter()
+            .filter(|s| Some(s.slug.as_str()) != current)
+            .filter(|s| {
+                query.is_empty()
+                    || s.slug.contains(&query)
+                    || s.display_title().to_lowercase().contains(&query)
+            })
+            .collect()
+    }
+
+    fn handle_pick_session_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Enter => {
+                let dest = self
+                    .picker_candidates()
+                    .get(self.picker_cursor)
+                    .map(|s| s.slug.clone());
+                if let (Some(dest), Some((path, is_move))) = (dest, self.transfer.take()) {
+                    match self.storage.transfer_file(&path, &dest, is_move) {
+                        Ok(_) => {
+                            if let Err(e) = self.refresh_sessions() {
+                                self.set_error(format!("Failed to refresh: {e}"));
+                            }
+                        }
+                        Err(e) => self.set_error(format!("{e:#}")),
+                    }
+                }
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Esc => {
+                self.transfer = None;
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Up => {
+                self.picker_cursor = self.picker_cursor.saturating_sub(1);
+            }
+            KeyCode::Down => {
+                let len = self.picker_candidates().len();
+                self.picker_cursor = (self.picker_cursor + 1).min(len.saturating_sub(1));
+            }
             KeyCode::Backspace => {
                 self.input.pop();
+                self.picker_cursor = 0;
             }
             KeyCode::Char(c) => {
                 self.input.push(c);
+                self.picker_cursor = 0;
+            }
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    /// Rescan the workspace for open items, keeping the cursor in range
+    pub fn refresh_todos(&mut self) {
+        self.todos = todos::workspace_todos(&self.storage.workspace_path());
+        self.todo_cursor = self.todo_cursor.min(self.todos.len().saturating_sub(1));
+    }
+
+    fn handle_todos_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('T') => {
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.todo_cursor = self.todo_cursor.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.todo_cursor = (self.todo_cursor + 1).min(self.todos.len().saturating_sub(1));
+            }
+            // Jump to source: select the session and open the file at the item's line
+            KeyCode::Enter => {
+                if let Some(item) = self.todos.get(self.todo_cursor).cloned() {
+                    if !self.search_query.is_empty() {
+                        self.search_query.clear();
+                        self.apply_filter();
+                    }
+                    self.select_session_by_name(&item.slug);
+                    let path = self.storage.session_dir(&item.slug).join(&item.path);
+                    if self.config.read_only {
+                        return Action::ViewExternal(path);
+                    }
+                    return Action::EditExternal(path, Some(item.line));
+                }
+            }
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    /// Number of sessions last updated on each local day
+    pub fn sessions_per_day(&self) -> HashMap<NaiveDate, usize> {
+        let mut counts = HashMap::new();
+        for session in &self.sessions {
+            *counts.entry(local_day(session.updated_at)).or_default() += 1;
+        }
+        counts
+    }
+
+    /// Sessions last updated on `day`, most recent first
+    pub fn sessions_on(&self, day: NaiveDate) -> Vec<&Session> {
+        self.sessions
+            .iter()
+            .filter(|s| local_day(s.updated_at) == day)
+            .collect()
+    }
+
+    fn handle_timeline_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('C') => {
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Left => self.move_timeline_day(-7),
+            KeyCode::Right => self.move_timeline_day(7),
+            KeyCode::Up => self.move_timeline_day(-1),
+            KeyCode::Down => self.move_timeline_day(1),
+            KeyCode::Char('k') => {
+                self.timeline_cursor = self.timeline_cursor.saturating_sub(1);
+            }
+            KeyCode::Char('j') => {
+                let len = self.sessions_on(self.timeline_day).len();
+                self.timeline_cursor = (self.timeline_cursor + 1).min(len.saturating_sub(1));
+            }
+            KeyCode::Enter => {
+                let slug = self
+                    .sessions_on(self.timeline_day)
+                    .get(self.timeline_cursor)
+                    .map(|s| s.slug.clone());
+                if let Some(slug) = slug {
+                    if !self.search_query.is_empty() {
+                        self.search_query.clear();
+                        self.apply_filter();
+                    }
+                    self.select_session_by_name(&slug);
+                    self.mode = Mode::Normal;
+                }
+            }
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    /// Move the timeline cursor by `days`, never past today
+    fn move_timeline_day(&mut self, days: i64) {
+        let today = Local::now().date_naive();
+        self.timeline_day = (self.timeline_day + chrono::Duration::days(days)).min(today);
+        self.timeline_cursor = 0;
+    }
+
+    /// Sessions with `status`, most recently updated first
+    pub fn board_sessions(&self, status: Status) -> Vec<&Session> {
+        self.sessions
+            .iter()
+            .filter(|s| self.statuses.get(&s.slug).copied().unwrap_or_default() == status)
+            .collect()
+    }
+
+    fn handle_board_key(&mut self, key: KeyEvent) -> Action {
+        let column = Status::ALL[self.board_column];
+        let selected = self
+            .board_sessions(column)
+            .get(self.board_cursor)
+            .map(|s| s.slug.clone());
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('B') => {
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Left => {
+                self.board_column = self.board_column.saturating_sub(1);
+                self.board_cursor = 0;
+            }
+            KeyCode::Right => {
+                self.board_column = (self.board_column + 1).min(Status::ALL.len() - 1);
+                self.board_cursor = 0;
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.board_cursor = self.board_cursor.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                let len = self.board_sessions(column).len();
+                self.board_cursor = (self.board_cursor + 1).min(len.saturating_sub(1));
+            }
+            KeyCode::Char(c @ ('h' | 'l')) => {
+                let Some(slug) = selected else {
+                    return Action::Continue;
+                };
+                if self.config.read_only {
+                    self.set_error("Read-only mode".to_string());
+                    return Action::Continue;
+                }
+                let target = if c == 'h' {
+                    self.board_column.checked_sub(1)
+                } else {
+                    Some(self.board_column + 1).filter(|&i| i < Status::ALL.len())
+                };
+                if let Some(target) = target {
+                    self.move_to_status(&slug, Status::ALL[target]);
+                }
+            }
+            KeyCode::Enter => {
+                if let Some(slug) = selected {
+                    self.mode = Mode::Normal;
+                    self.jump_to_session(&slug);
+                }
+            }
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    /// Save a new status and keep the board selection on the moved session
+    fn move_to_status(&mut self, slug: &str, status: Status) {
+        let result = self.storage.load_meta(slug).and_then(|mut meta| {
+            meta.status = status;
+            self.storage.save_meta(slug, &meta)
+        });
+        if let Err(e) = result {
+            self.set_error(format!("{e:#}"));
+            return;
+        }
+        self.statuses.insert(slug.to_string(), status);
+        if self.selected_session().is_some_and(|s| s.slug == slug) {
+            self.meta.status = status;
+        }
+        self.board_column = status.index();
+        self.board_cursor = self
+            .board_sessions(status)
+            .iter()
+            .position(|s| s.slug == slug)
+            .unwrap_or(0);
+    }
+
+    fn handle_resolve_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.resolving = None;
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.resolve_scroll = self.resolve_scroll.saturating_add(1);
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                self.resolve_scroll = self.resolve_scroll.saturating_sub(1);
+            }
+            KeyCode::Char('l') => self.resolve_conflict(Resolution::Local),
+            KeyCode::Char('r') => self.resolve_conflict(Resolution::Remote),
+            KeyCode::Char('m') => self.resolve_conflict(Resolution::Merged),
+            KeyCode::Char('e') => {
+                // Edit the merge, then come back to take it
+                if let Some((conflict, _)) = &self.resolving
+                    && conflict.merged
+                {
+                    let path = self.storage.workspace_path().join(&conflict.saved);
+                    self.resolving = None;
+                    self.mode = Mode::Normal;
+                    return Action::EditExternal(path, None);
+                }
+                self.set_error("Only merges can be edited".to_string());
             }
             _ => {}
         }
@@ -457,8 +2619,617 @@ impl App {
     }
 }
 
+pub fn local_day(time: DateTime<Utc>) -> NaiveDate {
+    time.with_timezone(&Local).date_naive()
+}
+
 fn calculate_hash(content: &str) -> u64 {
     let mut hasher = DefaultHasher::new();
     content.hash(&mut hasher);
     hasher.finish()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::WorktreeMeta;
+    use crossterm::event::KeyModifiers;
+
+    fn test_app(slugs: &[&str]) -> (tempfile::TempDir, App) {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            workspace_path: dir.path().to_string_lossy().to_string(),
+            // Keep sessions created by tests out of the real usage.json
+            usage_stats: false,
+            ..Config::default()
+        };
+        let storage = Storage::new(config.clone(), Context::User);
+        for slug in slugs {
+            storage.create_session(&Session::new(*slug), None).unwrap();
+        }
+        let mut app = App::new(storage, config, Context::User, vec![Context::User]);
+        app.refresh_sessions().unwrap();
+        (dir, app)
+    }
+
+    fn type_str(app: &mut App, text: &str) {
+        for c in text.chars() {
+            app.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+    }
+
+    /// A lone `g`, once it's clear no second one follows
+    fn lone_g(app: &mut App) {
+        type_str(app, "g");
+        app.prefix = Some(Prefix::G(Instant::now() - KEY_TIMEOUT));
+        app.tick();
+    }
+
+    /// Tick until the size worker has reported back
+    fn wait_for_sizes(app: &mut App) {
+        let start = Instant::now();
+        while app.size_worker.is_busy() && start.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(5));
+            app.tick();
+        }
+    }
+
+    #[test]
+    fn sizes_catch_up_with_nested_edits() {
+        let (dir, mut app) = test_app(&["alpha"]);
+        let nested = dir.path().join("alpha/data/raw");
+        std::fs::create_dir_all(&nested).unwrap();
+        app.refresh_sessions().unwrap();
+        wait_for_sizes(&mut app);
+        let before = app.session_size("alpha").unwrap();
+
+        // A file deep in the session leaves the session's mtime as it was
+        std::fs::write(nested.join("dump.csv"), vec![b'x'; 1000]).unwrap();
+        app.refresh_sessions().unwrap();
+        wait_for_sizes(&mut app);
+        assert_eq!(app.session_size("alpha"), Some(before));
+
+        app.sizes_checked_at = Some(Instant::now() - SIZE_CHECK_INTERVAL);
+        app.tick();
+        wait_for_sizes(&mut app);
+        assert_eq!(app.session_size("alpha"), Some(before + 1000));
+    }
+
+    #[test]
+    fn edits_to_sessions_behind_the_remote_are_staged() {
+        let (dir, mut app) = test_app(&["alpha", "beta"]);
+        let notes = dir.path().join("alpha/notes.md");
+        std::fs::write(&notes, "mine").unwrap();
+        app.sync_status = Some(sync::status::Status {
+            unapplied: ["alpha".to_string()].into(),
+            ..Default::default()
+        });
+
+        let staged = app.stage_edit(&notes).unwrap();
+        assert_ne!(staged, notes);
+        assert_eq!(std::fs::read_to_string(&staged).unwrap(), "mine");
+        assert!(app.notice.as_deref().unwrap().contains("staged"));
+        let listed = sync::staging::list(dir.path());
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].path, "alpha/notes.md");
+
+        let other = dir.path().join("beta/notes.md");
+        assert_eq!(app.stage_edit(&other).unwrap(), other);
+    }
+
+    #[test]
+    fn template_prompts_for_each_variable() {
+        let (dir, mut app) = test_app(&[]);
+        let templates_dir = dir.path().join(templates::TEMPLATES_DIR);
+        std::fs::create_dir_all(&templates_dir).unwrap();
+        std::fs::write(
+            templates_dir.join("brief.md"),
+            "# {{ticket}}\n\nGoal: {{goal}}\n",
+        )
+        .unwrap();
+
+        type_str(&mut app, "t");
+        assert_eq!(app.mode, Mode::PickTemplate);
+        app.template_cursor = app
+            .templates
+            .iter()
+            .position(|t| t.name == "brief")
+            .unwrap();
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.mode, Mode::TemplateVar);
+        assert_eq!(
+            app.template_fill.as_ref().unwrap().current(),
+            Some("ticket")
+        );
+
+        type_str(&mut app, "ABC-1");
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        type_str(&mut app, "ship it");
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.mode, Mode::NewSession);
+
+        type_str(&mut app, "abc");
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(
+            app.storage.read_notes("abc").unwrap(),
+            "# ABC-1\n\nGoal: ship it\n"
+        );
+        assert!(app.template_fill.is_none());
+    }
+
+    #[test]
+    fn selected_lines_become_a_quick_session() {
+        let (_dir, mut app) = test_app(&["brainstorm"]);
+        app.config.name_generator = "static".to_string();
+        app.storage
+            .write_notes("brainstorm", "# Ideas\none\ntwo\nthree\n")
+            .unwrap();
+        app.load_selected_notes();
+        app.focus = Focus::Detail;
+
+        type_str(&mut app, "VjjjkV");
+        assert_eq!(app.mode, Mode::Normal);
+        type_str(&mut app, "Vjjjk");
+        assert_eq!(app.selected_lines(), (0, 2));
+        app.line_anchor = 1;
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+
+        assert_eq!(app.sessions.len(), 2);
+        let new = app
+            .sessions
+            .iter()
+            .find(|s| s.slug != "brainstorm")
+            .unwrap();
+        assert_eq!(app.storage.read_notes(&new.slug).unwrap(), "one\ntwo");
+    }
+
+    #[test]
+    fn read_only_refuses_mutating_keys() {
+        let (_dir, mut app) = test_app(&["alpha"]);
+        app.config.read_only = true;
+
+        type_str(&mut app, "n");
+        assert_eq!(app.mode, Mode::Normal);
+        assert!(app.error_message.is_some());
+        assert!(matches!(
+            app.handle_key(KeyEvent::new(KeyCode::Char('e'), KeyModifiers::NONE)),
+            Action::Continue
+        ));
+        type_str(&mut app, "/al");
+        assert_eq!(app.mode, Mode::Search);
+    }
+
+    #[test]
+    fn live_search_refines_and_keeps_selection() {
+        let (_dir, mut app) = test_app(&["alpha-one", "alpha-two", "beta"]);
+        app.select_session_by_name("alpha-two");
+
+        type_str(&mut app, "/al");
+        app.apply_live_search();
+        assert_eq!(app.filtered_sessions.len(), 2);
+        assert_eq!(app.selected_session().unwrap().slug, "alpha-two");
+
+        type_str(&mut app, "pha-t");
+        app.apply_live_search();
+        assert_eq!(app.filtered_sessions.len(), 1);
+        assert_eq!(app.selected_session().unwrap().slug, "alpha-two");
+
+        // Esc restores the query that was active before editing
+        app.handle_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert_eq!(app.mode, Mode::Normal);
+        assert_eq!(app.filtered_sessions.len(), 3);
+    }
+
+    #[test]
+    fn meta_tab_edits_due_date() {
+        let (_dir, mut app) = test_app(&["plan"]);
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+
+        type_str(&mut app, "4");
+        assert_eq!(app.detail_tab, DetailTab::Meta);
+        app.handle_key(key(KeyCode::Tab));
+        app.handle_key(key(KeyCode::Enter));
+        assert_eq!(app.mode, Mode::EditMeta(MetaField::Due));
+
+        type_str(&mut app, "2030-01-02");
+        app.handle_key(key(KeyCode::Enter));
+        assert_eq!(app.mode, Mode::Normal);
+        let due = NaiveDate::from_ymd_opt(2030, 1, 2);
+        assert_eq!(app.storage.load_meta("plan").unwrap().due, due);
+        assert_eq!(app.due_dates.get("plan").copied(), due);
+
+        // h wraps around to the last tab from the first
+        type_str(&mut app, "1h");
+        assert_eq!(app.detail_tab, DetailTab::Meta);
+    }
+
+    #[test]
+    fn files_tab_moves_file_to_picked_session() {
+        let (_dir, mut app) = test_app(&["source", "target"]);
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        app.select_session_by_name("source");
+        let src = app.storage.session_dir("source").join("draft.md");
+        std::fs::write(&src, "x").unwrap();
+        app.refresh_sessions().unwrap();
+
+        type_str(&mut app, "2");
+        app.handle_key(key(KeyCode::Tab));
+        app.file_cursor = app.file_tree.iter().position(|e| e.path == src).unwrap();
+        type_str(&mut app, "x");
+        assert_eq!(app.mode, Mode::PickSession);
+
+        type_str(&mut app, "tar");
+        assert_eq!(app.picker_candidates().len(), 1);
+        app.handle_key(key(KeyCode::Enter));
+        assert!(!src.exists());
+        assert!(app.storage.session_dir("target").join("draft.md").exists());
+    }
+
+    #[test]
+    fn todo_view_jumps_to_source_line() {
+        let (_dir, mut app) = test_app(&["alpha", "beta"]);
+        let notes = app.storage.session_dir("beta").join("notes.md");
+        std::fs::write(&notes, "# Beta\n\n- [ ] follow up\n").unwrap();
+
+        type_str(&mut app, "T");
+        assert_eq!(app.mode, Mode::Todos);
+        assert_eq!(app.todos.len(), 1);
+
+        let action = app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(matches!(action, Action::EditExternal(path, Some(3)) if path == notes));
+        assert_eq!(app.selected_session().unwrap().slug, "beta");
+    }
+
+    #[test]
+    fn timeline_groups_by_day_and_jumps_to_session() {
+        let (_dir, mut app) = test_app(&["alpha", "beta"]);
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        let today = Local::now().date_naive();
+
+        type_str(&mut app, "C");
+        assert_eq!(app.mode, Mode::Timeline);
+        assert_eq!(app.timeline_day, today);
+        assert_eq!(app.sessions_on(today).len(), 2);
+
+        // Can't move into the future; moving back a week leaves an empty day
+        app.handle_key(key(KeyCode::Right));
+        assert_eq!(app.timeline_day, today);
+        app.handle_key(key(KeyCode::Left));
+        assert!(app.sessions_on(app.timeline_day).is_empty());
+        app.handle_key(key(KeyCode::Right));
+
+        type_str(&mut app, "j");
+        let expected = app.sessions_on(today)[1].slug.clone();
+        app.handle_key(key(KeyCode::Enter));
+        assert_eq!(app.mode, Mode::Normal);
+        assert_eq!(app.selected_session().unwrap().slug, expected);
+    }
+
+    #[test]
+    fn board_moves_sessions_between_columns() {
+        let (_dir, mut app) = test_app(&["alpha", "beta"]);
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+
+        type_str(&mut app, "B");
+        assert_eq!(app.mode, Mode::Board);
+        assert_eq!(app.board_sessions(Status::Inbox).len(), 2);
+
+        let moved = app.board_sessions(Status::Inbox)[0].slug.clone();
+        type_str(&mut app, "ll");
+        assert_eq!(app.board_column, Status::Blocked.index());
+        assert_eq!(
+            app.storage.load_meta(&moved).unwrap().status,
+            Status::Blocked
+        );
+        type_str(&mut app, "h");
+        assert_eq!(app.board_sessions(Status::Active)[0].slug, moved);
+
+        app.handle_key(key(KeyCode::Left));
+        assert_eq!(app.board_sessions(Status::Inbox).len(), 1);
+        app.handle_key(key(KeyCode::Enter));
+        assert_eq!(app.mode, Mode::Normal);
+        assert_ne!(app.selected_session().unwrap().slug, moved);
+    }
+
+    #[test]
+    fn snoozes_and_completes_due_reminders() {
+        let (_dir, mut app) = test_app(&["experiment", "other"]);
+        let mut meta = app.storage.load_meta("experiment").unwrap();
+        meta.reminders.push(Reminder {
+            at: Utc::now() - chrono::Duration::hours(1),
+            message: "check agent results".to_string(),
+        });
+        app.storage.save_meta("experiment", &meta).unwrap();
+        app.refresh_sessions().unwrap();
+        assert_eq!(app.due_reminders(Utc::now()).len(), 1);
+
+        app.select_session_by_name("experiment");
+        type_str(&mut app, "z");
+        assert!(app.due_reminders(Utc::now()).is_empty());
+        assert_eq!(
+            app.storage.load_meta("experiment").unwrap().reminders.len(),
+            1
+        );
+
+        let tomorrow = Utc::now() + chrono::Duration::days(2);
+        assert_eq!(app.due_reminders(tomorrow).len(), 1);
+        meta.reminders[0].at = Utc::now() - chrono::Duration::minutes(1);
+        app.storage.save_meta("experiment", &meta).unwrap();
+        type_str(&mut app, "D");
+        assert!(
+            app.storage
+                .load_meta("experiment")
+                .unwrap()
+                .reminders
+                .is_empty()
+        );
+        assert!(app.reminders.is_empty());
+    }
+
+    #[test]
+    fn opens_notes_next_to_the_sessions_worktree() {
+        let (_dir, mut app) = test_app(&["alpha"]);
+        app.select_session_by_name("alpha");
+        let key = KeyEvent::new(KeyCode::Char('E'), KeyModifiers::NONE);
+        assert!(matches!(app.handle_key(key), Action::Continue));
+        assert!(app.error_message.is_some());
+
+        let session = app.storage.session_dir("alpha");
+        std::fs::create_dir_all(session.join("repo-wt")).unwrap();
+        let mut meta = app.storage.load_meta("alpha").unwrap();
+        meta.worktrees.push(WorktreeMeta {
+            repo: PathBuf::from("/src/repo"),
+            path: PathBuf::from("repo-wt"),
+            branch: "sp/alpha".to_string(),
+        });
+        app.storage.save_meta("alpha", &meta).unwrap();
+        let notes = app.storage.find_entry_point("alpha").unwrap();
+        assert!(matches!(
+            app.handle_key(key),
+            Action::EditWithProject(path, root) if path == notes && root == session.join("repo-wt")
+        ));
+    }
+
+    #[test]
+    fn previews_a_file_until_another_session_is_selected() {
+        let (_dir, mut app) = test_app(&["alpha", "beta"]);
+        app.select_session_by_name("alpha");
+        let plan = app.storage.session_dir("alpha").join("plan.md");
+        std::fs::write(&plan, "# Plan\n\n- step one\n").unwrap();
+        app.load_selected_notes();
+        app.preview_file(&plan);
+        assert!(app.notes_content.contains("step one"));
+        assert_eq!(app.file_tree[app.file_cursor].path, plan);
+        let key = KeyEvent::new(KeyCode::Char('e'), KeyModifiers::NONE);
+        assert!(matches!(app.handle_key(key), Action::EditExternal(path, None) if path == plan));
+
+        app.select_session_by_name("beta");
+        assert_eq!(app.previewed_file, None);
+        assert!(!app.notes_content.contains("step one"));
+    }
+
+    #[test]
+    fn dashboard_shows_until_a_session_is_selected() {
+        let (_dir, mut app) = test_app(&["alpha", "beta"]);
+        assert!(!app.show_dashboard());
+
+        std::fs::write(app.storage.dashboard_path(), "# Dashboard\n\n- ship v2\n").unwrap();
+        assert!(app.show_dashboard());
+        assert!(app.notes_content.contains("ship v2"));
+        assert!(matches!(
+            app.handle_key(KeyEvent::new(KeyCode::Char('e'), KeyModifiers::NONE)),
+            Action::EditExternal(path, None) if path == app.storage.dashboard_path()
+        ));
+
+        type_str(&mut app, "j");
+        assert!(!app.dashboard);
+        assert!(!app.notes_content.contains("ship v2"));
+        type_str(&mut app, "H");
+        assert!(app.dashboard);
+    }
+
+    #[test]
+    fn unwrapped_notes_scroll_sideways() {
+        let (_dir, mut app) = test_app(&["alpha"]);
+        type_str(&mut app, "l");
+        assert_eq!(app.detail_tab, DetailTab::Files);
+        type_str(&mut app, "h");
+
+        // Only with the detail pane focused and wrapping off
+        app.handle_key(KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE));
+        type_str(&mut app, "wll");
+        assert_eq!(app.detail_tab, DetailTab::Notes);
+        assert_eq!(app.notes_hscroll, 2 * HSCROLL_STEP);
+        type_str(&mut app, "h");
+        assert_eq!(app.notes_hscroll, HSCROLL_STEP);
+
+        type_str(&mut app, "w");
+        assert!(app.notes_wrap);
+        assert_eq!(app.notes_hscroll, 0);
+        type_str(&mut app, "l");
+        assert_eq!(app.detail_tab, DetailTab::Files);
+    }
+
+    #[test]
+    fn outline_scrolls_the_preview_to_a_heading() {
+        let (_dir, mut app) = test_app(&["alpha"]);
+        let notes =
+            "# Plan\n\nintro\n\n```\n# not a heading\n```\n\n## Steps\n\n- one\n\n## Plan\n";
+        app.storage.write_notes("alpha", notes).unwrap();
+        app.load_selected_notes();
+        app.ensure_rendered_notes(80);
+
+        type_str(&mut app, "O");
+        assert_eq!(app.mode, Mode::Outline);
+        let texts: Vec<&str> = app.outline.iter().map(|e| e.text.as_str()).collect();
+        assert_eq!(texts, ["Plan", "Steps", "Plan"]);
+        assert_eq!(app.outline_cursor, 0);
+        type_str(&mut app, "jj");
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.mode, Mode::Normal);
+        // The second "Plan", below the steps
+        assert!(app.notes_scroll > app.outline[1].row);
+
+        type_str(&mut app, "O");
+        assert_eq!(app.outline_cursor, 2);
+    }
+
+    #[test]
+    fn counts_and_marks_move_through_the_list() {
+        let slugs: Vec<String> = (0..12).map(|i| format!("s{i:02}")).collect();
+        let slugs: Vec<&str> = slugs.iter().map(String::as_str).collect();
+        let (_dir, mut app) = test_app(&slugs);
+        let selected = |app: &App| app.selected_session().unwrap().slug.clone();
+        let first = selected(&app);
+
+        type_str(&mut app, "G");
+        assert_eq!(app.selected_index, 11);
+        type_str(&mut app, "gg");
+        assert_eq!(selected(&app), first);
+        type_str(&mut app, "10j");
+        assert_eq!(app.selected_index, 10);
+        assert_eq!(app.detail_tab, DetailTab::Notes);
+        // 2 switched to Files before turning out to be a count
+        type_str(&mut app, "2k");
+        assert_eq!(app.selected_index, 8);
+        assert_eq!(app.detail_tab, DetailTab::Notes);
+        type_str(&mut app, "3G");
+        assert_eq!(app.selected_index, 2);
+
+        let marked = selected(&app);
+        type_str(&mut app, "magg");
+        assert_ne!(selected(&app), marked);
+        type_str(&mut app, "'a");
+        assert_eq!(selected(&app), marked);
+        type_str(&mut app, "'b");
+        assert!(app.error_message.is_some());
+
+        // Marks outlive the TUI
+        let mut app = App::new(app.storage, app.config, Context::User, vec![Context::User]);
+        app.refresh_sessions().unwrap();
+        type_str(&mut app, "'a");
+        assert_eq!(selected(&app), marked);
+    }
+
+    #[test]
+    fn jumps_to_linked_session() {
+        let (_dir, mut app) = test_app(&["origin", "fork", "fork-2"]);
+        app.storage
+            .link_sessions("fork", "origin", crate::models::Relation::Parent)
+            .unwrap();
+        app.jump_to_session("fork");
+        assert_eq!(app.meta.links.len(), 1);
+
+        // A search hiding the target is cleared on jump
+        app.search_query = "fork".to_string();
+        app.apply_filter();
+        type_str(&mut app, "L");
+        assert_eq!(app.mode, Mode::PickLink);
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.mode, Mode::Normal);
+        assert_eq!(app.selected_session().unwrap().slug, "origin");
+        assert!(app.search_query.is_empty());
+
+        type_str(&mut app, "L");
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.selected_session().unwrap().slug, "fork");
+    }
+
+    #[test]
+    fn groups_sessions_by_tag_and_folds_them() {
+        let (_dir, mut app) = test_app(&["api", "garden", "infra"]);
+        for (slug, tag) in [("api", "work"), ("infra", "work")] {
+            let mut meta = app.storage.load_meta(slug).unwrap();
+            meta.tags = vec![tag.to_string()];
+            app.storage.save_meta(slug, &meta).unwrap();
+        }
+        app.refresh_sessions().unwrap();
+        type_str(&mut app, "FF");
+        assert_eq!(app.group_by, GroupBy::Tag);
+        let headers: Vec<(&str, usize)> = app
+            .rows
+            .iter()
+            .filter_map(|row| match row {
+                ListRow::Group { label, count, .. } => Some((label.as_str(), *count)),
+                ListRow::Session(_) => None,
+            })
+            .collect();
+        assert_eq!(headers, [("work", 2), ("Untagged", 1)]);
+        assert_eq!(app.rows.len(), 5);
+
+        app.select_session_by_name("infra");
+        type_str(&mut app, "f");
+        assert_eq!(app.rows.len(), 3);
+        assert!(app.selected_session().is_none());
+        type_str(&mut app, "jj");
+        assert_eq!(app.selected_session().unwrap().slug, "garden");
+
+        // Jumping to a folded session unfolds its group
+        assert!(app.jump_to_session("api"));
+        assert_eq!(app.rows.len(), 5);
+        type_str(&mut app, "F");
+        assert_eq!(app.rows.len(), 3);
+        assert_eq!(app.selected_session().unwrap().slug, "api");
+    }
+
+    #[test]
+    fn switches_workspace_keeping_the_search() {
+        let (_dir, mut app) = test_app(&["api-notes", "home"]);
+        let other = tempfile::tempdir().unwrap();
+        let other_config = Config {
+            workspace_path: other.path().to_string_lossy().to_string(),
+            ..Config::default()
+        };
+        let storage = Storage::new(other_config, Context::User);
+        for slug in ["api-design", "garden"] {
+            storage.create_session(&Session::new(slug), None).unwrap();
+        }
+        app.config.workspaces.insert(
+            "work".to_string(),
+            other.path().to_string_lossy().to_string(),
+        );
+        app.workspace_choices = workspace_choices(&app.config, &app.available_contexts);
+
+        type_str(&mut app, "/api");
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.filtered_sessions.len(), 1);
+
+        type_str(&mut app, "W");
+        assert_eq!(app.mode, Mode::PickWorkspace);
+        type_str(&mut app, "j");
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.named_workspace(), Some("work"));
+        assert_eq!(app.storage.workspace_path(), other.path());
+        assert_eq!(app.search_query, "api");
+        assert_eq!(app.selected_session().unwrap().slug, "api-design");
+    }
+
+    #[test]
+    fn creates_the_project_scratchpad_and_switches_to_it() {
+        let (_dir, mut app) = test_app(&["home"]);
+        let repo = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(repo.path().join(".git/info")).unwrap();
+        app.init_root = Some(repo.path().to_path_buf());
+
+        type_str(&mut app, "Wj");
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.mode, Mode::InitProject);
+        type_str(&mut app, "2");
+        assert_eq!(
+            app.context,
+            Context::Project(repo.path().join(".scratchpad"))
+        );
+        assert!(app.sessions.is_empty());
+        assert!(app.init_root.is_none());
+        assert_eq!(
+            std::fs::read_to_string(repo.path().join(".git/info/exclude")).unwrap(),
+            ".scratchpad/\n"
+        );
+
+        // Back to the user workspace with g, and to the project again
+        lone_g(&mut app);
+        assert_eq!(app.sessions[0].slug, "home");
+        lone_g(&mut app);
+        assert!(app.sessions.is_empty());
+    }
+}
@@ -0,0 +1,65 @@
+//! Remote changes pulled while the TUI is open
+//!
+//! With a `[server]`, a worker subscribes to the workspace on the server's WebSocket,
+//! like `sp sync --watch`, and pulls whenever another device's ops are announced, so the
+//! list and preview follow edits made elsewhere. Nothing is pushed: local changes wait
+//! for the next `sp sync`. The event loop polls the worker like the others.
+
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use crate::models::{Config, Context, SyncBackend};
+use crate::storage::Storage;
+use crate::sync::{self, Report, device::Device};
+
+pub struct LiveWorker {
+    results: Receiver<Report>,
+}
+
+impl LiveWorker {
+    /// None without a server or with another backend, in read-only mode, or when the
+    /// context isn't synced
+    pub fn spawn(config: &Config, context: &Context) -> Option<Self> {
+        let server = config.server.clone()?;
+        let backend = config.sync.as_ref().and_then(|s| s.backend);
+        if backend.is_some_and(|b| b != SyncBackend::Server)
+            || config.read_only
+            || !sync::allowed(config.sync.as_ref(), context).unwrap_or(false)
+        {
+            return None;
+        }
+        let device = Device::load(config.sync.as_ref()).ok()?;
+        let storage = Storage::new(config.clone(), context.clone());
+        if !storage.workspace_path().exists() {
+            return None;
+        }
+        let (announced, _) =
+            sync::watch::announcements(&server, &sync::workspace_id(&server, context), &device);
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            while announced.recv().is_ok() {
+                while announced.try_recv().is_ok() {}
+                // Failures show up in the sync status; the next announcement retries
+                let Ok(report) = sync::pull(&storage, &server, &device) else {
+                    continue;
+                };
+                if tx.send(report).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Some(Self { results: rx })
+    }
+
+    /// Whether a pull changed the workspace since the last call, without blocking
+    pub fn drain(&self) -> bool {
+        self.results.try_iter().fold(false, |changed, report| {
+            changed
+                || report.pulled > 0
+                || !report.merged.is_empty()
+                || !report.conflicts.is_empty()
+        })
+    }
+}
@@ -1,9 +1,14 @@
 mod app;
+mod live;
+mod queue;
+mod sizes;
+mod sync_status;
 mod ui;
 
 pub use app::App;
 
 use std::io;
+use std::path::Path;
 
 use anyhow::Result;
 use crossterm::{
@@ -13,8 +18,13 @@ use crossterm::{
 };
 use ratatui::{Terminal, backend::CrosstermBackend};
 
-use crate::models::{Config, Context};
-use crate::open::{open_folder_nonblocking, open_path_nonblocking};
+use crate::crypto;
+use crate::models::{Config, Context, RunRecord};
+use crate::notify;
+use crate::open::{
+    open_folder_nonblocking, open_path_blocking, open_path_nonblocking,
+    open_with_editor_and_project, open_with_editor_at,
+};
 use crate::storage::Storage;
 
 pub fn run(
@@ -22,6 +32,7 @@ pub fn run(
     context: Context,
     available_contexts: Vec<Context>,
     session_name: Option<&str>,
+    file: Option<&Path>,
 ) -> Result<()> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -32,7 +43,7 @@ pub fn run(
     let storage = Storage::new(config.clone(), context.clone());
     let mut app = App::new(storage, config, context, available_contexts);
 
-    let res = run_app(&mut terminal, &mut app, session_name);
+    let res = run_app(&mut terminal, &mut app, session_name, file);
 
     disable_raw_mode()?;
     execute!(
@@ -53,16 +64,30 @@ fn run_app(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
     app: &mut App,
     session_name: Option<&str>,
+    file: Option<&Path>,
 ) -> Result<()> {
     app.refresh_sessions()?;
-    if let Some(name) = session_name {
-        app.select_session_by_name(name);
+    match session_name {
+        Some(name) => app.select_session_by_name(name),
+        None => {
+            app.show_dashboard();
+        }
+    }
+    if let Some(file) = file {
+        app.preview_file(file);
     }
 
     loop {
         terminal.draw(|f| ui::draw(f, app))?;
 
-        if let Event::Key(key) = event::read()? {
+        // Block on input unless the app has time-based work pending (e.g. debounced search)
+        let event = match app.poll_timeout() {
+            Some(timeout) if !event::poll(timeout)? => None,
+            _ => Some(event::read()?),
+        };
+        app.tick();
+
+        if let Some(Event::Key(key)) = event {
             if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
                 return Ok(());
             }
@@ -80,6 +105,8 @@ fn run_app(
                     terminal.show_cursor()?;
 
                     let session_dir = app.storage.session_dir(&slug);
+                    let started_at = chrono::Utc::now();
+                    let started = std::time::Instant::now();
                     let status = std::process::Command::new(agent.command())
                         .current_dir(&session_dir)
                         .status();
@@ -92,44 +119,46 @@ fn run_app(
                     )?;
                     terminal.clear()?;
 
-                    if let Err(e) = status {
-                        app.set_error(format!("Failed to run agent: {e}"));
+                    match status {
+                        Ok(status) => {
+                            let run = RunRecord {
+                                agent,
+                                started_at,
+                                duration_secs: started.elapsed().as_secs(),
+                                exit_code: status.code(),
+                            };
+                            if let Err(e) = app.storage.record_run(&slug, run) {
+                                app.set_error(format!("Failed to record run: {e}"));
+                            }
+                            app.notify(notify::Event::AgentFinished {
+                                slug: &slug,
+                                agent,
+                                exit_code: status.code(),
+                                duration: started.elapsed(),
+                            });
+                        }
+                        Err(e) => app.set_error(format!("Failed to run agent: {e}")),
                     }
 
-                    app.refresh_sessions()?;
+                    app.refresh_session(&slug)?;
+                }
+                app::Action::ViewExternal(path) if crypto::is_encrypted(&path) => {
+                    // The plaintext only exists while the viewer runs, so wait for it
+                    open_external(terminal, app, &path, None, None, true)?;
                 }
                 app::Action::ViewExternal(path) => {
                     if let Err(e) = open_path_nonblocking(&path, app.config.viewer.as_deref()) {
                         app.set_error(format!("Failed to view: {e}"));
                     }
                 }
-                app::Action::EditExternal(path) => {
-                    // For editor, we need to exit TUI temporarily
-                    disable_raw_mode()?;
-                    execute!(
-                        terminal.backend_mut(),
-                        LeaveAlternateScreen,
-                        DisableMouseCapture
-                    )?;
-                    terminal.show_cursor()?;
-
-                    if let Err(e) =
-                        crate::open::open_with_editor(&path, app.config.editor.as_deref())
-                    {
-                        app.set_error(format!("Failed to edit: {e}"));
-                    }
-
-                    enable_raw_mode()?;
-                    execute!(
-                        terminal.backend_mut(),
-                        EnterAlternateScreen,
-                        EnableMouseCapture
-                    )?;
-                    terminal.clear()?;
-
-                    // Reload notes after editing
-                    app.refresh_sessions()?;
-                }
+                app::Action::EditExternal(path, line) => match app.stage_edit(&path) {
+                    Ok(path) => open_external(terminal, app, &path, line, None, false)?,
+                    Err(e) => app.set_error(format!("Failed to stage the edit: {e:#}")),
+                },
+                app::Action::EditWithProject(path, root) => match app.stage_edit(&path) {
+                    Ok(path) => open_external(terminal, app, &path, None, Some(&root), false)?,
+                    Err(e) => app.set_error(format!("Failed to stage the edit: {e:#}")),
+                },
                 app::Action::OpenFolder(path) => {
                     if let Err(e) = open_folder_nonblocking(&path) {
                         app.set_error(format!("Failed to open folder: {e}"));
@@ -139,3 +168,67 @@ fn run_app(
         }
     }
 }
+
+/// Leave the TUI to edit (or view, blocking) a file, decrypting `.age` files into a
+/// temporary copy, then reload the session. With `project`, the editor opens that
+/// directory next to the file.
+fn open_external(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    app: &mut App,
+    path: &Path,
+    line: Option<usize>,
+    project: Option<&Path>,
+    view: bool,
+) -> Result<()> {
+    // For editor, we need to exit TUI temporarily
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture
+    )?;
+    terminal.show_cursor()?;
+
+    let open = |p: &Path| {
+        if view {
+            open_path_blocking(p, app.config.viewer.as_deref())
+        } else if let Some(root) = project {
+            open_with_editor_and_project(p, root, app.config.editor.as_deref())
+        } else {
+            open_with_editor_at(p, line, app.config.editor.as_deref())
+        }
+    };
+    let result = if crypto::is_encrypted(path) {
+        app.storage
+            .cipher()
+            .and_then(|cipher| crypto::edit_encrypted(&cipher, path, open))
+    } else {
+        open(path)
+    };
+    if let Err(e) = result {
+        let verb = if view { "view" } else { "edit" };
+        app.set_error(format!("Failed to {verb}: {e:#}"));
+    }
+
+    enable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        EnterAlternateScreen,
+        EnableMouseCapture
+    )?;
+    terminal.clear()?;
+
+    // Reload only the edited session
+    let dashboard = app.dashboard;
+    match app.selected_session().map(|s| s.slug.clone()) {
+        Some(slug) => app.refresh_session(&slug)?,
+        None => app.refresh_sessions()?,
+    }
+    if dashboard {
+        app.show_dashboard();
+    }
+    if app.mode == app::Mode::Todos {
+        app.refresh_todos();
+    }
+    Ok(())
+}
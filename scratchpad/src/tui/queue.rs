@@ -0,0 +1,456 @@
+//! Agent runs queued from the TUI (`a`), run one after another in the background
+//!
+//! Unlike `r`, which hands the terminal over to the agent, a queued run gets a
+//! pseudo-terminal of its own, so agents that expect one still start, and the TUI stays
+//! usable meanwhile. The next pending run starts once the previous one ends. The last
+//! line each run printed is kept for the queue panel (`A`); the event loop polls the
+//! worker like the others, and records and notifies each run as it finishes. Runs still
+//! going when the TUI quits are killed.
+
+use std::fs::File;
+use std::io::{self, Read as _};
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+
+use crate::models::Agent;
+
+/// How often the worker checks whether the running agent exited or was stopped
+const WAIT_INTERVAL: Duration = Duration::from_millis(100);
+/// Size of the agents' terminal
+const PTY_ROWS: u16 = 50;
+const PTY_COLS: u16 = 160;
+
+/// Terminal escape sequences, dropped from output lines
+static ESCAPES: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\x1b\[[0-?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(\x07|\x1b\\)|\x1b[@-_]").unwrap()
+});
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobState {
+    Pending,
+    Running {
+        started: Instant,
+        started_at: DateTime<Utc>,
+    },
+    /// Exited, with its code (None when killed)
+    Done {
+        exit_code: Option<i32>,
+        duration: Duration,
+    },
+    /// Couldn't be started
+    Failed(String),
+}
+
+pub struct Job {
+    id: u64,
+    pub slug: String,
+    pub agent: Agent,
+    command: Vec<String>,
+    dir: PathBuf,
+    pub state: JobState,
+    /// Last non-blank line of output, without escape sequences
+    pub last_line: String,
+}
+
+impl Job {
+    pub fn is_finished(&self) -> bool {
+        matches!(self.state, JobState::Done { .. } | JobState::Failed(_))
+    }
+}
+
+/// A run that ended, to record and notify
+pub struct Finished {
+    pub slug: String,
+    pub agent: Agent,
+    pub started_at: DateTime<Utc>,
+    pub duration: Duration,
+    /// Err when the agent couldn't be started
+    pub exit_code: Result<Option<i32>, String>,
+}
+
+struct Request {
+    id: u64,
+    command: Vec<String>,
+    dir: PathBuf,
+}
+
+enum Update {
+    Output(u64, String),
+    Exited(u64, Result<Option<i32>, String>),
+}
+
+pub struct RunQueue {
+    /// In the order queued; finished runs stay until cleared
+    pub jobs: Vec<Job>,
+    requests: Sender<Request>,
+    updates: Receiver<Update>,
+    /// The agent running now, shared with the worker so it can be stopped
+    running: Arc<Mutex<Option<Child>>>,
+    next_id: u64,
+}
+
+impl RunQueue {
+    pub fn spawn() -> Self {
+        let (req_tx, req_rx) = mpsc::channel::<Request>();
+        let (upd_tx, upd_rx) = mpsc::channel();
+        let running = Arc::new(Mutex::new(None));
+
+        let worker_running = Arc::clone(&running);
+        thread::spawn(move || {
+            for request in req_rx {
+                let result = run(&request, &worker_running, &upd_tx);
+                if upd_tx.send(Update::Exited(request.id, result)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            jobs: Vec::new(),
+            requests: req_tx,
+            updates: upd_rx,
+            running,
+            next_id: 0,
+        }
+    }
+
+    /// Queue `agent` to run in `dir`, the directory of session `slug`
+    pub fn push(&mut self, slug: String, agent: Agent, dir: PathBuf) {
+        let command = vec![agent.command().to_string()];
+        self.push_command(slug, agent, dir, command);
+    }
+
+    fn push_command(&mut self, slug: String, agent: Agent, dir: PathBuf, command: Vec<String>) {
+        self.jobs.push(Job {
+            id: self.next_id,
+            slug,
+            agent,
+            command,
+            dir,
+            state: JobState::Pending,
+            last_line: String::new(),
+        });
+        self.next_id += 1;
+        self.start_next();
+    }
+
+    /// Drop a pending run, or stop the running one
+    pub fn cancel(&mut self, index: usize) {
+        match self.jobs.get(index).map(|job| &job.state) {
+            Some(JobState::Pending) => {
+                self.jobs.remove(index);
+            }
+            Some(JobState::Running { .. }) => self.kill(),
+            _ => {}
+        }
+    }
+
+    /// Forget finished runs
+    pub fn clear_finished(&mut self) {
+        self.jobs.retain(|job| !job.is_finished());
+    }
+
+    /// Whether a run is pending or running
+    pub fn is_active(&self) -> bool {
+        self.jobs.iter().any(|job| !job.is_finished())
+    }
+
+    pub fn pending(&self) -> usize {
+        self.jobs
+            .iter()
+            .filter(|job| job.state == JobState::Pending)
+            .count()
+    }
+
+    /// Take in the worker's progress without blocking, starting the next run when one
+    /// ended. Returns the runs that ended.
+    pub fn drain(&mut self) -> Vec<Finished> {
+        let mut finished = Vec::new();
+        for update in self.updates.try_iter().collect::<Vec<_>>() {
+            match update {
+                Update::Output(id, line) => {
+                    if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+                        job.last_line = line;
+                    }
+                }
+                Update::Exited(id, result) => {
+                    let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) else {
+                        continue;
+                    };
+                    let JobState::Running {
+                        started,
+                        started_at,
+                    } = job.state
+                    else {
+                        continue;
+                    };
+                    let duration = started.elapsed();
+                    job.state = match &result {
+                        Ok(exit_code) => JobState::Done {
+                            exit_code: *exit_code,
+                            duration,
+                        },
+                        Err(e) => JobState::Failed(e.clone()),
+                    };
+                    finished.push(Finished {
+                        slug: job.slug.clone(),
+                        agent: job.agent,
+                        started_at,
+                        duration,
+                        exit_code: result,
+                    });
+                }
+            }
+        }
+        self.start_next();
+        finished
+    }
+
+    fn start_next(&mut self) {
+        if self
+            .jobs
+            .iter()
+            .any(|job| matches!(job.state, JobState::Running { .. }))
+        {
+            return;
+        }
+        let Some(job) = self
+            .jobs
+            .iter_mut()
+            .find(|job| job.state == JobState::Pending)
+        else {
+            return;
+        };
+        let request = Request {
+            id: job.id,
+            command: job.command.clone(),
+            dir: job.dir.clone(),
+        };
+        if self.requests.send(request).is_ok() {
+            job.state = JobState::Running {
+                started: Instant::now(),
+                started_at: Utc::now(),
+            };
+        }
+    }
+
+    fn kill(&self) {
+        if let Some(child) = self.running.lock().unwrap().as_mut() {
+            let _ = child.kill();
+        }
+    }
+}
+
+impl Drop for RunQueue {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+/// Run one agent to the end, passing its output lines on. Returns its exit code.
+fn run(
+    request: &Request,
+    running: &Mutex<Option<Child>>,
+    updates: &Sender<Update>,
+) -> Result<Option<i32>, String> {
+    let (program, args) = request.command.split_first().ok_or("Empty command")?;
+    let mut command = Command::new(program);
+    command.args(args).current_dir(&request.dir);
+    let (child, output) = spawn(command).map_err(|e| format!("Failed to run {program}: {e}"))?;
+    if let Some(output) = output {
+        let (id, updates) = (request.id, updates.clone());
+        thread::spawn(move || {
+            read_lines(output, |line| {
+                let _ = updates.send(Update::Output(id, line));
+            })
+        });
+    }
+    *running.lock().unwrap() = Some(child);
+    loop {
+        thread::sleep(WAIT_INTERVAL);
+        let mut running = running.lock().unwrap();
+        let Some(child) = running.as_mut() else {
+            return Ok(None);
+        };
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                running.take();
+                return Ok(status.code());
+            }
+            Ok(None) => {}
+            Err(e) => {
+                running.take();
+                return Err(e.to_string());
+            }
+        }
+    }
+}
+
+/// Start `command` in a new pseudo-terminal. Returns the child and the terminal's end to
+/// read its output from.
+#[cfg(unix)]
+fn spawn(mut command: Command) -> io::Result<(Child, Option<File>)> {
+    use std::os::fd::{AsRawFd as _, FromRawFd as _, OwnedFd};
+    use std::os::unix::process::CommandExt as _;
+
+    let (mut master, mut slave) = (0, 0);
+    let mut size = libc::winsize {
+        ws_row: PTY_ROWS,
+        ws_col: PTY_COLS,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: openpty only writes the two descriptors it opens, owned from here on
+    let (master, slave) = unsafe {
+        if libc::openpty(
+            &mut master,
+            &mut slave,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            &raw mut size,
+        ) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave))
+    };
+    // SAFETY: sets a flag on a descriptor we own; the agent mustn't inherit our end
+    unsafe { libc::fcntl(master.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) };
+    command
+        .stdin(slave.try_clone()?)
+        .stdout(slave.try_clone()?)
+        .stderr(slave);
+    // SAFETY: only async-signal-safe calls between fork and exec. The agent leads a new
+    // session with the terminal as its controlling one, like in a terminal emulator.
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY, 0) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    // Dropping `command` closes our copies of the agent's end, so reads end when it exits
+    let child = command.spawn()?;
+    Ok((child, Some(File::from(master))))
+}
+
+/// Without pseudo-terminals, output is discarded
+#[cfg(not(unix))]
+fn spawn(mut command: Command) -> io::Result<(Child, Option<File>)> {
+    use std::process::Stdio;
+
+    let child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok((child, None))
+}
+
+/// Call `on_line` with each non-blank line read, stripped of escape sequences, until the
+/// output ends
+fn read_lines(mut output: File, mut on_line: impl FnMut(String)) {
+    let mut buf = [0; 4096];
+    let mut pending = Vec::new();
+    // Reads fail with EIO rather than returning 0 once the agent closed the terminal
+    while let Ok(n @ 1..) = output.read(&mut buf) {
+        pending.extend_from_slice(&buf[..n]);
+        while let Some(end) = pending.iter().position(|&b| b == b'\n' || b == b'\r') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            let line = ESCAPES
+                .replace_all(&String::from_utf8_lossy(&line), "")
+                .to_string();
+            let line: String = line.chars().filter(|c| !c.is_control()).collect();
+            if !line.trim().is_empty() {
+                on_line(line.trim_end().to_string());
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn finish(queue: &mut RunQueue) -> Vec<Finished> {
+        let mut finished = Vec::new();
+        for _ in 0..100 {
+            finished.extend(queue.drain());
+            if !queue.is_active() {
+                break;
+            }
+            thread::sleep(WAIT_INTERVAL);
+        }
+        finished
+    }
+
+    #[test]
+    fn queued_runs_go_one_at_a_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut queue = RunQueue::spawn();
+        let sh = |script: &str| vec!["sh".to_string(), "-c".to_string(), script.to_string()];
+        let script = "[ -t 1 ] && echo \"\\033[1mtty\\033[0m in $(basename $PWD)\"; exit 3";
+        queue.push_command(
+            "first".to_string(),
+            Agent::Claude,
+            dir.path().to_path_buf(),
+            sh(script),
+        );
+        queue.push_command(
+            "second".to_string(),
+            Agent::Codex,
+            dir.path().to_path_buf(),
+            sh("exit 0"),
+        );
+        queue.push_command(
+            "missing".to_string(),
+            Agent::Codex,
+            dir.path().to_path_buf(),
+            vec!["sp-no-such-agent".to_string()],
+        );
+        assert!(matches!(queue.jobs[0].state, JobState::Running { .. }));
+        assert_eq!(queue.pending(), 2);
+
+        let finished = finish(&mut queue);
+        let slugs: Vec<&str> = finished.iter().map(|f| f.slug.as_str()).collect();
+        assert_eq!(slugs, ["first", "second", "missing"]);
+        assert_eq!(finished[0].exit_code, Ok(Some(3)));
+        assert_eq!(finished[1].exit_code, Ok(Some(0)));
+        assert!(finished[2].exit_code.is_err());
+        let name = dir.path().file_name().unwrap().to_string_lossy();
+        assert_eq!(queue.jobs[0].last_line, format!("tty in {name}"));
+
+        queue.clear_finished();
+        assert!(queue.jobs.is_empty());
+    }
+
+    #[test]
+    fn cancelling_stops_the_running_agent_and_drops_pending_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut queue = RunQueue::spawn();
+        for slug in ["slow", "next"] {
+            queue.push_command(
+                slug.to_string(),
+                Agent::Claude,
+                dir.path().to_path_buf(),
+                vec!["sleep".to_string(), "30".to_string()],
+            );
+        }
+        queue.cancel(1);
+        assert_eq!(queue.jobs.len(), 1);
+        // Once the worker has it
+        thread::sleep(WAIT_INTERVAL * 2);
+        queue.cancel(0);
+        let finished = finish(&mut queue);
+        assert_eq!(finished.len(), 1);
+        assert_eq!(finished[0].exit_code, Ok(None));
+    }
+}
@@ -0,0 +1,54 @@
+//! Background computation of session directory sizes
+//!
+//! Walking large session directories (node_modules, datasets) can take seconds, so sizes
+//! are computed on a worker thread and polled by the event loop.
+
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use crate::storage::dir_size;
+
+pub struct SizeWorker {
+    requests: Sender<(String, PathBuf)>,
+    results: Receiver<(String, u64)>,
+    pending: usize,
+}
+
+impl SizeWorker {
+    pub fn spawn() -> Self {
+        let (req_tx, req_rx) = mpsc::channel::<(String, PathBuf)>();
+        let (res_tx, res_rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            for (slug, dir) in req_rx {
+                if res_tx.send((slug, dir_size(&dir))).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            requests: req_tx,
+            results: res_rx,
+            pending: 0,
+        }
+    }
+
+    pub fn request(&mut self, slug: String, dir: PathBuf) {
+        if self.requests.send((slug, dir)).is_ok() {
+            self.pending += 1;
+        }
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.pending > 0
+    }
+
+    /// Collect finished results without blocking
+    pub fn drain(&mut self) -> Vec<(String, u64)> {
+        let results: Vec<_> = self.results.try_iter().collect();
+        self.pending = self.pending.saturating_sub(results.len());
+        results
+    }
+}
@@ -0,0 +1,59 @@
+//! Background checks of where the workspace stands with its sync remote
+//!
+//! Asking the server for new ops takes a round trip, so checks run on a worker thread
+//! and are polled by the event loop, like sizes.
+
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use crate::models::{Config, Context};
+use crate::storage::Storage;
+use crate::sync::status::{self, Status};
+
+pub struct SyncWorker {
+    requests: Sender<(Config, Context)>,
+    /// Checks by the workspace they were for
+    results: Receiver<(PathBuf, Option<Status>)>,
+    pending: usize,
+}
+
+impl SyncWorker {
+    pub fn spawn() -> Self {
+        let (req_tx, req_rx) = mpsc::channel::<(Config, Context)>();
+        let (res_tx, res_rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            for (config, context) in req_rx {
+                let storage = Storage::new(config.clone(), context);
+                let result = status::check(&storage, &config).ok().flatten();
+                if res_tx.send((storage.workspace_path(), result)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            requests: req_tx,
+            results: res_rx,
+            pending: 0,
+        }
+    }
+
+    pub fn request(&mut self, config: Config, context: Context) {
+        if self.requests.send((config, context)).is_ok() {
+            self.pending += 1;
+        }
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.pending > 0
+    }
+
+    /// The latest finished check, without blocking
+    pub fn drain(&mut self) -> Option<(PathBuf, Option<Status>)> {
+        let results: Vec<_> = self.results.try_iter().collect();
+        self.pending = self.pending.saturating_sub(results.len());
+        results.into_iter().last()
+    }
+}
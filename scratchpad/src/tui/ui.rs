@@ -3,23 +3,48 @@ use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span, Text},
-    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Wrap},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Tabs, Wrap},
 };
 
-use crate::models::Context;
+use std::collections::HashMap;
+use std::time::Duration;
 
-use super::app::{App, Focus, Mode};
+use chrono::{DateTime, Datelike, Local, NaiveDate, Utc};
+
+use crate::git::RepoStatus;
+use crate::list_format::{self, Field};
+use crate::models::{Context, Relation, Session, Status};
+use crate::notify::format_duration;
+use crate::remind;
+use crate::storage::format_size;
+use crate::sync;
+
+use super::app::{App, DetailTab, Focus, ListRow, MetaField, Mode};
+use super::queue::JobState;
 
 pub fn draw(f: &mut Frame, app: &mut App) {
     let size = f.area();
 
+    let header_height = u16::from(app.repo_status.is_some());
+    let reminder_lines = build_reminder_lines(app, Utc::now());
     let main_chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Min(3), Constraint::Length(1)])
+        .constraints([
+            Constraint::Length(header_height),
+            Constraint::Length(reminder_lines.len() as u16),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
         .split(size);
 
-    let content_area = main_chunks[0];
-    let status_area = main_chunks[1];
+    let content_area = main_chunks[2];
+    let status_area = main_chunks[3];
+    if let Some(repo) = &app.repo_status {
+        draw_repo_header(f, app, repo, main_chunks[0]);
+    }
+    if !reminder_lines.is_empty() {
+        f.render_widget(Paragraph::new(reminder_lines), main_chunks[1]);
+    }
 
     if app.show_preview {
         let chunks = Layout::default()
@@ -28,7 +53,7 @@ pub fn draw(f: &mut Frame, app: &mut App) {
             .split(content_area);
 
         draw_session_list(f, app, chunks[0]);
-        draw_notes_panel(f, app, chunks[1]);
+        draw_detail_panel(f, app, chunks[1]);
     } else {
         draw_session_list(f, app, content_area);
     }
@@ -37,8 +62,46 @@ pub fn draw(f: &mut Frame, app: &mut App) {
 
     match app.mode {
         Mode::Search => draw_input_popup(f, app, "Search", size),
-        Mode::NewSession => draw_input_popup(f, app, "New Session (name, Enter for random)", size),
+        Mode::NewSession => {
+            let title = match &app.template_fill {
+                Some(fill) => format!("New Session from {} (name, Enter for random)", fill.name),
+                None => "New Session (name, Enter for random)".to_string(),
+            };
+            draw_input_popup(f, app, &title, size)
+        }
+        Mode::TemplateVar => {
+            let title = app
+                .template_fill
+                .as_ref()
+                .and_then(|fill| {
+                    let var = fill.current()?;
+                    let index = fill.variables.iter().position(|v| v == var)? + 1;
+                    Some(format!(
+                        "{}: {var} ({index}/{})",
+                        fill.name,
+                        fill.variables.len()
+                    ))
+                })
+                .unwrap_or_default();
+            draw_input_popup(f, app, &title, size)
+        }
         Mode::QuickSession => draw_input_popup(f, app, "Quick Session (note)", size),
+        Mode::EditMeta(field) => {
+            let title = format!("{} (empty to clear)", field.label());
+            draw_input_popup(f, app, &title, size)
+        }
+        Mode::PickSession => draw_picker_popup(f, app, size),
+        Mode::SelectLines => {}
+        Mode::PickTemplate => draw_template_popup(f, app, size),
+        Mode::PickLink => draw_links_popup(f, app, size),
+        Mode::Outline => draw_outline_popup(f, app, size),
+        Mode::Queue => draw_queue_popup(f, app, size),
+        Mode::Todos => draw_todos_popup(f, app, size),
+        Mode::Timeline => draw_timeline_popup(f, app, size),
+        Mode::Board => draw_board_popup(f, app, size),
+        Mode::Resolve => draw_resolve_popup(f, app, size),
+        Mode::PickWorkspace => draw_workspace_popup(f, app, size),
+        Mode::InitProject => draw_init_popup(f, app, size),
         Mode::Help => draw_help_popup(f, size),
         Mode::Normal => {}
     }
@@ -48,48 +111,307 @@ pub fn draw(f: &mut Frame, app: &mut App) {
     }
 }
 
-fn draw_session_list(f: &mut Frame, app: &App, area: Rect) {
+/// Most due reminders listed above the session list; the rest are counted
+const MAX_REMINDER_LINES: usize = 3;
+
+/// One line per due reminder, oldest first
+fn build_reminder_lines(app: &App, now: DateTime<Utc>) -> Vec<Line<'static>> {
+    let due = app.due_reminders(now);
+    let mut lines: Vec<Line> = due
+        .iter()
+        .take(MAX_REMINDER_LINES)
+        .map(|(slug, reminder)| {
+            let mut spans = vec![
+                Span::styled(
+                    " ⏰ ",
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(
+                    slug.to_string(),
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(format!(": {}", reminder.message)),
+                Span::styled(
+                    format!(" ({})", remind::relative(reminder.at, now)),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ];
+            if app.selected_session().is_some_and(|s| s.slug == *slug) {
+                spans.push(Span::styled(
+                    "  z:snooze 1d D:done",
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            Line::from(spans)
+        })
+        .collect();
+    if due.len() > MAX_REMINDER_LINES {
+        lines.push(Line::styled(
+            format!(" … {} more (sp list --due)", due.len() - MAX_REMINDER_LINES),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    lines
+}
+
+/// Rendered list rows keyed by slug. A row is rebuilt only when the session's mtime,
+/// derived title, size or `list_format` text changes; selection is applied as an item
+/// style on top.
+#[derive(Default)]
+pub struct ListRowCache {
+    rows: HashMap<String, CachedRow>,
+}
+
+struct CachedRow {
+    updated_at: DateTime<Utc>,
+    title: Option<String>,
+    size: Option<u64>,
+    due: Option<(NaiveDate, bool)>,
+    unread: bool,
+    on_branch: bool,
+    sync: SyncMark,
+    custom: Option<Vec<(Option<Field>, String)>>,
+    line: Line<'static>,
+}
+
+/// A session's changes not synced yet, by side
+#[derive(Clone, Copy, Default, PartialEq)]
+struct SyncMark {
+    unpushed: bool,
+    unapplied: bool,
+}
+
+impl ListRowCache {
+    pub fn clear(&mut self) {
+        self.rows.clear();
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn row(
+        &mut self,
+        session: &Session,
+        title: Option<&String>,
+        size: Option<u64>,
+        due: Option<(NaiveDate, bool)>,
+        unread: bool,
+        on_branch: bool,
+        sync: SyncMark,
+        custom: Option<Vec<(Option<Field>, String)>>,
+    ) -> Line<'static> {
+        if let Some(cached) = self.rows.get(&session.slug)
+            && cached.updated_at == session.updated_at
+            && cached.title.as_ref() == title
+            && cached.size == size
+            && cached.due == due
+            && cached.unread == unread
+            && cached.on_branch == on_branch
+            && cached.sync == sync
+            && cached.custom == custom
+        {
+            return cached.line.clone();
+        }
+
+        let mut spans = vec![if unread {
+            Span::styled("● ", Style::default().fg(Color::Cyan))
+        } else {
+            Span::raw("  ")
+        }];
+        let slug_style = if unread {
+            Style::default().add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        match &custom {
+            Some(segments) => spans.extend(segments.iter().map(|(field, text)| {
+                let style = match field {
+                    None => Style::default(),
+                    Some(Field::Slug) => slug_style,
+                    Some(Field::Title) => Style::default().fg(Color::Gray),
+                    Some(_) => Style::default().fg(Color::DarkGray),
+                };
+                Span::styled(text.clone(), style)
+            })),
+            None => {
+                spans.push(Span::styled(session.slug.clone(), slug_style));
+                if let Some(title) = title {
+                    spans.push(Span::styled(
+                        format!("  {title}"),
+                        Style::default().fg(Color::Gray),
+                    ));
+                }
+                let date = session.updated_at.format("%m/%d %H:%M");
+                let size_label = size.map(format_size).unwrap_or_else(|| "…".to_string());
+                spans.push(Span::styled(
+                    format!("  {date}  {size_label}"),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+        }
+        if let Some((date, overdue)) = due {
+            let style = if overdue {
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(Color::Yellow)
+            };
+            spans.push(Span::styled(
+                format!("  due {}", date.format("%m/%d")),
+                style,
+            ));
+        }
+        if on_branch {
+            spans.push(Span::styled(
+                "  ⎇ current branch",
+                Style::default().fg(Color::Magenta),
+            ));
+        }
+        let arrows = match (sync.unpushed, sync.unapplied) {
+            (true, true) => "  ↑↓",
+            (true, false) => "  ↑",
+            (false, true) => "  ↓",
+            (false, false) => "",
+        };
+        if !arrows.is_empty() {
+            spans.push(Span::styled(arrows, Style::default().fg(Color::Yellow)));
+        }
+        let line = Line::from(spans);
+
+        self.rows.insert(
+            session.slug.clone(),
+            CachedRow {
+                updated_at: session.updated_at,
+                title: title.cloned(),
+                size,
+                due,
+                unread,
+                on_branch,
+                sync,
+                custom,
+                line: line.clone(),
+            },
+        );
+        line
+    }
+}
+
+fn draw_session_list(f: &mut Frame, app: &mut App, area: Rect) {
     let border_style = if app.focus == Focus::List && app.mode == Mode::Normal {
         Style::default().fg(Color::Cyan)
     } else {
         Style::default().fg(Color::DarkGray)
     };
 
-    let items: Vec<ListItem> = app
-        .filtered_sessions
-        .iter()
-        .enumerate()
-        .filter_map(|(i, &idx)| {
-            app.sessions.get(idx).map(|session| {
+    // Only rows inside the visible window are built; keep the selection in view
+    let visible = area.height.saturating_sub(2) as usize;
+    if app.selected_index < app.list_offset {
+        app.list_offset = app.selected_index;
+    } else if visible > 0 && app.selected_index >= app.list_offset + visible {
+        app.list_offset = app.selected_index + 1 - visible;
+    }
+    app.list_offset = app.list_offset.min(app.rows.len().saturating_sub(visible));
+
+    let today = Local::now().date_naive();
+    let now = Utc::now();
+    let end = (app.list_offset + visible).min(app.rows.len());
+    let mut items = Vec::with_capacity(end - app.list_offset);
+    for i in app.list_offset..end {
+        let session = match &app.rows[i] {
+            ListRow::Group {
+                label,
+                count,
+                collapsed,
+            } => {
+                let marker = if *collapsed { "▸" } else { "▾" };
+                let line = Line::from(vec![
+                    Span::styled(
+                        format!("{marker} {label} "),
+                        Style::default()
+                            .fg(Color::Yellow)
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                    Span::styled(format!("({count})"), Style::default().fg(Color::Gray)),
+                ]);
                 let style = if i == app.selected_index {
-                    Style::default()
-                        .bg(Color::DarkGray)
-                        .add_modifier(Modifier::BOLD)
+                    Style::default().bg(Color::DarkGray)
                 } else {
                     Style::default()
                 };
-
-                let date = session.updated_at.format("%m/%d %H:%M");
-                let content = Line::from(vec![
-                    Span::styled(&session.slug, style),
-                    Span::styled(format!("  {date}"), Style::default().fg(Color::DarkGray)),
-                ]);
-
-                ListItem::new(content).style(style)
+                items.push(ListItem::new(line).style(style));
+                continue;
+            }
+            &ListRow::Session(index) => match app.sessions.get(index) {
+                Some(session) => session,
+                None => continue,
+            },
+        };
+        let size = app.session_size(&session.slug);
+        let unread = app.viewed.is_unread(session);
+        let due = app
+            .due_dates
+            .get(&session.slug)
+            .map(|&date| (date, date < today));
+        let on_branch = app.branch_session.as_ref() == Some(&session.slug);
+        let sync = app
+            .sync_status
+            .as_ref()
+            .map(|status| SyncMark {
+                unpushed: status.unpushed.contains(&session.slug),
+                unapplied: status.unapplied.contains(&session.slug),
             })
-        })
-        .collect();
+            .unwrap_or_default();
+        let title = app.titles.get(&session.slug);
+        let custom = app.config.list_format.as_ref().map(|format| {
+            let row = list_format::Row {
+                session,
+                title: title.map(String::as_str),
+                tags: app.tags.get(&session.slug).map_or(&[], Vec::as_slice),
+                size,
+                status: app.statuses.get(&session.slug).copied().unwrap_or_default(),
+            };
+            format.segments(&row, now)
+        });
+        let line = app
+            .list_rows
+            .row(session, title, size, due, unread, on_branch, sync, custom);
 
-    let context_label = match &app.context {
-        Context::User => "User".to_string(),
-        Context::Project(_) => format!("Project: {}", app.context.display_name()),
+        let style = if i == app.selected_index {
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        items.push(ListItem::new(line).style(style));
+    }
+
+    let context_label = match (&app.context, app.named_workspace()) {
+        (_, Some(name)) => format!("Workspace: {name}"),
+        (Context::User, None) => "User".to_string(),
+        (Context::Project(_), None) => format!("Project: {}", app.context.display_name()),
     };
 
+    let sort_label = if app.sort_by_size { " by size" } else { "" };
+    let unread = app
+        .sessions
+        .iter()
+        .filter(|s| app.viewed.is_unread(s))
+        .count();
+    let unread_label = if unread > 0 {
+        format!(" {unread} new")
+    } else {
+        String::new()
+    };
     let title = if app.search_query.is_empty() {
-        format!(" {context_label} ({}) ", app.filtered_sessions.len())
+        format!(
+            " {context_label} ({}){unread_label}{sort_label} ",
+            app.filtered_sessions.len()
+        )
     } else {
         format!(
-            " {context_label} ({}/{}) [{}] ",
+            " {context_label} ({}/{}) [{}]{unread_label}{sort_label} ",
             app.filtered_sessions.len(),
             app.sessions.len(),
             app.search_query
@@ -108,18 +430,28 @@ fn draw_session_list(f: &mut Frame, app: &App, area: Rect) {
     f.render_widget(list, area);
 }
 
-fn draw_notes_panel(f: &mut Frame, app: &mut App, area: Rect) {
-    let border_style = if app.focus == Focus::Detail && app.mode == Mode::Normal {
-        Style::default().fg(Color::Cyan)
+fn draw_detail_panel(f: &mut Frame, app: &mut App, area: Rect) {
+    let border_style =
+        if app.focus == Focus::Detail && matches!(app.mode, Mode::Normal | Mode::SelectLines) {
+            Style::default().fg(Color::Cyan)
+        } else {
+            Style::default().fg(Color::DarkGray)
+        };
+
+    let title = match app.selected_session() {
+        _ if app.dashboard => " Dashboard ".to_string(),
+        Some(session) => match app.previewed_file.as_ref().and_then(|f| f.file_name()) {
+            Some(file) => format!(" {} — {} ", session.display_title(), file.to_string_lossy()),
+            None => format!(" {} ", session.display_title()),
+        },
+        None => " Notes ".to_string(),
+    };
+    let title = if app.notes_wrap || app.detail_tab != DetailTab::Notes {
+        title
     } else {
-        Style::default().fg(Color::DarkGray)
+        format!("{title}[no wrap] ")
     };
 
-    let title = app
-        .selected_session()
-        .map(|s| format!(" {} ", s.display_title()))
-        .unwrap_or_else(|| " Notes ".to_string());
-
     let block = Block::default()
         .borders(Borders::ALL)
         .title(title)
@@ -128,37 +460,240 @@ fn draw_notes_panel(f: &mut Frame, app: &mut App, area: Rect) {
     let inner_area = block.inner(area);
     f.render_widget(block, area);
 
-    let show_tree = app.file_tree.len() > 1;
+    let conflicts = app.session_conflicts();
+    let conflicts_height = u16::from(!conflicts.is_empty());
+    let links_height = u16::from(!app.meta.links.is_empty());
+    let summary_height = u16::from(app.meta.summary.is_some());
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Length(conflicts_height),
+            Constraint::Length(summary_height),
+            Constraint::Length(links_height),
+            Constraint::Min(1),
+        ])
+        .split(inner_area);
+    let (tabs_area, conflicts_area, summary_area, links_area, content_area) =
+        (chunks[0], chunks[1], chunks[2], chunks[3], chunks[4]);
+    if !conflicts.is_empty() {
+        let names: Vec<&str> = conflicts.iter().map(|c| c.name()).collect();
+        let banner = format!(" ⚠ Sync conflict in {} — X to resolve ", names.join(", "));
+        f.render_widget(
+            Paragraph::new(banner).style(Style::default().bg(Color::Yellow).fg(Color::Black)),
+            conflicts_area,
+        );
+    }
+    if let Some(summary) = &app.meta.summary {
+        let line = Line::from(Span::styled(
+            summary.clone(),
+            Style::default()
+                .fg(Color::Gray)
+                .add_modifier(Modifier::ITALIC),
+        ));
+        f.render_widget(Paragraph::new(line), summary_area);
+    }
+    if links_height > 0 {
+        f.render_widget(Paragraph::new(build_links_line(app)), links_area);
+    }
 
-    if show_tree {
-        let tree_content_height = app.file_tree.len() as u16 + 2;
-        let max_tree = (inner_area.height * 40 / 100).min(12);
-        let tree_height = tree_content_height.min(max_tree).min(inner_area.height);
+    let titles = DetailTab::ALL
+        .iter()
+        .enumerate()
+        .map(|(i, tab)| format!("{} {}", i + 1, tab.title()));
+    let tabs = Tabs::new(titles)
+        .select(app.detail_tab.index())
+        .style(Style::default().fg(Color::DarkGray))
+        .highlight_style(
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        );
+    f.render_widget(tabs, tabs_area);
 
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Length(tree_height), Constraint::Min(1)])
-            .split(inner_area);
+    match app.detail_tab {
+        DetailTab::Notes if app.mode == Mode::SelectLines => {
+            // Raw, unwrapped lines so rows map one-to-one onto selectable lines
+            let visible = content_area.height as usize;
+            let offset = (app.line_cursor + 1).saturating_sub(visible);
+            let paragraph = Paragraph::new(build_line_selection_text(app, offset, visible));
+            f.render_widget(paragraph, content_area);
+        }
+        DetailTab::Notes => {
+            let content_text = build_content_text(app, content_area);
+            let mut content_widget = Paragraph::new(content_text);
+            if app.notes_wrap {
+                content_widget = content_widget.wrap(Wrap { trim: false });
+            }
+            f.render_widget(
+                content_widget.scroll((app.notes_scroll, app.notes_hscroll)),
+                content_area,
+            );
+        }
+        DetailTab::Files => {
+            // Keep the cursor in view
+            let visible = content_area.height.saturating_sub(1) as usize;
+            let offset = (app.file_cursor + 1).saturating_sub(visible);
+            let tree_text = render_file_tree(&app.file_tree, Some(app.file_cursor), offset);
+            f.render_widget(Paragraph::new(tree_text), content_area);
+        }
+        DetailTab::Runs => {
+            let runs = Paragraph::new(build_runs_text(app)).scroll((app.notes_scroll, 0));
+            f.render_widget(runs, content_area);
+        }
+        DetailTab::Meta => {
+            f.render_widget(Paragraph::new(build_meta_text(app)), content_area);
+        }
+    }
+}
 
-        let tree_area = chunks[0];
-        let content_area = chunks[1];
+/// Note lines from `offset`, with the selected range highlighted
+fn build_line_selection_text(app: &App, offset: usize, height: usize) -> Text<'static> {
+    let (start, end) = app.selected_lines();
+    let number_width = app.notes_content.lines().count().to_string().len();
+    let lines = app
+        .notes_content
+        .lines()
+        .enumerate()
+        .skip(offset)
+        .take(height)
+        .map(|(i, line)| {
+            let selected = (start..=end).contains(&i);
+            let style = if selected {
+                Style::default().bg(Color::DarkGray).fg(Color::White)
+            } else {
+                Style::default()
+            };
+            Line::from(vec![
+                Span::styled(
+                    format!("{:>number_width$} ", i + 1),
+                    Style::default().fg(if selected {
+                        Color::Yellow
+                    } else {
+                        Color::DarkGray
+                    }),
+                ),
+                Span::styled(line.to_string(), style),
+            ])
+        })
+        .collect::<Vec<_>>();
+    Text::from(lines)
+}
 
-        let tree_text = render_file_tree(&app.file_tree, tree_area.width);
-        let tree_widget = Paragraph::new(tree_text);
-        f.render_widget(tree_widget, tree_area);
+fn build_runs_text(app: &App) -> Text<'static> {
+    if app.meta.runs.is_empty() {
+        return Text::from(Line::from(Span::styled(
+            "No agent runs yet. Press 'r' to run one.",
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
 
-        let content_text = build_content_text(app, content_area);
-        let content_widget = Paragraph::new(content_text)
-            .wrap(Wrap { trim: false })
-            .scroll((app.notes_scroll, 0));
-        f.render_widget(content_widget, content_area);
-    } else {
-        let content_text = build_content_text(app, inner_area);
-        let content_widget = Paragraph::new(content_text)
-            .wrap(Wrap { trim: false })
-            .scroll((app.notes_scroll, 0));
-        f.render_widget(content_widget, inner_area);
+    let lines = app
+        .meta
+        .runs
+        .iter()
+        .rev()
+        .map(|run| {
+            let (status, color) = match run.exit_code {
+                Some(0) => ("ok".to_string(), Color::Green),
+                Some(code) => (format!("exit {code}"), Color::Red),
+                None => ("killed".to_string(), Color::Yellow),
+            };
+            Line::from(vec![
+                Span::styled(
+                    run.started_at
+                        .with_timezone(&Local)
+                        .format("%m/%d %H:%M  ")
+                        .to_string(),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::raw(format!("{:<8}", run.agent.to_string())),
+                Span::styled(format!("{status:<9}"), Style::default().fg(color)),
+                Span::styled(
+                    format_duration(Duration::from_secs(run.duration_secs)),
+                    Style::default().fg(Color::Gray),
+                ),
+            ])
+        })
+        .collect::<Vec<_>>();
+    Text::from(lines)
+}
+
+fn build_meta_text(app: &App) -> Text<'static> {
+    let Some(session) = app.selected_session() else {
+        return Text::default();
+    };
+    let label =
+        |name: &str| Span::styled(format!("{name:<10}"), Style::default().fg(Color::DarkGray));
+    let size = app
+        .session_size(&session.slug)
+        .map(format_size)
+        .unwrap_or_else(|| "…".to_string());
+
+    let mut lines = vec![
+        Line::from(vec![
+            label("Created"),
+            Span::raw(
+                session
+                    .created_at
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string(),
+            ),
+        ]),
+        Line::from(vec![
+            label("Updated"),
+            Span::raw(
+                session
+                    .updated_at
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string(),
+            ),
+        ]),
+        Line::from(vec![label("Size"), Span::raw(size)]),
+        Line::from(vec![
+            label("Path"),
+            Span::raw(app.storage.session_dir(&session.slug).display().to_string()),
+        ]),
+        Line::from(""),
+    ];
+
+    for (i, field) in MetaField::ALL.iter().enumerate() {
+        let value = field.value(&app.meta);
+        let value = if value.is_empty() {
+            "—".to_string()
+        } else {
+            value
+        };
+        let style = if i == app.meta_cursor && app.focus == Focus::Detail {
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        lines.push(Line::from(vec![label(field.label()), Span::raw(value)]).style(style));
+    }
+
+    if !app.meta.worktrees.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(Line::from(label("Worktrees")));
+        for wt in &app.meta.worktrees {
+            lines.push(Line::from(format!(
+                "  {} ({})",
+                wt.path.display(),
+                wt.branch
+            )));
+        }
     }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "Tab to focus, j/k to select, Enter to edit",
+        Style::default().fg(Color::DarkGray),
+    )));
+    Text::from(lines)
 }
 
 fn build_content_text(app: &mut App, area: Rect) -> Text<'static> {
@@ -190,15 +725,39 @@ fn build_content_text(app: &mut App, area: Rect) -> Text<'static> {
             Style::default().fg(Color::DarkGray),
         )))
     } else {
-        let content_width = area.width.max(20);
-        app.ensure_rendered_notes(content_width);
-        app.rendered_notes
+        // Unwrapped, render wide enough that no line of the note is broken
+        let content_width = if app.notes_wrap {
+            area.width
+        } else {
+            let longest = app.notes_content.lines().map(|l| l.chars().count()).max();
+            area.width
+                .max(longest.unwrap_or(0).saturating_add(4).min(1000) as u16)
+        };
+        app.ensure_rendered_notes(content_width.max(20));
+        let mut text = app
+            .rendered_notes
             .clone()
-            .unwrap_or_else(|| Text::from(Line::from("(render failed)")))
+            .unwrap_or_else(|| Text::from(Line::from("(render failed)")));
+        if app.notes_truncated {
+            text.lines.push(Line::from(""));
+            text.lines.push(Line::from(Span::styled(
+                format!(
+                    "… file truncated at {} KB — press '+' to load more, 'v' to view full",
+                    app.notes_content.len() / 1024
+                ),
+                Style::default().fg(Color::Yellow),
+            )));
+        }
+        text
     }
 }
 
-fn render_file_tree(tree: &[crate::models::FileTreeEntry], _width: u16) -> Text<'static> {
+/// File tree rows starting at `offset`, with the `selected` row highlighted
+fn render_file_tree(
+    tree: &[crate::models::FileTreeEntry],
+    selected: Option<usize>,
+    offset: usize,
+) -> Text<'static> {
     let mut lines = Vec::new();
 
     lines.push(Line::from(Span::styled(
@@ -208,7 +767,7 @@ fn render_file_tree(tree: &[crate::models::FileTreeEntry], _width: u16) -> Text<
             .add_modifier(Modifier::BOLD),
     )));
 
-    for entry in tree {
+    for (i, entry) in tree.iter().enumerate().skip(offset) {
         let mut spans = Vec::new();
 
         spans.push(Span::raw("  "));
@@ -236,6 +795,9 @@ fn render_file_tree(tree: &[crate::models::FileTreeEntry], _width: u16) -> Text<
         if entry.is_entry_point {
             style = style.add_modifier(Modifier::BOLD);
         }
+        if selected == Some(i) {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
         spans.push(Span::styled(entry.name.clone(), style));
 
         if entry.is_entry_point {
@@ -245,11 +807,6 @@ fn render_file_tree(tree: &[crate::models::FileTreeEntry], _width: u16) -> Text<
         lines.push(Line::from(spans));
     }
 
-    lines.push(Line::from(Span::styled(
-        "─".repeat(20),
-        Style::default().fg(Color::DarkGray),
-    )));
-
     Text::from(lines)
 }
 
@@ -269,40 +826,160 @@ fn file_type_color(name: &str, is_dir: bool) -> Color {
     }
 }
 
+fn draw_repo_header(f: &mut Frame, app: &App, repo: &RepoStatus, area: Rect) {
+    let mut spans = vec![
+        Span::styled(
+            format!(" {} ", app.context.display_name()),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        Span::styled(
+            format!(" ⎇ {}", repo.branch),
+            Style::default().fg(Color::Magenta),
+        ),
+    ];
+    if repo.dirty > 0 {
+        spans.push(Span::styled(
+            format!("  ● {} changed", repo.dirty),
+            Style::default().fg(Color::Yellow),
+        ));
+    } else {
+        spans.push(Span::styled("  ✓ clean", Style::default().fg(Color::Green)));
+    }
+    if repo.ahead > 0 {
+        spans.push(Span::styled(
+            format!("  ↑{}", repo.ahead),
+            Style::default().fg(Color::Cyan),
+        ));
+    }
+    if repo.behind > 0 {
+        spans.push(Span::styled(
+            format!("  ↓{}", repo.behind),
+            Style::default().fg(Color::Red),
+        ));
+    }
+    f.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
 fn draw_status_bar(f: &mut Frame, app: &App, area: Rect) {
     let mode_str = match app.mode {
         Mode::Normal => "NORMAL",
         Mode::Search => "SEARCH",
         Mode::NewSession => "NEW",
         Mode::QuickSession => "QUICK",
+        Mode::EditMeta(_) => "EDIT",
+        Mode::SelectLines => "SELECT",
+        Mode::PickSession => "PICK",
+        Mode::PickTemplate => "TEMPLATE",
+        Mode::TemplateVar => "TEMPLATE",
+        Mode::PickLink => "LINKS",
+        Mode::Outline => "OUTLINE",
+        Mode::Queue => "QUEUE",
+        Mode::Todos => "TODOS",
+        Mode::Timeline => "TIMELINE",
+        Mode::Board => "BOARD",
+        Mode::Resolve => "RESOLVE",
+        Mode::PickWorkspace | Mode::InitProject => "WORKSPACE",
         Mode::Help => "HELP",
     };
 
     let keybinds = match app.mode {
         Mode::Normal => {
             if app.available_contexts.len() > 1 {
-                "n:new Q:quick /:search r:run e:edit v:view o:folder g:context ?:help q:quit"
+                "n:new Q:quick /:search r:run e:edit v:view o:folder 1-4:tabs g:context ?:help q:quit"
             } else {
-                "n:new Q:quick /:search r:run e:edit v:view o:folder ?:help q:quit"
+                "n:new Q:quick /:search r:run e:edit v:view o:folder 1-4:tabs ?:help q:quit"
             }
         }
-        Mode::Search | Mode::NewSession | Mode::QuickSession => "Enter:confirm Esc:cancel",
+        Mode::Search
+        | Mode::NewSession
+        | Mode::QuickSession
+        | Mode::EditMeta(_)
+        | Mode::TemplateVar => "Enter:confirm Esc:cancel",
+        Mode::SelectLines => "j/k:extend Enter:new quick session Esc:cancel",
+        Mode::PickSession => "type:filter Up/Down:select Enter:confirm Esc:cancel",
+        Mode::PickTemplate => "j/k:select Enter:use template Esc:cancel",
+        Mode::PickLink => "j/k:select Enter:jump Esc:cancel",
+        Mode::Outline => "j/k:select Enter:scroll to heading Esc:cancel",
+        Mode::Queue => "j/k:select x:cancel/stop c:clear finished Enter:go to session Esc:close",
+        Mode::Todos => "j/k:select Enter:open at line Esc:close",
+        Mode::Timeline => "←/→:week ↑/↓:day j/k:select Enter:go to session Esc:close",
+        Mode::Board => "←/→:column j/k:select h/l:move Enter:go to session Esc:close",
+        Mode::PickWorkspace => "j/k:select Enter:switch Esc:cancel",
+        Mode::InitProject => "1:.gitignore 2:.git/info/exclude Esc:cancel",
+        Mode::Resolve => {
+            "l:keep local r:take remote m:take merged e:edit merge j/k:scroll Esc:close"
+        }
         Mode::Help => "Esc/q:close",
     };
 
-    let status = Line::from(vec![
-        Span::styled(
-            format!(" {mode_str} "),
-            Style::default().bg(Color::Cyan).fg(Color::Black),
-        ),
-        Span::raw(" "),
-        Span::styled(keybinds, Style::default().fg(Color::DarkGray)),
-    ]);
+    let mut spans = vec![Span::styled(
+        format!(" {mode_str} "),
+        Style::default().bg(Color::Cyan).fg(Color::Black),
+    )];
+    if app.config.read_only {
+        spans.push(Span::styled(
+            " READ-ONLY ",
+            Style::default().bg(Color::Yellow).fg(Color::Black),
+        ));
+    }
+    if let Some(sync) = &app.sync_status {
+        spans.push(sync_indicator(sync));
+    }
+    if let Some(running) = queue_indicator(app) {
+        spans.push(running);
+    }
+    spans.push(Span::raw(" "));
+    match &app.notice {
+        Some(notice) => spans.push(Span::styled(
+            notice.clone(),
+            Style::default().fg(Color::Green),
+        )),
+        None => spans.push(Span::styled(keybinds, Style::default().fg(Color::DarkGray))),
+    }
+    let status = Line::from(spans);
 
     let paragraph = Paragraph::new(status);
     f.render_widget(paragraph, area);
 }
 
+/// `synced`, or how many sessions each side has yet to sync (↑ to push, ↓ to pull)
+fn sync_indicator(status: &sync::status::Status) -> Span<'static> {
+    let (label, color) = if status.is_synced() {
+        (" synced ".to_string(), Color::Green)
+    } else {
+        let mut label = String::from(" ");
+        if !status.unpushed.is_empty() {
+            label.push_str(&format!("↑{} ", status.unpushed.len()));
+        }
+        if !status.unapplied.is_empty() {
+            label.push_str(&format!("↓{} ", status.unapplied.len()));
+        }
+        if status.remote_error.is_some() {
+            label.push_str("offline ");
+        }
+        (label, Color::Yellow)
+    };
+    Span::styled(label, Style::default().bg(color).fg(Color::Black))
+}
+
+/// The agent running from the queue, and how many wait after it
+fn queue_indicator(app: &App) -> Option<Span<'static>> {
+    let running = app
+        .queue
+        .jobs
+        .iter()
+        .find(|job| matches!(job.state, JobState::Running { .. }))?;
+    let mut label = format!(" ▶ {} {} ", running.agent, running.slug);
+    let pending = app.queue.pending();
+    if pending > 0 {
+        label.push_str(&format!("+{pending} "));
+    }
+    Some(Span::styled(
+        label,
+        Style::default().bg(Color::Magenta).fg(Color::Black),
+    ))
+}
+
 fn draw_input_popup(f: &mut Frame, app: &App, title: &str, area: Rect) {
     let popup_area = centered_rect_fixed_height(60, 3, area);
     f.render_widget(Clear, popup_area);
@@ -321,6 +998,637 @@ fn draw_input_popup(f: &mut Frame, app: &App, title: &str, area: Rect) {
     f.set_cursor_position((popup_area.x + app.input.len() as u16 + 1, popup_area.y + 1));
 }
 
+fn draw_picker_popup(f: &mut Frame, app: &App, area: Rect) {
+    let popup_area = centered_rect(50, 50, area);
+    f.render_widget(Clear, popup_area);
+
+    let (verb, name) = match &app.transfer {
+        Some((path, is_move)) => (
+            if *is_move { "Move" } else { "Copy" },
+            path.file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        ),
+        None => ("Copy", String::new()),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" {verb} {name} to… "))
+        .border_style(Style::default().fg(Color::Yellow));
+    let inner = block.inner(popup_area);
+    f.render_widget(block, popup_area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(1)])
+        .split(inner);
+    f.render_widget(Paragraph::new(format!("> {}", app.input)), chunks[0]);
+
+    let items: Vec<ListItem> = app
+        .picker_candidates()
+        .into_iter()
+        .map(|session| ListItem::new(session.slug.clone()))
+        .collect();
+    // Stateful rendering keeps the cursor scrolled into view
+    let list = List::new(items).highlight_style(
+        Style::default()
+            .bg(Color::DarkGray)
+            .add_modifier(Modifier::BOLD),
+    );
+    let mut state = ListState::default().with_selected(Some(app.picker_cursor));
+    f.render_stateful_widget(list, chunks[1], &mut state);
+}
+
+fn draw_workspace_popup(f: &mut Frame, app: &App, area: Rect) {
+    let popup_area = centered_rect(50, 40, area);
+    f.render_widget(Clear, popup_area);
+
+    let current = app.current_workspace();
+    let mut items: Vec<ListItem> = app
+        .workspace_choices
+        .iter()
+        .enumerate()
+        .map(|(i, choice)| {
+            let path = match &choice.context {
+                Context::Project(path) => path.display().to_string(),
+                Context::User => choice.workspace_path.clone(),
+            };
+            let mut spans = vec![
+                Span::raw(choice.name.clone()),
+                Span::styled(format!("  {path}"), Style::default().fg(Color::DarkGray)),
+            ];
+            if current == Some(i) {
+                spans.push(Span::styled(" (open)", Style::default().fg(Color::Green)));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    if let Some(root) = &app.init_root {
+        items.push(ListItem::new(Line::from(vec![
+            Span::styled("+ new project scratchpad", Style::default().fg(Color::Cyan)),
+            Span::styled(
+                format!("  {}", root.display()),
+                Style::default().fg(Color::DarkGray),
+            ),
+        ])));
+    }
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Switch Workspace ")
+                .border_style(Style::default().fg(Color::Yellow)),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        );
+    let mut state = ListState::default().with_selected(Some(app.workspace_cursor));
+    f.render_stateful_widget(list, popup_area, &mut state);
+}
+
+fn draw_init_popup(f: &mut Frame, app: &App, area: Rect) {
+    let popup_area = centered_rect_fixed_height(60, 6, area);
+    f.render_widget(Clear, popup_area);
+
+    let root = app
+        .init_root
+        .as_ref()
+        .map(|root| root.display().to_string())
+        .unwrap_or_default();
+    let text = vec![
+        Line::from(format!("Where should {root}/.scratchpad/ be ignored?")),
+        Line::from(vec![
+            Span::styled("  1", Style::default().fg(Color::Cyan)),
+            Span::raw(") .gitignore (visible to collaborators)"),
+        ]),
+        Line::from(vec![
+            Span::styled("  2", Style::default().fg(Color::Cyan)),
+            Span::raw(") .git/info/exclude (local only)"),
+        ]),
+    ];
+    let prompt = Paragraph::new(text).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" New Project Scratchpad ")
+            .border_style(Style::default().fg(Color::Yellow)),
+    );
+    f.render_widget(prompt, popup_area);
+}
+
+fn draw_template_popup(f: &mut Frame, app: &App, area: Rect) {
+    let popup_area = centered_rect(50, 50, area);
+    f.render_widget(Clear, popup_area);
+
+    let items: Vec<ListItem> = app
+        .templates
+        .iter()
+        .map(|t| ListItem::new(t.name.clone()))
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" New Session from Template ")
+                .border_style(Style::default().fg(Color::Yellow)),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        );
+    let mut state = ListState::default().with_selected(Some(app.template_cursor));
+    f.render_stateful_widget(list, popup_area, &mut state);
+}
+
+fn draw_links_popup(f: &mut Frame, app: &App, area: Rect) {
+    let popup_area = centered_rect(50, 40, area);
+    f.render_widget(Clear, popup_area);
+
+    let items: Vec<ListItem> = app
+        .meta
+        .links
+        .iter()
+        .map(|link| {
+            let exists = app.sessions.iter().any(|s| s.slug == link.session);
+            let mut spans = vec![
+                Span::styled(
+                    format!("{:<8} ", link.rel.to_string()),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::raw(link.session.clone()),
+            ];
+            if !exists {
+                spans.push(Span::styled(" (missing)", Style::default().fg(Color::Red)));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Jump to Linked Session ")
+                .border_style(Style::default().fg(Color::Yellow)),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        );
+    let mut state = ListState::default().with_selected(Some(app.link_cursor));
+    f.render_stateful_widget(list, popup_area, &mut state);
+}
+
+fn draw_outline_popup(f: &mut Frame, app: &App, area: Rect) {
+    let popup_area = centered_rect(50, 60, area);
+    f.render_widget(Clear, popup_area);
+
+    let items: Vec<ListItem> = app
+        .outline
+        .iter()
+        .map(|entry| {
+            let indent = "  ".repeat(entry.level.saturating_sub(1));
+            let style = if entry.level == 1 {
+                Style::default().add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            ListItem::new(Line::from(Span::styled(
+                format!("{indent}{}", entry.text),
+                style,
+            )))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Outline ")
+                .border_style(Style::default().fg(Color::Yellow)),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        );
+    let mut state = ListState::default().with_selected(Some(app.outline_cursor));
+    f.render_stateful_widget(list, popup_area, &mut state);
+}
+
+fn draw_queue_popup(f: &mut Frame, app: &App, area: Rect) {
+    let popup_area = centered_rect(70, 50, area);
+    f.render_widget(Clear, popup_area);
+
+    let items: Vec<ListItem> = if app.queue.jobs.is_empty() {
+        vec![ListItem::new(Span::styled(
+            "Nothing queued; a runs the agent in the selected session",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        app.queue
+            .jobs
+            .iter()
+            .map(|job| {
+                let (marker, color, detail) = match &job.state {
+                    JobState::Pending => ("…", Color::DarkGray, "pending".to_string()),
+                    JobState::Running { started, .. } => (
+                        "▶",
+                        Color::Yellow,
+                        format!("running {}", format_duration(started.elapsed())),
+                    ),
+                    JobState::Done {
+                        exit_code: Some(0),
+                        duration,
+                    } => ("✓", Color::Green, format_duration(*duration)),
+                    JobState::Done {
+                        exit_code,
+                        duration,
+                    } => (
+                        "✗",
+                        Color::Red,
+                        match exit_code {
+                            Some(code) => {
+                                format!("exit {code} after {}", format_duration(*duration))
+                            }
+                            None => format!("stopped after {}", format_duration(*duration)),
+                        },
+                    ),
+                    JobState::Failed(e) => ("✗", Color::Red, e.clone()),
+                };
+                let mut spans = vec![
+                    Span::styled(format!("{marker} "), Style::default().fg(color)),
+                    Span::styled(
+                        format!("{:<7}", job.agent.to_string()),
+                        Style::default().fg(Color::Cyan),
+                    ),
+                    Span::raw(job.slug.clone()),
+                    Span::styled(format!("  {detail}"), Style::default().fg(Color::DarkGray)),
+                ];
+                if !job.last_line.is_empty() {
+                    spans.push(Span::styled(
+                        format!("  {}", job.last_line),
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect()
+    };
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Agent queue ")
+                .border_style(Style::default().fg(Color::Yellow)),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        );
+    let selected = (!app.queue.jobs.is_empty()).then_some(app.queue_cursor);
+    let mut state = ListState::default().with_selected(selected);
+    f.render_stateful_widget(list, popup_area, &mut state);
+}
+
+/// One-line summary of the selected session's links, e.g. `↑ origin  ~ other`
+fn build_links_line(app: &App) -> Line<'static> {
+    let mut spans = Vec::new();
+    for (i, link) in app.meta.links.iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::raw("  "));
+        }
+        let marker = match link.rel {
+            Relation::Parent => "↑ ",
+            Relation::Child => "↓ ",
+            Relation::Related => "~ ",
+        };
+        spans.push(Span::styled(marker, Style::default().fg(Color::DarkGray)));
+        spans.push(Span::styled(
+            link.session.clone(),
+            Style::default().fg(Color::Magenta),
+        ));
+    }
+    spans.push(Span::styled(
+        "  (L:jump)",
+        Style::default().fg(Color::DarkGray),
+    ));
+    Line::from(spans)
+}
+
+fn draw_todos_popup(f: &mut Frame, app: &App, area: Rect) {
+    let popup_area = centered_rect(80, 80, area);
+    f.render_widget(Clear, popup_area);
+
+    let sessions = {
+        let mut slugs: Vec<_> = app.todos.iter().map(|t| &t.slug).collect();
+        slugs.dedup();
+        slugs.len()
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(
+            " Open items: {} in {sessions} sessions ",
+            app.todos.len()
+        ))
+        .border_style(Style::default().fg(Color::Green));
+
+    if app.todos.is_empty() {
+        let empty = Paragraph::new(Span::styled(
+            "No unchecked tasks or TODO: markers.",
+            Style::default().fg(Color::DarkGray),
+        ))
+        .block(block);
+        f.render_widget(empty, popup_area);
+        return;
+    }
+
+    // Session headers are interleaved with items, so map the cursor to its row
+    let mut items = Vec::new();
+    let mut selected_row = 0;
+    let mut current: Option<&str> = None;
+    for (i, todo) in app.todos.iter().enumerate() {
+        if current != Some(todo.slug.as_str()) {
+            items.push(ListItem::new(Line::from(Span::styled(
+                todo.slug.clone(),
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ))));
+            current = Some(todo.slug.as_str());
+        }
+        if i == app.todo_cursor {
+            selected_row = items.len();
+        }
+        items.push(ListItem::new(Line::from(vec![
+            Span::styled(
+                format!("  {}:{}  ", todo.path.display(), todo.line),
+                Style::default().fg(Color::DarkGray),
+            ),
+            Span::raw(todo.text.clone()),
+        ])));
+    }
+
+    let list = List::new(items).block(block).highlight_style(
+        Style::default()
+            .bg(Color::DarkGray)
+            .add_modifier(Modifier::BOLD),
+    );
+    let mut state = ListState::default().with_selected(Some(selected_row));
+    f.render_stateful_widget(list, popup_area, &mut state);
+}
+
+/// One column per status; the selected column's cursor is highlighted
+fn draw_board_popup(f: &mut Frame, app: &App, area: Rect) {
+    let popup_area = centered_rect(90, 85, area);
+    f.render_widget(Clear, popup_area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Board ")
+        .border_style(Style::default().fg(Color::Green));
+    let inner = block.inner(popup_area);
+    f.render_widget(block, popup_area);
+
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Ratio(1, Status::ALL.len() as u32); Status::ALL.len()])
+        .split(inner);
+
+    for (i, status) in Status::ALL.into_iter().enumerate() {
+        let selected = i == app.board_column;
+        let sessions = app.board_sessions(status);
+        let mut title = status.to_string();
+        title[..1].make_ascii_uppercase();
+        let items: Vec<ListItem> = sessions
+            .iter()
+            .map(|session| {
+                let mut lines = vec![Line::from(session.slug.clone())];
+                if let Some(title) = app.titles.get(&session.slug) {
+                    lines.push(Line::styled(
+                        format!("  {title}"),
+                        Style::default().fg(Color::Gray),
+                    ));
+                }
+                ListItem::new(lines)
+            })
+            .collect();
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(" {title} ({}) ", sessions.len()))
+                    .border_style(if selected {
+                        Style::default().fg(Color::Yellow)
+                    } else {
+                        Style::default().fg(Color::DarkGray)
+                    }),
+            )
+            .highlight_style(
+                Style::default()
+                    .bg(Color::DarkGray)
+                    .add_modifier(Modifier::BOLD),
+            );
+        let mut state = ListState::default().with_selected(selected.then_some(app.board_cursor));
+        f.render_stateful_widget(list, columns[i], &mut state);
+    }
+}
+
+/// Local, remote and merged versions of a conflicting file side by side
+fn draw_resolve_popup(f: &mut Frame, app: &App, area: Rect) {
+    let Some((conflict, sides)) = &app.resolving else {
+        return;
+    };
+    let popup_area = centered_rect(90, 85, area);
+    f.render_widget(Clear, popup_area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" Sync conflict: {} ", conflict.path))
+        .border_style(Style::default().fg(Color::Yellow));
+    let inner = block.inner(popup_area);
+    f.render_widget(block, popup_area);
+
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Ratio(1, 3); 3])
+        .split(inner);
+    let merged = match &sides.merged {
+        Some(merged) => merged.as_str(),
+        None => "(no merge: only markdown files are merged)",
+    };
+    let panes = [
+        ("l Local", sides.local.as_str()),
+        ("r Remote", sides.remote.as_str()),
+        ("m Merged", merged),
+    ];
+    for (column, (title, content)) in columns.iter().zip(panes) {
+        let pane = Paragraph::new(content.to_string())
+            .wrap(Wrap { trim: false })
+            .scroll((app.resolve_scroll, 0))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(" {title} "))
+                    .border_style(Style::default().fg(Color::DarkGray)),
+            );
+        f.render_widget(pane, *column);
+    }
+}
+
+/// Rows of the timeline grid: month labels plus one row per weekday
+const TIMELINE_GRID_HEIGHT: u16 = 8;
+
+fn draw_timeline_popup(f: &mut Frame, app: &App, area: Rect) {
+    let popup_area = centered_rect(90, 85, area);
+    f.render_widget(Clear, popup_area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Timeline ")
+        .border_style(Style::default().fg(Color::Green));
+    let inner = block.inner(popup_area);
+    f.render_widget(block, popup_area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(TIMELINE_GRID_HEIGHT),
+            Constraint::Length(2),
+            Constraint::Min(1),
+        ])
+        .split(inner);
+
+    let counts = app.sessions_per_day();
+    f.render_widget(
+        Paragraph::new(build_timeline_grid(app, &counts, chunks[0].width)),
+        chunks[0],
+    );
+
+    let day = app.timeline_day;
+    let week_start = day - chrono::Duration::days(day.weekday().num_days_from_monday() as i64);
+    let week_total: usize = (0..7)
+        .filter_map(|i| counts.get(&(week_start + chrono::Duration::days(i))))
+        .sum();
+    let sessions = app.sessions_on(day);
+    let summary = Line::from(vec![
+        Span::styled(
+            day.format("%A, %B %-d %Y").to_string(),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        Span::styled(
+            format!("  {} that day · {week_total} that week", sessions.len()),
+            Style::default().fg(Color::DarkGray),
+        ),
+    ]);
+    f.render_widget(Paragraph::new(summary), chunks[1]);
+
+    let items: Vec<ListItem> = sessions
+        .iter()
+        .map(|session| {
+            let mut spans = vec![
+                Span::styled(
+                    session
+                        .updated_at
+                        .with_timezone(&Local)
+                        .format("%H:%M  ")
+                        .to_string(),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::raw(session.slug.clone()),
+            ];
+            if let Some(title) = app.titles.get(&session.slug) {
+                spans.push(Span::styled(
+                    format!("  {title}"),
+                    Style::default().fg(Color::Gray),
+                ));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    let list = List::new(items).highlight_style(
+        Style::default()
+            .bg(Color::DarkGray)
+            .add_modifier(Modifier::BOLD),
+    );
+    let mut state = ListState::default().with_selected(Some(app.timeline_cursor));
+    f.render_stateful_widget(list, chunks[2], &mut state);
+}
+
+/// Contribution-style grid: one column per week (Monday first), one row per weekday.
+/// The rightmost column is the current week unless the selected day is further back.
+fn build_timeline_grid(app: &App, counts: &HashMap<NaiveDate, usize>, width: u16) -> Text<'static> {
+    const LABEL_WIDTH: usize = 4;
+    let weeks = ((width as usize).saturating_sub(LABEL_WIDTH) / 2).max(1) as i64;
+    let today = Local::now().date_naive();
+    let monday =
+        |d: NaiveDate| d - chrono::Duration::days(d.weekday().num_days_from_monday() as i64);
+
+    let selected_week = monday(app.timeline_day);
+    let mut last_week = monday(today);
+    if selected_week < last_week - chrono::Duration::weeks(weeks - 1) {
+        last_week = selected_week + chrono::Duration::weeks(weeks - 1);
+    }
+    let first_week = last_week - chrono::Duration::weeks(weeks - 1);
+
+    // Month labels above the first week of each month
+    let mut header = " ".repeat(LABEL_WIDTH + weeks as usize * 2);
+    let mut prev_month = None;
+    let mut free_from = 0;
+    for w in 0..weeks {
+        let start = first_week + chrono::Duration::weeks(w);
+        let col = LABEL_WIDTH + w as usize * 2;
+        if prev_month != Some(start.month()) && col >= free_from {
+            let label = start.format("%b").to_string();
+            header.replace_range(col..col + label.len().min(header.len() - col), &label);
+            free_from = col + label.len() + 1;
+        }
+        prev_month = Some(start.month());
+    }
+    let mut lines = vec![Line::from(Span::styled(
+        header.trim_end().to_string(),
+        Style::default().fg(Color::DarkGray),
+    ))];
+
+    for weekday in 0..7 {
+        let label = match weekday {
+            0 => "Mon ",
+            2 => "Wed ",
+            4 => "Fri ",
+            _ => "    ",
+        };
+        let mut spans = vec![Span::styled(label, Style::default().fg(Color::DarkGray))];
+        for w in 0..weeks {
+            let day = first_week + chrono::Duration::days(w * 7 + weekday);
+            if day > today {
+                spans.push(Span::raw("  "));
+                continue;
+            }
+            let count = counts.get(&day).copied().unwrap_or(0);
+            let (symbol, mut style) = match count {
+                0 => ("·", Style::default().fg(Color::DarkGray)),
+                1 => ("■", Style::default().fg(Color::Green)),
+                2..=3 => ("■", Style::default().fg(Color::LightGreen)),
+                _ => (
+                    "■",
+                    Style::default()
+                        .fg(Color::LightGreen)
+                        .add_modifier(Modifier::BOLD),
+                ),
+            };
+            if day == app.timeline_day {
+                style = style.bg(Color::White).fg(Color::Black);
+            }
+            spans.push(Span::styled(symbol, style));
+            spans.push(Span::raw(" "));
+        }
+        lines.push(Line::from(spans));
+    }
+    Text::from(lines)
+}
+
 fn draw_help_popup(f: &mut Frame, area: Rect) {
     let popup_area = centered_rect(55, 70, area);
     f.render_widget(Clear, popup_area);
@@ -339,6 +1647,10 @@ fn draw_help_popup(f: &mut Frame, area: Rect) {
             Span::styled("Q", Style::default().fg(Color::Cyan)),
             Span::raw("        Quick session (with note)"),
         ]),
+        Line::from(vec![
+            Span::styled("t", Style::default().fg(Color::Cyan)),
+            Span::raw("        New session from a template"),
+        ]),
         Line::from(vec![
             Span::styled("/", Style::default().fg(Color::Cyan)),
             Span::raw("        Search sessions"),
@@ -347,10 +1659,22 @@ fn draw_help_popup(f: &mut Frame, area: Rect) {
             Span::styled("r", Style::default().fg(Color::Cyan)),
             Span::raw("        Run agent in session"),
         ]),
+        Line::from(vec![
+            Span::styled("a", Style::default().fg(Color::Cyan)),
+            Span::raw("        Queue agent run in the background"),
+        ]),
+        Line::from(vec![
+            Span::styled("A", Style::default().fg(Color::Cyan)),
+            Span::raw("        Agent queue: pending, running and finished runs"),
+        ]),
         Line::from(vec![
             Span::styled("e", Style::default().fg(Color::Cyan)),
             Span::raw("        Edit notes in $EDITOR"),
         ]),
+        Line::from(vec![
+            Span::styled("E", Style::default().fg(Color::Cyan)),
+            Span::raw("        Edit notes next to the session's project"),
+        ]),
         Line::from(vec![
             Span::styled("v", Style::default().fg(Color::Cyan)),
             Span::raw("        View notes in viewer"),
@@ -361,24 +1685,112 @@ fn draw_help_popup(f: &mut Frame, area: Rect) {
         ]),
         Line::from(vec![
             Span::styled("g", Style::default().fg(Color::Cyan)),
-            Span::raw("        Toggle context (User/Project)"),
+            Span::raw("        Toggle context (User/Project), or pick among nested projects"),
+        ]),
+        Line::from(vec![
+            Span::styled("W", Style::default().fg(Color::Cyan)),
+            Span::raw("        Switch workspace, or create the repo's project scratchpad"),
         ]),
         Line::from(vec![
             Span::styled("p", Style::default().fg(Color::Cyan)),
             Span::raw("        Toggle preview panel"),
         ]),
+        Line::from(vec![
+            Span::styled("R", Style::default().fg(Color::Cyan)),
+            Span::raw("        Reload all sessions"),
+        ]),
+        Line::from(vec![
+            Span::styled("C", Style::default().fg(Color::Cyan)),
+            Span::raw("        Timeline of sessions by day"),
+        ]),
+        Line::from(vec![
+            Span::styled("H", Style::default().fg(Color::Cyan)),
+            Span::raw("        Workspace dashboard (_index.md)"),
+        ]),
+        Line::from(vec![
+            Span::styled("B", Style::default().fg(Color::Cyan)),
+            Span::raw("        Board of sessions by status (h/l to move)"),
+        ]),
+        Line::from(vec![
+            Span::styled("X", Style::default().fg(Color::Cyan)),
+            Span::raw("        Resolve the session's sync conflicts"),
+        ]),
+        Line::from(vec![
+            Span::styled("T", Style::default().fg(Color::Cyan)),
+            Span::raw("        Open tasks and TODOs across sessions"),
+        ]),
+        Line::from(vec![
+            Span::styled("M", Style::default().fg(Color::Cyan)),
+            Span::raw("        Mark all sessions read"),
+        ]),
+        Line::from(vec![
+            Span::styled("z / D", Style::default().fg(Color::Cyan)),
+            Span::raw("    Snooze (1 day) / complete due reminders"),
+        ]),
+        Line::from(vec![
+            Span::styled("S", Style::default().fg(Color::Cyan)),
+            Span::raw("        Sort by size / recency"),
+        ]),
+        Line::from(vec![
+            Span::styled("F", Style::default().fg(Color::Cyan)),
+            Span::raw("        Group by date / tag / not at all"),
+        ]),
+        Line::from(vec![
+            Span::styled("f / ⏎", Style::default().fg(Color::Cyan)),
+            Span::raw("    Fold or unfold the selected group"),
+        ]),
         Line::from(vec![
             Span::styled("Tab", Style::default().fg(Color::Cyan)),
             Span::raw("      Switch focus"),
         ]),
         Line::from(vec![
             Span::styled("j/k", Style::default().fg(Color::Cyan)),
-            Span::raw("      Navigate up/down"),
+            Span::raw("      Navigate up/down (5j: five rows)"),
+        ]),
+        Line::from(vec![
+            Span::styled("gg / G", Style::default().fg(Color::Cyan)),
+            Span::raw("   First / last session (10G: tenth row)"),
+        ]),
+        Line::from(vec![
+            Span::styled("m / '", Style::default().fg(Color::Cyan)),
+            Span::raw("    Set a mark (m a) / jump to it (' a)"),
+        ]),
+        Line::from(vec![
+            Span::styled("1-4 h/l", Style::default().fg(Color::Cyan)),
+            Span::raw("  Switch tab: Notes, Files, Runs, Meta"),
+        ]),
+        Line::from(vec![
+            Span::styled("Enter", Style::default().fg(Color::Cyan)),
+            Span::raw("    Open file / edit field (detail focus)"),
+        ]),
+        Line::from(vec![
+            Span::styled("L", Style::default().fg(Color::Cyan)),
+            Span::raw("        Jump to a linked session (parent, child, related)"),
+        ]),
+        Line::from(vec![
+            Span::styled("O", Style::default().fg(Color::Cyan)),
+            Span::raw("        Outline: scroll the notes to a heading"),
+        ]),
+        Line::from(vec![
+            Span::styled("V", Style::default().fg(Color::Cyan)),
+            Span::raw("        Select note lines into a new session (Notes tab)"),
+        ]),
+        Line::from(vec![
+            Span::styled("y/x", Style::default().fg(Color::Cyan)),
+            Span::raw("      Copy/move file to another session (Files tab)"),
         ]),
         Line::from(vec![
             Span::styled("PgUp/Dn", Style::default().fg(Color::Cyan)),
             Span::raw("  Scroll notes"),
         ]),
+        Line::from(vec![
+            Span::styled("w", Style::default().fg(Color::Cyan)),
+            Span::raw("        Wrap notes on/off (unwrapped, h/l scroll sideways)"),
+        ]),
+        Line::from(vec![
+            Span::styled("+", Style::default().fg(Color::Cyan)),
+            Span::raw("        Load more of a truncated note"),
+        ]),
         Line::from(vec![
             Span::styled("Esc", Style::default().fg(Color::Cyan)),
             Span::raw("      Clear search / Cancel"),
@@ -0,0 +1,200 @@
+//! `sp self-update`: replace the running binary with a GitHub release, the same
+//! archive `install.sh` installs
+//!
+//! Releases publish `sp-<tag>-<target>.tar.gz` and `sp-<tag>-SHA256SUMS.txt`; the
+//! archive must match its checksum before anything is replaced. The new binary is
+//! written next to the old one and renamed over it, so an interrupted update leaves
+//! the old binary in place.
+
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+const REPO: &str = "miltonparedes/scratchpad";
+const BINARY: &str = "sp";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+/// Largest archive accepted
+const MAX_ARCHIVE_BYTES: u64 = 200 * 1024 * 1024;
+
+/// Update to `version` (a release tag such as `v0.2.0`), or the latest release. With
+/// `check`, only report whether an update is available.
+pub fn run(version: Option<String>, check: bool) -> Result<()> {
+    let target = target()?;
+    let tag = match version {
+        Some(tag) => tag,
+        None => latest_tag()?,
+    };
+    let current = env!("CARGO_PKG_VERSION");
+    if tag.trim_start_matches('v') == current {
+        println!("sp {current} is up to date");
+        return Ok(());
+    }
+    if check {
+        println!("sp {tag} is available (installed: {current}); run `sp self-update`");
+        return Ok(());
+    }
+
+    let archive_name = format!("{BINARY}-{tag}-{target}.tar.gz");
+    let base = format!("https://github.com/{REPO}/releases/download/{tag}");
+    eprintln!("Downloading {archive_name}...");
+    let archive = download(&format!("{base}/{archive_name}"))?;
+    let sums = download(&format!("{base}/{BINARY}-{tag}-SHA256SUMS.txt"))?;
+    verify(&archive, &archive_name, &String::from_utf8_lossy(&sums))?;
+
+    let exe = std::env::current_exe()
+        .and_then(fs::canonicalize)
+        .context("Failed to locate the sp binary")?;
+    let binary = extract(&archive)?;
+    replace(&exe, &binary)?;
+    println!("Updated sp {current} → {tag} ({})", exe.display());
+    Ok(())
+}
+
+/// Target triple of the release archives, as in `install.sh`
+fn target() -> Result<&'static str> {
+    match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("macos", "aarch64") => Ok("aarch64-apple-darwin"),
+        ("macos", "x86_64") => Ok("x86_64-apple-darwin"),
+        ("linux", "x86_64") => Ok("x86_64-unknown-linux-gnu"),
+        ("linux", "aarch64") => Ok("aarch64-unknown-linux-gnu"),
+        (os, arch) => bail!("No release builds for {os}/{arch}; build from source instead"),
+    }
+}
+
+fn latest_tag() -> Result<String> {
+    #[derive(Deserialize)]
+    struct Release {
+        tag_name: String,
+    }
+    let url = format!("https://api.github.com/repos/{REPO}/releases/latest");
+    let release: Release = ureq::get(&url)
+        .timeout(REQUEST_TIMEOUT)
+        .set("User-Agent", "sp-self-update")
+        .call()
+        .context("Failed to fetch the latest release")?
+        .into_json()
+        .context("Invalid release response")?;
+    Ok(release.tag_name)
+}
+
+fn download(url: &str) -> Result<Vec<u8>> {
+    let response = ureq::get(url)
+        .timeout(DOWNLOAD_TIMEOUT)
+        .call()
+        .with_context(|| format!("Failed to download {url}"))?;
+    let mut bytes = Vec::new();
+    response
+        .into_reader()
+        .take(MAX_ARCHIVE_BYTES)
+        .read_to_end(&mut bytes)
+        .with_context(|| format!("Failed to download {url}"))?;
+    Ok(bytes)
+}
+
+/// Check `archive` against its line in a `sha256sum` listing
+fn verify(archive: &[u8], name: &str, sums: &str) -> Result<()> {
+    let expected = sums
+        .lines()
+        .filter_map(|line| line.split_once(char::is_whitespace))
+        .find(|(_, file)| file.trim().trim_start_matches('*') == name)
+        .map(|(sum, _)| sum.to_ascii_lowercase())
+        .ok_or_else(|| anyhow!("No checksum for {name} in the release"))?;
+    let actual: String = Sha256::digest(archive)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    if actual != expected {
+        bail!("Checksum mismatch for {name}: expected {expected}, got {actual}");
+    }
+    Ok(())
+}
+
+/// The `sp` binary inside a release archive
+fn extract(archive: &[u8]) -> Result<Vec<u8>> {
+    let mut tar = tar::Archive::new(GzDecoder::new(archive));
+    for entry in tar.entries().context("Invalid release archive")? {
+        let mut entry = entry.context("Invalid release archive")?;
+        let path = entry.path()?.into_owned();
+        if entry.header().entry_type().is_file() && path.file_name() == Some(BINARY.as_ref()) {
+            let mut binary = Vec::new();
+            entry.read_to_end(&mut binary)?;
+            return Ok(binary);
+        }
+    }
+    bail!("No {BINARY} binary in the release archive")
+}
+
+/// Write `binary` beside `exe` and rename it over it
+fn replace(exe: &Path, binary: &[u8]) -> Result<()> {
+    let dir = exe
+        .parent()
+        .context("The sp binary has no parent directory")?;
+    let staged: PathBuf = dir.join(format!(".{BINARY}.update-{}", std::process::id()));
+    let written = fs::write(&staged, binary).and_then(|()| make_executable(&staged));
+    if let Err(e) = written.and_then(|()| fs::rename(&staged, exe)) {
+        let _ = fs::remove_file(&staged);
+        return Err(e).with_context(|| {
+            format!(
+                "Failed to replace {} (reinstall with install.sh if it's not writable)",
+                exe.display()
+            )
+        });
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifies_against_the_matching_line() {
+        let archive = b"release bytes";
+        let sum: String = Sha256::digest(archive)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        let sums = format!("0000  sp-v1-other.tar.gz\n{sum}  sp-v1-x86_64.tar.gz\n");
+        assert!(verify(archive, "sp-v1-x86_64.tar.gz", &sums).is_ok());
+        assert!(verify(b"tampered", "sp-v1-x86_64.tar.gz", &sums).is_err());
+        assert!(verify(archive, "sp-v1-aarch64.tar.gz", &sums).is_err());
+    }
+
+    #[test]
+    fn extracts_the_binary_and_swaps_it_in() {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(3);
+        header.set_mode(0o755);
+        builder.append_data(&mut header, "sp", &b"new"[..]).unwrap();
+        let archive = builder.into_inner().unwrap().finish().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let exe = dir.path().join("sp");
+        fs::write(&exe, "old").unwrap();
+        replace(&exe, &extract(&archive).unwrap()).unwrap();
+        assert_eq!(fs::read_to_string(&exe).unwrap(), "new");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
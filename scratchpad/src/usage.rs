@@ -0,0 +1,226 @@
+//! Local usage counters (`sp usage`): commands run, and sessions created and agent runs
+//! per week
+//!
+//! Kept in `usage.json` next to the config file and never sent anywhere; set
+//! `usage_stats = false` to stop recording. Recording is best effort: a stats file that
+//! can't be read or written never fails the command being counted.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context as _, Result};
+use chrono::{Datelike, Duration, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::models::Config;
+use crate::notify::Event;
+
+const USAGE_FILE: &str = "usage.json";
+const SPARK_CHARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+/// Commands listed by `sp usage`
+const TOP_COMMANDS: usize = 10;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Usage {
+    /// Times each subcommand ran, `tui` for a bare `sp`
+    #[serde(default)]
+    commands: BTreeMap<String, u64>,
+    /// Agent runs by agent
+    #[serde(default)]
+    agents: BTreeMap<String, u64>,
+    /// Counters by ISO week, e.g. `2026-W42`
+    #[serde(default)]
+    weeks: BTreeMap<String, Week>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+struct Week {
+    #[serde(default)]
+    sessions: u64,
+    #[serde(default)]
+    agent_runs: u64,
+}
+
+fn path() -> PathBuf {
+    crate::config::config_path().with_file_name(USAGE_FILE)
+}
+
+fn week_key(date: NaiveDate) -> String {
+    let week = date.iso_week();
+    format!("{}-W{:02}", week.year(), week.week())
+}
+
+impl Usage {
+    pub fn load() -> Self {
+        fs::read_to_string(path())
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context("Failed to create config directory")?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp, &path).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn add_command(&mut self, name: &str) {
+        *self.commands.entry(name.to_string()).or_default() += 1;
+    }
+
+    fn add_event(&mut self, event: &Event, today: NaiveDate) {
+        let week = self.weeks.entry(week_key(today)).or_default();
+        match event {
+            Event::SessionCreated { .. } => week.sessions += 1,
+            Event::AgentFinished { agent, .. } => {
+                week.agent_runs += 1;
+                *self.agents.entry(agent.command().to_string()).or_default() += 1;
+            }
+            Event::SessionDeleted { .. } => {}
+        }
+    }
+
+    /// The last `weeks` weeks up to the one holding `today`, oldest first
+    fn recent(&self, weeks: usize, today: NaiveDate) -> Vec<Week> {
+        (0..weeks)
+            .rev()
+            .map(|ago| {
+                let day = today - Duration::weeks(ago as i64);
+                self.weeks.get(&week_key(day)).copied().unwrap_or_default()
+            })
+            .collect()
+    }
+
+    /// The `sp usage` report over the last `weeks` weeks
+    pub fn report(&self, weeks: usize, today: NaiveDate) -> String {
+        let recent = self.recent(weeks, today);
+        let mut out = String::new();
+        for (label, counts) in [
+            (
+                "Sessions created",
+                recent.iter().map(|w| w.sessions).collect::<Vec<_>>(),
+            ),
+            (
+                "Agent runs",
+                recent.iter().map(|w| w.agent_runs).collect::<Vec<_>>(),
+            ),
+        ] {
+            out.push_str(&format!(
+                "{label:<18}{}  {} in {weeks} weeks\n",
+                sparkline(&counts),
+                counts.iter().sum::<u64>()
+            ));
+        }
+        if !self.agents.is_empty() {
+            let agents: Vec<String> = self
+                .agents
+                .iter()
+                .map(|(agent, n)| format!("{agent} {n}"))
+                .collect();
+            out.push_str(&format!("{:<18}{}\n", "Runs by agent", agents.join(", ")));
+        }
+
+        let mut commands: Vec<(&String, &u64)> = self.commands.iter().collect();
+        commands.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        if !commands.is_empty() {
+            out.push_str("\nMost used commands\n");
+            let width = commands.iter().map(|(c, _)| c.len()).max().unwrap_or(0);
+            for (command, n) in commands.into_iter().take(TOP_COMMANDS) {
+                out.push_str(&format!("  {command:<width$}  {n}\n"));
+            }
+        }
+        out
+    }
+}
+
+/// One bar per value, scaled to the largest
+fn sparkline(values: &[u64]) -> String {
+    let max = values.iter().copied().max().unwrap_or(0);
+    values
+        .iter()
+        .map(|&v| match v {
+            0 => ' ',
+            _ => SPARK_CHARS[((v * (SPARK_CHARS.len() as u64 - 1)) / max.max(1)) as usize],
+        })
+        .collect()
+}
+
+fn update(config: &Config, f: impl FnOnce(&mut Usage)) {
+    if !config.usage_stats {
+        return;
+    }
+    let mut usage = Usage::load();
+    f(&mut usage);
+    let _ = usage.save();
+}
+
+/// Count a run of the `name` subcommand
+pub fn record_command(config: &Config, name: &str) {
+    update(config, |usage| usage.add_command(name));
+}
+
+/// Count a session created or agent run
+pub fn record_event(config: &Config, event: &Event) {
+    if matches!(event, Event::SessionDeleted { .. }) {
+        return;
+    }
+    update(config, |usage| {
+        usage.add_event(event, Local::now().date_naive())
+    });
+}
+
+/// Print the report for `sp usage`
+pub fn show(weeks: usize) -> Result<()> {
+    let usage = Usage::load();
+    if usage.commands.is_empty() && usage.weeks.is_empty() {
+        println!("No usage recorded yet ({})", path().display());
+        return Ok(());
+    }
+    print!("{}", usage.report(weeks.max(1), Local::now().date_naive()));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Agent;
+
+    #[test]
+    fn sparkline_scales_to_the_largest_value() {
+        assert_eq!(sparkline(&[0, 1, 4, 8]), " ▁▄█");
+        assert_eq!(sparkline(&[0, 0]), "  ");
+    }
+
+    #[test]
+    fn report_counts_by_week() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 16).unwrap();
+        let mut usage = Usage::default();
+        let created = Event::SessionCreated { slug: "alpha" };
+        usage.add_event(&created, today);
+        usage.add_event(&created, today - Duration::weeks(1));
+        usage.add_event(&created, today - Duration::weeks(10));
+        usage.add_event(
+            &Event::AgentFinished {
+                slug: "alpha",
+                agent: Agent::Claude,
+                exit_code: Some(0),
+                duration: std::time::Duration::from_secs(1),
+            },
+            today,
+        );
+        usage.add_command("list");
+        usage.add_command("list");
+        usage.add_command("new");
+
+        let report = usage.report(4, today);
+        assert!(report.contains("Sessions created    ██  2 in 4 weeks"));
+        assert!(report.contains("Agent runs           █  1 in 4 weeks"));
+        assert!(report.contains("Runs by agent     claude 1"));
+        assert!(report.contains("  list  2\n  new   1\n"));
+    }
+}
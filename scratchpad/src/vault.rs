@@ -0,0 +1,381 @@
+//! Obsidian/Logseq vault interop
+//!
+//! Sessions are exported as folders under `<vault>/<folder>/<slug>/`. The entry point gets
+//! frontmatter with the session's timestamps, and `[[session]]` links are rewritten to
+//! vault paths (`[[folder/slug/notes|slug]]`). Import reverses both steps.
+//!
+//! With `--frontmatter`, the frontmatter is the session's metadata rather than a courtesy
+//! to the vault: export adds the session's tags, and import reads `created`, `updated` and
+//! `tags` back (as written by sp or by other markdown tools) instead of going by the
+//! timestamps of the copied files.
+
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
+use crate::models::Session;
+use crate::names::slugify;
+use crate::spignore::IgnoreRules;
+use crate::storage::{Storage, copy_dir_filtered, copy_dir_recursive, find_entry_point_in_dir};
+use crate::tags;
+
+pub struct ExportSummary {
+    pub exported: usize,
+}
+
+pub struct ImportSummary {
+    pub imported: Vec<String>,
+    pub skipped: Vec<String>,
+}
+
+pub fn export_vault(
+    storage: &Storage,
+    vault: &Path,
+    folder: &str,
+    frontmatter: bool,
+) -> Result<ExportSummary> {
+    let sessions = storage.list_sessions()?;
+    let target_root = vault.join(folder);
+    fs::create_dir_all(&target_root)
+        .with_context(|| format!("Failed to create {}", target_root.display()))?;
+
+    // Link targets resolve to the entry point stem of each session (usually "notes")
+    let entry_stems: Vec<(String, String)> = sessions
+        .iter()
+        .map(|s| {
+            let stem = storage
+                .find_entry_point(&s.slug)
+                .and_then(|p| p.file_stem().map(|n| n.to_string_lossy().to_string()))
+                .unwrap_or_else(|| "notes".to_string());
+            (s.slug.clone(), stem)
+        })
+        .collect();
+
+    for session in &sessions {
+        let source = storage.session_dir(&session.slug);
+        let target = target_root.join(&session.slug);
+        copy_dir_filtered(&source, &target, &IgnoreRules::for_session(&source))?;
+
+        if let Some(entry_point) = find_entry_point_in_dir(&target) {
+            let content = fs::read_to_string(&entry_point)?;
+            let linked = rewrite_links(&content, |target, alias| {
+                let slug = target.to_lowercase();
+                let (_, stem) = entry_stems.iter().find(|(s, _)| *s == slug)?;
+                Some(format!(
+                    "[[{folder}/{slug}/{stem}|{}]]",
+                    alias.unwrap_or(target)
+                ))
+            });
+            let tags = if frontmatter {
+                storage.load_meta(&session.slug)?.tags
+            } else {
+                Vec::new()
+            };
+            fs::write(&entry_point, with_frontmatter(session, &tags, &linked))?;
+        }
+    }
+
+    Ok(ExportSummary {
+        exported: sessions.len(),
+    })
+}
+
+pub fn import_vault(
+    storage: &Storage,
+    vault: &Path,
+    folder: &str,
+    frontmatter: bool,
+) -> Result<ImportSummary> {
+    let source_root = vault.join(folder);
+    let mut summary = ImportSummary {
+        imported: Vec::new(),
+        skipped: Vec::new(),
+    };
+
+    let mut dirs: Vec<_> = fs::read_dir(&source_root)
+        .with_context(|| format!("Failed to read {}", source_root.display()))?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir() && !e.file_name().to_string_lossy().starts_with('.'))
+        .collect();
+    dirs.sort_by_key(|e| e.file_name());
+
+    let link_prefix = format!("{folder}/");
+    for dir in dirs {
+        let name = dir.file_name().to_string_lossy().to_string();
+        let Some(slug) = slugify(&name) else {
+            summary.skipped.push(name);
+            continue;
+        };
+        let target = storage.session_dir(&slug);
+        if target.exists() {
+            summary.skipped.push(slug);
+            continue;
+        }
+
+        copy_dir_recursive(&dir.path(), &target)?;
+        let mut updated = None;
+        if let Some(entry_point) = find_entry_point_in_dir(&target) {
+            let content = fs::read_to_string(&entry_point)?;
+            if frontmatter {
+                let fields = read_frontmatter(&content);
+                let mut meta = storage.load_meta(&slug)?;
+                if !fields.tags.is_empty() {
+                    meta.tags.clear();
+                    tags::add(&mut meta.tags, &fields.tags);
+                }
+                meta.created = fields.created.or(meta.created);
+                storage.save_meta(&slug, &meta)?;
+                updated = fields.updated;
+            }
+            let body = strip_frontmatter(&content);
+            let unlinked = rewrite_links(body, |target, alias| {
+                let rest = target.strip_prefix(&link_prefix)?;
+                let slug = rest.split('/').next()?;
+                Some(match alias {
+                    Some(alias) if alias != slug => format!("[[{slug}|{alias}]]"),
+                    _ => format!("[[{slug}]]"),
+                })
+            });
+            fs::write(&entry_point, unlinked)?;
+        }
+        if let Some(updated) = updated {
+            // Sessions are ordered by their directory's mtime
+            fs::File::open(&target)
+                .and_then(|dir| dir.set_modified(SystemTime::from(updated)))
+                .with_context(|| format!("Failed to set the time of {}", target.display()))?;
+        }
+        summary.imported.push(slug);
+    }
+
+    Ok(summary)
+}
+
+fn with_frontmatter(session: &Session, tags: &[String], body: &str) -> String {
+    let tags = match tags {
+        [] => String::new(),
+        tags => format!("tags: [{}]\n", tags.join(", ")),
+    };
+    format!(
+        "---\nscratchpad: {}\ncreated: {}\nupdated: {}\n{tags}---\n\n{}",
+        session.slug,
+        session.created_at.to_rfc3339(),
+        session.updated_at.to_rfc3339(),
+        strip_frontmatter(body),
+    )
+}
+
+/// Metadata read back from an entry point's frontmatter
+#[derive(Debug, Default, PartialEq)]
+struct Frontmatter {
+    created: Option<DateTime<Utc>>,
+    updated: Option<DateTime<Utc>>,
+    tags: Vec<String>,
+}
+
+/// Read the fields sp cares about from a leading `---` block. Only the flat YAML that
+/// notes tools write is understood: `key: value` lines, with tags as `[a, b]`, `a, b`
+/// or a `- a` list, and times as RFC 3339, `YYYY-MM-DD HH:MM[:SS]` (UTC) or a date.
+fn read_frontmatter(content: &str) -> Frontmatter {
+    let mut fields = Frontmatter::default();
+    let Some(rest) = content.strip_prefix("---\n") else {
+        return fields;
+    };
+    let Some(end) = rest.find("\n---") else {
+        return fields;
+    };
+    let mut in_tags = false;
+    for line in rest[..end].lines() {
+        if in_tags && let Some(item) = line.trim_start().strip_prefix("- ") {
+            fields.tags.push(unquote(item).to_string());
+            continue;
+        }
+        in_tags = false;
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = unquote(value.trim());
+        match key.trim() {
+            "created" | "date" => fields.created = fields.created.or(parse_time(value)),
+            "updated" | "modified" => fields.updated = parse_time(value),
+            "tags" => {
+                let list = value.trim_start_matches('[').trim_end_matches(']');
+                fields.tags = list
+                    .split(',')
+                    .map(|t| unquote(t.trim()).to_string())
+                    .filter(|t| !t.is_empty())
+                    .collect();
+                in_tags = value.is_empty();
+            }
+            _ => {}
+        }
+    }
+    fields
+}
+
+fn unquote(value: &str) -> &str {
+    value.trim_matches(|c| c == '"' || c == '\'')
+}
+
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Some(time.with_timezone(&Utc));
+    }
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()?
+                .and_hms_opt(0, 0, 0)
+        })
+        .map(|time| time.and_utc())
+}
+
+/// Remove a leading `---` frontmatter block written by a previous export
+fn strip_frontmatter(content: &str) -> &str {
+    let Some(rest) = content.strip_prefix("---\n") else {
+        return content;
+    };
+    match rest.find("\n---\n") {
+        Some(end) => rest[end + 5..].trim_start_matches('\n'),
+        None => content,
+    }
+}
+
+/// Rewrite every `[[target]]` / `[[target|alias]]` link for which `f` returns a replacement
+fn rewrite_links(content: &str, f: impl Fn(&str, Option<&str>) -> Option<String>) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find("[[") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("]]") else {
+            out.push_str(&rest[start..]);
+            return out;
+        };
+        let inner = &after[..end];
+        let (target, alias) = match inner.split_once('|') {
+            Some((t, a)) => (t, Some(a)),
+            None => (inner, None),
+        };
+        match f(target, alias) {
+            Some(replacement) => out.push_str(&replacement),
+            None => out.push_str(&rest[start..start + 2 + end + 2]),
+        }
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone as _;
+
+    use crate::models::{Config, Context};
+
+    #[test]
+    fn rewrite_links_handles_aliases_and_unknown_targets() {
+        let out = rewrite_links("see [[alpha]] and [[beta|B]] or [[x", |t, a| {
+            (t != "beta").then(|| format!("<{t}:{}>", a.unwrap_or("-")))
+        });
+        assert_eq!(out, "see <alpha:-> and [[beta|B]] or [[x");
+    }
+
+    #[test]
+    fn export_then_import_roundtrips_links() {
+        let src = tempfile::tempdir().unwrap();
+        let dst = tempfile::tempdir().unwrap();
+        let vault = tempfile::tempdir().unwrap();
+        let storage_for = |dir: &Path| {
+            let config = Config {
+                workspace_path: dir.to_string_lossy().to_string(),
+                ..Config::default()
+            };
+            Storage::new(config, Context::User)
+        };
+
+        let source = storage_for(src.path());
+        source
+            .create_session(&Session::new("alpha"), Some("Links to [[beta]]\n"))
+            .unwrap();
+        source.create_session(&Session::new("beta"), None).unwrap();
+
+        assert_eq!(
+            export_vault(&source, vault.path(), "sp", false)
+                .unwrap()
+                .exported,
+            2
+        );
+        let exported = fs::read_to_string(vault.path().join("sp/alpha/notes.md")).unwrap();
+        assert!(exported.starts_with("---\nscratchpad: alpha\n"));
+        assert!(exported.contains("[[sp/beta/notes|beta]]"));
+
+        let dest = storage_for(dst.path());
+        let summary = import_vault(&dest, vault.path(), "sp", false).unwrap();
+        assert_eq!(summary.imported, vec!["alpha", "beta"]);
+        assert_eq!(dest.read_notes("alpha").unwrap(), "Links to [[beta]]\n");
+    }
+
+    #[test]
+    fn read_frontmatter_understands_other_tools() {
+        let fields = read_frontmatter(
+            "---\ntitle: Plan\ndate: 2024-03-01\nmodified: 2024-03-05 14:30\ntags:\n  - work\n  - \"deep dive\"\n---\nbody",
+        );
+        assert_eq!(
+            fields,
+            Frontmatter {
+                created: Some(Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap()),
+                updated: Some(Utc.with_ymd_and_hms(2024, 3, 5, 14, 30, 0).unwrap()),
+                tags: vec!["work".to_string(), "deep dive".to_string()],
+            }
+        );
+        let inline = read_frontmatter("---\ntags: [a, 'b']\n---\n");
+        assert_eq!(inline.tags, ["a", "b"]);
+        assert_eq!(read_frontmatter("no frontmatter"), Frontmatter::default());
+    }
+
+    #[test]
+    fn frontmatter_roundtrips_times_and_tags() {
+        let src = tempfile::tempdir().unwrap();
+        let dst = tempfile::tempdir().unwrap();
+        let vault = tempfile::tempdir().unwrap();
+        let storage_for = |dir: &Path| {
+            let config = Config {
+                workspace_path: dir.to_string_lossy().to_string(),
+                ..Config::default()
+            };
+            Storage::new(config, Context::User)
+        };
+
+        let source = storage_for(src.path());
+        source
+            .create_session(&Session::new("alpha"), Some("Notes\n"))
+            .unwrap();
+        let mut meta = source.load_meta("alpha").unwrap();
+        meta.tags = vec!["work".to_string()];
+        source.save_meta("alpha", &meta).unwrap();
+        export_vault(&source, vault.path(), "sp", true).unwrap();
+
+        // Edited by another tool, which also dropped the metadata file
+        let notes = vault.path().join("sp/alpha/notes.md");
+        fs::remove_file(vault.path().join("sp/alpha/.session.toml")).ok();
+        fs::write(
+            &notes,
+            "---\ncreated: 2020-01-02T03:04:05Z\nupdated: 2021-06-07T08:09:10Z\ntags: [work, Later]\n---\n\nNotes\n",
+        )
+        .unwrap();
+
+        let dest = storage_for(dst.path());
+        import_vault(&dest, vault.path(), "sp", true).unwrap();
+        let session = dest.load_session("alpha").unwrap();
+        assert_eq!(session.created_at.to_rfc3339(), "2020-01-02T03:04:05+00:00");
+        assert_eq!(session.updated_at.to_rfc3339(), "2021-06-07T08:09:10+00:00");
+        assert_eq!(dest.load_meta("alpha").unwrap().tags, ["later", "work"]);
+        assert_eq!(dest.read_notes("alpha").unwrap(), "Notes\n");
+    }
+}
@@ -0,0 +1,111 @@
+//! Per-workspace "last viewed" tracking for unread badges
+//!
+//! Stored in `<workspace>/.viewed.toml`, which stays on this machine (hidden files are
+//! not part of any session). A session is unread when it changed after it was last
+//! viewed, or after the baseline if it was never viewed.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::Session;
+
+pub const VIEWED_FILE: &str = ".viewed.toml";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ViewedData {
+    /// Sessions not viewed since this time are compared against it ("mark all read" resets it)
+    baseline: DateTime<Utc>,
+    #[serde(default)]
+    seen: BTreeMap<String, DateTime<Utc>>,
+}
+
+pub struct ViewedState {
+    path: PathBuf,
+    data: ViewedData,
+}
+
+impl ViewedState {
+    /// Load the state for a workspace. Without a state file, everything counts as read.
+    pub fn load(workspace: &Path) -> Self {
+        let path = workspace.join(VIEWED_FILE);
+        let data = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| toml::from_str(&content).ok())
+            .unwrap_or_else(|| ViewedData {
+                baseline: Utc::now(),
+                seen: BTreeMap::new(),
+            });
+        Self { path, data }
+    }
+
+    pub fn is_unread(&self, session: &Session) -> bool {
+        let last = self
+            .data
+            .seen
+            .get(&session.slug)
+            .unwrap_or(&self.data.baseline);
+        session.updated_at > *last
+    }
+
+    pub fn mark_viewed(&mut self, slug: &str) -> Result<()> {
+        self.data.seen.insert(slug.to_string(), Utc::now());
+        self.save()
+    }
+
+    pub fn mark_all_viewed(&mut self) -> Result<()> {
+        self.data.baseline = Utc::now();
+        self.data.seen.clear();
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        let content = toml::to_string(&self.data).context("Failed to serialize viewed state")?;
+        fs::write(&self.path, content)
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn session(slug: &str, updated_at: DateTime<Utc>) -> Session {
+        Session {
+            updated_at,
+            ..Session::new(slug)
+        }
+    }
+
+    #[test]
+    fn tracks_updates_since_last_view() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = ViewedState::load(dir.path());
+        let old = session("old", Utc::now() - Duration::hours(1));
+        assert!(!state.is_unread(&old));
+
+        let fresh = session("fresh", Utc::now() + Duration::seconds(5));
+        assert!(state.is_unread(&fresh));
+
+        state.mark_viewed("old").unwrap();
+        let state = ViewedState::load(dir.path());
+        assert!(!state.is_unread(&old));
+        assert!(state.is_unread(&fresh));
+    }
+
+    #[test]
+    fn mark_all_viewed_resets_baseline() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = ViewedState::load(dir.path());
+        let touched = session("touched", Utc::now() + Duration::milliseconds(1));
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert!(state.is_unread(&touched));
+        state.mark_all_viewed().unwrap();
+        assert!(!state.is_unread(&touched));
+    }
+}
@@ -0,0 +1,176 @@
+//! Operator subcommands of the server binary, run against the database directly
+
+use std::fs;
+use std::process;
+
+use anyhow::Result;
+
+use crate::cli::{Command, TokensAction, UsersAction, WorkspacesAction};
+use crate::db::{Database, LOGIN_CODE_TTL_MINUTES};
+
+pub fn run(db: &Database, command: Command) -> Result<()> {
+    match command {
+        Command::Serve => unreachable!("serve is handled by main"),
+        Command::Tokens {
+            action: TokensAction::Create { name, user },
+        } => {
+            let token = db.create_token(&name, user.as_deref())?;
+            match &user {
+                Some(user) => {
+                    eprintln!("Created token '{name}' for {user}. It is shown only once:")
+                }
+                None => eprintln!("Created token '{name}'. It is shown only once:"),
+            }
+            println!("{token}");
+        }
+        Command::Tokens {
+            action: TokensAction::Code { name, user },
+        } => {
+            let code = db.create_login_code(&name, user.as_deref())?;
+            eprintln!(
+                "Login code for token '{name}', valid for {LOGIN_CODE_TTL_MINUTES} minutes and \
+                 usable once. On the device, run `sp sync login` and enter:"
+            );
+            println!("{code}");
+        }
+        Command::Tokens {
+            action: TokensAction::Revoke { name },
+        } => {
+            if !db.revoke_token(&name)? {
+                eprintln!("No active token named '{name}'");
+                process::exit(1);
+            }
+            println!("Revoked token '{name}'");
+        }
+        Command::Tokens {
+            action: TokensAction::List,
+        } => {
+            let tokens = db.list_tokens()?;
+            if tokens.is_empty() {
+                eprintln!("No tokens.");
+            }
+            for token in tokens {
+                let state = match &token.revoked_at {
+                    Some(at) => format!("revoked {at}"),
+                    None => "active".to_string(),
+                };
+                let user = token.user.as_deref().unwrap_or("(all workspaces)");
+                println!(
+                    "{:<20}  {:<16}  created {}  {state}",
+                    token.name, user, token.created_at
+                );
+            }
+        }
+        Command::Users {
+            action: UsersAction::Create { name },
+        } => {
+            db.create_user(&name)?;
+            println!(
+                "Created user '{name}'. Give them a token with `sp-server tokens create <name> \
+                 --user {name}`"
+            );
+        }
+        Command::Users {
+            action: UsersAction::List,
+        } => {
+            let users = db.list_users()?;
+            if users.is_empty() {
+                eprintln!("No users.");
+                return Ok(());
+            }
+            println!(
+                "{:<20}  {:>6}  {:>10}  CREATED",
+                "USER", "TOKENS", "WORKSPACES"
+            );
+            for user in users {
+                println!(
+                    "{:<20}  {:>6}  {:>10}  {}",
+                    user.name, user.tokens, user.workspaces, user.created_at
+                );
+            }
+        }
+        Command::Workspaces {
+            action: WorkspacesAction::List,
+        } => {
+            let workspaces = db.list_workspaces()?;
+            if workspaces.is_empty() {
+                eprintln!("No workspaces.");
+                return Ok(());
+            }
+            println!(
+                "{:<36}  {:<16}  {:>8}  SNAPSHOT  LAST OP",
+                "WORKSPACE", "OWNER", "OPS"
+            );
+            for ws in workspaces {
+                println!(
+                    "{:<36}  {:<16}  {:>8}  {:<8}  {}",
+                    ws.workspace_id,
+                    ws.owner.as_deref().unwrap_or("-"),
+                    ws.ops,
+                    if ws.has_snapshot { "yes" } else { "no" },
+                    ws.last_op_at.as_deref().unwrap_or("-"),
+                );
+            }
+        }
+        Command::Workspaces {
+            action: WorkspacesAction::Devices { workspace_id },
+        } => {
+            let devices = db.list_devices(&workspace_id)?;
+            if devices.is_empty() {
+                eprintln!("No ops from named devices in {workspace_id}.");
+                return Ok(());
+            }
+            println!("{:<24}  {:<36}  {:>8}  LAST OP", "DEVICE", "ID", "OPS");
+            for device in devices {
+                println!(
+                    "{:<24}  {:<36}  {:>8}  {}",
+                    device.name.as_deref().unwrap_or("-"),
+                    device.client_id,
+                    device.ops,
+                    device.last_op_at.as_deref().unwrap_or("-"),
+                );
+            }
+        }
+        Command::Workspaces {
+            action: WorkspacesAction::Share { workspace_id, user },
+        } => {
+            if db.share_workspace(&workspace_id, &user)? {
+                println!("Shared {workspace_id} with {user}");
+            } else {
+                eprintln!("{user} can already use {workspace_id}");
+            }
+        }
+        Command::Workspaces {
+            action: WorkspacesAction::Unshare { workspace_id, user },
+        } => {
+            if !db.unshare_workspace(&workspace_id, &user)? {
+                eprintln!("{workspace_id} isn't shared with {user}");
+                process::exit(1);
+            }
+            println!("Took {workspace_id} away from {user}");
+        }
+        Command::Compact { workspace_id } => {
+            let deleted = db.compact(workspace_id.as_deref())?;
+            println!("Deleted {deleted} ops covered by snapshots");
+        }
+        Command::Export {
+            workspace_id,
+            output,
+        } => {
+            if !db.has_workspace(&workspace_id)? {
+                eprintln!("Workspace not found: {workspace_id}");
+                process::exit(1);
+            }
+            let export = db.export(&workspace_id)?;
+            let json = serde_json::to_string_pretty(&export)?;
+            match output {
+                Some(path) => {
+                    fs::write(&path, json + "\n")?;
+                    eprintln!("Exported {} ops to {}", export.ops.len(), path.display());
+                }
+                None => println!("{json}"),
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,143 @@
+//! Workspace tar archives for `/api/export` and `/api/import`
+//!
+//! An archive holds `manifest.json`, `snapshot.json` (when the workspace has one),
+//! `ops.jsonl` with one op per line in log order, and a `blobs/` directory with the
+//! workspace's blobs, each named by its hash.
+
+use std::io::Read;
+
+use anyhow::{Context, Result, bail};
+use scratchpad_protocol::Op;
+use serde::{Deserialize, Serialize};
+
+use crate::models::WorkspaceExport;
+
+/// Bumped when the archive layout changes incompatibly
+const FORMAT_VERSION: u32 = 1;
+
+const MANIFEST: &str = "manifest.json";
+const SNAPSHOT: &str = "snapshot.json";
+const OPS: &str = "ops.jsonl";
+const BLOBS: &str = "blobs/";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    format: u32,
+    workspace_id: String,
+    exported_at: String,
+    ops: usize,
+}
+
+/// `export` as a tar archive
+pub fn write(export: &WorkspaceExport) -> Result<Vec<u8>> {
+    let now = chrono::Utc::now();
+    let manifest = Manifest {
+        format: FORMAT_VERSION,
+        workspace_id: export.workspace_id.clone(),
+        exported_at: now.to_rfc3339(),
+        ops: export.ops.len(),
+    };
+    let mut ops = String::new();
+    for op in &export.ops {
+        ops.push_str(&serde_json::to_string(op)?);
+        ops.push('\n');
+    }
+
+    let mtime = now.timestamp().max(0) as u64;
+    let mut builder = tar::Builder::new(Vec::new());
+    append(
+        &mut builder,
+        mtime,
+        MANIFEST,
+        &serde_json::to_vec_pretty(&manifest)?,
+    )?;
+    if let Some(snapshot) = &export.snapshot {
+        append(
+            &mut builder,
+            mtime,
+            SNAPSHOT,
+            &serde_json::to_vec_pretty(snapshot)?,
+        )?;
+    }
+    append(&mut builder, mtime, OPS, ops.as_bytes())?;
+
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Directory);
+    header.set_mode(0o755);
+    header.set_mtime(mtime);
+    header.set_size(0);
+    builder.append_data(&mut header, BLOBS, std::io::empty())?;
+    for (hash, data) in &export.blobs {
+        append(&mut builder, mtime, &format!("{BLOBS}{hash}"), data)?;
+    }
+
+    Ok(builder.into_inner()?)
+}
+
+fn append(builder: &mut tar::Builder<Vec<u8>>, mtime: u64, path: &str, data: &[u8]) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_mode(0o644);
+    header.set_mtime(mtime);
+    header.set_size(data.len() as u64);
+    builder.append_data(&mut header, path, data)?;
+    Ok(())
+}
+
+/// Read an archive written by [`write`]. The ops and snapshot are returned under the
+/// workspace id recorded in the manifest.
+pub fn read(data: &[u8]) -> Result<WorkspaceExport> {
+    let mut manifest: Option<Manifest> = None;
+    let mut snapshot = None;
+    let mut ops = Vec::new();
+    let mut blobs = Vec::new();
+
+    let mut archive = tar::Archive::new(data);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().to_string();
+        if entry.header().entry_type().is_dir() {
+            continue;
+        }
+        let mut content = String::new();
+        match path.as_str() {
+            MANIFEST => {
+                entry.read_to_string(&mut content)?;
+                manifest = Some(serde_json::from_str(&content).context("Invalid manifest")?);
+            }
+            SNAPSHOT => {
+                entry.read_to_string(&mut content)?;
+                snapshot = Some(serde_json::from_str(&content).context("Invalid snapshot")?);
+            }
+            OPS => {
+                entry.read_to_string(&mut content)?;
+                for (n, line) in content.lines().enumerate() {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let op: Op = serde_json::from_str(line)
+                        .with_context(|| format!("Invalid op on line {}", n + 1))?;
+                    ops.push(op);
+                }
+            }
+            path if path.starts_with(BLOBS) => {
+                let mut data = Vec::new();
+                entry.read_to_end(&mut data)?;
+                blobs.push((path[BLOBS.len()..].to_string(), data));
+            }
+            path => bail!("Unexpected archive entry: {path}"),
+        }
+    }
+
+    let Some(manifest) = manifest else {
+        bail!("Archive has no {MANIFEST}");
+    };
+    if manifest.format != FORMAT_VERSION {
+        bail!("Unsupported archive format {}", manifest.format);
+    }
+    Ok(WorkspaceExport {
+        workspace_id: manifest.workspace_id,
+        snapshot,
+        ops,
+        blobs,
+    })
+}
@@ -0,0 +1,282 @@
+//! Bearer-token authentication for the API and WebSocket
+//!
+//! Tokens come from the database (`sp-server tokens create`, or a login code) and from
+//! `API_TOKENS`, a comma-separated list of `name=token` (or bare tokens, named `env`)
+//! for deployments configured through the environment. Once any token exists, every
+//! route but `/health` and `/api/login` needs an `Authorization: Bearer <token>` header,
+//! including the `/ws` handshake; before that the server is open, and says so at startup.
+//! Revoking every token doesn't reopen it.
+//!
+//! A database token may belong to a user (`sp-server tokens create --user`), and then
+//! only reaches the workspaces that user owns or was shared: the first to push to a
+//! workspace owns it, and `sp-server workspaces share` lets others in. Tokens without a
+//! user, static ones included, reach every workspace, as does anyone while the server
+//! is open.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode, header},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::AppState;
+use crate::db::Database;
+
+/// Name of a static token given without one
+const UNNAMED_TOKEN: &str = "env";
+
+/// Who a request comes from, added to it by `require_token`
+#[derive(Debug, Clone)]
+pub struct Caller {
+    /// Name of the token, empty while the server is open
+    pub name: String,
+    /// User the token belongs to; None for tokens that reach every workspace
+    pub user: Option<String>,
+}
+
+pub struct Auth {
+    /// Static tokens from `API_TOKENS`, to their names
+    tokens: HashMap<String, String>,
+}
+
+impl Auth {
+    pub fn from_env() -> Self {
+        Self::parse(&std::env::var("API_TOKENS").unwrap_or_default())
+    }
+
+    pub(crate) fn parse(spec: &str) -> Self {
+        let tokens = spec
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| match entry.split_once('=') {
+                Some((name, token)) => (token.trim().to_string(), name.trim().to_string()),
+                None => (entry.to_string(), UNNAMED_TOKEN.to_string()),
+            })
+            .collect();
+        Self { tokens }
+    }
+
+    /// Whether requests must carry a token
+    pub fn required(&self, db: &Database) -> Result<bool> {
+        Ok(!self.tokens.is_empty() || db.has_tokens()?)
+    }
+
+    /// Who holds `token`, if it's a static or active database token
+    pub fn caller(&self, db: &Database, token: &str) -> Result<Option<Caller>> {
+        if let Some(name) = self.tokens.get(token) {
+            return Ok(Some(Caller {
+                name: name.clone(),
+                user: None,
+            }));
+        }
+        Ok(db
+            .token_owner(token)?
+            .map(|(name, user)| Caller { name, user }))
+    }
+}
+
+/// The token in the `Authorization: Bearer` header
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// Check a request's token, or let it through when the server has none, and record
+/// who it comes from as a `Caller` extension
+pub async fn require_token(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, String)> {
+    let internal = |e: anyhow::Error| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    let caller = if state.auth.required(&state.db).map_err(internal)? {
+        let token = bearer_token(request.headers())
+            .ok_or((StatusCode::UNAUTHORIZED, "No bearer token".to_string()))?;
+        state
+            .auth
+            .caller(&state.db, token)
+            .map_err(internal)?
+            .ok_or((
+                StatusCode::UNAUTHORIZED,
+                "Unknown or revoked token".to_string(),
+            ))?
+    } else {
+        Caller {
+            name: String::new(),
+            user: None,
+        }
+    };
+    request.extensions_mut().insert(caller);
+    Ok(next.run(request).await)
+}
+
+/// Refuse a workspace the caller's user may not use. `write` claims one nobody used
+/// yet for them.
+pub fn authorize(
+    state: &AppState,
+    caller: &Caller,
+    workspace_id: &str,
+    write: bool,
+) -> Result<(), (StatusCode, String)> {
+    let Some(user) = &caller.user else {
+        return Ok(());
+    };
+    match state.db.can_access(workspace_id, user, write) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err((
+            StatusCode::FORBIDDEN,
+            format!(
+                "Workspace '{workspace_id}' isn't shared with user '{user}'; pick another \
+                 with [server] workspace, or ask the server's operator to share it"
+            ),
+        )),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Extension, Router, body::Body, extract::Path, middleware, routing::get};
+    use tokio::sync::{broadcast, watch};
+    use tower::ServiceExt as _;
+
+    use super::*;
+    use crate::blobs::BlobStore;
+    use crate::limits::Limits;
+
+    fn test_db() -> Database {
+        let db = Database::open(":memory:").unwrap();
+        db.init().unwrap();
+        db
+    }
+
+    /// The auth layer over a route that reads (GET) or pushes to (POST) a workspace
+    fn app(db: Database, auth: Auth) -> Router {
+        async fn touch(
+            State(state): State<Arc<AppState>>,
+            Extension(caller): Extension<Caller>,
+            Path(workspace_id): Path<String>,
+            request: Request,
+        ) -> Result<&'static str, (StatusCode, String)> {
+            let write = request.method() == "POST";
+            authorize(&state, &caller, &workspace_id, write)?;
+            Ok("ok")
+        }
+        let (tx, _) = broadcast::channel(1);
+        let (_, shutdown) = watch::channel(false);
+        let state = Arc::new(AppState {
+            db,
+            auth,
+            blobs: BlobStore::new("unused"),
+            tx,
+            presence: Default::default(),
+            metrics: Default::default(),
+            limits: Limits::new(0, 0, 0),
+            shutdown,
+        });
+        Router::new()
+            .route("/ws/{workspace_id}", get(touch).post(touch))
+            .route_layer(middleware::from_fn_with_state(
+                Arc::clone(&state),
+                require_token,
+            ))
+            .with_state(state)
+    }
+
+    async fn status(app: &Router, method: &str, workspace: &str, token: Option<&str>) -> u16 {
+        let mut request = axum::http::Request::builder()
+            .method(method)
+            .uri(format!("/ws/{workspace}"));
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        let response = app
+            .clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        response.status().as_u16()
+    }
+
+    #[tokio::test]
+    async fn tokens_gate_requests_and_users_their_workspaces() {
+        let db = test_db();
+        db.create_user("alice").unwrap();
+        db.create_user("bob").unwrap();
+        let alice = db.create_token("alice-laptop", Some("alice")).unwrap();
+        let bob = db.create_token("bob-laptop", Some("bob")).unwrap();
+        let admin = db.create_token("admin", None).unwrap();
+        let app = app(db, Auth::parse("ci=static-token"));
+
+        assert_eq!(status(&app, "GET", "ws", None).await, 401);
+        assert_eq!(status(&app, "GET", "ws", Some("wrong")).await, 401);
+        // Bob reading the unused workspace doesn't claim it; Alice's push does
+        assert_eq!(status(&app, "GET", "ws", Some(&bob)).await, 200);
+        assert_eq!(status(&app, "POST", "ws", Some(&alice)).await, 200);
+        assert_eq!(status(&app, "GET", "ws", Some(&bob)).await, 403);
+        assert_eq!(status(&app, "POST", "ws", Some(&bob)).await, 403);
+        assert_eq!(status(&app, "GET", "ws", Some(&alice)).await, 200);
+        // Tokens without a user, static ones included, reach every workspace
+        assert_eq!(status(&app, "POST", "ws", Some(&admin)).await, 200);
+        assert_eq!(status(&app, "POST", "ws", Some("static-token")).await, 200);
+    }
+
+    #[tokio::test]
+    async fn the_server_is_open_until_a_token_exists() {
+        let app = app(test_db(), Auth::parse(""));
+        assert_eq!(status(&app, "POST", "ws", None).await, 200);
+    }
+
+    #[test]
+    fn static_tokens_come_from_the_environment() {
+        let db = test_db();
+        assert!(!Auth::parse("").required(&db).unwrap());
+
+        let auth = Auth::parse(" ci=secret-1 , bare-token ,");
+        assert!(auth.required(&db).unwrap());
+        let ci = auth.caller(&db, "secret-1").unwrap().unwrap();
+        assert_eq!(ci.name, "ci");
+        assert_eq!(ci.user, None);
+        assert_eq!(
+            auth.caller(&db, "bare-token").unwrap().unwrap().name,
+            UNNAMED_TOKEN
+        );
+        assert!(auth.caller(&db, "ci").unwrap().is_none());
+    }
+
+    #[test]
+    fn database_tokens_close_the_server_and_carry_their_user() {
+        let db = test_db();
+        let auth = Auth::parse("");
+        db.create_user("alice").unwrap();
+        let token = db.create_token("laptop", Some("alice")).unwrap();
+        assert!(auth.required(&db).unwrap());
+
+        let caller = auth.caller(&db, &token).unwrap().unwrap();
+        assert_eq!(caller.name, "laptop");
+        assert_eq!(caller.user.as_deref(), Some("alice"));
+
+        db.revoke_token("laptop").unwrap();
+        assert!(auth.caller(&db, &token).unwrap().is_none());
+        assert!(auth.required(&db).unwrap());
+    }
+
+    #[test]
+    fn bearer_token_reads_the_authorization_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(bearer_token(&headers), None);
+        headers.insert(header::AUTHORIZATION, "Basic abc".parse().unwrap());
+        assert_eq!(bearer_token(&headers), None);
+        headers.insert(header::AUTHORIZATION, "Bearer abc ".parse().unwrap());
+        assert_eq!(bearer_token(&headers), Some("abc"));
+    }
+}
@@ -0,0 +1,104 @@
+//! Content-addressed blob storage for `/api/blobs`: binary session files kept on disk
+//! instead of in op payloads
+//!
+//! Each blob is a file named by its SHA-256 under `BLOB_PATH` (default
+//! `scratchpad-blobs`), fanned out by the first two hex digits. Blobs are stored once
+//! however many workspaces upload them; the `blobs` table records which workspaces have,
+//! and a workspace can only read the blobs it uploaded.
+
+use std::fs;
+use std::io::{self, Write as _};
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use sha2::{Digest, Sha256};
+
+pub struct BlobStore {
+    dir: PathBuf,
+}
+
+impl BlobStore {
+    pub fn from_env() -> Self {
+        Self::new(std::env::var("BLOB_PATH").unwrap_or_else(|_| "scratchpad-blobs".to_string()))
+    }
+
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, hash: &str) -> PathBuf {
+        self.dir.join(&hash[..2]).join(hash)
+    }
+
+    /// Store `data` under `hash`, which must be its SHA-256. Returns false if the blob
+    /// was already stored.
+    pub fn put(&self, hash: &str, data: &[u8]) -> Result<bool> {
+        if !is_hash(hash) {
+            bail!("Not a SHA-256 hash: {hash}");
+        }
+        let actual = hex(&Sha256::digest(data));
+        if actual != hash {
+            bail!("Blob content hashes to {actual}, not {hash}");
+        }
+        let path = self.path(hash);
+        if path.is_file() {
+            return Ok(false);
+        }
+        let dir = path.parent().unwrap_or(&self.dir);
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        // Written aside and renamed, so a crash never leaves a truncated blob in place
+        let tmp = dir.join(format!(".{hash}.{}", uuid::Uuid::new_v4()));
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &path).with_context(|| format!("Failed to store blob {hash}"))?;
+        Ok(true)
+    }
+
+    pub fn get(&self, hash: &str) -> Result<Option<Vec<u8>>> {
+        if !is_hash(hash) {
+            return Ok(None);
+        }
+        match fs::read(self.path(hash)) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read blob {hash}")),
+        }
+    }
+}
+
+/// Lowercase hex SHA-256
+pub fn is_hash(hash: &str) -> bool {
+    hash.len() == 64
+        && hash
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stores_blobs_once_under_their_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = BlobStore::new(dir.path());
+        let data = b"\x89PNG\r\n";
+        let hash = hex(&Sha256::digest(data));
+
+        assert_eq!(store.get(&hash).unwrap(), None);
+        assert!(store.put(&hash, data).unwrap());
+        assert!(!store.put(&hash, data).unwrap());
+        assert_eq!(store.get(&hash).unwrap().as_deref(), Some(&data[..]));
+        assert!(dir.path().join(&hash[..2]).join(&hash).is_file());
+
+        // Content that doesn't match its hash, and names that aren't hashes, are refused
+        assert!(store.put(&"0".repeat(64), data).is_err());
+        assert!(store.put("../escape", data).is_err());
+        assert_eq!(store.get("../escape").unwrap(), None);
+    }
+}
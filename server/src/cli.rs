@@ -0,0 +1,94 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+#[derive(Parser)]
+#[command(name = "sp-server")]
+#[command(about = "Relay server for ScratchPad sync", long_about = None)]
+#[command(version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the relay server (the default)
+    Serve,
+
+    /// Manage API tokens
+    Tokens {
+        #[command(subcommand)]
+        action: TokensAction,
+    },
+
+    /// Manage the users sharing this server
+    Users {
+        #[command(subcommand)]
+        action: UsersAction,
+    },
+
+    /// Inspect stored workspaces and who may use them
+    Workspaces {
+        #[command(subcommand)]
+        action: WorkspacesAction,
+    },
+
+    /// Delete ops already folded into a workspace snapshot and reclaim disk space
+    Compact {
+        /// Only compact this workspace
+        workspace_id: Option<String>,
+    },
+
+    /// Write a workspace's snapshot and ops as JSON
+    Export {
+        workspace_id: String,
+        /// Output file (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TokensAction {
+    /// Create a token and print it (it is only stored hashed)
+    Create {
+        /// Name to refer to the token by, e.g. the device or person it is for
+        name: String,
+        /// Only reach this user's workspaces (without it, the token reaches every one)
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// Create a one-time code that `sp sync login` exchanges for a token of this name
+    Code {
+        /// Name to give the token, e.g. the device or person it is for
+        name: String,
+        /// Only reach this user's workspaces (without it, the token reaches every one)
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// Revoke a token by name
+    Revoke { name: String },
+    /// List tokens
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum UsersAction {
+    /// Add a user; give them tokens with `tokens create --user`
+    Create { name: String },
+    /// List users with their active tokens and workspaces
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum WorkspacesAction {
+    /// List workspaces with their op counts, owners and last activity
+    List,
+    /// List the devices that changed a workspace, by the ops they pushed
+    Devices { workspace_id: String },
+    /// Let a user read and push to a workspace (the first one owns it)
+    Share { workspace_id: String, user: String },
+    /// Take a workspace away from a user
+    Unshare { workspace_id: String, user: String },
+}
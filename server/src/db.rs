@@ -1,11 +1,52 @@
-use anyhow::Result;
-use rusqlite::{Connection, Error as SqlError, params};
-use std::sync::Mutex;
+use anyhow::{Result, bail};
+use rusqlite::{Connection, Error as SqlError, OptionalExtension, params};
+use scratchpad_protocol::{Op, OpResult, OpStatus, SearchHit, Snapshot};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Instant;
 
-use crate::models::{Op, Snapshot};
+use crate::metrics::Latency;
+use crate::models::{DeviceInfo, TokenInfo, UserInfo, WorkspaceExport, WorkspaceInfo};
+
+/// Characters of a login code, without ones easily mistaken for each other
+const LOGIN_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const LOGIN_CODE_LEN: usize = 8;
+pub const LOGIN_CODE_TTL_MINUTES: i64 = 15;
 
 pub struct Database {
     conn: Mutex<Connection>,
+    /// How long each query held (or waited for) the connection, for `/metrics`
+    pub latency: Latency,
+}
+
+/// The locked connection, timed from the lock to the drop under `query`
+struct Timed<'a> {
+    conn: MutexGuard<'a, Connection>,
+    query: &'static str,
+    start: Instant,
+    latency: &'a Latency,
+}
+
+impl Deref for Timed<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        &self.conn
+    }
+}
+
+impl DerefMut for Timed<'_> {
+    fn deref_mut(&mut self) -> &mut Connection {
+        &mut self.conn
+    }
+}
+
+impl Drop for Timed<'_> {
+    fn drop(&mut self) {
+        self.latency.observe(self.query, self.start.elapsed());
+    }
 }
 
 impl Database {
@@ -13,11 +54,35 @@ impl Database {
         let conn = Connection::open(path)?;
         Ok(Self {
             conn: Mutex::new(conn),
+            latency: Latency::default(),
         })
     }
 
+    fn conn(&self, query: &'static str) -> Timed<'_> {
+        let start = Instant::now();
+        Timed {
+            conn: self.conn.lock().unwrap(),
+            query,
+            start,
+            latency: &self.latency,
+        }
+    }
+
+    /// Close the connection, surfacing errors that dropping it would ignore
+    pub fn close(self) -> Result<()> {
+        let conn = self.conn.into_inner().unwrap_or_else(|e| e.into_inner());
+        conn.execute_batch("PRAGMA optimize;")?;
+        conn.close().map_err(|(_, e)| e)?;
+        Ok(())
+    }
+
     pub fn init(&self) -> Result<()> {
         let conn = self.conn.lock().unwrap();
+        let has_search_index: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE name = 'search_index')",
+            [],
+            |row| row.get(0),
+        )?;
         conn.execute_batch(
             r#"
             CREATE TABLE IF NOT EXISTS ops (
@@ -39,33 +104,175 @@ impl Database {
                 last_op_id TEXT,
                 updated_at TEXT NOT NULL
             );
+
+            -- Names devices gave themselves, for the client_id on their ops
+            CREATE TABLE IF NOT EXISTS devices (
+                client_id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                last_seen TEXT NOT NULL
+            );
+
+            -- People sharing the server, each seeing only their own workspaces
+            CREATE TABLE IF NOT EXISTS users (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                created_at TEXT NOT NULL
+            );
+
+            -- Tokens without a user reach every workspace
+            CREATE TABLE IF NOT EXISTS tokens (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                token_hash TEXT NOT NULL UNIQUE,
+                created_at TEXT NOT NULL,
+                revoked_at TEXT,
+                user_id INTEGER REFERENCES users(id)
+            );
+
+            -- One-time codes `sp sync login` exchanges for a token of the same name
+            CREATE TABLE IF NOT EXISTS login_codes (
+                code_hash TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                expires_at TEXT NOT NULL,
+                used_at TEXT,
+                user_id INTEGER REFERENCES users(id)
+            );
+
+            -- Users who may read and push to a workspace: its owner, the user who first
+            -- pushed to it, and those it was shared with
+            CREATE TABLE IF NOT EXISTS workspace_access (
+                workspace_id TEXT NOT NULL,
+                user_id INTEGER NOT NULL REFERENCES users(id),
+                role TEXT NOT NULL,
+                PRIMARY KEY (workspace_id, user_id)
+            );
+
+            -- Blobs each workspace uploaded; the bytes are on disk, once per hash
+            CREATE TABLE IF NOT EXISTS blobs (
+                workspace_id TEXT NOT NULL,
+                hash TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                created_at TEXT NOT NULL,
+                PRIMARY KEY (workspace_id, hash)
+            );
+
+            -- Full-text index of file text pushed in ops and the latest snapshot of each
+            -- workspace
+            CREATE VIRTUAL TABLE IF NOT EXISTS search_index USING fts5(
+                workspace_id UNINDEXED,
+                kind UNINDEXED,
+                op_id UNINDEXED,
+                content
+            );
             "#,
         )?;
+        for table in ["tokens", "login_codes"] {
+            // Databases created before users existed
+            let has_user: bool = conn.query_row(
+                "SELECT EXISTS (SELECT 1 FROM pragma_table_info(?1) WHERE name = 'user_id')",
+                params![table],
+                |row| row.get(0),
+            )?;
+            if !has_user {
+                conn.execute_batch(&format!(
+                    "ALTER TABLE {table} ADD COLUMN user_id INTEGER REFERENCES users(id);"
+                ))?;
+            }
+        }
+        if !has_search_index {
+            // Databases created before search existed
+            conn.execute_batch(
+                r#"
+                INSERT INTO search_index (workspace_id, kind, op_id, content)
+                SELECT workspace_id, 'op', op_id, payload ->> '$.content' FROM ops
+                WHERE op_type = 'file.put' AND json_valid(payload)
+                    AND payload ->> '$.content' IS NOT NULL;
+                "#,
+            )?;
+            let snapshots = conn
+                .prepare("SELECT workspace_id, data FROM snapshots")?
+                .query_map([], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            for (workspace_id, data) in snapshots {
+                conn.execute(
+                    r#"
+                    INSERT INTO search_index (workspace_id, kind, op_id, content)
+                    VALUES (?1, 'snapshot', NULL, ?2)
+                    "#,
+                    params![workspace_id, snapshot_text(&data)],
+                )?;
+            }
+        }
         Ok(())
     }
 
-    pub fn push_op(&self, workspace_id: &str, op: &Op) -> Result<i64> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute(
-            r#"
-            INSERT OR IGNORE INTO ops (workspace_id, op_id, op_type, payload, timestamp, client_id)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
-            "#,
-            params![
-                workspace_id,
-                op.id,
-                op.op_type,
-                op.payload,
-                op.timestamp,
-                op.client_id,
-            ],
-        )?;
-        Ok(conn.last_insert_rowid())
+    /// Insert a batch of ops in a single transaction. Returns the outcome of each op:
+    /// stored, already stored under the same id, or rejected as invalid.
+    pub fn push_ops(&self, workspace_id: &str, ops: &[Op]) -> Result<Vec<OpResult>> {
+        let mut conn = self.conn("push_ops");
+        let tx = conn.transaction()?;
+        let mut inserted = Vec::with_capacity(ops.len());
+        {
+            let mut stmt = tx.prepare(
+                r#"
+                INSERT OR IGNORE INTO ops (workspace_id, op_id, op_type, payload, timestamp, client_id)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                "#,
+            )?;
+            let mut index = tx.prepare(
+                r#"
+                INSERT INTO search_index (workspace_id, kind, op_id, content)
+                VALUES (?1, 'op', ?2, ?3)
+                "#,
+            )?;
+            for op in ops {
+                if let Some(reason) = invalid_op(op) {
+                    inserted.push(OpResult {
+                        id: op.id.clone(),
+                        status: OpStatus::Rejected,
+                        reason: Some(reason.to_string()),
+                    });
+                    continue;
+                }
+                let changed = stmt.execute(params![
+                    workspace_id,
+                    op.id,
+                    op.op_type,
+                    op.payload,
+                    op.timestamp,
+                    op.client_id,
+                ])?;
+                let status = if changed > 0 {
+                    if let Some(text) = searchable_text(op) {
+                        index.execute(params![workspace_id, op.id, text])?;
+                    }
+                    OpStatus::Accepted
+                } else {
+                    OpStatus::Duplicate
+                };
+                inserted.push(OpResult {
+                    id: op.id.clone(),
+                    status,
+                    reason: None,
+                });
+            }
+        }
+        tx.commit()?;
+        Ok(inserted)
     }
 
-    pub fn get_ops(&self, workspace_id: &str, after_id: Option<i64>) -> Result<Vec<Op>> {
-        let conn = self.conn.lock().unwrap();
+    pub fn get_ops(
+        &self,
+        workspace_id: &str,
+        after_id: Option<i64>,
+        limit: Option<u32>,
+    ) -> Result<Vec<Op>> {
+        let conn = self.conn("get_ops");
         let after_id = after_id.unwrap_or(0);
+        // SQLite treats a negative limit as none
+        let limit = limit.map_or(-1, i64::from);
 
         let mut stmt = conn.prepare(
             r#"
@@ -73,11 +280,12 @@ impl Database {
             FROM ops
             WHERE workspace_id = ?1 AND id > ?2
             ORDER BY id ASC
+            LIMIT ?3
             "#,
         )?;
 
         let ops = stmt
-            .query_map(params![workspace_id, after_id], |row| {
+            .query_map(params![workspace_id, after_id, limit], |row| {
                 Ok(Op {
                     db_id: Some(row.get(0)?),
                     id: row.get(1)?,
@@ -93,12 +301,13 @@ impl Database {
     }
 
     pub fn get_snapshot(&self, workspace_id: &str) -> Result<Option<Snapshot>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.conn("get_snapshot");
         let mut stmt = conn.prepare(
             r#"
-            SELECT data, last_op_id, updated_at
-            FROM snapshots
-            WHERE workspace_id = ?1
+            SELECT s.data, s.last_op_id, s.updated_at, o.id
+            FROM snapshots s
+            LEFT JOIN ops o ON o.workspace_id = s.workspace_id AND o.op_id = s.last_op_id
+            WHERE s.workspace_id = ?1
             "#,
         )?;
 
@@ -108,6 +317,7 @@ impl Database {
                 data: row.get(0)?,
                 last_op_id: row.get(1)?,
                 updated_at: row.get(2)?,
+                cursor: row.get(3)?,
             })
         }) {
             Ok(snapshot) => Ok(Some(snapshot)),
@@ -117,8 +327,9 @@ impl Database {
     }
 
     pub fn save_snapshot(&self, snapshot: &Snapshot) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute(
+        let mut conn = self.conn("save_snapshot");
+        let tx = conn.transaction()?;
+        tx.execute(
             r#"
             INSERT OR REPLACE INTO snapshots (workspace_id, data, last_op_id, updated_at)
             VALUES (?1, ?2, ?3, ?4)
@@ -130,6 +341,752 @@ impl Database {
                 snapshot.updated_at,
             ],
         )?;
+        tx.execute(
+            "DELETE FROM search_index WHERE workspace_id = ?1 AND kind = 'snapshot'",
+            params![snapshot.workspace_id],
+        )?;
+        tx.execute(
+            r#"
+            INSERT INTO search_index (workspace_id, kind, op_id, content)
+            VALUES (?1, 'snapshot', NULL, ?2)
+            "#,
+            params![snapshot.workspace_id, snapshot_text(&snapshot.data)],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Best full-text matches in a workspace's ops and snapshot, each term of `query`
+    /// matched literally
+    pub fn search(&self, workspace_id: &str, query: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        let Some(query) = fts_query(query) else {
+            return Ok(Vec::new());
+        };
+        let conn = self.conn("search");
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT kind, op_id, snippet(search_index, 3, '[', ']', '…', 16)
+            FROM search_index
+            WHERE search_index MATCH ?2 AND workspace_id = ?1
+            ORDER BY rank
+            LIMIT ?3
+            "#,
+        )?;
+        let hits = stmt
+            .query_map(params![workspace_id, query, limit as i64], |row| {
+                Ok(SearchHit {
+                    kind: row.get(0)?,
+                    op_id: row.get(1)?,
+                    snippet: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(hits)
+    }
+
+    /// Create a named token, for `user`'s workspaces or (without one) every workspace,
+    /// and return it. Only its hash is stored.
+    pub fn create_token(&self, name: &str, user: Option<&str>) -> Result<String> {
+        let conn = self.conn("create_token");
+        let user_id = user.map(|user| user_id(&conn, user)).transpose()?;
+        insert_token(&conn, name, user_id)
+    }
+
+    /// Whether any token was ever created, revoked or not
+    pub fn has_tokens(&self) -> Result<bool> {
+        let conn = self.conn("has_tokens");
+        let exists =
+            conn.query_row("SELECT EXISTS (SELECT 1 FROM tokens)", [], |row| row.get(0))?;
+        Ok(exists)
+    }
+
+    /// Name and user of the active token `token`, if it is one
+    pub fn token_owner(&self, token: &str) -> Result<Option<(String, Option<String>)>> {
+        let conn = self.conn("token_owner");
+        let owner = conn
+            .query_row(
+                r#"
+                SELECT t.name, u.name FROM tokens t LEFT JOIN users u ON u.id = t.user_id
+                WHERE t.token_hash = ?1 AND t.revoked_at IS NULL
+                "#,
+                params![hash_token(token)],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        Ok(owner)
+    }
+
+    /// Create a one-time code for `sp sync login` to exchange for a token named `name`
+    /// (of `user`, if given), valid for `LOGIN_CODE_TTL_MINUTES`. Only its hash is stored.
+    pub fn create_login_code(&self, name: &str, user: Option<&str>) -> Result<String> {
+        let conn = self.conn("create_login_code");
+        let user_id = user.map(|user| user_id(&conn, user)).transpose()?;
+        let taken: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM tokens WHERE name = ?1)",
+            params![name],
+            |row| row.get(0),
+        )?;
+        if taken {
+            bail!("A token named '{name}' already exists");
+        }
+        let code: String = (0..LOGIN_CODE_LEN)
+            .map(|i| {
+                let c = LOGIN_CODE_ALPHABET[rand::random_range(0..LOGIN_CODE_ALPHABET.len())];
+                if i == LOGIN_CODE_LEN / 2 {
+                    format!("-{}", c as char)
+                } else {
+                    (c as char).to_string()
+                }
+            })
+            .collect();
+        let expires_at = chrono::Utc::now() + chrono::Duration::minutes(LOGIN_CODE_TTL_MINUTES);
+        conn.execute(
+            r#"
+            INSERT INTO login_codes (code_hash, name, expires_at, user_id)
+            VALUES (?1, ?2, ?3, ?4)
+            "#,
+            params![
+                hash_token(&normalize_code(&code)),
+                name,
+                expires_at.to_rfc3339(),
+                user_id,
+            ],
+        )?;
+        Ok(code)
+    }
+
+    /// Use up a login code, creating its token. None if the code is unknown, used or
+    /// expired.
+    pub fn redeem_login_code(&self, code: &str) -> Result<Option<(String, String)>> {
+        let mut conn = self.conn("redeem_login_code");
+        let tx = conn.transaction()?;
+        let now = chrono::Utc::now().to_rfc3339();
+        let redeemed: Option<(String, Option<i64>)> = tx
+            .query_row(
+                r#"
+                UPDATE login_codes SET used_at = ?2
+                WHERE code_hash = ?1 AND used_at IS NULL AND expires_at > ?2
+                RETURNING name, user_id
+                "#,
+                params![hash_token(&normalize_code(code)), now],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let Some((name, user_id)) = redeemed else {
+            return Ok(None);
+        };
+        let token = insert_token(&tx, &name, user_id)?;
+        tx.commit()?;
+        Ok(Some((name, token)))
+    }
+
+    /// Revoke an active token. Returns false if there is no active token with that name.
+    pub fn revoke_token(&self, name: &str) -> Result<bool> {
+        let conn = self.conn("revoke_token");
+        let revoked = conn.execute(
+            "UPDATE tokens SET revoked_at = ?2 WHERE name = ?1 AND revoked_at IS NULL",
+            params![name, chrono::Utc::now().to_rfc3339()],
+        )?;
+        Ok(revoked > 0)
+    }
+
+    pub fn list_tokens(&self) -> Result<Vec<TokenInfo>> {
+        let conn = self.conn("list_tokens");
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT t.name, t.created_at, t.revoked_at, u.name
+            FROM tokens t LEFT JOIN users u ON u.id = t.user_id
+            ORDER BY t.id ASC
+            "#,
+        )?;
+        let tokens = stmt
+            .query_map([], |row| {
+                Ok(TokenInfo {
+                    name: row.get(0)?,
+                    created_at: row.get(1)?,
+                    revoked_at: row.get(2)?,
+                    user: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(tokens)
+    }
+
+    pub fn list_workspaces(&self) -> Result<Vec<WorkspaceInfo>> {
+        let conn = self.conn("list_workspaces");
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT w.workspace_id,
+                   (SELECT COUNT(*) FROM ops o WHERE o.workspace_id = w.workspace_id),
+                   (SELECT MAX(timestamp) FROM ops o WHERE o.workspace_id = w.workspace_id),
+                   EXISTS (SELECT 1 FROM snapshots s WHERE s.workspace_id = w.workspace_id),
+                   (SELECT u.name FROM workspace_access a JOIN users u ON u.id = a.user_id
+                    WHERE a.workspace_id = w.workspace_id AND a.role = 'owner')
+            FROM (
+                SELECT workspace_id FROM ops UNION SELECT workspace_id FROM snapshots
+                UNION SELECT workspace_id FROM workspace_access
+            ) w
+            ORDER BY w.workspace_id
+            "#,
+        )?;
+        let workspaces = stmt
+            .query_map([], |row| {
+                Ok(WorkspaceInfo {
+                    workspace_id: row.get(0)?,
+                    ops: row.get(1)?,
+                    last_op_at: row.get(2)?,
+                    has_snapshot: row.get(3)?,
+                    owner: row.get(4)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(workspaces)
+    }
+
+    pub fn create_user(&self, name: &str) -> Result<()> {
+        let conn = self.conn("create_user");
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO users (name, created_at) VALUES (?1, ?2)",
+            params![name, chrono::Utc::now().to_rfc3339()],
+        )?;
+        if inserted == 0 {
+            bail!("A user named '{name}' already exists");
+        }
         Ok(())
     }
+
+    pub fn list_users(&self) -> Result<Vec<UserInfo>> {
+        let conn = self.conn("list_users");
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT u.name, u.created_at,
+                   (SELECT COUNT(*) FROM tokens t
+                    WHERE t.user_id = u.id AND t.revoked_at IS NULL),
+                   (SELECT COUNT(*) FROM workspace_access a WHERE a.user_id = u.id)
+            FROM users u
+            ORDER BY u.name
+            "#,
+        )?;
+        let users = stmt
+            .query_map([], |row| {
+                Ok(UserInfo {
+                    name: row.get(0)?,
+                    created_at: row.get(1)?,
+                    tokens: row.get(2)?,
+                    workspaces: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(users)
+    }
+
+    /// Whether `user` may use a workspace. One nobody has used yet is free to all, and
+    /// with `claim` becomes `user`'s; one with data but no users (from before there were
+    /// any) has to be shared with them first.
+    pub fn can_access(&self, workspace_id: &str, user: &str, claim: bool) -> Result<bool> {
+        let conn = self.conn("can_access");
+        let user_id = user_id(&conn, user)?;
+        let (allowed, claimed): (bool, bool) = conn.query_row(
+            r#"
+            SELECT EXISTS (SELECT 1 FROM workspace_access WHERE workspace_id = ?1 AND user_id = ?2),
+                   EXISTS (SELECT 1 FROM workspace_access WHERE workspace_id = ?1)
+            "#,
+            params![workspace_id, user_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        if allowed || claimed {
+            return Ok(allowed);
+        }
+        let used: bool = conn.query_row(
+            r#"
+            SELECT EXISTS (SELECT 1 FROM ops WHERE workspace_id = ?1)
+                OR EXISTS (SELECT 1 FROM snapshots WHERE workspace_id = ?1)
+            "#,
+            params![workspace_id],
+            |row| row.get(0),
+        )?;
+        if used {
+            return Ok(false);
+        }
+        if claim {
+            conn.execute(
+                "INSERT INTO workspace_access (workspace_id, user_id, role) VALUES (?1, ?2, 'owner')",
+                params![workspace_id, user_id],
+            )?;
+        }
+        Ok(true)
+    }
+
+    /// Let `user` use a workspace; the first user it's shared with owns it. Returns
+    /// false if they already could.
+    pub fn share_workspace(&self, workspace_id: &str, user: &str) -> Result<bool> {
+        let conn = self.conn("share_workspace");
+        let user_id = user_id(&conn, user)?;
+        let added = conn.execute(
+            r#"
+            INSERT OR IGNORE INTO workspace_access (workspace_id, user_id, role)
+            SELECT ?1, ?2, CASE WHEN EXISTS
+                (SELECT 1 FROM workspace_access WHERE workspace_id = ?1) THEN 'member' ELSE 'owner' END
+            "#,
+            params![workspace_id, user_id],
+        )?;
+        Ok(added > 0)
+    }
+
+    /// Take a workspace away from `user`. Returns false if they couldn't use it.
+    pub fn unshare_workspace(&self, workspace_id: &str, user: &str) -> Result<bool> {
+        let conn = self.conn("unshare_workspace");
+        let user_id = user_id(&conn, user)?;
+        let removed = conn.execute(
+            "DELETE FROM workspace_access WHERE workspace_id = ?1 AND user_id = ?2",
+            params![workspace_id, user_id],
+        )?;
+        Ok(removed > 0)
+    }
+
+    /// Remember the name of the device with id `client_id`
+    pub fn record_device(&self, client_id: &str, name: &str) -> Result<()> {
+        let conn = self.conn("record_device");
+        conn.execute(
+            r#"
+            INSERT INTO devices (client_id, name, last_seen) VALUES (?1, ?2, ?3)
+            ON CONFLICT(client_id) DO UPDATE SET name = ?2, last_seen = ?3
+            "#,
+            params![client_id, name, chrono::Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Devices that made a workspace's ops, most recently active first
+    pub fn list_devices(&self, workspace_id: &str) -> Result<Vec<DeviceInfo>> {
+        let conn = self.conn("list_devices");
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT o.client_id, d.name, COUNT(*), MAX(o.timestamp)
+            FROM ops o LEFT JOIN devices d ON d.client_id = o.client_id
+            WHERE o.workspace_id = ?1 AND o.client_id IS NOT NULL
+            GROUP BY o.client_id
+            ORDER BY MAX(o.id) DESC
+            "#,
+        )?;
+        let devices = stmt
+            .query_map(params![workspace_id], |row| {
+                Ok(DeviceInfo {
+                    client_id: row.get(0)?,
+                    name: row.get(1)?,
+                    ops: row.get(2)?,
+                    last_op_at: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(devices)
+    }
+
+    /// Delete ops up to each snapshot's `last_op_id` (optionally for one workspace), then
+    /// reclaim the space. Returns how many ops were deleted.
+    pub fn compact(&self, workspace_id: Option<&str>) -> Result<usize> {
+        let mut conn = self.conn("compact");
+        let tx = conn.transaction()?;
+        let covered: Vec<(String, i64)> = {
+            let mut stmt = tx.prepare(
+                r#"
+                SELECT s.workspace_id, o.id
+                FROM snapshots s
+                JOIN ops o ON o.workspace_id = s.workspace_id AND o.op_id = s.last_op_id
+                WHERE ?1 IS NULL OR s.workspace_id = ?1
+                "#,
+            )?;
+            stmt.query_map(params![workspace_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        let mut deleted = 0;
+        for (workspace_id, up_to) in covered {
+            tx.execute(
+                r#"
+                DELETE FROM search_index
+                WHERE kind = 'op' AND workspace_id = ?1 AND op_id IN
+                    (SELECT op_id FROM ops WHERE workspace_id = ?1 AND id <= ?2)
+                "#,
+                params![workspace_id, up_to],
+            )?;
+            deleted += tx.execute(
+                "DELETE FROM ops WHERE workspace_id = ?1 AND id <= ?2",
+                params![workspace_id, up_to],
+            )?;
+        }
+        tx.commit()?;
+        conn.execute_batch(
+            r#"
+            INSERT INTO search_index (search_index) VALUES ('optimize');
+            VACUUM;
+            "#,
+        )?;
+        Ok(deleted)
+    }
+
+    /// Snapshot and full op log of a workspace
+    pub fn export(&self, workspace_id: &str) -> Result<WorkspaceExport> {
+        Ok(WorkspaceExport {
+            workspace_id: workspace_id.to_string(),
+            snapshot: self.get_snapshot(workspace_id)?,
+            ops: self.get_ops(workspace_id, None, None)?,
+            blobs: Vec::new(),
+        })
+    }
+
+    /// Note that `workspace_id` has the blob `hash`
+    pub fn add_blob(&self, workspace_id: &str, hash: &str, size: usize) -> Result<()> {
+        let conn = self.conn("add_blob");
+        conn.execute(
+            "INSERT OR IGNORE INTO blobs (workspace_id, hash, size, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![workspace_id, hash, size as i64, chrono::Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    pub fn has_blob(&self, workspace_id: &str, hash: &str) -> Result<bool> {
+        let conn = self.conn("has_blob");
+        let found = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM blobs WHERE workspace_id = ?1 AND hash = ?2)",
+            params![workspace_id, hash],
+            |row| row.get(0),
+        )?;
+        Ok(found)
+    }
+
+    /// Hashes of the blobs `workspace_id` has
+    pub fn list_blobs(&self, workspace_id: &str) -> Result<Vec<String>> {
+        let conn = self.conn("list_blobs");
+        let mut stmt =
+            conn.prepare("SELECT hash FROM blobs WHERE workspace_id = ?1 ORDER BY hash")?;
+        let hashes = stmt
+            .query_map(params![workspace_id], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        Ok(hashes)
+    }
+
+    pub fn has_workspace(&self, workspace_id: &str) -> Result<bool> {
+        let conn = self.conn("has_workspace");
+        let found = conn
+            .query_row(
+                r#"
+                SELECT 1 FROM ops WHERE workspace_id = ?1
+                UNION SELECT 1 FROM snapshots WHERE workspace_id = ?1
+                LIMIT 1
+                "#,
+                params![workspace_id],
+                |_| Ok(()),
+            )
+            .optional()?;
+        Ok(found.is_some())
+    }
+}
+
+/// What of an op is worth searching: the text of a `file.put`. Chunks, blobs,
+/// deletes and sealed payloads hold nothing readable.
+fn searchable_text(op: &Op) -> Option<String> {
+    #[derive(Deserialize)]
+    struct FilePut {
+        content: Option<String>,
+    }
+    if op.op_type != "file.put" {
+        return None;
+    }
+    serde_json::from_str::<FilePut>(&op.payload).ok()?.content
+}
+
+/// The searchable text of a snapshot's ops (its `data` is the ops as a JSON array)
+fn snapshot_text(data: &str) -> String {
+    serde_json::from_str::<Vec<Op>>(data)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(searchable_text)
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Why an op can't be stored, if it can't
+fn invalid_op(op: &Op) -> Option<&'static str> {
+    if op.id.trim().is_empty() {
+        Some("missing id")
+    } else if op.op_type.trim().is_empty() {
+        Some("missing op_type")
+    } else if chrono::DateTime::parse_from_rfc3339(&op.timestamp).is_err() {
+        Some("timestamp is not RFC 3339")
+    } else {
+        None
+    }
+}
+
+fn user_id(conn: &Connection, name: &str) -> Result<i64> {
+    let id = conn
+        .query_row(
+            "SELECT id FROM users WHERE name = ?1",
+            params![name],
+            |row| row.get(0),
+        )
+        .optional()?;
+    id.ok_or_else(|| anyhow::anyhow!("No user named '{name}'"))
+}
+
+fn insert_token(conn: &Connection, name: &str, user_id: Option<i64>) -> Result<String> {
+    let token: String = rand::random::<[u8; 32]>()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    let inserted = conn.execute(
+        r#"
+        INSERT OR IGNORE INTO tokens (name, token_hash, created_at, user_id)
+        VALUES (?1, ?2, ?3, ?4)
+        "#,
+        params![
+            name,
+            hash_token(&token),
+            chrono::Utc::now().to_rfc3339(),
+            user_id
+        ],
+    )?;
+    if inserted == 0 {
+        bail!("A token named '{name}' already exists");
+    }
+    Ok(token)
+}
+
+/// A login code as typed: case and dashes don't matter
+fn normalize_code(code: &str) -> String {
+    code.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Hex SHA-256 of a token, as stored in the `tokens` table
+fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// FTS5 query requiring every whitespace-separated term, quoted so that operators and
+/// punctuation in user input are taken literally
+fn fts_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Database {
+        let db = Database::open(":memory:").unwrap();
+        db.init().unwrap();
+        db
+    }
+
+    fn op(id: &str, op_type: &str, payload: serde_json::Value) -> Op {
+        Op {
+            db_id: None,
+            id: id.to_string(),
+            op_type: op_type.to_string(),
+            payload: payload.to_string(),
+            timestamp: "2026-10-16T12:00:00Z".to_string(),
+            client_id: None,
+        }
+    }
+
+    #[test]
+    fn search_finds_note_text_only() {
+        let db = test_db();
+        let ops = [
+            op(
+                "put",
+                "file.put",
+                serde_json::json!({"path": "s/notes.md", "content": "deploy the falcon"}),
+            ),
+            op(
+                "chunk",
+                "chunk.put",
+                serde_json::json!({"hash": "abc", "data": "ZmFsY29u"}),
+            ),
+            op(
+                "blob",
+                "blob.put",
+                serde_json::json!({"hash": "def", "data": "ZmFsY29u"}),
+            ),
+            op(
+                "sealed",
+                "file.put",
+                serde_json::json!({"sealed": "ZmFsY29u"}),
+            ),
+            op(
+                "delete",
+                "file.delete",
+                serde_json::json!({"path": "s/falcon.md"}),
+            ),
+        ];
+        db.push_ops("ws", &ops).unwrap();
+
+        let hits = db.search("ws", "falcon", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].op_id.as_deref(), Some("put"));
+        assert!(hits[0].snippet.contains("[falcon]"));
+        // Neither payload keys nor base64 are indexed
+        assert!(db.search("ws", "ZmFsY29u", 10).unwrap().is_empty());
+        assert!(db.search("ws", "path", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn search_finds_snapshot_text_only() {
+        let db = test_db();
+        let ops = [
+            op(
+                "put",
+                "file.put",
+                serde_json::json!({"path": "s/notes.md", "content": "land the heron"}),
+            ),
+            op(
+                "chunk",
+                "chunk.put",
+                serde_json::json!({"hash": "abc", "data": "aGVyb24="}),
+            ),
+        ];
+        db.save_snapshot(&Snapshot {
+            workspace_id: "ws".to_string(),
+            data: serde_json::to_string(&ops).unwrap(),
+            last_op_id: None,
+            updated_at: "2026-10-16T12:00:00Z".to_string(),
+            cursor: None,
+        })
+        .unwrap();
+
+        let hits = db.search("ws", "heron", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].op_id, None);
+        assert!(db.search("ws", "aGVyb24", 10).unwrap().is_empty());
+        assert!(db.search("ws", "path", 10).unwrap().is_empty());
+        assert!(db.search("ws", "put", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn repushed_ops_are_duplicates_not_accepted() {
+        let db = test_db();
+        let ops = [op(
+            "put",
+            "file.put",
+            serde_json::json!({"path": "s/notes.md", "content": "once"}),
+        )];
+        let accepted = |results: &[OpResult]| {
+            results
+                .iter()
+                .filter(|r| r.status == OpStatus::Accepted)
+                .count()
+        };
+        assert_eq!(accepted(&db.push_ops("ws", &ops).unwrap()), 1);
+        let again = db.push_ops("ws", &ops).unwrap();
+        assert_eq!(accepted(&again), 0);
+        assert_eq!(again[0].status, OpStatus::Duplicate);
+        assert_eq!(db.get_ops("ws", None, None).unwrap().len(), 1);
+        assert_eq!(db.search("ws", "once", 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn first_push_claims_a_workspace_for_its_user() {
+        let db = test_db();
+        db.create_user("alice").unwrap();
+        db.create_user("bob").unwrap();
+
+        // Reading an unused workspace doesn't claim it
+        assert!(db.can_access("ws", "bob", false).unwrap());
+        assert!(db.can_access("ws", "alice", true).unwrap());
+        assert!(db.can_access("ws", "alice", false).unwrap());
+        assert!(!db.can_access("ws", "bob", false).unwrap());
+        assert!(!db.can_access("ws", "bob", true).unwrap());
+
+        assert!(db.share_workspace("ws", "bob").unwrap());
+        assert!(db.can_access("ws", "bob", true).unwrap());
+        assert!(db.unshare_workspace("ws", "bob").unwrap());
+        assert!(!db.can_access("ws", "bob", false).unwrap());
+
+        // Data from before users existed has to be shared first
+        db.push_ops(
+            "legacy",
+            &[op("a", "file.delete", serde_json::json!({"path": "s/x"}))],
+        )
+        .unwrap();
+        assert!(!db.can_access("legacy", "alice", true).unwrap());
+        assert!(db.can_access("nobody-uses-this", "carol", false).is_err());
+    }
+
+    #[test]
+    fn revoked_tokens_have_no_owner() {
+        let db = test_db();
+        db.create_user("alice").unwrap();
+        let token = db.create_token("laptop", Some("alice")).unwrap();
+        assert_eq!(
+            db.token_owner(&token).unwrap(),
+            Some(("laptop".to_string(), Some("alice".to_string())))
+        );
+        assert_eq!(db.token_owner("not-a-token").unwrap(), None);
+
+        assert!(db.revoke_token("laptop").unwrap());
+        assert_eq!(db.token_owner(&token).unwrap(), None);
+        assert!(!db.revoke_token("laptop").unwrap());
+        // Revoking every token doesn't reopen the server
+        assert!(db.has_tokens().unwrap());
+    }
+
+    #[test]
+    fn exports_round_trip_through_an_archive() {
+        let db = test_db();
+        let ops = [
+            op(
+                "a",
+                "file.put",
+                serde_json::json!({"path": "s/notes.md", "content": "one"}),
+            ),
+            op("b", "file.delete", serde_json::json!({"path": "s/old.md"})),
+        ];
+        db.push_ops("ws", &ops).unwrap();
+        db.save_snapshot(&Snapshot {
+            workspace_id: "ws".to_string(),
+            data: serde_json::to_string(&ops[..1]).unwrap(),
+            last_op_id: Some("a".to_string()),
+            updated_at: "2026-10-16T12:00:00Z".to_string(),
+            cursor: None,
+        })
+        .unwrap();
+        let mut export = db.export("ws").unwrap();
+        export.blobs.push(("ab".repeat(32), b"\x00bytes".to_vec()));
+
+        let read = crate::archive::read(&crate::archive::write(&export).unwrap()).unwrap();
+        assert_eq!(read.workspace_id, "ws");
+        assert_eq!(read.blobs, export.blobs);
+
+        // Loaded elsewhere as the import endpoint does
+        let other = test_db();
+        let results = other.push_ops("copy", &read.ops).unwrap();
+        assert!(results.iter().all(|r| r.status == OpStatus::Accepted));
+        let mut snapshot = read.snapshot.unwrap();
+        snapshot.workspace_id = "copy".to_string();
+        other.save_snapshot(&snapshot).unwrap();
+
+        let copied = other.export("copy").unwrap();
+        let ids = |export: &WorkspaceExport| -> Vec<(String, String, String)> {
+            export
+                .ops
+                .iter()
+                .map(|op| (op.id.clone(), op.op_type.clone(), op.payload.clone()))
+                .collect()
+        };
+        assert_eq!(ids(&copied), ids(&export));
+        let snapshot = copied.snapshot.unwrap();
+        assert_eq!(snapshot.data, export.snapshot.unwrap().data);
+        assert_eq!(snapshot.last_op_id.as_deref(), Some("a"));
+        assert_eq!(other.search("copy", "one", 10).unwrap().len(), 2);
+    }
 }
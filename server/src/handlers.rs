@@ -1,67 +1,173 @@
 use std::collections::HashSet;
+use std::net::SocketAddr;
 use std::sync::Arc;
 
-use axum::extract::ws::{Message, WebSocket};
+use axum::extract::ws::{CloseFrame, Message, WebSocket, close_code};
 use axum::{
-    Json,
-    extract::{Path, Query, State, WebSocketUpgrade},
-    http::StatusCode,
+    Extension, Json,
+    body::Bytes,
+    extract::{ConnectInfo, Path, Query, State, WebSocketUpgrade},
+    http::{HeaderMap, StatusCode, header},
     response::{IntoResponse, Response},
 };
 use futures::{SinkExt, StreamExt};
-use tokio::sync::RwLock;
+use scratchpad_protocol::{
+    GetOpsQuery, ImportResponse, LoginRequest, LoginResponse, Op, OpResult, OpStatus,
+    PresenceClient, PushOpsRequest, PushOpsResponse, SearchHit, SearchQuery, Snapshot,
+    WhoAmIResponse, WsMessage,
+};
+use tokio::sync::{RwLock, broadcast::error::RecvError, mpsc};
 
 use crate::AppState;
-use crate::models::{GetOpsQuery, Op, PushOpsRequest, PushOpsResponse, Snapshot, WsMessage};
+use crate::archive;
+use crate::auth::{self, Caller};
+use crate::blobs;
+use crate::limits;
+
+/// Largest archive accepted by `/api/import`
+pub const MAX_IMPORT_BYTES: usize = 256 * 1024 * 1024;
+
+/// Largest blob accepted by `/api/blobs`
+pub const MAX_BLOB_BYTES: usize = 100 * 1024 * 1024;
+
+/// Results returned by `/api/search` when no `limit` is given, and the most allowed
+const DEFAULT_SEARCH_LIMIT: usize = 20;
+const MAX_SEARCH_LIMIT: usize = 200;
 
 pub async fn health() -> &'static str {
     "ok"
 }
 
+/// Counters and database latencies for Prometheus
+pub async fn metrics(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(&state.db.latency),
+    )
+}
+
 pub async fn push_ops(
     State(state): State<Arc<AppState>>,
+    Extension(caller): Extension<Caller>,
     Json(req): Json<PushOpsRequest>,
 ) -> Result<Json<PushOpsResponse>, (StatusCode, String)> {
-    let mut accepted = 0;
-
-    for op in &req.ops {
-        match state.db.push_op(&req.workspace_id, op) {
-            Ok(_) => {
-                accepted += 1;
-                let msg = WsMessage {
-                    msg_type: "op".to_string(),
-                    workspace_id: Some(req.workspace_id.clone()),
-                    ops: Some(vec![op.clone()]),
-                    error: None,
-                };
-                if let Ok(json) = serde_json::to_string(&msg) {
-                    let _ = state.tx.send(json);
-                }
-            }
-            Err(e) => {
-                tracing::warn!("Failed to push op: {e}");
-            }
+    auth::authorize(&state, &caller, &req.workspace_id, true)?;
+    state.limits.check_push(req.ops.len())?;
+    if let Some(name) = &req.client_name {
+        let ids: HashSet<&str> = req
+            .ops
+            .iter()
+            .filter_map(|op| op.client_id.as_deref())
+            .collect();
+        for id in ids {
+            state
+                .db
+                .record_device(id, name)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
         }
     }
+    let results = state
+        .db
+        .push_ops(&req.workspace_id, &req.ops)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    state.metrics.record_push(&results);
+    let accepted = broadcast_accepted(&state, &req.workspace_id, req.ops, &results);
+    Ok(Json(PushOpsResponse { accepted, results }))
+}
+
+/// Broadcast the ops `results` marks as newly stored. Returns how many there were.
+fn broadcast_accepted(
+    state: &AppState,
+    workspace_id: &str,
+    ops: Vec<Op>,
+    results: &[OpResult],
+) -> usize {
+    let new_ops: Vec<Op> = ops
+        .into_iter()
+        .zip(results)
+        .filter_map(|(op, result)| (result.status == OpStatus::Accepted).then_some(op))
+        .collect();
+    let accepted = new_ops.len();
+    broadcast_ops(state, workspace_id, new_ops);
+    accepted
+}
+
+/// Announce newly stored ops to WebSocket subscribers of the workspace
+fn broadcast_ops(state: &AppState, workspace_id: &str, ops: Vec<Op>) {
+    for op in ops {
+        broadcast(
+            state,
+            WsMessage {
+                msg_type: "op".to_string(),
+                workspace_id: Some(workspace_id.to_string()),
+                ops: Some(vec![op]),
+                ..Default::default()
+            },
+        );
+    }
+}
+
+/// Send `msg` to every connection subscribed to its workspace
+fn broadcast(state: &AppState, msg: WsMessage) {
+    if let Ok(json) = serde_json::to_string(&msg) {
+        let _ = state.tx.send(json);
+    }
+}
+
+/// Add a subscribed connection to the workspace's presence, announcing it if new
+fn join_presence(state: &AppState, workspace_id: &str, client: PresenceClient) {
+    if state.presence.join(workspace_id, client.clone()) {
+        broadcast_presence(state, "join", workspace_id, client);
+    }
+}
+
+/// Remove a connection from the workspace's presence, announcing it if it was there
+fn leave_presence(state: &AppState, workspace_id: &str, connection_id: &str) {
+    if let Some(client) = state.presence.leave(workspace_id, connection_id) {
+        broadcast_presence(state, "leave", workspace_id, client);
+    }
+}
 
-    Ok(Json(PushOpsResponse { accepted }))
+/// Announce a client joining (`join`) or leaving (`leave`) a workspace
+fn broadcast_presence(
+    state: &AppState,
+    msg_type: &str,
+    workspace_id: &str,
+    client: PresenceClient,
+) {
+    broadcast(
+        state,
+        WsMessage {
+            msg_type: msg_type.to_string(),
+            workspace_id: Some(workspace_id.to_string()),
+            client: Some(client),
+            ..Default::default()
+        },
+    );
 }
 
 pub async fn get_ops(
     State(state): State<Arc<AppState>>,
+    Extension(caller): Extension<Caller>,
     Path(workspace_id): Path<String>,
     Query(query): Query<GetOpsQuery>,
 ) -> Result<Json<Vec<Op>>, (StatusCode, String)> {
-    match state.db.get_ops(&workspace_id, query.after) {
-        Ok(ops) => Ok(Json(ops)),
+    auth::authorize(&state, &caller, &workspace_id, false)?;
+    match state.db.get_ops(&workspace_id, query.after, query.limit) {
+        Ok(ops) => {
+            state.metrics.record_fetch(ops.len());
+            Ok(Json(ops))
+        }
         Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
     }
 }
 
 pub async fn get_snapshot(
     State(state): State<Arc<AppState>>,
+    Extension(caller): Extension<Caller>,
     Path(workspace_id): Path<String>,
 ) -> Result<Response, (StatusCode, String)> {
+    auth::authorize(&state, &caller, &workspace_id, false)?;
     match state.db.get_snapshot(&workspace_id) {
         Ok(Some(snapshot)) => Ok(Json(snapshot).into_response()),
         Ok(None) => Ok(StatusCode::NOT_FOUND.into_response()),
@@ -71,9 +177,11 @@ pub async fn get_snapshot(
 
 pub async fn save_snapshot(
     State(state): State<Arc<AppState>>,
+    Extension(caller): Extension<Caller>,
     Path(workspace_id): Path<String>,
     Json(mut snapshot): Json<Snapshot>,
 ) -> Result<StatusCode, (StatusCode, String)> {
+    auth::authorize(&state, &caller, &workspace_id, true)?;
     snapshot.workspace_id = workspace_id;
     match state.db.save_snapshot(&snapshot) {
         Ok(_) => Ok(StatusCode::OK),
@@ -81,23 +189,264 @@ pub async fn save_snapshot(
     }
 }
 
+pub async fn search(
+    State(state): State<Arc<AppState>>,
+    Extension(caller): Extension<Caller>,
+    Path(workspace_id): Path<String>,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<Vec<SearchHit>>, (StatusCode, String)> {
+    auth::authorize(&state, &caller, &workspace_id, false)?;
+    if query.q.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Missing search query".to_string()));
+    }
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .clamp(1, MAX_SEARCH_LIMIT);
+    match state.db.search(&workspace_id, &query.q, limit) {
+        Ok(hits) => Ok(Json(hits)),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+/// Store a blob for the workspace. Its hash must match the body; storing one the server
+/// already has, from any workspace, only records that this workspace has it too.
+pub async fn put_blob(
+    State(state): State<Arc<AppState>>,
+    Extension(caller): Extension<Caller>,
+    Path((workspace_id, hash)): Path<(String, String)>,
+    body: Bytes,
+) -> Result<StatusCode, (StatusCode, String)> {
+    auth::authorize(&state, &caller, &workspace_id, true)?;
+    if !blobs::is_hash(&hash) {
+        return Err((StatusCode::BAD_REQUEST, "Not a SHA-256 hash".to_string()));
+    }
+    let stored = state
+        .blobs
+        .put(&hash, &body)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("{e:#}")))?;
+    state
+        .db
+        .add_blob(&workspace_id, &hash, body.len())
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(if stored {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    })
+}
+
+/// A blob the workspace uploaded
+pub async fn get_blob(
+    State(state): State<Arc<AppState>>,
+    Extension(caller): Extension<Caller>,
+    Path((workspace_id, hash)): Path<(String, String)>,
+) -> Result<Response, (StatusCode, String)> {
+    auth::authorize(&state, &caller, &workspace_id, false)?;
+    let internal = |e: anyhow::Error| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    if !state.db.has_blob(&workspace_id, &hash).map_err(internal)? {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+    match state.blobs.get(&hash).map_err(internal)? {
+        Some(data) => {
+            let headers = [(header::CONTENT_TYPE, "application/octet-stream")];
+            Ok((headers, data).into_response())
+        }
+        None => Ok(StatusCode::NOT_FOUND.into_response()),
+    }
+}
+
+/// The workspace as a tar archive (see `archive.rs`)
+pub async fn export(
+    State(state): State<Arc<AppState>>,
+    Extension(caller): Extension<Caller>,
+    Path(workspace_id): Path<String>,
+) -> Result<Response, (StatusCode, String)> {
+    auth::authorize(&state, &caller, &workspace_id, false)?;
+    let internal = |e: anyhow::Error| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    if !state.db.has_workspace(&workspace_id).map_err(internal)? {
+        return Err((StatusCode::NOT_FOUND, "Workspace not found".to_string()));
+    }
+    let mut export = state.db.export(&workspace_id).map_err(internal)?;
+    for hash in state.db.list_blobs(&workspace_id).map_err(internal)? {
+        // A blob whose file went missing can't be exported, but the rest still can
+        match state.blobs.get(&hash).map_err(internal)? {
+            Some(data) => export.blobs.push((hash, data)),
+            None => tracing::warn!("Blob {hash} of {workspace_id} is missing on disk"),
+        }
+    }
+    let tar = archive::write(&export).map_err(internal)?;
+
+    let file_name: String = workspace_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "-_.".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let headers = [
+        (header::CONTENT_TYPE, "application/x-tar".to_string()),
+        (
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{file_name}.tar\""),
+        ),
+    ];
+    Ok((headers, tar).into_response())
+}
+
+/// Load an archive from `/api/export` into `workspace_id`, which may differ from the
+/// workspace it was exported from. Ops already stored are reported as duplicates.
+pub async fn import(
+    State(state): State<Arc<AppState>>,
+    Extension(caller): Extension<Caller>,
+    Path(workspace_id): Path<String>,
+    body: Bytes,
+) -> Result<Json<ImportResponse>, (StatusCode, String)> {
+    auth::authorize(&state, &caller, &workspace_id, true)?;
+    let internal = |e: anyhow::Error| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    let export = archive::read(&body).map_err(|e| (StatusCode::BAD_REQUEST, format!("{e:#}")))?;
+    for (hash, data) in &export.blobs {
+        state
+            .blobs
+            .put(hash, data)
+            .map_err(|e| (StatusCode::BAD_REQUEST, format!("{e:#}")))?;
+        state
+            .db
+            .add_blob(&workspace_id, hash, data.len())
+            .map_err(internal)?;
+    }
+
+    // Not broadcast: an import can be far larger than subscribers' channel buffer, and
+    // clients catch up through `/api/ops` anyway
+    let results = state
+        .db
+        .push_ops(&workspace_id, &export.ops)
+        .map_err(internal)?;
+    let accepted = results
+        .iter()
+        .filter(|r| r.status == OpStatus::Accepted)
+        .count();
+
+    let mut snapshot_restored = false;
+    if let Some(mut snapshot) = export.snapshot
+        && state
+            .db
+            .get_snapshot(&workspace_id)
+            .map_err(internal)?
+            .is_none()
+    {
+        snapshot.workspace_id = workspace_id;
+        state.db.save_snapshot(&snapshot).map_err(internal)?;
+        snapshot_restored = true;
+    }
+
+    Ok(Json(ImportResponse {
+        accepted,
+        results,
+        snapshot_restored,
+    }))
+}
+
+/// Exchange a one-time login code for a token
+pub async fn login(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<LoginRequest>,
+) -> Result<Json<LoginResponse>, (StatusCode, String)> {
+    match state.db.redeem_login_code(&req.code) {
+        Ok(Some((name, token))) => Ok(Json(LoginResponse { token, name })),
+        Ok(None) => Err((
+            StatusCode::UNAUTHORIZED,
+            "Unknown, used or expired login code".to_string(),
+        )),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+/// Name of the request's bearer token, to check it's valid
+pub async fn whoami(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<WhoAmIResponse>, (StatusCode, String)> {
+    let token = auth::bearer_token(&headers)
+        .ok_or((StatusCode::UNAUTHORIZED, "No bearer token".to_string()))?;
+    match state.auth.caller(&state.db, token) {
+        Ok(Some(caller)) => Ok(Json(WhoAmIResponse { name: caller.name })),
+        Ok(None) => Err((
+            StatusCode::UNAUTHORIZED,
+            "Unknown or revoked token".to_string(),
+        )),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+pub async fn presence(
+    State(state): State<Arc<AppState>>,
+    Extension(caller): Extension<Caller>,
+    Path(workspace_id): Path<String>,
+) -> Result<Json<Vec<PresenceClient>>, (StatusCode, String)> {
+    auth::authorize(&state, &caller, &workspace_id, false)?;
+    Ok(Json(state.presence.list(&workspace_id)))
+}
+
 pub async fn websocket_handler(
     ws: WebSocketUpgrade,
     State(state): State<Arc<AppState>>,
+    Extension(caller): Extension<Caller>,
+    connect: Option<Extension<ConnectInfo<SocketAddr>>>,
+    headers: HeaderMap,
 ) -> Response {
-    ws.on_upgrade(move |socket| handle_socket(socket, state))
+    // Pushes over the socket count against the same allowance as HTTP requests
+    let client = limits::client_key(
+        Some(&caller),
+        &headers,
+        connect.map(|Extension(info)| info.0),
+    );
+    ws.on_upgrade(move |socket| handle_socket(socket, state, caller, client))
 }
 
-async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
+async fn handle_socket(socket: WebSocket, state: Arc<AppState>, caller: Caller, client: String) {
     let (mut sender, mut receiver) = socket.split();
     let mut rx = state.tx.subscribe();
+    state.metrics.ws_connected();
+    // Messages for this client only, such as push acks
+    let (reply_tx, mut reply_rx) = mpsc::unbounded_channel::<String>();
 
     let subscribed_workspaces = Arc::new(RwLock::new(HashSet::new()));
+    let connection_id = uuid::Uuid::new_v4().to_string();
 
     let send_task = {
         let subscribed_workspaces = Arc::clone(&subscribed_workspaces);
+        let mut shutdown = state.shutdown.clone();
+        let state = Arc::clone(&state);
         tokio::spawn(async move {
-            while let Ok(msg) = rx.recv().await {
+            loop {
+                let msg = tokio::select! {
+                    msg = rx.recv() => match msg {
+                        Ok(msg) => msg,
+                        Err(RecvError::Lagged(missed)) => {
+                            state.metrics.record_lag(missed);
+                            break;
+                        }
+                        Err(RecvError::Closed) => break,
+                    },
+                    Some(reply) = reply_rx.recv() => {
+                        if sender.send(Message::Text(reply.into())).await.is_err() {
+                            break;
+                        }
+                        continue;
+                    }
+                    _ = shutdown.changed() => {
+                        let frame = CloseFrame {
+                            code: close_code::AWAY,
+                            reason: "server shutting down".into(),
+                        };
+                        let _ = sender.send(Message::Close(Some(frame))).await;
+                        break;
+                    }
+                };
                 let should_send = match serde_json::from_str::<WsMessage>(&msg) {
                     Ok(ws_msg) => {
                         if let Some(id) = ws_msg.workspace_id.as_ref() {
@@ -116,41 +465,218 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
         })
     };
 
-    while let Some(Ok(msg)) = receiver.next().await {
-        if let Message::Text(text) = msg {
-            if let Ok(ws_msg) = serde_json::from_str::<WsMessage>(&text) {
-                match ws_msg.msg_type.as_str() {
-                    "subscribe" => {
-                        if let Some(workspace_id) = ws_msg.workspace_id {
-                            subscribed_workspaces.write().await.insert(workspace_id);
+    // A push being handled when shutdown starts is finished before the loop exits
+    let mut shutdown = state.shutdown.clone();
+    loop {
+        let msg = tokio::select! {
+            msg = receiver.next() => msg,
+            _ = shutdown.changed() => break,
+        };
+        let Some(Ok(msg)) = msg else {
+            break;
+        };
+        if let Message::Text(text) = msg
+            && let Ok(ws_msg) = serde_json::from_str::<WsMessage>(&text)
+        {
+            if let Some(workspace_id) = &ws_msg.workspace_id
+                && let Err((_, error)) =
+                    auth::authorize(&state, &caller, workspace_id, ws_msg.msg_type == "push")
+            {
+                let reply = WsMessage {
+                    msg_type: "error".to_string(),
+                    workspace_id: Some(workspace_id.clone()),
+                    error: Some(error),
+                    ..Default::default()
+                };
+                if let Ok(json) = serde_json::to_string(&reply) {
+                    let _ = reply_tx.send(json);
+                }
+                continue;
+            }
+            match ws_msg.msg_type.as_str() {
+                "subscribe" => {
+                    if let Some(workspace_id) = ws_msg.workspace_id {
+                        subscribed_workspaces
+                            .write()
+                            .await
+                            .insert(workspace_id.clone());
+                        if let (Some(id), Some(name)) = (&ws_msg.client_id, &ws_msg.client_name)
+                            && let Err(e) = state.db.record_device(id, name)
+                        {
+                            tracing::warn!("Failed to record device {id}: {e}");
                         }
+                        let client = PresenceClient {
+                            connection_id: connection_id.clone(),
+                            client_id: ws_msg.client_id,
+                            name: ws_msg.client_name,
+                            since: chrono::Utc::now().to_rfc3339(),
+                        };
+                        join_presence(&state, &workspace_id, client);
                     }
-                    "unsubscribe" => {
-                        if let Some(workspace_id) = ws_msg.workspace_id {
-                            subscribed_workspaces.write().await.remove(&workspace_id);
-                        }
+                }
+                "unsubscribe" => {
+                    if let Some(workspace_id) = ws_msg.workspace_id {
+                        subscribed_workspaces.write().await.remove(&workspace_id);
+                        leave_presence(&state, &workspace_id, &connection_id);
                     }
-                    "push" => {
-                        if let (Some(workspace_id), Some(ops)) = (ws_msg.workspace_id, ws_msg.ops) {
-                            for op in ops {
-                                let _ = state.db.push_op(&workspace_id, &op);
-                                let broadcast_msg = WsMessage {
-                                    msg_type: "op".to_string(),
-                                    workspace_id: Some(workspace_id.clone()),
-                                    ops: Some(vec![op]),
-                                    error: None,
-                                };
-                                if let Ok(json) = serde_json::to_string(&broadcast_msg) {
-                                    let _ = state.tx.send(json);
-                                }
+                }
+                "push" => {
+                    if let (Some(workspace_id), Some(ops)) = (ws_msg.workspace_id, ws_msg.ops) {
+                        let refused = match state.limits.check(&client) {
+                            Err(wait) => {
+                                state.metrics.record_rate_limited();
+                                Some(format!(
+                                    "Too many requests, retry in {}s",
+                                    wait.as_secs().max(1)
+                                ))
                             }
+                            Ok(()) => state.limits.check_push(ops.len()).err().map(|(_, e)| e),
+                        };
+                        let ack = match refused {
+                            Some(error) => WsMessage {
+                                msg_type: "error".to_string(),
+                                workspace_id: Some(workspace_id),
+                                error: Some(error),
+                                ..Default::default()
+                            },
+                            None => match state.db.push_ops(&workspace_id, &ops) {
+                                Ok(results) => {
+                                    state.metrics.record_push(&results);
+                                    broadcast_accepted(&state, &workspace_id, ops, &results);
+                                    WsMessage {
+                                        msg_type: "ack".to_string(),
+                                        workspace_id: Some(workspace_id),
+                                        results: Some(results),
+                                        ..Default::default()
+                                    }
+                                }
+                                Err(e) => {
+                                    tracing::warn!("Failed to push ops: {e}");
+                                    WsMessage {
+                                        msg_type: "error".to_string(),
+                                        workspace_id: Some(workspace_id),
+                                        error: Some(e.to_string()),
+                                        ..Default::default()
+                                    }
+                                }
+                            },
+                        };
+                        if let Ok(json) = serde_json::to_string(&ack) {
+                            let _ = reply_tx.send(json);
                         }
                     }
-                    _ => {}
                 }
+                _ => {}
             }
         }
     }
 
-    send_task.abort();
+    for workspace_id in subscribed_workspaces.read().await.iter() {
+        leave_presence(&state, workspace_id, &connection_id);
+    }
+
+    state.metrics.ws_disconnected();
+    if *state.shutdown.borrow() {
+        // Let the close frame go out
+        let _ = send_task.await;
+    } else {
+        send_task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Router, body::Body, middleware, routing::get};
+    use tokio::sync::{broadcast, watch};
+    use tower::ServiceExt as _;
+
+    use super::*;
+    use crate::auth::Auth;
+    use crate::blobs::BlobStore;
+    use crate::db::Database;
+    use crate::limits::Limits;
+
+    fn state() -> (Arc<AppState>, broadcast::Receiver<String>) {
+        let db = Database::open(":memory:").unwrap();
+        db.init().unwrap();
+        let (tx, rx) = broadcast::channel(16);
+        let (_, shutdown) = watch::channel(false);
+        let state = Arc::new(AppState {
+            db,
+            auth: Auth::parse(""),
+            blobs: BlobStore::new("unused"),
+            tx,
+            presence: Default::default(),
+            metrics: Default::default(),
+            limits: Limits::new(0, 0, 0),
+            shutdown,
+        });
+        (state, rx)
+    }
+
+    fn client(connection_id: &str, name: &str) -> PresenceClient {
+        PresenceClient {
+            connection_id: connection_id.to_string(),
+            client_id: None,
+            name: Some(name.to_string()),
+            since: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    async fn listed(state: &Arc<AppState>, workspace: &str) -> Vec<PresenceClient> {
+        let app = Router::new()
+            .route("/api/presence/{workspace_id}", get(presence))
+            .route_layer(middleware::from_fn_with_state(
+                Arc::clone(state),
+                auth::require_token,
+            ))
+            .with_state(Arc::clone(state));
+        let request = axum::http::Request::builder()
+            .uri(format!("/api/presence/{workspace}"))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn event(rx: &mut broadcast::Receiver<String>) -> WsMessage {
+        serde_json::from_str(&rx.try_recv().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn presence_follows_joins_and_leaves() {
+        let (state, mut rx) = state();
+        join_presence(&state, "ws", client("c1", "laptop"));
+        join_presence(&state, "ws", client("c2", "desktop"));
+        // Subscribing again changes nothing and isn't announced
+        join_presence(&state, "ws", client("c1", "laptop"));
+
+        let joined = event(&mut rx);
+        assert_eq!(joined.msg_type, "join");
+        assert_eq!(joined.workspace_id.as_deref(), Some("ws"));
+        assert_eq!(joined.client.unwrap().connection_id, "c1");
+        assert_eq!(event(&mut rx).client.unwrap().connection_id, "c2");
+        assert!(rx.try_recv().is_err());
+        let names: Vec<_> = listed(&state, "ws")
+            .await
+            .into_iter()
+            .filter_map(|c| c.name)
+            .collect();
+        assert_eq!(names, ["laptop", "desktop"]);
+        assert!(listed(&state, "other").await.is_empty());
+
+        leave_presence(&state, "ws", "c1");
+        leave_presence(&state, "ws", "c1");
+        let left = event(&mut rx);
+        assert_eq!(left.msg_type, "leave");
+        assert_eq!(left.client.unwrap().connection_id, "c1");
+        assert!(rx.try_recv().is_err());
+        leave_presence(&state, "ws", "c2");
+        assert_eq!(event(&mut rx).msg_type, "leave");
+        assert!(listed(&state, "ws").await.is_empty());
+    }
 }
@@ -0,0 +1,262 @@
+//! Request rate limiting and size caps, so one misbehaving client can't flood the server
+//!
+//! Each token gets `RATE_LIMIT` requests a minute (default 600, `0` turns limiting off),
+//! with bursts up to a minute's worth; requests without one, while the server is open
+//! and to `/api/login`, count against the client's IP address instead. Behind a reverse
+//! proxy those all share the proxy's address. Over the limit, requests get 429 with a
+//! `Retry-After`. WebSocket pushes count like HTTP ones.
+//!
+//! Request bodies are capped at `MAX_BODY_BYTES` (default 16 MiB; import and blob
+//! uploads have their own caps) and pushes at `MAX_OPS_PER_PUSH` ops (default 1000),
+//! both answered with 413.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+
+use crate::AppState;
+use crate::auth::{self, Caller};
+
+const DEFAULT_RATE_LIMIT: u32 = 600;
+const DEFAULT_MAX_BODY_BYTES: usize = 16 * 1024 * 1024;
+const DEFAULT_MAX_OPS_PER_PUSH: usize = 1000;
+/// Buckets kept before full ones, which limit nothing, are dropped
+const MAX_TRACKED: usize = 10_000;
+
+pub struct Limits {
+    /// Requests a minute per token or address; 0 for no limit
+    pub rate: u32,
+    pub max_body_bytes: usize,
+    pub max_ops_per_push: usize,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+/// Requests a client may still make, refilled at `rate` a minute up to `rate`
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+fn env<T: std::str::FromStr>(name: &str, default: T) -> T {
+    match std::env::var(name) {
+        Ok(value) => value.trim().parse().unwrap_or_else(|_| {
+            tracing::warn!("Ignoring invalid {name}={value}");
+            default
+        }),
+        Err(_) => default,
+    }
+}
+
+impl Limits {
+    pub fn from_env() -> Self {
+        Self::new(
+            env("RATE_LIMIT", DEFAULT_RATE_LIMIT),
+            env("MAX_BODY_BYTES", DEFAULT_MAX_BODY_BYTES),
+            env("MAX_OPS_PER_PUSH", DEFAULT_MAX_OPS_PER_PUSH),
+        )
+    }
+
+    pub fn new(rate: u32, max_body_bytes: usize, max_ops_per_push: usize) -> Self {
+        Self {
+            rate,
+            max_body_bytes,
+            max_ops_per_push,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a request from `key`'s allowance. Err with how long until the next one is
+    /// allowed when it has none left.
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        self.check_at(key, Instant::now())
+    }
+
+    fn check_at(&self, key: &str, now: Instant) -> Result<(), Duration> {
+        if self.rate == 0 {
+            return Ok(());
+        }
+        let capacity = f64::from(self.rate);
+        let per_second = capacity / 60.0;
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED && !buckets.contains_key(key) {
+            buckets.retain(|_, bucket| {
+                bucket.tokens
+                    + now.saturating_duration_since(bucket.updated).as_secs_f64() * per_second
+                    < capacity
+            });
+        }
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        let refill = now.saturating_duration_since(bucket.updated).as_secs_f64() * per_second;
+        bucket.tokens = (bucket.tokens + refill).min(capacity);
+        bucket.updated = bucket.updated.max(now);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / per_second))
+        }
+    }
+
+    /// Refuse a push of more ops than allowed
+    pub fn check_push(&self, ops: usize) -> Result<(), (StatusCode, String)> {
+        if ops > self.max_ops_per_push {
+            return Err((
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!(
+                    "Too many ops in one push: {ops} (at most {})",
+                    self.max_ops_per_push
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Who a request counts against: the token it was let in with, else its address.
+/// Tokens are told apart by hash rather than name, since static tokens given without
+/// one all share the name `env`.
+pub fn client_key(
+    caller: Option<&Caller>,
+    headers: &HeaderMap,
+    addr: Option<SocketAddr>,
+) -> String {
+    let token = caller
+        .filter(|caller| !caller.name.is_empty())
+        .and_then(|_| auth::bearer_token(headers));
+    match (token, addr) {
+        (Some(token), _) => {
+            let hash: String = Sha256::digest(token)[..8]
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect();
+            format!("token:{hash}")
+        }
+        (None, Some(addr)) => format!("ip:{}", addr.ip()),
+        (None, None) => "unknown".to_string(),
+    }
+}
+
+/// Answer 429 to clients over their rate limit
+pub async fn rate_limit(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let key = client_key(
+        request.extensions().get::<Caller>(),
+        request.headers(),
+        request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|info| info.0),
+    );
+    match state.limits.check(&key) {
+        Ok(()) => next.run(request).await,
+        Err(wait) => {
+            state.metrics.record_rate_limited();
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, wait.as_secs().max(1).to_string())],
+                "Too many requests, slow down",
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bursts_up_to_the_rate_then_refills_over_the_minute() {
+        let limits = Limits::new(60, 0, 0);
+        let start = Instant::now();
+        for _ in 0..60 {
+            assert!(limits.check_at("token:a", start).is_ok());
+        }
+        let wait = limits.check_at("token:a", start).unwrap_err();
+        assert!((wait.as_secs_f64() - 1.0).abs() < 0.01, "{wait:?}");
+        // Other clients have their own allowance
+        assert!(limits.check_at("token:b", start).is_ok());
+
+        // One request a second comes back
+        let later = start + Duration::from_millis(1500);
+        assert!(limits.check_at("token:a", later).is_ok());
+        let wait = limits.check_at("token:a", later).unwrap_err();
+        assert!((wait.as_secs_f64() - 0.5).abs() < 0.01, "{wait:?}");
+
+        // An idle client refills to the burst, no further
+        let idle = later + Duration::from_secs(600);
+        for _ in 0..60 {
+            assert!(limits.check_at("token:a", idle).is_ok());
+        }
+        assert!(limits.check_at("token:a", idle).is_err());
+    }
+
+    #[test]
+    fn zero_rate_never_limits() {
+        let limits = Limits::new(0, 0, 0);
+        let now = Instant::now();
+        assert!((0..1000).all(|_| limits.check_at("ip:1.2.3.4", now).is_ok()));
+    }
+
+    #[test]
+    fn pushes_over_the_op_cap_are_too_large() {
+        let limits = Limits::new(0, 0, 1000);
+        assert!(limits.check_push(0).is_ok());
+        assert!(limits.check_push(1000).is_ok());
+        let (status, message) = limits.check_push(1001).unwrap_err();
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(message.contains("1001"));
+    }
+
+    #[test]
+    fn tokens_are_keyed_apart_even_when_unnamed() {
+        let unnamed = Caller {
+            name: "env".to_string(),
+            user: None,
+        };
+        let addr: SocketAddr = "10.0.0.1:5000".parse().unwrap();
+        let with_token = |token: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                header::AUTHORIZATION,
+                format!("Bearer {token}").parse().unwrap(),
+            );
+            headers
+        };
+        let a = client_key(Some(&unnamed), &with_token("first"), Some(addr));
+        let b = client_key(Some(&unnamed), &with_token("second"), Some(addr));
+        assert!(a.starts_with("token:"));
+        assert_ne!(a, b);
+        assert!(!a.contains("first"));
+
+        // Without a token that got the request in (an open server), the address counts
+        let open = Caller {
+            name: String::new(),
+            user: None,
+        };
+        assert_eq!(
+            client_key(Some(&open), &with_token("made-up"), Some(addr)),
+            "ip:10.0.0.1"
+        );
+        assert_eq!(
+            client_key(None, &HeaderMap::new(), Some(addr)),
+            "ip:10.0.0.1"
+        );
+        assert_eq!(client_key(None, &HeaderMap::new(), None), "unknown");
+    }
+}
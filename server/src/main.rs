@@ -1,26 +1,55 @@
+mod admin;
+mod archive;
+mod auth;
+mod blobs;
+mod cli;
 mod db;
 mod handlers;
+mod limits;
+mod metrics;
 mod models;
+mod presence;
+mod tls;
 
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
 use axum::{
     Router,
+    extract::DefaultBodyLimit,
+    middleware,
     routing::{get, post},
 };
-use tokio::sync::broadcast;
+use clap::Parser;
+use tokio::sync::{broadcast, watch};
 use tower_http::cors::{Any, CorsLayer};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+use auth::Auth;
+use blobs::BlobStore;
+use cli::{Cli, Command};
 use db::Database;
+use limits::Limits;
+use metrics::Metrics;
+use presence::Presence;
 
 pub struct AppState {
     pub db: Database,
+    pub auth: Auth,
+    pub blobs: BlobStore,
     pub tx: broadcast::Sender<String>,
+    pub presence: Presence,
+    pub metrics: Metrics,
+    pub limits: Limits,
+    /// Flips to true when the server starts shutting down
+    pub shutdown: watch::Receiver<bool>,
 }
 
+/// How long open connections get to finish after a shutdown signal
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::registry()
@@ -31,14 +60,38 @@ async fn main() -> Result<()> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
+    let cli = Cli::parse();
+
     let db_path =
         std::env::var("DATABASE_PATH").unwrap_or_else(|_| "scratchpad-server.db".to_string());
     let db = Database::open(&db_path)?;
     db.init()?;
 
+    match cli.command {
+        None | Some(Command::Serve) => {}
+        Some(command) => return admin::run(&db, command),
+    }
+
     let (tx, _rx) = broadcast::channel::<String>(100);
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
 
-    let state = Arc::new(AppState { db, tx });
+    let auth = Auth::from_env();
+    if !auth.required(&db)? {
+        tracing::warn!(
+            "No API tokens yet: anyone can read and push. Create one with `sp-server tokens \
+             create` or set API_TOKENS"
+        );
+    }
+    let state = Arc::new(AppState {
+        db,
+        auth,
+        blobs: BlobStore::from_env(),
+        tx,
+        presence: Presence::default(),
+        metrics: Metrics::default(),
+        limits: Limits::from_env(),
+        shutdown: shutdown_rx.clone(),
+    });
 
     let cors = CorsLayer::new()
         .allow_origin(Any)
@@ -46,7 +99,6 @@ async fn main() -> Result<()> {
         .allow_headers(Any);
 
     let app = Router::new()
-        .route("/health", get(handlers::health))
         .route("/api/ops", post(handlers::push_ops))
         .route("/api/ops/{workspace_id}", get(handlers::get_ops))
         .route("/api/snapshot/{workspace_id}", get(handlers::get_snapshot))
@@ -54,9 +106,42 @@ async fn main() -> Result<()> {
             "/api/snapshot/{workspace_id}",
             post(handlers::save_snapshot),
         )
+        .route("/api/search/{workspace_id}", get(handlers::search))
+        .route("/api/export/{workspace_id}", get(handlers::export))
+        .route(
+            "/api/import/{workspace_id}",
+            post(handlers::import).layer(DefaultBodyLimit::max(handlers::MAX_IMPORT_BYTES)),
+        )
+        .route(
+            "/api/blobs/{workspace_id}/{hash}",
+            get(handlers::get_blob)
+                .put(handlers::put_blob)
+                .layer(DefaultBodyLimit::max(handlers::MAX_BLOB_BYTES)),
+        )
+        .route("/api/presence/{workspace_id}", get(handlers::presence))
+        .route("/api/whoami", get(handlers::whoami))
         .route("/ws", get(handlers::websocket_handler))
+        .route("/metrics", get(handlers::metrics))
+        // Layers run last-added first, so the rate limit sees who auth let in
+        .route_layer(middleware::from_fn_with_state(
+            Arc::clone(&state),
+            limits::rate_limit,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            Arc::clone(&state),
+            auth::require_token,
+        ))
+        .route("/health", get(handlers::health))
+        .route(
+            "/api/login",
+            post(handlers::login).layer(middleware::from_fn_with_state(
+                Arc::clone(&state),
+                limits::rate_limit,
+            )),
+        )
+        .layer(DefaultBodyLimit::max(state.limits.max_body_bytes))
         .layer(cors)
-        .with_state(state);
+        .with_state(Arc::clone(&state));
 
     let port: u16 = std::env::var("PORT")
         .unwrap_or_else(|_| "3000".to_string())
@@ -64,10 +149,88 @@ async fn main() -> Result<()> {
         .unwrap_or(3000);
 
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    tracing::info!("Listening on {}", addr);
-
+    let tls = tls::from_env().await?;
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    match tls {
+        Some(_) => tracing::info!("Listening on {} (TLS)", addr),
+        None => tracing::info!("Listening on {}", addr),
+    }
+    tokio::spawn(shutdown_signal(shutdown_tx));
 
+    // Stop accepting connections on shutdown, then wait for open ones (WebSocket
+    // handlers close themselves) up to the drain timeout
+    let mut stopping = shutdown_rx.clone();
+    let server = async move {
+        match tls {
+            Some(config) => {
+                let handle = axum_server::Handle::new();
+                let stop = handle.clone();
+                tokio::spawn(async move {
+                    let _ = stopping.changed().await;
+                    stop.graceful_shutdown(None);
+                });
+                axum_server::from_tcp_rustls(listener.into_std()?, config)
+                    .handle(handle)
+                    .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                    .await
+            }
+            None => {
+                axum::serve(
+                    listener,
+                    app.into_make_service_with_connect_info::<SocketAddr>(),
+                )
+                .with_graceful_shutdown(async move {
+                    let _ = stopping.changed().await;
+                })
+                .await
+            }
+        }
+    };
+    let mut draining = shutdown_rx;
+    tokio::select! {
+        result = server => result?,
+        _ = async {
+            let _ = draining.changed().await;
+            tokio::time::sleep(DRAIN_TIMEOUT).await;
+        } => tracing::warn!("Connections still open after {DRAIN_TIMEOUT:?}, closing anyway"),
+    }
+
+    match Arc::try_unwrap(state) {
+        Ok(state) => state.db.close()?,
+        Err(_) => tracing::warn!("Database still in use at exit"),
+    }
+    tracing::info!("Shut down");
     Ok(())
 }
+
+/// Wait for SIGINT or SIGTERM, then tell the server and open connections to stop
+async fn shutdown_signal(shutdown: watch::Sender<bool>) {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl-C: {e}");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+    tracing::info!("Shutting down, draining connections");
+    let _ = shutdown.send(true);
+}
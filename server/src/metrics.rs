@@ -0,0 +1,221 @@
+//! Counters and database latency histograms, served in the Prometheus text format
+//! at `/metrics`
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use scratchpad_protocol::{OpResult, OpStatus};
+
+/// Upper bounds of the latency buckets, in seconds
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+];
+
+#[derive(Default)]
+pub struct Metrics {
+    ops_accepted: AtomicU64,
+    ops_duplicate: AtomicU64,
+    ops_rejected: AtomicU64,
+    ops_fetched: AtomicU64,
+    ws_connections: AtomicU64,
+    ws_open: AtomicU64,
+    /// Broadcast messages WebSocket clients missed by falling behind
+    broadcast_lagged: AtomicU64,
+    /// Requests and WebSocket pushes refused for going over the rate limit
+    rate_limited: AtomicU64,
+}
+
+impl Metrics {
+    /// Count a push's ops by how they were stored
+    pub fn record_push(&self, results: &[OpResult]) {
+        for result in results {
+            let counter = match result.status {
+                OpStatus::Accepted => &self.ops_accepted,
+                OpStatus::Duplicate => &self.ops_duplicate,
+                OpStatus::Rejected => &self.ops_rejected,
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_fetch(&self, ops: usize) {
+        self.ops_fetched.fetch_add(ops as u64, Ordering::Relaxed);
+    }
+
+    pub fn ws_connected(&self) {
+        self.ws_connections.fetch_add(1, Ordering::Relaxed);
+        self.ws_open.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn ws_disconnected(&self) {
+        self.ws_open.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn record_lag(&self, missed: u64) {
+        self.broadcast_lagged.fetch_add(missed, Ordering::Relaxed);
+    }
+
+    pub fn record_rate_limited(&self) {
+        self.rate_limited.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Everything in the Prometheus text exposition format, with the database's
+    /// query latencies
+    pub fn render(&self, latency: &Latency) -> String {
+        let mut out = String::new();
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+
+        out.push_str("# HELP scratchpad_ops_pushed_total Ops pushed, by how they were stored\n");
+        out.push_str("# TYPE scratchpad_ops_pushed_total counter\n");
+        for (status, counter) in [
+            ("accepted", &self.ops_accepted),
+            ("duplicate", &self.ops_duplicate),
+            ("rejected", &self.ops_rejected),
+        ] {
+            let _ = writeln!(
+                out,
+                "scratchpad_ops_pushed_total{{status=\"{status}\"}} {}",
+                load(counter)
+            );
+        }
+        counter(
+            &mut out,
+            "scratchpad_ops_fetched_total",
+            "Ops returned by /api/ops",
+            load(&self.ops_fetched),
+        );
+        counter(
+            &mut out,
+            "scratchpad_ws_connections_total",
+            "WebSocket connections accepted",
+            load(&self.ws_connections),
+        );
+        let _ = writeln!(
+            out,
+            "# HELP scratchpad_ws_connections Open WebSocket connections\n\
+             # TYPE scratchpad_ws_connections gauge\n\
+             scratchpad_ws_connections {}",
+            load(&self.ws_open)
+        );
+        counter(
+            &mut out,
+            "scratchpad_broadcast_lagged_total",
+            "Broadcast messages WebSocket clients missed by falling behind",
+            load(&self.broadcast_lagged),
+        );
+        counter(
+            &mut out,
+            "scratchpad_rate_limited_total",
+            "Requests and WebSocket pushes refused for going over the rate limit",
+            load(&self.rate_limited),
+        );
+        latency.render(&mut out);
+        out
+    }
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+    let _ = writeln!(
+        out,
+        "# HELP {name} {help}\n# TYPE {name} counter\n{name} {value}"
+    );
+}
+
+/// How long database queries take, lock wait included, by query
+#[derive(Default)]
+pub struct Latency {
+    queries: Mutex<BTreeMap<&'static str, Histogram>>,
+}
+
+#[derive(Default)]
+struct Histogram {
+    /// Observations at or under each of `LATENCY_BUCKETS`, not cumulative
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Latency {
+    pub fn observe(&self, query: &'static str, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        let mut queries = self.queries.lock().unwrap();
+        let histogram = queries.entry(query).or_default();
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|&le| seconds <= le) {
+            histogram.buckets[i] += 1;
+        }
+        histogram.count += 1;
+        histogram.sum += seconds;
+    }
+
+    fn render(&self, out: &mut String) {
+        const NAME: &str = "scratchpad_db_query_duration_seconds";
+        let _ = writeln!(
+            out,
+            "# HELP {NAME} Database query latency, lock wait included\n# TYPE {NAME} histogram"
+        );
+        let queries = self.queries.lock().unwrap();
+        for (query, histogram) in queries.iter() {
+            let mut cumulative = 0;
+            for (le, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "{NAME}_bucket{{query=\"{query}\",le=\"{le}\"}} {cumulative}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "{NAME}_bucket{{query=\"{query}\",le=\"+Inf\"}} {}\n\
+                 {NAME}_sum{{query=\"{query}\"}} {}\n\
+                 {NAME}_count{{query=\"{query}\"}} {}",
+                histogram.count, histogram.sum, histogram.count
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_counters_and_cumulative_latency_buckets() {
+        let metrics = Metrics::default();
+        let result = |status| OpResult {
+            id: "op".to_string(),
+            status,
+            reason: None,
+        };
+        metrics.record_push(&[
+            result(OpStatus::Accepted),
+            result(OpStatus::Accepted),
+            result(OpStatus::Duplicate),
+        ]);
+        metrics.ws_connected();
+        metrics.ws_connected();
+        metrics.ws_disconnected();
+        let latency = Latency::default();
+        latency.observe("get_ops", Duration::from_micros(300));
+        latency.observe("get_ops", Duration::from_millis(20));
+        latency.observe("get_ops", Duration::from_secs(5));
+
+        let out = metrics.render(&latency);
+        for line in [
+            "scratchpad_ops_pushed_total{status=\"accepted\"} 2",
+            "scratchpad_ops_pushed_total{status=\"duplicate\"} 1",
+            "scratchpad_ops_pushed_total{status=\"rejected\"} 0",
+            "scratchpad_ws_connections_total 2",
+            "scratchpad_ws_connections 1",
+            "scratchpad_db_query_duration_seconds_bucket{query=\"get_ops\",le=\"0.0005\"} 1",
+            "scratchpad_db_query_duration_seconds_bucket{query=\"get_ops\",le=\"0.025\"} 2",
+            "scratchpad_db_query_duration_seconds_bucket{query=\"get_ops\",le=\"2.5\"} 2",
+            "scratchpad_db_query_duration_seconds_bucket{query=\"get_ops\",le=\"+Inf\"} 3",
+            "scratchpad_db_query_duration_seconds_count{query=\"get_ops\"} 3",
+        ] {
+            assert!(out.lines().any(|l| l == line), "missing {line}:\n{out}");
+        }
+    }
+}
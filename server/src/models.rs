@@ -1,49 +1,59 @@
+//! Server-only types: operator commands and the export archive
+
+use scratchpad_protocol::{Op, Snapshot};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Op {
+pub struct TokenInfo {
+    pub name: String,
+    pub created_at: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub db_id: Option<i64>,
-    pub id: String,
-    pub op_type: String,
-    pub payload: String,
-    pub timestamp: String,
+    pub revoked_at: Option<String>,
+    /// None for tokens that reach every workspace
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub client_id: Option<String>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PushOpsRequest {
-    pub workspace_id: String,
-    pub ops: Vec<Op>,
+    pub user: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PushOpsResponse {
-    pub accepted: usize,
+pub struct UserInfo {
+    pub name: String,
+    pub created_at: String,
+    /// Active tokens
+    pub tokens: i64,
+    /// Workspaces the user owns or was shared
+    pub workspaces: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct GetOpsQuery {
-    pub after: Option<i64>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Snapshot {
+pub struct WorkspaceInfo {
     pub workspace_id: String,
-    pub data: String,
+    pub ops: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_op_at: Option<String>,
+    pub has_snapshot: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub last_op_id: Option<String>,
-    pub updated_at: String,
+    pub owner: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct WsMessage {
-    pub msg_type: String,
+pub struct DeviceInfo {
+    pub client_id: String,
+    /// As the device last named itself; None if it never did
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub workspace_id: Option<String>,
+    pub name: Option<String>,
+    pub ops: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub ops: Option<Vec<Op>>,
+    pub last_op_at: Option<String>,
+}
+
+/// Everything stored for a workspace, as written by `sp-server export` and `/api/export`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceExport {
+    pub workspace_id: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
+    pub snapshot: Option<Snapshot>,
+    pub ops: Vec<Op>,
+    /// Hashes and bytes of the workspace's blobs; only tar archives carry them
+    #[serde(skip)]
+    pub blobs: Vec<(String, Vec<u8>)>,
 }
@@ -0,0 +1,47 @@
+//! Which WebSocket clients are subscribed to each workspace (`/api/presence`)
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use scratchpad_protocol::PresenceClient;
+
+#[derive(Default)]
+pub struct Presence {
+    /// Workspace id to the clients subscribed to it, keyed by connection id
+    workspaces: Mutex<HashMap<String, HashMap<String, PresenceClient>>>,
+}
+
+impl Presence {
+    /// Record `client` as subscribed to `workspace_id`. Returns false if it already was.
+    pub fn join(&self, workspace_id: &str, client: PresenceClient) -> bool {
+        let mut workspaces = self.workspaces.lock().unwrap();
+        let clients = workspaces.entry(workspace_id.to_string()).or_default();
+        if clients.contains_key(&client.connection_id) {
+            return false;
+        }
+        clients.insert(client.connection_id.clone(), client);
+        true
+    }
+
+    /// Remove a connection from `workspace_id`, returning it if it was subscribed
+    pub fn leave(&self, workspace_id: &str, connection_id: &str) -> Option<PresenceClient> {
+        let mut workspaces = self.workspaces.lock().unwrap();
+        let clients = workspaces.get_mut(workspace_id)?;
+        let client = clients.remove(connection_id);
+        if clients.is_empty() {
+            workspaces.remove(workspace_id);
+        }
+        client
+    }
+
+    /// Clients subscribed to `workspace_id`, longest-connected first
+    pub fn list(&self, workspace_id: &str) -> Vec<PresenceClient> {
+        let workspaces = self.workspaces.lock().unwrap();
+        let mut clients: Vec<PresenceClient> = workspaces
+            .get(workspace_id)
+            .map(|clients| clients.values().cloned().collect())
+            .unwrap_or_default();
+        clients.sort_by(|a, b| a.since.cmp(&b.since));
+        clients
+    }
+}
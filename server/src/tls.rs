@@ -0,0 +1,30 @@
+//! Native TLS termination, for deployments without a reverse proxy in front
+//!
+//! Set `TLS_CERT_PATH` and `TLS_KEY_PATH` to PEM files (a certificate chain and its
+//! private key) and the server speaks HTTPS and `wss://` on `PORT` instead of plain
+//! HTTP. Setting only one of them is an error rather than a silent fallback to
+//! cleartext.
+
+use anyhow::{Context, Result, bail};
+use axum_server::tls_rustls::RustlsConfig;
+
+/// Load the TLS config named by the environment; None to serve plain HTTP
+pub async fn from_env() -> Result<Option<RustlsConfig>> {
+    let cert = std::env::var("TLS_CERT_PATH")
+        .ok()
+        .filter(|v| !v.is_empty());
+    let key = std::env::var("TLS_KEY_PATH").ok().filter(|v| !v.is_empty());
+    let (cert, key) = match (cert, key) {
+        (Some(cert), Some(key)) => (cert, key),
+        (None, None) => return Ok(None),
+        (Some(_), None) => bail!("TLS_CERT_PATH is set but TLS_KEY_PATH isn't"),
+        (None, Some(_)) => bail!("TLS_KEY_PATH is set but TLS_CERT_PATH isn't"),
+    };
+    // Several rustls providers can end up compiled in; pick ring explicitly. Fails only
+    // when one is already installed, which is fine
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let config = RustlsConfig::from_pem_file(&cert, &key)
+        .await
+        .with_context(|| format!("Failed to load TLS certificate {cert} and key {key}"))?;
+    Ok(Some(config))
+}